@@ -0,0 +1,175 @@
+//! Compares deserializing internally and adjacently tagged enums, which serde
+//! buffers before it knows the variant, with a struct of the same fields,
+//! which is read straight from the document, and with a `Value`.
+//!
+//! Run with `cargo bench --bench tagged`.
+
+use serde_derive::Deserialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ITERATIONS: u32 = 20;
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum Internal {
+    Service(Spec),
+}
+
+#[derive(Deserialize)]
+struct Item {
+    #[serde(rename = "type")]
+    kind: String,
+    spec: Spec,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "spec")]
+enum Adjacent {
+    Service(Spec),
+}
+
+#[derive(Deserialize)]
+struct Spec {
+    name: String,
+    replicas: u8,
+    ports: Vec<u16>,
+}
+
+impl Spec {
+    fn size(&self) -> usize {
+        self.name.len() + self.replicas as usize + self.ports.len()
+    }
+}
+
+fn document(spec: bool) -> String {
+    let mut yaml = String::new();
+    for i in 0..20_000 {
+        let indent = if spec {
+            yaml += "- type: Service\n  spec:\n";
+            "    "
+        } else {
+            yaml += "- type: Service\n";
+            "  "
+        };
+        yaml += &format!(
+            "{indent}name: service-{i}\n{indent}replicas: {}\n{indent}ports: [80, 443, {}]\n",
+            i % 5,
+            8000 + i % 1000,
+        );
+    }
+    yaml
+}
+
+fn measure(name: &str, f: impl Fn() -> usize) -> Duration {
+    let mut items = f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        items += f();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS as usize;
+    let bytes = (BYTES.load(Ordering::Relaxed) - bytes) / ITERATIONS as usize;
+    assert_ne!(items, 0);
+    println!(
+        "{:<24} {:>10.2?} {:>10} allocations {:>12} bytes allocated",
+        name, elapsed, allocations, bytes,
+    );
+    elapsed
+}
+
+fn main() {
+    let yaml = document(false);
+    println!("document of {} bytes", yaml.len());
+    // A flattened struct is buffered the same way, so the fields are read
+    // straight into the struct here for the status quo.
+    let plain = measure("struct", || {
+        #[derive(Deserialize)]
+        struct Plain {
+            #[serde(rename = "type")]
+            kind: String,
+            name: String,
+            replicas: u8,
+            ports: Vec<u16>,
+        }
+        let items: Vec<Plain> = serde_yaml::from_str(&yaml).unwrap();
+        items
+            .iter()
+            .map(|item| {
+                item.kind.len() + item.name.len() + item.replicas as usize + item.ports.len()
+            })
+            .sum()
+    });
+    let internal = measure("internally tagged", || {
+        let items: Vec<Internal> = serde_yaml::from_str(&yaml).unwrap();
+        items
+            .iter()
+            .map(|Internal::Service(spec)| spec.size())
+            .sum()
+    });
+    println!(
+        "internally tagged is {:.1}x slower",
+        internal.as_secs_f64() / plain.as_secs_f64(),
+    );
+    measure("from_str::<Value>", || {
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        value.as_sequence().unwrap().len()
+    });
+
+    // The error is in the last item, which is searched for the node it is
+    // about after the item has been buffered.
+    let invalid = format!(
+        "{}- type: Service\n  name: x\n  replicas: 300\n  ports: []\n",
+        yaml
+    );
+    measure("internally tagged error", || {
+        let err = serde_yaml::from_str::<Vec<Internal>>(&invalid)
+            .err()
+            .unwrap();
+        err.location().unwrap().line()
+    });
+
+    let yaml = document(true);
+    println!("document of {} bytes", yaml.len());
+    let plain = measure("struct", || {
+        let items: Vec<Item> = serde_yaml::from_str(&yaml).unwrap();
+        items
+            .iter()
+            .map(|item| item.kind.len() + item.spec.size())
+            .sum()
+    });
+    let adjacent = measure("adjacently tagged", || {
+        let items: Vec<Adjacent> = serde_yaml::from_str(&yaml).unwrap();
+        items
+            .iter()
+            .map(|Adjacent::Service(spec)| spec.size())
+            .sum()
+    });
+    println!(
+        "adjacently tagged is {:.1}x slower",
+        adjacent.as_secs_f64() / plain.as_secs_f64(),
+    );
+}
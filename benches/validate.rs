@@ -0,0 +1,104 @@
+//! Compares `validate` and `peek_root` with deserializing a `Value` on a large
+//! document.
+//!
+//! Run with `cargo bench --bench validate`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ITERATIONS: u32 = 20;
+
+fn document() -> String {
+    let mut yaml = String::new();
+    for i in 0..20_000 {
+        yaml += &format!(
+            "- name: service-{i}\n  image: \"registry.example.com/service:{i}\"\n  replicas: {}\n  ports: [80, 443, {}]\n  labels: {{tier: backend, team: 'team {}'}}\n",
+            i % 5,
+            8000 + i,
+            i % 17,
+        );
+    }
+    yaml
+}
+
+fn measure(name: &str, f: impl Fn() -> usize) -> Duration {
+    let mut nodes = f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        nodes += f();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS as usize;
+    let bytes = (BYTES.load(Ordering::Relaxed) - bytes) / ITERATIONS as usize;
+    assert_ne!(nodes, 0);
+    println!(
+        "{:<18} {:>10.2?} {:>10} allocations {:>12} bytes allocated",
+        name, elapsed, allocations, bytes,
+    );
+    elapsed
+}
+
+fn main() {
+    let yaml = document();
+    println!("document of {} bytes", yaml.len());
+    let validate = measure("validate", || serde_yaml::validate(&yaml).unwrap().nodes());
+    let value = measure("from_str::<Value>", || {
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        value.as_sequence().unwrap().len()
+    });
+    println!(
+        "validate is {:.1}x faster",
+        value.as_secs_f64() / validate.as_secs_f64(),
+    );
+
+    // The root is a sequence, so only its start is read.
+    let peek = measure("peek_root", || {
+        let root = serde_yaml::peek_root(&yaml).unwrap();
+        assert_eq!(root.kind(), serde_yaml::NodeKind::Sequence);
+        1
+    });
+    println!(
+        "peek_root is {:.1}x faster",
+        value.as_secs_f64() / peek.as_secs_f64(),
+    );
+
+    // A mapping with its `kind` first, where the values are read up to the
+    // last of the first 8 keys, which here is all of the input.
+    let list = format!("kind: List\nitems:\n{}", yaml);
+    let peek = measure("peek_root mapping", || {
+        let root = serde_yaml::peek_root(&list).unwrap();
+        assert_eq!(root.keys()[0], "kind");
+        root.keys().len()
+    });
+    let value = measure("from_str::<Value>", || {
+        let value: serde_yaml::Value = serde_yaml::from_str(&list).unwrap();
+        value.as_mapping().unwrap().len()
+    });
+    println!(
+        "peek_root is {:.1}x faster",
+        value.as_secs_f64() / peek.as_secs_f64(),
+    );
+}
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_yaml::{SerializerBuilder, Value};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() <= 10240 {
+        if let Ok(value) = serde_yaml::from_slice::<Value>(data) {
+            let builder = SerializerBuilder::new().verify_roundtrip(true);
+            if let Err(err) = builder.to_string(&value) {
+                panic!("{:?}: {}", value, err);
+            }
+        }
+    }
+});
@@ -0,0 +1,57 @@
+// The base64 alphabet of RFC 4648, which is what `!!binary` scalars are
+// written in.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[cfg(feature = "ser")]
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |group, (i, &b)| group | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let sextet = group >> (18 - 6 * i) & 0x3F;
+                encoded.push(char::from(ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Whitespace is skipped, as a `!!binary` block scalar has a line break after
+// every line of characters. None if the rest is not base64 with its padding.
+pub(crate) fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut digits = Vec::with_capacity(encoded.len());
+    let mut padding = 0;
+    for b in encoded.bytes() {
+        if matches!(b, b' ' | b'\t' | b'\r' | b'\n') {
+            continue;
+        }
+        if b == b'=' {
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return None;
+        }
+        let sextet = ALPHABET.iter().position(|&digit| digit == b)?;
+        digits.push(sextet as u8);
+    }
+    if (digits.len() + padding) % 4 != 0 || padding > 2 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(digits.len() / 4 * 3 + 2);
+    for chunk in digits.chunks(4) {
+        let group = chunk.iter().enumerate().fold(0, |group, (i, &sextet)| {
+            group | u32::from(sextet) << (18 - 6 * i)
+        });
+        for i in 0..chunk.len() - 1 {
+            bytes.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,433 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::rc::Rc;
+
+// Newtype struct name by which with::default_as_comment asks the serializer
+// to write the entry whose value is being serialized as a comment.
+pub(crate) const COMMENT: &str = "$serde_yaml::comment";
+
+// Newtype struct name by which Commented asks the serializer to write a
+// comment above the entry or item whose value is being serialized.
+pub(crate) const COMMENTED: &str = "$serde_yaml::commented";
+
+// Written by the serializer as the value of an entry that is a comment, with
+// the nonce of the serializer and a number after it. The writer replaces the
+// line that ends in it.
+const PLACEHOLDER: &str = "__serde_yaml_comment_";
+
+// Written by the serializer as the key and value of an extra entry, or as an
+// extra item, before the one that has a comment above it, with the nonce and
+// a number after it. The writer replaces the line of it with the comment.
+const ABOVE: &str = "__serde_yaml_comment_above_";
+
+thread_local! {
+    // The comment of the Commented being serialized, for a YAML serializer
+    // to write. Other serializers never look at it.
+    static COMMENT_ABOVE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn take_comment_above() -> Option<String> {
+    COMMENT_ABOVE.with(|comment| comment.borrow_mut().take())
+}
+
+/// A value that the serializer writes with a comment above it, for files
+/// that people edit and that explain their own settings.
+///
+/// The comment is written above the key of a mapping entry whose value is
+/// `Commented`, or above an item of a sequence, at the indentation of the
+/// key or item, with `# ` before each line of it. Nothing is written for an
+/// empty comment. A comment can only be written in a block collection, so
+/// at the root of a document, in flow style and in [`canonical`] output the
+/// value is written without it, as it is with other serializers including
+/// [`to_value`][crate::to_value].
+///
+/// Deserializing a `Commented` gives the value with an empty comment, as
+/// comments are not part of the data that is read.
+///
+/// [`canonical`]: crate::SerializerBuilder::canonical
+///
+/// ```
+/// # use serde_derive::Serialize;
+/// use serde::Serialize;
+/// use serde_yaml::Commented;
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     name: String,
+///     port: Commented<u16>,
+///     hosts: Vec<Commented<&'static str>>,
+/// }
+///
+/// let config = Config {
+///     name: "web".to_owned(),
+///     port: Commented::new("The port to listen on.\nBelow 1024 needs root.", 8080),
+///     hosts: vec![
+///         Commented::new("", "a.example.com"),
+///         Commented::new("Only for testing", "b.example.com"),
+///     ],
+/// };
+///
+/// let yaml = serde_yaml::to_string(&config).unwrap();
+/// let expected = concat!(
+///     "name: web\n",
+///     "# The port to listen on.\n",
+///     "# Below 1024 needs root.\n",
+///     "port: 8080\n",
+///     "hosts:\n",
+///     "- a.example.com\n",
+///     "# Only for testing\n",
+///     "- b.example.com\n",
+/// );
+/// assert_eq!(yaml, expected);
+/// ```
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Commented<T> {
+    /// The text of the comment, whose line breaks start a new comment line.
+    pub comment: String,
+    /// The value written below the comment.
+    pub value: T,
+}
+
+impl<T> Commented<T> {
+    /// Wraps a value to be written below a comment.
+    pub fn new(comment: impl Into<String>, value: T) -> Self {
+        Commented {
+            comment: comment.into(),
+            value,
+        }
+    }
+}
+
+impl<T> Serialize for Commented<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.comment.is_empty() {
+            return self.value.serialize(serializer);
+        }
+        COMMENT_ABOVE.with(|comment| *comment.borrow_mut() = Some(self.comment.clone()));
+        let result = serializer.serialize_newtype_struct(COMMENTED, &self.value);
+        COMMENT_ABOVE.with(|comment| *comment.borrow_mut() = None);
+        result
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Commented<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(|value| Commented {
+            comment: String::new(),
+            value,
+        })
+    }
+}
+
+// The text of the entries to write as comments, for the serializer to share
+// with the writer between the emitter and the output.
+#[derive(Clone, Default)]
+pub(crate) struct Comments(Rc<RefCell<Entries>>);
+
+struct Entries {
+    // Random for each serializer, so that a string in the data that starts
+    // like a placeholder is never taken for one.
+    nonce: u64,
+    next: usize,
+    // What follows the key: the rest of its line and the lines after it.
+    text: BTreeMap<usize, String>,
+}
+
+impl Default for Entries {
+    fn default() -> Self {
+        Entries {
+            nonce: RandomState::new().build_hasher().finish(),
+            next: 0,
+            text: BTreeMap::new(),
+        }
+    }
+}
+
+impl Comments {
+    // Keeps the text of an entry, which is everything after the key of its
+    // first line as it is written at the root of a document, and returns the
+    // placeholder to write as its value.
+    pub fn add(&self, text: String) -> String {
+        self.insert(PLACEHOLDER, text)
+    }
+
+    // Keeps the lines of a comment, which are separated by `\n`, and returns
+    // the placeholder to write before the entry or item that it is above.
+    pub fn add_above(&self, text: String) -> String {
+        self.insert(ABOVE, text)
+    }
+
+    fn insert(&self, prefix: &str, text: String) -> String {
+        let mut entries = self.0.borrow_mut();
+        let id = entries.next;
+        entries.next += 1;
+        entries.text.insert(id, text);
+        format!("{}{:016x}_{}", prefix, entries.nonce, id)
+    }
+
+    pub fn is_placeholder(&self, value: &str) -> bool {
+        self.id(PLACEHOLDER, value.as_bytes()).is_some()
+    }
+
+    pub fn is_above(&self, value: &str) -> bool {
+        self.id(ABOVE, value.as_bytes()).is_some()
+    }
+
+    fn id(&self, prefix: &str, value: &[u8]) -> Option<usize> {
+        let entries = self.0.borrow();
+        let rest = value.strip_prefix(prefix.as_bytes())?;
+        let nonce = format!("{:016x}_", entries.nonce);
+        let digits = rest.strip_prefix(nonce.as_bytes())?;
+        let id = std::str::from_utf8(digits).ok()?.parse().ok()?;
+        if entries.text.contains_key(&id) {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.borrow().text.is_empty()
+    }
+
+    fn take(&self, id: usize) -> String {
+        self.0.borrow_mut().text.remove(&id).unwrap()
+    }
+}
+
+// The length from which the start of a line is written through while no
+// comment is waiting for its placeholder. A line with a placeholder has at
+// most a simple key of 128 characters in front of it, besides indentation,
+// while JSON and other flow style output can be one line of any length.
+const LONG_LINE: usize = 8192;
+
+// Writes to the underlying writer one line at a time, with each line that
+// ends in a placeholder replaced by the comment for its entry.
+pub(crate) struct CommentWriter<W> {
+    pub writer: W,
+    comments: Comments,
+    // The start of a line whose end has not been written yet.
+    line: Vec<u8>,
+    // Whether the start of the current line was long and has been written
+    // already, for the rest of it to be written through as well.
+    passing: bool,
+}
+
+impl<W> CommentWriter<W>
+where
+    W: io::Write,
+{
+    pub fn new(writer: W, comments: Comments) -> Self {
+        CommentWriter {
+            writer,
+            comments,
+            line: Vec::new(),
+            passing: false,
+        }
+    }
+
+    // The remaining start of a line, once the emitter is done.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let line = std::mem::take(&mut self.line);
+        self.writer.write_all(&line)
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if let Some((id, prefix)) = self.above(line) {
+            return self.write_above(line, id, prefix);
+        }
+        let (id, at) = match self.placeholder(line) {
+            Some(placeholder) => placeholder,
+            None => return self.writer.write_all(line),
+        };
+        let text = self.comments.take(id);
+        let indent = indentation(line);
+        let key = &line[indent..at];
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or_default();
+        // The text of the entry has `\n` line breaks, whichever ones the
+        // emitter is writing.
+        let line_break = line_break(line);
+        let w = &mut self.writer;
+        if key.is_empty() {
+            // The `: ` of a complex key, which would still be there with
+            // the rest commented out. The entry is written as it is instead.
+            w.write_all(&line[..at])?;
+            w.write_all(b":")?;
+            w.write_all(first.as_bytes())?;
+            for line in lines {
+                w.write_all(line_break)?;
+                w.write_all(&line_prefix(indent))?;
+                w.write_all(line.as_bytes())?;
+            }
+            return w.write_all(line_break);
+        }
+        w.write_all(&line[..indent])?;
+        w.write_all(b"# ")?;
+        w.write_all(key)?;
+        w.write_all(b":")?;
+        w.write_all(first.as_bytes())?;
+        for line in lines {
+            w.write_all(line_break)?;
+            w.write_all(&line_prefix(indent))?;
+            if line.is_empty() {
+                w.write_all(b"#")?;
+            } else {
+                w.write_all(b"# ")?;
+                w.write_all(line.as_bytes())?;
+            }
+        }
+        w.write_all(line_break)
+    }
+
+    // The comment lines in place of the extra entry or item, after what
+    // comes before it on its line, such as the `- ` of an enclosing
+    // sequence.
+    fn write_above(&mut self, line: &[u8], id: usize, prefix: usize) -> io::Result<()> {
+        let text = self.comments.take(id);
+        let line_break = line_break(line);
+        let w = &mut self.writer;
+        w.write_all(&line[..prefix])?;
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                w.write_all(&line_prefix(prefix))?;
+            }
+            if line.is_empty() {
+                w.write_all(b"#")?;
+            } else {
+                w.write_all(b"# ")?;
+                w.write_all(line.as_bytes())?;
+            }
+            w.write_all(line_break)?;
+        }
+        Ok(())
+    }
+
+    // The number of the placeholder of a line that is an extra entry or item
+    // for a comment, and the length of what comes before that entry or item.
+    fn above(&self, line: &[u8]) -> Option<(usize, usize)> {
+        let line = line.strip_suffix(b"\n")?;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let indent = indentation(line);
+        let rest = &line[indent..];
+        match rest.iter().position(|&b| b == b':') {
+            // The key of an entry, whose value is the placeholder again.
+            Some(colon) => {
+                let id = self.comments.id(ABOVE, &rest[..colon])?;
+                Some((id, indent))
+            }
+            // An item, without the `- ` in front of it.
+            None => {
+                let id = self.comments.id(ABOVE, rest)?;
+                Some((id, indent.checked_sub(2)?))
+            }
+        }
+    }
+
+    // The number of the placeholder at the end of a line, after `: ` and
+    // any spaces that line it up with other values, and where the `:` is.
+    fn placeholder(&self, line: &[u8]) -> Option<(usize, usize)> {
+        let line = line.strip_suffix(b"\n")?;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let start = line.len() - line.iter().rev().position(|&b| b == b' ')?;
+        let id = self.comments.id(PLACEHOLDER, &line[start..])?;
+        let at = line[..start].iter().rposition(|&b| b != b' ')?;
+        if line[at] == b':' {
+            Some((id, at))
+        } else {
+            None
+        }
+    }
+}
+
+// Length of the spaces and sequence indicators before the key of a line.
+fn indentation(line: &[u8]) -> usize {
+    let mut i = 0;
+    loop {
+        while line.get(i) == Some(&b' ') {
+            i += 1;
+        }
+        if line.get(i) == Some(&b'-') && line.get(i + 1) == Some(&b' ') {
+            i += 1;
+        } else {
+            return i;
+        }
+    }
+}
+
+fn line_break(line: &[u8]) -> &'static [u8] {
+    if line.ends_with(b"\r\n") {
+        b"\r\n"
+    } else {
+        b"\n"
+    }
+}
+
+fn line_prefix(indent: usize) -> Vec<u8> {
+    vec![b' '; indent]
+}
+
+impl<W> io::Write for CommentWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.passing {
+            match buf.iter().position(|&b| b == b'\n') {
+                Some(newline) => {
+                    self.writer.write_all(&buf[..=newline])?;
+                    self.passing = false;
+                    self.write_all(&buf[newline + 1..])?;
+                }
+                None => self.writer.write_all(buf)?,
+            }
+            return Ok(buf.len());
+        }
+        let end = match buf.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => newline + 1,
+            None => {
+                if self.line.len() + buf.len() >= LONG_LINE && self.comments.is_empty() {
+                    let line = std::mem::take(&mut self.line);
+                    self.writer.write_all(&line)?;
+                    self.writer.write_all(buf)?;
+                    self.passing = true;
+                } else {
+                    self.line.extend_from_slice(buf);
+                }
+                return Ok(buf.len());
+            }
+        };
+        if self.line.is_empty() && self.comments.is_empty() {
+            self.writer.write_all(&buf[..end])?;
+        } else {
+            let mut lines = std::mem::take(&mut self.line);
+            lines.extend_from_slice(&buf[..end]);
+            for line in lines.split_inclusive(|&b| b == b'\n') {
+                self.write_line(line)?;
+            }
+        }
+        self.line.extend_from_slice(&buf[end..]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
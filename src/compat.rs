@@ -0,0 +1,74 @@
+use crate::scalar::{self, QuoteContext, QuoteReason};
+
+/// A frozen set of the rules that decide how the serializer writes scalars,
+/// selected with [`SerializerBuilder::emitter_compat`].
+///
+/// The output for a given version stays the same byte for byte in later
+/// releases of this crate, for output that is signed or checked in and
+/// compared. Changes to the rules for quoting strings, formatting floats and
+/// breaking lines are only made to [`Latest`][EmitterCompat::Latest], which
+/// is the default, and get a version of their own once released.
+///
+/// The other options of the [`SerializerBuilder`] are not part of a version
+/// and keep their meaning as documented.
+///
+/// ```
+/// use serde_yaml::{EmitterCompat, SerializerBuilder};
+///
+/// let yaml = SerializerBuilder::new()
+///     .emitter_compat(EmitterCompat::V0_9)
+///     .to_string(&("yes", 0.1, "two\nlines"))
+///     .unwrap();
+/// assert_eq!(yaml, "- yes\n- 0.1\n- |-\n  two\n  lines\n");
+/// ```
+///
+/// [`SerializerBuilder`]: crate::SerializerBuilder
+/// [`SerializerBuilder::emitter_compat`]: crate::SerializerBuilder::emitter_compat
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EmitterCompat {
+    /// The rules of serde_yaml 0.9:
+    ///
+    /// - A string is written as a plain scalar unless
+    ///   [`quote_reason`][crate::scalar::quote_reason] gives a reason, as it
+    ///   does in 0.9.24. A string with a `\n` is then written as a literal
+    ///   block scalar where the emitter allows one, and any other in single
+    ///   quotes, or in double quotes if it cannot be single-quoted.
+    /// - A float is written as the shortest decimal that reads back as the
+    ///   same float, with an exponent for very large and very small ones,
+    ///   and as `.inf`, `-.inf` or `.nan` if it is not finite.
+    /// - Lines are never broken to fit a width, however long a scalar is.
+    V0_9,
+    /// The newest rules, which are currently those of
+    /// [`V0_9`][EmitterCompat::V0_9].
+    #[default]
+    Latest,
+}
+
+// The rules of one version, which the serializer goes through instead of
+// applying them itself. Before the rules of `Latest` change, the version they
+// are frozen as gets its own copy of the functions they use.
+pub(crate) struct Strategy {
+    // Why a string is not written as a plain scalar.
+    pub quote_reason: fn(&str, QuoteContext) -> Option<QuoteReason>,
+    // The text of finite floats.
+    pub format_f32: fn(f32, &mut ryu::Buffer) -> &str,
+    pub format_f64: fn(f64, &mut ryu::Buffer) -> &str,
+    // The column that the emitter breaks long lines at, or -1 for never.
+    pub width: i32,
+}
+
+static V0_9: Strategy = Strategy {
+    quote_reason: scalar::quote_reason,
+    format_f32: |float, buffer| buffer.format_finite(float),
+    format_f64: |float, buffer| buffer.format_finite(float),
+    width: -1,
+};
+
+impl EmitterCompat {
+    pub(crate) fn strategy(self) -> &'static Strategy {
+        match self {
+            EmitterCompat::V0_9 | EmitterCompat::Latest => &V0_9,
+        }
+    }
+}
@@ -1,24 +1,101 @@
-use crate::error::{self, Error, ErrorImpl};
+//! Deserializing YAML into Rust values.
+//!
+//! Besides the [`Deserializer`], which is also available at the root of the
+//! crate, this module has types whose `Deserialize` impl learns more from
+//! this crate's Deserializer than serde's data model carries.
+
+use crate::base64;
+#[cfg(feature = "tokio")]
+use crate::encoding::{Decoder, Encoding};
+use crate::error::{self, Culprit, Error, ErrorImpl, Location, Span};
+#[cfg(feature = "tokio")]
+use crate::libyaml::error::line_break_len;
 use crate::libyaml::error::Mark;
 use crate::libyaml::parser::{MappingStart, Scalar, ScalarStyle, SequenceStart};
 use crate::libyaml::tag::Tag;
-use crate::loader::{Document, Loader};
+use crate::loader::{self, Document, Loader};
+#[cfg(feature = "value")]
+use crate::number;
+use crate::order::KeyOrder;
 use crate::path::Path;
-use serde::de::value::StrDeserializer;
+use crate::schema::{CoercionFlags, SchemaProfile};
+use crate::spanned;
+use crate::styled;
+#[cfg(feature = "value")]
+use crate::value::tagged::{self, TaggedValue};
+use serde::de::value::{BorrowedStrDeserializer, SeqDeserializer, StrDeserializer};
 use serde::de::{
     self, Deserialize, DeserializeOwned, DeserializeSeed, Expected, IgnoredAny, Unexpected, Visitor,
 };
+use std::borrow::Cow;
+#[cfg(feature = "value")]
+use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::fmt;
 use std::io;
 use std::mem;
 use std::num::ParseIntError;
 use std::str;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+pub use crate::styled::StyledString;
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// A structure that deserializes YAML into Rust values.
 ///
+/// # Scalar resolution
+///
+/// When the type being deserialized does not say what it expects, as with
+/// `deserialize_any`, [`Value`](crate::Value), or the variants of an untagged
+/// enum, a plain scalar without a tag is resolved according to the YAML 1.2
+/// core schema by trying the following in order:
+///
+/// 1. Empty, `~`, `null`, `Null`, `NULL` are null.
+/// 2. `true`, `True`, `TRUE`, `false`, `False`, `FALSE` are booleans. YAML
+///    1.1 spellings like `yes`, `no`, `on`, `off` are strings.
+/// 3. Decimal integers with an optional sign, and `0x`, `0o`, `0b` prefixed
+///    integers, are integers. Digits with a leading zero, like `01` or `007`,
+///    are strings rather than octal or decimal.
+/// 4. Floats in decimal or exponent notation such as `1.`, `.5`, `1e2`, plus
+///    `.inf`, `-.inf`, `.nan`, are floats.
+/// 5. Anything else, and every quoted or block scalar, is a string.
+///
+/// Deserializing a plain scalar directly into `bool`, an integer type or a
+/// float type accepts exactly the scalars which resolve to that type above,
+/// except that a float also accepts an integer. A string accepts any scalar.
+/// An untagged enum sees the resolved value and picks the first variant that
+/// accepts it.
+///
+/// # Borrowed strings
+///
+/// A `&'de str`, or a `Cow<'de, str>` with `#[serde(borrow)]`, is borrowed
+/// from the input of [`from_str`](Self::from_str) or
+/// [`from_slice`](Self::from_slice) without a copy wherever the content of
+/// the scalar is in the input as it is. That is the case for a plain or
+/// quoted scalar on one line without escapes, and a block scalar of one line.
+/// Otherwise, as for `'it''s'`, `"a\tb"` or a scalar folded from more than
+/// one line, a `&str` fails to deserialize and a `Cow` is a copy.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// use std::borrow::Cow;
+///
+/// #[derive(Deserialize)]
+/// struct Record<'a> {
+///     name: &'a str,
+///     #[serde(borrow)]
+///     note: Cow<'a, str>,
+/// }
+///
+/// let record: Record = serde_yaml::from_str("name: web\nnote: \"a\\tb\"\n").unwrap();
+/// assert_eq!(record.name, "web");
+/// assert!(matches!(record.note, Cow::Owned(_)));
+///
+/// let record: Record = serde_yaml::from_str("name: 'web'\nnote: |\n  ok\n").unwrap();
+/// assert!(matches!(record.note, Cow::Borrowed("ok\n")));
+/// ```
+///
 /// # Examples
 ///
 /// Deserializing a single document:
@@ -57,28 +134,47 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 /// ```
 pub struct Deserializer<'de> {
     progress: Progress<'de>,
+    options: DeserializerBuilder,
 }
 
 pub(crate) enum Progress<'de> {
     Str(&'de str),
     Slice(&'de [u8]),
     Read(Box<dyn io::Read + 'de>),
+    // A part of a larger input with one document in it, which starts at the
+    // start of a line at this byte index and line.
+    #[cfg(feature = "tokio")]
+    Part {
+        input: Vec<u8>,
+        index: u64,
+        line: u64,
+    },
     Iterable(Loader<'de>),
     Document(Document<'de>),
     Fail(Arc<ErrorImpl>),
 }
 
 impl<'de> Deserializer<'de> {
+    /// Creates a builder of deserializers with options, which is the same as
+    /// [`DeserializerBuilder::new`].
+    pub fn builder() -> DeserializerBuilder {
+        DeserializerBuilder::new()
+    }
+
     /// Creates a YAML deserializer from a `&str`.
     pub fn from_str(s: &'de str) -> Self {
-        let progress = Progress::Str(s);
-        Deserializer { progress }
+        DeserializerBuilder::new().build_from_str(s)
     }
 
     /// Creates a YAML deserializer from a `&[u8]`.
+    ///
+    /// Like a reader, the bytes can be UTF-8, UTF-16 or UTF-32, which is
+    /// found from the byte order mark or else from the first character as the
+    /// YAML spec describes. Text that is not UTF-8 is transcoded first, so
+    /// strings are not borrowed from it and its documents have no
+    /// [`source`](Self::source).
     pub fn from_slice(v: &'de [u8]) -> Self {
-        let progress = Progress::Slice(v);
-        Deserializer { progress }
+        DeserializerBuilder::new().build_from_slice(v)
     }
 
     /// Creates a YAML deserializer from an `io::Read`.
@@ -86,31 +182,128 @@ impl<'de> Deserializer<'de> {
     /// Reader-based deserializers do not support deserializing borrowed types
     /// like `&str`, since the `std::io::Read` trait has no non-copying methods
     /// -- everything it does involves copying bytes out of the data source.
+    ///
+    /// The input is read in chunks as it is parsed, so iterating over the
+    /// documents of a stream only ever holds one document in memory. It can
+    /// be UTF-8, UTF-16 or UTF-32, as for [`from_slice`](Self::from_slice).
+    ///
+    /// A document is parsed in full before it is deserialized, and its
+    /// events are kept until it is done: about 150 bytes for each scalar and
+    /// each start and end of a collection, besides the text of the scalars.
+    /// For a document of short scalars that is 20 times the size of its text
+    /// or more, which a single large document needs however it is read.
     pub fn from_reader<R>(rdr: R) -> Self
     where
         R: io::Read + 'de,
     {
-        let progress = Progress::Read(Box::new(rdr));
-        Deserializer { progress }
+        DeserializerBuilder::new().build_from_reader(rdr)
+    }
+
+    /// Deserializes a document with a [`DeserializeSeed`], which can fill in
+    /// a value that already exists instead of allocating a new one.
+    ///
+    /// This is the same as `seed.deserialize(document)`, for use on each
+    /// document of a multi-document stream, except that an error from a type
+    /// which buffers its input, like an internally tagged enum, is located at
+    /// the node it is about even at the top level of the document.
+    ///
+    /// ```
+    /// use serde::de::DeserializeSeed;
+    /// use std::marker::PhantomData;
+    ///
+    /// let input = "---\nk: 107\n---\nj: 106\n";
+    /// for document in serde_yaml::Deserializer::from_str(input) {
+    ///     let value = document
+    ///         .deserialize_seed(PhantomData::<serde_yaml::Value>)
+    ///         .unwrap();
+    ///     assert!(value.is_mapping());
+    /// }
+    /// ```
+    pub fn deserialize_seed<S>(self, seed: S) -> Result<S::Value>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        // The document is still loaded when the seed is done.
+        self.de(|state| seed.deserialize(state))
+    }
+
+    /// The text of this document exactly as it is in the input, for a
+    /// document yielded by iterating over a deserializer of a `&str` or
+    /// `&[u8]`. Unlike splitting the input on `---` lines, this is not
+    /// confused by a `---` inside a block scalar or a quoted string.
+    ///
+    /// With `markers`, the text includes the `---` that starts the document
+    /// and the directives before it, and the `...` that ends it, if they are
+    /// written. Without, it is the content between them, which reads back as
+    /// the same value unless it uses a tag handle from a `%TAG` directive.
+    ///
+    /// Returns `None` for a deserializer that is not such a document, or for
+    /// a document that failed to parse.
+    ///
+    /// ```
+    /// let input = "---\nkey: |\n  ---\n--- '\n  ---'\n";
+    ///
+    /// let sources: Vec<&str> = serde_yaml::Deserializer::from_str(input)
+    ///     .map(|document| document.source(true).unwrap())
+    ///     .collect();
+    /// assert_eq!(sources, ["---\nkey: |\n  ---\n", "--- '\n  ---'\n"]);
+    ///
+    /// let sources: Vec<&str> = serde_yaml::Deserializer::from_str(input)
+    ///     .map(|document| document.source(false).unwrap())
+    ///     .collect();
+    /// assert_eq!(sources, ["key: |\n  ---\n", "'\n  ---'\n"]);
+    /// ```
+    pub fn source(&self, markers: bool) -> Option<&'de str> {
+        let document = match &self.progress {
+            Progress::Document(document) => document,
+            _ => return None,
+        };
+        let source = document.source.as_ref()?;
+        let range = if markers {
+            source.marked.clone()
+        } else {
+            source.content.clone()
+        };
+        str::from_utf8(&document.input?[range]).ok()
     }
 
     fn de<T>(
         self,
         f: impl for<'document> FnOnce(&mut DeserializerFromEvents<'de, 'document>) -> Result<T>,
     ) -> Result<T> {
-        let mut pos = 0;
-        let mut jumpcount = 0;
+        let mut f = Some(f);
+        self.de_again(false, |state| match f.take() {
+            Some(f) => f(state),
+            None => unreachable!(),
+        })
+    }
+
+    // Deserializes T, and again if an error is about a key that the field
+    // identifier of a flattened struct should see as a string, which the
+    // functions that are given T can do while a visitor can only be used
+    // once.
+    fn deserialize_again<T>(self) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        self.de_again(true, |state| T::deserialize(state))
+    }
 
+    fn de_again<T>(
+        self,
+        again: bool,
+        mut f: impl for<'document> FnMut(&mut DeserializerFromEvents<'de, 'document>) -> Result<T>,
+    ) -> Result<T> {
         match self.progress {
             Progress::Iterable(_) => return Err(error::new(ErrorImpl::MoreThanOneDocument)),
-            Progress::Document(document) => {
-                let t = f(&mut DeserializerFromEvents {
-                    document: &document,
-                    pos: &mut pos,
-                    jumpcount: &mut jumpcount,
-                    path: Path::Root,
-                    remaining_depth: 128,
-                    current_enum: None,
+            Progress::Document(mut document) => {
+                if again {
+                    report_ignored_keys_once(&mut document);
+                }
+                let result = deserialize_document(&document, again, &mut f);
+                let t = result.map_err(|err| match document.input {
+                    Some(input) => error::with_excerpt(err, input),
+                    None => err,
                 })?;
                 if let Some(parse_error) = document.error {
                     return Err(error::shared(parse_error));
@@ -120,19 +313,16 @@ impl<'de> Deserializer<'de> {
             _ => {}
         }
 
-        let mut loader = Loader::new(self.progress)?;
-        let document = match loader.next_document() {
+        let mut loader = self.options.loader(self.progress)?;
+        let mut document = match loader.next_document() {
             Some(document) => document,
             None => return Err(error::new(ErrorImpl::EndOfStream)),
         };
-        let t = f(&mut DeserializerFromEvents {
-            document: &document,
-            pos: &mut pos,
-            jumpcount: &mut jumpcount,
-            path: Path::Root,
-            remaining_depth: 128,
-            current_enum: None,
-        })?;
+        if again {
+            report_ignored_keys_once(&mut document);
+        }
+        let t =
+            deserialize_document(&document, again, &mut f).map_err(|err| loader.excerpt(err))?;
         if let Some(parse_error) = document.error {
             return Err(error::shared(parse_error));
         }
@@ -144,6 +334,1165 @@ impl<'de> Deserializer<'de> {
     }
 }
 
+/// Options for configuring a [`Deserializer`].
+///
+/// ```
+/// use serde_yaml::DeserializerBuilder;
+///
+/// let builder = DeserializerBuilder::new().map_scalar(|raw, _style| {
+///     Ok(raw.strip_prefix("${").and_then(|rest| rest.strip_suffix('}')).map(|name| {
+///         match name {
+///             "HOST" => "db: 5432 # not a comment".to_owned(),
+///             _ => String::new(),
+///         }
+///     }))
+/// });
+///
+/// let host: String = builder.from_str("${HOST}").unwrap();
+/// assert_eq!(host, "db: 5432 # not a comment");
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeserializerBuilder {
+    map_scalar: Option<ScalarHook>,
+    schema: SchemaProfile,
+    preserve_number_repr: bool,
+    preserve_core_tags: bool,
+    preserve_scalar_style: bool,
+    strict_numbers: bool,
+    integral_floats: bool,
+    recursion_limit: usize,
+    repetition_limit: Option<usize>,
+    duplicate_keys: DuplicateKeyPolicy,
+    merge_keys: bool,
+    max_errors: usize,
+    on_ignored_key: Option<IgnoredKeyHook>,
+    ignore_unknown_tags: bool,
+    ignore_unknown_anchors: bool,
+    tag_constructors: TagConstructors,
+}
+
+/// What a deserializer does with a mapping that has the same key twice, set
+/// by [`DeserializerBuilder::duplicate_keys`].
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// Every entry is given to the type being deserialized, which decides.
+    /// This is the default.
+    #[default]
+    Unchecked,
+    /// A duplicate key is an error at the second one.
+    Error,
+    /// The first entry with the key is kept and the others are skipped, as
+    /// if they were not in the input.
+    FirstWins,
+    /// The last entry with the key is kept and the others are skipped. It
+    /// is in the place of the last one, among the other entries.
+    LastWins,
+}
+
+type MapScalar = dyn Fn(&str, ScalarStyle) -> Result<Option<String>> + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct ScalarHook(Arc<MapScalar>);
+
+impl ScalarHook {
+    pub fn call(&self, raw: &str, style: ScalarStyle) -> Result<Option<String>> {
+        (self.0)(raw, style)
+    }
+}
+
+impl fmt::Debug for ScalarHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("ScalarHook")
+    }
+}
+
+type OnIgnoredKey = dyn Fn(&str, Location) + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct IgnoredKeyHook(Arc<OnIgnoredKey>);
+
+impl IgnoredKeyHook {
+    pub fn call(&self, path: &str, location: Location) {
+        (self.0)(path, location);
+    }
+}
+
+impl fmt::Debug for IgnoredKeyHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("IgnoredKeyHook")
+    }
+}
+
+#[cfg(feature = "value")]
+type ConstructTag = dyn Fn(TaggedValue) -> Result<crate::Value> + Send + Sync;
+
+// The constructors of `construct_tag`, by the name of their tag without the
+// `!`. There are none without Value.
+#[derive(Clone, Default)]
+pub(crate) struct TagConstructors {
+    #[cfg(feature = "value")]
+    by_name: BTreeMap<String, Arc<ConstructTag>>,
+}
+
+impl TagConstructors {
+    // Takes the tag off of a node if it has a constructor, and gives its name.
+    pub fn take(&self, tag: &mut Option<Tag>) -> Option<String> {
+        #[cfg(feature = "value")]
+        {
+            let name = tagged::nobang(str::from_utf8(tag.as_deref()?).ok()?);
+            if self.by_name.contains_key(name) {
+                let name = name.to_owned();
+                *tag = None;
+                return Some(name);
+            }
+        }
+        let _ = tag;
+        None
+    }
+
+    #[cfg(feature = "value")]
+    pub fn construct(&self, name: &str, value: crate::Value) -> Result<crate::Value> {
+        let tagged = TaggedValue {
+            tag: tagged::Tag::new(name),
+            value,
+        };
+        (self.by_name[name])(tagged)
+    }
+}
+
+impl fmt::Debug for TagConstructors {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("TagConstructors")
+    }
+}
+
+impl DeserializerBuilder {
+    /// Creates a builder with the default options, which read the same way as
+    /// [`Deserializer::from_str`] and the other constructors.
+    pub fn new() -> Self {
+        DeserializerBuilder {
+            map_scalar: None,
+            schema: SchemaProfile::Core12,
+            preserve_number_repr: false,
+            preserve_core_tags: false,
+            preserve_scalar_style: false,
+            strict_numbers: false,
+            integral_floats: false,
+            recursion_limit: 128,
+            repetition_limit: Some(100),
+            duplicate_keys: DuplicateKeyPolicy::Unchecked,
+            merge_keys: false,
+            max_errors: 1,
+            on_ignored_key: None,
+            ignore_unknown_tags: false,
+            ignore_unknown_anchors: false,
+            tag_constructors: TagConstructors::default(),
+        }
+    }
+
+    /// Passes the content of every string scalar, keys included, to a hook
+    /// before it is deserialized.
+    ///
+    /// The hook is given the content of the scalar after quotes and escapes
+    /// are processed, and the style it was written in. Returning `Some`
+    /// replaces the content, which is never parsed as YAML again: whatever
+    /// characters it contains, the result is still a single scalar. A plain
+    /// scalar keeps its style, so its new content is resolved as a null,
+    /// boolean or number where the type being deserialized allows it, while a
+    /// quoted or block scalar stays a string. Scalars that resolve to
+    /// something other than a string, like `8080` or `!!int 1`, are not passed
+    /// to the hook.
+    ///
+    /// An error returned by the hook fails deserialization with the location
+    /// of the scalar.
+    ///
+    /// ```
+    /// use serde::de::Error as _;
+    /// use serde_derive::Deserialize;
+    /// use serde_yaml::{DeserializerBuilder, Error};
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Config {
+    ///     url: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// fn env(name: &str) -> Option<&'static str> {
+    ///     match name {
+    ///         "DATABASE_URL" => Some("postgres://db/app?sslmode=require"),
+    ///         "PORT" => Some("5432"),
+    ///         _ => None,
+    ///     }
+    /// }
+    ///
+    /// let builder = DeserializerBuilder::new().map_scalar(|raw, _style| {
+    ///     match raw.strip_prefix("${").and_then(|rest| rest.strip_suffix('}')) {
+    ///         Some(name) => match env(name) {
+    ///             Some(value) => Ok(Some(value.to_owned())),
+    ///             None => Err(Error::custom(format!("{} is not set", name))),
+    ///         },
+    ///         None => Ok(None),
+    ///     }
+    /// });
+    ///
+    /// let config: Config = builder.from_str("url: ${DATABASE_URL}\nport: ${PORT}\n").unwrap();
+    /// assert_eq!(config.url, "postgres://db/app?sslmode=require");
+    /// assert_eq!(config.port, 5432);
+    ///
+    /// let error = builder.from_str::<Config>("url: ${DB}\n").unwrap_err();
+    /// assert_eq!(error.to_string(), "DB is not set at line 1 column 6");
+    /// ```
+    pub fn map_scalar<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str, ScalarStyle) -> Result<Option<String>> + Send + Sync + 'static,
+    {
+        self.map_scalar = Some(ScalarHook(Arc::new(hook)));
+        self
+    }
+
+    /// Selects how plain scalars resolve to nulls, booleans and numbers. The
+    /// default is [`SchemaProfile::Core12`].
+    ///
+    /// ```
+    /// use serde_yaml::{DeserializerBuilder, SchemaProfile};
+    ///
+    /// let builder = DeserializerBuilder::new().schema(SchemaProfile::Legacy11);
+    /// let enabled: Vec<bool> = builder.from_str("[yes, off]").unwrap();
+    /// assert_eq!(enabled, [true, false]);
+    /// ```
+    pub fn schema(mut self, schema: SchemaProfile) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Records how each number was written, like `0x1F` or `1e3`, in the
+    /// [`Number`] values that are deserialized. See
+    /// [`Number::original_repr`].
+    ///
+    /// This only affects [`Value`] and [`Number`], not numeric fields of
+    /// other types.
+    ///
+    /// [`Number`]: crate::Number
+    /// [`Number::original_repr`]: crate::Number::original_repr
+    /// [`Value`]: crate::Value
+    #[cfg(feature = "value")]
+    pub fn preserve_number_repr(mut self, preserve_number_repr: bool) -> Self {
+        self.preserve_number_repr = preserve_number_repr;
+        self
+    }
+
+    /// Keeps the explicit `!!str`, `!!int`, `!!float`, `!!bool` and `!!null`
+    /// tags of scalars in the [`Value`]s that are deserialized, so that the
+    /// serializer writes them out again. Such a scalar becomes a
+    /// [`Value::Tagged`] around what the tag resolves it to, with the full
+    /// name of the tag, like `tag:yaml.org,2002:str`.
+    ///
+    /// These tags decide what a scalar resolves to either way, for every
+    /// type. This option only affects whether a `Value` keeps them.
+    ///
+    /// ```
+    /// use serde_yaml::{DeserializerBuilder, Value};
+    ///
+    /// let builder = DeserializerBuilder::new().preserve_core_tags(true);
+    /// let value: Value = builder.from_str("port: !!str 8080").unwrap();
+    /// assert_eq!(value["port"].as_str(), Some("8080"));
+    /// assert_eq!(serde_yaml::to_string(&value).unwrap(), "port: !!str 8080\n");
+    ///
+    /// let value: Value = serde_yaml::from_str("port: !!str 8080").unwrap();
+    /// assert_eq!(serde_yaml::to_string(&value).unwrap(), "port: '8080'\n");
+    /// ```
+    ///
+    /// [`Value`]: crate::Value
+    /// [`Value::Tagged`]: crate::Value::Tagged
+    #[cfg(feature = "value")]
+    pub fn preserve_core_tags(mut self, preserve_core_tags: bool) -> Self {
+        self.preserve_core_tags = preserve_core_tags;
+        self
+    }
+
+    /// Keeps the style of each quoted or block string scalar in the
+    /// [`Value`]s that are deserialized, so that the serializer writes it in
+    /// the same style again rather than choosing one by its content. Such a
+    /// string becomes a [`Value::Styled`]. Plain strings stay a
+    /// [`Value::String`], which the serializer writes plain when it can.
+    ///
+    /// This only affects `Value`. A field of type [`StyledString`] keeps
+    /// its style either way.
+    ///
+    /// ```
+    /// use serde_yaml::{DeserializerBuilder, Value};
+    ///
+    /// let yaml = "name: 'web'\nscript: |\n  make\nport: \"80\"\n";
+    /// let builder = DeserializerBuilder::new().preserve_scalar_style(true);
+    /// let value: Value = builder.from_str(yaml).unwrap();
+    /// assert_eq!(value["name"], "web");
+    /// assert_eq!(serde_yaml::to_string(&value).unwrap(), yaml);
+    ///
+    /// let value: Value = serde_yaml::from_str(yaml).unwrap();
+    /// let expected = "name: web\nscript: |\n  make\nport: '80'\n";
+    /// assert_eq!(serde_yaml::to_string(&value).unwrap(), expected);
+    /// ```
+    ///
+    /// [`StyledString`]: crate::de::StyledString
+    /// [`Value`]: crate::Value
+    /// [`Value::String`]: crate::Value::String
+    /// [`Value::Styled`]: crate::Value::Styled
+    #[cfg(feature = "value")]
+    pub fn preserve_scalar_style(mut self, preserve_scalar_style: bool) -> Self {
+        self.preserve_scalar_style = preserve_scalar_style;
+        self
+    }
+
+    /// Rejects an integer that is read into an `f32` or `f64` if the float
+    /// cannot hold it exactly, rather than rounding it.
+    ///
+    /// By default such an integer becomes the nearest float, with ties going
+    /// to the one with an even mantissa, which is what `as` does. Above 2^53
+    /// not every integer has an `f64` of its own, so `9007199254740993` reads
+    /// as `9007199254740992.0`. An integer `-0` reads as `0.0`, while the
+    /// float `-0.0` keeps its sign.
+    ///
+    /// ```
+    /// use serde_yaml::DeserializerBuilder;
+    ///
+    /// let lossy: f64 = serde_yaml::from_str("9007199254740993").unwrap();
+    /// assert_eq!(lossy, 9007199254740992.0);
+    ///
+    /// let builder = DeserializerBuilder::new().strict_numbers(true);
+    /// let err = builder.from_str::<f64>("9007199254740993").unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "integer `9007199254740993` cannot be represented exactly as f64",
+    /// );
+    /// ```
+    pub fn strict_numbers(mut self, strict_numbers: bool) -> Self {
+        self.strict_numbers = strict_numbers;
+        self
+    }
+
+    /// Accepts a float where an integer is expected if it is equal to an
+    /// integer that fits the type, so that `1e2` reads as `100`.
+    ///
+    /// A float that is not an integer, like `1.5` or `.inf`, is an error
+    /// either way. So is one that is an integer out of the range of the type,
+    /// whichever the option.
+    ///
+    /// ```
+    /// use serde_yaml::DeserializerBuilder;
+    ///
+    /// let err = serde_yaml::from_str::<u64>("1e2").unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "invalid type: floating point `1e2`, expected u64",
+    /// );
+    ///
+    /// let builder = DeserializerBuilder::new().integral_floats(true);
+    /// assert_eq!(builder.from_str::<u64>("1e2").unwrap(), 100);
+    /// ```
+    pub fn integral_floats(mut self, integral_floats: bool) -> Self {
+        self.integral_floats = integral_floats;
+        self
+    }
+
+    /// How deeply sequences, mappings and newtypes may be nested, 128 by
+    /// default. Deeper input is an error rather than overflowing the stack.
+    ///
+    /// A lower limit bounds the stack that untrusted input can use. Each
+    /// level takes stack space in the `Deserialize` impls too, so a much
+    /// higher limit may need a thread with a larger stack.
+    ///
+    /// ```
+    /// use serde_yaml::{Deserializer, Value};
+    ///
+    /// let builder = Deserializer::builder().recursion_limit(2);
+    /// assert!(builder.from_str::<Value>("[[1]]").is_ok());
+    /// let err = builder.from_str::<Value>("[[[1]]]").unwrap_err();
+    /// assert_eq!(err.to_string(), "recursion limit exceeded at line 1 column 3");
+    /// ```
+    pub fn recursion_limit(mut self, recursion_limit: usize) -> Self {
+        self.recursion_limit = recursion_limit;
+        self
+    }
+
+    /// How many times aliases may be expanded while deserializing a
+    /// document, as a multiple of the number of events in it, 100 by
+    /// default. None for no limit.
+    ///
+    /// Each alias is deserialized again wherever it is used, so a few lines
+    /// of aliases to aliases can expand exponentially. This is the limit
+    /// that stops such a document with "repetition limit exceeded". A
+    /// document that uses a shared anchor many times may need a higher one.
+    ///
+    /// ```
+    /// use serde_yaml::{Deserializer, Value};
+    ///
+    /// let yaml = "a: &a [1, 2, 3, 4]
+    /// b: &b [*a, *a, *a, *a]
+    /// c: &c [*b, *b, *b, *b]
+    /// d: [*c, *c, *c, *c]
+    /// ";
+    /// assert!(serde_yaml::from_str::<Value>(yaml).is_ok());
+    ///
+    /// let builder = Deserializer::builder().repetition_limit(Some(2));
+    /// let err = builder.from_str::<Value>(yaml).unwrap_err();
+    /// assert_eq!(err.to_string(), "repetition limit exceeded");
+    ///
+    /// let builder = Deserializer::builder().repetition_limit(None);
+    /// assert!(builder.from_str::<Value>(yaml).is_ok());
+    /// ```
+    pub fn repetition_limit(mut self, repetition_limit: Option<usize>) -> Self {
+        self.repetition_limit = repetition_limit;
+        self
+    }
+
+    /// What to do with a mapping that has the same key twice. By default
+    /// this is up to the type it is deserialized into: a struct or a
+    /// [`Value`](crate::Value) rejects it, while for a map the last entry
+    /// wins.
+    ///
+    /// Keys are the same if they are scalars that resolve to the same value
+    /// under the [`schema`](Self::schema), so `1` and `0x1` are the same key
+    /// but `1` and `'1'` are not. This is how the
+    /// [`DuplicateKeys`](crate::lint::Rule::DuplicateKeys) lint compares them.
+    ///
+    /// ```
+    /// use serde_yaml::{Deserializer, DuplicateKeyPolicy};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let yaml = "port: 80\nport: 8080\n";
+    /// let map: BTreeMap<String, u16> = serde_yaml::from_str(yaml).unwrap();
+    /// assert_eq!(map["port"], 8080);
+    ///
+    /// let builder = Deserializer::builder().duplicate_keys(DuplicateKeyPolicy::Error);
+    /// let err = builder.from_str::<BTreeMap<String, u16>>(yaml).unwrap_err();
+    /// assert_eq!(err.to_string(), "duplicate key `port` at line 2 column 1");
+    ///
+    /// let builder = Deserializer::builder().duplicate_keys(DuplicateKeyPolicy::FirstWins);
+    /// let map: BTreeMap<String, u16> = builder.from_str(yaml).unwrap();
+    /// assert_eq!(map["port"], 80);
+    /// ```
+    pub fn duplicate_keys(mut self, duplicate_keys: DuplicateKeyPolicy) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+
+    /// Whether a `<<` key merges the entries of the mapping it refers to
+    /// into its own mapping, or of each mapping in a sequence of them,
+    /// before the mapping is deserialized. This is the merge key of YAML
+    /// 1.1, as [`Value::apply_merge`](crate::Value::apply_merge) does it
+    /// after the fact. Off by default, when `<<` is a key like any other.
+    ///
+    /// The entries of the mapping itself take precedence over merged ones,
+    /// and among a sequence of mappings the earlier ones do, so merging
+    /// does not count as a duplicate key. A `<<` key that is quoted or
+    /// tagged is not a merge key.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// use serde_yaml::Deserializer;
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// struct Service {
+    ///     image: String,
+    ///     restart: String,
+    /// }
+    ///
+    /// let yaml = "
+    /// defaults: &defaults
+    ///   image: app:1.0
+    ///   restart: always
+    /// web:
+    ///   <<: *defaults
+    ///   image: app:2.0
+    /// ";
+    /// #[derive(Deserialize)]
+    /// struct Compose {
+    ///     web: Service,
+    /// }
+    /// let builder = Deserializer::builder().merge_keys(true);
+    /// let compose: Compose = builder.from_str(yaml).unwrap();
+    /// assert_eq!(
+    ///     compose.web,
+    ///     Service {
+    ///         image: "app:2.0".to_owned(),
+    ///         restart: "always".to_owned(),
+    ///     },
+    /// );
+    /// ```
+    pub fn merge_keys(mut self, merge_keys: bool) -> Self {
+        self.merge_keys = merge_keys;
+        self
+    }
+
+    /// How many errors in the data to find before giving up, so that a
+    /// program can report all that is wrong with its input at once. The
+    /// default is 1, for stopping at the first error.
+    ///
+    /// After an error such as a value of the wrong type or a missing field,
+    /// the document is deserialized again without the node that the error is
+    /// at, which is the key and value of a mapping entry or an item of a
+    /// sequence. An error from a collection that this makes invalid, such as
+    /// a struct with a field missing now, is not counted again; the
+    /// collection is left out of its own parent in turn. What is found
+    /// stops at an error that is not at a node, such as invalid syntax, or
+    /// at the root of the document.
+    ///
+    /// This applies to [`from_str`](Self::from_str),
+    /// [`from_slice`](Self::from_slice) and [`from_reader`](Self::from_reader)
+    /// of the builder, which return an error with all of them in
+    /// [`Error::errors`](crate::Error::errors). A type is deserialized once
+    /// for each error, so this takes longer for a document with many.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// use serde_yaml::Deserializer;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Server {
+    ///     host: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// let yaml = "
+    /// - host: a.example.com
+    ///   port: http
+    /// - host: b.example.com
+    /// - host: c.example.com
+    ///   port: 99999
+    /// ";
+    /// let builder = Deserializer::builder().max_errors(10);
+    /// let err = builder.from_str::<Vec<Server>>(yaml).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     ".[0].port: invalid type: string \"http\", expected u16 at line 3 column 9\n\
+    ///      .[1]: missing field `port` at line 4 column 3\n\
+    ///      .[2].port: invalid value: integer `99999`, expected u16 at line 6 column 9",
+    /// );
+    /// assert_eq!(err.errors().len(), 3);
+    /// ```
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+
+    /// Calls a function for each mapping entry that the type being
+    /// deserialized ignores, with the path to its value and the location of
+    /// its key, such as for warning about a misspelled key in a config file
+    /// that is accepted anyway.
+    ///
+    /// An entry is ignored when its value is skipped over, as for a key that
+    /// is not a field of a struct without `#[serde(deny_unknown_fields)]`.
+    /// The entries that a struct with `#[serde(flatten)]` fields collects for
+    /// them are not ignored, even if no flattened field wants them.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// use serde_yaml::Deserializer;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Config {
+    ///     name: String,
+    ///     #[serde(default)]
+    ///     verbose: bool,
+    /// }
+    ///
+    /// let ignored = Arc::new(Mutex::new(Vec::new()));
+    /// let builder = Deserializer::builder().on_ignored_key({
+    ///     let ignored = Arc::clone(&ignored);
+    ///     move |path, location| {
+    ///         ignored.lock().unwrap().push((path.to_owned(), location.line()));
+    ///     }
+    /// });
+    /// let config: Config = builder.from_str("name: web\nverbos: true\n").unwrap();
+    /// assert_eq!(config.name, "web");
+    /// assert!(!config.verbose);
+    /// assert_eq!(*ignored.lock().unwrap(), [("verbos".to_owned(), 2)]);
+    /// ```
+    pub fn on_ignored_key<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str, Location) + Send + Sync + 'static,
+    {
+        self.on_ignored_key = Some(IgnoredKeyHook(Arc::new(hook)));
+        self
+    }
+
+    /// Whether tags that this crate does not resolve itself, like `!vault`
+    /// or `!!timestamp`, are left out, so that the node is deserialized as
+    /// if it had no tag. The default is false.
+    ///
+    /// By default such a tag is given to the type being deserialized: an
+    /// enum takes it as the name of its variant, and a [`Value`] keeps it in
+    /// a [`TaggedValue`], while other types only see the node without it.
+    /// That makes a scalar like `!vault ~` not null, and an enum fail with
+    /// an unknown variant. With this option a tag is only the variant of an
+    /// enum if the enum has a variant by that name.
+    ///
+    /// The tags that are kept are those of the core schema, such as `!!str`
+    /// and `!!int`, and `!!binary`, `!!seq` and `!!map`.
+    ///
+    /// [`Value`]: crate::Value
+    /// [`TaggedValue`]: crate::value::TaggedValue
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// use serde_yaml::Deserializer;
+    ///
+    /// #[derive(Deserialize, PartialEq, Debug)]
+    /// enum Become {
+    ///     Sudo,
+    ///     Su,
+    /// }
+    ///
+    /// #[derive(Deserialize, PartialEq, Debug)]
+    /// struct Task {
+    ///     password: String,
+    ///     timeout: Option<u32>,
+    ///     method: Become,
+    /// }
+    ///
+    /// let yaml = "
+    /// password: !vault secret
+    /// timeout: !vendor ~
+    /// method: !vendor Sudo
+    /// ";
+    /// assert!(serde_yaml::from_str::<Task>(yaml).is_err());
+    ///
+    /// let builder = Deserializer::builder().ignore_unknown_tags(true);
+    /// let task: Task = builder.from_str(yaml).unwrap();
+    /// assert_eq!(task.password, "secret");
+    /// assert_eq!(task.timeout, None);
+    /// assert_eq!(task.method, Become::Sudo);
+    /// ```
+    pub fn ignore_unknown_tags(mut self, ignore_unknown_tags: bool) -> Self {
+        self.ignore_unknown_tags = ignore_unknown_tags;
+        self
+    }
+
+    /// Lets aliases refer to anchors that are not defined before them, for
+    /// getting what is needed out of a document that has such aliases in the
+    /// parts that are not. An alias to an unknown anchor is then only an
+    /// error if its node is deserialized, rather than wherever it is.
+    ///
+    /// A node that is skipped, such as the value of a field that the type
+    /// does not have or that [`IgnoredAny`] stands
+    /// for, can have such aliases in it. A merge key whose value is one is
+    /// still an error with [`merge_keys`](Self::merge_keys), as the entries it
+    /// merges are part of the mapping.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// use serde_yaml::Deserializer;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Service {
+    ///     name: String,
+    /// }
+    ///
+    /// let yaml = "name: web\nports: [*http, 443]\n";
+    /// let err = serde_yaml::from_str::<Service>(yaml).unwrap_err();
+    /// assert_eq!(err.to_string(), "unknown anchor at line 2 column 9");
+    ///
+    /// let builder = Deserializer::builder().ignore_unknown_anchors(true);
+    /// let service: Service = builder.from_str(yaml).unwrap();
+    /// assert_eq!(service.name, "web");
+    ///
+    /// let err = builder.from_str::<Service>("name: *web\n").unwrap_err();
+    /// assert_eq!(err.to_string(), "unknown anchor at line 1 column 7");
+    /// ```
+    pub fn ignore_unknown_anchors(mut self, ignore_unknown_anchors: bool) -> Self {
+        self.ignore_unknown_anchors = ignore_unknown_anchors;
+        self
+    }
+
+    /// Registers a constructor for the nodes with a tag, like `!Ref` or
+    /// `!secret`, for a dialect of YAML with tags of its own. The leading `!`
+    /// of the tag is optional.
+    ///
+    /// The constructor is given each such node as a [`TaggedValue`] while
+    /// the document is loaded, and the node is replaced by the [`Value`] it
+    /// returns, which is then deserialized as if it had been in the input.
+    /// The nodes inside of a node are constructed before it, and the tags of
+    /// a value that a constructor returns are not constructed again. An
+    /// error from a constructor fails deserialization with the location of
+    /// its node.
+    ///
+    /// Anchors inside of a node that is replaced cannot be referred to by
+    /// aliases after it. An alias to the node itself is its value.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// use serde::de::Error;
+    /// use serde_yaml::{Deserializer, Mapping, Value};
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Template {
+    ///     bucket: Value,
+    ///     password: String,
+    /// }
+    ///
+    /// let builder = Deserializer::builder()
+    ///     .construct_tag("!Ref", |tagged| {
+    ///         let mut mapping = Mapping::new();
+    ///         mapping.insert("Ref".into(), tagged.value);
+    ///         Ok(Value::Mapping(mapping))
+    ///     })
+    ///     .construct_tag("secret", |tagged| match tagged.value.as_str() {
+    ///         Some("db") => Ok("hunter2".into()),
+    ///         _ => Err(serde_yaml::Error::custom("unknown secret")),
+    ///     });
+    ///
+    /// let yaml = "bucket: !Ref Logs\npassword: !secret db\n";
+    /// let template: Template = builder.from_str(yaml).unwrap();
+    /// assert_eq!(template.bucket["Ref"], "Logs");
+    /// assert_eq!(template.password, "hunter2");
+    ///
+    /// let err = builder.from_str::<Template>("password: !secret api").unwrap_err();
+    /// assert_eq!(err.to_string(), "unknown secret at line 1 column 11");
+    /// ```
+    ///
+    /// [`TaggedValue`]: crate::value::TaggedValue
+    /// [`Value`]: crate::Value
+    #[cfg(feature = "value")]
+    pub fn construct_tag<F>(mut self, tag: &str, constructor: F) -> Self
+    where
+        F: Fn(TaggedValue) -> Result<crate::Value> + Send + Sync + 'static,
+    {
+        let name = tagged::nobang(tag).to_owned();
+        self.tag_constructors
+            .by_name
+            .insert(name, Arc::new(constructor));
+        self
+    }
+
+    fn loader<'de>(&self, progress: Progress<'de>) -> Result<Loader<'de>> {
+        Loader::new(
+            progress,
+            self.map_scalar.clone(),
+            self.schema.flags(),
+            self.preserve_number_repr,
+            self.preserve_core_tags,
+            self.preserve_scalar_style,
+            self.strict_numbers,
+            self.integral_floats,
+            self.recursion_limit,
+            self.repetition_limit,
+            self.duplicate_keys,
+            self.merge_keys,
+            self.on_ignored_key.clone(),
+            self.ignore_unknown_tags,
+            self.ignore_unknown_anchors,
+            self.tag_constructors.clone(),
+        )
+    }
+
+    // Deserializes the only document of the input again and again with the
+    // nodes that have an error left out, for `max_errors`.
+    fn deserialize_collecting<'de, T>(&self, progress: Progress<'de>) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let mut loader = self.loader(progress)?;
+        let mut document = match loader.next_document() {
+            Some(document) => document,
+            None => return Err(error::new(ErrorImpl::EndOfStream)),
+        };
+        report_ignored_keys_once(&mut document);
+        let nodes = Nodes::new(&document);
+        let mut errors = Vec::new();
+        // The nodes left out so far, with the error at each.
+        let mut left_out = Vec::new();
+        let result = loop {
+            let string_keys = document.string_keys.borrow().len();
+            let mut pos = 0;
+            let mut jumpcount = 0;
+            let result = T::deserialize(&mut DeserializerFromEvents {
+                document: &document,
+                pos: &mut pos,
+                jumpcount: &mut jumpcount,
+                path: Path::Root,
+                remaining_depth: document.recursion_limit,
+                current_enum: None,
+                resolve_identifier: false,
+                entry_key: None,
+            });
+            let err = match result {
+                Ok(t) if errors.is_empty() => break Ok(t),
+                Ok(_) => break Err(error::multiple(errors)),
+                Err(_) if document.string_keys.borrow().len() > string_keys => continue,
+                Err(err) => loader.excerpt(locate_buffered_root(&document, err)),
+            };
+            if document.error.is_some() {
+                break Err(err);
+            }
+            let node = err.span().and_then(|span| nodes.find(&document, span));
+            let entry = node.and_then(|node| nodes.entry[node]);
+            let (node, entry) = match (node, entry) {
+                (Some(node), Some(entry)) if !document.skipped.contains(&entry) => (node, entry),
+                _ => {
+                    if errors.is_empty() || !nodes.contains_any(node, &left_out) {
+                        errors.push(err);
+                    }
+                    break Err(error::multiple(errors));
+                }
+            };
+            if !nodes.contains_any(Some(node), &left_out) {
+                errors.push(err);
+                if errors.len() >= self.max_errors {
+                    break Err(error::multiple(errors));
+                }
+            }
+            document.skipped.insert(entry);
+            left_out.push(node);
+        };
+        let t = result?;
+        if let Some(parse_error) = document.error {
+            return Err(error::shared(parse_error));
+        }
+        if loader.next_document().is_none() {
+            Ok(t)
+        } else {
+            Err(error::new(ErrorImpl::MoreThanOneDocument))
+        }
+    }
+
+    /// Creates a deserializer with these options from a `&str`.
+    pub fn build_from_str<'de>(self, s: &'de str) -> Deserializer<'de> {
+        let progress = Progress::Str(s);
+        Deserializer {
+            progress,
+            options: self,
+        }
+    }
+
+    /// Creates a deserializer with these options from a `&[u8]`.
+    pub fn build_from_slice<'de>(self, v: &'de [u8]) -> Deserializer<'de> {
+        let progress = Progress::Slice(v);
+        Deserializer {
+            progress,
+            options: self,
+        }
+    }
+
+    /// Creates a deserializer with these options from an `io::Read`.
+    pub fn build_from_reader<'de, R>(self, rdr: R) -> Deserializer<'de>
+    where
+        R: io::Read + 'de,
+    {
+        let progress = Progress::Read(Box::new(rdr));
+        Deserializer {
+            progress,
+            options: self,
+        }
+    }
+
+    /// Deserialize an instance of type `T` from a string of YAML text, using
+    /// these options.
+    pub fn from_str<'de, T>(&self, s: &'de str) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        if self.max_errors > 1 {
+            return self.deserialize_collecting(Progress::Str(s));
+        }
+        self.clone().build_from_str(s).deserialize_again()
+    }
+
+    /// Deserialize an instance of type `T` from bytes of YAML text, using
+    /// these options.
+    pub fn from_slice<'de, T>(&self, v: &'de [u8]) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        if self.max_errors > 1 {
+            return self.deserialize_collecting(Progress::Slice(v));
+        }
+        self.clone().build_from_slice(v).deserialize_again()
+    }
+
+    /// Deserialize an instance of type `T` from an IO stream of YAML, using
+    /// these options.
+    pub fn from_reader<R, T>(&self, rdr: R) -> Result<T>
+    where
+        R: io::Read,
+        T: DeserializeOwned,
+    {
+        if self.max_errors > 1 {
+            return self.deserialize_collecting(Progress::Read(Box::new(rdr)));
+        }
+        self.clone().build_from_reader(rdr).deserialize_again()
+    }
+
+    /// Deserialize an instance of type `T` from a tokio `AsyncRead`, using
+    /// these options. Like [`from_async_reader`], this holds one document of
+    /// the input in memory at a time.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async_reader<R, T>(&self, rdr: R) -> Result<T>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        T: DeserializeOwned,
+    {
+        let mut documents = self.async_documents(rdr);
+        let t = match documents.next_document().await {
+            Some(t) => t?,
+            None => return Err(error::new(ErrorImpl::EndOfStream)),
+        };
+        match documents.next_part().await {
+            None => Ok(t),
+            Some(Ok(_)) => Err(error::new(ErrorImpl::MoreThanOneDocument)),
+            Some(Err(io_error)) => Err(error::new(ErrorImpl::Io(io_error))),
+        }
+    }
+
+    /// Reads the documents of a tokio `AsyncRead` one at a time, using these
+    /// options, like iterating over a [`Deserializer`].
+    ///
+    /// Each document is read in full before it is parsed. What is held in
+    /// memory at once is the text of one document and the events that it is
+    /// parsed into, which take a few times the size of the text. A document
+    /// ends at the next line that starts with `---`, or at the end of a line
+    /// that starts with `...`.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct Event {
+    ///     kind: String,
+    /// }
+    ///
+    /// async fn kinds<R>(log: R) -> serde_yaml::Result<Vec<String>>
+    /// where
+    ///     R: tokio::io::AsyncRead + Unpin,
+    /// {
+    ///     let mut documents = serde_yaml::DeserializerBuilder::new().async_documents(log);
+    ///     let mut kinds = Vec::new();
+    ///     while let Some(event) = documents.next_document::<Event>().await {
+    ///         kinds.push(event?.kind);
+    ///     }
+    ///     Ok(kinds)
+    /// }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn async_documents<R>(&self, rdr: R) -> AsyncDocuments<R>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        AsyncDocuments {
+            read: rdr,
+            options: self.clone(),
+            raw: Vec::new(),
+            decoder: None,
+            buffer: Vec::new(),
+            index: 0,
+            line: 0,
+            scanned: 0,
+            content: false,
+            detected: false,
+            eof: false,
+            done: false,
+            started: false,
+        }
+    }
+}
+
+/// The documents of a tokio `AsyncRead`, from
+/// [`DeserializerBuilder::async_documents`].
+#[cfg(feature = "tokio")]
+pub struct AsyncDocuments<R> {
+    read: R,
+    options: DeserializerBuilder,
+    // The last bytes read, and for input in another encoding than UTF-8,
+    // what transcodes them.
+    raw: Vec<u8>,
+    decoder: Option<Decoder>,
+    // What has been read and not given to a document yet, in UTF-8, which
+    // starts at the start of a line at this byte index and line of the
+    // input.
+    buffer: Vec<u8>,
+    index: u64,
+    line: u64,
+    // How much of the buffer has been looked at for where a document ends,
+    // and whether any of it is part of a document and not only comments,
+    // directives or blank lines.
+    scanned: usize,
+    content: bool,
+    // Whether enough has been read to tell the encoding.
+    detected: bool,
+    eof: bool,
+    done: bool,
+    // Whether a document has been given.
+    started: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl<R> AsyncDocuments<R>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    /// Deserializes the next document as an instance of type `T`, or gives
+    /// None after the last one. An input without any document has a single
+    /// null document, as for [`Deserializer`].
+    pub async fn next_document<T>(&mut self) -> Option<Result<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let part = match self.next_part().await? {
+            Ok(part) => part,
+            Err(io_error) => return Some(Err(error::new(ErrorImpl::Io(io_error)))),
+        };
+        if self.options.max_errors > 1 {
+            return Some(self.options.deserialize_collecting(part));
+        }
+        let deserializer = Deserializer {
+            progress: part,
+            options: self.options.clone(),
+        };
+        Some(deserializer.deserialize_again())
+    }
+
+    // Reads up to the end of the next document, and takes it out of the
+    // buffer. Parts of the input with no document in them are left out.
+    async fn next_part(&mut self) -> Option<io::Result<Progress<'static>>> {
+        loop {
+            while let Some(end) = self.split() {
+                let input: Vec<u8> = self.buffer.drain(..end).collect();
+                let (index, line) = (self.index, self.line);
+                self.index += input.len() as u64;
+                self.line += line_breaks(&input);
+                self.scanned = 0;
+                let part = Progress::Part { input, index, line };
+                if mem::take(&mut self.content) {
+                    self.started = true;
+                    return Some(Ok(part));
+                }
+            }
+            if self.eof {
+                if mem::replace(&mut self.done, true) {
+                    return None;
+                }
+                // Whatever comes after the last document. The document of an
+                // input that has none is null.
+                if self.started && !self.content {
+                    return None;
+                }
+                self.started = true;
+                return Some(Ok(Progress::Part {
+                    input: mem::take(&mut self.buffer),
+                    index: self.index,
+                    line: self.line,
+                }));
+            }
+            self.raw.resize(8192, 0);
+            let read = tokio::io::AsyncReadExt::read(&mut self.read, &mut self.raw);
+            let decoded = match read.await {
+                Ok(n) => {
+                    self.eof = n == 0;
+                    self.decode(n)
+                }
+                Err(io_error) => Err(io_error),
+            };
+            match decoded {
+                Ok(()) => {}
+                Err(io_error) => {
+                    self.buffer.clear();
+                    self.eof = true;
+                    self.done = true;
+                    return Some(Err(io_error));
+                }
+            }
+        }
+    }
+
+    // Adds the first bytes of `raw` to the buffer, once it is known whether
+    // they need to be transcoded to UTF-8.
+    fn decode(&mut self, n: usize) -> io::Result<()> {
+        let raw = &self.raw[..n];
+        match &mut self.decoder {
+            Some(decoder) => decoder.decode(raw, &mut self.buffer)?,
+            None => self.buffer.extend_from_slice(raw),
+        }
+        if !self.detected && (self.buffer.len() >= 4 || self.eof) {
+            self.detected = true;
+            if let Some(encoding) = Encoding::detect(&self.buffer) {
+                let mut decoder = Decoder::new(encoding);
+                let raw = mem::take(&mut self.buffer);
+                decoder.decode(&raw, &mut self.buffer)?;
+                self.decoder = Some(decoder);
+            }
+        }
+        match &self.decoder {
+            Some(decoder) if self.eof => decoder.finish(),
+            _ => Ok(()),
+        }
+    }
+
+    // Looks at the lines read since the last time, for where the part of the
+    // input with the next document ends: before a `---` line that starts the
+    // document after it, or after a `...` line. Neither can be inside of a
+    // scalar, which has to be indented in a block and which is an error in a
+    // quoted scalar.
+    fn split(&mut self) -> Option<usize> {
+        if !self.detected {
+            return None;
+        }
+        while self.scanned < self.buffer.len() {
+            let start = self.scanned;
+            let end = match self.buffer[start..].iter().position(|&b| b == b'\n') {
+                Some(i) => start + i + 1,
+                None if self.eof => self.buffer.len(),
+                None => return None,
+            };
+            let mut line = &self.buffer[start..end];
+            if self.index == 0 && start == 0 {
+                line = line.strip_prefix("\u{feff}".as_bytes()).unwrap_or(line);
+            }
+            if is_marker(line, b"---") && self.content {
+                return Some(start);
+            }
+            self.scanned = end;
+            if is_marker(line, b"...") {
+                return Some(end);
+            }
+            let text = line.iter().position(|&b| !b" \t\r\n".contains(&b));
+            if let Some(i) = text {
+                if line[i] != b'#' && !(i == 0 && line[0] == b'%') {
+                    self.content = true;
+                }
+            }
+        }
+        None
+    }
+}
+
+// Whether the line starts with a document marker.
+#[cfg(feature = "tokio")]
+fn is_marker(line: &[u8], marker: &[u8]) -> bool {
+    line.starts_with(marker) && matches!(line.get(3), None | Some(b' ' | b'\t' | b'\r' | b'\n'))
+}
+
+// The number of lines that the parser counts in this text.
+#[cfg(feature = "tokio")]
+fn line_breaks(text: &[u8]) -> u64 {
+    let mut lines = 0;
+    let mut i = 0;
+    while i < text.len() {
+        match line_break_len(text, i) {
+            0 => i += 1,
+            len => {
+                lines += 1;
+                i += len;
+            }
+        }
+    }
+    lines
+}
+
+impl Default for DeserializerBuilder {
+    fn default() -> Self {
+        DeserializerBuilder::new()
+    }
+}
+
 impl<'de> Iterator for Deserializer<'de> {
     type Item = Self;
 
@@ -153,12 +1502,14 @@ impl<'de> Iterator for Deserializer<'de> {
                 let document = loader.next_document()?;
                 return Some(Deserializer {
                     progress: Progress::Document(document),
+                    options: DeserializerBuilder::new(),
                 });
             }
             Progress::Document(_) => return None,
             Progress::Fail(err) => {
                 return Some(Deserializer {
                     progress: Progress::Fail(Arc::clone(err)),
+                    options: DeserializerBuilder::new(),
                 });
             }
             _ => {}
@@ -166,7 +1517,7 @@ impl<'de> Iterator for Deserializer<'de> {
 
         let dummy = Progress::Str("");
         let input = mem::replace(&mut self.progress, dummy);
-        match Loader::new(input) {
+        match self.options.loader(input) {
             Ok(loader) => {
                 self.progress = Progress::Iterable(loader);
                 self.next()
@@ -176,6 +1527,7 @@ impl<'de> Iterator for Deserializer<'de> {
                 self.progress = Progress::Fail(Arc::clone(&fail));
                 Some(Deserializer {
                     progress: Progress::Fail(fail),
+                    options: DeserializerBuilder::new(),
                 })
             }
         }
@@ -418,6 +1770,73 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     }
 }
 
+// How the nodes of a document are nested, for leaving them out.
+struct Nodes {
+    // For each event that starts a node, the index after the node.
+    end: Vec<usize>,
+    // For each event that starts a node, the event by which it is left out
+    // of its collection: the node itself in a sequence, or the key of its
+    // entry in a mapping. None for the root and for events that end a
+    // collection.
+    entry: Vec<Option<usize>>,
+}
+
+impl Nodes {
+    fn new(document: &Document) -> Self {
+        let len = document.events.len();
+        let mut nodes = Nodes {
+            end: vec![0; len],
+            entry: vec![None; len],
+        };
+        // The start of each collection that the next node is in, whether it
+        // is a mapping, and the key of the current entry.
+        let mut parents: Vec<(usize, bool, Option<usize>)> = Vec::new();
+        for (index, (event, _mark)) in document.events.iter().enumerate() {
+            if let Event::SequenceEnd | Event::MappingEnd = event {
+                if let Some((start, _, _)) = parents.pop() {
+                    nodes.end[start] = index + 1;
+                }
+                continue;
+            }
+            nodes.entry[index] = match parents.last_mut() {
+                None => None,
+                Some((_, false, _)) => Some(index),
+                Some((_, true, key @ None)) => {
+                    *key = Some(index);
+                    Some(index)
+                }
+                Some((_, true, key)) => key.take(),
+            };
+            match event {
+                Event::SequenceStart(_) => parents.push((index, false, None)),
+                Event::MappingStart(_) => parents.push((index, true, None)),
+                _ => nodes.end[index] = index + 1,
+            }
+        }
+        nodes
+    }
+
+    // The innermost node that is at this span.
+    fn find(&self, document: &Document, span: Span) -> Option<usize> {
+        let same = |a: Mark, b: Mark| a.index() == b.index();
+        (0..document.events.len()).rev().find(|&index| {
+            self.end[index] != 0
+                && same(document.events[index].1, span.start)
+                && same(document.ends[index], span.end)
+        })
+    }
+
+    // Whether any of these nodes is the node or inside of it.
+    fn contains_any(&self, node: Option<usize>, nodes: &[usize]) -> bool {
+        match node {
+            Some(node) => nodes
+                .iter()
+                .any(|&inner| node <= inner && inner < self.end[node]),
+            None => !nodes.is_empty(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum Event<'de> {
     Alias(usize),
@@ -429,13 +1848,39 @@ pub(crate) enum Event<'de> {
     Void,
 }
 
+// The Value of the node at this index of a document that is still being
+// loaded, for the constructor of its tag.
+#[cfg(feature = "value")]
+pub(crate) fn value_at(document: &Document, mut pos: usize) -> Result<crate::Value> {
+    let mut jumpcount = 0;
+    crate::Value::deserialize(&mut DeserializerFromEvents {
+        document,
+        pos: &mut pos,
+        jumpcount: &mut jumpcount,
+        path: Path::Root,
+        remaining_depth: document.recursion_limit,
+        current_enum: None,
+        resolve_identifier: false,
+        entry_key: None,
+    })
+}
+
 struct DeserializerFromEvents<'de, 'document> {
     document: &'document Document<'de>,
     pos: &'document mut usize,
     jumpcount: &'document mut usize,
     path: Path<'document>,
-    remaining_depth: u8,
+    remaining_depth: usize,
     current_enum: Option<CurrentEnum<'document>>,
+    // Whether deserialize_identifier resolves plain scalars the way
+    // deserialize_any does. This is the case for keys of a map, including a
+    // struct with #[serde(flatten)] fields which serde deserializes as a map,
+    // but not for the field names of an ordinary struct, where an integer key
+    // would otherwise be mistaken for a field index.
+    resolve_identifier: bool,
+    // For the value of a mapping entry, where its key starts, which is given
+    // to the hook of `on_ignored_key` if the value is ignored.
+    entry_key: Option<Mark>,
 }
 
 #[derive(Copy, Clone)]
@@ -446,12 +1891,15 @@ struct CurrentEnum<'document> {
 
 impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
     fn peek_event(&self) -> Result<&'document Event<'de>> {
-        self.peek_event_mark().map(|(event, _mark)| event)
+        self.peek_event_span().map(|(event, _span)| event)
     }
 
-    fn peek_event_mark(&self) -> Result<(&'document Event<'de>, Mark)> {
+    fn peek_event_span(&self) -> Result<(&'document Event<'de>, Span)> {
         match self.document.events.get(*self.pos) {
-            Some((event, mark)) => Ok((event, *mark)),
+            Some((event, mark)) => {
+                let end = self.document.ends[*self.pos];
+                Ok((event, Span { start: *mark, end }))
+            }
             None => Err(match &self.document.error {
                 Some(parse_error) => error::shared(Arc::clone(parse_error)),
                 None => error::new(ErrorImpl::EndOfStream),
@@ -460,14 +1908,14 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
     }
 
     fn next_event(&mut self) -> Result<&'document Event<'de>> {
-        self.next_event_mark().map(|(event, _mark)| event)
+        self.next_event_span().map(|(event, _span)| event)
     }
 
-    fn next_event_mark(&mut self) -> Result<(&'document Event<'de>, Mark)> {
-        self.peek_event_mark().map(|(event, mark)| {
+    fn next_event_span(&mut self) -> Result<(&'document Event<'de>, Span)> {
+        self.peek_event_span().map(|(event, span)| {
             *self.pos += 1;
             self.current_enum = None;
-            (event, mark)
+            (event, span)
         })
     }
 
@@ -476,8 +1924,10 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         pos: &'anchor mut usize,
     ) -> Result<DeserializerFromEvents<'de, 'anchor>> {
         *self.jumpcount += 1;
-        if *self.jumpcount > self.document.events.len() * 100 {
-            return Err(error::new(ErrorImpl::RepetitionLimitExceeded));
+        if let Some(limit) = self.document.repetition_limit {
+            if *self.jumpcount > self.document.events.len().saturating_mul(limit) {
+                return Err(error::new(ErrorImpl::RepetitionLimitExceeded));
+            }
         }
         match self.document.aliases.get(pos) {
             Some(found) => {
@@ -489,10 +1939,118 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
                     path: Path::Alias { parent: &self.path },
                     remaining_depth: self.remaining_depth,
                     current_enum: None,
+                    resolve_identifier: self.resolve_identifier,
+                    entry_key: None,
                 })
             }
-            None => panic!("unresolved alias: {}", *pos),
+            // An alias to an unknown anchor, with `ignore_unknown_anchors`,
+            // which is the last event.
+            None => {
+                let mark = self.document.events[*self.pos - 1].1;
+                Err(error::new(ErrorImpl::UnknownAnchor(mark)))
+            }
+        }
+    }
+
+    // The tag that names the variant of an enum at the next event. With
+    // `ignore_unknown_tags`, a tag that was set aside is still the variant if
+    // the enum has one by that name.
+    fn variant_tag(
+        &self,
+        tag: &'document Option<Tag>,
+        variants: &[&str],
+    ) -> Option<&'document str> {
+        if let Some(tag) = parse_tag(tag.as_ref()) {
+            return Some(tag);
+        }
+        let tag = parse_tag(self.document.unknown_tags.get(&*self.pos))?;
+        if variants.contains(&tag) {
+            Some(tag)
+        } else {
+            None
+        }
+    }
+
+    // Deserializes the node at these segments of a path below the next node,
+    // for `from_str_at`. The nodes around it are skipped over.
+    fn deserialize_at<T>(&mut self, segments: &[Segment], path: &str) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return T::deserialize(self),
+        };
+        let (event, span) = self.peek_event_span()?;
+        match (event, segment) {
+            (Event::Alias(mut pos), _) => {
+                *self.pos += 1;
+                return self.jump(&mut pos)?.deserialize_at(segments, path);
+            }
+            (Event::MappingStart(_), Segment::Key(key)) => {
+                *self.pos += 1;
+                // The last of duplicate keys, which is the one that a map has.
+                let mut found = None;
+                loop {
+                    let skipped = self.document.skipped.contains(self.pos);
+                    let matches = match self.peek_event()? {
+                        Event::MappingEnd => break,
+                        Event::Scalar(scalar) => !skipped && *scalar.value == *key.as_bytes(),
+                        _ => false,
+                    };
+                    self.ignore_any()?;
+                    if matches {
+                        found = Some(*self.pos);
+                    }
+                    self.ignore_any()?;
+                }
+                if let Some(value) = found {
+                    *self.pos = value;
+                    let mut value_de = DeserializerFromEvents {
+                        document: self.document,
+                        pos: self.pos,
+                        jumpcount: self.jumpcount,
+                        path: Path::Map {
+                            parent: &self.path,
+                            key,
+                        },
+                        remaining_depth: self.remaining_depth,
+                        current_enum: None,
+                        resolve_identifier: false,
+                        entry_key: None,
+                    };
+                    return value_de.deserialize_at(rest, path);
+                }
+            }
+            (Event::SequenceStart(_), Segment::Index(index)) => {
+                *self.pos += 1;
+                for _ in 0..*index {
+                    if let Event::SequenceEnd = self.peek_event()? {
+                        break;
+                    }
+                    self.ignore_any()?;
+                }
+                if !matches!(self.peek_event()?, Event::SequenceEnd) {
+                    let mut element_de = DeserializerFromEvents {
+                        document: self.document,
+                        pos: self.pos,
+                        jumpcount: self.jumpcount,
+                        path: Path::Seq {
+                            parent: &self.path,
+                            index: *index,
+                        },
+                        remaining_depth: self.remaining_depth,
+                        current_enum: None,
+                        resolve_identifier: false,
+                        entry_key: None,
+                    };
+                    return element_de.deserialize_at(rest, path);
+                }
+            }
+            _ => {}
         }
+        let msg = format!("no node at path `{}`", path);
+        Err(error::fix_mark(de::Error::custom(msg), span, Path::Root))
     }
 
     fn ignore_any(&mut self) -> Result<()> {
@@ -531,15 +2089,29 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         }
     }
 
-    fn visit_sequence<V>(&mut self, visitor: V, mark: Mark) -> Result<V::Value>
+    // Gives a Spanned where the next node starts and ends, and then the node.
+    fn visit_spanned<V>(&mut self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let (value, len) = self.recursion_check(mark, |de| {
+        let (_, span) = self.peek_event_span()?;
+        visitor.visit_map(SpannedAccess {
+            de: self,
+            span,
+            field: 0,
+        })
+    }
+
+    fn visit_sequence<V>(&mut self, visitor: V, span: Span) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (value, len) = self.recursion_check(span.start, |de| {
             let mut seq = SeqAccess {
                 empty: false,
                 de,
                 len: 0,
+                skipped: 0,
             };
             let value = visitor.visit_seq(&mut seq)?;
             Ok((value, seq.len))
@@ -548,16 +2120,18 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         Ok(value)
     }
 
-    fn visit_mapping<V>(&mut self, visitor: V, mark: Mark) -> Result<V::Value>
+    fn visit_mapping<V>(&mut self, visitor: V, span: Span, resolve_keys: bool) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let (value, len) = self.recursion_check(mark, |de| {
+        let (value, len) = self.recursion_check(span.start, |de| {
             let mut map = MapAccess {
                 empty: false,
                 de,
                 len: 0,
                 key: None,
+                key_start: None,
+                resolve_keys,
             };
             let value = visitor.visit_map(&mut map)?;
             Ok((value, map.len))
@@ -566,12 +2140,122 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         Ok(value)
     }
 
+    fn deserialize_mapping<V>(&mut self, visitor: V, resolve_keys: bool) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let index = *self.pos;
+        let (next, span) = self.next_event_span()?;
+        match next {
+            Event::Alias(mut pos) => self
+                .jump(&mut pos)?
+                .deserialize_mapping(visitor, resolve_keys),
+            Event::MappingStart(_) => self.visit_mapping(visitor, span, resolve_keys),
+            other => {
+                if match other {
+                    Event::Void => true,
+                    Event::Scalar(scalar) => {
+                        scalar.value.is_empty()
+                            && scalar.style == ScalarStyle::Plain
+                            && self.document.schema.empty_null
+                    }
+                    _ => false,
+                } {
+                    visitor.visit_map(MapAccess {
+                        empty: true,
+                        de: self,
+                        len: 0,
+                        key: None,
+                        key_start: None,
+                        resolve_keys,
+                    })
+                } else {
+                    Err(invalid_type(other, &visitor, &self.document.schema))
+                }
+            }
+        }
+        .map_err(|err| {
+            if resolve_keys {
+                string_key_for(self.document, &err, index);
+            }
+            error::fix_mark(err, span, self.path)
+        })
+    }
+
+    // Floats of either width, for which `exact` tells whether an integer of
+    // that magnitude is one the float can hold without rounding.
+    fn deserialize_float<V>(&mut self, visitor: V, exact: fn(u128) -> bool) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let tagged_already = self.current_enum.is_some();
+        let (next, span) = self.next_event_span()?;
+        if self.document.strict_numbers {
+            if let Some(text) =
+                inexact_int_scalar(next, tagged_already, &self.document.schema, exact)
+            {
+                return Err(error::fix_mark(
+                    de::Error::custom(format_args!(
+                        "integer `{}` cannot be represented exactly as {}",
+                        text, &visitor as &dyn Expected,
+                    )),
+                    span,
+                    self.path,
+                ));
+            }
+        }
+        loop {
+            match next {
+                Event::Alias(mut pos) => {
+                    break self.jump(&mut pos)?.deserialize_float(visitor, exact)
+                }
+                Event::Scalar(scalar) if core_tag(scalar, tagged_already).is_some() => {
+                    break visit_scalar(visitor, scalar, false, &self.document.schema);
+                }
+                Event::Scalar(scalar)
+                    if is_plain_or_tagged_scalar(
+                        Tag::FLOAT,
+                        scalar,
+                        tagged_already,
+                        &self.document.schema,
+                    ) =>
+                {
+                    if let Ok(value) = str::from_utf8(&scalar.value) {
+                        // Resolve the same way as deserialize_any, so that a
+                        // scalar which would be an int there is accepted as a
+                        // float here, and one which would be a string is not.
+                        if scalar.tag.is_none() || tagged_already {
+                            let visitor = match visit_int(visitor, value, &self.document.schema) {
+                                Ok(result) => break result,
+                                Err(visitor) => visitor,
+                            };
+                            if digits_but_not_number(value) {
+                                break Err(invalid_type(next, &visitor, &self.document.schema));
+                            }
+                            break match parse_f64(value, &self.document.schema) {
+                                Some(float) => visitor.visit_f64(float),
+                                None => Err(invalid_type(next, &visitor, &self.document.schema)),
+                            };
+                        }
+                        if let Some(float) = parse_f64(value, &self.document.schema) {
+                            break visitor.visit_f64(float);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            break Err(invalid_type(next, &visitor, &self.document.schema));
+        }
+        .map_err(|err| error::fix_mark(err, span, self.path))
+    }
+
     fn end_sequence(&mut self, len: usize) -> Result<()> {
         let total = {
             let mut seq = SeqAccess {
                 empty: false,
                 de: self,
                 len,
+                skipped: 0,
             };
             while de::SeqAccess::next_element::<IgnoredAny>(&mut seq)?.is_some() {}
             seq.len
@@ -583,65 +2267,281 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         if total == len {
             Ok(())
         } else {
-            struct ExpectedSeq(usize);
-            impl Expected for ExpectedSeq {
-                fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                    if self.0 == 1 {
-                        write!(formatter, "sequence of 1 element")
-                    } else {
-                        write!(formatter, "sequence of {} elements", self.0)
-                    }
+            Err(de::Error::invalid_length(total, &ExpectedSeq(len)))
+        }
+    }
+
+    fn end_mapping(&mut self, len: usize) -> Result<()> {
+        let total = {
+            let mut map = MapAccess {
+                empty: false,
+                de: self,
+                len,
+                key: None,
+                key_start: None,
+                resolve_keys: false,
+            };
+            while de::MapAccess::next_entry::<IgnoredAny, IgnoredAny>(&mut map)?.is_some() {}
+            map.len
+        };
+        match self.next_event()? {
+            Event::MappingEnd | Event::Void => {}
+            _ => panic!("expected a MappingEnd event"),
+        }
+        if total == len {
+            Ok(())
+        } else {
+            Err(de::Error::invalid_length(total, &ExpectedMap(len)))
+        }
+    }
+
+    fn recursion_check<F: FnOnce(&mut Self) -> Result<T>, T>(
+        &mut self,
+        mark: Mark,
+        f: F,
+    ) -> Result<T> {
+        let previous_depth = self.remaining_depth;
+        self.remaining_depth = match previous_depth.checked_sub(1) {
+            Some(depth) => depth,
+            None => return Err(error::new(ErrorImpl::RecursionLimitExceeded(mark))),
+        };
+        let result = f(self);
+        self.remaining_depth = previous_depth;
+        result
+    }
+}
+
+// Each ignored entry is reported once, not again on every pass over the
+// document.
+fn report_ignored_keys_once(document: &mut Document) {
+    if let Some(hook) = document.on_ignored_key.take() {
+        let reported = Mutex::new(HashSet::new());
+        document.on_ignored_key = Some(IgnoredKeyHook(Arc::new(move |path, location| {
+            if reported.lock().unwrap().insert(location.index()) {
+                hook.call(path, location);
+            }
+        })));
+    }
+}
+
+// Deserializes the document with `f`, and with `again`, once more for every
+// key that an error gives to `string_keys`, until one pass succeeds or an
+// error gives no new key. The error is then that of the first pass.
+fn deserialize_document<'de, T>(
+    document: &Document<'de>,
+    again: bool,
+    f: &mut impl for<'document> FnMut(&mut DeserializerFromEvents<'de, 'document>) -> Result<T>,
+) -> Result<T> {
+    let mut first_error = None;
+    loop {
+        let string_keys = document.string_keys.borrow().len();
+        let mut pos = 0;
+        let mut jumpcount = 0;
+        let err = match f(&mut DeserializerFromEvents {
+            document,
+            pos: &mut pos,
+            jumpcount: &mut jumpcount,
+            path: Path::Root,
+            remaining_depth: document.recursion_limit,
+            current_enum: None,
+            resolve_identifier: false,
+            entry_key: None,
+        }) {
+            Ok(t) => return Ok(t),
+            Err(err) => locate_buffered_root(document, err),
+        };
+        if !again || document.string_keys.borrow().len() == string_keys {
+            return Err(first_error.unwrap_or(err));
+        }
+        first_error.get_or_insert(err);
+    }
+}
+
+// The key of a mapping deserialized as a flattened struct, which starts at
+// `index`, that the error is about, to give to the field identifier as a
+// string on the next pass. Plain scalar keys are at first resolved the way
+// the map that the struct flattens into wants them, like `1` as an integer,
+// but serde does not tell whether the key names one of the struct's own
+// fields instead, like one renamed to "1", which only matches the string. A
+// field that is missing for that reason, or a map that wants a string key,
+// points at the key.
+fn string_key_for(document: &Document, err: &Error, index: usize) {
+    let culprit = match error::culprit(err) {
+        Some(culprit) => culprit,
+        None => return,
+    };
+    if let Some((Event::MappingStart(_), _mark)) = document.events.get(index) {
+    } else {
+        return;
+    }
+    let mut pos = index + 1;
+    while let Some((event, _mark)) = document.events.get(pos) {
+        match event {
+            Event::MappingEnd => return,
+            Event::Scalar(scalar)
+                if parse_tag(scalar.tag.as_ref()).is_none() && !document.skipped.contains(&pos) =>
+            {
+                let key = scalar_key(scalar, &document.schema);
+                let about = match culprit {
+                    Culprit::Missing(field) => *scalar.value == *field.as_bytes(),
+                    Culprit::Scalar(culprit) => key == *culprit,
+                    Culprit::Key(_) => false,
+                };
+                if about
+                    && !matches!(key, ScalarKey::String(_))
+                    && document.string_keys.borrow_mut().insert(pos)
+                {
+                    return;
                 }
             }
-            Err(de::Error::invalid_length(total, &ExpectedSeq(len)))
+            _ => {}
         }
+        // Past the key and its value.
+        pos = match loader::node_end(document, pos).and_then(|pos| loader::node_end(document, pos))
+        {
+            Some(pos) => pos,
+            None => return,
+        };
     }
+}
 
-    fn end_mapping(&mut self, len: usize) -> Result<()> {
-        let total = {
-            let mut map = MapAccess {
-                empty: false,
-                de: self,
-                len,
-                key: None,
+// Gives an error from a type which buffers its input, like an internally
+// tagged enum, the location of the node it is about, among the nodes of the
+// one at `index`. Such a type reports errors after the node has been consumed
+// and without a location of their own, so they get the location of the whole
+// node if there is not exactly one node they can be about.
+fn locate_buffered(document: &Document, err: Error, index: usize, span: Span, path: Path) -> Error {
+    match find_culprit(document, &err, index, path) {
+        Some((span, path)) => error::fix_mark_at(err, span, || path),
+        None => error::fix_mark(err, span, path),
+    }
+}
+
+// The same for the top-level node, where an error that is found nowhere is
+// left without a location.
+fn locate_buffered_root(document: &Document, err: Error) -> Error {
+    match find_culprit(document, &err, 0, Path::Root) {
+        Some((span, path)) => error::fix_mark_at(err, span, || path),
+        None => err,
+    }
+}
+
+fn find_culprit(
+    document: &Document,
+    err: &Error,
+    index: usize,
+    path: Path,
+) -> Option<(Span, String)> {
+    enum Found {
+        Nothing,
+        One(Span, String),
+        Many,
+    }
+
+    impl Found {
+        fn add(&mut self, span: Span, path: &Path) {
+            *self = match self {
+                Found::Nothing => Found::One(span, path.to_string()),
+                Found::One(..) | Found::Many => Found::Many,
             };
-            while de::MapAccess::next_entry::<IgnoredAny, IgnoredAny>(&mut map)?.is_some() {}
-            map.len
-        };
-        match self.next_event()? {
-            Event::MappingEnd | Event::Void => {}
-            _ => panic!("expected a MappingEnd event"),
         }
-        if total == len {
-            Ok(())
-        } else {
-            struct ExpectedMap(usize);
-            impl Expected for ExpectedMap {
-                fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                    if self.0 == 1 {
-                        write!(formatter, "map containing 1 entry")
-                    } else {
-                        write!(formatter, "map containing {} entries", self.0)
+    }
+
+    // Walks the node at `index` and gives the index after it. The nodes
+    // which are left out are walked with no culprit. An alias whose node has
+    // the culprit in it is found as well as where the node is anchored, so
+    // that a value that is read through an alias is not taken for the one
+    // under the key of the anchor. Aliases inside of that node are not
+    // followed again.
+    fn walk(
+        document: &Document,
+        culprit: Option<&Culprit>,
+        index: usize,
+        path: Path,
+        found: &mut Found,
+        aliases: bool,
+    ) -> usize {
+        let span = match (document.events.get(index), document.ends.get(index)) {
+            (Some((_, start)), Some(end)) => Span {
+                start: *start,
+                end: *end,
+            },
+            // The document ended early with an error.
+            _ => return document.events.len(),
+        };
+        match &document.events[index].0 {
+            Event::Scalar(scalar) => {
+                if let Some(Culprit::Scalar(key)) = culprit {
+                    if scalar_key(scalar, &document.schema) == *key {
+                        found.add(span, &path);
                     }
                 }
+                index + 1
             }
-            Err(de::Error::invalid_length(total, &ExpectedMap(len)))
+            Event::SequenceStart(_) => {
+                let mut pos = index + 1;
+                let mut len = 0;
+                while let Some((event, _mark)) = document.events.get(pos) {
+                    if let Event::SequenceEnd = event {
+                        return pos + 1;
+                    }
+                    let culprit = culprit.filter(|_| !document.skipped.contains(&pos));
+                    let path = Path::Seq {
+                        parent: &path,
+                        index: len,
+                    };
+                    pos = walk(document, culprit, pos, path, found, aliases);
+                    len += 1;
+                }
+                pos
+            }
+            Event::MappingStart(_) => {
+                let mut pos = index + 1;
+                while let Some((event, _mark)) = document.events.get(pos) {
+                    let key = match event {
+                        Event::MappingEnd => return pos + 1,
+                        Event::Scalar(scalar) => str::from_utf8(&scalar.value).ok(),
+                        _ => None,
+                    };
+                    let culprit = culprit.filter(|_| !document.skipped.contains(&pos));
+                    if let (Some(Culprit::Key(name)), Some(key)) = (culprit, key) {
+                        if name == key {
+                            let span = Span {
+                                start: document.events[pos].1,
+                                end: document.ends[pos],
+                            };
+                            found.add(span, &path);
+                        }
+                    }
+                    pos = walk(document, culprit, pos, path, found, aliases);
+                    let path = match key {
+                        Some(key) => Path::Map { parent: &path, key },
+                        None => Path::Unknown { parent: &path },
+                    };
+                    pos = walk(document, culprit, pos, path, found, aliases);
+                }
+                pos
+            }
+            Event::Alias(target) => {
+                if culprit.is_some() && aliases {
+                    let mut inside = Found::Nothing;
+                    walk(document, culprit, *target, Path::Root, &mut inside, false);
+                    if let Found::One(..) | Found::Many = inside {
+                        found.add(span, &path);
+                    }
+                }
+                index + 1
+            }
+            Event::Void | Event::SequenceEnd | Event::MappingEnd => index + 1,
         }
     }
 
-    fn recursion_check<F: FnOnce(&mut Self) -> Result<T>, T>(
-        &mut self,
-        mark: Mark,
-        f: F,
-    ) -> Result<T> {
-        let previous_depth = self.remaining_depth;
-        self.remaining_depth = match previous_depth.checked_sub(1) {
-            Some(depth) => depth,
-            None => return Err(error::new(ErrorImpl::RecursionLimitExceeded(mark))),
-        };
-        let result = f(self);
-        self.remaining_depth = previous_depth;
-        result
+    let culprit = error::culprit(err)?;
+    let mut found = Found::Nothing;
+    walk(document, Some(culprit), index, path, &mut found, true);
+    match found {
+        Found::One(span, path) => Some((span, path)),
+        Found::Nothing | Found::Many => None,
     }
 }
 
@@ -649,6 +2549,8 @@ struct SeqAccess<'de, 'document, 'seq> {
     empty: bool,
     de: &'seq mut DeserializerFromEvents<'de, 'document>,
     len: usize,
+    // The items left out, which still count for the index in the path.
+    skipped: usize,
 }
 
 impl<'de, 'document, 'seq> de::SeqAccess<'de> for SeqAccess<'de, 'document, 'seq> {
@@ -661,22 +2563,34 @@ impl<'de, 'document, 'seq> de::SeqAccess<'de> for SeqAccess<'de, 'document, 'seq
         if self.empty {
             return Ok(None);
         }
-        match self.de.peek_event()? {
-            Event::SequenceEnd | Event::Void => Ok(None),
-            _ => {
+        // The items that are left out.
+        while self.de.document.skipped.contains(self.de.pos) {
+            self.de.ignore_any()?;
+            self.skipped += 1;
+        }
+        match self.de.peek_event_span()? {
+            (Event::SequenceEnd | Event::Void, _mark) => Ok(None),
+            (_, span) => {
+                let index = *self.de.pos;
                 let mut element_de = DeserializerFromEvents {
                     document: self.de.document,
                     pos: self.de.pos,
                     jumpcount: self.de.jumpcount,
                     path: Path::Seq {
                         parent: &self.de.path,
-                        index: self.len,
+                        index: self.len + self.skipped,
                     },
                     remaining_depth: self.de.remaining_depth,
                     current_enum: None,
+                    resolve_identifier: false,
+                    entry_key: None,
                 };
                 self.len += 1;
-                seed.deserialize(&mut element_de).map(Some)
+                let path = element_de.path;
+                let document = self.de.document;
+                seed.deserialize(&mut element_de)
+                    .map(Some)
+                    .map_err(|err| locate_buffered(document, err, index, span, path))
             }
         }
     }
@@ -687,6 +2601,8 @@ struct MapAccess<'de, 'document, 'map> {
     de: &'map mut DeserializerFromEvents<'de, 'document>,
     len: usize,
     key: Option<&'document [u8]>,
+    key_start: Option<Mark>,
+    resolve_keys: bool,
 }
 
 impl<'de, 'document, 'map> de::MapAccess<'de> for MapAccess<'de, 'document, 'map> {
@@ -699,12 +2615,29 @@ impl<'de, 'document, 'map> de::MapAccess<'de> for MapAccess<'de, 'document, 'map
         if self.empty {
             return Ok(None);
         }
-        match self.de.peek_event()? {
+        // The entries that are left out.
+        while self.de.document.skipped.contains(self.de.pos) {
+            self.de.ignore_any()?;
+            self.de.ignore_any()?;
+        }
+        let (event, span) = self.de.peek_event_span()?;
+        self.key_start = Some(span.start);
+        match event {
             Event::MappingEnd | Event::Void => Ok(None),
             Event::Scalar(scalar) => {
                 self.len += 1;
                 self.key = Some(&scalar.value);
-                seed.deserialize(&mut *self.de).map(Some)
+                let mut key_de = DeserializerFromEvents {
+                    document: self.de.document,
+                    pos: self.de.pos,
+                    jumpcount: self.de.jumpcount,
+                    path: self.de.path,
+                    remaining_depth: self.de.remaining_depth,
+                    current_enum: None,
+                    resolve_identifier: self.resolve_keys,
+                    entry_key: None,
+                };
+                seed.deserialize(&mut key_de).map(Some)
             }
             _ => {
                 self.len += 1;
@@ -718,6 +2651,8 @@ impl<'de, 'document, 'map> de::MapAccess<'de> for MapAccess<'de, 'document, 'map
     where
         V: DeserializeSeed<'de>,
     {
+        let (_, span) = self.de.peek_event_span()?;
+        let index = *self.de.pos;
         let mut value_de = DeserializerFromEvents {
             document: self.de.document,
             pos: self.de.pos,
@@ -734,8 +2669,50 @@ impl<'de, 'document, 'map> de::MapAccess<'de> for MapAccess<'de, 'document, 'map
             },
             remaining_depth: self.de.remaining_depth,
             current_enum: None,
+            resolve_identifier: false,
+            entry_key: self.key_start,
         };
+        let path = value_de.path;
+        let document = self.de.document;
         seed.deserialize(&mut value_de)
+            .map_err(|err| locate_buffered(document, err, index, span, path))
+    }
+}
+
+struct SpannedAccess<'de, 'document, 'spanned> {
+    de: &'spanned mut DeserializerFromEvents<'de, 'document>,
+    span: Span,
+    field: usize,
+}
+
+impl<'de, 'document, 'spanned> de::MapAccess<'de> for SpannedAccess<'de, 'document, 'spanned> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match spanned::FIELDS.get(self.field) {
+            Some(field) => seed
+                .deserialize(BorrowedStrDeserializer::new(field))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.field += 1;
+        let mark = match self.field {
+            1 => self.span.start,
+            2 => self.span.end,
+            _ => return seed.deserialize(&mut *self.de),
+        };
+        let location = Location::from_mark(mark);
+        let location = [location.index(), location.line(), location.column()];
+        seed.deserialize(SeqDeserializer::new(location.into_iter()))
     }
 }
 
@@ -765,6 +2742,8 @@ impl<'de, 'document, 'variant> de::EnumAccess<'de> for EnumAccess<'de, 'document
                 name: self.name,
                 tag: self.tag,
             }),
+            resolve_identifier: false,
+            entry_key: None,
         };
         Ok((variant, visitor))
     }
@@ -774,6 +2753,15 @@ impl<'de, 'document> de::VariantAccess<'de> for DeserializerFromEvents<'de, 'doc
     type Error = Error;
 
     fn unit_variant(mut self) -> Result<()> {
+        // A tag on an empty node, as in `!Variant` or `!Variant ''`. The
+        // quoted spelling is what the emitter produces for a tagged unit
+        // variant used as a mapping key.
+        if let Event::Scalar(scalar) = self.peek_event()? {
+            if scalar.value.is_empty() {
+                self.next_event()?;
+                return Ok(());
+            }
+        }
         Deserialize::deserialize(&mut self)
     }
 
@@ -855,7 +2843,12 @@ impl<'de, 'document, 'variant> de::VariantAccess<'de>
     }
 }
 
-fn visit_scalar<'de, V>(visitor: V, scalar: &Scalar<'de>, tagged_already: bool) -> Result<V::Value>
+fn visit_scalar<'de, V>(
+    visitor: V,
+    scalar: &Scalar<'de>,
+    tagged_already: bool,
+    schema: &CoercionFlags,
+) -> Result<V::Value>
 where
     V: Visitor<'de>,
 {
@@ -870,30 +2863,30 @@ where
     };
     if let (Some(tag), false) = (&scalar.tag, tagged_already) {
         if tag == Tag::BOOL {
-            return match parse_bool(v) {
+            return match parse_bool(v, schema) {
                 Some(v) => visitor.visit_bool(v),
                 None => Err(de::Error::invalid_value(Unexpected::Str(v), &"a boolean")),
             };
         } else if tag == Tag::INT {
-            return match visit_int(visitor, v) {
+            return match visit_int(visitor, v, schema) {
                 Ok(result) => result,
                 Err(_) => Err(de::Error::invalid_value(Unexpected::Str(v), &"an integer")),
             };
         } else if tag == Tag::FLOAT {
-            return match parse_f64(v) {
+            return match parse_f64(v, schema) {
                 Some(v) => visitor.visit_f64(v),
                 None => Err(de::Error::invalid_value(Unexpected::Str(v), &"a float")),
             };
         } else if tag == Tag::NULL {
-            return match parse_null(v.as_bytes()) {
+            return match parse_null(v.as_bytes(), schema) {
                 Some(()) => visitor.visit_unit(),
                 None => Err(de::Error::invalid_value(Unexpected::Str(v), &"null")),
             };
         } else if tag.starts_with("!") && scalar.style == ScalarStyle::Plain {
-            return visit_untagged_scalar(visitor, v, scalar.repr, scalar.style);
+            return visit_untagged_scalar(visitor, v, scalar.repr, scalar.style, schema);
         }
     } else if scalar.style == ScalarStyle::Plain {
-        return visit_untagged_scalar(visitor, v, scalar.repr, scalar.style);
+        return visit_untagged_scalar(visitor, v, scalar.repr, scalar.style, schema);
     }
     if let Some(borrowed) = parse_borrowed_str(v, scalar.repr, scalar.style) {
         visitor.visit_borrowed_str(borrowed)
@@ -908,39 +2901,134 @@ fn parse_borrowed_str<'de>(
     style: ScalarStyle,
 ) -> Option<&'de str> {
     let borrowed_repr = repr?;
-    let expected_offset = match style {
-        ScalarStyle::Plain => 0,
-        ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted => 1,
-        ScalarStyle::Literal | ScalarStyle::Folded => return None,
+    let borrow = |expected_end: usize| {
+        let expected_start = expected_end.checked_sub(utf8_value.len())?;
+        let borrowed_bytes = borrowed_repr.get(expected_start..expected_end)?;
+        if borrowed_bytes == utf8_value.as_bytes() {
+            Some(unsafe { str::from_utf8_unchecked(borrowed_bytes) })
+        } else {
+            None
+        }
     };
-    let expected_end = borrowed_repr.len().checked_sub(expected_offset)?;
-    let expected_start = expected_end.checked_sub(utf8_value.len())?;
-    let borrowed_bytes = borrowed_repr.get(expected_start..expected_end)?;
-    if borrowed_bytes == utf8_value.as_bytes() {
-        return Some(unsafe { str::from_utf8_unchecked(borrowed_bytes) });
+    match style {
+        ScalarStyle::Plain => borrow(borrowed_repr.len()),
+        ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted => {
+            borrow(borrowed_repr.len().checked_sub(1)?)
+        }
+        // The content of a block scalar of one line ends with its line, or
+        // before its line break if it is chomped, and the blank lines after
+        // it are part of the scalar too.
+        ScalarStyle::Literal | ScalarStyle::Folded => {
+            let mut expected_end = borrowed_repr.len();
+            loop {
+                if let Some(borrowed) = borrow(expected_end) {
+                    return Some(borrowed);
+                }
+                match borrowed_repr[..expected_end].last() {
+                    Some(b' ' | b'\t' | b'\r' | b'\n') => expected_end -= 1,
+                    _ => return None,
+                }
+            }
+        }
     }
-    None
 }
 
-fn parse_null(scalar: &[u8]) -> Option<()> {
+fn parse_null(scalar: &[u8], schema: &CoercionFlags) -> Option<()> {
     match scalar {
-        b"null" | b"Null" | b"NULL" | b"~" => Some(()),
+        b"null" => Some(()),
+        b"Null" | b"NULL" | b"~" if schema.null_variants => Some(()),
         _ => None,
     }
 }
 
-fn parse_bool(scalar: &str) -> Option<bool> {
+// Whether an untagged plain scalar is null.
+fn is_null(scalar: &[u8], schema: &CoercionFlags) -> bool {
+    (scalar.is_empty() && schema.empty_null) || parse_null(scalar, schema).is_some()
+}
+
+fn parse_bool(scalar: &str, schema: &CoercionFlags) -> Option<bool> {
     match scalar {
-        "true" | "True" | "TRUE" => Some(true),
-        "false" | "False" | "FALSE" => Some(false),
+        "true" => Some(true),
+        "false" => Some(false),
+        "True" | "TRUE" if schema.bool_variants => Some(true),
+        "False" | "FALSE" if schema.bool_variants => Some(false),
+        "y" | "Y" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" if schema.yaml11_bools => Some(true),
+        "n" | "N" | "no" | "No" | "NO" | "off" | "Off" | "OFF" if schema.yaml11_bools => {
+            Some(false)
+        }
         _ => None,
     }
 }
 
+// Rewrites an integer written in any of the forms allowed by the schema into
+// one that the parse_*_int functions below understand, or returns None if the
+// schema does not allow the form it is written in.
+fn normalize_int<'a>(scalar: &'a str, schema: &CoercionFlags) -> Option<Cow<'a, str>> {
+    let (sign, unsigned) = match scalar.strip_prefix(['+', '-']) {
+        Some(unsigned) => (&scalar[..1], unsigned),
+        None => ("", scalar),
+    };
+    if sign == "+" && !schema.relaxed_numbers {
+        return None;
+    }
+    if !schema.prefixed_ints
+        && unsigned.starts_with(['0'])
+        && unsigned[1..].starts_with(['x', 'o', 'b'])
+    {
+        return None;
+    }
+    let mut scalar = Cow::Borrowed(scalar);
+    if schema.underscores && unsigned.contains('_') && !unsigned.starts_with('_') {
+        scalar = Cow::Owned(scalar.replace('_', ""));
+    }
+    if schema.sexagesimal && scalar.contains(':') {
+        return sexagesimal_int(&scalar).map(|int| Cow::Owned(int.to_string()));
+    }
+    if schema.leading_zero_octal && digits_but_not_number(&scalar) {
+        let (sign, unsigned) = scalar.split_at(sign.len());
+        if unsigned.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+            return Some(Cow::Owned(format!("{}0o{}", sign, &unsigned[1..])));
+        }
+    }
+    Some(scalar)
+}
+
+// An integer in base 60 like `1:30`, with underscores already removed.
+fn sexagesimal_int(scalar: &str) -> Option<i128> {
+    let (negative, unsigned) = match scalar.strip_prefix(['+', '-']) {
+        Some(unsigned) => (scalar.starts_with('-'), unsigned),
+        None => (false, scalar),
+    };
+    let mut parts = unsigned.split(':');
+    let first = parts.next()?;
+    if !first.starts_with(|ch: char| ('1'..='9').contains(&ch)) {
+        return None;
+    }
+    let mut int = parse_sexagesimal_digits(first, u32::MAX)?;
+    for part in parts {
+        let digit = parse_sexagesimal_digits(part, 59)?;
+        int = int.checked_mul(60)?.checked_add(digit)?;
+    }
+    Some(if negative { -int } else { int })
+}
+
+fn parse_sexagesimal_digits(digits: &str, max: u32) -> Option<i128> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let int = digits.parse::<i128>().ok()?;
+    if max != u32::MAX && (digits.len() > 2 || int > i128::from(max)) {
+        return None;
+    }
+    Some(int)
+}
+
 fn parse_unsigned_int<T>(
     scalar: &str,
     from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
+    schema: &CoercionFlags,
 ) -> Option<T> {
+    let scalar = &*normalize_int(scalar, schema)?;
     let unpositive = scalar.strip_prefix('+').unwrap_or(scalar);
     if let Some(rest) = unpositive.strip_prefix("0x") {
         if rest.starts_with(['+', '-']) {
@@ -978,7 +3066,9 @@ fn parse_unsigned_int<T>(
 fn parse_signed_int<T>(
     scalar: &str,
     from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
+    schema: &CoercionFlags,
 ) -> Option<T> {
+    let scalar = &*normalize_int(scalar, schema)?;
     let unpositive = if let Some(unpositive) = scalar.strip_prefix('+') {
         if unpositive.starts_with(['+', '-']) {
             return None;
@@ -1038,7 +3128,9 @@ fn parse_signed_int<T>(
 fn parse_negative_int<T>(
     scalar: &str,
     from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
+    schema: &CoercionFlags,
 ) -> Option<T> {
+    let scalar = &*normalize_int(scalar, schema)?;
     if let Some(rest) = scalar.strip_prefix("-0x") {
         let negative = format!("-{}", rest);
         if let Ok(int) = from_str_radix(&negative, 16) {
@@ -1063,7 +3155,7 @@ fn parse_negative_int<T>(
     from_str_radix(scalar, 10).ok()
 }
 
-pub(crate) fn parse_f64(scalar: &str) -> Option<f64> {
+pub(crate) fn parse_f64(scalar: &str, schema: &CoercionFlags) -> Option<f64> {
     let unpositive = if let Some(unpositive) = scalar.strip_prefix('+') {
         if unpositive.starts_with(['+', '-']) {
             return None;
@@ -1072,93 +3164,465 @@ pub(crate) fn parse_f64(scalar: &str) -> Option<f64> {
     } else {
         scalar
     };
-    if let ".inf" | ".Inf" | ".INF" = unpositive {
-        return Some(f64::INFINITY);
+    if schema.special_floats {
+        if let ".inf" | ".Inf" | ".INF" = unpositive {
+            return Some(f64::INFINITY);
+        }
+        if let "-.inf" | "-.Inf" | "-.INF" = scalar {
+            return Some(f64::NEG_INFINITY);
+        }
+        if let ".nan" | ".NaN" | ".NAN" = scalar {
+            return Some(f64::NAN);
+        }
     }
-    if let "-.inf" | "-.Inf" | "-.INF" = scalar {
-        return Some(f64::NEG_INFINITY);
+    let mut unpositive = Cow::Borrowed(unpositive);
+    if schema.underscores && unpositive.contains('_') && !unpositive.starts_with(['_', '-']) {
+        unpositive = Cow::Owned(unpositive.replace('_', ""));
     }
-    if let ".nan" | ".NaN" | ".NAN" = scalar {
-        return Some(f64::NAN);
+    if schema.sexagesimal && unpositive.contains(':') {
+        return sexagesimal_float(&unpositive);
+    }
+    if !schema.relaxed_numbers && !is_json_number(scalar) {
+        return None;
     }
     if let Ok(float) = unpositive.parse::<f64>() {
         if float.is_finite() {
             return Some(float);
         }
     }
-    None
+    None
+}
+
+// A float in base 60 like `1:30.5`, with underscores already removed.
+fn sexagesimal_float(scalar: &str) -> Option<f64> {
+    let (int, fraction) = match scalar.split_once('.') {
+        Some((int, fraction)) => (int, fraction),
+        None => (scalar, ""),
+    };
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (negative, unsigned) = match int.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, int),
+    };
+    let mut parts = unsigned.split(':');
+    let mut float = parse_sexagesimal_digits(parts.next()?, u32::MAX)? as f64;
+    for part in parts {
+        float = float * 60.0 + parse_sexagesimal_digits(part, 59)? as f64;
+    }
+    if !fraction.is_empty() {
+        float += format!("0.{}", fraction).parse::<f64>().ok()?;
+    }
+    Some(if negative { -float } else { float })
+}
+
+// Whether a scalar is a number in the syntax of JSON.
+fn is_json_number(scalar: &str) -> bool {
+    let rest = scalar.strip_prefix('-').unwrap_or(scalar);
+    let int_len = rest.bytes().take_while(u8::is_ascii_digit).count();
+    if int_len == 0 || (int_len > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &rest[int_len..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let fraction_len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if fraction_len == 0 {
+            return false;
+        }
+        rest = &fraction[fraction_len..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        return !exponent.is_empty() && exponent.bytes().all(|b| b.is_ascii_digit());
+    }
+    rest.is_empty()
+}
+
+pub(crate) fn digits_but_not_number(scalar: &str) -> bool {
+    // Leading zero(s) followed by numeric characters is a string according to
+    // the YAML 1.2 spec. https://yaml.org/spec/1.2/spec.html#id2761292
+    let scalar = scalar.strip_prefix(['-', '+']).unwrap_or(scalar);
+    scalar.len() > 1 && scalar.starts_with('0') && scalar[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+pub(crate) fn visit_int<'de, V>(
+    visitor: V,
+    v: &str,
+    schema: &CoercionFlags,
+) -> Result<Result<V::Value>, V>
+where
+    V: Visitor<'de>,
+{
+    if let Some(int) = parse_unsigned_int(v, u64::from_str_radix, schema) {
+        return Ok(visitor.visit_u64(int));
+    }
+    if let Some(int) = parse_negative_int(v, i64::from_str_radix, schema) {
+        return Ok(visitor.visit_i64(int));
+    }
+    if let Some(int) = parse_unsigned_int(v, u128::from_str_radix, schema) {
+        return Ok(visitor.visit_u128(int));
+    }
+    if let Some(int) = parse_negative_int(v, i128::from_str_radix, schema) {
+        return Ok(visitor.visit_i128(int));
+    }
+    Err(visitor)
+}
+
+pub(crate) fn visit_untagged_scalar<'de, V>(
+    visitor: V,
+    v: &str,
+    repr: Option<&'de [u8]>,
+    style: ScalarStyle,
+    schema: &CoercionFlags,
+) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    if is_null(v.as_bytes(), schema) {
+        return visitor.visit_unit();
+    }
+    if let Some(boolean) = parse_bool(v, schema) {
+        return visitor.visit_bool(boolean);
+    }
+    let visitor = match visit_int(visitor, v, schema) {
+        Ok(result) => return result,
+        Err(visitor) => visitor,
+    };
+    if !digits_but_not_number(v) {
+        if let Some(float) = parse_f64(v, schema) {
+            return visitor.visit_f64(float);
+        }
+    }
+    if let Some(borrowed) = parse_borrowed_str(v, repr, style) {
+        visitor.visit_borrowed_str(borrowed)
+    } else {
+        visitor.visit_str(v)
+    }
+}
+
+// Whether deserialize_any sees a scalar as a string.
+pub(crate) fn is_string_scalar(scalar: &Scalar, schema: &CoercionFlags) -> bool {
+    struct IsString;
+
+    impl<'de> Visitor<'de> for IsString {
+        type Value = bool;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("any scalar")
+        }
+
+        fn visit_bool<E>(self, _v: bool) -> Result<bool, E> {
+            Ok(false)
+        }
+
+        fn visit_i64<E>(self, _v: i64) -> Result<bool, E> {
+            Ok(false)
+        }
+
+        fn visit_i128<E>(self, _v: i128) -> Result<bool, E> {
+            Ok(false)
+        }
+
+        fn visit_u64<E>(self, _v: u64) -> Result<bool, E> {
+            Ok(false)
+        }
+
+        fn visit_u128<E>(self, _v: u128) -> Result<bool, E> {
+            Ok(false)
+        }
+
+        fn visit_f64<E>(self, _v: f64) -> Result<bool, E> {
+            Ok(false)
+        }
+
+        fn visit_str<E>(self, _v: &str) -> Result<bool, E> {
+            Ok(true)
+        }
+
+        fn visit_unit<E>(self) -> Result<bool, E> {
+            Ok(false)
+        }
+    }
+
+    let v = match str::from_utf8(&scalar.value) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    match &scalar.tag {
+        Some(tag) if tag == Tag::BOOL || tag == Tag::INT || tag == Tag::FLOAT => false,
+        Some(tag) if tag == Tag::NULL => false,
+        Some(tag) if !tag.starts_with("!") => true,
+        _ if scalar.style != ScalarStyle::Plain => true,
+        _ => visit_untagged_scalar(IsString, v, None, scalar.style, schema).unwrap_or(false),
+    }
+}
+
+// What a scalar key resolves to, for telling whether two keys of a mapping
+// are the same key: `1` and `0x1` are, but `1` and `'1'` are not. A scalar
+// with a tag other than those of the core schema is compared by its tag and
+// what its content resolves to, as in a TaggedValue.
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) enum ScalarKey {
+    Null,
+    Bool(bool),
+    Unsigned(u128),
+    Negative(i128),
+    // The bits of the float, with one NaN and one zero.
+    Float(u64),
+    String(Box<str>),
+    Tagged(Box<str>, Box<ScalarKey>),
+}
+
+impl ScalarKey {
+    pub(crate) fn signed(v: i128) -> Self {
+        match u128::try_from(v) {
+            Ok(v) => ScalarKey::Unsigned(v),
+            Err(_) => ScalarKey::Negative(v),
+        }
+    }
+
+    pub(crate) fn float(v: f64) -> Self {
+        let v = if v.is_nan() {
+            f64::NAN
+        } else if v == 0.0 {
+            0.0
+        } else {
+            v
+        };
+        ScalarKey::Float(v.to_bits())
+    }
+}
+
+pub(crate) fn scalar_key(scalar: &Scalar, schema: &CoercionFlags) -> ScalarKey {
+    struct Resolve;
+
+    impl<'de> Visitor<'de> for Resolve {
+        type Value = ScalarKey;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("any scalar")
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::Bool(v))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::signed(i128::from(v)))
+        }
+
+        fn visit_i128<E>(self, v: i128) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::signed(v))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::Unsigned(u128::from(v)))
+        }
+
+        fn visit_u128<E>(self, v: u128) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::Unsigned(v))
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::float(v))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::String(Box::from(v)))
+        }
+
+        fn visit_unit<E>(self) -> Result<ScalarKey, E> {
+            Ok(ScalarKey::Null)
+        }
+    }
+
+    let v = String::from_utf8_lossy(&scalar.value);
+    let tag = match &scalar.tag {
+        None if scalar.style == ScalarStyle::Plain => {
+            return visit_untagged_scalar(Resolve, &v, None, scalar.style, schema)
+                .unwrap_or_else(|_| ScalarKey::String(Box::from(&*v)));
+        }
+        None => return ScalarKey::String(Box::from(&*v)),
+        Some(tag) => tag,
+    };
+    if core_tag(scalar, false).is_some() {
+        if let Ok(key) = visit_scalar(Resolve, scalar, false, schema) {
+            return key;
+        }
+    }
+    let untagged = Scalar {
+        anchor: None,
+        tag: None,
+        value: Box::from(v.as_bytes()),
+        style: scalar.style,
+        repr: None,
+    };
+    let tag = String::from_utf8_lossy(tag).into();
+    ScalarKey::Tagged(tag, Box::new(scalar_key(&untagged, schema)))
 }
 
-pub(crate) fn digits_but_not_number(scalar: &str) -> bool {
-    // Leading zero(s) followed by numeric characters is a string according to
-    // the YAML 1.2 spec. https://yaml.org/spec/1.2/spec.html#id2761292
-    let scalar = scalar.strip_prefix(['-', '+']).unwrap_or(scalar);
-    scalar.len() > 1 && scalar.starts_with('0') && scalar[1..].bytes().all(|b| b.is_ascii_digit())
+fn is_binary(scalar: &Scalar) -> bool {
+    matches!(&scalar.tag, Some(tag) if *tag == *Tag::BINARY)
 }
 
-pub(crate) fn visit_int<'de, V>(visitor: V, v: &str) -> Result<Result<V::Value>, V>
-where
-    V: Visitor<'de>,
-{
-    if let Some(int) = parse_unsigned_int(v, u64::from_str_radix) {
-        return Ok(visitor.visit_u64(int));
-    }
-    if let Some(int) = parse_negative_int(v, i64::from_str_radix) {
-        return Ok(visitor.visit_i64(int));
-    }
-    if let Some(int) = parse_unsigned_int(v, u128::from_str_radix) {
-        return Ok(visitor.visit_u128(int));
-    }
-    if let Some(int) = parse_negative_int(v, i128::from_str_radix) {
-        return Ok(visitor.visit_i128(int));
+// The content of a `!!binary` scalar, which is base64 with any line breaks.
+fn decode_binary(scalar: &Scalar) -> Result<Vec<u8>> {
+    let v = str::from_utf8(&scalar.value).unwrap_or_default();
+    base64::decode(v)
+        .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(v), &"base64-encoded binary data"))
+}
+
+// The tag of a scalar if it is one of the tags of the core schema, like
+// `!!str`, which decides what the scalar resolves to in place of its content.
+fn core_tag(scalar: &Scalar, tagged_already: bool) -> Option<&'static str> {
+    let tag = match (&scalar.tag, tagged_already) {
+        (Some(tag), false) => tag,
+        _ => return None,
+    };
+    [Tag::STR, Tag::INT, Tag::FLOAT, Tag::BOOL, Tag::NULL]
+        .into_iter()
+        .find(|core| tag == *core)
+}
+
+// The text and value of a scalar that is a float where an integer is
+// expected, like `1.5` or `!!float 1`. The typed int deserializers report
+// these with the float as written.
+fn float_scalar<'a>(
+    event: &'a Event,
+    tagged_already: bool,
+    schema: &CoercionFlags,
+) -> Option<(&'a str, f64)> {
+    let scalar = match event {
+        Event::Scalar(scalar) => scalar,
+        _ => return None,
+    };
+    let v = str::from_utf8(&scalar.value).ok()?;
+    match core_tag(scalar, tagged_already) {
+        Some(tag) if tag == Tag::FLOAT => return Some((v, parse_f64(v, schema)?)),
+        Some(_) => return None,
+        None => {}
+    }
+    if !is_plain_or_tagged_scalar(Tag::INT, scalar, tagged_already, schema)
+        || digits_but_not_number(v)
+        || parse_unsigned_int(v, u128::from_str_radix, schema).is_some()
+        || parse_signed_int(v, i128::from_str_radix, schema).is_some()
+    {
+        return None;
     }
-    Err(visitor)
+    Some((v, parse_f64(v, schema)?))
 }
 
-pub(crate) fn visit_untagged_scalar<'de, V>(
+// A float that is equal to an integer in range of the type is accepted with
+// `integral_floats`. Others are an error that names the float as written, or
+// if it is an integer out of range, says so.
+fn visit_float_as_int<'de, V, I>(
     visitor: V,
-    v: &str,
-    repr: Option<&'de [u8]>,
-    style: ScalarStyle,
+    text: &str,
+    float: f64,
+    integral_floats: bool,
+    convert: fn(f64) -> Option<I>,
+    visit: fn(V, I) -> Result<V::Value>,
 ) -> Result<V::Value>
 where
     V: Visitor<'de>,
 {
-    if v.is_empty() || parse_null(v.as_bytes()) == Some(()) {
-        return visitor.visit_unit();
-    }
-    if let Some(boolean) = parse_bool(v) {
-        return visitor.visit_bool(boolean);
+    // Not true of infinity or NaN.
+    if float.fract() == 0.0 {
+        match convert(float) {
+            Some(int) if integral_floats => return visit(visitor, int),
+            Some(_) => {}
+            None => {
+                return Err(de::Error::custom(format_args!(
+                    "float `{}` is out of range for {}",
+                    text, &visitor as &dyn Expected,
+                )));
+            }
+        }
     }
-    let visitor = match visit_int(visitor, v) {
-        Ok(result) => return result,
-        Err(visitor) => visitor,
+    let unexpected = format!("floating point `{}`", text);
+    Err(de::Error::invalid_type(
+        Unexpected::Other(&unexpected),
+        &visitor,
+    ))
+}
+
+// The text of a scalar that resolves to an integer, if `exact` says the float
+// being deserialized would have to round it.
+fn inexact_int_scalar<'a>(
+    event: &'a Event,
+    tagged_already: bool,
+    schema: &CoercionFlags,
+    exact: fn(u128) -> bool,
+) -> Option<&'a str> {
+    let scalar = match event {
+        Event::Scalar(scalar) => scalar,
+        _ => return None,
     };
-    if !digits_but_not_number(v) {
-        if let Some(float) = parse_f64(v) {
-            return visitor.visit_f64(float);
+    let v = str::from_utf8(&scalar.value).ok()?;
+    match core_tag(scalar, tagged_already) {
+        Some(tag) if tag == Tag::INT => {}
+        Some(_) => return None,
+        None if scalar.tag.is_none() || tagged_already => {
+            if !is_plain_or_tagged_scalar(Tag::FLOAT, scalar, tagged_already, schema) {
+                return None;
+            }
         }
+        None => return None,
     }
-    if let Some(borrowed) = parse_borrowed_str(v, repr, style) {
-        visitor.visit_borrowed_str(borrowed)
+    let magnitude = match parse_unsigned_int(v, u128::from_str_radix, schema) {
+        Some(int) => int,
+        None => parse_negative_int(v, i128::from_str_radix, schema)?.unsigned_abs(),
+    };
+    if exact(magnitude) {
+        None
     } else {
-        visitor.visit_str(v)
+        Some(v)
     }
 }
 
-fn is_plain_or_tagged_literal_scalar(
+fn is_plain_or_tagged_scalar(
     expected: &str,
     scalar: &Scalar,
     tagged_already: bool,
+    schema: &CoercionFlags,
 ) -> bool {
     match (scalar.style, &scalar.tag, tagged_already) {
         (ScalarStyle::Plain, _, _) => true,
-        (ScalarStyle::Literal, Some(tag), false) => tag == expected,
+        (_, Some(tag), false) => tag == expected,
+        (ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted, None, _) => schema.coerce_quoted,
         _ => false,
     }
 }
 
-fn invalid_type(event: &Event, exp: &dyn Expected) -> Error {
+// What a visitor that stopped after some number of elements or entries
+// expected, for the error about the ones it left over. The Value
+// deserializer reports these in the same way.
+pub(crate) struct ExpectedSeq(pub usize);
+
+impl Expected for ExpectedSeq {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == 1 {
+            write!(formatter, "sequence of 1 element")
+        } else {
+            write!(formatter, "sequence of {} elements", self.0)
+        }
+    }
+}
+
+pub(crate) struct ExpectedMap(pub usize);
+
+impl Expected for ExpectedMap {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == 1 {
+            write!(formatter, "map containing 1 entry")
+        } else {
+            write!(formatter, "map containing {} entries", self.0)
+        }
+    }
+}
+
+fn invalid_type(event: &Event, exp: &dyn Expected, schema: &CoercionFlags) -> Error {
     enum Void {}
 
     struct InvalidType<'a> {
@@ -1177,7 +3641,7 @@ fn invalid_type(event: &Event, exp: &dyn Expected) -> Error {
         Event::Alias(_) => unreachable!(),
         Event::Scalar(scalar) => {
             let get_type = InvalidType { exp };
-            match visit_scalar(get_type, scalar, false) {
+            match visit_scalar(get_type, scalar, false, schema) {
                 Ok(void) => match void {},
                 Err(invalid_type) => invalid_type,
             }
@@ -1190,8 +3654,8 @@ fn invalid_type(event: &Event, exp: &dyn Expected) -> Error {
     }
 }
 
-fn parse_tag(libyaml_tag: &Option<Tag>) -> Option<&str> {
-    let mut bytes: &[u8] = libyaml_tag.as_ref()?;
+fn parse_tag(libyaml_tag: Option<&Tag>) -> Option<&str> {
+    let mut bytes: &[u8] = libyaml_tag?;
     if let (b'!', rest) = bytes.split_first()? {
         if !rest.is_empty() {
             bytes = rest;
@@ -1210,12 +3674,12 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         V: Visitor<'de>,
     {
         let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
+        let (next, span) = self.next_event_span()?;
         fn enum_tag(tag: &Option<Tag>, tagged_already: bool) -> Option<&str> {
             if tagged_already {
                 return None;
             }
-            parse_tag(tag)
+            parse_tag(tag.as_ref())
         }
         loop {
             match next {
@@ -1229,7 +3693,33 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                             tag,
                         });
                     }
-                    break visit_scalar(visitor, scalar, tagged_already);
+                    let document = self.document;
+                    let visit = move || {
+                        if document.preserve_number_repr {
+                            #[cfg(feature = "value")]
+                            if let Ok(repr) = str::from_utf8(&scalar.value) {
+                                return number::with_parsed_repr(repr, || {
+                                    visit_scalar(visitor, scalar, tagged_already, &document.schema)
+                                });
+                            }
+                        }
+                        visit_scalar(visitor, scalar, tagged_already, &document.schema)
+                    };
+                    let style = scalar.style;
+                    let visit = move || {
+                        if document.preserve_scalar_style && style != ScalarStyle::Plain {
+                            #[cfg(feature = "value")]
+                            return styled::with_parsed_style(style, visit);
+                        }
+                        visit()
+                    };
+                    if document.preserve_core_tags {
+                        #[cfg(feature = "value")]
+                        if let Some(tag) = core_tag(scalar, tagged_already) {
+                            break tagged::with_parsed_tag(tag, visit);
+                        }
+                    }
+                    break visit();
                 }
                 Event::SequenceStart(sequence) => {
                     if let Some(tag) = enum_tag(&sequence.tag, tagged_already) {
@@ -1240,7 +3730,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                             tag,
                         });
                     }
-                    break self.visit_sequence(visitor, mark);
+                    break self.visit_sequence(visitor, span);
                 }
                 Event::MappingStart(mapping) => {
                     if let Some(tag) = enum_tag(&mapping.tag, tagged_already) {
@@ -1251,7 +3741,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                             tag,
                         });
                     }
-                    break self.visit_mapping(visitor, mark);
+                    break self.visit_mapping(visitor, span, true);
                 }
                 Event::SequenceEnd => panic!("unexpected end of sequence"),
                 Event::MappingEnd => panic!("unexpected end of mapping"),
@@ -1260,7 +3750,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         }
         // The de::Error impl creates errors with unknown line and column. Fill
         // in the position here by looking at the current index in the input.
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
@@ -1268,24 +3758,32 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         V: Visitor<'de>,
     {
         let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
+        let (next, span) = self.next_event_span()?;
         loop {
             match next {
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_bool(visitor),
+                Event::Scalar(scalar) if core_tag(scalar, tagged_already).is_some() => {
+                    break visit_scalar(visitor, scalar, false, &self.document.schema);
+                }
                 Event::Scalar(scalar)
-                    if is_plain_or_tagged_literal_scalar(Tag::BOOL, scalar, tagged_already) =>
+                    if is_plain_or_tagged_scalar(
+                        Tag::BOOL,
+                        scalar,
+                        tagged_already,
+                        &self.document.schema,
+                    ) =>
                 {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(boolean) = parse_bool(value) {
+                        if let Some(boolean) = parse_bool(value, &self.document.schema) {
                             break visitor.visit_bool(boolean);
                         }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            break Err(invalid_type(next, &visitor, &self.document.schema));
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
@@ -1314,24 +3812,54 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         V: Visitor<'de>,
     {
         let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
+        let (next, span) = self.next_event_span()?;
+        if let Some((text, float)) = float_scalar(next, tagged_already, &self.document.schema) {
+            return visit_float_as_int(
+                visitor,
+                text,
+                float,
+                self.document.integral_floats,
+                |float| {
+                    (float >= i64::MIN as f64 && float < -(i64::MIN as f64)).then_some(float as i64)
+                },
+                V::visit_i64,
+            )
+            .map_err(|err| error::fix_mark(err, span, self.path));
+        }
         loop {
             match next {
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_i64(visitor),
+                Event::Scalar(scalar) if core_tag(scalar, tagged_already).is_some() => {
+                    break visit_scalar(visitor, scalar, false, &self.document.schema);
+                }
                 Event::Scalar(scalar)
-                    if is_plain_or_tagged_literal_scalar(Tag::INT, scalar, tagged_already) =>
+                    if is_plain_or_tagged_scalar(
+                        Tag::INT,
+                        scalar,
+                        tagged_already,
+                        &self.document.schema,
+                    ) =>
                 {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_signed_int(value, i64::from_str_radix) {
+                        if let Some(int) =
+                            parse_signed_int(value, i64::from_str_radix, &self.document.schema)
+                        {
                             break visitor.visit_i64(int);
                         }
+                        // Out of range, which the visitor reports as an
+                        // invalid value, as it does for a Value.
+                        if let Some(int) =
+                            parse_unsigned_int(value, u64::from_str_radix, &self.document.schema)
+                        {
+                            break visitor.visit_u64(int);
+                        }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            break Err(invalid_type(next, &visitor, &self.document.schema));
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
@@ -1339,24 +3867,53 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         V: Visitor<'de>,
     {
         let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
+        let (next, span) = self.next_event_span()?;
+        if let Some((text, float)) = float_scalar(next, tagged_already, &self.document.schema) {
+            return visit_float_as_int(
+                visitor,
+                text,
+                float,
+                self.document.integral_floats,
+                |float| {
+                    (float >= i128::MIN as f64 && float < -(i128::MIN as f64))
+                        .then_some(float as i128)
+                },
+                V::visit_i128,
+            )
+            .map_err(|err| error::fix_mark(err, span, self.path));
+        }
         loop {
             match next {
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_i128(visitor),
+                Event::Scalar(scalar) if core_tag(scalar, tagged_already).is_some() => {
+                    break visit_scalar(visitor, scalar, false, &self.document.schema);
+                }
                 Event::Scalar(scalar)
-                    if is_plain_or_tagged_literal_scalar(Tag::INT, scalar, tagged_already) =>
+                    if is_plain_or_tagged_scalar(
+                        Tag::INT,
+                        scalar,
+                        tagged_already,
+                        &self.document.schema,
+                    ) =>
                 {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_signed_int(value, i128::from_str_radix) {
+                        if let Some(int) =
+                            parse_signed_int(value, i128::from_str_radix, &self.document.schema)
+                        {
                             break visitor.visit_i128(int);
                         }
+                        if let Some(int) =
+                            parse_unsigned_int(value, u128::from_str_radix, &self.document.schema)
+                        {
+                            break visitor.visit_u128(int);
+                        }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            break Err(invalid_type(next, &visitor, &self.document.schema));
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
@@ -1385,24 +3942,52 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         V: Visitor<'de>,
     {
         let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
+        let (next, span) = self.next_event_span()?;
+        if let Some((text, float)) = float_scalar(next, tagged_already, &self.document.schema) {
+            return visit_float_as_int(
+                visitor,
+                text,
+                float,
+                self.document.integral_floats,
+                |float| (float >= 0.0 && float < u64::MAX as f64).then_some(float as u64),
+                V::visit_u64,
+            )
+            .map_err(|err| error::fix_mark(err, span, self.path));
+        }
         loop {
             match next {
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_u64(visitor),
+                Event::Scalar(scalar) if core_tag(scalar, tagged_already).is_some() => {
+                    break visit_scalar(visitor, scalar, false, &self.document.schema);
+                }
                 Event::Scalar(scalar)
-                    if is_plain_or_tagged_literal_scalar(Tag::INT, scalar, tagged_already) =>
+                    if is_plain_or_tagged_scalar(
+                        Tag::INT,
+                        scalar,
+                        tagged_already,
+                        &self.document.schema,
+                    ) =>
                 {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_unsigned_int(value, u64::from_str_radix) {
+                        if let Some(int) =
+                            parse_unsigned_int(value, u64::from_str_radix, &self.document.schema)
+                        {
                             break visitor.visit_u64(int);
                         }
+                        // Negative, which the visitor reports as an invalid
+                        // value, as it does for a Value.
+                        if let Some(int) =
+                            parse_signed_int(value, i64::from_str_radix, &self.document.schema)
+                        {
+                            break visitor.visit_i64(int);
+                        }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            break Err(invalid_type(next, &visitor, &self.document.schema));
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
@@ -1410,56 +3995,64 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         V: Visitor<'de>,
     {
         let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
+        let (next, span) = self.next_event_span()?;
+        if let Some((text, float)) = float_scalar(next, tagged_already, &self.document.schema) {
+            return visit_float_as_int(
+                visitor,
+                text,
+                float,
+                self.document.integral_floats,
+                |float| (float >= 0.0 && float < u128::MAX as f64).then_some(float as u128),
+                V::visit_u128,
+            )
+            .map_err(|err| error::fix_mark(err, span, self.path));
+        }
         loop {
             match next {
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_u128(visitor),
+                Event::Scalar(scalar) if core_tag(scalar, tagged_already).is_some() => {
+                    break visit_scalar(visitor, scalar, false, &self.document.schema);
+                }
                 Event::Scalar(scalar)
-                    if is_plain_or_tagged_literal_scalar(Tag::INT, scalar, tagged_already) =>
+                    if is_plain_or_tagged_scalar(
+                        Tag::INT,
+                        scalar,
+                        tagged_already,
+                        &self.document.schema,
+                    ) =>
                 {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_unsigned_int(value, u128::from_str_radix) {
+                        if let Some(int) =
+                            parse_unsigned_int(value, u128::from_str_radix, &self.document.schema)
+                        {
                             break visitor.visit_u128(int);
                         }
+                        if let Some(int) =
+                            parse_signed_int(value, i64::from_str_radix, &self.document.schema)
+                        {
+                            break visitor.visit_i64(int);
+                        }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            break Err(invalid_type(next, &visitor, &self.document.schema));
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_f64(visitor)
+        self.deserialize_float(visitor, |int| int as f32 as u128 == int)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
-        loop {
-            match next {
-                Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_f64(visitor),
-                Event::Scalar(scalar)
-                    if is_plain_or_tagged_literal_scalar(Tag::FLOAT, scalar, tagged_already) =>
-                {
-                    if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(float) = parse_f64(value) {
-                            break visitor.visit_f64(float);
-                        }
-                    }
-                }
-                _ => {}
-            }
-            break Err(invalid_type(next, &visitor));
-        }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        self.deserialize_float(visitor, |int| int as f64 as u128 == int)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
@@ -1473,23 +4066,31 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
-        let (next, mark) = self.next_event_mark()?;
+        let tagged_already = self.current_enum.is_some();
+        let (next, span) = self.next_event_span()?;
         match next {
+            Event::Scalar(scalar)
+                if matches!(core_tag(scalar, tagged_already), Some(tag) if tag != Tag::STR) =>
+            {
+                visit_scalar(visitor, scalar, false, &self.document.schema)
+            }
             Event::Scalar(scalar) => {
                 if let Ok(v) = str::from_utf8(&scalar.value) {
-                    if let Some(borrowed) = parse_borrowed_str(v, scalar.repr, scalar.style) {
-                        visitor.visit_borrowed_str(borrowed)
-                    } else {
-                        visitor.visit_str(v)
-                    }
+                    styled::with_parsed_style(scalar.style, || {
+                        if let Some(borrowed) = parse_borrowed_str(v, scalar.repr, scalar.style) {
+                            visitor.visit_borrowed_str(borrowed)
+                        } else {
+                            visitor.visit_str(v)
+                        }
+                    })
                 } else {
-                    Err(invalid_type(next, &visitor))
+                    Err(invalid_type(next, &visitor, &self.document.schema))
                 }
             }
             Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_str(visitor),
-            other => Err(invalid_type(other, &visitor)),
+            other => Err(invalid_type(other, &visitor, &self.document.schema)),
         }
-        .map_err(|err: Error| error::fix_mark(err, mark, self.path))
+        .map_err(|err: Error| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -1499,18 +4100,31 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         self.deserialize_str(visitor)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(error::new(ErrorImpl::BytesUnsupported))
+        self.deserialize_byte_buf(visitor)
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    /// Decodes a `!!binary` scalar, and also accepts a sequence of bytes.
+    /// The decoded bytes are never borrowed from the input, so a `&[u8]`
+    /// can only be deserialized from a sequence if the visitor allows it.
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(error::new(ErrorImpl::BytesUnsupported))
+        let tagged_already = self.current_enum.is_some();
+        let (next, span) = self.next_event_span()?;
+        match next {
+            Event::Scalar(scalar) if !tagged_already && is_binary(scalar) => {
+                visitor.visit_byte_buf(decode_binary(scalar)?)
+            }
+            Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_byte_buf(visitor),
+            Event::SequenceStart(_) => self.visit_sequence(visitor, span),
+            other => Err(invalid_type(other, &visitor, &self.document.schema)),
+        }
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     /// Parses `null` as None and any other values as `Some(...)`.
@@ -1525,11 +4139,9 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
             }
             Event::Scalar(scalar) => {
                 let tagged_already = self.current_enum.is_some();
-                if scalar.style != ScalarStyle::Plain {
-                    true
-                } else if let (Some(tag), false) = (&scalar.tag, tagged_already) {
+                if let (Some(tag), false) = (&scalar.tag, tagged_already) {
                     if tag == Tag::NULL {
-                        if let Some(()) = parse_null(&scalar.value) {
+                        if let Some(()) = parse_null(&scalar.value, &self.document.schema) {
                             false
                         } else if let Ok(v) = str::from_utf8(&scalar.value) {
                             return Err(de::Error::invalid_value(Unexpected::Str(v), &"null"));
@@ -1542,8 +4154,10 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                     } else {
                         true
                     }
+                } else if scalar.style != ScalarStyle::Plain {
+                    true
                 } else {
-                    !scalar.value.is_empty() && parse_null(&scalar.value).is_none()
+                    !is_null(&scalar.value, &self.document.schema)
                 }
             }
             Event::SequenceStart(_) | Event::MappingStart(_) => true,
@@ -1565,18 +4179,23 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         V: Visitor<'de>,
     {
         let tagged_already = self.current_enum.is_some();
-        let (next, mark) = self.next_event_mark()?;
+        let (next, span) = self.next_event_span()?;
         match next {
+            Event::Scalar(scalar) if core_tag(scalar, tagged_already).is_some() => {
+                visit_scalar(visitor, scalar, false, &self.document.schema)
+            }
             Event::Scalar(scalar) => {
-                let is_null = if scalar.style != ScalarStyle::Plain {
+                let is_null = if let (Some(tag), false) = (&scalar.tag, tagged_already) {
+                    tag == Tag::NULL && parse_null(&scalar.value, &self.document.schema).is_some()
+                } else if scalar.style != ScalarStyle::Plain {
                     false
-                } else if let (Some(tag), false) = (&scalar.tag, tagged_already) {
-                    tag == Tag::NULL && parse_null(&scalar.value).is_some()
                 } else {
-                    scalar.value.is_empty() || parse_null(&scalar.value).is_some()
+                    is_null(&scalar.value, &self.document.schema)
                 };
                 if is_null {
                     visitor.visit_unit()
+                } else if scalar.tag.is_none() || tagged_already {
+                    Err(invalid_type(next, &visitor, &self.document.schema))
                 } else if let Ok(v) = str::from_utf8(&scalar.value) {
                     Err(de::Error::invalid_value(Unexpected::Str(v), &"null"))
                 } else {
@@ -1588,9 +4207,9 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
             }
             Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_unit(visitor),
             Event::Void => visitor.visit_unit(),
-            other => Err(invalid_type(other, &visitor)),
+            other => Err(invalid_type(other, &visitor, &self.document.schema)),
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
@@ -1605,23 +4224,32 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
-        let (_event, mark) = self.peek_event_mark()?;
-        self.recursion_check(mark, |de| visitor.visit_newtype_struct(de))
+        let (_event, span) = self.peek_event_span()?;
+        self.recursion_check(span.start, |de| visitor.visit_newtype_struct(de))
     }
 
+    /// A `!!binary` scalar is a sequence of its bytes, for a `Vec<u8>` or
+    /// an array of them.
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let (next, mark) = self.next_event_mark()?;
+        let tagged_already = self.current_enum.is_some();
+        let (next, span) = self.next_event_span()?;
         match next {
             Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_seq(visitor),
-            Event::SequenceStart(_) => self.visit_sequence(visitor, mark),
+            Event::SequenceStart(_) => self.visit_sequence(visitor, span),
+            Event::Scalar(scalar) if !tagged_already && is_binary(scalar) => {
+                let bytes = decode_binary(scalar)?;
+                de::Deserializer::deserialize_any(SeqDeserializer::new(bytes.into_iter()), visitor)
+            }
             other => {
                 if match other {
                     Event::Void => true,
                     Event::Scalar(scalar) => {
-                        scalar.value.is_empty() && scalar.style == ScalarStyle::Plain
+                        scalar.value.is_empty()
+                            && scalar.style == ScalarStyle::Plain
+                            && self.document.schema.empty_null
                     }
                     _ => false,
                 } {
@@ -1629,13 +4257,14 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                         empty: true,
                         de: self,
                         len: 0,
+                        skipped: 0,
                     })
                 } else {
-                    Err(invalid_type(other, &visitor))
+                    Err(invalid_type(other, &visitor, &self.document.schema))
                 }
             }
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
@@ -1661,42 +4290,22 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
-        let (next, mark) = self.next_event_mark()?;
-        match next {
-            Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_map(visitor),
-            Event::MappingStart(_) => self.visit_mapping(visitor, mark),
-            other => {
-                if match other {
-                    Event::Void => true,
-                    Event::Scalar(scalar) => {
-                        scalar.value.is_empty() && scalar.style == ScalarStyle::Plain
-                    }
-                    _ => false,
-                } {
-                    visitor.visit_map(MapAccess {
-                        empty: true,
-                        de: self,
-                        len: 0,
-                        key: None,
-                    })
-                } else {
-                    Err(invalid_type(other, &visitor))
-                }
-            }
-        }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        self.deserialize_mapping(visitor, true)
     }
 
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
-        _fields: &'static [&'static str],
+        name: &'static str,
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        if name == spanned::NAME && fields == spanned::FIELDS {
+            return self.visit_spanned(visitor);
+        }
+        self.deserialize_mapping(visitor, false)
     }
 
     /// Parses an enum as a single key:value pair where the key identifies the
@@ -1711,7 +4320,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
-        let (next, mark) = self.peek_event_mark()?;
+        let (next, span) = self.peek_event_span()?;
         loop {
             if let Some(current_enum) = self.current_enum {
                 if let Event::Scalar(scalar) = next {
@@ -1730,7 +4339,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                         current_enum.tag,
                     )
                 };
-                break Err(error::new(ErrorImpl::Message(message, None)));
+                break Err(error::new(ErrorImpl::Message(message, None, None)));
             }
             break match next {
                 Event::Alias(mut pos) => {
@@ -1739,7 +4348,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                         .deserialize_enum(name, variants, visitor)
                 }
                 Event::Scalar(scalar) => {
-                    if let Some(tag) = parse_tag(&scalar.tag) {
+                    if let Some(tag) = self.variant_tag(&scalar.tag, variants) {
                         return visitor.visit_enum(EnumAccess {
                             de: self,
                             name: Some(name),
@@ -1749,7 +4358,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                     visitor.visit_enum(UnitVariantAccess { de: self })
                 }
                 Event::MappingStart(mapping) => {
-                    if let Some(tag) = parse_tag(&mapping.tag) {
+                    if let Some(tag) = self.variant_tag(&mapping.tag, variants) {
                         return visitor.visit_enum(EnumAccess {
                             de: self,
                             name: Some(name),
@@ -1758,10 +4367,10 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                     }
                     let err =
                         de::Error::invalid_type(Unexpected::Map, &"a YAML tag starting with '!'");
-                    Err(error::fix_mark(err, mark, self.path))
+                    Err(error::fix_mark(err, span, self.path))
                 }
                 Event::SequenceStart(sequence) => {
-                    if let Some(tag) = parse_tag(&sequence.tag) {
+                    if let Some(tag) = self.variant_tag(&sequence.tag, variants) {
                         return visitor.visit_enum(EnumAccess {
                             de: self,
                             name: Some(name),
@@ -1770,20 +4379,34 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                     }
                     let err =
                         de::Error::invalid_type(Unexpected::Seq, &"a YAML tag starting with '!'");
-                    Err(error::fix_mark(err, mark, self.path))
+                    Err(error::fix_mark(err, span, self.path))
                 }
                 Event::SequenceEnd => panic!("unexpected end of sequence"),
                 Event::MappingEnd => panic!("unexpected end of mapping"),
                 Event::Void => Err(error::new(ErrorImpl::EndOfStream)),
             };
         }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        .map_err(|err| error::fix_mark(err, span, self.path))
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.resolve_identifier && !self.document.string_keys.borrow().contains(&*self.pos) {
+            match self.peek_event_span()? {
+                (Event::Alias(mut pos), _mark) => {
+                    *self.pos += 1;
+                    return self.jump(&mut pos)?.deserialize_identifier(visitor);
+                }
+                (Event::Scalar(scalar), span) if parse_tag(scalar.tag.as_ref()).is_none() => {
+                    *self.pos += 1;
+                    return visit_scalar(visitor, scalar, false, &self.document.schema)
+                        .map_err(|err| error::fix_mark(err, span, self.path));
+                }
+                _ => {}
+            }
+        }
         self.deserialize_str(visitor)
     }
 
@@ -1791,6 +4414,9 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
+        if let (Some(key_start), Some(hook)) = (self.entry_key, &self.document.on_ignored_key) {
+            hook.call(&self.path.to_string(), Location::from_mark(key_start));
+        }
         self.ignore_any()?;
         visitor.visit_unit()
     }
@@ -1809,7 +4435,7 @@ pub fn from_str<'de, T>(s: &'de str) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    T::deserialize(Deserializer::from_str(s))
+    Deserializer::from_str(s).deserialize_again()
 }
 
 /// Deserialize an instance of type `T` from an IO stream of YAML.
@@ -1821,12 +4447,54 @@ where
 /// is wrong with the data, for example required struct fields are missing from
 /// the YAML map or some number is too big to fit in the expected primitive
 /// type.
+///
+/// The input is read in chunks as it is parsed, but the events of the
+/// document are all kept until it has been deserialized, as they are for
+/// [`from_slice`]. These take about 150 bytes for each scalar and each start
+/// and end of a collection, besides the text of the scalars, so a document of
+/// short scalars needs 20 times the size of its text in memory or more.
 pub fn from_reader<R, T>(rdr: R) -> Result<T>
 where
     R: io::Read,
     T: DeserializeOwned,
 {
-    T::deserialize(Deserializer::from_reader(rdr))
+    Deserializer::from_reader(rdr).deserialize_again()
+}
+
+/// Deserialize an instance of type `T` from a tokio `AsyncRead`, such as the
+/// body of an HTTP request.
+///
+/// The input is read in chunks, awaiting the reader as it goes, up to where
+/// the document ends, and the document is parsed once all of it has been
+/// read. What is held in memory at once is the text of the document and the
+/// events that it is parsed into, as with [`from_slice`]. The rest of the
+/// input is then read to make sure that it has no other document in it. This
+/// fails in the same cases as [`from_reader`]. To read an input with more
+/// than one document, use [`DeserializerBuilder::async_documents`].
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Manifest {
+///     name: String,
+///     replicas: u32,
+/// }
+///
+/// async fn replicas<R>(body: R) -> serde_yaml::Result<u32>
+/// where
+///     R: tokio::io::AsyncRead + Unpin,
+/// {
+///     let manifest: Manifest = serde_yaml::from_async_reader(body).await?;
+///     Ok(manifest.replicas)
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn from_async_reader<R, T>(rdr: R) -> Result<T>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    DeserializerBuilder::new().from_async_reader(rdr).await
 }
 
 /// Deserialize an instance of type `T` from bytes of YAML text.
@@ -1838,9 +4506,273 @@ where
 /// is wrong with the data, for example required struct fields are missing from
 /// the YAML map or some number is too big to fit in the expected primitive
 /// type.
+///
+/// The document is parsed into events in full before it is deserialized,
+/// which are kept until it is done. These take about 150 bytes for each
+/// scalar and each start and end of a collection, besides the text of the
+/// scalars.
 pub fn from_slice<'de, T>(v: &'de [u8]) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    T::deserialize(Deserializer::from_slice(v))
+    Deserializer::from_slice(v).deserialize_again()
+}
+
+/// Deserialize an instance of type `T` from the node at a path in a string of
+/// YAML text, such as `spec.template.metadata`, without deserializing the
+/// rest of the document.
+///
+/// The path is written the way that errors show it: keys of mappings
+/// separated by `.`, and indices of sequences in brackets, as in
+/// `spec.containers[0].image`. A path of `.` is the whole document. Keys
+/// are matched against the text of scalar keys, so one with a `.` or `[` in
+/// it cannot be part of a path.
+///
+/// This fails in the same cases as [`from_str`], and if there is no node at
+/// the path. The nodes around the one at the path are only checked for
+/// being valid YAML, without being copied out of the input.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct Metadata {
+///     name: String,
+/// }
+///
+/// let yaml = "
+/// kind: Deployment
+/// spec:
+///   replicas: 3
+///   template:
+///     metadata:
+///       name: web
+///     containers:
+///       - image: nginx
+/// ";
+/// let metadata: Metadata = serde_yaml::from_str_at(yaml, "spec.template.metadata").unwrap();
+/// assert_eq!(metadata.name, "web");
+///
+/// let image: String = serde_yaml::from_str_at(yaml, "spec.template.containers[0].image").unwrap();
+/// assert_eq!(image, "nginx");
+///
+/// let err = serde_yaml::from_str_at::<u32>(yaml, "spec.template.replicas").unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "no node at path `spec.template.replicas` at line 6 column 5",
+/// );
+/// ```
+pub fn from_str_at<'de, T>(s: &'de str, path: &str) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let segments = Segment::parse(path);
+    Deserializer::from_str(s).de(|state| state.deserialize_at(&segments, path))
+}
+
+// A part of a path for `from_str_at`: a key of a mapping, or an index in a
+// sequence.
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+impl<'a> Segment<'a> {
+    fn parse(path: &'a str) -> Vec<Self> {
+        let mut segments = Vec::new();
+        if path == "." {
+            return segments;
+        }
+        for part in path.split('.') {
+            let mut key = part;
+            let mut indices = Vec::new();
+            while let Some(rest) = key.strip_suffix(']') {
+                match rest.rfind('[').map(|open| (open, rest[open + 1..].parse())) {
+                    Some((open, Ok(index))) => {
+                        indices.push(index);
+                        key = &rest[..open];
+                    }
+                    _ => break,
+                }
+            }
+            if !key.is_empty() || indices.is_empty() {
+                segments.push(Segment::Key(key));
+            }
+            segments.extend(indices.into_iter().rev().map(Segment::Index));
+        }
+        segments
+    }
+}
+
+/// Deserialize every document of a string of YAML text, in order.
+///
+/// This stops at the first document that fails to deserialize, with an error
+/// whose location is in the whole input rather than in that document. Input
+/// without any documents, such as an empty string or only comments, gives an
+/// empty vector.
+///
+/// ```
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct Service {
+///     name: String,
+///     port: u16,
+/// }
+///
+/// let yaml = "---\nname: web\nport: 80\n---\nname: db\nport: 5432\n";
+/// let services: Vec<Service> = serde_yaml::from_str_multi(yaml).unwrap();
+/// assert_eq!(services[1].name, "db");
+///
+/// let yaml = "---\nname: web\nport: 80\n---\nname: db\nport: lots\n";
+/// let error = serde_yaml::from_str_multi::<Service>(yaml).unwrap_err();
+/// assert_eq!(error.location().unwrap().line(), 6);
+/// ```
+pub fn from_str_multi<'de, T>(s: &'de str) -> Result<Vec<T>>
+where
+    T: Deserialize<'de>,
+{
+    let mut documents = Vec::new();
+    for document in Deserializer::from_str(s) {
+        if let Progress::Document(document) = &document.progress {
+            // The one document of a stream without any.
+            if let [(Event::Void, _)] = document.events[..] {
+                break;
+            }
+        }
+        documents.push(document.deserialize_again()?);
+    }
+    Ok(documents)
+}
+
+/// Deserialize a string of YAML text with a [`DeserializeSeed`], which can
+/// fill in a value that already exists instead of allocating a new one.
+///
+/// This fails in the same cases as [`from_str`]. As there, the result may
+/// borrow strings from the input.
+///
+/// ```
+/// use serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor};
+/// use std::fmt;
+///
+/// // Reads a sequence of numbers into a vector, reusing its allocation.
+/// struct ReadInto<'a>(&'a mut Vec<u32>);
+///
+/// impl<'de, 'a> DeserializeSeed<'de> for ReadInto<'a> {
+///     type Value = ();
+///
+///     fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+///         deserializer.deserialize_seq(self)
+///     }
+/// }
+///
+/// impl<'de, 'a> Visitor<'de> for ReadInto<'a> {
+///     type Value = ();
+///
+///     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+///         formatter.write_str("a sequence of numbers")
+///     }
+///
+///     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+///         self.0.clear();
+///         while let Some(n) = seq.next_element()? {
+///             self.0.push(n);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut ports = Vec::with_capacity(8);
+/// serde_yaml::from_str_seed(ReadInto(&mut ports), "[80, 443]").unwrap();
+/// assert_eq!(ports, [80, 443]);
+/// assert_eq!(ports.capacity(), 8);
+/// ```
+pub fn from_str_seed<'de, S>(seed: S, s: &'de str) -> Result<S::Value>
+where
+    S: DeserializeSeed<'de>,
+{
+    Deserializer::from_str(s).deserialize_seed(seed)
+}
+
+/// Deserialize bytes of YAML text with a [`DeserializeSeed`], like
+/// [`from_str_seed`].
+pub fn from_slice_seed<'de, S>(seed: S, v: &'de [u8]) -> Result<S::Value>
+where
+    S: DeserializeSeed<'de>,
+{
+    Deserializer::from_slice(v).deserialize_seed(seed)
+}
+
+/// Deserialize an IO stream of YAML with a [`DeserializeSeed`], like
+/// [`from_str_seed`].
+///
+/// Nothing can be borrowed from a reader, so the seed fails if it asks for a
+/// borrowed string, as [`from_reader`] does for `&str`.
+pub fn from_reader_seed<'de, R, S>(seed: S, rdr: R) -> Result<S::Value>
+where
+    R: io::Read + 'de,
+    S: DeserializeSeed<'de>,
+{
+    Deserializer::from_reader(rdr).deserialize_seed(seed)
+}
+
+/// Deserialize an instance of type `T` from a string of YAML text, together
+/// with the order of the keys of every mapping in it.
+///
+/// This fails in the same cases as [`from_str`]. The [`KeyOrder`] can be
+/// given to [`SerializerBuilder::key_order`] to write the value, or a changed
+/// copy of it, with its entries in the order they were read.
+///
+/// [`SerializerBuilder::key_order`]: crate::SerializerBuilder::key_order
+///
+/// ```
+/// use serde_yaml::SerializerBuilder;
+/// use std::collections::HashMap;
+///
+/// let yaml = "zone: b\napp: web\ntier: front\n";
+/// let (mut labels, order): (HashMap<String, String>, _) =
+///     serde_yaml::from_str_with_order(yaml).unwrap();
+/// labels.insert("app".to_owned(), "api".to_owned());
+///
+/// let yaml = SerializerBuilder::new()
+///     .key_order(&order)
+///     .to_string(&labels)
+///     .unwrap();
+/// assert_eq!(yaml, "zone: b\napp: api\ntier: front\n");
+/// ```
+pub fn from_str_with_order<'de, T>(s: &'de str) -> Result<(T, KeyOrder)>
+where
+    T: Deserialize<'de>,
+{
+    let mut order = KeyOrder::new();
+    let value = Deserializer::from_str(s).de(|state| {
+        order = KeyOrder::record(&state.document.events);
+        T::deserialize(state)
+    })?;
+    Ok((value, order))
+}
+
+/// Checks whether a string of YAML text can be deserialized as type `T`,
+/// without keeping the result.
+///
+/// This fails in the same cases as [`from_str`]. Content that `T` does not
+/// look at, such as unknown struct fields, is skipped over without being
+/// copied out of the input.
+///
+/// ```
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Deployment {
+///     #[allow(dead_code)]
+///     replicas: u32,
+/// }
+///
+/// assert!(serde_yaml::conforms::<Deployment>("replicas: 3\nlabels: [a, b]\n").is_ok());
+/// assert!(serde_yaml::conforms::<Deployment>("replicas: many\n").is_err());
+/// ```
+pub fn conforms<'de, T>(s: &'de str) -> Result<()>
+where
+    T: Deserialize<'de>,
+{
+    from_str::<T>(s).map(drop)
 }
@@ -0,0 +1,207 @@
+use std::io;
+
+// The encodings other than UTF-8 that a YAML stream can be in. The input is
+// transcoded to UTF-8, which is the only one that the parser is given.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum Encoding {
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Encoding {
+    // The encoding of input that starts with these bytes, by its byte order
+    // mark or else by which of the bytes of its first character are zero, as
+    // in section 5.2 of the YAML spec. None for UTF-8.
+    pub fn detect(prefix: &[u8]) -> Option<Self> {
+        match prefix {
+            [0, 0, 0xFE, 0xFF, ..] | [0, 0, 0, _, ..] => Some(Encoding::Utf32Be),
+            [0xFF, 0xFE, 0, 0, ..] | [_, 0, 0, 0, ..] => Some(Encoding::Utf32Le),
+            [0xFE, 0xFF, ..] | [0, _, ..] => Some(Encoding::Utf16Be),
+            [0xFF, 0xFE, ..] | [_, 0, ..] => Some(Encoding::Utf16Le),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf32Le => "UTF-32LE",
+            Encoding::Utf32Be => "UTF-32BE",
+        }
+    }
+
+    fn unit_len(self) -> usize {
+        match self {
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+            Encoding::Utf32Le | Encoding::Utf32Be => 4,
+        }
+    }
+
+    fn unit(self, bytes: &[u8]) -> u32 {
+        match self {
+            Encoding::Utf16Le => u32::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+            Encoding::Utf16Be => u32::from(u16::from_be_bytes([bytes[0], bytes[1]])),
+            Encoding::Utf32Le => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            Encoding::Utf32Be => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+// Transcodes input that comes in pieces, which can end in the middle of a
+// character.
+pub(crate) struct Decoder {
+    encoding: Encoding,
+    // The bytes of a character that is not complete yet.
+    pending: Vec<u8>,
+    // Where the pending bytes are in the input.
+    offset: u64,
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding) -> Self {
+        Decoder {
+            encoding,
+            pending: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    pub fn decode(&mut self, bytes: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        self.pending.extend_from_slice(bytes);
+        let unit_len = self.encoding.unit_len();
+        let mut i = 0;
+        while self.pending.len() - i >= unit_len {
+            let unit = self.encoding.unit(&self.pending[i..]);
+            let (ch, len) = if unit_len == 2 && (0xD800..0xDC00).contains(&unit) {
+                // A high surrogate, which the next unit completes.
+                if self.pending.len() - i < 4 {
+                    break;
+                }
+                let low = self.encoding.unit(&self.pending[i + 2..]);
+                if !(0xDC00..0xE000).contains(&low) {
+                    return Err(self.invalid(i));
+                }
+                (
+                    char::from_u32(0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)),
+                    4,
+                )
+            } else {
+                (char::from_u32(unit), unit_len)
+            };
+            let ch = match ch {
+                Some(ch) => ch,
+                None => return Err(self.invalid(i)),
+            };
+            // The byte order mark is not part of the text.
+            if self.offset + i as u64 != 0 || ch != '\u{FEFF}' {
+                out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            i += len;
+        }
+        self.pending.drain(..i);
+        self.offset += i as u64;
+        Ok(())
+    }
+
+    // At the end of the input, which must not be in the middle of a
+    // character.
+    pub fn finish(&self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(self.invalid(0))
+        }
+    }
+
+    fn invalid(&self, i: usize) -> io::Error {
+        let msg = format!(
+            "invalid {} at byte {} of the input",
+            self.encoding.name(),
+            self.offset + i as u64,
+        );
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+}
+
+pub(crate) fn to_utf8(input: &[u8], encoding: Encoding) -> io::Result<Vec<u8>> {
+    let mut decoder = Decoder::new(encoding);
+    let mut utf8 = Vec::with_capacity(input.len());
+    decoder.decode(input, &mut utf8)?;
+    decoder.finish()?;
+    Ok(utf8)
+}
+
+// A reader of UTF-8, from one whose encoding is found from its first bytes.
+pub(crate) struct Utf8Reader<'a> {
+    read: Box<dyn io::Read + 'a>,
+    // None until the first read, and then for UTF-8 input.
+    decoder: Option<Decoder>,
+    started: bool,
+    // What has been transcoded and not been read yet.
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<'a> Utf8Reader<'a> {
+    pub fn new(read: Box<dyn io::Read + 'a>) -> Self {
+        Utf8Reader {
+            read,
+            decoder: None,
+            started: false,
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        let mut prefix = [0; 4];
+        let mut len = 0;
+        while len < prefix.len() {
+            match self.read.read(&mut prefix[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        self.started = true;
+        self.decoder = Encoding::detect(&prefix[..len]).map(Decoder::new);
+        match &mut self.decoder {
+            Some(decoder) => decoder.decode(&prefix[..len], &mut self.buffer),
+            None => {
+                self.buffer.extend_from_slice(&prefix[..len]);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> io::Read for Utf8Reader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.started {
+            self.start()?;
+        }
+        while self.position == self.buffer.len() {
+            self.buffer.clear();
+            self.position = 0;
+            let decoder = match &mut self.decoder {
+                Some(decoder) => decoder,
+                None => return self.read.read(buf),
+            };
+            let mut chunk = [0; 8192];
+            let n = self.read.read(&mut chunk)?;
+            if n == 0 {
+                decoder.finish()?;
+                return Ok(0);
+            }
+            decoder.decode(&chunk[..n], &mut self.buffer)?;
+        }
+        let n = buf.len().min(self.buffer.len() - self.position);
+        buf[..n].copy_from_slice(&self.buffer[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
@@ -1,22 +1,51 @@
-use crate::libyaml::{emitter, error as libyaml};
+#[cfg(feature = "de")]
+use crate::de::ScalarKey;
+#[cfg(feature = "ser")]
+use crate::libyaml::emitter;
+use crate::libyaml::error as libyaml;
+#[cfg(feature = "de")]
+use crate::libyaml::error::line_break_len;
+#[cfg(feature = "de")]
 use crate::path::Path;
+#[cfg(feature = "de")]
+use serde::de::{Expected, Unexpected};
 use serde::{de, ser};
+use std::cell::RefCell;
+use std::cmp;
 use std::error::Error as StdError;
 use std::fmt::{self, Debug, Display};
 use std::io;
+#[cfg(feature = "de")]
+use std::mem;
 use std::result;
+use std::slice;
 use std::string;
 use std::sync::Arc;
 
 /// An error that happened serializing or deserializing YAML data.
 pub struct Error(Box<ErrorImpl>);
 
+thread_local! {
+    // The text and a copy of the last serde_yaml::Error displayed. A
+    // Deserialize or Serialize impl that wraps our error in its own passes it
+    // back to `custom` only as part of a message, which it is found in.
+    static DISPLAYED: RefCell<Option<(String, Error)>> = const { RefCell::new(None) };
+}
+
 /// Alias for a `Result` with the error type `serde_yaml::Error`.
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug)]
+#[cfg_attr(not(feature = "value"), allow(dead_code))]
 pub(crate) enum ErrorImpl {
-    Message(String, Option<Pos>),
+    // A message from `custom`, with the serde_yaml error that was displayed
+    // as part of it, if any.
+    Message(String, Option<Pos>, Option<Error>),
+    // A message about a scalar or key that does not have a location yet,
+    // with what it is about, to find the node by if it comes from content
+    // that was buffered, like that of an internally tagged enum.
+    #[cfg(feature = "de")]
+    Unlocated(String, Culprit),
 
     Libyaml(libyaml::Error),
     Io(io::Error),
@@ -26,27 +55,89 @@ pub(crate) enum ErrorImpl {
     MoreThanOneDocument,
     RecursionLimitExceeded(libyaml::Mark),
     RepetitionLimitExceeded,
-    BytesUnsupported,
     UnknownAnchor(libyaml::Mark),
+    DuplicateKey(String, Span),
     SerializeNestedEnum,
+    SerializeDuplicateKey(String),
+    SerializeTag(String),
+    SerializeTagCharacter(String, char),
+    SerializeCommentCharacter(char),
+    SerializeCommentNotAscii(char),
+    SerializeNotJson(String),
+    IndentedBlockScalar,
     ScalarInMerge,
     TaggedInMerge,
     ScalarInMergeElement,
     SequenceInMergeElement,
+    MergeConflict(String),
     EmptyTag,
     FailedToParseNumber,
+    DepthLimitExceeded(usize),
+
+    // An error from a Value, which has no input location, together with the
+    // path of the offending node within the Value.
+    WithPath(String, Box<ErrorImpl>),
+
+    // From a serializer with round trip verification: descriptions of the
+    // node as it was serialized and as it was read back, or the error from
+    // reading back the serialized output.
+    NotRoundTrip(String, String),
+    UnreadableOutput(Box<ErrorImpl>),
+
+    // An error from deserializing, with the part of the input around it.
+    WithExcerpt(Excerpt, Box<ErrorImpl>),
+
+    // A copy of an error that was displayed, kept as the source of an error
+    // whose message contains it: its text, location and source.
+    Snapshot(String, Option<Span>, Option<Error>),
+
+    // The errors found by a deserializer with `max_errors`, in the order
+    // they were found. There are always more than one.
+    Multiple(Vec<Error>),
 
     Shared(Arc<ErrorImpl>),
 }
 
 #[derive(Debug)]
 pub(crate) struct Pos {
-    mark: libyaml::Mark,
+    span: Span,
     path: String,
 }
 
-/// The input location that an error occured.
+// What an error about a node is about, as it is in the input.
+#[cfg(feature = "de")]
 #[derive(Debug)]
+pub(crate) enum Culprit {
+    // A scalar that resolves to this.
+    Scalar(ScalarKey),
+    // A key of a mapping with this text.
+    Key(String),
+    // A field of a struct not found among the keys of its mapping.
+    Missing(&'static str),
+}
+
+// Where the node that an error is about is in the input, from where its
+// first event starts to where its last one ends.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Span {
+    pub start: libyaml::Mark,
+    pub end: libyaml::Mark,
+}
+
+// Number of bytes of input kept with an error by `with_excerpt`.
+#[cfg(feature = "de")]
+const MAX_EXCERPT: usize = 256;
+
+// A piece of the line where an error occurred, and the byte offset of the
+// error within it.
+#[derive(Debug)]
+pub(crate) struct Excerpt {
+    text: String,
+    offset: usize,
+}
+
+/// The input location that an error occured.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Location {
     index: usize,
     line: usize,
@@ -69,9 +160,17 @@ impl Location {
         self.column
     }
 
+    pub(crate) fn new(index: usize, line: usize, column: usize) -> Self {
+        Location {
+            index,
+            line,
+            column,
+        }
+    }
+
     // This is to keep decoupled with the yaml crate
     #[doc(hidden)]
-    fn from_mark(mark: libyaml::Mark) -> Self {
+    pub(crate) fn from_mark(mark: libyaml::Mark) -> Self {
         Location {
             index: mark.index() as usize,
             // `line` and `column` returned from libyaml are 0-indexed but all error messages add +1 to this value
@@ -102,6 +201,75 @@ impl Error {
     pub fn location(&self) -> Option<Location> {
         self.0.location()
     }
+
+    /// Returns where the node that the error is about ends, just after its
+    /// last character, for underlining the whole node from
+    /// [`location`](Error::location) up to here.
+    ///
+    /// This is `None` exactly when the location is. For an error at a single
+    /// point, such as invalid syntax, it is the same as the location.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let yaml = "name: web\nport: [80, 443]\n";
+    /// let err = serde_yaml::from_str::<BTreeMap<String, u16>>(yaml).unwrap_err();
+    ///
+    /// let start = err.location().unwrap();
+    /// let end = err.end_location().unwrap();
+    /// assert_eq!((start.line(), start.column()), (1, 7));
+    /// assert_eq!(&yaml[start.index()..end.index()], "web");
+    /// ```
+    pub fn end_location(&self) -> Option<Location> {
+        self.0.end_mark().map(Location::from_mark)
+    }
+
+    /// Returns each of the errors that deserializing found, for a
+    /// deserializer with [`max_errors`] above 1. For any other error this
+    /// is the error alone.
+    ///
+    /// The other methods of an error with more than one describe the first
+    /// of them, other than `Display`, which writes each on a line of its own.
+    ///
+    /// [`max_errors`]: crate::DeserializerBuilder::max_errors
+    pub fn errors(&self) -> &[Error] {
+        match &*self.0 {
+            ErrorImpl::Multiple(errors) => errors,
+            _ => slice::from_ref(self),
+        }
+    }
+
+    /// Returns up to `max_len` bytes of the input line around the location
+    /// of the error, with the error as close to the middle as the line
+    /// allows.
+    ///
+    /// Errors keep only 256 bytes of the line, so a larger `max_len` gives
+    /// no more context. This is `None` for errors
+    /// without a location, and for errors from serializing or from a
+    /// [`Value`][crate::Value].
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// #
+    /// let yaml = format!("[{}{{a: 1]]", "1, ".repeat(10_000));
+    /// let err = serde_yaml::from_str::<Value>(&yaml).unwrap_err();
+    ///
+    /// assert_eq!(err.context_snippet(12), Some(", 1, {a: 1]]"));
+    /// ```
+    pub fn context_snippet(&self, max_len: usize) -> Option<&str> {
+        let excerpt = self.0.excerpt()?;
+        let text = &excerpt.text;
+        let start = excerpt.offset.saturating_sub(max_len / 2);
+        let mut end = cmp::min(text.len(), start.saturating_add(max_len));
+        let mut start = end.saturating_sub(max_len);
+        while !text.is_char_boundary(start) {
+            start += 1;
+        }
+        while end > start && !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Some(&text[start..end])
+    }
 }
 
 pub(crate) fn new(inner: ErrorImpl) -> Error {
@@ -112,17 +280,118 @@ pub(crate) fn shared(shared: Arc<ErrorImpl>) -> Error {
     Error(Box::new(ErrorImpl::Shared(shared)))
 }
 
-pub(crate) fn fix_mark(mut error: Error, mark: libyaml::Mark, path: Path) -> Error {
-    if let ErrorImpl::Message(_, none @ None) = error.0.as_mut() {
-        *none = Some(Pos {
-            mark,
-            path: path.to_string(),
-        });
+#[cfg(feature = "de")]
+pub(crate) fn multiple(mut errors: Vec<Error>) -> Error {
+    if errors.len() == 1 {
+        errors.remove(0)
+    } else {
+        Error(Box::new(ErrorImpl::Multiple(errors)))
+    }
+}
+
+#[cfg(feature = "de")]
+pub(crate) fn fix_mark(error: Error, span: Span, path: Path) -> Error {
+    fix_mark_at(error, span, || path.to_string())
+}
+
+#[cfg(feature = "de")]
+pub(crate) fn fix_mark_at(mut error: Error, span: Span, path: impl FnOnce() -> String) -> Error {
+    match error.0.as_mut() {
+        ErrorImpl::Message(_, none @ None, _) => {
+            *none = Some(Pos { span, path: path() });
+        }
+        ErrorImpl::Unlocated(msg, _) => {
+            let pos = Pos { span, path: path() };
+            *error.0 = ErrorImpl::Message(mem::take(msg), Some(pos), None);
+        }
+        _ => {}
     }
     error
 }
 
+// What an error that does not have a location yet is about.
+#[cfg(feature = "de")]
+pub(crate) fn culprit(error: &Error) -> Option<&Culprit> {
+    match error.0.as_ref() {
+        ErrorImpl::Unlocated(_, culprit) => Some(culprit),
+        _ => None,
+    }
+}
+
+// Adds one level of nesting, a map key or a sequence index like `[0]`, in
+// front of the path of an error that came from a node inside of a Value.
+// Errors with an input location are left unchanged.
+#[cfg(feature = "value")]
+pub(crate) fn prepend_path(mut error: Error, segment: &str) -> Error {
+    match error.0.as_mut() {
+        ErrorImpl::WithPath(path, _) => {
+            if !path.starts_with('[') {
+                path.insert(0, '.');
+            }
+            path.insert_str(0, segment);
+            error
+        }
+        ErrorImpl::Libyaml(_) | ErrorImpl::Shared(_) => error,
+        inner if inner.mark().is_some() => error,
+        _ => Error(Box::new(ErrorImpl::WithPath(segment.to_owned(), error.0))),
+    }
+}
+
+// Keeps the part of the error's line of input that is at most MAX_EXCERPT
+// bytes away from the error.
+#[cfg(feature = "de")]
+pub(crate) fn with_excerpt(error: Error, input: &[u8]) -> Error {
+    with_excerpt_at(error, input, 0)
+}
+
+// Like `with_excerpt`, for the part of the input that starts at this index.
+#[cfg(feature = "de")]
+pub(crate) fn with_excerpt_at(error: Error, input: &[u8], input_start: usize) -> Error {
+    if error.0.excerpt().is_some() {
+        return error;
+    }
+    let index = match error.0.mark() {
+        Some(mark) if mark.index() as usize >= input_start => {
+            cmp::min(mark.index() as usize - input_start, input.len())
+        }
+        _ => return error,
+    };
+    let line_start = (0..index)
+        .rev()
+        .find(|&i| line_break_len(input, i) > 0)
+        .map_or(0, |i| i + line_break_len(input, i));
+    let line_end = (index..input.len())
+        .find(|&i| line_break_len(input, i) > 0)
+        .unwrap_or(input.len());
+    let mut end = cmp::min(line_end, cmp::max(index, line_start) + MAX_EXCERPT / 2);
+    let mut start = cmp::max(line_start, end.saturating_sub(MAX_EXCERPT));
+    end = cmp::min(line_end, start + MAX_EXCERPT);
+    // Do not cut a character in half.
+    let is_continuation = |byte: u8| byte & 0xC0 == 0x80;
+    while start < index && is_continuation(input[start]) {
+        start += 1;
+    }
+    while end > index && end < input.len() && is_continuation(input[end]) {
+        end -= 1;
+    }
+    let excerpt = Excerpt {
+        text: String::from_utf8_lossy(&input[start..end]).into_owned(),
+        offset: String::from_utf8_lossy(&input[start..index]).len(),
+    };
+    Error(Box::new(ErrorImpl::WithExcerpt(excerpt, error.0)))
+}
+
+#[cfg(feature = "value")]
+pub(crate) fn unreadable_output(error: Error) -> Error {
+    Error(Box::new(ErrorImpl::UnreadableOutput(error.0)))
+}
+
 impl Error {
+    #[cfg(feature = "de")]
+    pub(crate) fn span(&self) -> Option<Span> {
+        self.0.span()
+    }
+
     pub(crate) fn shared(self) -> Arc<ErrorImpl> {
         if let ErrorImpl::Shared(err) = *self.0 {
             err
@@ -138,6 +407,7 @@ impl From<libyaml::Error> for Error {
     }
 }
 
+#[cfg(feature = "ser")]
 impl From<emitter::Error> for Error {
     fn from(err: emitter::Error) -> Self {
         match err {
@@ -155,7 +425,10 @@ impl StdError for Error {
 
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.display(f)
+        let text = self.0.to_string();
+        let snapshot = self.snapshot(text.clone());
+        DISPLAYED.with(|displayed| *displayed.borrow_mut() = Some((text.clone(), snapshot)));
+        f.write_str(&text)
     }
 }
 
@@ -169,13 +442,117 @@ impl Debug for Error {
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error(Box::new(ErrorImpl::Message(msg.to_string(), None)))
+        custom(msg)
     }
 }
 
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error(Box::new(ErrorImpl::Message(msg.to_string(), None)))
+        custom(msg)
+    }
+
+    #[cfg(feature = "de")]
+    fn invalid_type(unexp: Unexpected, exp: &dyn Expected) -> Self {
+        let msg = DefaultMessage::invalid_type(unexp, exp).0;
+        match Culprit::unexpected(unexp) {
+            Some(culprit) => Error(Box::new(ErrorImpl::Unlocated(msg, culprit))),
+            None => custom(msg),
+        }
+    }
+
+    #[cfg(feature = "de")]
+    fn invalid_value(unexp: Unexpected, exp: &dyn Expected) -> Self {
+        let msg = DefaultMessage::invalid_value(unexp, exp).0;
+        match Culprit::unexpected(unexp) {
+            Some(culprit) => Error(Box::new(ErrorImpl::Unlocated(msg, culprit))),
+            None => custom(msg),
+        }
+    }
+
+    #[cfg(feature = "de")]
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        let msg = DefaultMessage::unknown_variant(variant, expected).0;
+        let culprit = Culprit::Scalar(ScalarKey::String(Box::from(variant)));
+        Error(Box::new(ErrorImpl::Unlocated(msg, culprit)))
+    }
+
+    #[cfg(feature = "de")]
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        let msg = DefaultMessage::unknown_field(field, expected).0;
+        Error(Box::new(ErrorImpl::Unlocated(
+            msg,
+            Culprit::Key(field.to_owned()),
+        )))
+    }
+
+    #[cfg(feature = "de")]
+    fn missing_field(field: &'static str) -> Self {
+        let msg = DefaultMessage::missing_field(field).0;
+        Error(Box::new(ErrorImpl::Unlocated(msg, Culprit::Missing(field))))
+    }
+}
+
+// The messages of the methods of de::Error that are not `custom`, as serde
+// writes them.
+#[cfg(feature = "de")]
+#[derive(Debug)]
+struct DefaultMessage(String);
+
+#[cfg(feature = "de")]
+impl Display for DefaultMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(feature = "de")]
+impl StdError for DefaultMessage {}
+
+#[cfg(feature = "de")]
+impl de::Error for DefaultMessage {
+    fn custom<T: Display>(msg: T) -> Self {
+        DefaultMessage(msg.to_string())
+    }
+}
+
+#[cfg(feature = "de")]
+impl Culprit {
+    fn unexpected(unexp: Unexpected) -> Option<Self> {
+        let key = match unexp {
+            Unexpected::Bool(v) => ScalarKey::Bool(v),
+            Unexpected::Unsigned(v) => ScalarKey::Unsigned(u128::from(v)),
+            Unexpected::Signed(v) => ScalarKey::signed(i128::from(v)),
+            Unexpected::Float(v) => ScalarKey::float(v),
+            Unexpected::Char(v) => ScalarKey::String(Box::from(v.encode_utf8(&mut [0; 4]))),
+            Unexpected::Str(v) => ScalarKey::String(Box::from(v)),
+            Unexpected::Unit => ScalarKey::Null,
+            _ => return None,
+        };
+        Some(Culprit::Scalar(key))
+    }
+}
+
+fn custom<T: Display>(msg: T) -> Error {
+    let msg = msg.to_string();
+    let cause = DISPLAYED.with(|displayed| {
+        let mut displayed = displayed.borrow_mut();
+        match &*displayed {
+            Some((text, _)) if msg.contains(text.as_str()) => displayed.take().map(|(_, err)| err),
+            _ => None,
+        }
+    });
+    Error(Box::new(ErrorImpl::Message(msg, None, cause)))
+}
+
+impl Error {
+    // An error that displays as `text` and has the location of this one, to
+    // keep as the source of an error made from a message containing it.
+    fn snapshot(&self, text: String) -> Error {
+        let cause = self
+            .0
+            .cause()
+            .map(|cause| cause.snapshot(cause.0.to_string()));
+        Error(Box::new(ErrorImpl::Snapshot(text, self.0.span(), cause)))
     }
 }
 
@@ -186,29 +563,79 @@ impl ErrorImpl {
 
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
+            ErrorImpl::Message(_, _, Some(cause)) | ErrorImpl::Snapshot(_, _, Some(cause)) => {
+                Some(cause)
+            }
             ErrorImpl::Io(err) => err.source(),
             ErrorImpl::FromUtf8(err) => err.source(),
+            ErrorImpl::WithPath(_, err) | ErrorImpl::WithExcerpt(_, err) => err.source(),
             ErrorImpl::Shared(err) => err.source(),
             _ => None,
         }
     }
 
     fn mark(&self) -> Option<libyaml::Mark> {
+        self.span().map(|span| span.start)
+    }
+
+    fn end_mark(&self) -> Option<libyaml::Mark> {
+        self.span().map(|span| span.end)
+    }
+
+    fn span(&self) -> Option<Span> {
         match self {
-            ErrorImpl::Message(_, Some(Pos { mark, path: _ }))
-            | ErrorImpl::RecursionLimitExceeded(mark)
-            | ErrorImpl::UnknownAnchor(mark) => Some(*mark),
-            ErrorImpl::Libyaml(err) => Some(err.mark()),
-            ErrorImpl::Shared(err) => err.mark(),
+            ErrorImpl::Message(_, Some(Pos { span, path: _ }), _)
+            | ErrorImpl::DuplicateKey(_, span)
+            | ErrorImpl::Snapshot(_, Some(span), _) => Some(*span),
+            ErrorImpl::RecursionLimitExceeded(mark) | ErrorImpl::UnknownAnchor(mark) => {
+                Some(Span {
+                    start: *mark,
+                    end: *mark,
+                })
+            }
+            ErrorImpl::Libyaml(err) => Some(Span {
+                start: err.mark(),
+                end: err.mark(),
+            }),
+            ErrorImpl::WithPath(_, err) | ErrorImpl::WithExcerpt(_, err) => err.span(),
+            ErrorImpl::Multiple(errors) => errors[0].0.span(),
+            ErrorImpl::Shared(err) => err.span(),
+            _ => None,
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match self {
+            ErrorImpl::Message(_, _, cause) | ErrorImpl::Snapshot(_, _, cause) => cause.as_ref(),
+            ErrorImpl::WithPath(_, err) | ErrorImpl::WithExcerpt(_, err) => err.cause(),
+            ErrorImpl::Shared(err) => err.cause(),
+            _ => None,
+        }
+    }
+
+    fn excerpt(&self) -> Option<&Excerpt> {
+        match self {
+            ErrorImpl::WithExcerpt(excerpt, _) => Some(excerpt),
+            ErrorImpl::WithPath(_, err) => err.excerpt(),
+            ErrorImpl::Multiple(errors) => errors[0].0.excerpt(),
+            ErrorImpl::Shared(err) => err.excerpt(),
             _ => None,
         }
     }
 
     fn message_no_mark(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ErrorImpl::Message(msg, None) => f.write_str(msg),
-            ErrorImpl::Message(msg, Some(Pos { mark: _, path })) => {
-                if path != "." {
+            ErrorImpl::Message(msg, None, _) | ErrorImpl::Snapshot(msg, _, _) => f.write_str(msg),
+            #[cfg(feature = "de")]
+            ErrorImpl::Unlocated(msg, _) => f.write_str(msg),
+            ErrorImpl::Message(msg, Some(Pos { span: _, path }), cause) => {
+                let prefix = format!("{}: ", path);
+                // Written once if the message wraps an error at the same path.
+                let wrapped = match cause {
+                    Some(cause) => cause.0.to_string().starts_with(&prefix),
+                    None => false,
+                };
+                if path != "." && !wrapped {
                     write!(f, "{}: ", path)?;
                 }
                 f.write_str(msg)
@@ -222,13 +649,34 @@ impl ErrorImpl {
             ),
             ErrorImpl::RecursionLimitExceeded(_mark) => f.write_str("recursion limit exceeded"),
             ErrorImpl::RepetitionLimitExceeded => f.write_str("repetition limit exceeded"),
-            ErrorImpl::BytesUnsupported => {
-                f.write_str("serialization and deserialization of bytes in YAML is not implemented")
-            }
             ErrorImpl::UnknownAnchor(_mark) => f.write_str("unknown anchor"),
+            ErrorImpl::DuplicateKey(key, _span) => write!(f, "duplicate key `{}`", key),
             ErrorImpl::SerializeNestedEnum => {
                 f.write_str("serializing nested enums in YAML is not supported yet")
             }
+            ErrorImpl::SerializeDuplicateKey(key) => {
+                write!(f, "duplicate entry with {}", key)
+            }
+            ErrorImpl::SerializeTag(tag) => {
+                write!(f, "tag `{}` is not allowed when tags are disabled", tag)
+            }
+            ErrorImpl::SerializeTagCharacter(tag, ch) => write!(
+                f,
+                "tag `{}` contains character U+{:04X}, which is not allowed in YAML",
+                tag.escape_debug(),
+                *ch as u32,
+            ),
+            ErrorImpl::SerializeCommentCharacter(ch) => write!(
+                f,
+                "comment contains character U+{:04X}, which is not allowed in YAML",
+                *ch as u32,
+            ),
+            ErrorImpl::SerializeCommentNotAscii(ch) => write!(
+                f,
+                "comment contains character U+{:04X}, which cannot be written as ASCII",
+                *ch as u32,
+            ),
+            ErrorImpl::SerializeNotJson(what) => write!(f, "{} cannot be written as JSON", what),
             ErrorImpl::ScalarInMerge => {
                 f.write_str("expected a mapping or list of mappings for merging, but found scalar")
             }
@@ -239,21 +687,66 @@ impl ErrorImpl {
             ErrorImpl::SequenceInMergeElement => {
                 f.write_str("expected a mapping for merging, but found sequence")
             }
+            ErrorImpl::MergeConflict(key) => {
+                write!(f, "conflicting values for {} in merge", key)
+            }
             ErrorImpl::EmptyTag => f.write_str("empty YAML tag is not allowed"),
+            ErrorImpl::IndentedBlockScalar => f.write_str(
+                "block scalar that starts with a space or line break cannot be written with an initial indent",
+            ),
             ErrorImpl::FailedToParseNumber => f.write_str("failed to parse YAML number"),
-            ErrorImpl::Shared(_) => unreachable!(),
+            ErrorImpl::DepthLimitExceeded(limit) => {
+                write!(f, "value is nested more than {} levels deep", limit)
+            }
+            ErrorImpl::WithPath(path, err) => {
+                // Formatted the same as a `Path` from the deserializer.
+                if path.starts_with('[') {
+                    f.write_str(".")?;
+                }
+                write!(f, "{}: ", path)?;
+                err.message_no_mark(f)
+            }
+            ErrorImpl::NotRoundTrip(expected, found) => {
+                write!(f, "serialized {} but it reads back as {}", expected, found)
+            }
+            ErrorImpl::UnreadableOutput(err) => {
+                f.write_str("serialized YAML cannot be read back: ")?;
+                err.display(f)
+            }
+            ErrorImpl::WithExcerpt(..) | ErrorImpl::Multiple(_) | ErrorImpl::Shared(_) => {
+                unreachable!()
+            }
         }
     }
 
     fn display(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ErrorImpl::Libyaml(err) => Display::fmt(err, f),
+            ErrorImpl::WithExcerpt(_, err) => err.display(f),
+            ErrorImpl::Snapshot(text, _, _) => f.write_str(text),
+            ErrorImpl::Multiple(errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("\n")?;
+                    }
+                    err.0.display(f)?;
+                }
+                Ok(())
+            }
             ErrorImpl::Shared(err) => err.display(f),
             _ => {
-                self.message_no_mark(f)?;
+                let msg = MessageNoMark(self).to_string();
+                f.write_str(&msg)?;
                 if let Some(mark) = self.mark() {
-                    if mark.line() != 0 || mark.column() != 0 {
-                        write!(f, " at {}", mark)?;
+                    // Written once if the message wraps an error at the same
+                    // location.
+                    let same = |cause: &Error| {
+                        cause.0.mark().map(|cause| cause.index()) == Some(mark.index())
+                    };
+                    let at = format!(" at {}", mark);
+                    let wrapped = self.cause().map_or(false, same) && msg.contains(&at);
+                    if (mark.line() != 0 || mark.column() != 0) && !wrapped {
+                        f.write_str(&at)?;
                     }
                 }
                 Ok(())
@@ -264,15 +757,11 @@ impl ErrorImpl {
     fn debug(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ErrorImpl::Libyaml(err) => Debug::fmt(err, f),
+            ErrorImpl::WithExcerpt(_, err) => err.debug(f),
+            ErrorImpl::Multiple(errors) => f.debug_list().entries(errors).finish(),
             ErrorImpl::Shared(err) => err.debug(f),
             _ => {
                 f.write_str("Error(")?;
-                struct MessageNoMark<'a>(&'a ErrorImpl);
-                impl<'a> Display for MessageNoMark<'a> {
-                    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                        self.0.message_no_mark(f)
-                    }
-                }
                 let msg = MessageNoMark(self).to_string();
                 Debug::fmt(&msg, f)?;
                 if let Some(mark) = self.mark() {
@@ -288,3 +777,17 @@ impl ErrorImpl {
         }
     }
 }
+
+impl Display for ErrorImpl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.display(f)
+    }
+}
+
+struct MessageNoMark<'a>(&'a ErrorImpl);
+
+impl<'a> Display for MessageNoMark<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.message_no_mark(f)
+    }
+}
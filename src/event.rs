@@ -0,0 +1,343 @@
+//! The events that YAML text is parsed into and emitted from, for tools that
+//! work with how a document is written rather than with the values in it,
+//! such as linters and converters.
+//!
+//! [`Events`] parses YAML text into a stream of [`Event`]s, each with where
+//! it is in the input, and [`Emitter`] writes a stream of them back out as
+//! YAML text. Unlike the [`Deserializer`](crate::Deserializer), the events
+//! keep anchors, aliases, tags and scalar styles as they are written, and
+//! nothing is resolved: a scalar is its text, whatever type it would be.
+//!
+//! ```
+//! use serde_yaml::event::{Emitter, Event, Events};
+//!
+//! let yaml = "name: &name web\nhosts: [*name, 'db']\n";
+//!
+//! let mut output = Vec::new();
+//! let mut emitter = Emitter::new(&mut output);
+//! for event in Events::from_str(yaml) {
+//!     let mut event = event.unwrap().value;
+//!     if let Event::Scalar(scalar) = &mut event {
+//!         scalar.value = scalar.value.to_uppercase();
+//!     }
+//!     emitter.emit(event).unwrap();
+//! }
+//! emitter.flush().unwrap();
+//! drop(emitter);
+//!
+//! let expected = "NAME: &name WEB\nHOSTS: [*name, 'DB']\n";
+//! assert_eq!(String::from_utf8(output).unwrap(), expected);
+//! ```
+
+use crate::encoding::{self, Encoding, Utf8Reader};
+use crate::error::{self, Error, ErrorImpl, Location, Result};
+use crate::libyaml::parser::{self, Parser, ScalarStyle};
+use crate::spanned::Spanned;
+use std::borrow::Cow;
+use std::io;
+#[cfg(feature = "ser")]
+use {crate::libyaml::emitter, serde::ser::Error as _};
+
+/// An event of a YAML stream.
+///
+/// A stream is a [`StreamStart`](Event::StreamStart), then each document
+/// from its [`DocumentStart`](Event::DocumentStart) to its
+/// [`DocumentEnd`](Event::DocumentEnd), and a
+/// [`StreamEnd`](Event::StreamEnd). A document has one node, which is an
+/// alias, a scalar, or a sequence or mapping with the nodes in it between its
+/// start and end events. The nodes of a mapping alternate between keys and
+/// values.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// The start of the stream.
+    StreamStart,
+    /// The end of the stream.
+    StreamEnd,
+    /// The start of a document, with or without a `---` marker.
+    DocumentStart,
+    /// The end of a document, with or without a `...` marker.
+    DocumentEnd,
+    /// An alias to the node with this anchor, without the `*`.
+    Alias(String),
+    /// A scalar.
+    Scalar(Scalar),
+    /// The start of a sequence.
+    SequenceStart(SequenceStart),
+    /// The end of a sequence.
+    SequenceEnd,
+    /// The start of a mapping.
+    MappingStart(MappingStart),
+    /// The end of a mapping.
+    MappingEnd,
+}
+
+/// A scalar node.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Scalar {
+    /// The anchor of the node, without the `&`.
+    pub anchor: Option<String>,
+    /// The tag of the node. A tag with a handle is given in full, so `!!str`
+    /// is `tag:yaml.org,2002:str`, and a local tag like `!Ref` as written.
+    pub tag: Option<String>,
+    /// The content of the scalar, without its quotes and escapes.
+    pub value: String,
+    /// How the scalar is written. The emitter writes it in another style if
+    /// its content cannot be written in this one.
+    pub style: ScalarStyle,
+}
+
+/// The start of a sequence node.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SequenceStart {
+    /// The anchor of the node, without the `&`.
+    pub anchor: Option<String>,
+    /// The tag of the node, as for a [`Scalar`].
+    pub tag: Option<String>,
+    /// Whether the sequence is written in brackets rather than as a block.
+    pub flow: bool,
+}
+
+/// The start of a mapping node.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MappingStart {
+    /// The anchor of the node, without the `&`.
+    pub anchor: Option<String>,
+    /// The tag of the node, as for a [`Scalar`].
+    pub tag: Option<String>,
+    /// Whether the mapping is written in braces rather than as a block.
+    pub flow: bool,
+}
+
+/// An iterator over the events of YAML text, each with the location where it
+/// starts and ends in the input.
+///
+/// The iterator ends after the [`StreamEnd`](Event::StreamEnd) event, or
+/// after an error if the input is not valid YAML.
+pub struct Events<'input> {
+    parser: Parser<'input>,
+    // An error before the first event, from transcoding the input.
+    error: Option<Error>,
+    done: bool,
+}
+
+impl<'input> Events<'input> {
+    /// The events of a string of YAML text.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'input str) -> Self {
+        Self::new(Parser::new(Cow::Borrowed(s.as_bytes())))
+    }
+
+    /// The events of bytes of YAML text, which can be in UTF-16 or UTF-32 as
+    /// well as UTF-8.
+    pub fn from_slice(v: &'input [u8]) -> Self {
+        match Encoding::detect(v) {
+            None => Self::new(Parser::new(Cow::Borrowed(v))),
+            Some(encoding) => match encoding::to_utf8(v, encoding) {
+                Ok(utf8) => Self::new(Parser::new(Cow::Owned(utf8))),
+                Err(io_error) => Events {
+                    error: Some(error::new(ErrorImpl::Io(io_error))),
+                    ..Self::new(Parser::new(Cow::Borrowed(b"")))
+                },
+            },
+        }
+    }
+
+    /// The events of an IO stream of YAML. What has been read of the stream
+    /// is kept until the end of the document it is in.
+    pub fn from_reader<R>(rdr: R) -> Self
+    where
+        R: io::Read + 'input,
+    {
+        Self::new(Parser::from_reader(Box::new(Utf8Reader::new(Box::new(
+            rdr,
+        )))))
+    }
+
+    fn new(parser: Parser<'input>) -> Self {
+        Events {
+            parser,
+            error: None,
+            done: false,
+        }
+    }
+}
+
+impl<'input> Iterator for Events<'input> {
+    type Item = Result<Spanned<Event>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(err) = self.error.take() {
+            self.done = true;
+            return Some(Err(err));
+        }
+        let (event, mark) = match self.parser.next() {
+            Ok(next) => next,
+            Err(err) => {
+                self.done = true;
+                let err = match self.parser.take_read_error() {
+                    Some(io_error) => error::new(ErrorImpl::Io(io_error)),
+                    None => error::with_excerpt_at(
+                        Error::from(err),
+                        self.parser.input(),
+                        self.parser.input_start(),
+                    ),
+                };
+                return Some(Err(err));
+            }
+        };
+        let start = Location::from_mark(mark);
+        let end = Location::from_mark(self.parser.end_mark());
+        let event = match event {
+            parser::Event::StreamStart => Event::StreamStart,
+            parser::Event::StreamEnd => {
+                self.done = true;
+                Event::StreamEnd
+            }
+            parser::Event::DocumentStart => Event::DocumentStart,
+            parser::Event::DocumentEnd => {
+                self.parser.forget_input();
+                Event::DocumentEnd
+            }
+            parser::Event::Alias(anchor) => Event::Alias(text(&anchor)),
+            parser::Event::Scalar(scalar) => Event::Scalar(Scalar {
+                anchor: scalar.anchor.as_deref().map(text),
+                tag: scalar.tag.as_deref().map(text),
+                value: text(&scalar.value),
+                style: scalar.style,
+            }),
+            parser::Event::SequenceStart(start) => Event::SequenceStart(SequenceStart {
+                anchor: start.anchor.as_deref().map(text),
+                tag: start.tag.as_deref().map(text),
+                flow: start.flow,
+            }),
+            parser::Event::SequenceEnd => Event::SequenceEnd,
+            parser::Event::MappingStart(start) => Event::MappingStart(MappingStart {
+                anchor: start.anchor.as_deref().map(text),
+                tag: start.tag.as_deref().map(text),
+                flow: start.flow,
+            }),
+            parser::Event::MappingEnd => Event::MappingEnd,
+        };
+        Some(Ok(Spanned {
+            start,
+            end,
+            value: event,
+        }))
+    }
+}
+
+// The parser only gives valid UTF-8.
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Writes a stream of [`Event`]s as YAML text.
+///
+/// The events must make up a whole stream, as the [`Events`] of some input
+/// do, or emitting the one that is out of place fails. The output is written
+/// as the emitter goes, and is complete with the
+/// [`StreamEnd`](Event::StreamEnd) event or a [`flush`](Emitter::flush).
+///
+/// Documents after the first start with `---`, and a scalar is written in
+/// its style where its content allows it, in the same way as by the
+/// [`Serializer`](crate::Serializer). A tag like `tag:yaml.org,2002:str` is
+/// written as `!!str`.
+#[cfg(feature = "ser")]
+pub struct Emitter<'a> {
+    emitter: emitter::Emitter<'a>,
+}
+
+#[cfg(feature = "ser")]
+impl<'a> Emitter<'a> {
+    /// An emitter that writes to a writer, such as a `&mut Vec<u8>`.
+    pub fn new<W>(writer: W) -> Self
+    where
+        W: io::Write + 'a,
+    {
+        Emitter {
+            emitter: emitter::Emitter::new(Box::new(writer)),
+        }
+    }
+
+    /// Writes the next event of the stream.
+    pub fn emit(&mut self, event: Event) -> Result<()> {
+        let event = match event {
+            Event::StreamStart => emitter::Event::StreamStart,
+            Event::StreamEnd => {
+                self.emitter.emit(emitter::Event::StreamEnd)?;
+                return self.flush();
+            }
+            Event::DocumentStart => emitter::Event::DocumentStart,
+            Event::DocumentEnd => emitter::Event::DocumentEnd,
+            Event::Alias(anchor) => emitter::Event::Alias(check_anchor(anchor)?),
+            Event::Scalar(scalar) => {
+                let anchor = scalar.anchor.map(check_anchor).transpose()?;
+                self.emitter.set_scalar_anchor(anchor);
+                return self.emit_scalar(scalar.tag, &scalar.value, scalar.style);
+            }
+            Event::SequenceStart(start) => emitter::Event::SequenceStart(emitter::Sequence {
+                anchor: start.anchor.map(check_anchor).transpose()?,
+                tag: start.tag.map(check_tag).transpose()?,
+                style: collection_style(start.flow),
+            }),
+            Event::SequenceEnd => emitter::Event::SequenceEnd,
+            Event::MappingStart(start) => emitter::Event::MappingStart(emitter::Mapping {
+                anchor: start.anchor.map(check_anchor).transpose()?,
+                tag: start.tag.map(check_tag).transpose()?,
+                style: collection_style(start.flow),
+            }),
+            Event::MappingEnd => emitter::Event::MappingEnd,
+        };
+        Ok(self.emitter.emit(event)?)
+    }
+
+    fn emit_scalar(&mut self, tag: Option<String>, value: &str, style: ScalarStyle) -> Result<()> {
+        let scalar = emitter::Scalar {
+            tag: tag.map(check_tag).transpose()?,
+            value,
+            style: match style {
+                ScalarStyle::Plain => emitter::ScalarStyle::Plain,
+                ScalarStyle::SingleQuoted => emitter::ScalarStyle::SingleQuoted,
+                ScalarStyle::DoubleQuoted => emitter::ScalarStyle::DoubleQuoted,
+                ScalarStyle::Literal => emitter::ScalarStyle::Literal,
+                ScalarStyle::Folded => emitter::ScalarStyle::Folded,
+            },
+        };
+        Ok(self.emitter.emit(emitter::Event::Scalar(scalar))?)
+    }
+
+    /// Writes out what has been emitted so far, and flushes the writer.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.emitter.flush_writer()?)
+    }
+}
+
+#[cfg(feature = "ser")]
+fn collection_style(flow: bool) -> emitter::CollectionStyle {
+    if flow {
+        emitter::CollectionStyle::Flow
+    } else {
+        emitter::CollectionStyle::Any
+    }
+}
+
+// The emitter is given anchors and tags that end at a NUL.
+#[cfg(feature = "ser")]
+fn check_anchor(anchor: String) -> Result<String> {
+    if anchor.contains('\0') {
+        let msg = format!("anchor `{}` contains a NUL", anchor.escape_debug());
+        return Err(Error::custom(msg));
+    }
+    Ok(anchor)
+}
+
+#[cfg(feature = "ser")]
+fn check_tag(tag: String) -> Result<String> {
+    if tag.contains('\0') {
+        return Err(error::new(ErrorImpl::SerializeTagCharacter(tag, '\0')));
+    }
+    Ok(tag)
+}
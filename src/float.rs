@@ -0,0 +1,89 @@
+use std::borrow::Cow;
+use std::fmt::{Display, LowerExp};
+use std::str::FromStr;
+
+/// How finite floats are written by the serializer, selected with
+/// [`SerializerBuilder::float_format`].
+///
+/// [`SerializerBuilder::float_format`]: crate::SerializerBuilder::float_format
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// The shortest decimal that reads back as the same float, as in
+    /// `0.30000000000000004` for `0.1 + 0.2`. This is the default.
+    Shortest,
+    /// Rounded to this many digits after the decimal point, as in `0.30` for
+    /// 2, and never with an exponent. With 0 digits, `.0` is still written
+    /// so that the value reads back as a float rather than an integer.
+    Fixed(usize),
+    /// Rounded to at most this many significant digits, as in `0.3` for
+    /// `0.1 + 0.2` and 15 digits. Zeros at the end are left out. A count of
+    /// 0 is taken as 1.
+    Significant(usize),
+}
+
+// The text of a finite float. `shortest` is the formatting of the emitter
+// compat version, which is used as is unless an option changes the digits
+// or where the exponent starts.
+pub(crate) fn format<'a, F>(
+    float: F,
+    format: FloatFormat,
+    scientific_threshold: Option<u32>,
+    shortest: fn(F, &'a mut ryu::Buffer) -> &'a str,
+    buffer: &'a mut ryu::Buffer,
+) -> Cow<'a, str>
+where
+    F: Copy + Display + LowerExp + FromStr,
+{
+    let exponential = match format {
+        FloatFormat::Fixed(decimals) => {
+            let mut text = format!("{:.*}", decimals, float);
+            if decimals == 0 {
+                text.push_str(".0");
+            }
+            return Cow::Owned(text);
+        }
+        FloatFormat::Shortest if scientific_threshold.is_none() => {
+            return Cow::Borrowed(shortest(float, buffer));
+        }
+        FloatFormat::Shortest => format!("{:e}", float),
+        FloatFormat::Significant(digits) => {
+            format!("{:.*e}", digits.max(1) - 1, float)
+        }
+    };
+    let (mantissa, exponent) = exponential.split_once('e').unwrap();
+    let mantissa = if mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    let exponent: i32 = exponent.parse().unwrap();
+    let threshold = match scientific_threshold {
+        Some(threshold) => threshold,
+        None => {
+            // Rounded to a float that the compat version then writes.
+            let rounded = format!("{}e{}", mantissa, exponent).parse();
+            return Cow::Borrowed(shortest(rounded.unwrap_or(float), buffer));
+        }
+    };
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    if mantissa != "0" && exponent.unsigned_abs() >= threshold {
+        return Cow::Owned(format!("{}{}e{}", sign, mantissa, exponent));
+    }
+    let digits = mantissa.replace('.', "");
+    let text = if exponent < 0 {
+        let zeros = "0".repeat(exponent.unsigned_abs() as usize - 1);
+        format!("{}0.{}{}", sign, zeros, digits)
+    } else {
+        let point = exponent as usize + 1;
+        if digits.len() > point {
+            format!("{}{}.{}", sign, &digits[..point], &digits[point..])
+        } else {
+            let zeros = "0".repeat(point - digits.len());
+            format!("{}{}{}.0", sign, digits, zeros)
+        }
+    };
+    Cow::Owned(text)
+}
@@ -0,0 +1,487 @@
+use crate::comments::Comments;
+use crate::libyaml::parser::{EventKind, Parser};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::io;
+use std::mem;
+use std::rc::Rc;
+use std::str;
+
+// Writes the spaces of the initial indent at the start of every line that is
+// not empty, for output that is pasted into another document below a key.
+// With `trailing_newline(false)`, also holds back the line break at the end
+// of the output until something comes after it.
+pub(crate) struct IndentWriter<W> {
+    pub writer: W,
+    prefix: Vec<u8>,
+    // Whether the next byte written starts a line.
+    line_start: bool,
+    // For a bare first line, set by the serializer if the root of the
+    // document is a collection. A block collection cannot start on the line
+    // of the key, so the output then starts with a line break instead, unless
+    // it starts with a tag or anchor or is written in flow style.
+    first_line: Option<Rc<Cell<bool>>>,
+    line_break: &'static [u8],
+    trailing_newline: bool,
+    held: Vec<u8>,
+}
+
+impl<W> IndentWriter<W> {
+    pub fn new(
+        writer: W,
+        spaces: usize,
+        bare_first_line: Option<Rc<Cell<bool>>>,
+        crlf: bool,
+        trailing_newline: bool,
+    ) -> Self {
+        IndentWriter {
+            writer,
+            prefix: vec![b' '; spaces],
+            line_start: bare_first_line.is_none(),
+            first_line: bare_first_line,
+            line_break: if crlf { b"\r\n" } else { b"\n" },
+            trailing_newline,
+            held: Vec::new(),
+        }
+    }
+}
+
+impl<W> IndentWriter<W>
+where
+    W: io::Write,
+{
+    fn write_lines(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.prefix.is_empty() {
+            return self.writer.write_all(buf);
+        }
+        let mut rest = buf;
+        while !rest.is_empty() {
+            let end = match rest.iter().position(|&b| b == b'\n') {
+                Some(newline) => newline + 1,
+                None => rest.len(),
+            };
+            let line = &rest[..end];
+            if self.line_start && line[0] != b'\n' && line[0] != b'\r' {
+                self.writer.write_all(&self.prefix)?;
+            }
+            self.writer.write_all(line)?;
+            self.line_start = line.ends_with(b"\n");
+            rest = &rest[end..];
+        }
+        Ok(())
+    }
+}
+
+impl<W> io::Write for IndentWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(root_collection) = self.first_line.take() {
+            if root_collection.get() && !matches!(buf[0], b'!' | b'&' | b'[' | b'{') {
+                self.writer.write_all(self.line_break)?;
+                self.line_start = true;
+            }
+        }
+        if !self.held.is_empty() {
+            let held = mem::take(&mut self.held);
+            self.write_lines(&held)?;
+        }
+        let mut keep = buf.len();
+        if !self.trailing_newline {
+            if buf.ends_with(b"\r\n") {
+                keep -= 2;
+            } else if buf.ends_with(b"\n") {
+                keep -= 1;
+            }
+        }
+        self.write_lines(&buf[..keep])?;
+        self.held.extend_from_slice(&buf[keep..]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// With `indent_sequences(true)` or `align_values(true)`, holds back each
+// document until it is flushed, and then indents the block sequences that
+// are the value of a mapping entry, which the emitter writes at the
+// indentation of their key, or lines up the values of each block mapping.
+// With `json(true)`, rewrites the output of the emitter into JSON as it is
+// written, which is all in flow style.
+pub(crate) struct LayoutWriter<W> {
+    pub writer: W,
+    // The spaces added for each such sequence, if they are indented.
+    indent: Option<usize>,
+    align: bool,
+    json: Option<JsonText>,
+    // For the placeholders of comments, which are not lined up.
+    comments: Comments,
+    document: Vec<u8>,
+}
+
+impl<W> LayoutWriter<W>
+where
+    W: io::Write,
+{
+    pub fn new(
+        writer: W,
+        indent: Option<usize>,
+        align: bool,
+        json: bool,
+        comments: Comments,
+    ) -> Self {
+        LayoutWriter {
+            writer,
+            indent,
+            align,
+            json: if json { Some(JsonText::new()) } else { None },
+            comments,
+            document: Vec::new(),
+        }
+    }
+
+    // Whether everything is written through as is.
+    fn is_passthrough(&self) -> bool {
+        self.indent.is_none() && !self.align
+    }
+
+    // The rest of the output, once the emitter is done.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.is_passthrough() {
+            return Ok(());
+        }
+        let document = std::mem::take(&mut self.document);
+        let layout = relayout(&document, self.indent, self.align, &self.comments);
+        self.writer.write_all(&layout)
+    }
+}
+
+impl<W> io::Write for LayoutWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(json) = &mut self.json {
+            let mut text = Vec::with_capacity(buf.len());
+            json.rewrite(buf, &mut text);
+            if self.is_passthrough() {
+                self.writer.write_all(&text)?;
+            } else {
+                self.document.extend_from_slice(&text);
+            }
+            return Ok(buf.len());
+        }
+        if self.is_passthrough() {
+            return self.writer.write(buf);
+        }
+        self.document.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.finish()?;
+        self.writer.flush()
+    }
+}
+
+// Turns the flow style output of the emitter into JSON. Outside of strings it
+// only differs in the `? ` before a key that the emitter does not write as a
+// simple key, one that is longer than 128 bytes or has a line break, and the
+// space before the `:` after such a key. Inside of strings, the escapes that
+// JSON does not have, like `\0`, `\xE9` or `\N`, are written as `\u`
+// escapes.
+struct JsonText {
+    in_string: bool,
+    // The part of an escape that has been written so far, after the `\`.
+    escape: Option<Vec<u8>>,
+    // Whether a space outside of a string was held back, for a `:` after it.
+    space: bool,
+    // Whether the `?` of an explicit key was left out, and the space after
+    // it is too.
+    explicit_key: bool,
+}
+
+impl JsonText {
+    fn new() -> Self {
+        JsonText {
+            in_string: false,
+            escape: None,
+            space: false,
+            explicit_key: false,
+        }
+    }
+
+    fn rewrite(&mut self, buf: &[u8], out: &mut Vec<u8>) {
+        for &b in buf {
+            if let Some(escape) = &mut self.escape {
+                escape.push(b);
+                let len = match escape[0] {
+                    b'x' => 3,
+                    b'u' => 5,
+                    b'U' => 9,
+                    _ => 1,
+                };
+                if escape.len() == len {
+                    let escape = self.escape.take().unwrap();
+                    write_json_escape(&escape, out);
+                }
+            } else if self.in_string {
+                match b {
+                    b'\\' => self.escape = Some(Vec::new()),
+                    b'"' => {
+                        self.in_string = false;
+                        out.push(b);
+                    }
+                    _ => out.push(b),
+                }
+            } else if b == b'?' {
+                self.explicit_key = true;
+            } else if b == b' ' && mem::take(&mut self.explicit_key) {
+            } else {
+                if mem::take(&mut self.space) && b != b':' {
+                    out.push(b' ');
+                }
+                match b {
+                    b' ' => self.space = true,
+                    b'"' => {
+                        self.in_string = true;
+                        out.push(b);
+                    }
+                    _ => out.push(b),
+                }
+            }
+        }
+    }
+}
+
+// Writes an escape of a double-quoted YAML scalar, without its `\`, as one of
+// JSON.
+fn write_json_escape(escape: &[u8], out: &mut Vec<u8>) {
+    let ch = match escape[0] {
+        b'0' => 0x0,
+        b'a' => 0x7,
+        b'v' => 0xB,
+        b'e' => 0x1B,
+        b'N' => 0x85,
+        b'_' => 0xA0,
+        b'L' => 0x2028,
+        b'P' => 0x2029,
+        b'x' | b'u' => {
+            let hex = str::from_utf8(&escape[1..]).unwrap_or_default();
+            u32::from_str_radix(hex, 16).unwrap_or_default()
+        }
+        // `\"`, `\\`, `\b`, `\f`, `\n`, `\r` and `\t` are the same in JSON.
+        _ => {
+            out.push(b'\\');
+            out.extend_from_slice(escape);
+            return;
+        }
+    };
+    out.extend_from_slice(format!("\\u{:04X}", ch).as_bytes());
+}
+
+// The sequences and mapping entries are found by parsing the output again.
+// Each line of such a sequence is indented, including the lines of the nodes
+// inside of it, so that these keep their indentation relative to the
+// sequence. A sequence whose first `-` is not at the start of its line is
+// left as it is. A value is lined up with the others of its mapping if it
+// starts on the line of its key and is not a block collection.
+fn relayout<'a>(
+    document: &'a [u8],
+    indent: Option<usize>,
+    align: bool,
+    comments: &Comments,
+) -> Cow<'a, [u8]> {
+    struct Parent {
+        block_mapping: bool,
+        children: usize,
+        // The line of the first `-` of a sequence to be indented.
+        indented_from: Option<usize>,
+        // The line of the key of the current entry.
+        key_line: Option<usize>,
+        // Where each value on the line of its key starts, and its column.
+        values: Vec<(usize, usize)>,
+    }
+
+    let mut parser = Parser::new(Cow::Borrowed(document));
+    let mut parents: Vec<Parent> = Vec::new();
+    let mut line_indents: Vec<usize> = Vec::new();
+    // Spaces inserted before a value, by where it starts.
+    let mut paddings: Vec<(usize, usize)> = Vec::new();
+    loop {
+        let (event, mark) = match parser.next_kind() {
+            Ok(next) => next,
+            Err(_) => return Cow::Borrowed(document),
+        };
+        let mut is_value = false;
+        if let EventKind::Scalar(..)
+        | EventKind::Alias(_)
+        | EventKind::SequenceStart(_)
+        | EventKind::MappingStart(_) = event
+        {
+            if let Some(parent) = parents.last_mut() {
+                is_value = parent.block_mapping && parent.children % 2 == 1;
+                if align && parent.block_mapping {
+                    let index = mark.index() as usize;
+                    let start =
+                        index + document[index..].iter().take_while(|&&b| b == b' ').count();
+                    let line = mark.line() as usize;
+                    // A block collection only starts on the line of its key
+                    // with its tag, and its entries are below it.
+                    let is_block = match event {
+                        EventKind::SequenceStart(_) | EventKind::MappingStart(_) => {
+                            !matches!(document[node_start(document, index)], b'[' | b'{')
+                        }
+                        _ => false,
+                    };
+                    let is_comment = match event {
+                        EventKind::Scalar(_, len) => is_comment(document, index, len, comments),
+                        _ => false,
+                    };
+                    if !is_value {
+                        let is_scalar =
+                            matches!(event, EventKind::Scalar(..) | EventKind::Alias(_));
+                        parent.key_line = if is_scalar && !is_comment {
+                            Some(line)
+                        } else {
+                            None
+                        };
+                    } else if parent.key_line == Some(line)
+                        && !is_comment
+                        && !is_block
+                        && start < document.len()
+                    {
+                        let line_start = document[..start]
+                            .iter()
+                            .rposition(|&b| b == b'\n')
+                            .map_or(0, |i| i + 1);
+                        let column = String::from_utf8_lossy(&document[line_start..start])
+                            .chars()
+                            .count();
+                        parent.values.push((start, column));
+                    }
+                }
+                parent.children += 1;
+            }
+        }
+        match event {
+            EventKind::SequenceStart(_) => {
+                let index = mark.index() as usize;
+                let start = node_start(document, index);
+                let line_start = document[..start].iter().rposition(|&b| b == b'\n');
+                let first_in_line = document[line_start.map_or(0, |i| i + 1)..start]
+                    .iter()
+                    .all(|&b| b == b' ');
+                let indented_from =
+                    if indent.is_some() && is_value && document[start] == b'-' && first_in_line {
+                        let newlines = document[index..start].iter().filter(|&&b| b == b'\n');
+                        Some(mark.line() as usize + newlines.count())
+                    } else {
+                        None
+                    };
+                parents.push(Parent {
+                    block_mapping: false,
+                    children: 0,
+                    indented_from,
+                    key_line: None,
+                    values: Vec::new(),
+                });
+            }
+            EventKind::MappingStart(_) => {
+                let start = node_start(document, mark.index() as usize);
+                parents.push(Parent {
+                    block_mapping: document[start] != b'{',
+                    children: 0,
+                    indented_from: None,
+                    key_line: None,
+                    values: Vec::new(),
+                });
+            }
+            EventKind::SequenceEnd | EventKind::MappingEnd => {
+                let parent = match parents.pop() {
+                    Some(parent) => parent,
+                    None => continue,
+                };
+                if let (Some(from), Some(indent)) = (parent.indented_from, indent) {
+                    let to = mark.line() as usize;
+                    if line_indents.len() < to {
+                        line_indents.resize(to, 0);
+                    }
+                    for spaces in &mut line_indents[from..to] {
+                        *spaces += indent;
+                    }
+                }
+                if let Some(max) = parent.values.iter().map(|&(_, column)| column).max() {
+                    for (start, column) in parent.values {
+                        if column < max {
+                            paddings.push((start, max - column));
+                        }
+                    }
+                }
+            }
+            EventKind::StreamEnd => break,
+            _ => {}
+        }
+    }
+    if line_indents.iter().all(|&spaces| spaces == 0) && paddings.is_empty() {
+        return Cow::Borrowed(document);
+    }
+    paddings.sort_unstable();
+    let mut paddings = paddings.into_iter().peekable();
+    let mut indented = Vec::with_capacity(document.len() + document.len() / 4);
+    let mut offset = 0;
+    for (i, line) in document.split_inclusive(|&b| b == b'\n').enumerate() {
+        // Empty lines, which can be part of a block scalar, stay empty.
+        if !matches!(line, b"\n" | b"\r\n") {
+            let spaces = line_indents.get(i).copied().unwrap_or(0);
+            indented.resize(indented.len() + spaces, b' ');
+        }
+        let mut copied = 0;
+        while let Some(&(start, spaces)) = paddings.peek() {
+            if start >= offset + line.len() {
+                break;
+            }
+            indented.extend_from_slice(&line[copied..start - offset]);
+            indented.resize(indented.len() + spaces, b' ');
+            copied = start - offset;
+            paddings.next();
+        }
+        indented.extend_from_slice(&line[copied..]);
+        offset += line.len();
+    }
+    Cow::Owned(indented)
+}
+
+// Whether the scalar at this index is the placeholder of a comment, as the
+// key or the value of its entry.
+fn is_comment(document: &[u8], index: usize, len: usize, comments: &Comments) -> bool {
+    let start = node_start(document, index);
+    match document.get(start..start + len).map(str::from_utf8) {
+        Some(Ok(text)) => comments.is_placeholder(text) || comments.is_above(text),
+        _ => false,
+    }
+}
+
+// Where the content of the node at this index starts, after its anchor and
+// its tag if it has them.
+fn node_start(document: &[u8], index: usize) -> usize {
+    let is_blank = |b: &u8| matches!(b, b' ' | b'\t' | b'\r' | b'\n');
+    let mut start = index;
+    loop {
+        while start < document.len() && is_blank(&document[start]) {
+            start += 1;
+        }
+        if !matches!(document.get(start), Some(b'!' | b'&')) {
+            return start.min(document.len() - 1);
+        }
+        while start < document.len() && !is_blank(&document[start]) {
+            start += 1;
+        }
+    }
+}
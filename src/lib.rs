@@ -34,6 +34,20 @@
 //! }
 //! ```
 //!
+//! # Features
+//!
+//! All of these are enabled by default.
+//!
+//! - `de` — deserializing from YAML text with `from_str` and friends.
+//! - `ser` — serializing to YAML text with `to_string` and friends. Implies
+//!   `de`.
+//! - `value` — the dynamically typed `Value`, `Mapping` and `Number`,
+//!   and the `with` helpers. Implies `de` and `ser`.
+//!
+//! A build with `default-features = false, features = ["de"]` contains only
+//! the deserializer and the error types, for programs which deserialize into
+//! fixed types.
+//!
 //! ## Using Serde derive
 //!
 //! It can also be used with Serde's derive macros to handle structs and enums
@@ -114,9 +128,23 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ## Optional values
+//!
+//! `None` serializes as `null`, and `Some(value)` serializes exactly the same
+//! as `value` would. This holds at the root of a document too, so
+//! `to_string(&None::<T>)` is `"null\n"`. When deserializing an `Option`, an
+//! empty document, `null` and `~` are all read as `None`, and anything else
+//! is deserialized as `Some` of the inner type.
+//!
+//! Because YAML has only one null, an `Option<()>`, `Option<Option<T>>` or
+//! `Option<Value>` holding `Some` of a null-like value reads back as `None`.
+//! For struct fields of type `Option<Option<T>>` the two can be kept apart with
+//! `with::nested_option`, which leaves out the field for `None`.
 
 #![doc(html_root_url = "https://docs.rs/serde_yaml/0.9.24")]
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "de"), allow(dead_code, unused_imports))]
 // Suppressed clippy_pedantic lints
 #![allow(
     // buggy
@@ -161,27 +189,104 @@
     clippy::must_use_candidate,
 )]
 
-pub use crate::de::{from_reader, from_slice, from_str, Deserializer};
+#[cfg(feature = "ser")]
+pub use crate::comments::Commented;
+#[cfg(feature = "ser")]
+pub use crate::compat::EmitterCompat;
+#[cfg(feature = "de")]
+pub use crate::de::{
+    conforms, from_reader, from_reader_seed, from_slice, from_slice_seed, from_str, from_str_at,
+    from_str_multi, from_str_seed, from_str_with_order, Deserializer, DeserializerBuilder,
+    DuplicateKeyPolicy,
+};
+#[cfg(feature = "tokio")]
+pub use crate::de::{from_async_reader, AsyncDocuments};
 pub use crate::error::{Error, Location, Result};
-pub use crate::ser::{to_string, to_writer, Serializer};
+#[cfg(feature = "ser")]
+pub use crate::float::FloatFormat;
+#[cfg(feature = "de")]
+pub use crate::libyaml::parser::ScalarStyle;
+#[cfg(feature = "de")]
+pub use crate::lint::lint;
+#[cfg(feature = "de")]
+pub use crate::order::KeyOrder;
+#[cfg(feature = "de")]
+pub use crate::peek::{peek_root, NodeKind, RootInfo};
+#[cfg(feature = "de")]
+pub use crate::schema::{CoercionFlags, SchemaProfile};
+#[cfg(feature = "ser")]
+pub use crate::ser::{
+    to_fmt_writer, to_string, to_string_multi, to_writer, to_writer_multi, NodeAction, NodeRef,
+    NullStyle, PathSegment, QuoteStyle, ScalarAction, ScalarRef, SerPath, Serializer,
+    SerializerBuilder, SerializerProfile, UnitVariants,
+};
+#[cfg(feature = "de")]
+pub use crate::spanned::Spanned;
+#[cfg(feature = "de")]
+pub use crate::validate::{is_valid, validate, DocumentStats};
+#[cfg(feature = "value")]
 #[doc(inline)]
 pub use crate::value::{from_value, to_value, Index, Number, Sequence, Value};
 
+#[cfg(feature = "value")]
 #[doc(inline)]
 pub use crate::mapping::Mapping;
 
-mod de;
+#[cfg(feature = "de")]
+mod base64;
+#[cfg(feature = "ser")]
+mod comments;
+#[cfg(feature = "ser")]
+mod compat;
+#[cfg(feature = "de")]
+pub mod de;
+#[cfg(feature = "de")]
+mod encoding;
 mod error;
+#[cfg(feature = "de")]
+pub mod event;
+#[cfg(feature = "ser")]
+mod float;
+#[cfg(feature = "ser")]
+mod indent;
 mod libyaml;
+#[cfg(feature = "de")]
+pub mod lint;
+#[cfg(feature = "de")]
 mod loader;
+#[cfg(feature = "value")]
 pub mod mapping;
+#[cfg(feature = "value")]
 mod number;
+#[cfg(feature = "de")]
+mod order;
+#[cfg(feature = "de")]
 mod path;
+#[cfg(feature = "de")]
+mod peek;
+#[cfg(feature = "ser")]
+pub mod scalar;
+#[cfg(feature = "de")]
+mod schema;
+#[cfg(feature = "ser")]
 mod ser;
+#[cfg(feature = "ser")]
+mod shared;
+#[cfg(feature = "de")]
+mod spanned;
+#[cfg(feature = "de")]
+mod styled;
+#[cfg(any(feature = "chrono", feature = "time"))]
+mod timestamp;
+#[cfg(feature = "de")]
+mod validate;
+#[cfg(feature = "value")]
 pub mod value;
+#[cfg(feature = "value")]
 pub mod with;
 
 // Prevent downstream code from implementing the Index trait.
+#[cfg(feature = "value")]
 mod private {
     pub trait Sealed {}
     impl Sealed for usize {}
@@ -15,6 +15,14 @@ pub(crate) enum Error {
 
 pub(crate) struct Emitter<'a> {
     pin: Owned<EmitterPinned<'a>>,
+    explicit_document_start: bool,
+    explicit_document_end: bool,
+    version_directive: Option<(i32, i32)>,
+    // Handles and prefixes, each with a NUL at the end.
+    tag_directives: Vec<(String, String)>,
+    // The anchor of the next scalar. Only those from the public event
+    // emitter have one; the serializer anchors sequences and mappings.
+    scalar_anchor: Option<String>,
 }
 
 struct EmitterPinned<'a> {
@@ -29,6 +37,7 @@ pub(crate) enum Event<'a> {
     StreamEnd,
     DocumentStart,
     DocumentEnd,
+    Alias(String),
     Scalar(Scalar<'a>),
     SequenceStart(Sequence),
     SequenceEnd,
@@ -43,22 +52,34 @@ pub(crate) struct Scalar<'a> {
     pub style: ScalarStyle,
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 pub(crate) enum ScalarStyle {
     Any,
     Plain,
     SingleQuoted,
+    DoubleQuoted,
     Literal,
+    Folded,
+}
+
+#[derive(Debug)]
+pub(crate) enum CollectionStyle {
+    Any,
+    Flow,
 }
 
 #[derive(Debug)]
 pub(crate) struct Sequence {
+    pub anchor: Option<String>,
     pub tag: Option<String>,
+    pub style: CollectionStyle,
 }
 
 #[derive(Debug)]
 pub(crate) struct Mapping {
+    pub anchor: Option<String>,
     pub tag: Option<String>,
+    pub style: CollectionStyle,
 }
 
 impl<'a> Emitter<'a> {
@@ -76,7 +97,69 @@ impl<'a> Emitter<'a> {
             sys::yaml_emitter_set_output(emitter, write_handler, owned.ptr.cast());
             Owned::assume_init(owned)
         };
-        Emitter { pin }
+        Emitter {
+            pin,
+            explicit_document_start: false,
+            explicit_document_end: false,
+            version_directive: None,
+            tag_directives: Vec::new(),
+            scalar_anchor: None,
+        }
+    }
+
+    pub fn set_indent(&mut self, indent: usize) {
+        let indent = i32::try_from(indent).unwrap_or(i32::MAX);
+        unsafe { sys::yaml_emitter_set_indent(addr_of_mut!((*self.pin.ptr).sys), indent) }
+    }
+
+    pub fn set_width(&mut self, width: i32) {
+        unsafe { sys::yaml_emitter_set_width(addr_of_mut!((*self.pin.ptr).sys), width) }
+    }
+
+    pub fn set_unicode(&mut self, unicode: bool) {
+        unsafe { sys::yaml_emitter_set_unicode(addr_of_mut!((*self.pin.ptr).sys), unicode) }
+    }
+
+    pub fn set_canonical(&mut self, canonical: bool) {
+        unsafe { sys::yaml_emitter_set_canonical(addr_of_mut!((*self.pin.ptr).sys), canonical) }
+    }
+
+    pub fn set_crlf(&mut self, crlf: bool) {
+        let line_break = if crlf {
+            sys::YAML_CRLN_BREAK
+        } else {
+            sys::YAML_LN_BREAK
+        };
+        unsafe { sys::yaml_emitter_set_break(addr_of_mut!((*self.pin.ptr).sys), line_break) }
+    }
+
+    // Writes `---` at the start of every document, not only the ones after
+    // the first.
+    pub fn set_explicit_document_start(&mut self, explicit: bool) {
+        self.explicit_document_start = explicit;
+    }
+
+    // Writes `...` at the end of every document.
+    pub fn set_explicit_document_end(&mut self, explicit: bool) {
+        self.explicit_document_end = explicit;
+    }
+
+    // Writes `%YAML` and `%TAG` directives before every document, which then
+    // also starts with `---`.
+    pub fn set_directives(
+        &mut self,
+        version: Option<(i32, i32)>,
+        tags: impl IntoIterator<Item = (String, String)>,
+    ) {
+        self.version_directive = version;
+        self.tag_directives = tags
+            .into_iter()
+            .map(|(handle, prefix)| (handle + "\0", prefix + "\0"))
+            .collect();
+    }
+
+    pub fn set_scalar_anchor(&mut self, anchor: Option<String>) {
+        self.scalar_anchor = anchor;
     }
 
     pub fn emit(&mut self, event: Event) -> Result<(), Error> {
@@ -90,10 +173,29 @@ impl<'a> Emitter<'a> {
                 }
                 Event::StreamEnd => sys::yaml_stream_end_event_initialize(sys_event),
                 Event::DocumentStart => {
-                    let version_directive = ptr::null_mut();
-                    let tag_directives_start = ptr::null_mut();
-                    let tag_directives_end = ptr::null_mut();
-                    let implicit = true;
+                    // The event gets copies of the directives.
+                    let mut version = self.version_directive.map(|(major, minor)| {
+                        let mut version: sys::yaml_version_directive_t = mem::zeroed();
+                        version.major = major;
+                        version.minor = minor;
+                        version
+                    });
+                    let version_directive = version
+                        .as_mut()
+                        .map_or_else(ptr::null_mut, |version| version as *mut _);
+                    let mut tag_directives: Vec<sys::yaml_tag_directive_t> = self
+                        .tag_directives
+                        .iter()
+                        .map(|(handle, prefix)| {
+                            let mut directive: sys::yaml_tag_directive_t = mem::zeroed();
+                            directive.handle = handle.as_ptr() as *mut u8;
+                            directive.prefix = prefix.as_ptr() as *mut u8;
+                            directive
+                        })
+                        .collect();
+                    let tag_directives_start = tag_directives.as_mut_ptr();
+                    let tag_directives_end = tag_directives_start.add(tag_directives.len());
+                    let implicit = !self.explicit_document_start;
                     sys::yaml_document_start_event_initialize(
                         sys_event,
                         version_directive,
@@ -103,11 +205,19 @@ impl<'a> Emitter<'a> {
                     )
                 }
                 Event::DocumentEnd => {
-                    let implicit = true;
+                    let implicit = !self.explicit_document_end;
                     sys::yaml_document_end_event_initialize(sys_event, implicit)
                 }
+                Event::Alias(mut anchor) => {
+                    anchor.push('\0');
+                    sys::yaml_alias_event_initialize(sys_event, anchor.as_ptr())
+                }
                 Event::Scalar(mut scalar) => {
-                    let anchor = ptr::null();
+                    let mut anchor = self.scalar_anchor.take();
+                    let anchor = anchor.as_mut().map_or_else(ptr::null, |anchor| {
+                        anchor.push('\0');
+                        anchor.as_ptr()
+                    });
                     let tag = scalar.tag.as_mut().map_or_else(ptr::null, |tag| {
                         tag.push('\0');
                         tag.as_ptr()
@@ -120,7 +230,9 @@ impl<'a> Emitter<'a> {
                         ScalarStyle::Any => sys::YAML_ANY_SCALAR_STYLE,
                         ScalarStyle::Plain => sys::YAML_PLAIN_SCALAR_STYLE,
                         ScalarStyle::SingleQuoted => sys::YAML_SINGLE_QUOTED_SCALAR_STYLE,
+                        ScalarStyle::DoubleQuoted => sys::YAML_DOUBLE_QUOTED_SCALAR_STYLE,
                         ScalarStyle::Literal => sys::YAML_LITERAL_SCALAR_STYLE,
+                        ScalarStyle::Folded => sys::YAML_FOLDED_SCALAR_STYLE,
                     };
                     sys::yaml_scalar_event_initialize(
                         sys_event,
@@ -134,26 +246,38 @@ impl<'a> Emitter<'a> {
                     )
                 }
                 Event::SequenceStart(mut sequence) => {
-                    let anchor = ptr::null();
+                    let anchor = sequence.anchor.as_mut().map_or_else(ptr::null, |anchor| {
+                        anchor.push('\0');
+                        anchor.as_ptr()
+                    });
                     let tag = sequence.tag.as_mut().map_or_else(ptr::null, |tag| {
                         tag.push('\0');
                         tag.as_ptr()
                     });
                     let implicit = tag.is_null();
-                    let style = sys::YAML_ANY_SEQUENCE_STYLE;
+                    let style = match sequence.style {
+                        CollectionStyle::Any => sys::YAML_ANY_SEQUENCE_STYLE,
+                        CollectionStyle::Flow => sys::YAML_FLOW_SEQUENCE_STYLE,
+                    };
                     sys::yaml_sequence_start_event_initialize(
                         sys_event, anchor, tag, implicit, style,
                     )
                 }
                 Event::SequenceEnd => sys::yaml_sequence_end_event_initialize(sys_event),
                 Event::MappingStart(mut mapping) => {
-                    let anchor = ptr::null();
+                    let anchor = mapping.anchor.as_mut().map_or_else(ptr::null, |anchor| {
+                        anchor.push('\0');
+                        anchor.as_ptr()
+                    });
                     let tag = mapping.tag.as_mut().map_or_else(ptr::null, |tag| {
                         tag.push('\0');
                         tag.as_ptr()
                     });
                     let implicit = tag.is_null();
-                    let style = sys::YAML_ANY_MAPPING_STYLE;
+                    let style = match mapping.style {
+                        CollectionStyle::Any => sys::YAML_ANY_MAPPING_STYLE,
+                        CollectionStyle::Flow => sys::YAML_FLOW_MAPPING_STYLE,
+                    };
                     sys::yaml_mapping_start_event_initialize(
                         sys_event, anchor, tag, implicit, style,
                     )
@@ -180,6 +304,12 @@ impl<'a> Emitter<'a> {
         Ok(())
     }
 
+    // Also flushes the writer, for one that holds back its output until then.
+    pub fn flush_writer(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        unsafe { (*self.pin.ptr).write.flush() }.map_err(Error::Io)
+    }
+
     pub fn into_inner(self) -> Box<dyn io::Write + 'a> {
         let sink = Box::new(io::sink());
         unsafe { mem::replace(&mut (*self.pin.ptr).write, sink) }
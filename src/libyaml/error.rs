@@ -1,5 +1,8 @@
 use crate::libyaml::cstr::CStr;
+#[cfg(feature = "de")]
+use std::cmp;
 use std::fmt::{self, Debug, Display};
+#[cfg(feature = "de")]
 use std::mem::MaybeUninit;
 use std::ptr::NonNull;
 use unsafe_libyaml as sys;
@@ -10,6 +13,7 @@ pub(crate) struct Error {
     kind: sys::yaml_error_type_t,
     problem: CStr<'static>,
     problem_offset: u64,
+    problem_value: i32,
     problem_mark: Mark,
     context: Option<CStr<'static>>,
     context_mark: Mark,
@@ -24,6 +28,7 @@ impl Error {
                 None => CStr::from_bytes_with_nul(b"libyaml parser failed but there is no error\0"),
             },
             problem_offset: (*parser).problem_offset,
+            problem_value: (*parser).problem_value,
             problem_mark: Mark {
                 sys: (*parser).problem_mark,
             },
@@ -37,6 +42,7 @@ impl Error {
         }
     }
 
+    #[cfg(feature = "ser")]
     pub unsafe fn emit_error(emitter: *const sys::yaml_emitter_t) -> Self {
         Error {
             kind: (*emitter).error,
@@ -47,6 +53,7 @@ impl Error {
                 }
             },
             problem_offset: 0,
+            problem_value: -1,
             problem_mark: Mark {
                 sys: MaybeUninit::<sys::yaml_mark_t>::zeroed().assume_init(),
             },
@@ -57,16 +64,104 @@ impl Error {
         }
     }
 
+    // Errors of the reader, about a character or byte that it does not
+    // accept, only have the byte offset of it. This finds the line and column
+    // of that offset, counted the same way as in the marks of the scanner,
+    // from the mark of where the input starts.
+    #[cfg(feature = "de")]
+    pub fn locate(mut self, input: &[u8], start: Mark) -> Self {
+        if self.kind != sys::YAML_READER_ERROR {
+            return self;
+        }
+        let problem_offset = cmp::max(self.problem_offset, start.sys.index) - start.sys.index;
+        let offset = cmp::min(problem_offset as usize, input.len());
+        let mut mark = start.sys;
+        mark.index += offset as u64;
+        let mut i = if start.sys.index == 0 && input.starts_with(b"\xEF\xBB\xBF") {
+            3
+        } else {
+            0
+        };
+        while i < offset {
+            match line_break_len(input, i) {
+                0 => {
+                    if input[i] & 0xC0 != 0x80 {
+                        mark.column += 1;
+                    }
+                    i += 1;
+                }
+                len => {
+                    mark.line += 1;
+                    mark.column = 0;
+                    i += len;
+                }
+            }
+        }
+        self.problem_mark = Mark { sys: mark };
+        self
+    }
+
+    // The error with its marks in a larger input that the input it is in
+    // starts at this mark of, at the start of a line.
+    #[cfg(feature = "de")]
+    pub fn shifted(mut self, by: Mark) -> Self {
+        self.problem_mark = self.problem_mark.shifted(by);
+        self.context_mark = self.context_mark.shifted(by);
+        self
+    }
+
     pub fn mark(&self) -> Mark {
         self.problem_mark
     }
+
+    fn has_mark(&self) -> bool {
+        // Reader errors have a mark once located, even at the start of the
+        // input.
+        self.kind == sys::YAML_READER_ERROR
+            || self.problem_mark.sys.line != 0
+            || self.problem_mark.sys.column != 0
+    }
+}
+
+// The length of the line break starting at `index`, or 0. These are the
+// breaks that libyaml counts lines by in the marks of its errors and events:
+// LF, CR, CRLF, NEL, and the Unicode line and paragraph separators.
+pub(crate) fn line_break_len(input: &[u8], index: usize) -> usize {
+    match input[index..] {
+        [b'\r', b'\n', ..] => 2,
+        [b'\n' | b'\r', ..] => 1,
+        [0xC2, 0x85, ..] => 2,
+        [0xE2, 0x80, 0xA8 | 0xA9, ..] => 3,
+        _ => 0,
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        write!(formatter, "{}", self.problem)?;
-        if self.problem_mark.sys.line != 0 || self.problem_mark.sys.column != 0 {
-            write!(formatter, " at {}", self.problem_mark)?;
+        match (self.kind, self.problem.to_bytes()) {
+            (sys::YAML_READER_ERROR, b"control characters are not allowed") => {
+                // Also the noncharacters U+FFFE and U+FFFF, which are not C0
+                // or C1 control characters.
+                let kind = if self.problem_value <= 0x9F {
+                    "control character"
+                } else {
+                    "character"
+                };
+                write!(
+                    formatter,
+                    "{} U+{:04X} not allowed in YAML",
+                    kind, self.problem_value,
+                )?;
+            }
+            (
+                sys::YAML_READER_ERROR,
+                b"invalid leading UTF-8 octet" | b"invalid trailing UTF-8 octet",
+            ) => write!(formatter, "{} 0x{:02X}", self.problem, self.problem_value)?,
+            _ => write!(formatter, "{}", self.problem)?,
+        }
+        if self.has_mark() {
+            write!(formatter, " at ")?;
+            self.problem_mark.fmt_line_column(formatter)?;
         } else if self.problem_offset != 0 {
             write!(formatter, " at position {}", self.problem_offset)?;
         }
@@ -99,7 +194,10 @@ impl Debug for Error {
             formatter.field("kind", &format_args!("{}", kind));
         }
         formatter.field("problem", &self.problem);
-        if self.problem_mark.sys.line != 0 || self.problem_mark.sys.column != 0 {
+        if self.problem_value >= 0 {
+            formatter.field("problem_value", &self.problem_value);
+        }
+        if self.has_mark() {
             formatter.field("problem_mark", &self.problem_mark);
         } else if self.problem_offset != 0 {
             formatter.field("problem_offset", &self.problem_offset);
@@ -120,6 +218,13 @@ pub(crate) struct Mark {
 }
 
 impl Mark {
+    #[cfg(feature = "de")]
+    pub(crate) fn zero() -> Self {
+        Mark {
+            sys: unsafe { MaybeUninit::<sys::yaml_mark_t>::zeroed().assume_init() },
+        }
+    }
+
     pub fn index(&self) -> u64 {
         self.sys.index
     }
@@ -131,17 +236,38 @@ impl Mark {
     pub fn column(&self) -> u64 {
         self.sys.column
     }
+
+    #[cfg(feature = "de")]
+    pub(crate) fn shifted(mut self, by: Mark) -> Self {
+        self.sys.index += by.sys.index;
+        self.sys.line += by.sys.line;
+        self
+    }
+}
+
+// Columns after which a location also includes the byte offset, which is
+// easier to jump to than a column in a very long line.
+const LONG_LINE: u64 = 1000;
+
+impl Mark {
+    fn fmt_line_column(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "line {} column {}",
+            self.sys.line + 1,
+            self.sys.column + 1,
+        )?;
+        if self.sys.column >= LONG_LINE {
+            write!(formatter, " (byte {})", self.sys.index)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for Mark {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         if self.sys.line != 0 || self.sys.column != 0 {
-            write!(
-                formatter,
-                "line {} column {}",
-                self.sys.line + 1,
-                self.sys.column + 1,
-            )
+            self.fmt_line_column(formatter)
         } else {
             write!(formatter, "position {}", self.sys.index)
         }
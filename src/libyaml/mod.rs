@@ -1,8 +1,11 @@
 mod cstr;
+#[cfg(feature = "ser")]
 pub mod emitter;
 pub mod error;
+#[cfg(feature = "de")]
 pub mod parser;
 pub mod tag;
 mod util;
 
+#[cfg(feature = "ser")]
 use self::error::Error;
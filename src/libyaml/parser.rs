@@ -3,19 +3,33 @@ use crate::libyaml::error::{Error, Mark, Result};
 use crate::libyaml::tag::Tag;
 use crate::libyaml::util::Owned;
 use std::borrow::Cow;
+use std::ffi::c_void;
 use std::fmt::{self, Debug};
+use std::io;
 use std::mem::MaybeUninit;
+use std::ops::Deref;
 use std::ptr::{addr_of_mut, NonNull};
 use std::slice;
 use unsafe_libyaml as sys;
 
 pub(crate) struct Parser<'input> {
     pin: Owned<ParserPinned<'input>>,
+    // Where the last event ends in the input, which for the start or end of
+    // a document is after its `---` or `...` marker.
+    end: Mark,
+    // Where the part of the input that is still kept starts.
+    start: Mark,
+    // Where the input starts in a larger input, which the marks that the
+    // parser gives are in.
+    shift: Mark,
 }
 
 struct ParserPinned<'input> {
     sys: sys::yaml_parser_t,
+    // For input from a reader, what has been read of it since `start`.
     input: Cow<'input, [u8]>,
+    read: Option<Box<dyn io::Read + 'input>>,
+    read_error: Option<io::Error>,
 }
 
 #[derive(Debug)]
@@ -32,6 +46,22 @@ pub(crate) enum Event<'input> {
     MappingEnd,
 }
 
+// An event without its content, other than the anchors that aliases refer to
+// and the length of a scalar.
+pub(crate) enum EventKind {
+    StreamStart,
+    StreamEnd,
+    DocumentStart,
+    DocumentEnd,
+    Alias(Anchor),
+    Scalar(Option<Anchor>, usize),
+    SequenceStart(Option<Anchor>),
+    SequenceEnd,
+    MappingStart(Option<Anchor>),
+    MappingEnd,
+}
+
+#[derive(Clone)]
 pub(crate) struct Scalar<'input> {
     pub anchor: Option<Anchor>,
     pub tag: Option<Tag>,
@@ -44,23 +74,35 @@ pub(crate) struct Scalar<'input> {
 pub(crate) struct SequenceStart {
     pub anchor: Option<Anchor>,
     pub tag: Option<Tag>,
+    // Whether the sequence is written in brackets rather than as a block.
+    pub flow: bool,
 }
 
 #[derive(Debug)]
 pub(crate) struct MappingStart {
     pub anchor: Option<Anchor>,
     pub tag: Option<Tag>,
+    // Whether the mapping is written in braces rather than as a block.
+    pub flow: bool,
 }
 
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub(crate) struct Anchor(Box<[u8]>);
 
+/// How a scalar is written in the input, passed to a
+/// [`DeserializerBuilder::map_scalar`](crate::DeserializerBuilder::map_scalar)
+/// hook.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub(crate) enum ScalarStyle {
+pub enum ScalarStyle {
+    /// Unquoted, as in `key: value`.
     Plain,
+    /// In single quotes, as in `key: 'value'`.
     SingleQuoted,
+    /// In double quotes, as in `key: "value"`.
     DoubleQuoted,
+    /// A literal block scalar introduced by `|`.
     Literal,
+    /// A folded block scalar introduced by `>`.
     Folded,
 }
 
@@ -75,28 +117,125 @@ impl<'input> Parser<'input> {
             sys::yaml_parser_set_encoding(parser, sys::YAML_UTF8_ENCODING);
             sys::yaml_parser_set_input_string(parser, input.as_ptr(), input.len() as u64);
             addr_of_mut!((*owned.ptr).input).write(input);
+            addr_of_mut!((*owned.ptr).read).write(None);
+            addr_of_mut!((*owned.ptr).read_error).write(None);
             Owned::assume_init(owned)
         };
-        Parser { pin }
+        Parser {
+            pin,
+            end: Mark::zero(),
+            start: Mark::zero(),
+            shift: Mark::zero(),
+        }
+    }
+
+    // Reads the input in chunks as the parser gets to them, rather than all
+    // of it up front.
+    pub fn from_reader(read: Box<dyn io::Read + 'input>) -> Parser<'input> {
+        let owned = Owned::<ParserPinned>::new_uninit();
+        let pin = unsafe {
+            let parser = addr_of_mut!((*owned.ptr).sys);
+            if sys::yaml_parser_initialize(parser).fail {
+                panic!("malloc error: {}", Error::parse_error(parser));
+            }
+            sys::yaml_parser_set_encoding(parser, sys::YAML_UTF8_ENCODING);
+            addr_of_mut!((*owned.ptr).input).write(Cow::Owned(Vec::new()));
+            addr_of_mut!((*owned.ptr).read).write(Some(read));
+            addr_of_mut!((*owned.ptr).read_error).write(None);
+            sys::yaml_parser_set_input(parser, read_handler, owned.ptr.cast());
+            Owned::assume_init(owned)
+        };
+        Parser {
+            pin,
+            end: Mark::zero(),
+            start: Mark::zero(),
+            shift: Mark::zero(),
+        }
+    }
+
+    // Gives the input the marks that it has in a larger input which it is a
+    // part of, starting at the start of a line at this byte index.
+    #[cfg(feature = "tokio")]
+    pub fn start_at(&mut self, index: u64, line: u64) {
+        self.shift.sys.index = index;
+        self.shift.sys.line = line;
+    }
+
+    // All of the input, or for a reader, the part since `input_start`.
+    pub fn input(&self) -> &Cow<'input, [u8]> {
+        unsafe { &(*self.pin.ptr).input }
+    }
+
+    pub fn input_start(&self) -> usize {
+        self.start.shifted(self.shift).index() as usize
+    }
+
+    pub fn end(&self) -> usize {
+        self.end_mark().index() as usize
+    }
+
+    // Where the last event ends.
+    pub fn end_mark(&self) -> Mark {
+        self.end.shifted(self.shift)
+    }
+
+    // For a reader, drops what has been read of it before the end of the
+    // last event, which nothing refers to anymore once a document is done.
+    pub fn forget_input(&mut self) {
+        unsafe {
+            if (*self.pin.ptr).read.is_none() {
+                return;
+            }
+            let input = (*self.pin.ptr).input.to_mut();
+            let len = self.end.index().saturating_sub(self.start.index()) as usize;
+            input.drain(..len.min(input.len()));
+        }
+        self.start = self.end;
+    }
+
+    // The error of the reader that made the parser fail, if it did.
+    pub fn take_read_error(&mut self) -> Option<io::Error> {
+        unsafe { (*self.pin.ptr).read_error.take() }
     }
 
     pub fn next(&mut self) -> Result<(Event<'input>, Mark)> {
+        self.next_with(|event, input| unsafe { convert_event(event, input) })
+    }
+
+    // Like `next`, but copies nothing but anchors out of the event.
+    pub fn next_kind(&mut self) -> Result<(EventKind, Mark)> {
+        self.next_with(|event, _input| unsafe { event_kind(event) })
+    }
+
+    fn next_with<T>(
+        &mut self,
+        convert: impl FnOnce(&sys::yaml_event_t, &Cow<'input, [u8]>) -> T,
+    ) -> Result<(T, Mark)> {
         let mut event = MaybeUninit::<sys::yaml_event_t>::uninit();
         unsafe {
             let parser = addr_of_mut!((*self.pin.ptr).sys);
             if (*parser).error != sys::YAML_NO_ERROR {
-                return Err(Error::parse_error(parser));
+                let input = &(*self.pin.ptr).input;
+                return Err(Error::parse_error(parser)
+                    .locate(input, self.start)
+                    .shifted(self.shift));
             }
             let event = event.as_mut_ptr();
             if sys::yaml_parser_parse(parser, event).fail {
-                return Err(Error::parse_error(parser));
+                let input = &(*self.pin.ptr).input;
+                return Err(Error::parse_error(parser)
+                    .locate(input, self.start)
+                    .shifted(self.shift));
             }
-            let ret = convert_event(&*event, &(*self.pin.ptr).input);
+            let ret = convert(&*event, &(*self.pin.ptr).input);
             let mark = Mark {
                 sys: (*event).start_mark,
             };
+            self.end = Mark {
+                sys: (*event).end_mark,
+            };
             sys::yaml_event_delete(event);
-            Ok((ret, mark))
+            Ok((ret, mark.shifted(self.shift)))
         }
     }
 }
@@ -135,11 +274,13 @@ unsafe fn convert_event<'input>(
         sys::YAML_SEQUENCE_START_EVENT => Event::SequenceStart(SequenceStart {
             anchor: optional_anchor(sys.data.sequence_start.anchor),
             tag: optional_tag(sys.data.sequence_start.tag),
+            flow: sys.data.sequence_start.style == sys::YAML_FLOW_SEQUENCE_STYLE,
         }),
         sys::YAML_SEQUENCE_END_EVENT => Event::SequenceEnd,
         sys::YAML_MAPPING_START_EVENT => Event::MappingStart(MappingStart {
             anchor: optional_anchor(sys.data.mapping_start.anchor),
             tag: optional_tag(sys.data.mapping_start.tag),
+            flow: sys.data.mapping_start.style == sys::YAML_FLOW_MAPPING_STYLE,
         }),
         sys::YAML_MAPPING_END_EVENT => Event::MappingEnd,
         sys::YAML_NO_EVENT => unreachable!(),
@@ -147,6 +288,53 @@ unsafe fn convert_event<'input>(
     }
 }
 
+unsafe fn read_handler(data: *mut c_void, buffer: *mut u8, size: u64, size_read: *mut u64) -> i32 {
+    let data = data.cast::<ParserPinned>();
+    let read = match &mut (*data).read {
+        Some(read) => read,
+        None => return 0,
+    };
+    let buffer = slice::from_raw_parts_mut(buffer, size as usize);
+    loop {
+        match read.read(buffer) {
+            Ok(n) => {
+                (*data).input.to_mut().extend_from_slice(&buffer[..n]);
+                *size_read = n as u64;
+                return 1;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                (*data).read_error = Some(err);
+                return 0;
+            }
+        }
+    }
+}
+
+unsafe fn event_kind(sys: &sys::yaml_event_t) -> EventKind {
+    match sys.type_ {
+        sys::YAML_STREAM_START_EVENT => EventKind::StreamStart,
+        sys::YAML_STREAM_END_EVENT => EventKind::StreamEnd,
+        sys::YAML_DOCUMENT_START_EVENT => EventKind::DocumentStart,
+        sys::YAML_DOCUMENT_END_EVENT => EventKind::DocumentEnd,
+        sys::YAML_ALIAS_EVENT => EventKind::Alias(optional_anchor(sys.data.alias.anchor).unwrap()),
+        sys::YAML_SCALAR_EVENT => EventKind::Scalar(
+            optional_anchor(sys.data.scalar.anchor),
+            sys.data.scalar.length as usize,
+        ),
+        sys::YAML_SEQUENCE_START_EVENT => {
+            EventKind::SequenceStart(optional_anchor(sys.data.sequence_start.anchor))
+        }
+        sys::YAML_SEQUENCE_END_EVENT => EventKind::SequenceEnd,
+        sys::YAML_MAPPING_START_EVENT => {
+            EventKind::MappingStart(optional_anchor(sys.data.mapping_start.anchor))
+        }
+        sys::YAML_MAPPING_END_EVENT => EventKind::MappingEnd,
+        sys::YAML_NO_EVENT => unreachable!(),
+        _ => unimplemented!(),
+    }
+}
+
 unsafe fn optional_anchor(anchor: *const u8) -> Option<Anchor> {
     let ptr = NonNull::new(anchor as *mut i8)?;
     let cstr = CStr::from_ptr(ptr);
@@ -187,6 +375,13 @@ impl<'input> Debug for Scalar<'input> {
     }
 }
 
+impl Deref for Anchor {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 impl Debug for Anchor {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         cstr::debug_lossy(&self.0, formatter)
@@ -2,7 +2,7 @@ use crate::libyaml::cstr;
 use std::fmt::{self, Debug};
 use std::ops::Deref;
 
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub(crate) struct Tag(pub(in crate::libyaml) Box<[u8]>);
 
 impl Tag {
@@ -10,9 +10,23 @@ impl Tag {
     pub const BOOL: &'static str = "tag:yaml.org,2002:bool";
     pub const INT: &'static str = "tag:yaml.org,2002:int";
     pub const FLOAT: &'static str = "tag:yaml.org,2002:float";
+    pub const STR: &'static str = "tag:yaml.org,2002:str";
+    pub const BINARY: &'static str = "tag:yaml.org,2002:binary";
 }
 
+// Only written out by the serializer, in canonical output.
+#[cfg_attr(not(feature = "ser"), allow(dead_code))]
 impl Tag {
+    pub const SEQ: &'static str = "tag:yaml.org,2002:seq";
+    pub const MAP: &'static str = "tag:yaml.org,2002:map";
+}
+
+impl Tag {
+    #[cfg(feature = "value")]
+    pub fn new(tag: String) -> Self {
+        Tag(tag.into_bytes().into_boxed_slice())
+    }
+
     pub fn starts_with(&self, prefix: &str) -> bool {
         self.0.starts_with(prefix.as_bytes())
     }
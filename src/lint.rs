@@ -0,0 +1,570 @@
+//! Style and safety checks for YAML documents.
+//!
+//! The [`lint`][crate::lint()] function reports problems such as duplicate
+//! keys, tab indentation and scalars whose meaning changed between YAML 1.1
+//! and 1.2, without deserializing the input.
+//!
+//! ```
+//! use serde_yaml::lint::{LintConfig, Rule};
+//!
+//! let yaml = "---\nenabled: no\nenabled: yes\n";
+//! let diagnostics = serde_yaml::lint(yaml, &LintConfig::new());
+//!
+//! let rules: Vec<Rule> = diagnostics.iter().map(|d| d.rule()).collect();
+//! assert_eq!(
+//!     rules,
+//!     [Rule::AmbiguousScalars, Rule::DuplicateKeys, Rule::AmbiguousScalars],
+//! );
+//! assert_eq!(diagnostics[1].location().line(), 3);
+//! ```
+
+use crate::de::{self, ScalarKey};
+use crate::error::Location;
+use crate::libyaml::error::{self, Mark};
+use crate::libyaml::parser::{Anchor, Event, Parser, Scalar, ScalarStyle};
+use crate::schema::CoercionFlags;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Display};
+use std::iter;
+
+/// A check performed by [`lint`][crate::lint()].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Rule {
+    /// The input is not valid YAML. This rule cannot be disabled.
+    Syntax,
+    /// The same key appears twice in one mapping. Keys are compared by what
+    /// they resolve to in the core schema, so `1` and `0x1` are the same key
+    /// but `1` and `'1'` are not.
+    DuplicateKeys,
+    /// A line is indented using tabs.
+    Tabs,
+    /// A line ends in spaces or tabs.
+    TrailingWhitespace,
+    /// Nested block collections are indented by different widths.
+    Indentation,
+    /// A plain scalar, like `no` or `08:30`, which YAML 1.1 parsers read as a
+    /// boolean or a base 60 number rather than a string.
+    AmbiguousScalars,
+    /// A line is longer than the configured maximum.
+    LineLength,
+    /// A document does not begin with a `---` marker.
+    DocumentStart,
+    /// An anchor is defined but never referred to by an alias.
+    UnusedAnchors,
+}
+
+const RULES: [Rule; 9] = [
+    Rule::Syntax,
+    Rule::DuplicateKeys,
+    Rule::Tabs,
+    Rule::TrailingWhitespace,
+    Rule::Indentation,
+    Rule::AmbiguousScalars,
+    Rule::LineLength,
+    Rule::DocumentStart,
+    Rule::UnusedAnchors,
+];
+
+impl Rule {
+    /// The identifier of the rule, like `duplicate-keys`.
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::Syntax => "syntax",
+            Rule::DuplicateKeys => "duplicate-keys",
+            Rule::Tabs => "tabs",
+            Rule::TrailingWhitespace => "trailing-whitespace",
+            Rule::Indentation => "indentation",
+            Rule::AmbiguousScalars => "ambiguous-scalars",
+            Rule::LineLength => "line-length",
+            Rule::DocumentStart => "document-start",
+            Rule::UnusedAnchors => "unused-anchors",
+        }
+    }
+
+    fn default_severity(self) -> Severity {
+        match self {
+            Rule::Syntax | Rule::DuplicateKeys => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.id())
+    }
+}
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    /// A style problem.
+    Warning,
+    /// A problem which is likely to change the meaning of the document.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// The set of rules checked by [`lint`][crate::lint()].
+///
+/// Every rule is enabled by default at its default severity, with lines
+/// limited to 80 columns and the indentation width taken from the first
+/// nested collection in the input.
+#[derive(Clone, Debug)]
+pub struct LintConfig {
+    severities: [Option<Severity>; RULES.len()],
+    max_line_length: usize,
+    indent_width: Option<usize>,
+}
+
+impl LintConfig {
+    /// Creates a configuration with every rule enabled.
+    pub fn new() -> Self {
+        LintConfig {
+            severities: RULES.map(|rule| Some(rule.default_severity())),
+            max_line_length: 80,
+            indent_width: None,
+        }
+    }
+
+    /// Enables a rule at its default severity.
+    pub fn enable(mut self, rule: Rule) -> Self {
+        self.severities[rule as usize] = Some(rule.default_severity());
+        self
+    }
+
+    /// Stops reporting a rule. Syntax errors are always reported.
+    pub fn disable(mut self, rule: Rule) -> Self {
+        if rule != Rule::Syntax {
+            self.severities[rule as usize] = None;
+        }
+        self
+    }
+
+    /// Enables a rule and reports it at the given severity.
+    pub fn severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.severities[rule as usize] = Some(severity);
+        self
+    }
+
+    /// Sets the longest line, in characters, allowed by [`Rule::LineLength`].
+    pub fn max_line_length(mut self, columns: usize) -> Self {
+        self.max_line_length = columns;
+        self
+    }
+
+    /// Sets the number of spaces expected by [`Rule::Indentation`] for each
+    /// level of nesting.
+    pub fn indent_width(mut self, spaces: usize) -> Self {
+        self.indent_width = Some(spaces);
+        self
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig::new()
+    }
+}
+
+/// A problem found by [`lint`][crate::lint()].
+#[derive(Debug)]
+pub struct Diagnostic {
+    rule: Rule,
+    severity: Severity,
+    message: String,
+    location: Location,
+}
+
+impl Diagnostic {
+    /// The rule which found the problem.
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// How serious the problem is.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// A description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Where in the input the problem is.
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}:{}: {}: {} ({})",
+            self.location.line(),
+            self.location.column(),
+            self.severity,
+            self.message,
+            self.rule,
+        )
+    }
+}
+
+/// Checks YAML text against a set of style and safety rules.
+///
+/// Diagnostics are returned in the order of their location in the input.
+/// Checking stops at the first syntax error, which is reported as a
+/// diagnostic for [`Rule::Syntax`].
+pub fn lint(input: &str, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        input,
+        config,
+        indent_width: config.indent_width,
+        diagnostics: Vec::new(),
+    };
+    linter.check_lines();
+    linter.check_events();
+    linter
+        .diagnostics
+        .sort_by_key(|diagnostic| diagnostic.location.index());
+    linter.diagnostics
+}
+
+// The index and text of each line, without its line break.
+fn lines(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut next = Some(0);
+    iter::from_fn(move || {
+        let start = next?;
+        let bytes = input.as_bytes();
+        let mut end = start;
+        next = loop {
+            if end == bytes.len() {
+                break None;
+            }
+            match error::line_break_len(bytes, end) {
+                0 => end += 1,
+                len => break Some(end + len),
+            }
+        };
+        Some((start, &input[start..end]))
+    })
+}
+
+struct Linter<'a> {
+    input: &'a str,
+    config: &'a LintConfig,
+    indent_width: Option<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+enum Frame {
+    Sequence {
+        flow: bool,
+    },
+    Mapping {
+        flow: bool,
+        column: u64,
+        // Whether the next node is a key rather than a value.
+        at_key: bool,
+        key_line: u64,
+        keys: HashSet<ScalarKey>,
+    },
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, rule: Rule, location: Location, message: String) {
+        if let Some(severity) = self.config.severities[rule as usize] {
+            self.diagnostics.push(Diagnostic {
+                rule,
+                severity,
+                message,
+                location,
+            });
+        }
+    }
+
+    fn check_lines(&mut self) {
+        let input = self.input;
+        let mut document_start = None;
+        for (line, (start, text)) in lines(input).enumerate() {
+            let location = |column: usize| {
+                Location::new(
+                    start + column,
+                    line + 1,
+                    1 + { text[..column].chars().count() },
+                )
+            };
+
+            let content = text.trim_start_matches([' ', '\t']);
+            if text[..text.len() - content.len()].contains('\t') {
+                let column = text.find('\t').unwrap();
+                let message = "indentation contains a tab".to_owned();
+                self.report(Rule::Tabs, location(column), message);
+            }
+
+            let trimmed = text.trim_end_matches([' ', '\t']);
+            if trimmed.len() < text.len() {
+                let message = "trailing whitespace".to_owned();
+                self.report(Rule::TrailingWhitespace, location(trimmed.len()), message);
+            }
+
+            let length = text.chars().count();
+            if length > self.config.max_line_length {
+                let message = format!(
+                    "line is {} characters long, more than the limit of {}",
+                    length, self.config.max_line_length,
+                );
+                let column = text
+                    .char_indices()
+                    .nth(self.config.max_line_length)
+                    .map_or(text.len(), |(column, _ch)| column);
+                self.report(Rule::LineLength, location(column), message);
+            }
+
+            if document_start.is_none()
+                && !content.is_empty()
+                && !content.starts_with('#')
+                && !text.starts_with('%')
+            {
+                document_start = Some((text.starts_with("---"), location(0)));
+            }
+        }
+
+        if let Some((false, location)) = document_start {
+            let message = "missing document start marker `---`".to_owned();
+            self.report(Rule::DocumentStart, location, message);
+        }
+    }
+
+    fn check_events(&mut self) {
+        let mut parser = Parser::new(Cow::Borrowed(self.input.as_bytes()));
+        let mut stack = Vec::new();
+        let mut anchors = BTreeMap::new();
+        loop {
+            let (event, mark) = match parser.next() {
+                Ok(next) => next,
+                Err(error) => {
+                    let location = Location::from_mark(error.mark());
+                    self.report(Rule::Syntax, location, error.to_string());
+                    return;
+                }
+            };
+            let anchor = match &event {
+                Event::StreamEnd => return,
+                Event::DocumentEnd => {
+                    self.check_anchors(&mut anchors);
+                    continue;
+                }
+                Event::Scalar(scalar) => scalar.anchor.as_ref(),
+                Event::SequenceStart(sequence) => sequence.anchor.as_ref(),
+                Event::MappingStart(mapping) => mapping.anchor.as_ref(),
+                Event::Alias(anchor) => {
+                    if let Some((_mark, used)) = anchors.get_mut(anchor) {
+                        *used = true;
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(anchor) = anchor {
+                anchors.insert(anchor.clone(), (mark, false));
+            }
+
+            match event {
+                Event::Scalar(scalar) => {
+                    let is_key = self.enter_node(&mut stack, mark, true);
+                    if is_key {
+                        self.check_duplicate_key(&mut stack, mark, &scalar);
+                    }
+                    if scalar.style == ScalarStyle::Plain && scalar.tag.is_none() {
+                        self.check_ambiguous(mark, &scalar.value);
+                    }
+                    self.exit_node(&mut stack);
+                }
+                Event::Alias(_) => {
+                    self.enter_node(&mut stack, mark, true);
+                    self.exit_node(&mut stack);
+                }
+                Event::SequenceStart(_) => {
+                    let flow = self.enter_node(&mut stack, mark, false) || self.is_flow(mark);
+                    stack.push(Frame::Sequence { flow });
+                }
+                Event::MappingStart(_) => {
+                    let flow = self.enter_node(&mut stack, mark, false) || self.is_flow(mark);
+                    stack.push(Frame::Mapping {
+                        flow,
+                        column: mark.column(),
+                        at_key: true,
+                        key_line: mark.line(),
+                        keys: HashSet::new(),
+                    });
+                }
+                Event::SequenceEnd | Event::MappingEnd => {
+                    stack.pop();
+                    self.exit_node(&mut stack);
+                }
+                Event::StreamStart
+                | Event::StreamEnd
+                | Event::DocumentStart
+                | Event::DocumentEnd => {}
+            }
+        }
+    }
+
+    // Called at the start of every node. For scalars and aliases, returns
+    // whether the node is a mapping key. For collections, returns whether
+    // the collection is inside of a flow collection, after checking the
+    // indentation of block collections nested in block mappings.
+    fn enter_node(&mut self, stack: &mut [Frame], mark: Mark, scalar: bool) -> bool {
+        match stack.last_mut() {
+            Some(Frame::Mapping {
+                flow,
+                column,
+                at_key,
+                key_line,
+                ..
+            }) => {
+                if *at_key {
+                    *key_line = mark.line();
+                    return scalar || *flow;
+                }
+                if scalar || *flow || mark.line() == *key_line || self.is_flow(mark) {
+                    return !scalar && *flow;
+                }
+                let parent_column = *column;
+                self.check_indentation(mark, parent_column);
+                false
+            }
+            Some(Frame::Sequence { flow }) => !scalar && *flow,
+            None => false,
+        }
+    }
+
+    fn exit_node(&mut self, stack: &mut [Frame]) {
+        if let Some(Frame::Mapping { at_key, .. }) = stack.last_mut() {
+            *at_key = !*at_key;
+        }
+    }
+
+    fn check_indentation(&mut self, mark: Mark, parent_column: u64) {
+        // Block sequences may be written at the same column as their key.
+        if mark.column() <= parent_column {
+            return;
+        }
+        let width = (mark.column() - parent_column) as usize;
+        match self.indent_width {
+            None => self.indent_width = Some(width),
+            Some(expected) if expected != width => {
+                let message = format!(
+                    "wrong indentation: expected {} spaces but found {}",
+                    expected, width,
+                );
+                self.report(Rule::Indentation, Location::from_mark(mark), message);
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn check_duplicate_key(&mut self, stack: &mut [Frame], mark: Mark, scalar: &Scalar) {
+        if let Some(Frame::Mapping { keys, .. }) = stack.last_mut() {
+            if !keys.insert(de::scalar_key(scalar, &CoercionFlags::CORE_12)) {
+                let value = String::from_utf8_lossy(&scalar.value);
+                let message = format!("duplicate key `{}`", value);
+                self.report(Rule::DuplicateKeys, Location::from_mark(mark), message);
+            }
+        }
+    }
+
+    fn check_ambiguous(&mut self, mark: Mark, value: &[u8]) {
+        let value = match std::str::from_utf8(value) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        let meaning = if is_yaml_1_1_bool(value) {
+            "a boolean"
+        } else if is_sexagesimal(value) {
+            "a base 60 number"
+        } else {
+            return;
+        };
+        let message = format!(
+            "plain scalar `{}` is {} in YAML 1.1; quote it to keep it a string",
+            value, meaning,
+        );
+        self.report(Rule::AmbiguousScalars, Location::from_mark(mark), message);
+    }
+
+    fn check_anchors(&mut self, anchors: &mut BTreeMap<Anchor, (Mark, bool)>) {
+        for (anchor, (mark, used)) in std::mem::take(anchors) {
+            if !used {
+                let message = format!("anchor {:?} is never used", anchor);
+                self.report(Rule::UnusedAnchors, Location::from_mark(mark), message);
+            }
+        }
+    }
+
+    // Whether the collection starting at this mark is written in flow style.
+    // The mark is at the node's anchor or tag, if it has any.
+    fn is_flow(&self, mark: Mark) -> bool {
+        let mut rest = &self.input[mark.index() as usize..];
+        while rest.starts_with(['&', '!']) {
+            let end = rest
+                .find(|ch: char| ch.is_whitespace())
+                .unwrap_or(rest.len());
+            rest = rest[end..].trim_start();
+        }
+        rest.starts_with(['[', '{'])
+    }
+}
+
+fn is_yaml_1_1_bool(scalar: &str) -> bool {
+    match scalar {
+        "y" | "Y" | "yes" | "Yes" | "YES" | "n" | "N" | "no" | "No" | "NO" | "on" | "On" | "ON"
+        | "off" | "Off" | "OFF" => true,
+        _ => false,
+    }
+}
+
+// YAML 1.1 integers and floats like `1:20:30` or `190:20:30.15`.
+fn is_sexagesimal(scalar: &str) -> bool {
+    let scalar = scalar.strip_prefix(['-', '+']).unwrap_or(scalar);
+    let (scalar, fraction) = match scalar.split_once('.') {
+        Some((scalar, fraction)) => (scalar, Some(fraction)),
+        None => (scalar, None),
+    };
+    if let Some(fraction) = fraction {
+        if !fraction.bytes().all(|b| b.is_ascii_digit() || b == b'_') {
+            return false;
+        }
+    }
+    let mut parts = scalar.split(':');
+    let first = parts.next().unwrap();
+    if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit() || b == b'_') {
+        return false;
+    }
+    let mut count = 0;
+    for part in parts {
+        count += 1;
+        let valid = match part.as_bytes() {
+            [digit] => digit.is_ascii_digit(),
+            [tens, ones] => (b'0'..=b'5').contains(tens) && ones.is_ascii_digit(),
+            _ => false,
+        };
+        if !valid {
+            return false;
+        }
+    }
+    count > 0
+}
@@ -1,119 +1,741 @@
-use crate::de::{Event, Progress};
-use crate::error::{self, Error, ErrorImpl, Result};
-use crate::libyaml::error::Mark;
-use crate::libyaml::parser::{Event as YamlEvent, Parser};
+use crate::de::{
+    self, scalar_key, DuplicateKeyPolicy, Event, IgnoredKeyHook, Progress, ScalarHook, ScalarKey,
+    TagConstructors,
+};
+use crate::encoding::{self, Encoding, Utf8Reader};
+use crate::error::{self, Error, ErrorImpl, Result, Span};
+use crate::libyaml::error::{line_break_len, Mark};
+use crate::libyaml::parser::{Anchor, Event as YamlEvent, Parser, Scalar, ScalarStyle};
+#[cfg(feature = "value")]
+use crate::libyaml::parser::{MappingStart, SequenceStart};
+use crate::libyaml::tag::Tag;
+use crate::path::Path;
+use crate::schema::CoercionFlags;
+#[cfg(feature = "value")]
+use crate::value::Value;
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
 use std::sync::Arc;
 
 pub(crate) struct Loader<'input> {
-    parser: Option<Parser<'input>>,
+    parser: Parser<'input>,
+    done: bool,
     document_count: usize,
+    map_scalar: Option<ScalarHook>,
+    schema: CoercionFlags,
+    preserve_number_repr: bool,
+    preserve_core_tags: bool,
+    preserve_scalar_style: bool,
+    strict_numbers: bool,
+    integral_floats: bool,
+    recursion_limit: usize,
+    repetition_limit: Option<usize>,
+    duplicate_keys: DuplicateKeyPolicy,
+    merge_keys: bool,
+    on_ignored_key: Option<IgnoredKeyHook>,
+    ignore_unknown_tags: bool,
+    ignore_unknown_anchors: bool,
+    tag_constructors: TagConstructors,
 }
 
 pub(crate) struct Document<'input> {
     pub events: Vec<(Event<'input>, Mark)>,
+    /// Where the node that starts with each of the events ends in the input,
+    /// or the end of a collection where it ends.
+    pub ends: Vec<Mark>,
     pub error: Option<Arc<ErrorImpl>>,
     /// Map from alias id to index in events.
     pub aliases: BTreeMap<usize, usize>,
+    /// The index in events of each node that is left out of its collection:
+    /// the key of a mapping entry, which leaves out the entry, or an item of
+    /// a sequence. These are entries that lost to another with the same key,
+    /// and nodes with an error in them for a deserializer with `max_errors`.
+    pub skipped: HashSet<usize>,
+    // Keys which the field identifier of a flattened struct sees as strings
+    // rather than as the values they resolve to, found by an earlier pass.
+    pub string_keys: RefCell<HashSet<usize>>,
+    /// The tags that are left out of the events with `ignore_unknown_tags`,
+    /// by index in events, which can still name the variant of an enum.
+    pub unknown_tags: HashMap<usize, Tag>,
+    /// The input, if the document borrows from it, for excerpts in errors.
+    pub input: Option<&'input [u8]>,
+    /// How plain scalars of the document resolve.
+    pub schema: CoercionFlags,
+    /// Whether numbers deserialized into a Number keep their text.
+    pub preserve_number_repr: bool,
+    /// Whether scalars deserialized into a Value keep their core schema tag.
+    pub preserve_core_tags: bool,
+    /// Whether strings deserialized into a Value keep their scalar style.
+    pub preserve_scalar_style: bool,
+    /// Whether integers that do not fit a float exactly are an error there.
+    pub strict_numbers: bool,
+    /// Whether floats equal to an integer are accepted as one.
+    pub integral_floats: bool,
+    /// How deeply nodes may be nested.
+    pub recursion_limit: usize,
+    /// How many times aliases may be expanded, per event of the document.
+    pub repetition_limit: Option<usize>,
+    /// What is told about the mapping entries that are ignored.
+    pub on_ignored_key: Option<IgnoredKeyHook>,
+    /// Where the document is in the input, if it borrows from it.
+    pub source: Option<Source>,
+}
+
+// The document with its directives and `---` and `...` markers, and the
+// content between the markers.
+#[derive(Clone)]
+pub(crate) struct Source {
+    pub marked: Range<usize>,
+    pub content: Range<usize>,
 }
 
 impl<'input> Loader<'input> {
-    pub fn new(progress: Progress<'input>) -> Result<Self> {
-        let input = match progress {
-            Progress::Str(s) => Cow::Borrowed(s.as_bytes()),
-            Progress::Slice(bytes) => Cow::Borrowed(bytes),
-            Progress::Read(mut rdr) => {
-                let mut buffer = Vec::new();
-                if let Err(io_error) = rdr.read_to_end(&mut buffer) {
-                    return Err(error::new(ErrorImpl::Io(io_error)));
-                }
-                Cow::Owned(buffer)
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        progress: Progress<'input>,
+        map_scalar: Option<ScalarHook>,
+        schema: CoercionFlags,
+        preserve_number_repr: bool,
+        preserve_core_tags: bool,
+        preserve_scalar_style: bool,
+        strict_numbers: bool,
+        integral_floats: bool,
+        recursion_limit: usize,
+        repetition_limit: Option<usize>,
+        duplicate_keys: DuplicateKeyPolicy,
+        merge_keys: bool,
+        on_ignored_key: Option<IgnoredKeyHook>,
+        ignore_unknown_tags: bool,
+        ignore_unknown_anchors: bool,
+        tag_constructors: TagConstructors,
+    ) -> Result<Self> {
+        let parser = match progress {
+            Progress::Str(s) => Parser::new(Cow::Borrowed(s.as_bytes())),
+            Progress::Slice(bytes) => match Encoding::detect(bytes) {
+                None => Parser::new(Cow::Borrowed(bytes)),
+                Some(encoding) => match encoding::to_utf8(bytes, encoding) {
+                    Ok(utf8) => Parser::new(Cow::Owned(utf8)),
+                    Err(io_error) => return Err(error::new(ErrorImpl::Io(io_error))),
+                },
+            },
+            Progress::Read(rdr) => Parser::from_reader(Box::new(Utf8Reader::new(rdr))),
+            #[cfg(feature = "tokio")]
+            Progress::Part { input, index, line } => {
+                let mut parser = Parser::new(Cow::Owned(input));
+                parser.start_at(index, line);
+                parser
             }
             Progress::Iterable(_) | Progress::Document(_) => unreachable!(),
             Progress::Fail(err) => return Err(error::shared(err)),
         };
 
         Ok(Loader {
-            parser: Some(Parser::new(input)),
+            parser,
+            done: false,
             document_count: 0,
+            map_scalar,
+            schema,
+            preserve_number_repr,
+            preserve_core_tags,
+            preserve_scalar_style,
+            strict_numbers,
+            integral_floats,
+            recursion_limit,
+            repetition_limit,
+            duplicate_keys,
+            merge_keys,
+            on_ignored_key,
+            ignore_unknown_tags,
+            ignore_unknown_anchors,
+            tag_constructors,
         })
     }
 
     pub fn next_document(&mut self) -> Option<Document<'input>> {
-        let parser = match &mut self.parser {
-            Some(parser) => parser,
-            None => return None,
-        };
+        if self.done {
+            return None;
+        }
 
         let first = self.document_count == 0;
         self.document_count += 1;
+        // The documents before this one are not read again, so that a reader
+        // is not held in memory all at once.
+        self.parser.forget_input();
 
         let mut anchors = BTreeMap::new();
+        let mut next_id = 0;
         let mut document = Document {
             events: Vec::new(),
+            ends: Vec::new(),
             error: None,
             aliases: BTreeMap::new(),
+            skipped: HashSet::new(),
+            string_keys: RefCell::new(HashSet::new()),
+            unknown_tags: HashMap::new(),
+            input: match self.parser.input() {
+                Cow::Borrowed(input) => Some(input),
+                Cow::Owned(_) => None,
+            },
+            schema: self.schema,
+            preserve_number_repr: self.preserve_number_repr,
+            preserve_core_tags: self.preserve_core_tags,
+            preserve_scalar_style: self.preserve_scalar_style,
+            strict_numbers: self.strict_numbers,
+            integral_floats: self.integral_floats,
+            recursion_limit: self.recursion_limit,
+            repetition_limit: self.repetition_limit,
+            on_ignored_key: self.on_ignored_key.clone(),
+            source: None,
         };
+        let mut start = None;
+        // If duplicate or merge keys are looked for, each collection that
+        // the next node is in, with the keys so far if it is a mapping.
+        let check_keys = self.duplicate_keys != DuplicateKeyPolicy::Unchecked || self.merge_keys;
+        let mut keys: Vec<Option<Keys>> = Vec::new();
+        // Where each collection that the next node is in starts.
+        let mut collections = Vec::new();
+        // Where each node with a constructor for its tag starts that has not
+        // ended yet, with the name of the tag.
+        let mut constructing: Vec<(usize, String)> = Vec::new();
 
         loop {
-            let (event, mark) = match parser.next() {
+            let (event, mark) = match self.parser.next() {
                 Ok((event, mark)) => (event, mark),
                 Err(err) => {
-                    document.error = Some(Error::from(err).shared());
+                    let err = match self.parser.take_read_error() {
+                        Some(io_error) => error::new(ErrorImpl::Io(io_error)),
+                        None => self.excerpt(Error::from(err)),
+                    };
+                    document.error = Some(err.shared());
                     return Some(document);
                 }
             };
             let event = match event {
                 YamlEvent::StreamStart => continue,
                 YamlEvent::StreamEnd => {
-                    self.parser = None;
+                    self.done = true;
                     return if first {
                         if document.events.is_empty() {
                             document.events.push((Event::Void, mark));
+                            document.ends.push(mark);
                         }
                         Some(document)
                     } else {
                         None
                     };
                 }
-                YamlEvent::DocumentStart => continue,
-                YamlEvent::DocumentEnd => return Some(document),
-                YamlEvent::Alias(alias) => match anchors.get(&alias) {
-                    Some(id) => Event::Alias(*id),
-                    None => {
-                        document.error = Some(error::new(ErrorImpl::UnknownAnchor(mark)).shared());
-                        return Some(document);
+                YamlEvent::DocumentStart => {
+                    let start_index = mark.index() as usize;
+                    let mut content_start = self.parser.end();
+                    if content_start > start_index && document.input.is_some() {
+                        // The rest of the line of the `---`, if blank.
+                        let input = &self.parser.input()[content_start..];
+                        let blank = input
+                            .iter()
+                            .take_while(|&&b| b == b' ' || b == b'\t')
+                            .count();
+                        content_start += blank + line_break_len(input, blank);
                     }
-                },
+                    start = Some((start_index, content_start));
+                    continue;
+                }
+                YamlEvent::DocumentEnd => {
+                    if let (Some(_), Some((start, content_start))) = (document.input, start) {
+                        document.source = Some(Source {
+                            marked: start..self.parser.end(),
+                            content: content_start.min(mark.index() as usize)
+                                ..mark.index() as usize,
+                        });
+                    }
+                    return Some(document);
+                }
+                YamlEvent::Alias(alias) => {
+                    // An alias is not compared as a key.
+                    enter_node(&mut keys, document.events.len());
+                    match anchors.get(&alias) {
+                        Some(id) => Event::Alias(*id),
+                        // An id without a node in aliases.
+                        None if self.ignore_unknown_anchors => {
+                            next_id += 1;
+                            Event::Alias(next_id - 1)
+                        }
+                        None => {
+                            let err = error::new(ErrorImpl::UnknownAnchor(mark));
+                            document.error = Some(self.excerpt(err).shared());
+                            return Some(document);
+                        }
+                    }
+                }
                 YamlEvent::Scalar(mut scalar) => {
+                    if let Some(name) = self.tag_constructors.take(&mut scalar.tag) {
+                        constructing.push((document.events.len(), name));
+                    }
+                    if self.ignore_unknown_tags {
+                        set_aside_tag(&mut document, &mut scalar.tag);
+                    }
+                    let index = document.events.len();
+                    let is_key = enter_node(&mut keys, index);
+                    if let (true, Some(Some(mapping))) = (is_key, keys.last_mut()) {
+                        let merge = self.merge_keys
+                            && scalar.tag.is_none()
+                            && scalar.style == ScalarStyle::Plain
+                            && *scalar.value == *b"<<";
+                        let key = scalar_key(&scalar, &self.schema);
+                        let kept = if merge {
+                            mapping.at_merge = true;
+                            document.skipped.insert(index);
+                            index
+                        } else {
+                            *mapping.kept.entry(key).or_insert(index)
+                        };
+                        if kept != index {
+                            match self.duplicate_keys {
+                                DuplicateKeyPolicy::Unchecked => {}
+                                DuplicateKeyPolicy::Error => {
+                                    let key = String::from_utf8_lossy(&scalar.value).into_owned();
+                                    let span = Span {
+                                        start: mark,
+                                        end: self.parser.end_mark(),
+                                    };
+                                    let err = error::new(ErrorImpl::DuplicateKey(key, span));
+                                    document.error = Some(self.excerpt(err).shared());
+                                    return Some(document);
+                                }
+                                DuplicateKeyPolicy::FirstWins => {
+                                    document.skipped.insert(index);
+                                }
+                                DuplicateKeyPolicy::LastWins => {
+                                    document.skipped.insert(kept);
+                                    let key = scalar_key(&scalar, &self.schema);
+                                    mapping.kept.insert(key, index);
+                                }
+                            }
+                        }
+                    }
                     if let Some(anchor) = scalar.anchor.take() {
-                        let id = anchors.len();
+                        let id = next_id;
+                        next_id += 1;
                         anchors.insert(anchor, id);
                         document.aliases.insert(id, document.events.len());
                     }
+                    if let Some(hook) = &self.map_scalar {
+                        if let Err(err) = map_scalar(hook, &mut scalar, &self.schema) {
+                            let span = Span {
+                                start: mark,
+                                end: self.parser.end_mark(),
+                            };
+                            let err = error::fix_mark(err, span, Path::Root);
+                            document.error = Some(self.excerpt(err).shared());
+                            return Some(document);
+                        }
+                    }
                     Event::Scalar(scalar)
                 }
                 YamlEvent::SequenceStart(mut sequence_start) => {
+                    if let Some(name) = self.tag_constructors.take(&mut sequence_start.tag) {
+                        constructing.push((document.events.len(), name));
+                    }
+                    if self.ignore_unknown_tags {
+                        set_aside_tag(&mut document, &mut sequence_start.tag);
+                    }
+                    enter_node(&mut keys, document.events.len());
+                    if check_keys {
+                        keys.push(None);
+                    }
                     if let Some(anchor) = sequence_start.anchor.take() {
-                        let id = anchors.len();
+                        let id = next_id;
+                        next_id += 1;
                         anchors.insert(anchor, id);
                         document.aliases.insert(id, document.events.len());
                     }
                     Event::SequenceStart(sequence_start)
                 }
-                YamlEvent::SequenceEnd => Event::SequenceEnd,
+                YamlEvent::SequenceEnd => {
+                    keys.pop();
+                    Event::SequenceEnd
+                }
                 YamlEvent::MappingStart(mut mapping_start) => {
+                    if let Some(name) = self.tag_constructors.take(&mut mapping_start.tag) {
+                        constructing.push((document.events.len(), name));
+                    }
+                    if self.ignore_unknown_tags {
+                        set_aside_tag(&mut document, &mut mapping_start.tag);
+                    }
+                    enter_node(&mut keys, document.events.len());
+                    if check_keys {
+                        keys.push(Some(Keys {
+                            kept: HashMap::new(),
+                            at_key: true,
+                            at_merge: false,
+                            merges: Vec::new(),
+                        }));
+                    }
                     if let Some(anchor) = mapping_start.anchor.take() {
-                        let id = anchors.len();
+                        let id = next_id;
+                        next_id += 1;
                         anchors.insert(anchor, id);
                         document.aliases.insert(id, document.events.len());
                     }
                     Event::MappingStart(mapping_start)
                 }
-                YamlEvent::MappingEnd => Event::MappingEnd,
+                YamlEvent::MappingEnd => {
+                    if let Some(Some(mapping)) = keys.pop() {
+                        if let Err(err) = merge(&mut document, &mut next_id, mapping) {
+                            document.error = Some(self.excerpt(err).shared());
+                            return Some(document);
+                        }
+                    }
+                    Event::MappingEnd
+                }
             };
+            let mut end = self.parser.end_mark();
+            // The node that ends with this event.
+            let mut completed = None;
+            match event {
+                Event::Scalar(_) => completed = Some(document.events.len()),
+                Event::SequenceStart(_) | Event::MappingStart(_) => {
+                    collections.push(document.events.len());
+                }
+                Event::SequenceEnd | Event::MappingEnd => {
+                    // The end of a block collection is where the next token
+                    // starts, which can be lines after its last node.
+                    if let (true, Some(last)) = (end.index() == mark.index(), document.ends.last())
+                    {
+                        end = *last;
+                    }
+                    if let Some(start) = collections.pop() {
+                        document.ends[start] = end;
+                        completed = Some(start);
+                    }
+                }
+                _ => {}
+            }
             document.events.push((event, mark));
+            document.ends.push(end);
+            if let (Some(index), Some((start, _))) = (completed, constructing.last()) {
+                if index == *start {
+                    let (start, name) = constructing.pop().unwrap();
+                    if let Err(err) = self.construct(&mut document, &mut anchors, start, &name) {
+                        document.error = Some(self.excerpt(err).shared());
+                        return Some(document);
+                    }
+                }
+            }
         }
     }
+
+    // Replaces the node at this index, the last one in the events, with the
+    // value that the constructor of its tag makes of it.
+    #[cfg(feature = "value")]
+    fn construct(
+        &self,
+        document: &mut Document<'input>,
+        anchors: &mut BTreeMap<Anchor, usize>,
+        start: usize,
+        name: &str,
+    ) -> Result<()> {
+        let span = Span {
+            start: document.events[start].1,
+            end: document.ends[start],
+        };
+        let value = de::value_at(document, start)
+            .and_then(|value| self.tag_constructors.construct(name, value));
+        // The node is not deserialized without its value, in which case the
+        // error is where the events end.
+        document.events.truncate(start);
+        document.ends.truncate(start);
+        let value = value.map_err(|err| error::fix_mark(err, span, Path::Root))?;
+        // The anchors inside of the node are gone with it.
+        document.aliases.retain(|_, index| *index <= start);
+        anchors.retain(|_, id| document.aliases.contains_key(id));
+        document.skipped.retain(|index| *index <= start);
+        document.unknown_tags.retain(|index, _| *index < start);
+        push_value(document, value, None, span);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "value"))]
+    fn construct(
+        &self,
+        _document: &mut Document<'input>,
+        _anchors: &mut BTreeMap<Anchor, usize>,
+        _start: usize,
+        _name: &str,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    // Attaches the part of the input around the location of an error.
+    pub fn excerpt(&self, err: Error) -> Error {
+        error::with_excerpt_at(err, self.parser.input(), self.parser.input_start())
+    }
+}
+
+// Takes the tag of the next node out of it if the deserializer does not
+// resolve it, for `ignore_unknown_tags`.
+fn set_aside_tag(document: &mut Document, tag: &mut Option<Tag>) {
+    match tag.take() {
+        Some(unknown)
+            if ![
+                Tag::NULL,
+                Tag::BOOL,
+                Tag::INT,
+                Tag::FLOAT,
+                Tag::STR,
+                Tag::BINARY,
+                Tag::SEQ,
+                Tag::MAP,
+            ]
+            .iter()
+            .any(|known| unknown == **known) =>
+        {
+            document.unknown_tags.insert(document.events.len(), unknown);
+        }
+        known => *tag = known,
+    }
+}
+
+// Adds the events of a value from a constructor, all at the location of the
+// node that it replaces. Strings are quoted so that they stay strings, and
+// tags are written the way that the serializer writes them.
+#[cfg(feature = "value")]
+fn push_value(document: &mut Document, value: Value, tag: Option<Tag>, span: Span) {
+    let (text, style) = match value {
+        Value::Null => ("null".to_owned(), ScalarStyle::Plain),
+        Value::Bool(b) => (b.to_string(), ScalarStyle::Plain),
+        Value::Number(n) => (n.to_string(), ScalarStyle::Plain),
+        Value::String(s) => (s, ScalarStyle::DoubleQuoted),
+        Value::Styled(styled) => match styled.style {
+            ScalarStyle::Plain => (styled.value, ScalarStyle::DoubleQuoted),
+            style => (styled.value, style),
+        },
+        Value::Sequence(sequence) => {
+            let start = Event::SequenceStart(SequenceStart {
+                anchor: None,
+                tag,
+                flow: false,
+            });
+            push_event(document, start, span);
+            for element in sequence {
+                push_value(document, element, None, span);
+            }
+            push_event(document, Event::SequenceEnd, span);
+            return;
+        }
+        Value::Mapping(mapping) => {
+            let start = Event::MappingStart(MappingStart {
+                anchor: None,
+                tag,
+                flow: false,
+            });
+            push_event(document, start, span);
+            for (key, value) in mapping {
+                push_value(document, key, None, span);
+                push_value(document, value, None, span);
+            }
+            push_event(document, Event::MappingEnd, span);
+            return;
+        }
+        Value::Tagged(tagged) => {
+            let mut name = tagged.tag.string;
+            if !name.starts_with('!') && !name.starts_with("tag:yaml.org,2002:") {
+                name.insert(0, '!');
+            }
+            push_value(document, tagged.value, Some(Tag::new(name)), span);
+            return;
+        }
+    };
+    let scalar = Scalar {
+        anchor: None,
+        tag,
+        value: text.into_bytes().into_boxed_slice(),
+        style,
+        repr: None,
+    };
+    push_event(document, Event::Scalar(scalar), span);
+}
+
+#[cfg(feature = "value")]
+fn push_event(document: &mut Document, event: Event<'static>, span: Span) {
+    document.events.push((event, span.start));
+    document.ends.push(span.end);
+}
+
+// A mapping that the next node is in.
+struct Keys {
+    // The scalar keys so far, each with the index of the one that is kept.
+    kept: HashMap<ScalarKey, usize>,
+    // Whether the next node is a key.
+    at_key: bool,
+    // Whether the next node is the value of a merge key.
+    at_merge: bool,
+    // Where the value of each merge key is in the events.
+    merges: Vec<usize>,
+}
+
+// Whether the node at this index of the events is a key of a mapping, going
+// on to the node after it.
+fn enter_node(keys: &mut [Option<Keys>], index: usize) -> bool {
+    match keys.last_mut() {
+        Some(Some(mapping)) => {
+            let is_key = mapping.at_key;
+            mapping.at_key = !is_key;
+            if !is_key && mapping.at_merge {
+                mapping.at_merge = false;
+                mapping.merges.push(index);
+            }
+            is_key
+        }
+        _ => false,
+    }
+}
+
+// Adds the entries of the mappings that the merge keys of a mapping refer to
+// before its end, other than those with a key that it has already. Earlier
+// mappings in a sequence of them take precedence, as in Value::apply_merge.
+// The entries are a copy of the key and an alias to the value.
+fn merge(document: &mut Document, next_id: &mut usize, mut mapping: Keys) -> Result<()> {
+    for merge in mapping.merges {
+        let mark = document.events[merge].1;
+        let span = Span {
+            start: mark,
+            end: document.ends[merge],
+        };
+        let merge_error = |unexpected| {
+            let err = <Error as serde::de::Error>::custom(error::new(unexpected));
+            error::fix_mark(err, span, Path::Root)
+        };
+        let mut sources = Vec::new();
+        let target = alias_target(document, merge)?;
+        match &document.events[target].0 {
+            Event::MappingStart(start) if start.tag.is_none() => sources.push(target),
+            Event::SequenceStart(start) if start.tag.is_none() => {
+                let mut element = target + 1;
+                loop {
+                    let unexpected = match &document.events[element].0 {
+                        Event::SequenceEnd => break,
+                        Event::Alias(_) | Event::MappingStart(_) => {
+                            let source = alias_target(document, element)?;
+                            match &document.events[source].0 {
+                                Event::MappingStart(start) if start.tag.is_none() => {
+                                    sources.push(source);
+                                    element = node_end(document, element)
+                                        .ok_or_else(|| merge_error(ErrorImpl::ScalarInMerge))?;
+                                    continue;
+                                }
+                                Event::MappingStart(_) | Event::SequenceStart(_) => {
+                                    ErrorImpl::TaggedInMerge
+                                }
+                                _ => ErrorImpl::ScalarInMergeElement,
+                            }
+                        }
+                        Event::SequenceStart(start) if start.tag.is_none() => {
+                            ErrorImpl::SequenceInMergeElement
+                        }
+                        Event::SequenceStart(_) => ErrorImpl::TaggedInMerge,
+                        _ => ErrorImpl::ScalarInMergeElement,
+                    };
+                    return Err(merge_error(unexpected));
+                }
+            }
+            Event::MappingStart(_) | Event::SequenceStart(_) => {
+                return Err(merge_error(ErrorImpl::TaggedInMerge));
+            }
+            _ => return Err(merge_error(ErrorImpl::ScalarInMerge)),
+        }
+        for source in sources {
+            let mut entries = Vec::new();
+            let mut key = source + 1;
+            while !matches!(document.events.get(key), Some((Event::MappingEnd, _))) {
+                // A mapping that is merged into itself does not have an end.
+                let value = node_end(document, key);
+                let next = value.and_then(|value| node_end(document, value));
+                let (value, next) = match (value, next) {
+                    (Some(value), Some(next)) => (value, next),
+                    _ => return Err(error::new(ErrorImpl::RecursionLimitExceeded(mark))),
+                };
+                if !document.skipped.contains(&key) {
+                    entries.push((key, value));
+                }
+                key = next;
+            }
+            for (key, value) in entries {
+                let key_mark = document.events[key].1;
+                let key_event = match &document.events[key].0 {
+                    Event::Scalar(scalar) => {
+                        let kept = scalar_key(scalar, &document.schema);
+                        if mapping.kept.contains_key(&kept) {
+                            continue;
+                        }
+                        mapping.kept.insert(kept, document.events.len());
+                        Event::Scalar(scalar.clone())
+                    }
+                    _ => alias_to(document, next_id, key),
+                };
+                document.events.push((key_event, key_mark));
+                document.ends.push(document.ends[key]);
+                let value_event = alias_to(document, next_id, value);
+                let value_mark = document.events[value].1;
+                document.events.push((value_event, value_mark));
+                document.ends.push(document.ends[value]);
+            }
+        }
+    }
+    Ok(())
+}
+
+// The index of the node that the event at this index is or refers to, which
+// is unknown for an alias with `ignore_unknown_anchors`.
+fn alias_target(document: &Document, index: usize) -> Result<usize> {
+    match document.events[index].0 {
+        Event::Alias(id) => match document.aliases.get(&id) {
+            Some(target) => Ok(*target),
+            None => Err(error::new(ErrorImpl::UnknownAnchor(
+                document.events[index].1,
+            ))),
+        },
+        _ => Ok(index),
+    }
+}
+
+// An alias to the node at this index, which is the same alias if it is one.
+fn alias_to(document: &mut Document, next_id: &mut usize, index: usize) -> Event<'static> {
+    if let Event::Alias(id) = document.events[index].0 {
+        return Event::Alias(id);
+    }
+    let id = *next_id;
+    *next_id += 1;
+    document.aliases.insert(id, index);
+    Event::Alias(id)
+}
+
+// The index after the node at this index, if it has ended.
+pub(crate) fn node_end(document: &Document, index: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, (event, _mark)) in document.events.iter().enumerate().skip(index) {
+        match event {
+            Event::SequenceStart(_) | Event::MappingStart(_) => depth += 1,
+            Event::SequenceEnd | Event::MappingEnd => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+fn map_scalar(hook: &ScalarHook, scalar: &mut Scalar, schema: &CoercionFlags) -> Result<()> {
+    if !de::is_string_scalar(scalar, schema) {
+        return Ok(());
+    }
+    let raw = String::from_utf8_lossy(&scalar.value);
+    if let Some(value) = hook.call(&raw, scalar.style)? {
+        scalar.value = value.into_bytes().into_boxed_slice();
+        // The scalar can no longer be borrowed from the input.
+        scalar.repr = None;
+    }
+    Ok(())
 }
@@ -1,16 +1,61 @@
 use crate::de;
 use crate::error::{self, Error, ErrorImpl};
+use crate::schema::CoercionFlags;
 use serde::de::{Unexpected, Visitor};
 use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::fmt::{self, Display};
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 /// Represents a YAML number, whether integer or floating point.
-#[derive(Clone, PartialEq, PartialOrd)]
+///
+/// Integers are kept exactly from `i128::MIN` to `u128::MAX`. Numbers compare
+/// equal if they have the same value, whichever way they were written.
+#[derive(Clone)]
 pub struct Number {
-    n: N,
+    n: Stored,
+}
+
+/// The base that an integer was written in, returned by [`Number::radix`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Radix {
+    /// Base 2, as in `0b101`.
+    Binary,
+    /// Base 8, as in `0o17`, or `017` under [`SchemaProfile::Legacy11`].
+    ///
+    /// [`SchemaProfile::Legacy11`]: crate::SchemaProfile::Legacy11
+    Octal,
+    /// Base 10.
+    Decimal,
+    /// Base 16, as in `0x1F`.
+    Hexadecimal,
+    /// Base 60, as in `1:30` under [`SchemaProfile::Legacy11`].
+    ///
+    /// [`SchemaProfile::Legacy11`]: crate::SchemaProfile::Legacy11
+    Sexagesimal,
+}
+
+thread_local! {
+    // The text of the scalar that a deserializer with `preserve_number_repr`
+    // is passing to a visitor, for a Number created by that visitor to keep.
+    static PARSED_REPR: RefCell<Option<Box<str>>> = const { RefCell::new(None) };
+    // The text of the Number being serialized, for a YAML serializer to write
+    // instead of formatting the value. Other serializers never look at it.
+    static SERIALIZED_REPR: RefCell<Option<Box<str>>> = const { RefCell::new(None) };
+}
+
+// Makes `repr` available to Numbers created during `f`.
+pub(crate) fn with_parsed_repr<T>(repr: &str, f: impl FnOnce() -> T) -> T {
+    PARSED_REPR.with(|parsed| *parsed.borrow_mut() = Some(Box::from(repr)));
+    let result = f();
+    PARSED_REPR.with(|parsed| *parsed.borrow_mut() = None);
+    result
+}
+
+pub(crate) fn take_serialized_repr() -> Option<Box<str>> {
+    SERIALIZED_REPR.with(|serialized| serialized.borrow_mut().take())
 }
 
 // "N" is a prefix of "NegInt"... this is a false positive.
@@ -18,11 +63,59 @@ pub struct Number {
 #[allow(clippy::enum_variant_names)]
 #[derive(Copy, Clone)]
 enum N {
+    PosInt(u128),
+    /// Always less than zero.
+    NegInt(i128),
+    /// May be infinite or NaN.
+    Float(f64),
+}
+
+// How a Number is kept: values that fit in 64 bits inline, and the others,
+// or ones with the text they were written as, in a box, so that Number, and
+// with it Value, is no bigger than when every number fit in 64 bits.
+#[derive(Clone)]
+enum Stored {
     PosInt(u64),
     /// Always less than zero.
     NegInt(i64),
-    /// May be infinite or NaN.
     Float(f64),
+    Wide(Box<Wide>),
+}
+
+#[derive(Clone)]
+struct Wide {
+    n: N,
+    // As written in the input, for a number read by a deserializer with
+    // `preserve_number_repr(true)`.
+    repr: Option<Box<str>>,
+}
+
+impl Number {
+    fn new(n: N, repr: Option<Box<str>>) -> Self {
+        let n = match (n, repr) {
+            (N::PosInt(u), None) if u <= u128::from(u64::MAX) => Stored::PosInt(u as u64),
+            (N::NegInt(i), None) if i >= i128::from(i64::MIN) => Stored::NegInt(i as i64),
+            (N::Float(f), None) => Stored::Float(f),
+            (n, repr) => Stored::Wide(Box::new(Wide { n, repr })),
+        };
+        Number { n }
+    }
+
+    fn n(&self) -> N {
+        match &self.n {
+            Stored::PosInt(u) => N::PosInt(u128::from(*u)),
+            Stored::NegInt(i) => N::NegInt(i128::from(*i)),
+            Stored::Float(f) => N::Float(*f),
+            Stored::Wide(wide) => wide.n,
+        }
+    }
+
+    fn repr(&self) -> Option<&str> {
+        match &self.n {
+            Stored::Wide(wide) => wide.repr.as_deref(),
+            Stored::PosInt(_) | Stored::NegInt(_) | Stored::Float(_) => None,
+        }
+    }
 }
 
 impl Number {
@@ -52,13 +145,8 @@ impl Number {
     /// # }
     /// ```
     #[inline]
-    #[allow(clippy::cast_sign_loss)]
     pub fn is_i64(&self) -> bool {
-        match self.n {
-            N::PosInt(v) => v <= i64::max_value() as u64,
-            N::NegInt(_) => true,
-            N::Float(_) => false,
-        }
+        self.as_i64().is_some()
     }
 
     /// Returns true if the `Number` is an integer between zero and `u64::MAX`.
@@ -86,10 +174,7 @@ impl Number {
     /// ```
     #[inline]
     pub fn is_u64(&self) -> bool {
-        match self.n {
-            N::PosInt(_) => true,
-            N::NegInt(_) | N::Float(_) => false,
-        }
+        self.as_u64().is_some()
     }
 
     /// Returns true if the `Number` can be represented by f64.
@@ -97,8 +182,8 @@ impl Number {
     /// For any Number on which `is_f64` returns true, `as_f64` is guaranteed to
     /// return the floating point value.
     ///
-    /// Currently this function returns true if and only if both `is_i64` and
-    /// `is_u64` return false but this is not a guarantee in the future.
+    /// Currently this function returns true if and only if the `Number` is
+    /// not an integer but this is not a guarantee in the future.
     ///
     /// ```
     /// # fn main() -> serde_yaml::Result<()> {
@@ -118,7 +203,7 @@ impl Number {
     /// ```
     #[inline]
     pub fn is_f64(&self) -> bool {
-        match self.n {
+        match self.n() {
             N::Float(_) => true,
             N::PosInt(_) | N::NegInt(_) => false,
         }
@@ -144,17 +229,7 @@ impl Number {
     /// ```
     #[inline]
     pub fn as_i64(&self) -> Option<i64> {
-        match self.n {
-            N::PosInt(n) => {
-                if n <= i64::max_value() as u64 {
-                    Some(n as i64)
-                } else {
-                    None
-                }
-            }
-            N::NegInt(n) => Some(n),
-            N::Float(_) => None,
-        }
+        self.as_i128().and_then(|n| i64::try_from(n).ok())
     }
 
     /// If the `Number` is an integer, represent it as u64 if possible. Returns
@@ -176,7 +251,55 @@ impl Number {
     /// ```
     #[inline]
     pub fn as_u64(&self) -> Option<u64> {
-        match self.n {
+        self.as_u128().and_then(|n| u64::try_from(n).ok())
+    }
+
+    /// If the `Number` is an integer, represent it as i128 if possible.
+    /// Returns None otherwise.
+    ///
+    /// ```
+    /// # fn main() -> serde_yaml::Result<()> {
+    /// let v: serde_yaml::Value = serde_yaml::from_str(r#"
+    /// a: -170141183460469231731687303715884105728
+    /// b: 170141183460469231731687303715884105728
+    /// c: 256.0
+    /// "#)?;
+    ///
+    /// assert_eq!(v["a"].as_i128(), Some(i128::MIN));
+    /// assert_eq!(v["b"].as_i128(), None);
+    /// assert_eq!(v["c"].as_i128(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn as_i128(&self) -> Option<i128> {
+        match self.n() {
+            N::PosInt(n) => i128::try_from(n).ok(),
+            N::NegInt(n) => Some(n),
+            N::Float(_) => None,
+        }
+    }
+
+    /// If the `Number` is an integer, represent it as u128 if possible.
+    /// Returns None otherwise.
+    ///
+    /// ```
+    /// # fn main() -> serde_yaml::Result<()> {
+    /// let v: serde_yaml::Value = serde_yaml::from_str(r#"
+    /// a: 340282366920938463463374607431768211455
+    /// b: -64
+    /// c: 256.0
+    /// "#)?;
+    ///
+    /// assert_eq!(v["a"].as_u128(), Some(u128::MAX));
+    /// assert_eq!(v["b"].as_u128(), None);
+    /// assert_eq!(v["c"].as_u128(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn as_u128(&self) -> Option<u128> {
+        match self.n() {
             N::PosInt(n) => Some(n),
             N::NegInt(_) | N::Float(_) => None,
         }
@@ -214,7 +337,7 @@ impl Number {
     /// ```
     #[inline]
     pub fn as_f64(&self) -> Option<f64> {
-        match self.n {
+        match self.n() {
             N::PosInt(n) => Some(n as f64),
             N::NegInt(n) => Some(n as f64),
             N::Float(n) => Some(n),
@@ -238,7 +361,7 @@ impl Number {
     /// ```
     #[inline]
     pub fn is_nan(&self) -> bool {
-        match self.n {
+        match self.n() {
             N::PosInt(_) | N::NegInt(_) => false,
             N::Float(f) => f.is_nan(),
         }
@@ -262,7 +385,7 @@ impl Number {
     /// ```
     #[inline]
     pub fn is_infinite(&self) -> bool {
-        match self.n {
+        match self.n() {
             N::PosInt(_) | N::NegInt(_) => false,
             N::Float(f) => f.is_infinite(),
         }
@@ -285,16 +408,104 @@ impl Number {
     /// ```
     #[inline]
     pub fn is_finite(&self) -> bool {
-        match self.n {
+        match self.n() {
             N::PosInt(_) | N::NegInt(_) => true,
             N::Float(f) => f.is_finite(),
         }
     }
 }
 
+impl Number {
+    /// The number as it was written in the input, like `0x1F` or `1e3`.
+    ///
+    /// This is only recorded by a deserializer with
+    /// [`preserve_number_repr(true)`], and the serializer writes the number
+    /// the same way again. A number created in any other way, including by
+    /// arithmetic on the value of one that was read, has none.
+    ///
+    /// [`preserve_number_repr(true)`]: crate::DeserializerBuilder::preserve_number_repr
+    ///
+    /// ```
+    /// use serde_yaml::{DeserializerBuilder, Number};
+    ///
+    /// let builder = DeserializerBuilder::new().preserve_number_repr(true);
+    /// let numbers: Vec<Number> = builder.from_str("[0x1F, 31, 1e3]").unwrap();
+    /// assert_eq!(numbers[0].original_repr(), Some("0x1F"));
+    /// assert_eq!(numbers[0], Number::from(31));
+    /// assert_eq!(serde_yaml::to_string(&numbers).unwrap(), "- 0x1F\n- 31\n- 1e3\n");
+    /// ```
+    pub fn original_repr(&self) -> Option<&str> {
+        self.repr()
+    }
+
+    /// The base that an integer was written in, if it has an
+    /// [`original_repr`][Number::original_repr]. `None` for floats.
+    ///
+    /// ```
+    /// use serde_yaml::value::Radix;
+    /// use serde_yaml::{DeserializerBuilder, Number};
+    ///
+    /// let builder = DeserializerBuilder::new().preserve_number_repr(true);
+    /// let numbers: Vec<Number> = builder.from_str("[0o17, -0x1F, 15.0]").unwrap();
+    /// assert_eq!(numbers[0].radix(), Some(Radix::Octal));
+    /// assert_eq!(numbers[1].radix(), Some(Radix::Hexadecimal));
+    /// assert_eq!(numbers[2].radix(), None);
+    /// ```
+    pub fn radix(&self) -> Option<Radix> {
+        let repr = self.repr()?;
+        if let N::Float(_) = self.n() {
+            return None;
+        }
+        let unsigned = repr.strip_prefix(['+', '-']).unwrap_or(repr);
+        Some(if unsigned.starts_with("0x") {
+            Radix::Hexadecimal
+        } else if unsigned.starts_with("0o") {
+            Radix::Octal
+        } else if unsigned.starts_with("0b") {
+            Radix::Binary
+        } else if unsigned.contains(':') {
+            Radix::Sexagesimal
+        } else if unsigned.len() > 1 && unsigned.starts_with('0') {
+            Radix::Octal
+        } else {
+            Radix::Decimal
+        })
+    }
+
+    // Keeps the text of the scalar being deserialized, if there is one.
+    pub(crate) fn with_repr(self) -> Self {
+        match PARSED_REPR.with(|parsed| parsed.borrow_mut().take()) {
+            Some(repr) => Number::new(self.n(), Some(repr)),
+            None => self,
+        }
+    }
+
+    // The original representation, if the core schema reads it back as the
+    // same number. Forms of other schemas, like `1_000`, are not kept.
+    fn valid_repr(&self) -> Option<&str> {
+        let repr = self.repr()?;
+        match Number::from_str(repr) {
+            Ok(number) if number == *self => Some(repr),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.n() == other.n()
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        self.n().partial_cmp(&other.n())
+    }
+}
+
 impl Display for Number {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        match self.n {
+        match self.n() {
             N::PosInt(i) => formatter.write_str(itoa::Buffer::new().format(i)),
             N::NegInt(i) => formatter.write_str(itoa::Buffer::new().format(i)),
             N::Float(f) if f.is_nan() => formatter.write_str(".nan"),
@@ -314,11 +525,11 @@ impl FromStr for Number {
     type Err = Error;
 
     fn from_str(repr: &str) -> Result<Self, Self::Err> {
-        if let Ok(result) = de::visit_int(NumberVisitor, repr) {
+        if let Ok(result) = de::visit_int(NumberVisitor, repr, &CoercionFlags::CORE_12) {
             return result;
         }
         if !de::digits_but_not_number(repr) {
-            if let Some(float) = de::parse_f64(repr) {
+            if let Some(float) = de::parse_f64(repr, &CoercionFlags::CORE_12) {
                 return Ok(float.into());
             }
         }
@@ -387,9 +598,28 @@ impl N {
     }
 }
 
+impl N {
+    fn deserialize_any<'de, V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            N::PosInt(i) => match u64::try_from(i) {
+                Ok(i) => visitor.visit_u64(i),
+                Err(_) => visitor.visit_u128(i),
+            },
+            N::NegInt(i) => match i64::try_from(i) {
+                Ok(i) => visitor.visit_i64(i),
+                Err(_) => visitor.visit_i128(i),
+            },
+            N::Float(f) => visitor.visit_f64(f),
+        }
+    }
+}
+
 impl Number {
     pub(crate) fn total_cmp(&self, other: &Self) -> Ordering {
-        self.n.total_cmp(&other.n)
+        self.n().total_cmp(&other.n())
     }
 }
 
@@ -399,11 +629,24 @@ impl Serialize for Number {
     where
         S: Serializer,
     {
-        match self.n {
-            N::PosInt(i) => serializer.serialize_u64(i),
-            N::NegInt(i) => serializer.serialize_i64(i),
-            N::Float(f) => serializer.serialize_f64(f),
+        if let Some(repr) = self.valid_repr() {
+            SERIALIZED_REPR.with(|serialized| *serialized.borrow_mut() = Some(Box::from(repr)));
         }
+        // Integers that fit in 64 bits are serialized as such, for
+        // serializers that do not support 128-bit integers.
+        let result = match self.n() {
+            N::PosInt(i) => match u64::try_from(i) {
+                Ok(i) => serializer.serialize_u64(i),
+                Err(_) => serializer.serialize_u128(i),
+            },
+            N::NegInt(i) => match i64::try_from(i) {
+                Ok(i) => serializer.serialize_i64(i),
+                Err(_) => serializer.serialize_i128(i),
+            },
+            N::Float(f) => serializer.serialize_f64(f),
+        };
+        SERIALIZED_REPR.with(|serialized| *serialized.borrow_mut() = None);
+        result
     }
 }
 
@@ -418,17 +661,27 @@ impl<'de> Visitor<'de> for NumberVisitor {
 
     #[inline]
     fn visit_i64<E>(self, value: i64) -> Result<Number, E> {
-        Ok(value.into())
+        Ok(Number::from(value).with_repr())
     }
 
     #[inline]
     fn visit_u64<E>(self, value: u64) -> Result<Number, E> {
-        Ok(value.into())
+        Ok(Number::from(value).with_repr())
+    }
+
+    #[inline]
+    fn visit_i128<E>(self, value: i128) -> Result<Number, E> {
+        Ok(Number::from(value).with_repr())
+    }
+
+    #[inline]
+    fn visit_u128<E>(self, value: u128) -> Result<Number, E> {
+        Ok(Number::from(value).with_repr())
     }
 
     #[inline]
     fn visit_f64<E>(self, value: f64) -> Result<Number, E> {
-        Ok(value.into())
+        Ok(Number::from(value).with_repr())
     }
 }
 
@@ -450,11 +703,7 @@ impl<'de> Deserializer<'de> for Number {
     where
         V: Visitor<'de>,
     {
-        match self.n {
-            N::PosInt(i) => visitor.visit_u64(i),
-            N::NegInt(i) => visitor.visit_i64(i),
-            N::Float(f) => visitor.visit_f64(f),
-        }
+        self.n().deserialize_any(visitor)
     }
 
     forward_to_deserialize_any! {
@@ -472,11 +721,7 @@ impl<'de, 'a> Deserializer<'de> for &'a Number {
     where
         V: Visitor<'de>,
     {
-        match self.n {
-            N::PosInt(i) => visitor.visit_u64(i),
-            N::NegInt(i) => visitor.visit_i64(i),
-            N::Float(f) => visitor.visit_f64(f),
-        }
+        self.n().deserialize_any(visitor)
     }
 
     forward_to_deserialize_any! {
@@ -494,9 +739,9 @@ macro_rules! from_signed {
                 #[allow(clippy::cast_sign_loss)]
                 fn from(i: $signed_ty) -> Self {
                     if i < 0 {
-                        Number { n: N::NegInt(i as i64) }
+                        Number::new(N::NegInt(i as i128), None)
                     } else {
-                        Number { n: N::PosInt(i as u64) }
+                        Number::new(N::PosInt(i as u128), None)
                     }
                 }
             }
@@ -510,7 +755,7 @@ macro_rules! from_unsigned {
             impl From<$unsigned_ty> for Number {
                 #[inline]
                 fn from(u: $unsigned_ty) -> Self {
-                    Number { n: N::PosInt(u as u64) }
+                    Number::new(N::PosInt(u as u128), None)
                 }
             }
         )*
@@ -523,15 +768,15 @@ macro_rules! from_float {
             impl From<$float_ty> for Number {
                 #[inline]
                 fn from(f: $float_ty) -> Self {
-                    Number { n: N::Float(f as f64) }
+                    Number::new(N::Float(f as f64), None)
                 }
             }
         )*
     }
 }
 
-from_signed!(i8 i16 i32 i64 isize);
-from_unsigned!(u8 u16 u32 u64 usize);
+from_signed!(i8 i16 i32 i64 i128 isize);
+from_unsigned!(u8 u16 u32 u64 u128 usize);
 from_float!(f32 f64);
 
 // This is fine, because we don't _really_ implement hash for floats
@@ -539,7 +784,7 @@ from_float!(f32 f64);
 #[allow(clippy::derived_hash_with_manual_eq)]
 impl Hash for Number {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        match self.n {
+        match self.n() {
             N::Float(_) => {
                 // you should feel bad for using f64 as a map key
                 3.hash(state);
@@ -551,9 +796,15 @@ impl Hash for Number {
 }
 
 pub(crate) fn unexpected(number: &Number) -> Unexpected {
-    match number.n {
-        N::PosInt(u) => Unexpected::Unsigned(u),
-        N::NegInt(i) => Unexpected::Signed(i),
+    match number.n() {
+        N::PosInt(u) => match u64::try_from(u) {
+            Ok(u) => Unexpected::Unsigned(u),
+            Err(_) => Unexpected::Other("128-bit integer"),
+        },
+        N::NegInt(i) => match i64::try_from(i) {
+            Ok(i) => Unexpected::Signed(i),
+            Err(_) => Unexpected::Other("128-bit integer"),
+        },
         N::Float(f) => Unexpected::Float(f),
     }
 }
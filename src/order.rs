@@ -0,0 +1,131 @@
+use crate::de::Event;
+use crate::libyaml::error::Mark;
+#[cfg(feature = "value")]
+use crate::ser::PathSegment;
+use std::collections::BTreeMap;
+
+/// The order of the keys of every mapping in a document, as returned by
+/// [`from_str_with_order`][crate::from_str_with_order].
+///
+/// Given to [`SerializerBuilder::key_order`], a serializer writes the entries
+/// of each mapping in the order that its keys had in the document, also for
+/// types like `HashMap` that have no order of their own. This makes it
+/// possible to read a document, change some of it, and write it out again
+/// with its entries where they were.
+///
+/// [`SerializerBuilder::key_order`]: crate::SerializerBuilder::key_order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyOrder {
+    // The keys of each mapping, by the path to the mapping from the root.
+    mappings: BTreeMap<Vec<Step>, Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    // Scalar keys as they are written, and other keys as `?`, like the path
+    // segments of the serializer.
+    Key(String),
+    Index(usize),
+}
+
+enum Frame {
+    Sequence(usize),
+    Mapping {
+        keys: Vec<String>,
+        // Whether the next node is a key rather than a value.
+        at_key: bool,
+        // Nesting of the collections inside of the current key.
+        key_depth: usize,
+    },
+}
+
+impl KeyOrder {
+    /// An empty key order, which leaves the order of every mapping as it is
+    /// serialized.
+    pub fn new() -> Self {
+        KeyOrder::default()
+    }
+
+    pub(crate) fn record(events: &[(Event, Mark)]) -> Self {
+        let mut order = KeyOrder::new();
+        let mut stack = Vec::new();
+        for (event, _mark) in events {
+            if let Some(Frame::Mapping { key_depth, .. }) = stack.last_mut() {
+                if *key_depth > 0 {
+                    match event {
+                        Event::SequenceStart(_) | Event::MappingStart(_) => *key_depth += 1,
+                        Event::SequenceEnd | Event::MappingEnd => *key_depth -= 1,
+                        Event::Alias(_) | Event::Scalar(_) | Event::Void => {}
+                    }
+                    continue;
+                }
+            }
+            let frame = match event {
+                Event::SequenceEnd | Event::MappingEnd => {
+                    if let Some(Frame::Mapping { keys, .. }) = stack.pop() {
+                        order.mappings.insert(path(&stack), keys);
+                    }
+                    continue;
+                }
+                Event::SequenceStart(_) => Some(Frame::Sequence(0)),
+                Event::MappingStart(_) => Some(Frame::Mapping {
+                    keys: Vec::new(),
+                    at_key: true,
+                    key_depth: 0,
+                }),
+                Event::Alias(_) | Event::Scalar(_) | Event::Void => None,
+            };
+            match stack.last_mut() {
+                Some(Frame::Sequence(len)) => *len += 1,
+                Some(Frame::Mapping {
+                    keys,
+                    at_key,
+                    key_depth,
+                }) => {
+                    if *at_key {
+                        keys.push(match event {
+                            Event::Scalar(scalar) => {
+                                String::from_utf8_lossy(&scalar.value).into_owned()
+                            }
+                            _ => "?".to_owned(),
+                        });
+                    }
+                    *at_key = !*at_key;
+                    // The contents of a key are not part of the path of
+                    // any mapping.
+                    if !*at_key && frame.is_some() {
+                        *key_depth = 1;
+                        continue;
+                    }
+                }
+                None => {}
+            }
+            stack.extend(frame);
+        }
+        order
+    }
+
+    // The keys of the mapping at the given path, in the order they were read.
+    #[cfg(feature = "value")]
+    pub(crate) fn keys(&self, path: &[PathSegment]) -> Option<&[String]> {
+        let path: Vec<Step> = path
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Key(key) => Step::Key(key.clone()),
+                PathSegment::Index(index) => Step::Index(*index),
+            })
+            .collect();
+        self.mappings.get(&path).map(Vec::as_slice)
+    }
+}
+
+// The path to the node that is being started in the innermost collection.
+fn path(stack: &[Frame]) -> Vec<Step> {
+    stack
+        .iter()
+        .map(|frame| match frame {
+            Frame::Sequence(len) => Step::Index(len - 1),
+            Frame::Mapping { keys, .. } => Step::Key(keys.last().cloned().unwrap_or_default()),
+        })
+        .collect()
+}
@@ -0,0 +1,143 @@
+use crate::error::{self, Error, ErrorImpl, Result};
+use crate::libyaml::parser::{Event, Parser};
+use std::borrow::Cow;
+
+// Number of top-level keys of a mapping collected by peek_root.
+const MAX_KEYS: usize = 8;
+
+/// The kind of YAML node at the root of a document.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NodeKind {
+    /// A scalar, including the null of an empty document.
+    Scalar,
+    /// A sequence.
+    Sequence,
+    /// A mapping.
+    Mapping,
+}
+
+/// A summary of the root node of a YAML document, returned by
+/// [`peek_root`].
+#[derive(Clone, Debug)]
+pub struct RootInfo {
+    kind: NodeKind,
+    tag: Option<String>,
+    anchor: Option<String>,
+    keys: Vec<String>,
+}
+
+impl RootInfo {
+    /// The kind of the root node.
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// The tag of the root node as written, like `!Config`, if it has one.
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// The anchor of the root node, without the `&`, if it has one.
+    pub fn anchor(&self) -> Option<&str> {
+        self.anchor.as_deref()
+    }
+
+    /// The first few scalar keys of a root mapping, in the order they are
+    /// written. Complex keys are skipped. Empty for other kinds of node.
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
+
+/// Looks at the root node of the first document in a string of YAML text,
+/// reading only as much of the input as needed.
+///
+/// For a mapping, up to 8 top-level keys are collected. Values of those
+/// entries are still parsed to find the next key, but nothing beyond the
+/// last collected key is read, so syntax errors later in the input are not
+/// reported.
+///
+/// ```
+/// use serde_yaml::NodeKind;
+///
+/// let yaml = "kind: Deployment\nspec:\n  replicas: 3\n";
+/// let root = serde_yaml::peek_root(yaml).unwrap();
+/// assert_eq!(root.kind(), NodeKind::Mapping);
+/// assert_eq!(root.keys(), ["kind", "spec"]);
+/// ```
+pub fn peek_root(s: &str) -> Result<RootInfo> {
+    let mut parser = Parser::new(Cow::Borrowed(s.as_bytes()));
+    let mut next = move || parser.next().map_err(Error::from);
+
+    let (event, mark) = loop {
+        match next()? {
+            (Event::StreamStart, _) | (Event::DocumentStart, _) => {}
+            (Event::StreamEnd, _) => {
+                return Ok(RootInfo {
+                    kind: NodeKind::Scalar,
+                    tag: None,
+                    anchor: None,
+                    keys: Vec::new(),
+                });
+            }
+            event => break event,
+        }
+    };
+
+    let (kind, tag, anchor) = match event {
+        Event::Scalar(scalar) => (NodeKind::Scalar, scalar.tag, scalar.anchor),
+        Event::SequenceStart(sequence) => (NodeKind::Sequence, sequence.tag, sequence.anchor),
+        Event::MappingStart(mapping) => (NodeKind::Mapping, mapping.tag, mapping.anchor),
+        Event::Alias(_) => return Err(error::new(ErrorImpl::UnknownAnchor(mark))),
+        Event::StreamStart
+        | Event::StreamEnd
+        | Event::DocumentStart
+        | Event::DocumentEnd
+        | Event::SequenceEnd
+        | Event::MappingEnd => unreachable!(),
+    };
+
+    let mut keys = Vec::new();
+    if kind == NodeKind::Mapping {
+        // Depth of nesting below the root mapping, and whether the next node
+        // at depth 0 is a key.
+        let mut depth = 0usize;
+        let mut at_key = true;
+        while keys.len() < MAX_KEYS {
+            let (event, _mark) = next()?;
+            let is_key = depth == 0 && at_key;
+            match event {
+                Event::Scalar(scalar) => {
+                    if is_key {
+                        keys.push(String::from_utf8_lossy(&scalar.value).into_owned());
+                    }
+                }
+                Event::Alias(_) => {}
+                Event::SequenceStart(_) | Event::MappingStart(_) => {
+                    depth += 1;
+                    continue;
+                }
+                Event::SequenceEnd | Event::MappingEnd => {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                }
+                Event::StreamStart
+                | Event::StreamEnd
+                | Event::DocumentStart
+                | Event::DocumentEnd => unreachable!(),
+            }
+            if depth == 0 {
+                at_key = !at_key;
+            }
+        }
+    }
+
+    Ok(RootInfo {
+        kind,
+        tag: tag.map(|tag| String::from_utf8_lossy(&tag).into_owned()),
+        anchor: anchor.map(|anchor| String::from_utf8_lossy(&anchor).into_owned()),
+        keys,
+    })
+}
@@ -0,0 +1,265 @@
+//! Why the serializer writes a string in quotes.
+//!
+//! The serializer writes a string as a plain scalar unless that would change
+//! what it reads back as, or would not parse at all. [`quote_reason`] is the
+//! check that the serializer itself makes to decide, so its answer always
+//! matches the output of [`to_string`][crate::to_string].
+//!
+//! ```
+//! use serde_yaml::scalar::{quote_reason, QuoteContext, QuoteReason};
+//!
+//! let context = QuoteContext::new();
+//! assert_eq!(quote_reason("text", context), None);
+//! assert_eq!(quote_reason("true", context), Some(QuoteReason::Bool));
+//! assert_eq!(quote_reason("*ref", context), Some(QuoteReason::Indicator('*')));
+//! assert_eq!(quote_reason("a: b", context), Some(QuoteReason::ColonSpace));
+//!
+//! let reason = quote_reason("8080", context).unwrap();
+//! assert_eq!(reason.to_string(), "looks like a number");
+//! ```
+
+use crate::libyaml::parser::ScalarStyle;
+use crate::schema::{CoercionFlags, SchemaProfile};
+use serde::de::Visitor;
+use std::fmt::{self, Display};
+
+/// Where a string is written, for [`quote_reason`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuoteContext {
+    flow: bool,
+    quote_for: SchemaProfile,
+}
+
+impl QuoteContext {
+    /// A string in a block collection or at the root of a document, quoted
+    /// for the core schema, which is where the serializer writes strings
+    /// unless configured otherwise.
+    pub fn new() -> Self {
+        QuoteContext::default()
+    }
+
+    /// Whether the string is inside of a flow sequence or mapping, like
+    /// `[a, b]`, where `,`, `[`, `]`, `{`, `}`, `?` and `:` cannot be part of a
+    /// plain scalar. The serializer writes the collections inside of a
+    /// mapping key in flow style.
+    pub fn flow(mut self, flow: bool) -> Self {
+        self.flow = flow;
+        self
+    }
+
+    /// The schema given to [`SerializerBuilder::quote_for`], whose
+    /// resolutions are quoted in addition to those of the core schema.
+    ///
+    /// [`SerializerBuilder::quote_for`]: crate::SerializerBuilder::quote_for
+    pub fn quote_for(mut self, schema: SchemaProfile) -> Self {
+        self.quote_for = schema;
+        self
+    }
+}
+
+/// Why a string is not written as a plain scalar, as returned by
+/// [`quote_reason`].
+///
+/// The `Display` form is a short phrase, like `looks like a boolean`, for
+/// explaining the quotes to a user.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QuoteReason {
+    /// Unquoted, it would read back as null, like `~` or an empty string.
+    Null,
+    /// Unquoted, it would read back as a boolean, like `true`, or `yes` if
+    /// quoting for YAML 1.1.
+    Bool,
+    /// Unquoted, it would read back as a number, like `8080` or `.inf`.
+    Number,
+    /// It contains a line break. Strings with a `\n` are written as literal
+    /// block scalars where possible, and other ones in quotes.
+    LineBreak,
+    /// It contains a character that can only be written as an escape in a
+    /// double-quoted scalar, like a control character or the byte order
+    /// mark.
+    SpecialCharacter(char),
+    /// It starts or ends with a space.
+    Space,
+    /// It starts with `---` or `...`, which mark where a document starts or
+    /// ends.
+    DocumentMarker,
+    /// It starts with a character that has a meaning at the start of a
+    /// scalar, like `*`, `&`, `!`, `[`, or `-`, `?` or `:` followed by a space.
+    Indicator(char),
+    /// It contains a `:` followed by a space or at its end, which would make
+    /// it a mapping entry.
+    ColonSpace,
+    /// It contains a `#` after a space, which would start a comment.
+    SpaceHash,
+    /// It is inside of a flow collection and contains a character that
+    /// separates or ends the entries of one.
+    FlowIndicator(char),
+}
+
+impl Display for QuoteReason {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuoteReason::Null => formatter.write_str("looks like null"),
+            QuoteReason::Bool => formatter.write_str("looks like a boolean"),
+            QuoteReason::Number => formatter.write_str("looks like a number"),
+            QuoteReason::LineBreak => formatter.write_str("contains a line break"),
+            QuoteReason::SpecialCharacter(ch) => {
+                write!(
+                    formatter,
+                    "contains the special character U+{:04X}",
+                    *ch as u32
+                )
+            }
+            QuoteReason::Space => formatter.write_str("starts or ends with a space"),
+            QuoteReason::DocumentMarker => formatter.write_str("starts with a document marker"),
+            QuoteReason::Indicator(ch) => write!(formatter, "starts with the indicator `{}`", ch),
+            QuoteReason::ColonSpace => formatter.write_str("contains a colon-space"),
+            QuoteReason::SpaceHash => formatter.write_str("contains a space-hash"),
+            QuoteReason::FlowIndicator(ch) => {
+                write!(formatter, "contains the flow indicator `{}`", ch)
+            }
+        }
+    }
+}
+
+/// Why the serializer would write the string `s` in quotes or as a block
+/// scalar, or `None` if it would write it as a plain scalar.
+///
+/// Where several reasons apply, the first of them in the order of
+/// [`QuoteReason`] is returned. Canonical output, from
+/// [`SerializerBuilder::canonical`], quotes every string regardless.
+///
+/// [`SerializerBuilder::canonical`]: crate::SerializerBuilder::canonical
+pub fn quote_reason(s: &str, context: QuoteContext) -> Option<QuoteReason> {
+    if s.chars().any(is_break) {
+        return Some(QuoteReason::LineBreak);
+    }
+    if let Some(ch) = s.chars().find(|&ch| !is_printable(ch)) {
+        return Some(QuoteReason::SpecialCharacter(ch));
+    }
+    if let Some(reason) = resolution(s, &CoercionFlags::CORE_12) {
+        return Some(reason);
+    }
+    if context.quote_for != SchemaProfile::Core12 {
+        if let Some(reason) = resolution(s, &context.quote_for.flags()) {
+            return Some(reason);
+        }
+    }
+    if s.starts_with(' ') || s.ends_with(' ') {
+        return Some(QuoteReason::Space);
+    }
+    if s.starts_with("---") || s.starts_with("...") {
+        return Some(QuoteReason::DocumentMarker);
+    }
+
+    let chars: Vec<char> = s.chars().collect();
+    // Whether the character at an index is followed by whitespace or the end
+    // of the string.
+    let followed_by_blank = |i: usize| chars.get(i + 1).map_or(true, |&next| is_blank(next));
+    match chars.first() {
+        Some(
+            &first @ ('#' | ',' | '[' | ']' | '{' | '}' | '&' | '*' | '!' | '|' | '>' | '\'' | '"'
+            | '%' | '@' | '`'),
+        ) => return Some(QuoteReason::Indicator(first)),
+        Some(&first @ ('?' | ':' | '-')) if followed_by_blank(0) => {
+            return Some(QuoteReason::Indicator(first));
+        }
+        _ => {}
+    }
+    if (1..chars.len()).any(|i| chars[i] == ':' && followed_by_blank(i)) {
+        return Some(QuoteReason::ColonSpace);
+    }
+    if (1..chars.len()).any(|i| chars[i] == '#' && is_blank(chars[i - 1])) {
+        return Some(QuoteReason::SpaceHash);
+    }
+    if context.flow {
+        let flow_indicator = |ch: &&char| matches!(ch, ',' | '?' | '[' | ']' | '{' | '}' | ':');
+        if let Some(&ch) = chars.iter().find(flow_indicator) {
+            return Some(QuoteReason::FlowIndicator(ch));
+        }
+    }
+    None
+}
+
+// Whether the emitter writes `s` with an indentation indicator if it is asked
+// for a literal or folded scalar, which it does for contents that start with a
+// space or a line break. For the contents that cannot be written in either of
+// those styles, it goes on to double quotes instead.
+pub(crate) fn has_indentation_indicator(s: &str) -> bool {
+    let chars: Vec<char> = s.chars().collect();
+    let block_allowed = !s.ends_with([' ', '\u{2028}', '\u{2029}'])
+        && chars.iter().all(|&ch| is_printable(ch) || ch == '\u{85}')
+        && !chars
+            .windows(2)
+            .any(|pair| pair[0] == ' ' && is_break(pair[1]));
+    block_allowed
+        && chars
+            .first()
+            .map_or(false, |&first| first == ' ' || is_break(first))
+}
+
+// The characters that the emitter writes without escaping them in a plain or
+// single-quoted scalar, which leaves out tabs, NEL and the byte order mark.
+fn is_printable(ch: char) -> bool {
+    matches!(ch,
+        '\n' | ' '..='~' | '\u{A0}'..='\u{D7FF}' | '\u{E000}'..='\u{FEFE}'
+        | '\u{FF00}'..='\u{FFFD}' | '\u{10000}'..='\u{10FFFF}')
+}
+
+fn is_break(ch: char) -> bool {
+    matches!(ch, '\r' | '\n' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+fn is_blank(ch: char) -> bool {
+    ch == ' ' || ch == '\t' || is_break(ch)
+}
+
+// What a plain scalar other than a string would read back as.
+fn resolution(s: &str, schema: &CoercionFlags) -> Option<QuoteReason> {
+    struct Resolution;
+
+    impl<'de> Visitor<'de> for Resolution {
+        type Value = Option<QuoteReason>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("any scalar")
+        }
+
+        fn visit_bool<E>(self, _v: bool) -> Result<Self::Value, E> {
+            Ok(Some(QuoteReason::Bool))
+        }
+
+        fn visit_i64<E>(self, _v: i64) -> Result<Self::Value, E> {
+            Ok(Some(QuoteReason::Number))
+        }
+
+        fn visit_i128<E>(self, _v: i128) -> Result<Self::Value, E> {
+            Ok(Some(QuoteReason::Number))
+        }
+
+        fn visit_u64<E>(self, _v: u64) -> Result<Self::Value, E> {
+            Ok(Some(QuoteReason::Number))
+        }
+
+        fn visit_u128<E>(self, _v: u128) -> Result<Self::Value, E> {
+            Ok(Some(QuoteReason::Number))
+        }
+
+        fn visit_f64<E>(self, _v: f64) -> Result<Self::Value, E> {
+            Ok(Some(QuoteReason::Number))
+        }
+
+        fn visit_str<E>(self, _v: &str) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(Some(QuoteReason::Null))
+        }
+    }
+
+    crate::de::visit_untagged_scalar(Resolution, s, None, ScalarStyle::Plain, schema)
+        .ok()
+        .flatten()
+}
@@ -0,0 +1,127 @@
+/// How plain scalars are resolved to nulls, booleans and numbers, selected
+/// with [`DeserializerBuilder::schema`].
+///
+/// Every preset is a fixed set of [`CoercionFlags`], returned by
+/// [`flags`][SchemaProfile::flags]. To use a different combination, start
+/// from the flags of the closest preset and pass them as
+/// [`Custom`][SchemaProfile::Custom].
+///
+/// [`DeserializerBuilder::schema`]: crate::DeserializerBuilder::schema
+///
+/// ```
+/// use serde_yaml::{DeserializerBuilder, SchemaProfile, Value};
+///
+/// let yaml = "[~, .inf, 0x1F, yes, 1_000]";
+///
+/// let core: Value = serde_yaml::from_str(yaml).unwrap();
+/// assert_eq!(core, serde_yaml::from_str::<Value>("[null, .inf, 31, 'yes', '1_000']").unwrap());
+///
+/// let json: Value = DeserializerBuilder::new().schema(SchemaProfile::Json).from_str(yaml).unwrap();
+/// assert_eq!(json, serde_yaml::from_str::<Value>("['~', '.inf', '0x1F', 'yes', '1_000']").unwrap());
+///
+/// let legacy: Value = DeserializerBuilder::new().schema(SchemaProfile::Legacy11).from_str(yaml).unwrap();
+/// assert_eq!(legacy, serde_yaml::from_str::<Value>("[null, .inf, 31, true, 1000]").unwrap());
+/// ```
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum SchemaProfile {
+    /// The YAML 1.2 core schema, as described on [`Deserializer`]. This is
+    /// the default.
+    ///
+    /// [`Deserializer`]: crate::Deserializer
+    #[default]
+    Core12,
+    /// Only the resolutions of the YAML 1.2 JSON schema: `null`, `true`,
+    /// `false`, and numbers in JSON syntax. Everything else, including `~`,
+    /// `True`, `.inf`, `0x1F`, `+1` and an empty scalar, is a string.
+    Json,
+    /// The resolutions of YAML 1.1, as used by PyYAML and older tools: also
+    /// `yes`, `no`, `on`, `off` and the like as booleans, `0755` as octal,
+    /// `1_000` with digit separators, and `1:30` in base 60.
+    Legacy11,
+    /// Any other combination of flags.
+    Custom(CoercionFlags),
+}
+
+/// The individual choices made by a [`SchemaProfile`].
+///
+/// Each flag allows forms beyond the strict JSON schema, so all of them are
+/// false in [`SchemaProfile::Json`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CoercionFlags {
+    /// `~`, `Null` and `NULL` are null, not only `null`.
+    pub null_variants: bool,
+    /// An empty plain scalar, as in `key:`, is null rather than an empty
+    /// string.
+    pub empty_null: bool,
+    /// `True`, `TRUE`, `False` and `FALSE` are booleans, not only `true` and
+    /// `false`.
+    pub bool_variants: bool,
+    /// The YAML 1.1 booleans `y`, `yes`, `on`, `n`, `no`, `off` are booleans,
+    /// in lowercase, capitalized or uppercase.
+    pub yaml11_bools: bool,
+    /// Integers may be written in hexadecimal, octal or binary with a `0x`,
+    /// `0o` or `0b` prefix.
+    pub prefixed_ints: bool,
+    /// Digits with a leading zero, like `0755`, are an octal integer rather
+    /// than a string.
+    pub leading_zero_octal: bool,
+    /// Numbers may contain `_` between digits, as in `1_000`.
+    pub underscores: bool,
+    /// Numbers may be written in base 60 with `:` between the digits, as in
+    /// `1:30` for 90.
+    pub sexagesimal: bool,
+    /// `.inf`, `-.inf` and `.nan` are floats.
+    pub special_floats: bool,
+    /// Numbers may have forms outside of JSON number syntax, like a leading
+    /// `+` or a float such as `1.` or `.5`.
+    pub relaxed_numbers: bool,
+    /// A quoted scalar is accepted when deserializing a boolean or number if
+    /// its content would resolve to one, for example `'8080'` for a `u16`.
+    pub coerce_quoted: bool,
+}
+
+impl SchemaProfile {
+    /// The flags that make up this profile.
+    pub fn flags(self) -> CoercionFlags {
+        match self {
+            SchemaProfile::Core12 => CoercionFlags::CORE_12,
+            SchemaProfile::Json => CoercionFlags {
+                null_variants: false,
+                empty_null: false,
+                bool_variants: false,
+                yaml11_bools: false,
+                prefixed_ints: false,
+                leading_zero_octal: false,
+                underscores: false,
+                sexagesimal: false,
+                special_floats: false,
+                relaxed_numbers: false,
+                coerce_quoted: false,
+            },
+            SchemaProfile::Legacy11 => CoercionFlags {
+                yaml11_bools: true,
+                leading_zero_octal: true,
+                underscores: true,
+                sexagesimal: true,
+                ..CoercionFlags::CORE_12
+            },
+            SchemaProfile::Custom(flags) => flags,
+        }
+    }
+}
+
+impl CoercionFlags {
+    pub(crate) const CORE_12: CoercionFlags = CoercionFlags {
+        null_variants: true,
+        empty_null: true,
+        bool_variants: true,
+        yaml11_bools: false,
+        prefixed_ints: true,
+        leading_zero_octal: false,
+        underscores: false,
+        sexagesimal: false,
+        special_floats: true,
+        relaxed_numbers: true,
+        coerce_quoted: false,
+    };
+}
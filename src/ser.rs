@@ -2,18 +2,41 @@
 //!
 //! This module provides YAML serialization with the type `Serializer`.
 
+use crate::base64;
+use crate::comments::{self, CommentWriter, Comments};
+use crate::compat::EmitterCompat;
 use crate::error::{self, Error, ErrorImpl};
+use crate::float::{self, FloatFormat};
+use crate::indent::{IndentWriter, LayoutWriter};
 use crate::libyaml;
-use crate::libyaml::emitter::{Emitter, Event, Mapping, Scalar, ScalarStyle, Sequence};
-use crate::value::tagged::{self, MaybeTag};
+use crate::libyaml::emitter::{
+    CollectionStyle, Emitter, Event, Mapping, Scalar, ScalarStyle, Sequence,
+};
+use crate::libyaml::tag::Tag;
+#[cfg(feature = "value")]
+use crate::order::KeyOrder;
+use crate::scalar::{self, QuoteContext, QuoteReason};
+use crate::schema::{CoercionFlags, SchemaProfile};
+use crate::shared::{self, Anchors, Identity};
+#[cfg(feature = "value")]
+use crate::value::roundtrip::{Tee, Verify};
+#[cfg(feature = "value")]
+use crate::value::Value;
 use serde::de::Visitor;
 use serde::ser::{self, Serializer as _};
-use std::fmt::{self, Display};
+use std::borrow::Cow;
+use std::cell::Cell;
+#[cfg(feature = "value")]
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Display};
 use std::io;
 use std::marker::PhantomData;
 use std::mem;
 use std::num;
+use std::rc::Rc;
 use std::str;
+use std::sync::Arc;
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -41,13 +64,261 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 ///     Ok(())
 /// }
 /// ```
+///
+/// # Order of entries
+///
+/// Mapping entries are written in the order they are serialized, which for a
+/// struct is the order its fields are declared in. This includes structs with
+/// `#[serde(flatten)]` fields: each flattened field contributes its entries at
+/// the position where it is declared, in its own iteration order, so a
+/// [`Mapping`] or `BTreeMap` gives stable output while a `HashMap` does not.
+/// Declare flattened fields after the named ones to have the named fields
+/// come first.
+///
+/// Serde passes flattened entries to the serializer in the same way as named
+/// fields, so the serializer cannot reorder one relative to the other. A
+/// flattened key that repeats a named field is an error, as with any other
+/// duplicate key.
+///
+/// [`Mapping`]: crate::Mapping
+///
+/// ```
+/// # use serde_derive::Serialize;
+/// use serde_yaml::Mapping;
+///
+/// #[derive(Serialize)]
+/// struct Service {
+///     name: String,
+///     #[serde(flatten)]
+///     labels: Mapping,
+///     replicas: u32,
+/// }
+///
+/// let mut labels = Mapping::new();
+/// labels.insert("tier".into(), "web".into());
+/// labels.insert("app".into(), "shop".into());
+/// let service = Service {
+///     name: "frontend".to_owned(),
+///     labels,
+///     replicas: 3,
+/// };
+///
+/// let yaml = serde_yaml::to_string(&service).unwrap();
+/// assert_eq!(yaml, "name: frontend\ntier: web\napp: shop\nreplicas: 3\n");
+/// ```
 pub struct Serializer<W> {
     depth: usize,
+    // Depth of the outermost mapping key currently being serialized.
+    key_depth: Option<usize>,
+    // Depth of the outermost node whose sequences and mappings are written in
+    // flow style, like a key from `with::singleton_map_keys`.
+    flow_depth: Option<usize>,
+    // With a `map_node` hook, the number of collections open around the one
+    // being left out, whose events are not emitted until it ends.
+    skip_from: Option<usize>,
+    // The entries from a `map_node` hook to add at the end of the mappings
+    // that are open, with the number of collections open around each.
+    inserted: Vec<(usize, Vec<(String, ScalarRef<'static>)>)>,
+    // One entry for each sequence and mapping that is currently open.
+    collections: Vec<Collection>,
+    // The number of documents started so far.
+    documents: usize,
     state: State,
     emitter: Emitter<'static>,
+    options: SerializerBuilder,
+    #[cfg(feature = "value")]
+    verify: Option<Verify>,
+    comments: Comments,
+    // The placeholder of a comment from a Commented, which is written before
+    // the entry or item that starts next.
+    comment_above: Option<String>,
+    // The anchors of the values from with::shared in the current document,
+    // and the value whose sequence or mapping is to be anchored next.
+    anchors: Anchors,
+    anchor_next: Option<Identity>,
+    compact: Compact,
+    // With `bare_first_line(true)`, whether the root of the document is a
+    // sequence or mapping, which the writer needs for the first line.
+    root_collection: Option<Rc<Cell<bool>>>,
     writer: PhantomData<W>,
 }
 
+enum Collection {
+    // The number of elements started so far.
+    Sequence(usize),
+    Mapping(MappingKeys),
+}
+
+#[derive(Default)]
+struct MappingKeys {
+    // The tag, whether the key is plain, and the text of each scalar key.
+    // Collections used as keys are not checked.
+    seen: HashSet<(Option<String>, bool, String)>,
+    next_is_value: bool,
+    // Whether the current key is a scalar, which an entry needs to be
+    // written as a comment.
+    scalar_key: bool,
+    // Only tracked when there is a `map_node` hook: the path segment of the
+    // current key.
+    key: Option<String>,
+    // The current key if it is a scalar, which is not emitted until its
+    // value starts, as the value might be skipped or have a comment to be
+    // written above the key.
+    pending: Option<PendingKey>,
+    // Only with `deterministic(true)`, for a mapping from `serialize_map`, or
+    // with `key_order` for a mapping whose keys are in it: the events of its
+    // entries, which are written out sorted at the end.
+    #[cfg(feature = "value")]
+    sorted: Option<SortedEntries>,
+}
+
+#[cfg(feature = "value")]
+#[derive(Default)]
+struct SortedEntries {
+    entries: Vec<SortedEntry>,
+    // Whether the entries are sorted by their keys as a Value, and then the
+    // keys in the order of a KeyOrder, if any.
+    by_value: bool,
+    order: Option<Vec<String>>,
+    // Nesting of the event being recorded below the mapping, and whether the
+    // next node directly in the mapping is a value.
+    depth: usize,
+    next_is_value: bool,
+}
+
+#[cfg(feature = "value")]
+struct SortedEntry {
+    // The extra entry of a comment from a Commented, which stays above it.
+    comment: Vec<Recorded>,
+    key: Vec<Recorded>,
+    value: Vec<Recorded>,
+}
+
+#[cfg(feature = "value")]
+enum Recorded {
+    Scalar(Option<String>, String, ScalarStyle),
+    SequenceStart(Sequence),
+    SequenceEnd,
+    MappingStart(Mapping),
+    MappingEnd,
+}
+
+#[cfg(feature = "value")]
+impl SortedEntries {
+    fn record(&mut self, event: Event) {
+        let node_start = self.depth == 0;
+        let event = match event {
+            Event::Scalar(scalar) => {
+                Recorded::Scalar(scalar.tag, scalar.value.to_owned(), scalar.style)
+            }
+            Event::SequenceStart(sequence) => {
+                self.depth += 1;
+                Recorded::SequenceStart(sequence)
+            }
+            Event::MappingStart(mapping) => {
+                self.depth += 1;
+                Recorded::MappingStart(mapping)
+            }
+            Event::SequenceEnd => {
+                self.depth -= 1;
+                Recorded::SequenceEnd
+            }
+            Event::MappingEnd => {
+                self.depth -= 1;
+                Recorded::MappingEnd
+            }
+            // Values from with::shared are written out in full in a mapping
+            // whose entries are sorted.
+            Event::Alias(_)
+            | Event::StreamStart
+            | Event::StreamEnd
+            | Event::DocumentStart
+            | Event::DocumentEnd => unreachable!(),
+        };
+        if node_start && !self.next_is_value {
+            self.entries.push(SortedEntry {
+                comment: Vec::new(),
+                key: Vec::new(),
+                value: Vec::new(),
+            });
+        }
+        let entry = self.entries.last_mut().unwrap();
+        if self.next_is_value {
+            entry.value.push(event);
+        } else {
+            entry.key.push(event);
+        }
+        if self.depth == 0 {
+            self.next_is_value = !self.next_is_value;
+        }
+    }
+}
+
+// Where the key of a recorded entry is in the order of a KeyOrder. Keys that
+// are not in it come after all of the others.
+#[cfg(feature = "value")]
+fn key_position(order: &[String], key: &[Recorded]) -> usize {
+    let key = match key {
+        [Recorded::Scalar(_tag, value, _style)] => value.as_str(),
+        _ => "?",
+    };
+    order
+        .iter()
+        .position(|ordered| ordered == key)
+        .unwrap_or(order.len())
+}
+
+// The key of a recorded entry as a Value, which is what entries are sorted by.
+#[cfg(feature = "value")]
+fn key_value(events: &[Recorded]) -> Value {
+    let mut builder = Verify::new();
+    for event in events {
+        match event {
+            Recorded::Scalar(tag, value, style) => {
+                let plain = matches!(style, ScalarStyle::Plain);
+                builder.scalar(tag.as_deref(), value, plain);
+            }
+            Recorded::SequenceStart(sequence) => {
+                builder.start_sequence(None, sequence.tag.as_deref());
+            }
+            Recorded::MappingStart(mapping) => builder.start_mapping(None, mapping.tag.as_deref()),
+            Recorded::SequenceEnd | Recorded::MappingEnd => builder.end(),
+        }
+    }
+    builder.into_document().unwrap_or(Value::Null)
+}
+
+struct PendingKey {
+    tag: Option<String>,
+    value: String,
+    style: ScalarStyle,
+}
+
+// The longest text of a collection that `compact(true)` writes in flow style.
+const COMPACT_WIDTH: usize = 60;
+
+// The name of a newtype struct whose sequences and mappings are written in
+// flow style, for the keys from `with::singleton_map_keys`.
+pub(crate) const FLOW: &str = "$serde_yaml::flow";
+
+// With `compact(true)`, the events on their way to the emitter are held back
+// from the start of a collection below the root until it is known whether it
+// is short and only has scalars in it.
+#[derive(Default)]
+struct Compact {
+    // Nesting of the collections that have been emitted.
+    depth: usize,
+    start: Option<CollectionStart>,
+    scalars: Vec<(Option<String>, String, ScalarStyle)>,
+    // The length of the collection written in flow style so far.
+    width: usize,
+}
+
+enum CollectionStart {
+    Sequence(Sequence),
+    Mapping(Mapping),
+}
+
 enum State {
     NothingInParticular,
     CheckForTag,
@@ -56,114 +327,2745 @@ enum State {
     AlreadyTagged,
 }
 
+// These take no writer, which is given to the builder later, so they are on
+// one type of serializer for a call like `Serializer::builder()` to need no
+// annotation of which.
+impl Serializer<io::Sink> {
+    /// Creates a builder of serializers with options, which is the same as
+    /// [`SerializerBuilder::new`].
+    pub fn builder() -> SerializerBuilder {
+        SerializerBuilder::new()
+    }
+
+    /// Creates a builder with the options of a profile, which is the same as
+    /// [`SerializerBuilder::with_profile`].
+    pub fn with_profile(profile: SerializerProfile) -> SerializerBuilder {
+        SerializerBuilder::with_profile(profile)
+    }
+}
+
 impl<W> Serializer<W>
 where
     W: io::Write,
 {
     /// Creates a new YAML serializer.
+    ///
+    /// This uses the default options. To configure the output, use
+    /// [`SerializerBuilder`].
     pub fn new(writer: W) -> Self {
-        let mut emitter = Emitter::new({
-            let writer = Box::new(writer);
+        SerializerBuilder::new().build(writer)
+    }
+
+    fn with_options(writer: W, options: SerializerBuilder) -> Self {
+        #[cfg(feature = "value")]
+        let verify = if options.verify_roundtrip {
+            Some(Verify::new())
+        } else {
+            None
+        };
+        let comments = Comments::default();
+        let root_collection = if options.bare_first_line {
+            Some(Rc::new(Cell::new(false)))
+        } else {
+            None
+        };
+        let writer = IndentWriter::new(
+            writer,
+            options.initial_indent,
+            root_collection.clone(),
+            options.crlf,
+            options.trailing_newline,
+        );
+        // The emitter uses an indentation of 2 instead of one outside of 2..=9.
+        let sequence_indent = if options.indent_sequences {
+            Some(if (2..=9).contains(&options.indent) {
+                options.indent
+            } else {
+                2
+            })
+        } else {
+            None
+        };
+        let align_values = options.align_values && !options.json;
+        let writer: Box<dyn io::Write> = {
+            #[cfg(feature = "value")]
+            let writer: Box<dyn io::Write> = match &verify {
+                Some(verify) => Box::new(LayoutWriter::new(
+                    CommentWriter::new(verify.tee(writer), comments.clone()),
+                    sequence_indent,
+                    align_values,
+                    options.json,
+                    comments.clone(),
+                )),
+                None => Box::new(LayoutWriter::new(
+                    CommentWriter::new(writer, comments.clone()),
+                    sequence_indent,
+                    align_values,
+                    options.json,
+                    comments.clone(),
+                )),
+            };
+            #[cfg(not(feature = "value"))]
+            let writer: Box<dyn io::Write> = Box::new(LayoutWriter::new(
+                CommentWriter::new(writer, comments.clone()),
+                sequence_indent,
+                align_values,
+                options.json,
+                comments.clone(),
+            ));
             unsafe { mem::transmute::<Box<dyn io::Write>, Box<dyn io::Write>>(writer) }
-        });
+        };
+        let mut emitter = Emitter::new(writer);
+        emitter.set_indent(options.indent);
+        emitter.set_width(options.line_width());
+        emitter.set_canonical(options.canonical && !options.json);
+        emitter.set_unicode(!options.ascii);
+        emitter.set_crlf(options.crlf);
+        emitter.set_explicit_document_start(options.document_start && !options.json);
+        emitter.set_explicit_document_end(options.document_end && !options.json);
+        if !options.json {
+            let version = if options.yaml_directive {
+                Some((1, 2))
+            } else {
+                None
+            };
+            emitter.set_directives(version, options.tag_directives.iter().cloned());
+        }
         emitter.emit(Event::StreamStart).unwrap();
         Serializer {
             depth: 0,
+            key_depth: None,
+            flow_depth: None,
+            skip_from: None,
+            inserted: Vec::new(),
+            collections: Vec::new(),
+            documents: 0,
             state: State::NothingInParticular,
             emitter,
+            options,
+            #[cfg(feature = "value")]
+            verify,
+            comments,
+            comment_above: None,
+            anchors: Anchors::default(),
+            anchor_next: None,
+            compact: Compact::default(),
+            root_collection,
             writer: PhantomData,
         }
     }
 
-    /// Calls [`.flush()`](io::Write::flush) on the underlying `io::Write`
-    /// object.
-    pub fn flush(&mut self) -> Result<()> {
-        self.emitter.flush()?;
+    /// Calls [`.flush()`](io::Write::flush) on the underlying `io::Write`
+    /// object.
+    pub fn flush(&mut self) -> Result<()> {
+        self.emitter.flush()?;
+        Ok(())
+    }
+
+    /// Unwrap the underlying `io::Write` object from the `Serializer`.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.emitter.emit(Event::StreamEnd)?;
+        self.emitter.flush()?;
+        let writer = self.emitter.into_inner();
+        let finish = |err| error::new(ErrorImpl::Io(err));
+        #[cfg(feature = "value")]
+        if self.verify.is_some() {
+            let writer =
+                Box::into_raw(writer).cast::<LayoutWriter<CommentWriter<Tee<IndentWriter<W>>>>>();
+            let mut writer = *unsafe { Box::from_raw(writer) };
+            writer.finish().map_err(finish)?;
+            writer.writer.finish().map_err(finish)?;
+            return Ok(writer.writer.writer.writer.writer);
+        }
+        let writer = Box::into_raw(writer).cast::<LayoutWriter<CommentWriter<IndentWriter<W>>>>();
+        let mut writer = *unsafe { Box::from_raw(writer) };
+        writer.finish().map_err(finish)?;
+        writer.writer.finish().map_err(finish)?;
+        Ok(writer.writer.writer.writer)
+    }
+
+    fn emit_scalar(&mut self, mut scalar: Scalar) -> Result<()> {
+        self.flush_mapping_start()?;
+        if let Some(tag) = self.take_tag()? {
+            scalar.tag = Some(tag);
+        }
+        let is_key = self.child_start();
+        if is_key {
+            if self.options.json {
+                check_json_key(&mut scalar, self.options.ascii)?;
+            }
+            self.check_for_duplicate_key(&scalar)?;
+            #[cfg(feature = "value")]
+            let track_key = self.options.key_order.is_some();
+            if let Some(Collection::Mapping(keys)) = self.collections.last_mut() {
+                keys.scalar_key = true;
+                #[cfg(feature = "value")]
+                if track_key {
+                    keys.key = Some(scalar.value.to_owned());
+                }
+            }
+        }
+        if is_key {
+            let track_key = self.options.map_node.is_some();
+            if let Some(Collection::Mapping(keys)) = self.collections.last_mut() {
+                if track_key {
+                    keys.key = Some(scalar.value.to_owned());
+                }
+                keys.pending = Some(PendingKey {
+                    tag: scalar.tag,
+                    value: scalar.value.to_owned(),
+                    style: scalar.style,
+                });
+            }
+            return Ok(());
+        }
+        let hook = match &self.options.map_node {
+            Some(hook) => Arc::clone(&hook.0),
+            None => return self.write_scalar(scalar),
+        };
+        if self.in_key() || self.skip_from.is_some() {
+            return self.write_scalar(scalar);
+        }
+        let segments = self.path_segments();
+        let path = SerPath {
+            segments: &segments,
+        };
+        let replacement = match hook(&path, NodeRef::Scalar(scalar_ref(&scalar))) {
+            NodeAction::Keep | NodeAction::Insert(_) => return self.write_scalar(scalar),
+            NodeAction::Replace(replacement) => replacement,
+            NodeAction::Skip if self.skip_entry() => return Ok(()),
+            NodeAction::Skip => ScalarRef::Null,
+        };
+        let (value, style) = self.replacement_text(&replacement);
+        self.write_scalar(Scalar {
+            tag: scalar.tag,
+            value,
+            style,
+        })
+    }
+
+    // Whether the node that is starting is inside of a mapping key. The key
+    // depth is already set while the start of a mapping with a single entry
+    // is held back until its key is checked for a tag.
+    fn in_key(&self) -> bool {
+        matches!(self.key_depth, Some(key_depth) if self.depth >= key_depth)
+    }
+
+    // Leaves out the entry or element that the current node is the value of,
+    // for a `map_node` hook. False at the root of a document, which cannot
+    // be left out.
+    fn skip_entry(&mut self) -> bool {
+        match self.collections.last_mut() {
+            Some(Collection::Mapping(keys)) => keys.pending = None,
+            Some(Collection::Sequence(_)) => {}
+            None => return false,
+        }
+        self.comment_above = None;
+        true
+    }
+
+    // The text and style of a scalar from a `map_node` hook.
+    fn replacement_text<'r>(&self, replacement: &'r ScalarRef) -> (&'r str, ScalarStyle) {
+        match replacement {
+            ScalarRef::Null => (self.null_text(), ScalarStyle::Plain),
+            ScalarRef::Bool(true) => ("true", ScalarStyle::Plain),
+            ScalarRef::Bool(false) => ("false", ScalarStyle::Plain),
+            ScalarRef::Number(number) => (number, ScalarStyle::Plain),
+            ScalarRef::String(string) => (string, self.string_style(string)),
+        }
+    }
+
+    // Writes the scalar that a `map_node` hook replaced the sequence or
+    // mapping that is starting with, and leaves out what is inside of it.
+    fn replace_collection(&mut self, tag: Option<String>, replacement: &ScalarRef) -> Result<()> {
+        let (value, style) = self.replacement_text(replacement);
+        self.emit(Event::Scalar(Scalar { tag, value, style }))?;
+        self.skip_from = Some(self.collections.len() - 1);
+        Ok(())
+    }
+
+    // Writes the entries that a `map_node` hook added to the mapping that is
+    // ending.
+    fn write_inserted_entries(&mut self) -> Result<()> {
+        let entries = match self.inserted.last() {
+            Some((outside, _)) if *outside + 1 == self.collections.len() => {
+                self.inserted.pop().unwrap().1
+            }
+            _ => return Ok(()),
+        };
+        for (key, value) in entries {
+            let style = self.string_style(&key);
+            self.emit_scalar(Scalar {
+                tag: None,
+                value: &key,
+                style,
+            })?;
+            self.child_start();
+            let (value, style) = self.replacement_text(&value);
+            self.write_scalar(Scalar {
+                tag: None,
+                value,
+                style,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn write_scalar(&mut self, scalar: Scalar) -> Result<()> {
+        self.emit_pending_key()?;
+        // A shared value that is a scalar is written out every time.
+        self.anchor_next = None;
+        self.write_scalar_node(scalar)
+    }
+
+    fn write_scalar_node(&mut self, scalar: Scalar) -> Result<()> {
+        // The indentation indicator of a block scalar counts from the
+        // indentation of its parent, which is not part of the output at the
+        // root of a document.
+        let indented = self.options.initial_indent > 0 || self.options.bare_first_line;
+        if self.depth == 0
+            && indented
+            && matches!(scalar.style, ScalarStyle::Literal | ScalarStyle::Folded)
+            && scalar::has_indentation_indicator(scalar.value)
+        {
+            return Err(error::new(ErrorImpl::IndentedBlockScalar));
+        }
+        self.value_start()?;
+        self.emit(Event::Scalar(scalar))?;
+        self.value_end()
+    }
+
+    // Every event inside of a document goes through here, on its way to the
+    // emitter or to the entries of a mapping that are being sorted.
+    fn emit(&mut self, mut event: Event) -> Result<()> {
+        if self.skip_from.is_some() {
+            return Ok(());
+        }
+        if self.options.json {
+            if let Event::Scalar(scalar) = &mut event {
+                check_json_scalar(scalar, self.options.ascii)?;
+            }
+        }
+        #[cfg(feature = "value")]
+        {
+            if let Some(entries) = self.sorted_entries() {
+                entries.record(event);
+                return Ok(());
+            }
+            if let Some(verify) = &mut self.verify {
+                match &event {
+                    Event::Scalar(scalar) if self.comments.is_placeholder(scalar.value) => {
+                        verify.skip_value();
+                    }
+                    Event::Scalar(scalar) if self.comments.is_above(scalar.value) => {}
+                    Event::Scalar(scalar) => {
+                        let plain = matches!(scalar.style, ScalarStyle::Plain);
+                        verify.scalar(scalar.tag.as_deref(), scalar.value, plain);
+                    }
+                    Event::Alias(anchor) => verify.alias(anchor),
+                    Event::SequenceStart(sequence) => {
+                        let anchor = sequence.anchor.as_deref();
+                        verify.start_sequence(anchor, sequence.tag.as_deref());
+                    }
+                    Event::MappingStart(mapping) => {
+                        let anchor = mapping.anchor.as_deref();
+                        verify.start_mapping(anchor, mapping.tag.as_deref());
+                    }
+                    Event::SequenceEnd | Event::MappingEnd => verify.end(),
+                    Event::StreamStart
+                    | Event::StreamEnd
+                    | Event::DocumentStart
+                    | Event::DocumentEnd => {}
+                }
+            }
+        }
+        if (self.options.canonical || self.options.explicit_tags) && !self.options.json {
+            match &mut event {
+                Event::Scalar(scalar) if scalar.tag.is_none() => {
+                    scalar.tag = Some(core_tag(scalar).to_owned());
+                }
+                Event::SequenceStart(sequence) if sequence.tag.is_none() => {
+                    sequence.tag = Some(Tag::SEQ.to_owned());
+                }
+                Event::MappingStart(mapping) if mapping.tag.is_none() => {
+                    mapping.tag = Some(Tag::MAP.to_owned());
+                }
+                _ => {}
+            }
+        }
+        self.emit_compact(event)
+    }
+
+    // With `compact(true)`, holds back the events of a collection below the
+    // root, to write it in flow style if it turns out to be short and to only
+    // have scalars in it that can be written in flow style.
+    fn emit_compact(&mut self, event: Event) -> Result<()> {
+        if !self.options.compact {
+            self.emitter.emit(event)?;
+            return Ok(());
+        }
+        let start = match event {
+            Event::SequenceStart(sequence) => CollectionStart::Sequence(sequence),
+            Event::MappingStart(mapping) => CollectionStart::Mapping(mapping),
+            Event::Scalar(scalar) if self.compact.start.is_some() => {
+                let fits = !matches!(scalar.style, ScalarStyle::Literal | ScalarStyle::Folded)
+                    && !scalar.value.contains(['\n', '\r'])
+                    && !self.comments.is_placeholder(scalar.value)
+                    && !self.comments.is_above(scalar.value);
+                if fits {
+                    let compact = &mut self.compact;
+                    let quotes = match scalar.style {
+                        ScalarStyle::Any | ScalarStyle::Plain => 0,
+                        _ => 2,
+                    };
+                    let tag = scalar.tag.as_ref().map_or(0, |tag| tag.len() + 1);
+                    if !compact.scalars.is_empty() {
+                        compact.width += 2;
+                    }
+                    compact.width += scalar.value.chars().count() + quotes + tag;
+                    let tag = scalar.tag;
+                    let value = scalar.value.to_owned();
+                    compact.scalars.push((tag, value, scalar.style));
+                    if compact.width <= COMPACT_WIDTH {
+                        return Ok(());
+                    }
+                    return self.flush_compact(CollectionStyle::Any);
+                }
+                self.flush_compact(CollectionStyle::Any)?;
+                self.emitter.emit(Event::Scalar(scalar))?;
+                return Ok(());
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.compact.depth -= 1;
+                if self.compact.start.is_some() {
+                    self.flush_compact(CollectionStyle::Flow)?;
+                } else {
+                    self.flush_compact(CollectionStyle::Any)?;
+                }
+                self.emitter.emit(event)?;
+                return Ok(());
+            }
+            event => {
+                self.flush_compact(CollectionStyle::Any)?;
+                self.emitter.emit(event)?;
+                return Ok(());
+            }
+        };
+        self.flush_compact(CollectionStyle::Any)?;
+        let below_root = self.compact.depth > 0;
+        self.compact.depth += 1;
+        let block = match &start {
+            CollectionStart::Sequence(sequence) => matches!(sequence.style, CollectionStyle::Any),
+            CollectionStart::Mapping(mapping) => matches!(mapping.style, CollectionStyle::Any),
+        };
+        if below_root && block {
+            self.compact.start = Some(start);
+            self.compact.width = 2;
+            return Ok(());
+        }
+        self.emitter.emit(match start {
+            CollectionStart::Sequence(sequence) => Event::SequenceStart(sequence),
+            CollectionStart::Mapping(mapping) => Event::MappingStart(mapping),
+        })?;
+        Ok(())
+    }
+
+    // Emits the collection start and scalars that are being held back, with
+    // the collection in the given style.
+    fn flush_compact(&mut self, style: CollectionStyle) -> Result<()> {
+        let start = match self.compact.start.take() {
+            Some(start) => start,
+            None => return Ok(()),
+        };
+        self.emitter.emit(match start {
+            CollectionStart::Sequence(mut sequence) => {
+                sequence.style = style;
+                Event::SequenceStart(sequence)
+            }
+            CollectionStart::Mapping(mut mapping) => {
+                mapping.style = style;
+                Event::MappingStart(mapping)
+            }
+        })?;
+        for (tag, value, style) in mem::take(&mut self.compact.scalars) {
+            self.emitter.emit(Event::Scalar(Scalar {
+                tag,
+                value: &value,
+                style,
+            }))?;
+        }
+        Ok(())
+    }
+
+    // The text of a finite float, with `float_format` and
+    // `scientific_threshold` applied to the formatting of the compat version.
+    fn format_float<'a, F>(
+        &self,
+        v: F,
+        shortest: fn(F, &'a mut ryu::Buffer) -> &'a str,
+        buffer: &'a mut ryu::Buffer,
+    ) -> Cow<'a, str>
+    where
+        F: Copy + Display + fmt::LowerExp + str::FromStr,
+    {
+        let options = &self.options;
+        float::format(
+            v,
+            options.float_format,
+            options.scientific_threshold,
+            shortest,
+            buffer,
+        )
+    }
+
+    // How a Number from a deserializer with `preserve_number_repr` was
+    // written, which is written out again unless the output has to be the
+    // same for all equal numbers.
+    #[cfg(feature = "value")]
+    fn original_repr(&self) -> Option<Box<str>> {
+        let repr = crate::number::take_serialized_repr()?;
+        if self.options.deterministic || self.options.canonical || self.options.json {
+            None
+        } else {
+            Some(repr)
+        }
+    }
+
+    // The innermost mapping around the current node whose entries are being
+    // recorded to be sorted.
+    #[cfg(feature = "value")]
+    fn sorted_entries(&mut self) -> Option<&mut SortedEntries> {
+        self.collections
+            .iter_mut()
+            .rev()
+            .find_map(|collection| match collection {
+                Collection::Mapping(keys) => keys.sorted.as_mut(),
+                Collection::Sequence(_) => None,
+            })
+    }
+
+    // Starts recording the entries of the mapping that was just started, if
+    // they are sorted by their keys or the mapping has a key order.
+    #[cfg(feature = "value")]
+    fn sort_entries(&mut self, by_value: bool) {
+        let order = match &self.options.key_order {
+            Some(key_order) => {
+                let segments = self.path_segments();
+                let path = &segments[..segments.len() - 1];
+                key_order.keys(path).map(<[String]>::to_vec)
+            }
+            None => None,
+        };
+        if !by_value && order.is_none() {
+            return;
+        }
+        if let Some(Collection::Mapping(keys)) = self.collections.last_mut() {
+            keys.sorted = Some(SortedEntries {
+                by_value,
+                order,
+                ..SortedEntries::default()
+            });
+        }
+    }
+
+    // Writes out the entries recorded for the mapping that is ending, in the
+    // order of their keys.
+    #[cfg(feature = "value")]
+    fn write_sorted_entries(&mut self) -> Result<()> {
+        let sorted = match self.collections.last_mut() {
+            Some(Collection::Mapping(keys)) => match keys.sorted.take() {
+                Some(sorted) => sorted,
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        let mut entries = Vec::with_capacity(sorted.entries.len());
+        let mut comment = Vec::new();
+        for mut entry in sorted.entries {
+            let is_comment = match entry.key.as_slice() {
+                [Recorded::Scalar(None, value, _style)] => self.comments.is_above(value),
+                _ => false,
+            };
+            if is_comment {
+                comment = entry.key;
+                comment.append(&mut entry.value);
+            } else {
+                entry.comment = mem::take(&mut comment);
+                entries.push(entry);
+            }
+        }
+        if sorted.by_value {
+            let mut by_value: Vec<(Value, SortedEntry)> = entries
+                .into_iter()
+                .map(|entry| (key_value(&entry.key), entry))
+                .collect();
+            by_value.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            entries = by_value.into_iter().map(|(_key, entry)| entry).collect();
+        }
+        if let Some(order) = &sorted.order {
+            entries.sort_by_key(|entry| key_position(order, &entry.key));
+        }
+        for entry in entries {
+            let events = entry
+                .comment
+                .into_iter()
+                .chain(entry.key)
+                .chain(entry.value);
+            for event in events {
+                match event {
+                    Recorded::Scalar(tag, value, style) => self.emit(Event::Scalar(Scalar {
+                        tag,
+                        value: &value,
+                        style,
+                    }))?,
+                    Recorded::SequenceStart(sequence) => {
+                        self.emit(Event::SequenceStart(sequence))?;
+                    }
+                    Recorded::SequenceEnd => self.emit(Event::SequenceEnd)?,
+                    Recorded::MappingStart(mapping) => self.emit(Event::MappingStart(mapping))?,
+                    Recorded::MappingEnd => self.emit(Event::MappingEnd)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_sequence_start(&mut self) -> Result<()> {
+        self.flush_mapping_start()?;
+        let replacement = self.start_collection(Collection::Sequence(0))?;
+        self.value_start()?;
+        let tag = self.take_tag()?;
+        let style = self.collection_style();
+        let anchor = self.take_anchor();
+        if let Some(replacement) = replacement {
+            return self.replace_collection(tag, &replacement);
+        }
+        self.start_root_collection();
+        self.emit(Event::SequenceStart(Sequence { anchor, tag, style }))
+    }
+
+    fn emit_sequence_end(&mut self) -> Result<()> {
+        self.collections.pop();
+        self.emit(Event::SequenceEnd)?;
+        self.end_skip();
+        self.value_end()
+    }
+
+    fn emit_mapping_start(&mut self) -> Result<()> {
+        self.flush_mapping_start()?;
+        let replacement = self.start_collection(Collection::Mapping(MappingKeys::default()))?;
+        self.value_start()?;
+        let tag = self.take_tag()?;
+        let style = self.collection_style();
+        let anchor = self.take_anchor();
+        if let Some(replacement) = replacement {
+            return self.replace_collection(tag, &replacement);
+        }
+        self.start_root_collection();
+        self.emit(Event::MappingStart(Mapping { anchor, tag, style }))
+    }
+
+    fn emit_mapping_end(&mut self) -> Result<()> {
+        self.collections.pop();
+        self.emit(Event::MappingEnd)?;
+        self.end_skip();
+        self.value_end()
+    }
+
+    // Emits events again once the collection that was left out has ended.
+    fn end_skip(&mut self) {
+        if self.skip_from == Some(self.collections.len()) {
+            self.skip_from = None;
+        }
+    }
+
+    fn start_root_collection(&mut self) {
+        if let Some(root_collection) = &self.root_collection {
+            if self.depth == 1 {
+                root_collection.set(true);
+            }
+        }
+    }
+
+    // Returns the scalar to write instead of the collection, if a `map_node`
+    // hook replaces it.
+    fn start_collection(&mut self, collection: Collection) -> Result<Option<ScalarRef<'static>>> {
+        let is_key = self.child_start();
+        if is_key && self.options.json {
+            return Err(not_json(match collection {
+                Collection::Sequence(_) => "sequence as a key",
+                Collection::Mapping(_) => "mapping as a key",
+            }));
+        }
+        let mut replacement = None;
+        if let Some(hook) = &self.options.map_node {
+            let hook = Arc::clone(&hook.0);
+            if let Some(Collection::Mapping(keys)) = self.collections.last_mut() {
+                if is_key {
+                    keys.key = Some("?".to_owned());
+                }
+            }
+            if !is_key && !self.in_key() && self.skip_from.is_none() {
+                let segments = self.path_segments();
+                let path = SerPath {
+                    segments: &segments,
+                };
+                let node = match collection {
+                    Collection::Sequence(_) => NodeRef::Sequence,
+                    Collection::Mapping(_) => NodeRef::Mapping,
+                };
+                match hook(&path, node) {
+                    NodeAction::Keep => {}
+                    NodeAction::Replace(scalar) => replacement = Some(scalar),
+                    NodeAction::Skip if self.skip_entry() => {
+                        self.skip_from = Some(self.collections.len());
+                    }
+                    NodeAction::Skip => replacement = Some(ScalarRef::Null),
+                    NodeAction::Insert(entries) => {
+                        if let Collection::Mapping(_) = collection {
+                            self.inserted.push((self.collections.len(), entries));
+                        }
+                    }
+                }
+            }
+        }
+        self.emit_pending_key()?;
+        #[cfg(feature = "value")]
+        let track_key = self.options.key_order.is_some();
+        if let Some(Collection::Mapping(keys)) = self.collections.last_mut() {
+            if is_key {
+                keys.scalar_key = false;
+                #[cfg(feature = "value")]
+                if track_key {
+                    keys.key = Some("?".to_owned());
+                }
+            }
+        }
+        self.collections.push(collection);
+        Ok(replacement)
+    }
+
+    // Counts a new node in the innermost open collection, and returns whether
+    // it is a mapping key.
+    fn child_start(&mut self) -> bool {
+        match self.collections.last_mut() {
+            Some(Collection::Sequence(len)) => {
+                *len += 1;
+                false
+            }
+            Some(Collection::Mapping(keys)) => {
+                keys.next_is_value = !keys.next_is_value;
+                keys.next_is_value
+            }
+            None => false,
+        }
+    }
+
+    // Emits what is held back until the next entry or item starts: the extra
+    // entry or item that is written as a comment above it, and its key.
+    fn emit_pending_key(&mut self) -> Result<()> {
+        if let Some(placeholder) = self.comment_above.take() {
+            // An entry has the placeholder as its key and its value.
+            let in_mapping = matches!(self.collections.last(), Some(Collection::Mapping(_)));
+            let scalars = if in_mapping { 2 } else { 1 };
+            for _ in 0..scalars {
+                self.emit(Event::Scalar(Scalar {
+                    tag: None,
+                    value: &placeholder,
+                    style: ScalarStyle::Plain,
+                }))?;
+            }
+        }
+        let key = match self.collections.last_mut() {
+            Some(Collection::Mapping(keys)) => keys.pending.take(),
+            _ => None,
+        };
+        match key {
+            Some(key) => self.write_scalar_node(Scalar {
+                tag: key.tag,
+                value: &key.value,
+                style: key.style,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn path_segments(&self) -> Vec<PathSegment> {
+        self.collections
+            .iter()
+            .map(|collection| match collection {
+                Collection::Sequence(len) => PathSegment::Index(len - 1),
+                Collection::Mapping(keys) => PathSegment::Key(keys.key.clone().unwrap_or_default()),
+            })
+            .collect()
+    }
+
+    fn check_for_duplicate_key(&mut self, scalar: &Scalar) -> Result<()> {
+        let keys = match self.collections.last_mut() {
+            Some(Collection::Mapping(keys)) => keys,
+            _ => return Ok(()),
+        };
+        let plain = matches!(scalar.style, ScalarStyle::Plain);
+        let key = (scalar.tag.clone(), plain, scalar.value.to_owned());
+        if keys.seen.insert(key) {
+            return Ok(());
+        }
+        let description = match (&scalar.tag, plain, scalar.value) {
+            (Some(tag), _, value) => format!("key `{} {}`", tag, value),
+            (None, true, "null" | "~") => "null key".to_owned(),
+            (None, true, value @ ("true" | "false")) => format!("key `{}`", value),
+            (None, true, value) => format!("key {}", value),
+            (None, false, value) => format!("key {:?}", value),
+        };
+        Err(error::new(ErrorImpl::SerializeDuplicateKey(description)))
+    }
+
+    fn null_text(&self) -> &'static str {
+        match self.options.null_style {
+            _ if self.options.canonical || self.options.json => "null",
+            NullStyle::Null => "null",
+            NullStyle::Tilde => "~",
+            // The emitter writes an empty plain scalar as '' in a flow
+            // collection or where it is a key.
+            NullStyle::Empty if self.in_flow() || self.next_is_key() => "null",
+            NullStyle::Empty => "",
+        }
+    }
+
+    fn next_is_key(&self) -> bool {
+        if let State::CheckForTag = self.state {
+            return true;
+        }
+        match self.collections.last() {
+            Some(Collection::Mapping(keys)) => !keys.next_is_value,
+            _ => false,
+        }
+    }
+
+    fn string_style(&self, value: &str) -> ScalarStyle {
+        match self.options.quote_style {
+            _ if self.options.json => ScalarStyle::DoubleQuoted,
+            QuoteStyle::WhenNeeded if self.options.quote_keys && self.next_is_key() => {
+                ScalarStyle::SingleQuoted
+            }
+            _ if self.options.literal_strings && value.contains('\n') => ScalarStyle::Literal,
+            _ if self.should_fold(value) => ScalarStyle::Folded,
+            QuoteStyle::WhenNeeded => infer_scalar_style(value, self.quote_reason(value)),
+            QuoteStyle::Single => ScalarStyle::SingleQuoted,
+            QuoteStyle::Double => ScalarStyle::DoubleQuoted,
+        }
+    }
+
+    // Whether a string is written as a folded block scalar for being longer
+    // than a line, which only happens where a block scalar is allowed.
+    fn should_fold(&self, value: &str) -> bool {
+        if !self.options.fold_long_strings || self.in_flow() || self.next_is_key() {
+            return false;
+        }
+        let width = self.options.line_width();
+        // The emitter does not fold a first line that starts with a space
+        // into the next one, which then reads back as two lines.
+        width >= 0
+            && !value.contains('\n')
+            && !value.starts_with(' ')
+            && value.contains(' ')
+            && value.chars().count() > width as usize
+    }
+
+    fn quote_reason(&self, value: &str) -> Option<QuoteReason> {
+        let quote_reason = self.options.emitter_compat.strategy().quote_reason;
+        quote_reason(value, self.quote_context())
+    }
+
+    // Where a scalar that is started next is written.
+    fn quote_context(&self) -> QuoteContext {
+        QuoteContext::new()
+            .flow(self.in_flow())
+            .quote_for(self.options.quote_for)
+    }
+
+    // Whether a scalar that is started next is part of a flow collection.
+    fn in_flow(&self) -> bool {
+        match self.flow_depth {
+            Some(flow_depth) => self.depth > flow_depth,
+            None => self.options.all_flow() && self.depth > 0,
+        }
+    }
+
+    fn collection_style(&self) -> CollectionStyle {
+        match self.flow_depth {
+            Some(flow_depth) if self.depth > flow_depth => CollectionStyle::Flow,
+            _ if self.options.all_flow() => CollectionStyle::Flow,
+            _ => CollectionStyle::Any,
+        }
+    }
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let depth = self.child_depth();
+        let previous = self.key_depth;
+        self.key_depth = Some(previous.map_or(depth, |previous| previous.min(depth)));
+        let result = key.serialize(&mut *self);
+        self.key_depth = previous;
+        result
+    }
+
+    // The depth of the node that is starting. It is a child of the current
+    // mapping, whose start event may not have been emitted yet if it is still
+    // being checked for a tag.
+    fn child_depth(&self) -> usize {
+        match self.state {
+            State::CheckForTag => self.depth + 1,
+            _ => self.depth,
+        }
+    }
+
+    fn value_start(&mut self) -> Result<()> {
+        if self.depth == 0 {
+            if self.options.json && self.documents > 0 {
+                return Err(not_json("more than one document"));
+            }
+            self.documents += 1;
+            self.emitter.emit(Event::DocumentStart)?;
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn value_end(&mut self) -> Result<()> {
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.anchors.clear();
+            self.emitter.emit(Event::DocumentEnd)?;
+            if self.options.indent_sequences || self.options.align_values {
+                self.emitter.flush_writer()?;
+            }
+            #[cfg(feature = "value")]
+            if let Some(verify) = &mut self.verify {
+                self.emitter.flush()?;
+                verify.check()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn take_tag(&mut self) -> Result<Option<String>> {
+        let state = mem::replace(&mut self.state, State::NothingInParticular);
+        if let State::FoundTag(mut tag) = state {
+            // The full name of a tag like `tag:yaml.org,2002:str`, from a
+            // Value deserialized with `preserve_core_tags`, is written as
+            // `!!str`.
+            let declared = |tag: &str| {
+                let prefixes = self.options.tag_directives.iter();
+                prefixes
+                    .map(|(_, prefix)| prefix)
+                    .any(|prefix| tag.starts_with(prefix.as_str()))
+            };
+            if !tag.starts_with('!') && !tag.starts_with("tag:yaml.org,2002:") && !declared(&tag) {
+                tag.insert(0, '!');
+            }
+            if self.options.json {
+                return Err(not_json(&format!("tag `{}`", tag)));
+            }
+            if !self.options.tags {
+                return Err(error::new(ErrorImpl::SerializeTag(tag)));
+            }
+            // Scalars with these characters are written with escapes, but
+            // tags have none.
+            if let Some(ch) = tag.chars().find(|&ch| !is_printable(ch)) {
+                return Err(error::new(ErrorImpl::SerializeTagCharacter(tag, ch)));
+            }
+            Ok(Some(tag))
+        } else {
+            self.state = state;
+            Ok(None)
+        }
+    }
+
+    // Whether the node being serialized is the value of an entry that can be
+    // written as a comment: in a block mapping, after a scalar key.
+    fn can_comment(&self) -> bool {
+        let after_scalar_key = match self.collections.last() {
+            Some(Collection::Mapping(keys)) => keys.next_is_value && keys.scalar_key,
+            _ => false,
+        };
+        after_scalar_key
+            && self.key_depth.is_none()
+            && !self.options.canonical
+            && !self.options.explicit_tags
+            && !self.options.all_flow()
+            && matches!(self.state, State::NothingInParticular)
+    }
+
+    // Writes a placeholder for the value, which CommentWriter replaces with
+    // the entry written as a comment.
+    fn serialize_comment<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        struct Entry<'a, T: ?Sized>(&'a T);
+
+        impl<'a, T> ser::Serialize for Entry<'a, T>
+        where
+            T: ?Sized + ser::Serialize,
+        {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: ser::Serializer,
+            {
+                let mut map = serializer.serialize_map(Some(1))?;
+                ser::SerializeMap::serialize_entry(&mut map, "k", self.0)?;
+                ser::SerializeMap::end(map)
+            }
+        }
+
+        let mut options = self.options.clone();
+        options.document_start = false;
+        options.document_end = false;
+        options.trailing_newline = true;
+        options.yaml_directive = false;
+        options.tag_directives.clear();
+        options.crlf = false;
+        options.initial_indent = 0;
+        options.bare_first_line = false;
+        #[cfg(feature = "value")]
+        {
+            options.verify_roundtrip = false;
+        }
+        let yaml = options.to_string(&Entry(value))?;
+        let text = yaml.strip_prefix("k:").unwrap_or(&yaml);
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let placeholder = self.comments.add(text.to_owned());
+        self.child_start();
+        self.write_scalar(Scalar {
+            tag: None,
+            value: &placeholder,
+            style: ScalarStyle::Plain,
+        })
+    }
+
+    // Keeps a comment to be written above the value that is being serialized,
+    // if it is an entry of a block mapping with a scalar key or an item of a
+    // block sequence.
+    fn add_comment_above(&mut self, comment: String) -> Result<()> {
+        let can_comment = match self.collections.last() {
+            Some(Collection::Mapping(keys)) => keys.next_is_value && keys.scalar_key,
+            Some(Collection::Sequence(_)) => true,
+            None => false,
+        };
+        if !can_comment
+            || self.key_depth.is_some()
+            || self.options.canonical
+            || self.options.explicit_tags
+            || self.options.all_flow()
+            || !matches!(self.state, State::NothingInParticular)
+        {
+            return Ok(());
+        }
+        if let Some(ch) = comment.chars().find(|&ch| !is_printable(ch)) {
+            return Err(error::new(ErrorImpl::SerializeCommentCharacter(ch)));
+        }
+        if self.options.ascii {
+            if let Some(ch) = comment.chars().find(|ch| !ch.is_ascii()) {
+                return Err(error::new(ErrorImpl::SerializeCommentNotAscii(ch)));
+            }
+        }
+        // Any line break that a parser reads as one ends a comment line.
+        let comment = comment
+            .replace("\r\n", "\n")
+            .replace(['\r', '\u{85}', '\u{2028}', '\u{2029}'], "\n");
+        self.comment_above = Some(self.comments.add_above(comment));
+        Ok(())
+    }
+
+    // Whether the value being serialized can be anchored, or written as an
+    // alias. Within a mapping whose entries are sorted, the first of them to
+    // be written is not known yet. Nothing can refer to the root.
+    fn can_share(&mut self) -> bool {
+        #[cfg(feature = "value")]
+        if self.sorted_entries().is_some() {
+            return false;
+        }
+        self.depth > 0
+            && self.key_depth.is_none()
+            && !self.options.json
+            && self.options.map_node.is_none()
+            && matches!(self.state, State::NothingInParticular)
+    }
+
+    // The anchor for the sequence or mapping that is starting, if it is the
+    // value from with::shared.
+    fn take_anchor(&mut self) -> Option<String> {
+        let identity = self.anchor_next.take()?;
+        Some(self.anchors.insert(identity))
+    }
+
+    fn emit_alias(&mut self, anchor: String) -> Result<()> {
+        self.child_start();
+        self.emit_pending_key()?;
+        self.value_start()?;
+        self.emit(Event::Alias(anchor))?;
+        self.value_end()
+    }
+
+    fn flush_mapping_start(&mut self) -> Result<()> {
+        if let State::CheckForTag = self.state {
+            self.state = State::NothingInParticular;
+            self.emit_mapping_start()?;
+        } else if let State::CheckForDuplicateTag = self.state {
+            self.state = State::NothingInParticular;
+        }
+        Ok(())
+    }
+}
+
+/// How strings are quoted by the serializer, selected with
+/// [`SerializerBuilder::quote_style`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Plain where that reads back as the same string, and otherwise in
+    /// single quotes, as a literal block scalar if it contains a line break,
+    /// or in double quotes if it contains a special character. This is the
+    /// default.
+    WhenNeeded,
+    /// Every string in single quotes, or in double quotes if it contains a
+    /// character that can only be written as an escape.
+    Single,
+    /// Every string in double quotes.
+    Double,
+}
+
+/// How `None` and `()` are written by the serializer, selected with
+/// [`SerializerBuilder::null_style`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NullStyle {
+    /// `null`, as in `port: null`. This is the default.
+    Null,
+    /// `~`, as in `port: ~`.
+    Tilde,
+    /// Nothing, as in `port:`. A null that is a key or part of a flow
+    /// collection is written as `null` instead, as an empty key or flow
+    /// item would be a string.
+    Empty,
+}
+
+/// How enum variants without data are represented by the serializer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnitVariants {
+    /// The variant name as a plain string, as in `state: Active`. This is the
+    /// default.
+    PlainString,
+    /// The variant name as a tag on an empty node, as in `state: !Active`,
+    /// matching the way variants with data are written.
+    Tag,
+}
+
+/// The location of a node in the document being serialized, passed to a
+/// [`SerializerBuilder::map_scalar`] or [`SerializerBuilder::map_node`] hook.
+///
+/// Formatted the same way as the path in an error message, like
+/// `servers[0].password`, or `.` for a node at the root of the document.
+#[derive(Debug)]
+pub struct SerPath<'a> {
+    segments: &'a [PathSegment],
+}
+
+/// One step of a [`SerPath`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The value of a mapping entry. Scalar keys are given as written, and
+    /// sequences or mappings used as keys as `?`.
+    Key(String),
+    /// An element of a sequence.
+    Index(usize),
+}
+
+impl<'a> SerPath<'a> {
+    /// The steps from the root of the document down to the node.
+    pub fn segments(&self) -> &'a [PathSegment] {
+        self.segments
+    }
+
+    /// The key of the mapping entry whose value is the node, or `None` if the
+    /// node is an element of a sequence or the root of the document.
+    pub fn key(&self) -> Option<&'a str> {
+        match self.segments.last() {
+            Some(PathSegment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Display for SerPath<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.segments.is_empty() {
+            return formatter.write_str(".");
+        }
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => formatter.write_str(key)?,
+                PathSegment::Key(key) => write!(formatter, ".{}", key)?,
+                PathSegment::Index(index) => write!(formatter, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A scalar being serialized, passed to a [`SerializerBuilder::map_scalar`]
+/// or [`SerializerBuilder::map_node`] hook.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScalarRef<'a> {
+    /// A null, including a unit variant written as a tag on an empty node.
+    Null,
+    /// A boolean.
+    Bool(bool),
+    /// An integer or float, in the form it is written, like `8080`, `0.5` or
+    /// `.inf`.
+    Number(Cow<'a, str>),
+    /// A string. Characters and unit variants are serialized as strings too.
+    String(Cow<'a, str>),
+}
+
+/// What a [`SerializerBuilder::map_scalar`] hook does with a scalar.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScalarAction {
+    /// Write the scalar unchanged.
+    Keep,
+    /// Write this scalar instead, with the same tag as the original. A
+    /// replacement string is quoted as needed to read back as a string, while
+    /// the text of a replacement number is written as is.
+    Replace(ScalarRef<'static>),
+    /// Leave out the mapping entry or sequence element holding the scalar. A
+    /// scalar at the root of a document is written as null instead.
+    Skip,
+}
+
+/// A node being serialized, passed to a [`SerializerBuilder::map_node`] hook.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeRef<'a> {
+    /// A scalar.
+    Scalar(ScalarRef<'a>),
+    /// The start of a sequence. Its elements are passed to the hook after
+    /// it.
+    Sequence,
+    /// The start of a mapping. The values of its entries are passed to the
+    /// hook after it.
+    Mapping,
+}
+
+/// What a [`SerializerBuilder::map_node`] hook does with a node.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeAction {
+    /// Write the node unchanged.
+    Keep,
+    /// Write this scalar instead of the node, with the same tag as the
+    /// original, as with [`ScalarAction::Replace`]. Whatever is inside of a
+    /// replaced sequence or mapping is left out.
+    Replace(ScalarRef<'static>),
+    /// Leave out the mapping entry or sequence element holding the node,
+    /// with whatever is inside of it. A node at the root of a document is
+    /// written as null instead.
+    Skip,
+    /// Write the mapping with these entries added at its end. Their values
+    /// are not passed to the hook. The same as `Keep` for a node that is
+    /// not a mapping.
+    Insert(Vec<(String, ScalarRef<'static>)>),
+}
+
+type MapNode = dyn Fn(&SerPath, NodeRef) -> NodeAction + Send + Sync;
+
+#[derive(Clone)]
+struct NodeHook(Arc<MapNode>);
+
+impl Debug for NodeHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("NodeHook")
+    }
+}
+
+/// Options for configuring a [`Serializer`].
+///
+/// Both spellings produced by these options are accepted by the deserializer
+/// regardless of how the serializer was configured.
+///
+/// ```
+/// # use serde_derive::Serialize;
+/// use serde::Serialize;
+/// use serde_yaml::{SerializerBuilder, UnitVariants};
+///
+/// #[derive(Serialize)]
+/// enum State {
+///     Active,
+/// }
+///
+/// fn main() -> serde_yaml::Result<()> {
+///     let yaml = SerializerBuilder::new()
+///         .unit_variants(UnitVariants::Tag)
+///         .to_string(&State::Active)?;
+///     assert_eq!(yaml, "!Active\n");
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SerializerBuilder {
+    unit_variants: UnitVariants,
+    #[cfg(feature = "value")]
+    verify_roundtrip: bool,
+    #[cfg(feature = "value")]
+    deterministic: bool,
+    #[cfg(feature = "value")]
+    sort_keys: bool,
+    #[cfg(feature = "value")]
+    key_order: Option<Arc<KeyOrder>>,
+    map_node: Option<NodeHook>,
+    indent: usize,
+    document_start: bool,
+    document_end: bool,
+    trailing_newline: bool,
+    yaml_directive: bool,
+    tag_directives: Vec<(String, String)>,
+    tags: bool,
+    canonical: bool,
+    explicit_tags: bool,
+    flow_style: bool,
+    compact: bool,
+    null_style: NullStyle,
+    quote_style: QuoteStyle,
+    quote_keys: bool,
+    literal_strings: bool,
+    indent_sequences: bool,
+    align_values: bool,
+    fold_long_strings: bool,
+    json: bool,
+    ascii: bool,
+    quote_for: SchemaProfile,
+    crlf: bool,
+    initial_indent: usize,
+    bare_first_line: bool,
+    width: Option<i32>,
+    float_format: FloatFormat,
+    scientific_threshold: Option<u32>,
+    emitter_compat: EmitterCompat,
+}
+
+/// A preset of [`SerializerBuilder`] options for output that is read by a
+/// particular kind of tool, selected with [`SerializerBuilder::with_profile`]
+/// or [`Serializer::with_profile`].
+///
+/// Each preset only sets options that have their own method on the builder,
+/// so any of them can be changed again after selecting a profile.
+///
+/// ```
+/// use serde_yaml::{SerializerBuilder, SerializerProfile};
+/// use std::collections::BTreeMap;
+///
+/// let mut labels = BTreeMap::new();
+/// labels.insert("app", "web");
+/// labels.insert("country", "NO");
+///
+/// let yaml = SerializerBuilder::with_profile(SerializerProfile::KubernetesStyle)
+///     .to_string(&labels)
+///     .unwrap();
+/// assert_eq!(yaml, "app: web\ncountry: 'NO'\n");
+///
+/// let yaml = SerializerBuilder::with_profile(SerializerProfile::KubernetesStyle)
+///     .document_start(true)
+///     .to_string(&labels)
+///     .unwrap();
+/// assert_eq!(yaml, "---\napp: web\ncountry: 'NO'\n");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SerializerProfile {
+    /// Manifests for Kubernetes and other tools built on YAML 1.1 parsers:
+    /// 2-space indentation, sequences inside a mapping indented below their
+    /// key, no `---` before a single document, strings quoted for
+    /// [`SchemaProfile::Legacy11`], and an error for any tagged value, which
+    /// these tools reject.
+    KubernetesStyle,
+    /// GitHub Actions workflows, laid out the same as
+    /// [`KubernetesStyle`](Self::KubernetesStyle) and also erroring on
+    /// tagged values. Strings are quoted for the core schema only, so the
+    /// `on` key of a workflow stays unquoted.
+    GitHubActionsStyle,
+    /// The canonical form of the YAML specification: flow collections,
+    /// double-quoted scalars, an explicit `---`, and the tag of every node
+    /// written out, so that it reads back the same under any schema.
+    Canonical,
+    /// Output for YAML 1.1 parsers such as PyYAML: strings quoted for
+    /// [`SchemaProfile::Legacy11`], otherwise the default options.
+    Compat11,
+}
+
+impl SerializerBuilder {
+    /// Creates a builder with the default options, which produce the same
+    /// output as [`Serializer::new`].
+    pub fn new() -> Self {
+        SerializerBuilder {
+            unit_variants: UnitVariants::PlainString,
+            #[cfg(feature = "value")]
+            verify_roundtrip: false,
+            #[cfg(feature = "value")]
+            deterministic: false,
+            #[cfg(feature = "value")]
+            sort_keys: false,
+            #[cfg(feature = "value")]
+            key_order: None,
+            map_node: None,
+            indent: 2,
+            document_start: false,
+            document_end: false,
+            trailing_newline: true,
+            yaml_directive: false,
+            tag_directives: Vec::new(),
+            tags: true,
+            canonical: false,
+            explicit_tags: false,
+            flow_style: false,
+            compact: false,
+            null_style: NullStyle::Null,
+            quote_style: QuoteStyle::WhenNeeded,
+            quote_keys: false,
+            literal_strings: false,
+            indent_sequences: false,
+            align_values: false,
+            fold_long_strings: false,
+            json: false,
+            ascii: false,
+            quote_for: SchemaProfile::Core12,
+            crlf: false,
+            initial_indent: 0,
+            bare_first_line: false,
+            width: None,
+            float_format: FloatFormat::Shortest,
+            scientific_threshold: None,
+            emitter_compat: EmitterCompat::Latest,
+        }
+    }
+
+    /// Creates a builder with the options of a profile. The options can be
+    /// changed further with the other methods of the builder.
+    pub fn with_profile(profile: SerializerProfile) -> Self {
+        let builder = SerializerBuilder::new();
+        match profile {
+            SerializerProfile::KubernetesStyle => builder
+                .indent(2)
+                .indent_sequences(true)
+                .tags(false)
+                .quote_for(SchemaProfile::Legacy11),
+            SerializerProfile::GitHubActionsStyle => {
+                builder.indent(2).indent_sequences(true).tags(false)
+            }
+            SerializerProfile::Canonical => builder.canonical(true).document_start(true),
+            SerializerProfile::Compat11 => builder.quote_for(SchemaProfile::Legacy11),
+        }
+    }
+
+    /// Selects how enum variants without data are written.
+    pub fn unit_variants(mut self, unit_variants: UnitVariants) -> Self {
+        self.unit_variants = unit_variants;
+        self
+    }
+
+    /// Checks that every document reads back as what was serialized.
+    ///
+    /// After each document is written, the output is parsed into a [`Value`]
+    /// and compared against the strings, numbers, tags and collections that
+    /// were serialized. If they differ, serialization fails with an error
+    /// naming the path of the first node that does not read back the same,
+    /// and if the output cannot be parsed at all, with the error from parsing
+    /// it. This parses everything that is written, and keeps a copy of each
+    /// document until it is checked, so it is intended for tests and debug
+    /// builds.
+    ///
+    /// [`Value`]: crate::Value
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut map = BTreeMap::new();
+    /// map.insert("version", "1.0");
+    /// map.insert("port", "8080");
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .verify_roundtrip(true)
+    ///     .to_string(&map)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "port: '8080'\nversion: '1.0'\n");
+    /// ```
+    #[cfg(feature = "value")]
+    pub fn verify_roundtrip(mut self, verify_roundtrip: bool) -> Self {
+        self.verify_roundtrip = verify_roundtrip;
+        self
+    }
+
+    /// Writes the same bytes every time for equal data, for reproducible
+    /// builds.
+    ///
+    /// The entries of every map are written sorted by their keys, in the
+    /// order of [`Value`]: nulls, then booleans, numbers by value, strings,
+    /// and sequences and mappings last. This applies to `HashMap` and any
+    /// other type that serializes as a map, including structs with
+    /// `#[serde(flatten)]` fields, but not to other structs, whose fields
+    /// are already written in the order they are declared. A zero float is
+    /// written as `0.0` even if it is negative; other floats always have a
+    /// single representation.
+    ///
+    /// A `HashSet` is given to the serializer as an ordinary sequence, whose
+    /// order it has to keep. Use [`with::sorted_set`] on such fields to sort
+    /// their elements.
+    ///
+    /// [`Value`]: crate::Value
+    /// [`with::sorted_set`]: crate::with::sorted_set
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("zone", "b");
+    /// map.insert("app", "web");
+    /// map.insert("tier", "front");
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .deterministic(true)
+    ///     .to_string(&map)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "app: web\ntier: front\nzone: b\n");
+    /// ```
+    #[cfg(feature = "value")]
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Writes the entries of every mapping sorted by their keys, for output
+    /// that is compared as text. Unlike [`deterministic`], this includes
+    /// structs, whose fields are written in lexicographic order instead of
+    /// the order they are declared in. Keys are sorted in the order of
+    /// [`Value`], so string keys are in lexicographic order and come after
+    /// nulls, booleans and numbers.
+    ///
+    /// [`deterministic`]: SerializerBuilder::deterministic
+    /// [`Value`]: crate::Value
+    ///
+    /// ```
+    /// use serde_derive::Serialize;
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Service {
+    ///     name: &'static str,
+    ///     image: &'static str,
+    ///     ports: Vec<u16>,
+    /// }
+    ///
+    /// let service = Service {
+    ///     name: "web",
+    ///     image: "nginx",
+    ///     ports: vec![80],
+    /// };
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .sort_keys(true)
+    ///     .to_string(&service)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "image: nginx\nname: web\nports:\n- 80\n");
+    /// ```
+    #[cfg(feature = "value")]
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Writes the entries of each mapping in the order that its keys have in
+    /// a [`KeyOrder`] from [`from_str_with_order`], for a mapping at the same
+    /// path as one that was read. Keys that were not read are written after
+    /// the others, in the order they would be written otherwise. Mappings
+    /// that were not read keep their order.
+    ///
+    /// This applies to structs as well as to `HashMap` and other maps. Keys
+    /// are matched by how they are written, so a key that was read as `0x10`
+    /// and is written as `16` counts as a new key.
+    ///
+    /// [`from_str_with_order`]: crate::from_str_with_order
+    ///
+    /// ```
+    /// use serde_derive::{Deserialize, Serialize};
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Service {
+    ///     name: String,
+    ///     port: u16,
+    ///     #[serde(default)]
+    ///     tls: bool,
+    /// }
+    ///
+    /// let yaml = "port: 80\nname: web\n";
+    /// let (mut service, order): (Service, _) = serde_yaml::from_str_with_order(yaml).unwrap();
+    /// service.port = 443;
+    /// service.tls = true;
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .key_order(&order)
+    ///     .to_string(&service)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "port: 443\nname: web\ntls: true\n");
+    /// ```
+    #[cfg(feature = "value")]
+    pub fn key_order(mut self, order: &KeyOrder) -> Self {
+        self.key_order = Some(Arc::new(order.clone()));
+        self
+    }
+
+    /// Passes every scalar value to a hook, which decides whether it is
+    /// written unchanged, replaced, or left out.
+    ///
+    /// The hook is given the path of the scalar within the document, which
+    /// makes it a central place to redact secrets, normalize floats or trim
+    /// strings. Mapping keys, and scalars inside of keys that are sequences or
+    /// mappings, are not passed to the hook. See [`map_node`] for a hook that
+    /// is also given sequences and mappings. Only one of the two hooks is
+    /// used, the one that is set last.
+    ///
+    /// [`map_node`]: SerializerBuilder::map_node
+    ///
+    /// ```
+    /// use serde_yaml::{ScalarAction, ScalarRef, SerializerBuilder};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut map = BTreeMap::new();
+    /// map.insert("user", "admin");
+    /// map.insert("password", "hunter2");
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .map_scalar(|path, _scalar| match path.key() {
+    ///         Some("password") => ScalarAction::Replace(ScalarRef::String("***".into())),
+    ///         _ => ScalarAction::Keep,
+    ///     })
+    ///     .to_string(&map)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "password: '***'\nuser: admin\n");
+    /// ```
+    pub fn map_scalar<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SerPath, ScalarRef) -> ScalarAction + Send + Sync + 'static,
+    {
+        self.map_node = Some(NodeHook(Arc::new(move |path, node| match node {
+            NodeRef::Scalar(scalar) => match hook(path, scalar) {
+                ScalarAction::Keep => NodeAction::Keep,
+                ScalarAction::Replace(replacement) => NodeAction::Replace(replacement),
+                ScalarAction::Skip => NodeAction::Skip,
+            },
+            NodeRef::Sequence | NodeRef::Mapping => NodeAction::Keep,
+        })));
+        self
+    }
+
+    /// Passes every node to a hook before it is written, which decides
+    /// whether it is written unchanged, replaced by a scalar, left out, or,
+    /// for a mapping, written with extra entries.
+    ///
+    /// This is [`map_scalar`] for sequences and mappings too. The hook is
+    /// given the start of each sequence and mapping before the nodes inside
+    /// of it, along with its path within the document, so that internal
+    /// fields can be left out and metadata added without changing the types
+    /// being serialized. Nodes inside of a node that is left out or replaced
+    /// are not passed to the hook, and neither are mapping keys. Only one of
+    /// the two hooks is used, the one that is set last.
+    ///
+    /// [`map_scalar`]: SerializerBuilder::map_scalar
+    ///
+    /// ```
+    /// use serde_yaml::{NodeAction, NodeRef, ScalarRef, SerializerBuilder};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut service = BTreeMap::new();
+    /// service.insert("name", BTreeMap::from([("web", "shop")]));
+    /// service.insert("internal", BTreeMap::from([("token", "abc")]));
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .map_node(|path, node| match (path.key(), node) {
+    ///         (Some("internal"), _) => NodeAction::Skip,
+    ///         (None, NodeRef::Mapping) => NodeAction::Insert(vec![(
+    ///             "audited_by".to_owned(),
+    ///             ScalarRef::String("ci".into()),
+    ///         )]),
+    ///         _ => NodeAction::Keep,
+    ///     })
+    ///     .to_string(&service)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "name:\n  web: shop\naudited_by: ci\n");
+    /// ```
+    pub fn map_node<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SerPath, NodeRef) -> NodeAction + Send + Sync + 'static,
+    {
+        self.map_node = Some(NodeHook(Arc::new(hook)));
+        self
+    }
+
+    /// The number of spaces that each level of a block mapping is indented
+    /// by, from 2 to 9. Other numbers are treated as 2, which is the default.
+    ///
+    /// A sequence that is the value of a mapping entry starts at the
+    /// indentation of its key, and the contents of each of its elements at
+    /// the next level, as libyaml and PyYAML write them.
+    ///
+    /// ```
+    /// use serde_yaml::{SerializerBuilder, Value};
+    ///
+    /// let value: Value = serde_yaml::from_str("{server: {port: 80, hosts: [a, {b: 1}]}}").unwrap();
+    /// let yaml = SerializerBuilder::new().indent(4).to_string(&value).unwrap();
+    /// assert_eq!(yaml, "server:\n    port: 80\n    hosts:\n    - a\n    -   b: 1\n");
+    /// ```
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Indents a sequence that is the value of a mapping entry by one level
+    /// more than its key, as in `hosts:` followed by `  - a` on the next
+    /// line, for linters like yamllint that require this. By default such a
+    /// sequence is written at the indentation of its key, as libyaml,
+    /// PyYAML and `kubectl` write it.
+    ///
+    /// The output of each document is held back until the document is
+    /// complete, and the underlying writer is flushed after each document.
+    ///
+    /// ```
+    /// use serde_yaml::{SerializerBuilder, Value};
+    ///
+    /// let value: Value = serde_yaml::from_str("{hosts: [a, {b: [1, 2]}]}").unwrap();
+    /// let yaml = SerializerBuilder::new()
+    ///     .indent_sequences(true)
+    ///     .to_string(&value)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "hosts:\n  - a\n  - b:\n      - 1\n      - 2\n");
+    /// ```
+    pub fn indent_sequences(mut self, indent_sequences: bool) -> Self {
+        self.indent_sequences = indent_sequences;
+        self
+    }
+
+    /// Lines up the values of each block mapping in a column, by writing
+    /// more spaces after the shorter keys, for output that is read as a
+    /// table. Only values that start on the line of their key are lined up,
+    /// so a nested block collection is not, and neither are the entries of
+    /// the mappings inside of it with those of the outer mapping.
+    ///
+    /// As with [`indent_sequences`], the output of each document is held
+    /// back until the document is complete.
+    ///
+    /// [`indent_sequences`]: SerializerBuilder::indent_sequences
+    ///
+    /// ```
+    /// use serde_derive::Serialize;
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Service {
+    ///     name: &'static str,
+    ///     port: u16,
+    ///     replicas: u32,
+    /// }
+    ///
+    /// let service = Service {
+    ///     name: "web",
+    ///     port: 80,
+    ///     replicas: 3,
+    /// };
+    /// let yaml = SerializerBuilder::new()
+    ///     .align_values(true)
+    ///     .to_string(&service)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "name:     web\nport:     80\nreplicas: 3\n");
+    /// ```
+    pub fn align_values(mut self, align_values: bool) -> Self {
+        self.align_values = align_values;
+        self
+    }
+
+    /// Writes `---` at the start of every document, for parsers that
+    /// require it, whether the root of the document is a scalar or a
+    /// collection. By default it is only written between documents, where it
+    /// is always needed to separate them.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let builder = SerializerBuilder::new().document_start(true);
+    /// assert_eq!(builder.to_string("text").unwrap(), "--- text\n");
+    /// assert_eq!(builder.to_string(&[1, 2]).unwrap(), "---\n- 1\n- 2\n");
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut serializer = SerializerBuilder::new().build(&mut buffer);
+    /// "first".serialize(&mut serializer).unwrap();
+    /// "second".serialize(&mut serializer).unwrap();
+    /// drop(serializer);
+    /// assert_eq!(buffer, b"first\n--- second\n");
+    /// ```
+    pub fn document_start(mut self, document_start: bool) -> Self {
+        self.document_start = document_start;
+        self
+    }
+
+    /// Writes `...` at the end of every document, which tells a reader of a
+    /// stream that the document is complete without waiting for the next
+    /// one to start.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut serializer = SerializerBuilder::new().document_end(true).build(&mut buffer);
+    /// "first".serialize(&mut serializer).unwrap();
+    /// [1, 2].serialize(&mut serializer).unwrap();
+    /// drop(serializer);
+    /// assert_eq!(buffer, b"first\n...\n---\n- 1\n- 2\n...\n");
+    /// ```
+    pub fn document_end(mut self, document_end: bool) -> Self {
+        self.document_end = document_end;
+        self
+    }
+
+    /// Whether the output ends with a line break, which is the default. When
+    /// false, the line break at the end of the last document is left out,
+    /// for output that is joined with other text on the same line. The line
+    /// breaks between documents are still written.
+    ///
+    /// A string at the end of the output that is written as a literal or
+    /// folded block scalar then reads back without its last line break.
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let builder = SerializerBuilder::new().trailing_newline(false);
+    /// assert_eq!(builder.to_string(&[1, 2]).unwrap(), "- 1\n- 2");
+    /// assert_eq!(builder.to_string("text").unwrap(), "text");
+    /// ```
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Writes a `%YAML 1.2` directive before each document, for parsers that
+    /// need to be told the version of YAML to read it as. A document with a
+    /// directive always starts with `---`.
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .yaml_directive(true)
+    ///     .to_string(&["a", "b"])
+    ///     .unwrap();
+    /// assert_eq!(yaml, "%YAML 1.2\n---\n- a\n- b\n");
+    /// ```
+    pub fn yaml_directive(mut self, yaml_directive: bool) -> Self {
+        self.yaml_directive = yaml_directive;
+        self
+    }
+
+    /// Adds a `%TAG` directive before each document, which declares `handle`
+    /// as a short form of `prefix`. A tag that starts with `prefix` is then
+    /// written with the handle instead, as in `!e!widget` for
+    /// `tag:example.com,2000:widget`, and it is not made into a local tag
+    /// with a `!` in front as other tags are.
+    ///
+    /// The handle is `!`, `!!` or a name of letters, digits, `-` and `_`
+    /// between two `!`, like `!e!`. Serializing with any other handle, or an
+    /// empty prefix, is an error.
+    ///
+    /// ```
+    /// # use serde_derive::Serialize;
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Shape {
+    ///     #[serde(rename = "tag:example.com,2000:circle")]
+    ///     Circle { radius: u8 },
+    /// }
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .tag_directive("!e!", "tag:example.com,2000:")
+    ///     .to_string(&Shape::Circle { radius: 3 })
+    ///     .unwrap();
+    /// assert_eq!(yaml, "%TAG !e! tag:example.com,2000:\n--- !e!circle\nradius: 3\n");
+    /// ```
+    pub fn tag_directive(mut self, handle: &str, prefix: &str) -> Self {
+        self.tag_directives
+            .push((handle.to_owned(), prefix.to_owned()));
+        self
+    }
+
+    /// Whether values may be written with a tag, which is the default. When
+    /// false, serializing an enum variant that has data, a [`Value::Tagged`],
+    /// a unit variant with [`UnitVariants::Tag`] or bytes is an error, for output
+    /// that is read by tools that do not accept tags.
+    ///
+    /// [`Value::Tagged`]: crate::Value::Tagged
+    pub fn tags(mut self, tags: bool) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Writes the canonical form of YAML, in which every collection is in
+    /// flow style, every scalar is double-quoted, and every node has a tag:
+    /// the tag it was serialized with, or else a tag of the core schema like
+    /// `!!int` or `!!str`.
+    ///
+    /// Equal values are written as the same text, for output from different
+    /// programs that is compared byte for byte: a null is `null` whatever
+    /// the [`null_style`], a zero float is `0.0` even if it is negative, and
+    /// a number that was read with [`preserve_number_repr`] is written the
+    /// same way as any other number, not in the form it was read in. The
+    /// entries of a mapping stay in the order they are serialized in unless
+    /// [`sort_keys`] is also set.
+    ///
+    /// [`null_style`]: SerializerBuilder::null_style
+    /// [`sort_keys`]: SerializerBuilder::sort_keys
+    /// [`preserve_number_repr`]: crate::DeserializerBuilder::preserve_number_repr
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .canonical(true)
+    ///     .to_string(&("port", 8080))
+    ///     .unwrap();
+    /// assert_eq!(yaml, "---\n!!seq [\n  !!str \"port\",\n  !!int \"8080\",\n]\n");
+    /// ```
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Writes a tag on every node, for loaders that do not resolve untagged
+    /// nodes: the tag it was serialized with, or else a tag of the core
+    /// schema like `!!int`, `!!str`, `!!seq` or `!!map`. Unlike
+    /// [`canonical`], this keeps block collections and plain scalars. A
+    /// string that would otherwise be quoted so that it does not read as a
+    /// boolean or a number can be plain, as its tag already says it is a
+    /// string. As in canonical output, there are no comments, and a value
+    /// that would be written as a comment is written as it is.
+    ///
+    /// [`canonical`]: SerializerBuilder::canonical
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let services = BTreeMap::from([("web", (8080, "yes", None::<u8>))]);
+    /// let yaml = SerializerBuilder::new()
+    ///     .explicit_tags(true)
+    ///     .to_string(&services)
+    ///     .unwrap();
+    /// let expected = "\
+    /// !!map
+    /// !!str web: !!seq
+    /// - !!int 8080
+    /// - !!str yes
+    /// - !!null null
+    /// ";
+    /// assert_eq!(yaml, expected);
+    /// ```
+    pub fn explicit_tags(mut self, explicit_tags: bool) -> Self {
+        self.explicit_tags = explicit_tags;
+        self
+    }
+
+    /// Writes every sequence and mapping in flow style, like
+    /// `{a: 1, b: [2, 3]}`, on a single line. Strings that contain a line
+    /// break are then written in double quotes, as block scalars cannot be
+    /// part of a flow collection.
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut map = BTreeMap::new();
+    /// map.insert("a", vec!["x, y", "two\nlines"]);
+    /// map.insert("b", vec![]);
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .flow_style(true)
+    ///     .to_string(&map)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "{a: ['x, y', \"two\\nlines\"], b: []}\n");
+    /// ```
+    pub fn flow_style(mut self, flow_style: bool) -> Self {
+        self.flow_style = flow_style;
+        self
+    }
+
+    /// Writes a sequence or mapping in flow style if it only has scalars in
+    /// it and is short, like `position: [1, 2, 3]`, and everything else in
+    /// block style. A collection is short if it takes up at most 60
+    /// characters in flow style. The root of the document, and a collection
+    /// with a string that needs a block scalar or with a comment, are always
+    /// written in block style.
+    ///
+    /// ```
+    /// use serde_yaml::{SerializerBuilder, Value};
+    ///
+    /// let unit: Value = serde_yaml::from_str(
+    ///     "{name: scout, position: [1, 2, 3], path: [[0, 0], [4, 2]], stats: {hp: 10, speed: 3}}",
+    /// )
+    /// .unwrap();
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .compact(true)
+    ///     .to_string(&unit)
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     yaml,
+    ///     "name: scout\nposition: [1, 2, 3]\npath:\n- [0, 0]\n- [4, 2]\nstats: {hp: 10, speed: 3}\n",
+    /// );
+    /// ```
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Selects how `None` and `()` are written, for tools that only accept
+    /// one of the ways YAML has of writing a null. A [`Value::Null`] is
+    /// written the same way.
+    ///
+    /// [`Value::Null`]: crate::Value::Null
+    ///
+    /// ```
+    /// use serde_yaml::{NullStyle, SerializerBuilder};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut map = BTreeMap::new();
+    /// map.insert("port", None);
+    /// map.insert("replicas", Some(3));
+    ///
+    /// let builder = SerializerBuilder::new().null_style(NullStyle::Tilde);
+    /// assert_eq!(builder.to_string(&map).unwrap(), "port: ~\nreplicas: 3\n");
+    ///
+    /// let builder = SerializerBuilder::new().null_style(NullStyle::Empty);
+    /// assert_eq!(builder.to_string(&map).unwrap(), "port:\nreplicas: 3\n");
+    /// ```
+    pub fn null_style(mut self, null_style: NullStyle) -> Self {
+        self.null_style = null_style;
+        self
+    }
+
+    /// Selects how strings are quoted, keys included, for tools that read
+    /// plain scalars like `no` or `1.0` as something other than a string.
+    /// A [`StyledString`][crate::de::StyledString] keeps the style of its
+    /// own.
+    ///
+    /// ```
+    /// use serde_yaml::{QuoteStyle, SerializerBuilder};
+    ///
+    /// let builder = SerializerBuilder::new().quote_style(QuoteStyle::Single);
+    /// assert_eq!(builder.to_string(&[("no", 1.0)]).unwrap(), "- - 'no'\n  - 1.0\n");
+    ///
+    /// let builder = SerializerBuilder::new().quote_style(QuoteStyle::Double);
+    /// assert_eq!(builder.to_string(&["text", "it's"]).unwrap(), "- \"text\"\n- \"it's\"\n");
+    /// ```
+    pub fn quote_style(mut self, quote_style: QuoteStyle) -> Self {
+        self.quote_style = quote_style;
+        self
+    }
+
+    /// Writes every string key in quotes, also the ones that read back as the
+    /// same string without them, for tools that read some plain keys as
+    /// something else, like `on` as a boolean in YAML 1.1. Keys are
+    /// single-quoted unless [`quote_style`] asks for double quotes. Keys that
+    /// are not strings, like numbers, are written as usual.
+    ///
+    /// [`quote_style`]: SerializerBuilder::quote_style
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut on = BTreeMap::new();
+    /// on.insert("push", vec!["main"]);
+    /// let mut workflow = BTreeMap::new();
+    /// workflow.insert("on", on);
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .quote_keys(true)
+    ///     .to_string(&workflow)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "'on':\n  'push':\n  - main\n");
+    /// ```
+    pub fn quote_keys(mut self, quote_keys: bool) -> Self {
+        self.quote_keys = quote_keys;
+        self
+    }
+
+    /// Writes every string with a line break in it as a literal block
+    /// scalar, as in `script: |` followed by the lines of the string, even
+    /// with a [`quote_style`] that quotes other strings. With the default
+    /// [`QuoteStyle::WhenNeeded`] this is already how such strings are
+    /// written.
+    ///
+    /// The emitter still writes a string in double quotes, with its line
+    /// breaks as `\n`, where a block scalar is not allowed: in a flow
+    /// collection, as a key, or if the string contains a tab, a space at the
+    /// end of a line, or a character that can only be written as an escape.
+    ///
+    /// [`quote_style`]: SerializerBuilder::quote_style
+    ///
+    /// ```
+    /// use serde_yaml::{QuoteStyle, SerializerBuilder};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut job = BTreeMap::new();
+    /// job.insert("image", "rust");
+    /// job.insert("script", "cargo build\ncargo test\n");
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .quote_style(QuoteStyle::Double)
+    ///     .literal_strings(true)
+    ///     .to_string(&job)
+    ///     .unwrap();
+    /// assert_eq!(yaml, "\"image\": \"rust\"\n\"script\": |\n  cargo build\n  cargo test\n");
+    /// ```
+    pub fn literal_strings(mut self, literal_strings: bool) -> Self {
+        self.literal_strings = literal_strings;
+        self
+    }
+
+    /// Writes every string that is longer than the line width and has a
+    /// space in it as a folded block scalar, as in `description: >`
+    /// followed by the words of the string broken into lines, for prose that
+    /// is read and edited as text. The line width is that of [`width`], or
+    /// 80 if none is given, in which case other long lines are broken at 80
+    /// columns as well.
+    ///
+    /// Strings with a line break in them or a space at the start are not
+    /// folded. As for
+    /// [`literal_strings`], a string is written as it would be otherwise
+    /// where the emitter does not allow a block scalar. A
+    /// [`StyledString`][crate::de::StyledString] with
+    /// [`ScalarStyle::Folded`][crate::ScalarStyle::Folded] is written as a
+    /// folded scalar whatever its length.
+    ///
+    /// [`width`]: SerializerBuilder::width
+    /// [`literal_strings`]: SerializerBuilder::literal_strings
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut doc = BTreeMap::new();
+    /// doc.insert("summary", "Short text.");
+    /// doc.insert("description", "A longer text that runs past the width.");
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .width(Some(20))
+    ///     .fold_long_strings(true)
+    ///     .to_string(&doc)
+    ///     .unwrap();
+    /// let expected = "description: >-\n  A longer text that runs\n  past the width.\nsummary: Short text.\n";
+    /// assert_eq!(yaml, expected);
+    ///
+    /// let back: BTreeMap<String, String> = serde_yaml::from_str(&yaml).unwrap();
+    /// assert_eq!(back["description"], doc["description"]);
+    /// ```
+    pub fn fold_long_strings(mut self, fold_long_strings: bool) -> Self {
+        self.fold_long_strings = fold_long_strings;
+        self
+    }
+
+    /// Writes output that is valid JSON as well as YAML, for the same
+    /// serialization to be read by JSON parsers: every collection in flow
+    /// style, every string including keys in double quotes, and nulls as
+    /// `null`. Numbers are written in their shortest decimal form.
+    ///
+    /// Characters in strings that YAML writes as escapes JSON does not have,
+    /// such as `\0`, `\e` or `\L`, are written as `\u` escapes, and keys
+    /// that YAML writes after a `?`, those longer than 128 bytes or with a
+    /// line break, are written as ordinary keys. As with serde_json, keys
+    /// that are numbers or booleans are written as strings, and an enum
+    /// variant is written as its name, or with data as a mapping from its
+    /// name to the data like `{"Http": 80}`, which reads back into the enum
+    /// with [`with::singleton_map_recursive`].
+    ///
+    /// Anything that cannot be written as both JSON and YAML is an error:
+    ///
+    /// - a tag;
+    /// - a float that is not finite;
+    /// - a key that is null or a collection, or one that is longer than 1024
+    ///   bytes with its quotes and escapes, which YAML does not read as a
+    ///   key;
+    /// - more than one document written by the same serializer.
+    ///
+    /// This takes the place of [`flow_style`], [`quote_style`],
+    /// [`null_style`], [`width`], [`canonical`], [`document_start`],
+    /// [`yaml_directive`] and [`tag_directive`].
+    ///
+    /// [`with::singleton_map_recursive`]: crate::with::singleton_map_recursive
+    /// [`flow_style`]: SerializerBuilder::flow_style
+    /// [`quote_style`]: SerializerBuilder::quote_style
+    /// [`null_style`]: SerializerBuilder::null_style
+    /// [`width`]: SerializerBuilder::width
+    /// [`canonical`]: SerializerBuilder::canonical
+    /// [`document_start`]: SerializerBuilder::document_start
+    /// [`yaml_directive`]: SerializerBuilder::yaml_directive
+    /// [`tag_directive`]: SerializerBuilder::tag_directive
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut map = BTreeMap::new();
+    /// map.insert("name", Some(vec!["web", "yes"]));
+    /// map.insert("tags", None);
+    ///
+    /// let builder = SerializerBuilder::new().json(true);
+    /// let json = builder.to_string(&map).unwrap();
+    /// assert_eq!(json, "{\"name\": [\"web\", \"yes\"], \"tags\": null}\n");
+    ///
+    /// let error = builder.to_string(&[f64::NAN]).unwrap_err();
+    /// assert_eq!(error.to_string(), "`.nan` cannot be written as JSON");
+    /// ```
+    pub fn json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Writes output that is all ASCII, for consumers that do not handle
+    /// UTF-8. A string with a character outside of ASCII is written in
+    /// double quotes, with the character as an escape: `\xE9` for `é`,
+    /// `\u4E2D` for `中` and `\U0001F600` for `😀`. These read back as the
+    /// same characters.
+    ///
+    /// Comments have no escapes, so a comment with such a character is an
+    /// error. With [`json`], the escapes are all `\u` ones, such as `\u00E9`,
+    /// and a string with a character above U+FFFF is an error, since YAML
+    /// does not read the pair of them that JSON would write it as.
+    ///
+    /// [`json`]: SerializerBuilder::json
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .ascii(true)
+    ///     .to_string(&["café", "中文", "plain"])
+    ///     .unwrap();
+    /// assert_eq!(yaml, "- \"caf\\xE9\"\n- \"\\u4E2D\\u6587\"\n- plain\n");
+    /// ```
+    pub fn ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Also quotes strings that would read back as something other than a
+    /// string under the given schema, for output that is read by a parser
+    /// using that schema. Strings that the core schema would resolve are
+    /// always quoted, so this has no effect for [`SchemaProfile::Core12`],
+    /// the default, or [`SchemaProfile::Json`].
+    ///
+    /// ```
+    /// use serde_yaml::{SchemaProfile, SerializerBuilder};
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .quote_for(SchemaProfile::Legacy11)
+    ///     .to_string(&["yes", "0755", "12:30", "text"])
+    ///     .unwrap();
+    /// assert_eq!(yaml, "- 'yes'\n- '0755'\n- '12:30'\n- text\n");
+    /// ```
+    pub fn quote_for(mut self, schema: SchemaProfile) -> Self {
+        self.quote_for = schema;
+        self
+    }
+
+    /// Ends lines with `\r\n` instead of `\n`, for files that are edited on
+    /// Windows. This includes the line breaks inside of literal and folded
+    /// scalars, which read back as `\n` like any other line break.
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .crlf(true)
+    ///     .to_string(&["first", "second"])
+    ///     .unwrap();
+    /// assert_eq!(yaml, "- first\r\n- second\r\n");
+    /// ```
+    pub fn crlf(mut self, crlf: bool) -> Self {
+        self.crlf = crlf;
+        self
+    }
+
+    /// Starts every line with this many spaces, for output that is pasted
+    /// into another document below a key at that indentation. Lines that
+    /// are empty are left empty.
+    ///
+    /// The output is meant to be one document without a `---`. A literal or
+    /// folded scalar at the root of the document results in an error if its
+    /// contents start with a space or a line break: such a scalar is written
+    /// with an indentation indicator, like `|2`, which would be read relative
+    /// to the key that it is pasted below.
+    ///
+    /// ```
+    /// use serde_yaml::{SerializerBuilder, Value};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut ports = BTreeMap::new();
+    /// ports.insert("http", 80);
+    /// ports.insert("https", 443);
+    ///
+    /// let fragment = SerializerBuilder::new()
+    ///     .initial_indent(4)
+    ///     .to_string(&ports)
+    ///     .unwrap();
+    /// assert_eq!(fragment, "    http: 80\n    https: 443\n");
+    ///
+    /// let document = format!("server:\n  ports:\n{}", fragment);
+    /// let value: Value = serde_yaml::from_str(&document).unwrap();
+    /// assert_eq!(value["server"]["ports"]["https"], 443);
+    /// ```
+    pub fn initial_indent(mut self, spaces: usize) -> Self {
+        self.initial_indent = spaces;
+        self
+    }
+
+    /// Leaves out the spaces of [`initial_indent`] on the first line, for
+    /// output that is pasted after a key, as in `key: <output>`. The output
+    /// of a sequence or mapping then starts with a line break, as it cannot
+    /// start on the line of the key, unless it is written in flow style or
+    /// starts with a tag or anchor.
+    ///
+    /// [`initial_indent`]: SerializerBuilder::initial_indent
+    ///
+    /// ```
+    /// use serde_yaml::{SerializerBuilder, Value};
+    ///
+    /// let builder = SerializerBuilder::new()
+    ///     .initial_indent(4)
+    ///     .bare_first_line(true);
+    ///
+    /// let fragment = builder.to_string("text:\nmore").unwrap();
+    /// assert_eq!(fragment, "|-\n      text:\n      more\n");
+    ///
+    /// let fragment = builder.to_string(&["a", "b"]).unwrap();
+    /// assert_eq!(fragment, "\n    - a\n    - b\n");
+    ///
+    /// let document = format!("server:\n  hosts: {}", fragment);
+    /// let value: Value = serde_yaml::from_str(&document).unwrap();
+    /// assert_eq!(value["server"]["hosts"][1], "b");
+    /// ```
+    pub fn bare_first_line(mut self, bare_first_line: bool) -> Self {
+        self.bare_first_line = bare_first_line;
+        self
+    }
+
+    /// Sets the column that long lines are broken after, or `None` to never
+    /// break them, which is the default. A line is broken at the first space
+    /// past this column, in a string or between the items of a flow
+    /// collection, so it runs past the width by up to one word. A string
+    /// with a line break in it is written as a literal block scalar, which
+    /// is never broken. The spaces of [`initial_indent`] are not counted.
+    ///
+    /// [`initial_indent`]: SerializerBuilder::initial_indent
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let text = "the quick brown fox jumps over the lazy dog";
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .width(Some(20))
+    ///     .to_string(&[text])
+    ///     .unwrap();
+    /// assert_eq!(yaml, "- the quick brown fox\n  jumps over the lazy\n  dog\n");
+    ///
+    /// let yaml = SerializerBuilder::new().width(None).to_string(&[text]).unwrap();
+    /// assert_eq!(yaml, format!("- {}\n", text));
+    /// ```
+    pub fn width(mut self, width: Option<usize>) -> Self {
+        self.width = Some(match width {
+            Some(width) => i32::try_from(width).unwrap_or(i32::MAX),
+            None => -1,
+        });
+        self
+    }
+
+    /// Selects how finite floats are written, for output that people read
+    /// and edit, where a float like `0.1 + 0.2` would otherwise be written
+    /// with all 17 digits that it takes to read back as the same float. The
+    /// default is [`FloatFormat::Shortest`]. A float read with
+    /// [`preserve_number_repr`] is written as it was read.
+    ///
+    /// [`preserve_number_repr`]: crate::DeserializerBuilder::preserve_number_repr
+    ///
+    /// ```
+    /// use serde_yaml::{FloatFormat, SerializerBuilder};
+    ///
+    /// let ratio = 0.1 + 0.2;
+    /// assert_eq!(serde_yaml::to_string(&ratio).unwrap(), "0.30000000000000004\n");
+    ///
+    /// let builder = SerializerBuilder::new().float_format(FloatFormat::Significant(15));
+    /// assert_eq!(builder.to_string(&ratio).unwrap(), "0.3\n");
+    ///
+    /// let builder = SerializerBuilder::new().float_format(FloatFormat::Fixed(2));
+    /// assert_eq!(builder.to_string(&ratio).unwrap(), "0.30\n");
+    /// ```
+    pub fn float_format(mut self, float_format: FloatFormat) -> Self {
+        self.float_format = float_format;
+        self
+    }
+
+    /// Writes a float with an exponent when the exponent of its first digit
+    /// is at least this far from 0, as in `1.5e6` for a threshold of 6, and
+    /// without one otherwise. `None`, the default, leaves this to the
+    /// formatting of the [`emitter_compat`] version, which writes an
+    /// exponent from `1e16` up and below `0.00001`. This has no effect on
+    /// [`FloatFormat::Fixed`], which never writes an exponent.
+    ///
+    /// [`emitter_compat`]: SerializerBuilder::emitter_compat
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    ///
+    /// let builder = SerializerBuilder::new().scientific_threshold(Some(6));
+    /// let yaml = builder.to_string(&[1500000.0, 250000.0, 0.000001]).unwrap();
+    /// assert_eq!(yaml, "- 1.5e6\n- 250000.0\n- 1e-6\n");
+    ///
+    /// let builder = SerializerBuilder::new().scientific_threshold(Some(30));
+    /// assert_eq!(builder.to_string(&1e20).unwrap(), "100000000000000000000.0\n");
+    /// ```
+    pub fn scientific_threshold(mut self, threshold: Option<u32>) -> Self {
+        self.scientific_threshold = threshold;
+        self
+    }
+
+    /// Writes scalars by the rules of a version of this crate, so that the
+    /// output stays the same when those of later versions change. The
+    /// default is [`EmitterCompat::Latest`].
+    ///
+    /// ```
+    /// use serde_yaml::{EmitterCompat, SerializerBuilder};
+    ///
+    /// let builder = SerializerBuilder::new().emitter_compat(EmitterCompat::V0_9);
+    /// assert_eq!(builder.to_string(&[1e21, 0.5]).unwrap(), "- 1e21\n- 0.5\n");
+    /// ```
+    pub fn emitter_compat(mut self, emitter_compat: EmitterCompat) -> Self {
+        self.emitter_compat = emitter_compat;
+        self
+    }
+
+    /// Creates a serializer with these options, writing to the given IO
+    /// stream.
+    pub fn build<W>(self, writer: W) -> Serializer<W>
+    where
+        W: io::Write,
+    {
+        Serializer::with_options(writer, self)
+    }
+
+    /// Serialize the given data structure as YAML into the IO stream, using
+    /// these options.
+    pub fn to_writer<W, T>(&self, writer: W, value: &T) -> Result<()>
+    where
+        W: io::Write,
+        T: ?Sized + ser::Serialize,
+    {
+        let mut serializer = self.clone().build(writer);
+        value.serialize(&mut serializer)
+    }
+
+    /// Serialize the given data structure as a String of YAML, using these
+    /// options.
+    pub fn to_string<T>(&self, value: &T) -> Result<String>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let mut vec = Vec::with_capacity(128);
+        self.to_writer(&mut vec, value)?;
+        String::from_utf8(vec).map_err(|error| error::new(ErrorImpl::FromUtf8(error)))
+    }
+
+    /// Serialize the given data structure as YAML into a [`fmt::Write`],
+    /// like a `String` or a `Formatter`, using these options.
+    pub fn to_fmt_writer<W, T>(&self, writer: W, value: &T) -> Result<()>
+    where
+        W: fmt::Write,
+        T: ?Sized + ser::Serialize,
+    {
+        let mut serializer = self.clone().build(FmtWriter::new(writer));
+        value.serialize(&mut serializer)
+    }
+
+    /// Serialize each of the values as a document of one YAML stream into
+    /// the IO stream, using these options.
+    pub fn to_writer_multi<W, I>(&self, writer: W, values: I) -> Result<()>
+    where
+        W: io::Write,
+        I: IntoIterator,
+        I::Item: ser::Serialize,
+    {
+        let mut serializer = self.clone().build(writer);
+        for value in values {
+            ser::Serialize::serialize(&value, &mut serializer)?;
+        }
         Ok(())
     }
 
-    /// Unwrap the underlying `io::Write` object from the `Serializer`.
-    pub fn into_inner(mut self) -> Result<W> {
-        self.emitter.emit(Event::StreamEnd)?;
-        self.emitter.flush()?;
-        let writer = self.emitter.into_inner();
-        Ok(*unsafe { Box::from_raw(Box::into_raw(writer).cast::<W>()) })
+    /// Serialize each of the values as a document of one YAML stream, as a
+    /// String, using these options.
+    ///
+    /// ```
+    /// use serde_yaml::SerializerBuilder;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let service = BTreeMap::from([("kind", "Service")]);
+    /// let deployment = BTreeMap::from([("kind", "Deployment")]);
+    ///
+    /// let yaml = SerializerBuilder::new()
+    ///     .document_start(true)
+    ///     .to_string_multi([&service, &deployment])
+    ///     .unwrap();
+    /// assert_eq!(yaml, "---\nkind: Service\n---\nkind: Deployment\n");
+    /// ```
+    pub fn to_string_multi<I>(&self, values: I) -> Result<String>
+    where
+        I: IntoIterator,
+        I::Item: ser::Serialize,
+    {
+        let mut vec = Vec::with_capacity(128);
+        self.to_writer_multi(&mut vec, values)?;
+        String::from_utf8(vec).map_err(|error| error::new(ErrorImpl::FromUtf8(error)))
     }
 
-    fn emit_scalar(&mut self, mut scalar: Scalar) -> Result<()> {
-        self.flush_mapping_start()?;
-        if let Some(tag) = self.take_tag() {
-            scalar.tag = Some(tag);
+    // The column that the emitter breaks lines after, or -1 for never.
+    fn line_width(&self) -> i32 {
+        match self.width {
+            // A double-quoted string that is broken across lines is not JSON.
+            _ if self.json => -1,
+            Some(-1) => -1,
+            // The emitter takes a width of up to twice the indentation as 80.
+            Some(width) => width.max(self.indent.clamp(2, 9) as i32 * 2 + 1),
+            None if self.fold_long_strings => 80,
+            None => self.emitter_compat.strategy().width,
         }
-        self.value_start()?;
-        self.emitter.emit(Event::Scalar(scalar))?;
-        self.value_end()
     }
+}
 
-    fn emit_sequence_start(&mut self) -> Result<()> {
-        self.flush_mapping_start()?;
-        self.value_start()?;
-        let tag = self.take_tag();
-        self.emitter.emit(Event::SequenceStart(Sequence { tag }))?;
-        Ok(())
+impl SerializerBuilder {
+    // Whether every collection is written in flow style.
+    fn all_flow(&self) -> bool {
+        self.flow_style || self.json
     }
+}
 
-    fn emit_sequence_end(&mut self) -> Result<()> {
-        self.emitter.emit(Event::SequenceEnd)?;
-        self.value_end()
+impl Default for SerializerBuilder {
+    fn default() -> Self {
+        SerializerBuilder::new()
     }
+}
 
-    fn emit_mapping_start(&mut self) -> Result<()> {
-        self.flush_mapping_start()?;
-        self.value_start()?;
-        let tag = self.take_tag();
-        self.emitter.emit(Event::MappingStart(Mapping { tag }))?;
-        Ok(())
+fn scalar_ref<'a>(scalar: &Scalar<'a>) -> ScalarRef<'a> {
+    // As with verify_roundtrip, the serializer only writes nulls, booleans
+    // and numbers in plain style.
+    match scalar.style {
+        ScalarStyle::Plain => match scalar.value {
+            "null" | "~" | "" => ScalarRef::Null,
+            "true" => ScalarRef::Bool(true),
+            "false" => ScalarRef::Bool(false),
+            number => ScalarRef::Number(Cow::Borrowed(number)),
+        },
+        _ => ScalarRef::String(Cow::Borrowed(scalar.value)),
     }
+}
 
-    fn emit_mapping_end(&mut self) -> Result<()> {
-        self.emitter.emit(Event::MappingEnd)?;
-        self.value_end()
-    }
+// The characters that YAML allows in a document without escaping them.
+fn is_printable(ch: char) -> bool {
+    matches!(ch,
+        '\t' | '\n' | '\r' | ' '..='~' | '\u{85}'
+        | '\u{A0}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..='\u{10FFFF}')
+}
 
-    fn value_start(&mut self) -> Result<()> {
-        if self.depth == 0 {
-            self.emitter.emit(Event::DocumentStart)?;
+// The style to write a StyledString in. The emitter drops the content of a
+// block scalar that ends in a line or paragraph separator, which only a
+// double-quoted scalar can hold at its end.
+fn written_style(style: libyaml::parser::ScalarStyle, value: &str) -> ScalarStyle {
+    match style {
+        libyaml::parser::ScalarStyle::Literal | libyaml::parser::ScalarStyle::Folded
+            if value.ends_with(['\u{2028}', '\u{2029}']) =>
+        {
+            ScalarStyle::DoubleQuoted
         }
-        self.depth += 1;
+        libyaml::parser::ScalarStyle::Plain => ScalarStyle::Plain,
+        libyaml::parser::ScalarStyle::SingleQuoted => ScalarStyle::SingleQuoted,
+        libyaml::parser::ScalarStyle::DoubleQuoted => ScalarStyle::DoubleQuoted,
+        libyaml::parser::ScalarStyle::Literal => ScalarStyle::Literal,
+        libyaml::parser::ScalarStyle::Folded => ScalarStyle::Folded,
+    }
+}
+
+// Picks a style for a string that reads back as a string.
+fn infer_scalar_style(value: &str, reason: Option<QuoteReason>) -> ScalarStyle {
+    match reason {
+        None => ScalarStyle::Any,
+        Some(QuoteReason::LineBreak) if value.contains('\n') => ScalarStyle::Literal,
+        // The emitter goes on to double quotes for a string that cannot be
+        // single-quoted.
+        Some(_) => ScalarStyle::SingleQuoted,
+    }
+}
+
+fn not_json(what: &str) -> Error {
+    error::new(ErrorImpl::SerializeNotJson(what.to_owned()))
+}
+
+// With `json(true)`, checks that the text of a scalar is JSON. Nulls,
+// booleans and numbers are written in plain style and strings double-quoted.
+// The escapes that the emitter writes in a string and JSON does not have,
+// like `\0`, `\x01` or `\L`, are rewritten by the LayoutWriter as `\u`
+// escapes. With `ascii(true)`, the characters above U+FFFF would be a
+// surrogate pair of them, which YAML does not accept.
+fn check_json_scalar(scalar: &mut Scalar, ascii: bool) -> Result<()> {
+    if !matches!(scalar.style, ScalarStyle::Plain) {
+        scalar.style = ScalarStyle::DoubleQuoted;
+        return match scalar.value.chars().find(|&ch| ascii && ch > '\u{FFFF}') {
+            Some(ch) => Err(not_json(&format!(
+                "string with character U+{:04X}",
+                ch as u32,
+            ))),
+            None => Ok(()),
+        };
+    }
+    if matches!(scalar.value, "null" | "true" | "false") || is_json_number(scalar.value) {
         Ok(())
+    } else {
+        Err(not_json(&format!("`{}`", scalar.value)))
     }
+}
 
-    fn value_end(&mut self) -> Result<()> {
-        self.depth -= 1;
-        if self.depth == 0 {
-            self.emitter.emit(Event::DocumentEnd)?;
+// With `json(true)`, checks that a key is a string, and one that YAML reads
+// as a key without a `?` before it, which is at most 1024 bytes as written.
+// Numbers and booleans are written as strings, as serde_json does.
+fn check_json_key(scalar: &mut Scalar, ascii: bool) -> Result<()> {
+    if matches!(scalar.style, ScalarStyle::Plain) {
+        if !matches!(scalar.value, "true" | "false") && !is_json_number(scalar.value) {
+            return Err(not_json(&format!("key `{}`", scalar.value)));
         }
-        Ok(())
+        scalar.style = ScalarStyle::DoubleQuoted;
+    }
+    let written: usize = scalar.value.chars().map(|ch| json_len(ch, ascii)).sum();
+    if written + 2 > 1024 {
+        return Err(not_json("key longer than 1024 bytes"));
     }
+    Ok(())
+}
 
-    fn take_tag(&mut self) -> Option<String> {
-        let state = mem::replace(&mut self.state, State::NothingInParticular);
-        if let State::FoundTag(mut tag) = state {
-            if !tag.starts_with('!') {
-                tag.insert(0, '!');
-            }
-            Some(tag)
-        } else {
-            self.state = state;
-            None
+// The length of a character in a double-quoted string with `json(true)`.
+fn json_len(ch: char, ascii: bool) -> usize {
+    match ch {
+        '"' | '\\' | '\u{8}' | '\u{C}' | '\n' | '\r' | '\t' => 2,
+        '\u{85}' | '\u{2028}' | '\u{2029}' | '\u{FEFF}' => 6,
+        _ if !is_printable(ch) || ascii && !ch.is_ascii() => 6,
+        _ => ch.len_utf8(),
+    }
+}
+
+// The number syntax of JSON: `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`.
+fn is_json_number(text: &str) -> bool {
+    fn digits(bytes: &[u8]) -> usize {
+        bytes.iter().take_while(|b| b.is_ascii_digit()).count()
+    }
+    let bytes = text.strip_prefix('-').unwrap_or(text).as_bytes();
+    let int = digits(bytes);
+    if int == 0 || int > 1 && bytes[0] == b'0' {
+        return false;
+    }
+    let mut rest = &bytes[int..];
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let len = digits(fraction);
+        if len == 0 {
+            return false;
+        }
+        rest = &fraction[len..];
+    }
+    if let Some(exponent) = rest.strip_prefix(b"e").or_else(|| rest.strip_prefix(b"E")) {
+        let exponent = exponent
+            .strip_prefix(b"+")
+            .or_else(|| exponent.strip_prefix(b"-"))
+            .unwrap_or(exponent);
+        let len = digits(exponent);
+        if len == 0 {
+            return false;
         }
+        rest = &exponent[len..];
     }
+    rest.is_empty()
+}
 
-    fn flush_mapping_start(&mut self) -> Result<()> {
-        if let State::CheckForTag = self.state {
-            self.state = State::NothingInParticular;
-            self.emit_mapping_start()?;
-        } else if let State::CheckForDuplicateTag = self.state {
-            self.state = State::NothingInParticular;
+// The tag of the core schema that an untagged scalar resolves to, which is
+// written out in canonical output.
+fn core_tag(scalar: &Scalar) -> &'static str {
+    struct CoreTag;
+
+    impl<'de> Visitor<'de> for CoreTag {
+        type Value = &'static str;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("any scalar")
         }
-        Ok(())
+
+        fn visit_bool<E>(self, _v: bool) -> Result<Self::Value, E> {
+            Ok(Tag::BOOL)
+        }
+
+        fn visit_i64<E>(self, _v: i64) -> Result<Self::Value, E> {
+            Ok(Tag::INT)
+        }
+
+        fn visit_i128<E>(self, _v: i128) -> Result<Self::Value, E> {
+            Ok(Tag::INT)
+        }
+
+        fn visit_u64<E>(self, _v: u64) -> Result<Self::Value, E> {
+            Ok(Tag::INT)
+        }
+
+        fn visit_u128<E>(self, _v: u128) -> Result<Self::Value, E> {
+            Ok(Tag::INT)
+        }
+
+        fn visit_f64<E>(self, _v: f64) -> Result<Self::Value, E> {
+            Ok(Tag::FLOAT)
+        }
+
+        fn visit_str<E>(self, _v: &str) -> Result<Self::Value, E> {
+            Ok(Tag::STR)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(Tag::NULL)
+        }
+    }
+
+    // Only nulls, booleans and numbers are written in plain style.
+    if !matches!(scalar.style, ScalarStyle::Plain) {
+        return Tag::STR;
     }
+    let result = crate::de::visit_untagged_scalar(
+        CoreTag,
+        scalar.value,
+        None,
+        libyaml::parser::ScalarStyle::Plain,
+        &CoercionFlags::CORE_12,
+    );
+    result.unwrap_or(Tag::STR)
 }
 
 impl<'a, W> ser::Serializer for &'a mut Serializer<W>
@@ -214,6 +3116,14 @@ where
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
+        #[cfg(feature = "value")]
+        if let Some(repr) = self.original_repr() {
+            return self.emit_scalar(Scalar {
+                tag: None,
+                value: &repr,
+                style: ScalarStyle::Plain,
+            });
+        }
         self.emit_scalar(Scalar {
             tag: None,
             value: itoa::Buffer::new().format(v),
@@ -254,6 +3164,14 @@ where
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
+        #[cfg(feature = "value")]
+        if let Some(repr) = self.original_repr() {
+            return self.emit_scalar(Scalar {
+                tag: None,
+                value: &repr,
+                style: ScalarStyle::Plain,
+            });
+        }
         self.emit_scalar(Scalar {
             tag: None,
             value: itoa::Buffer::new().format(v),
@@ -270,28 +3188,56 @@ where
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
+        let format_finite = self.options.emitter_compat.strategy().format_f32;
         let mut buffer = ryu::Buffer::new();
+        let text;
         self.emit_scalar(Scalar {
             tag: None,
             value: match v.classify() {
                 num::FpCategory::Infinite if v.is_sign_positive() => ".inf",
                 num::FpCategory::Infinite => "-.inf",
                 num::FpCategory::Nan => ".nan",
-                _ => buffer.format_finite(v),
+                #[cfg(feature = "value")]
+                num::FpCategory::Zero if self.options.deterministic || self.options.canonical => {
+                    text = self.format_float(0.0, format_finite, &mut buffer);
+                    &text
+                }
+                _ => {
+                    text = self.format_float(v, format_finite, &mut buffer);
+                    &text
+                }
             },
             style: ScalarStyle::Plain,
         })
     }
 
     fn serialize_f64(self, v: f64) -> Result<()> {
+        #[cfg(feature = "value")]
+        if let Some(repr) = self.original_repr() {
+            return self.emit_scalar(Scalar {
+                tag: None,
+                value: &repr,
+                style: ScalarStyle::Plain,
+            });
+        }
+        let format_finite = self.options.emitter_compat.strategy().format_f64;
         let mut buffer = ryu::Buffer::new();
+        let text;
         self.emit_scalar(Scalar {
             tag: None,
             value: match v.classify() {
                 num::FpCategory::Infinite if v.is_sign_positive() => ".inf",
                 num::FpCategory::Infinite => "-.inf",
                 num::FpCategory::Nan => ".nan",
-                _ => buffer.format_finite(v),
+                #[cfg(feature = "value")]
+                num::FpCategory::Zero if self.options.deterministic || self.options.canonical => {
+                    text = self.format_float(0.0, format_finite, &mut buffer);
+                    &text
+                }
+                _ => {
+                    text = self.format_float(v, format_finite, &mut buffer);
+                    &text
+                }
             },
             style: ScalarStyle::Plain,
         })
@@ -301,65 +3247,37 @@ where
         self.emit_scalar(Scalar {
             tag: None,
             value: value.encode_utf8(&mut [0u8; 4]),
-            style: ScalarStyle::SingleQuoted,
+            style: match self.options.quote_style {
+                _ if self.options.json => ScalarStyle::DoubleQuoted,
+                QuoteStyle::Double => ScalarStyle::DoubleQuoted,
+                QuoteStyle::WhenNeeded | QuoteStyle::Single => ScalarStyle::SingleQuoted,
+            },
         })
     }
 
     fn serialize_str(self, value: &str) -> Result<()> {
-        struct InferScalarStyle;
-
-        impl<'de> Visitor<'de> for InferScalarStyle {
-            type Value = ScalarStyle;
-
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("I wonder")
-            }
-
-            fn visit_bool<E>(self, _v: bool) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_i64<E>(self, _v: i64) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_i128<E>(self, _v: i128) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_u64<E>(self, _v: u64) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_u128<E>(self, _v: u128) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_f64<E>(self, _v: f64) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_str<E>(self, _v: &str) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::Any)
+        let style = match crate::styled::take_serialized_style() {
+            Some(_style) if self.options.json => ScalarStyle::DoubleQuoted,
+            Some(libyaml::parser::ScalarStyle::Plain)
+                if self.options.quote_keys && self.next_is_key() =>
+            {
+                self.string_style(value)
             }
-
-            fn visit_unit<E>(self) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
+            Some(style) => written_style(style, value),
+            None => {
+                let str_tag = matches!(&self.state, State::FoundTag(tag) if tag == Tag::STR);
+                match self.quote_reason(value) {
+                    // With an explicit `!!str`, the string does not need
+                    // quotes to keep it from resolving to something else.
+                    Some(QuoteReason::Null | QuoteReason::Bool | QuoteReason::Number)
+                        if str_tag && self.options.quote_style == QuoteStyle::WhenNeeded =>
+                    {
+                        ScalarStyle::Any
+                    }
+                    _ => self.string_style(value),
+                }
             }
-        }
-
-        let style = if value.contains('\n') {
-            ScalarStyle::Literal
-        } else {
-            let result = crate::de::visit_untagged_scalar(
-                InferScalarStyle,
-                value,
-                None,
-                libyaml::parser::ScalarStyle::Plain,
-            );
-            result.unwrap_or(ScalarStyle::Any)
         };
-
         self.emit_scalar(Scalar {
             tag: None,
             value,
@@ -367,14 +3285,30 @@ where
         })
     }
 
-    fn serialize_bytes(self, _value: &[u8]) -> Result<()> {
-        Err(error::new(ErrorImpl::BytesUnsupported))
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        if self.options.json {
+            return Err(not_json("bytes"));
+        }
+        if !self.options.tags {
+            return Err(error::new(ErrorImpl::SerializeTag("!!binary".to_owned())));
+        }
+        let encoded = base64::encode(value);
+        self.emit_scalar(Scalar {
+            tag: Some(Tag::BINARY.to_owned()),
+            value: &encoded,
+            style: if encoded.is_empty() {
+                ScalarStyle::SingleQuoted
+            } else {
+                ScalarStyle::Plain
+            },
+        })
     }
 
     fn serialize_unit(self) -> Result<()> {
+        let value = self.null_text();
         self.emit_scalar(Scalar {
             tag: None,
-            value: "null",
+            value,
             style: ScalarStyle::Plain,
         })
     }
@@ -389,13 +3323,58 @@ where
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<()> {
-        self.serialize_str(variant)
+        match self.options.unit_variants {
+            // JSON has no tags, so the variants of an enum are written the
+            // way serde_json writes them, as the string of a unit variant or
+            // as a mapping from the variant to its data.
+            _ if self.options.json => self.serialize_str(variant),
+            UnitVariants::PlainString => self.serialize_str(variant),
+            UnitVariants::Tag => {
+                self.flush_mapping_start()?;
+                if let State::FoundTag(_) = self.state {
+                    return Err(error::new(ErrorImpl::SerializeNestedEnum));
+                }
+                self.state = State::FoundTag(variant.to_owned());
+                self.emit_scalar(Scalar {
+                    tag: None,
+                    value: "",
+                    style: ScalarStyle::Plain,
+                })
+            }
+        }
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + ser::Serialize,
     {
+        if name == FLOW {
+            let depth = self.child_depth();
+            let previous = self.flow_depth;
+            self.flow_depth = Some(previous.map_or(depth, |previous| previous.min(depth)));
+            let result = value.serialize(&mut *self);
+            self.flow_depth = previous;
+            return result;
+        }
+        if name == comments::COMMENT && self.can_comment() {
+            return self.serialize_comment(value);
+        }
+        if name == comments::COMMENTED {
+            if let Some(comment) = comments::take_comment_above() {
+                self.add_comment_above(comment)?;
+            }
+        }
+        if name == shared::SHARED {
+            if let Some(identity) = shared::take_identity() {
+                if self.can_share() {
+                    if let Some(anchor) = self.anchors.get(identity) {
+                        let anchor = anchor.to_owned();
+                        return self.emit_alias(anchor);
+                    }
+                    self.anchor_next = Some(identity);
+                }
+            }
+        }
         value.serialize(self)
     }
 
@@ -409,6 +3388,12 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
+        if self.options.json {
+            self.emit_mapping_start()?;
+            self.serialize_str(variant)?;
+            value.serialize(&mut *self)?;
+            return self.emit_mapping_end();
+        }
         if let State::FoundTag(_) = self.state {
             return Err(error::new(ErrorImpl::SerializeNestedEnum));
         }
@@ -453,6 +3438,12 @@ where
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
+        if self.options.json {
+            self.emit_mapping_start()?;
+            self.serialize_str(variant)?;
+            self.emit_sequence_start()?;
+            return Ok(self);
+        }
         if let State::FoundTag(_) = self.state {
             return Err(error::new(ErrorImpl::SerializeNestedEnum));
         }
@@ -467,16 +3458,21 @@ where
                 self.emit_mapping_start()?;
                 State::CheckForDuplicateTag
             } else {
+                self.flush_mapping_start()?;
                 State::CheckForTag
             };
         } else {
             self.emit_mapping_start()?;
+            #[cfg(feature = "value")]
+            self.sort_entries(self.options.deterministic || self.options.sort_keys);
         }
         Ok(self)
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
         self.emit_mapping_start()?;
+        #[cfg(feature = "value")]
+        self.sort_entries(self.options.sort_keys);
         Ok(self)
     }
 
@@ -487,11 +3483,20 @@ where
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        if let State::FoundTag(_) = self.state {
-            return Err(error::new(ErrorImpl::SerializeNestedEnum));
+        if self.options.json {
+            self.emit_mapping_start()?;
+            self.serialize_str(variant)?;
+        } else {
+            if let State::FoundTag(_) = self.state {
+                return Err(error::new(ErrorImpl::SerializeNestedEnum));
+            }
+            self.state = State::FoundTag(variant.to_owned());
         }
-        self.state = State::FoundTag(variant.to_owned());
         self.emit_mapping_start()?;
+        #[cfg(feature = "value")]
+        if self.options.sort_keys {
+            self.sort_entries(true);
+        }
         Ok(self)
     }
 
@@ -500,7 +3505,7 @@ where
         T: ?Sized + Display,
     {
         let string = if let State::CheckForTag | State::CheckForDuplicateTag = self.state {
-            match tagged::check_for_tag(value) {
+            match check_for_tag(value) {
                 MaybeTag::NotTag(string) => string,
                 MaybeTag::Tag(string) => {
                     return if let State::CheckForDuplicateTag = self.state {
@@ -591,7 +3596,11 @@ where
     }
 
     fn end(self) -> Result<()> {
-        self.emit_sequence_end()
+        self.emit_sequence_end()?;
+        if self.options.json {
+            self.emit_mapping_end()?;
+        }
+        Ok(())
     }
 }
 
@@ -607,7 +3616,7 @@ where
         T: ?Sized + ser::Serialize,
     {
         self.flush_mapping_start()?;
-        key.serialize(&mut **self)
+        Serializer::serialize_key(*self, key)
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
@@ -622,7 +3631,7 @@ where
         K: ?Sized + ser::Serialize,
         V: ?Sized + ser::Serialize,
     {
-        key.serialize(&mut **self)?;
+        Serializer::serialize_key(*self, key)?;
         let tagged = matches!(self.state, State::FoundTag(_));
         value.serialize(&mut **self)?;
         if tagged {
@@ -635,6 +3644,11 @@ where
         if let State::CheckForTag = self.state {
             self.emit_mapping_start()?;
         }
+        if !matches!(self.state, State::AlreadyTagged) {
+            self.write_inserted_entries()?;
+        }
+        #[cfg(feature = "value")]
+        self.write_sorted_entries()?;
         if !matches!(self.state, State::AlreadyTagged) {
             self.emit_mapping_end()?;
         }
@@ -659,6 +3673,9 @@ where
     }
 
     fn end(self) -> Result<()> {
+        self.write_inserted_entries()?;
+        #[cfg(feature = "value")]
+        self.write_sorted_entries()?;
         self.emit_mapping_end()
     }
 }
@@ -679,14 +3696,32 @@ where
     }
 
     fn end(self) -> Result<()> {
-        self.emit_mapping_end()
+        self.write_inserted_entries()?;
+        #[cfg(feature = "value")]
+        self.write_sorted_entries()?;
+        self.emit_mapping_end()?;
+        if self.options.json {
+            self.emit_mapping_end()?;
+        }
+        Ok(())
     }
 }
 
 /// Serialize the given data structure as YAML into the IO stream.
 ///
+/// The output is written to the IO stream a few kilobytes at a time while
+/// the value is being serialized, so a large value is never held in memory
+/// as YAML all at once. Only [`indent_sequences`], [`align_values`] and
+/// [`verify_roundtrip`] hold back or keep a copy of each document until it
+/// is complete.
+///
 /// Serialization can fail if `T`'s implementation of `Serialize` decides to
-/// return an error.
+/// return an error, or if it writes the same scalar key twice in one mapping,
+/// as can happen with struct fields renamed to the same name.
+///
+/// [`indent_sequences`]: SerializerBuilder::indent_sequences
+/// [`align_values`]: SerializerBuilder::align_values
+/// [`verify_roundtrip`]: SerializerBuilder::verify_roundtrip
 pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
 where
     W: io::Write,
@@ -699,7 +3734,8 @@ where
 /// Serialize the given data structure as a String of YAML.
 ///
 /// Serialization can fail if `T`'s implementation of `Serialize` decides to
-/// return an error.
+/// return an error, or if it writes the same scalar key twice in one mapping,
+/// as can happen with struct fields renamed to the same name.
 pub fn to_string<T>(value: &T) -> Result<String>
 where
     T: ?Sized + ser::Serialize,
@@ -708,3 +3744,202 @@ where
     to_writer(&mut vec, value)?;
     String::from_utf8(vec).map_err(|error| error::new(ErrorImpl::FromUtf8(error)))
 }
+
+/// Serialize the given data structure as YAML into a [`fmt::Write`], like a
+/// `String` or a `Formatter`.
+///
+/// This lets a `Display` impl write YAML without collecting it into a
+/// `Vec<u8>` first.
+///
+/// ```
+/// use serde_derive::Serialize;
+/// use std::fmt::{self, Display};
+///
+/// #[derive(Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// impl Display for Point {
+///     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+///         serde_yaml::to_fmt_writer(formatter, self).map_err(|_| fmt::Error)
+///     }
+/// }
+///
+/// assert_eq!(Point { x: 1, y: 2 }.to_string(), "x: 1\ny: 2\n");
+/// ```
+pub fn to_fmt_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: fmt::Write,
+    T: ?Sized + ser::Serialize,
+{
+    let mut serializer = Serializer::new(FmtWriter::new(writer));
+    value.serialize(&mut serializer)
+}
+
+// Passes the output of the emitter on to a `fmt::Write`. The emitter writes
+// out its buffer whenever it is full, which can be in the middle of a
+// character, so the first bytes of that character are held back until the
+// rest of it comes.
+struct FmtWriter<W> {
+    writer: W,
+    incomplete: Vec<u8>,
+}
+
+impl<W> FmtWriter<W> {
+    fn new(writer: W) -> Self {
+        FmtWriter {
+            writer,
+            incomplete: Vec::new(),
+        }
+    }
+}
+
+impl<W> io::Write for FmtWriter<W>
+where
+    W: fmt::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let joined;
+        let bytes = if self.incomplete.is_empty() {
+            buf
+        } else {
+            self.incomplete.extend_from_slice(buf);
+            joined = mem::take(&mut self.incomplete);
+            &joined
+        };
+        let (valid, rest) = match str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..]),
+            Err(error) if error.error_len().is_none() => {
+                let (valid, rest) = bytes.split_at(error.valid_up_to());
+                (str::from_utf8(valid).unwrap(), rest)
+            }
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        };
+        self.writer
+            .write_str(valid)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        self.incomplete.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serialize each of the values as a document of one YAML stream into the IO
+/// stream.
+///
+/// Documents after the first start with `---`. This is the same as
+/// serializing the values one after another into one [`Serializer`].
+pub fn to_writer_multi<W, I>(writer: W, values: I) -> Result<()>
+where
+    W: io::Write,
+    I: IntoIterator,
+    I::Item: ser::Serialize,
+{
+    let mut serializer = Serializer::new(writer);
+    for value in values {
+        ser::Serialize::serialize(&value, &mut serializer)?;
+    }
+    Ok(())
+}
+
+/// Serialize each of the values as a document of one YAML stream, as a
+/// String.
+///
+/// Documents after the first start with `---`.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// let service = BTreeMap::from([("kind", "Service")]);
+/// let deployment = BTreeMap::from([("kind", "Deployment")]);
+///
+/// let yaml = serde_yaml::to_string_multi([&service, &deployment]).unwrap();
+/// assert_eq!(yaml, "kind: Service\n---\nkind: Deployment\n");
+/// ```
+pub fn to_string_multi<I>(values: I) -> Result<String>
+where
+    I: IntoIterator,
+    I::Item: ser::Serialize,
+{
+    let mut vec = Vec::with_capacity(128);
+    to_writer_multi(&mut vec, values)?;
+    String::from_utf8(vec).map_err(|error| error::new(ErrorImpl::FromUtf8(error)))
+}
+
+pub(crate) enum MaybeTag<T> {
+    Tag(String),
+    NotTag(T),
+}
+
+pub(crate) fn check_for_tag<T>(value: &T) -> MaybeTag<String>
+where
+    T: ?Sized + Display,
+{
+    enum CheckForTag {
+        Empty,
+        Bang,
+        Tag(String),
+        NotTag(String),
+    }
+
+    impl fmt::Write for CheckForTag {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            if s.is_empty() {
+                return Ok(());
+            }
+            match self {
+                CheckForTag::Empty => {
+                    if s == "!" {
+                        *self = CheckForTag::Bang;
+                    } else {
+                        *self = CheckForTag::NotTag(s.to_owned());
+                    }
+                }
+                CheckForTag::Bang => {
+                    *self = CheckForTag::Tag(s.to_owned());
+                }
+                CheckForTag::Tag(string) => {
+                    let mut string = mem::take(string);
+                    string.push_str(s);
+                    *self = CheckForTag::NotTag(string);
+                }
+                CheckForTag::NotTag(string) => {
+                    string.push_str(s);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let mut check_for_tag = CheckForTag::Empty;
+    fmt::write(&mut check_for_tag, format_args!("{}", value)).unwrap();
+    match check_for_tag {
+        CheckForTag::Empty => MaybeTag::NotTag(String::new()),
+        CheckForTag::Bang => MaybeTag::NotTag("!".to_owned()),
+        CheckForTag::Tag(string) => MaybeTag::Tag(string),
+        CheckForTag::NotTag(string) => MaybeTag::NotTag(string),
+    }
+}
+
+// Renders a value on one line in flow style, for use in error messages.
+#[cfg(feature = "value")]
+pub(crate) fn to_flow_string<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut vec = Vec::with_capacity(32);
+    let mut serializer = Serializer::new(&mut vec);
+    serializer.key_depth = Some(0);
+    serializer.flow_depth = Some(0);
+    value.serialize(&mut serializer)?;
+    drop(serializer);
+    let mut string =
+        String::from_utf8(vec).map_err(|error| error::new(ErrorImpl::FromUtf8(error)))?;
+    string.truncate(string.trim_end().len());
+    Ok(string)
+}
@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// Newtype struct name by which with::shared asks the serializer to write an
+// anchor for the value, or an alias if the same value was written before.
+pub(crate) const SHARED: &str = "$serde_yaml::shared";
+
+// The address of a shared value, and its type for values at the same address
+// such as a struct and its first field.
+pub(crate) type Identity = (usize, &'static str);
+
+thread_local! {
+    // The identity of the value of the Rc or Arc being serialized, for a
+    // YAML serializer to look up. Other serializers never look at it.
+    static IDENTITY: RefCell<Option<Identity>> = const { RefCell::new(None) };
+}
+
+// Makes the identity of a shared value available to the serializer during `f`.
+#[cfg(feature = "value")]
+pub(crate) fn with_identity<T>(identity: Identity, f: impl FnOnce() -> T) -> T {
+    IDENTITY.with(|current| *current.borrow_mut() = Some(identity));
+    let result = f();
+    IDENTITY.with(|current| *current.borrow_mut() = None);
+    result
+}
+
+pub(crate) fn take_identity() -> Option<Identity> {
+    IDENTITY.with(|current| current.borrow_mut().take())
+}
+
+// The anchors written so far in the current document. An alias cannot refer
+// to an anchor of another document.
+#[derive(Default)]
+pub(crate) struct Anchors {
+    names: HashMap<Identity, String>,
+}
+
+impl Anchors {
+    pub fn get(&self, identity: Identity) -> Option<&str> {
+        self.names.get(&identity).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, identity: Identity) -> String {
+        let name = format!("id{:03}", self.names.len() + 1);
+        self.names.insert(identity, name.clone());
+        name
+    }
+
+    pub fn clear(&mut self) {
+        self.names.clear();
+    }
+}
@@ -0,0 +1,113 @@
+use crate::error::Location;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+// Struct name and field names by which Spanned asks the deserializer for the
+// location of the node along with the node itself. This crate's Deserializer
+// gives each location as a tuple of its index, line and column.
+pub(crate) const NAME: &str = "$serde_yaml::spanned";
+pub(crate) const START: &str = "$serde_yaml::spanned::start";
+pub(crate) const END: &str = "$serde_yaml::spanned::end";
+pub(crate) const VALUE: &str = "$serde_yaml::spanned::value";
+pub(crate) const FIELDS: &[&str] = &[START, END, VALUE];
+
+/// A value together with where its node is in the input, for pointing at
+/// the line of a value that parsed fine but is not valid for the program.
+///
+/// The `start` is where the node begins, with any anchor and tag in front
+/// of it, and the `end` is just after its last character. For an alias, it
+/// is where the alias is written, not the node that it refers to.
+///
+/// Only this crate's [`Deserializer`](crate::Deserializer) knows where nodes
+/// are, so deserializing a `Spanned` from anything else, such as
+/// [`from_value`](crate::from_value), is an error. A `Spanned` is serialized
+/// as its value alone.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// use serde_yaml::Spanned;
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     name: String,
+///     port: Spanned<u16>,
+/// }
+///
+/// let yaml = "name: web\nport: 80\n";
+/// let config: Config = serde_yaml::from_str(yaml).unwrap();
+/// assert_eq!(config.port.value, 80);
+/// assert_eq!(config.port.start.line(), 2);
+/// assert_eq!(config.port.start.column(), 7);
+/// assert_eq!(&yaml[config.port.start.index()..config.port.end.index()], "80");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Spanned<T> {
+    /// Where the node starts.
+    pub start: Location,
+    /// Where the node ends.
+    pub end: Location,
+    /// The value deserialized from the node.
+    pub value: T,
+}
+
+impl<T> Serialize for Spanned<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Spanned<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SpannedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for SpannedVisitor<T>
+        where
+            T: Deserialize<'de>,
+        {
+            type Value = Spanned<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a value with its location")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Spanned<T>, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut next_location = |field| match map.next_key::<&str>() {
+                    Ok(Some(key)) if key == field => {
+                        let (index, line, column) = map.next_value()?;
+                        Ok(Location::new(index, line, column))
+                    }
+                    _ => Err(de::Error::custom(
+                        "Spanned can only be deserialized by serde_yaml::Deserializer",
+                    )),
+                };
+                let start = next_location(START)?;
+                let end = next_location(END)?;
+                match map.next_key::<&str>()? {
+                    Some(VALUE) => {}
+                    _ => return Err(de::Error::missing_field(VALUE)),
+                }
+                let value = map.next_value()?;
+                Ok(Spanned { start, end, value })
+            }
+        }
+
+        deserializer.deserialize_struct(NAME, FIELDS, SpannedVisitor(PhantomData))
+    }
+}
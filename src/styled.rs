@@ -0,0 +1,128 @@
+use crate::libyaml::parser::ScalarStyle;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::cell::Cell;
+use std::fmt;
+
+thread_local! {
+    // The style of the scalar that this crate's Deserializer is passing to
+    // the visitor of deserialize_str, for a StyledString to record.
+    static PARSED_STYLE: Cell<Option<ScalarStyle>> = const { Cell::new(None) };
+    // The style of the StyledString being serialized, for a YAML serializer
+    // to write it in. Other serializers never look at it.
+    static SERIALIZED_STYLE: Cell<Option<ScalarStyle>> = const { Cell::new(None) };
+}
+
+// Makes `style` available to a StyledString deserialized during `f`.
+pub(crate) fn with_parsed_style<T>(style: ScalarStyle, f: impl FnOnce() -> T) -> T {
+    PARSED_STYLE.with(|parsed| parsed.set(Some(style)));
+    let result = f();
+    PARSED_STYLE.with(|parsed| parsed.set(None));
+    result
+}
+
+// The style of the scalar a Value is being created from, for a deserializer
+// with `preserve_scalar_style`.
+#[cfg(feature = "value")]
+pub(crate) fn take_parsed_style() -> Option<ScalarStyle> {
+    PARSED_STYLE.with(Cell::take)
+}
+
+#[cfg(feature = "ser")]
+pub(crate) fn take_serialized_style() -> Option<ScalarStyle> {
+    SERIALIZED_STYLE.with(Cell::take)
+}
+
+/// A string together with the style of the scalar it was written as.
+///
+/// Serde's data model has no place for the style of a scalar, so a `String`
+/// field cannot tell `key: 'literal'` from `key: literal`. When deserialized
+/// by this crate's [`Deserializer`], a `StyledString` keeps the style of the
+/// scalar. Any other deserializer, including the one of
+/// [`Value`](crate::Value), has no style to give and the style is
+/// [`ScalarStyle::Plain`].
+///
+/// When serialized by this crate's [`Serializer`](crate::Serializer), the
+/// string is written in its style again, as far as the emitter allows for
+/// the content and position of the scalar. Other serializers see an ordinary
+/// string.
+///
+/// [`Deserializer`]: crate::Deserializer
+///
+/// ```
+/// use serde_yaml::de::StyledString;
+/// use serde_yaml::ScalarStyle;
+///
+/// let yaml = "[plain, 'single', \"double\"]\n";
+/// let strings: Vec<StyledString> = serde_yaml::from_str(yaml).unwrap();
+/// assert_eq!(strings[0].value, "plain");
+/// assert_eq!(strings[0].style, ScalarStyle::Plain);
+/// assert_eq!(strings[1].style, ScalarStyle::SingleQuoted);
+/// assert_eq!(strings[2].style, ScalarStyle::DoubleQuoted);
+///
+/// let expected = "- plain\n- 'single'\n- \"double\"\n";
+/// assert_eq!(serde_yaml::to_string(&strings).unwrap(), expected);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StyledString {
+    /// The content of the scalar.
+    pub value: String,
+    /// How the scalar was written.
+    pub style: ScalarStyle,
+}
+
+impl StyledString {
+    /// A string to be written in the given style.
+    pub fn new(value: impl Into<String>, style: ScalarStyle) -> Self {
+        StyledString {
+            value: value.into(),
+            style,
+        }
+    }
+}
+
+impl Serialize for StyledString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SERIALIZED_STYLE.with(|serialized| serialized.set(Some(self.style)));
+        let result = serializer.serialize_str(&self.value);
+        SERIALIZED_STYLE.with(|serialized| serialized.set(None));
+        result
+    }
+}
+
+impl<'de> Deserialize<'de> for StyledString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct StyledStringVisitor;
+
+        impl<'de> Visitor<'de> for StyledStringVisitor {
+            type Value = StyledString;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<StyledString, E>
+            where
+                E: de::Error,
+            {
+                self.visit_string(value.to_owned())
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<StyledString, E>
+            where
+                E: de::Error,
+            {
+                let style = PARSED_STYLE.with(Cell::get).unwrap_or(ScalarStyle::Plain);
+                Ok(StyledString { value, style })
+            }
+        }
+
+        deserializer.deserialize_str(StyledStringVisitor)
+    }
+}
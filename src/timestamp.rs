@@ -0,0 +1,160 @@
+use std::fmt::{self, Display};
+
+// The parts of a timestamp as written in YAML, for the date and time types of
+// other crates. The offset is in seconds east of UTC.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Fields {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub nanosecond: u32,
+    pub offset: i32,
+}
+
+impl Fields {
+    // The forms of the YAML 1.1 timestamp type, which include those of
+    // ISO 8601 and RFC 3339:
+    //
+    //     2001-12-14
+    //     2001-12-14T21:59:43.10-05:00
+    //     2001-12-14 21:59:43.10 -5
+    //     2001-12-15 2:59:43.10
+    //
+    // A date alone is at midnight, and a time without an offset is in UTC.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut scanner = Scanner(s.as_bytes());
+        let year = scanner.digits(4, 4)? as i32;
+        scanner.byte(b'-')?;
+        if scanner.0.len() == 5 {
+            let month = scanner.digits(2, 2)?;
+            scanner.byte(b'-')?;
+            let day = scanner.digits(2, 2)?;
+            return Some(Fields {
+                year,
+                month: month as u8,
+                day: day as u8,
+                hour: 0,
+                minute: 0,
+                second: 0,
+                nanosecond: 0,
+                offset: 0,
+            });
+        }
+        let month = scanner.digits(1, 2)? as u8;
+        scanner.byte(b'-')?;
+        let day = scanner.digits(1, 2)? as u8;
+        if scanner.byte(b'T').or_else(|| scanner.byte(b't')).is_none() && scanner.blanks() == 0 {
+            return None;
+        }
+        let hour = scanner.digits(1, 2)? as u8;
+        scanner.byte(b':')?;
+        let minute = scanner.digits(2, 2)? as u8;
+        scanner.byte(b':')?;
+        let second = scanner.digits(2, 2)? as u8;
+        let mut nanosecond = 0;
+        if scanner.byte(b'.').is_some() {
+            let fraction = scanner.take_while(|b| b.is_ascii_digit());
+            for i in 0..9 {
+                let digit = fraction.get(i).map_or(0, |b| b - b'0');
+                nanosecond = nanosecond * 10 + u32::from(digit);
+            }
+        }
+        scanner.blanks();
+        let offset = match scanner.0.first() {
+            None => 0,
+            Some(b'Z') => {
+                scanner.byte(b'Z')?;
+                0
+            }
+            Some(&sign @ (b'+' | b'-')) => {
+                scanner.byte(sign)?;
+                let hours = scanner.digits(1, 2)?;
+                let minutes = match scanner.byte(b':') {
+                    Some(()) => scanner.digits(2, 2)?,
+                    None => 0,
+                };
+                let offset = (hours * 60 + minutes) as i32 * 60;
+                if sign == b'-' {
+                    -offset
+                } else {
+                    offset
+                }
+            }
+            Some(_) => return None,
+        };
+        if !scanner.0.is_empty() {
+            return None;
+        }
+        Some(Fields {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            nanosecond,
+            offset,
+        })
+    }
+}
+
+// Written in the form of RFC 3339, with as many digits of the fraction as
+// it has, and `Z` for UTC.
+impl Display for Fields {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second,
+        )?;
+        if self.nanosecond != 0 {
+            let fraction = format!("{:09}", self.nanosecond);
+            write!(formatter, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        if self.offset == 0 {
+            return formatter.write_str("Z");
+        }
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let minutes = self.offset.unsigned_abs() / 60;
+        write!(formatter, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+    }
+}
+
+struct Scanner<'a>(&'a [u8]);
+
+impl<'a> Scanner<'a> {
+    fn byte(&mut self, byte: u8) -> Option<()> {
+        let rest = self.0.strip_prefix(&[byte])?;
+        self.0 = rest;
+        Some(())
+    }
+
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> &'a [u8] {
+        let len = self.0.iter().position(|&b| !f(b)).unwrap_or(self.0.len());
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        taken
+    }
+
+    fn blanks(&mut self) -> usize {
+        self.take_while(|b| b == b' ' || b == b'\t').len()
+    }
+
+    fn digits(&mut self, min: usize, max: usize) -> Option<u32> {
+        let len = self
+            .0
+            .iter()
+            .take(max)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if len < min {
+            return None;
+        }
+        let (digits, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(digits.iter().fold(0, |n, b| n * 10 + u32::from(b - b'0')))
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::{self, Error, ErrorImpl, Result};
+use crate::libyaml::parser::{Anchor, EventKind, Parser};
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::BTreeMap;
+
+// Deepest nesting of sequences and mappings, the same as the Deserializer
+// allows.
+const MAX_DEPTH: usize = 128;
+
+// Stands for an anchored node that is not complete yet, which an alias inside
+// of it would have to expand without end.
+const OPEN: u64 = u64::MAX;
+
+/// A summary of well-formed YAML input, returned by [`validate`].
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct DocumentStats {
+    documents: usize,
+    max_depth: usize,
+    nodes: usize,
+    scalar_bytes: usize,
+}
+
+impl DocumentStats {
+    /// The number of documents in the input. An empty input has none.
+    pub fn documents(&self) -> usize {
+        self.documents
+    }
+
+    /// The deepest nesting of sequences and mappings in any document. A
+    /// document that is only a scalar has a depth of 0, and `[[1]]` has a
+    /// depth of 2.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// The number of scalars, sequences, mappings and aliases in all of the
+    /// documents, counting mapping keys. Aliases are counted once each, not
+    /// as the nodes they refer to.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// The total length in bytes of the content of all scalars, after
+    /// escapes and line folding are applied.
+    pub fn scalar_bytes(&self) -> usize {
+        self.scalar_bytes
+    }
+}
+
+/// Checks whether a string is well-formed YAML, without building any values.
+///
+/// This is the same as `validate(s).is_ok()`.
+///
+/// ```
+/// assert!(serde_yaml::is_valid("name: web\nports: [80, 443]\n"));
+/// assert!(!serde_yaml::is_valid("{\"name\": \"web\"} trailing"));
+/// ```
+pub fn is_valid(s: &str) -> bool {
+    validate(s).is_ok()
+}
+
+/// Parses every document in a string of YAML text without building any
+/// values, and reports what was in it.
+///
+/// Nothing is kept of the nodes but their count, and nothing is copied out
+/// of the parser other than anchors for the aliases that refer to them. This
+/// makes it much cheaper than deserializing a [`Value`](crate::Value) to
+/// check the input, in both time and memory. Input that fails here also
+/// fails to deserialize into a `Value`:
+///
+/// - syntax errors, including content after the end of a document,
+/// - aliases to anchors that are not defined before them,
+/// - nesting of sequences and mappings deeper than 128 levels,
+/// - aliases that would expand to too many nodes, as in a "billion laughs"
+///   document.
+///
+/// Input that passes can still fail to deserialize into a particular type.
+///
+/// ```
+/// let yaml = "---\nname: web\nports: [80, 443]\n---\nname: db\n";
+/// let stats = serde_yaml::validate(yaml).unwrap();
+/// assert_eq!(stats.documents(), 2);
+/// assert_eq!(stats.max_depth(), 2);
+/// assert_eq!(stats.nodes(), 10);
+/// assert_eq!(stats.scalar_bytes(), 23);
+/// ```
+pub fn validate(s: &str) -> Result<DocumentStats> {
+    let mut parser = Parser::new(Cow::Borrowed(s.as_bytes()));
+    validate_events(&mut parser).map_err(|err| error::with_excerpt(err, s.as_bytes()))
+}
+
+fn validate_events(parser: &mut Parser) -> Result<DocumentStats> {
+    let mut stats = DocumentStats::default();
+
+    // For each anchor of the current document, how many aliases have to be
+    // followed to expand the node it is on.
+    let mut anchors: BTreeMap<Anchor, u64> = BTreeMap::new();
+    // The sequences and mappings that the next event is inside of, with
+    // their anchor and the number of aliases followed before they started.
+    let mut open: Vec<(Option<Anchor>, u64)> = Vec::with_capacity(MAX_DEPTH);
+    // Events of nodes in the current document, and how many aliases are
+    // followed to expand all of it. The Deserializer gives up after following
+    // 100 aliases per event.
+    let mut events = 0u64;
+    let mut jumps = 0u64;
+
+    loop {
+        let (event, mark) = parser.next_kind().map_err(Error::from)?;
+        match event {
+            EventKind::StreamStart => {}
+            EventKind::StreamEnd => return Ok(stats),
+            EventKind::DocumentStart => {
+                stats.documents += 1;
+                anchors.clear();
+                events = 0;
+                jumps = 0;
+            }
+            EventKind::DocumentEnd => {
+                if jumps > events.saturating_mul(100) {
+                    return Err(error::new(ErrorImpl::RepetitionLimitExceeded));
+                }
+            }
+            EventKind::Alias(anchor) => {
+                let expand = match anchors.get(&anchor) {
+                    Some(&OPEN) => return Err(error::new(ErrorImpl::RecursionLimitExceeded(mark))),
+                    Some(expand) => *expand,
+                    None => return Err(error::new(ErrorImpl::UnknownAnchor(mark))),
+                };
+                stats.nodes += 1;
+                events += 1;
+                jumps = jumps.saturating_add(1).saturating_add(expand);
+            }
+            EventKind::Scalar(anchor, len) => {
+                stats.nodes += 1;
+                stats.scalar_bytes += len;
+                events += 1;
+                if let Some(anchor) = anchor {
+                    anchors.insert(anchor, 0);
+                }
+            }
+            EventKind::SequenceStart(anchor) | EventKind::MappingStart(anchor) => {
+                if open.len() == MAX_DEPTH {
+                    return Err(error::new(ErrorImpl::RecursionLimitExceeded(mark)));
+                }
+                stats.nodes += 1;
+                events += 1;
+                if let Some(anchor) = &anchor {
+                    anchors.insert(anchor.clone(), OPEN);
+                }
+                open.push((anchor, jumps));
+                stats.max_depth = cmp::max(stats.max_depth, open.len());
+            }
+            EventKind::SequenceEnd | EventKind::MappingEnd => {
+                events += 1;
+                if let Some((Some(anchor), start)) = open.pop() {
+                    anchors.insert(anchor, cmp::min(jumps - start, OPEN - 1));
+                }
+            }
+        }
+    }
+}
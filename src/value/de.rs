@@ -1,6 +1,8 @@
+use crate::de::{ExpectedMap, ExpectedSeq, StyledString};
+use crate::styled;
 use crate::value::tagged::{self, TagStringVisitor};
 use crate::value::TaggedValue;
-use crate::{number, Error, Mapping, Sequence, Value};
+use crate::{number, Error, Mapping, Number, Sequence, Value};
 use serde::de::value::{BorrowedStrDeserializer, StrDeserializer};
 use serde::de::{
     self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, Error as _, Expected, MapAccess,
@@ -8,6 +10,7 @@ use serde::de::{
 };
 use serde::forward_to_deserialize_any;
 use std::fmt;
+use std::iter;
 use std::slice;
 use std::vec;
 
@@ -29,49 +32,63 @@ impl<'de> Deserialize<'de> for Value {
             where
                 E: de::Error,
             {
-                Ok(Value::Bool(b))
+                Ok(Value::Bool(b).with_parsed_tag())
             }
 
             fn visit_i64<E>(self, i: i64) -> Result<Value, E>
             where
                 E: de::Error,
             {
-                Ok(Value::Number(i.into()))
+                Ok(Value::Number(Number::from(i).with_repr()).with_parsed_tag())
             }
 
             fn visit_u64<E>(self, u: u64) -> Result<Value, E>
             where
                 E: de::Error,
             {
-                Ok(Value::Number(u.into()))
+                Ok(Value::Number(Number::from(u).with_repr()).with_parsed_tag())
+            }
+
+            fn visit_i128<E>(self, i: i128) -> Result<Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Value::Number(Number::from(i).with_repr()).with_parsed_tag())
+            }
+
+            fn visit_u128<E>(self, u: u128) -> Result<Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Value::Number(Number::from(u).with_repr()).with_parsed_tag())
             }
 
             fn visit_f64<E>(self, f: f64) -> Result<Value, E>
             where
                 E: de::Error,
             {
-                Ok(Value::Number(f.into()))
+                Ok(Value::Number(Number::from(f).with_repr()).with_parsed_tag())
             }
 
             fn visit_str<E>(self, s: &str) -> Result<Value, E>
             where
                 E: de::Error,
             {
-                Ok(Value::String(s.to_owned()))
+                Ok(Value::parsed_string(s.to_owned()).with_parsed_tag())
             }
 
             fn visit_string<E>(self, s: String) -> Result<Value, E>
             where
                 E: de::Error,
             {
-                Ok(Value::String(s))
+                Ok(Value::parsed_string(s).with_parsed_tag())
             }
 
             fn visit_unit<E>(self) -> Result<Value, E>
             where
                 E: de::Error,
             {
-                Ok(Value::Null)
+                Ok(Value::Null.with_parsed_tag())
             }
 
             fn visit_none<E>(self) -> Result<Value, E>
@@ -121,6 +138,15 @@ impl<'de> Deserialize<'de> for Value {
 }
 
 impl Value {
+    // A string from a deserializer with `preserve_scalar_style` keeps the
+    // style of its scalar.
+    fn parsed_string(string: String) -> Value {
+        match styled::take_parsed_style() {
+            Some(style) => Value::Styled(StyledString::new(string, style)),
+            None => Value::String(string),
+        }
+    }
+
     fn deserialize_number<'de, V>(&self, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
@@ -143,7 +169,7 @@ where
     if remaining == 0 {
         Ok(seq)
     } else {
-        Err(Error::invalid_length(len, &"fewer elements in sequence"))
+        Err(Error::invalid_length(len, &ExpectedSeq(len - remaining)))
     }
 }
 
@@ -158,37 +184,53 @@ where
     if remaining == 0 {
         Ok(seq)
     } else {
-        Err(Error::invalid_length(len, &"fewer elements in sequence"))
+        Err(Error::invalid_length(len, &ExpectedSeq(len - remaining)))
     }
 }
 
-fn visit_mapping<'de, V>(mapping: Mapping, visitor: V) -> Result<V::Value, Error>
+fn visit_mapping<'de, V>(
+    mapping: Mapping,
+    visitor: V,
+    resolve_keys: bool,
+) -> Result<V::Value, Error>
 where
     V: Visitor<'de>,
 {
     let len = mapping.len();
-    let mut deserializer = MapDeserializer::new(mapping);
+    let mut deserializer = MapDeserializer {
+        iter: mapping.into_iter(),
+        entry: None,
+        resolve_keys,
+    };
     let map = visitor.visit_map(&mut deserializer)?;
     let remaining = deserializer.iter.len();
     if remaining == 0 {
         Ok(map)
     } else {
-        Err(Error::invalid_length(len, &"fewer elements in map"))
+        Err(Error::invalid_length(len, &ExpectedMap(len - remaining)))
     }
 }
 
-fn visit_mapping_ref<'de, V>(mapping: &'de Mapping, visitor: V) -> Result<V::Value, Error>
+fn visit_mapping_ref<'de, V>(
+    mapping: &'de Mapping,
+    visitor: V,
+    resolve_keys: bool,
+) -> Result<V::Value, Error>
 where
     V: Visitor<'de>,
 {
     let len = mapping.len();
-    let mut deserializer = MapRefDeserializer::new(mapping);
+    let mut deserializer = MapRefDeserializer {
+        iter: Some(mapping.iter()),
+        entry: None,
+        resolve_keys,
+    };
     let map = visitor.visit_map(&mut deserializer)?;
     let remaining = deserializer.iter.unwrap().len();
     if remaining == 0 {
         Ok(map)
     } else {
-        Err(Error::invalid_length(len, &"fewer elements in map"))
+        Err(Error::invalid_length(len, &ExpectedMap(len - remaining)))
     }
 }
 
@@ -204,8 +246,9 @@ impl<'de> Deserializer<'de> for Value {
             Value::Bool(v) => visitor.visit_bool(v),
             Value::Number(n) => n.deserialize_any(visitor),
             Value::String(v) => visitor.visit_string(v),
+            Value::Styled(v) => visitor.visit_string(v.value),
             Value::Sequence(v) => visit_sequence(v, visitor),
-            Value::Mapping(v) => visit_mapping(v, visitor),
+            Value::Mapping(v) => visit_mapping(v, visitor, true),
             Value::Tagged(tagged) => visitor.visit_enum(*tagged),
         }
     }
@@ -324,6 +367,7 @@ impl<'de> Deserializer<'de> for Value {
     {
         match self.untag() {
             Value::String(v) => visitor.visit_string(v),
+            Value::Styled(v) => visitor.visit_string(v.value),
             other => Err(other.invalid_type(&visitor)),
         }
     }
@@ -341,6 +385,7 @@ impl<'de> Deserializer<'de> for Value {
     {
         match self.untag() {
             Value::String(v) => visitor.visit_string(v),
+            Value::Styled(v) => visitor.visit_string(v.value),
             Value::Sequence(v) => visit_sequence(v, visitor),
             other => Err(other.invalid_type(&visitor)),
         }
@@ -419,8 +464,8 @@ impl<'de> Deserializer<'de> for Value {
         V: Visitor<'de>,
     {
         match self.untag() {
-            Value::Mapping(v) => visit_mapping(v, visitor),
-            Value::Null => visit_mapping(Mapping::new(), visitor),
+            Value::Mapping(v) => visit_mapping(v, visitor, true),
+            Value::Null => visit_mapping(Mapping::new(), visitor, true),
             other => Err(other.invalid_type(&visitor)),
         }
     }
@@ -434,7 +479,11 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        match self.untag() {
+            Value::Mapping(v) => visit_mapping(v, visitor, false),
+            Value::Null => visit_mapping(Mapping::new(), visitor, false),
+            other => Err(other.invalid_type(&visitor)),
+        }
     }
 
     fn deserialize_enum<V>(
@@ -462,10 +511,17 @@ impl<'de> Deserializer<'de> for Value {
                 },
                 value: None,
             },
+            Value::Styled(variant) => EnumDeserializer {
+                tag: {
+                    tag = variant.value;
+                    &tag
+                },
+                value: None,
+            },
             other => {
                 return Err(Error::invalid_type(
                     other.unexpected(),
-                    &"a Value::Tagged enum",
+                    &"a YAML tag starting with '!'",
                 ));
             }
         })
@@ -475,7 +531,12 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_string(visitor)
+        match self.untag() {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Number(n) => n.deserialize_any(visitor),
+            other => other.deserialize_string(visitor),
+        }
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
@@ -566,13 +627,13 @@ impl<'de> VariantAccess<'de> for VariantDeserializer {
 }
 
 pub(crate) struct SeqDeserializer {
-    iter: vec::IntoIter<Value>,
+    iter: iter::Enumerate<vec::IntoIter<Value>>,
 }
 
 impl SeqDeserializer {
     pub(crate) fn new(vec: Vec<Value>) -> Self {
         SeqDeserializer {
-            iter: vec.into_iter(),
+            iter: vec.into_iter().enumerate(),
         }
     }
 }
@@ -594,7 +655,7 @@ impl<'de> Deserializer<'de> for SeqDeserializer {
             if remaining == 0 {
                 Ok(ret)
             } else {
-                Err(Error::invalid_length(len, &"fewer elements in sequence"))
+                Err(Error::invalid_length(len, &ExpectedSeq(len - remaining)))
             }
         }
     }
@@ -622,7 +683,10 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
         T: DeserializeSeed<'de>,
     {
         match self.iter.next() {
-            Some(value) => seed.deserialize(value).map(Some),
+            Some((index, value)) => seed
+                .deserialize(value)
+                .map(Some)
+                .map_err(|error| super::prepend_index(error, index)),
             None => Ok(None),
         }
     }
@@ -637,14 +701,16 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
 
 pub(crate) struct MapDeserializer {
     iter: <Mapping as IntoIterator>::IntoIter,
-    value: Option<Value>,
+    entry: Option<(Value, Value)>,
+    resolve_keys: bool,
 }
 
 impl MapDeserializer {
-    pub(crate) fn new(map: Mapping) -> Self {
+    pub(crate) fn new_struct(map: Mapping) -> Self {
         MapDeserializer {
             iter: map.into_iter(),
-            value: None,
+            entry: None,
+            resolve_keys: false,
         }
     }
 }
@@ -658,8 +724,25 @@ impl<'de> MapAccess<'de> for MapDeserializer {
     {
         match self.iter.next() {
             Some((key, value)) => {
-                self.value = Some(value);
-                seed.deserialize(key).map(Some)
+                // The key is kept for the path of errors in the value. A field
+                // name is read from it in place, and of a key that is given
+                // away only what the path shows of it is kept.
+                let (result, key) = match key {
+                    Value::String(name) if !self.resolve_keys => {
+                        let field = FieldNameDeserializer(StrDeserializer::new(&name));
+                        (seed.deserialize(field), Value::String(name))
+                    }
+                    key => {
+                        let path_key = path_key(&key);
+                        if self.resolve_keys {
+                            (seed.deserialize(key), path_key)
+                        } else {
+                            (seed.deserialize(FieldNameDeserializer(key)), path_key)
+                        }
+                    }
+                };
+                self.entry = Some((key, value));
+                result.map(Some)
             }
             None => Ok(None),
         }
@@ -669,8 +752,10 @@ impl<'de> MapAccess<'de> for MapDeserializer {
     where
         T: DeserializeSeed<'de>,
     {
-        match self.value.take() {
-            Some(value) => seed.deserialize(value),
+        match self.entry.take() {
+            Some((key, value)) => seed
+                .deserialize(value)
+                .map_err(|error| super::prepend_key(error, &key)),
             None => panic!("visit_value called before visit_key"),
         }
     }
@@ -683,6 +768,15 @@ impl<'de> MapAccess<'de> for MapDeserializer {
     }
 }
 
+// The part of a key that the path of an error shows, which leaves out a
+// sequence or mapping in the key rather than copying it.
+fn path_key(key: &Value) -> Value {
+    match key.untag_ref() {
+        key @ (Value::String(_) | Value::Bool(_) | Value::Number(_)) => key.clone(),
+        _ => Value::Null,
+    }
+}
+
 impl<'de> Deserializer<'de> for MapDeserializer {
     type Error = Error;
 
@@ -721,8 +815,9 @@ impl<'de> Deserializer<'de> for &'de Value {
             Value::Bool(v) => visitor.visit_bool(*v),
             Value::Number(n) => n.deserialize_any(visitor),
             Value::String(v) => visitor.visit_borrowed_str(v),
+            Value::Styled(v) => visitor.visit_borrowed_str(&v.value),
             Value::Sequence(v) => visit_sequence_ref(v, visitor),
-            Value::Mapping(v) => visit_mapping_ref(v, visitor),
+            Value::Mapping(v) => visit_mapping_ref(v, visitor, true),
             Value::Tagged(tagged) => visitor.visit_enum(&**tagged),
         }
     }
@@ -834,6 +929,7 @@ impl<'de> Deserializer<'de> for &'de Value {
     {
         match self.untag_ref() {
             Value::String(v) => visitor.visit_borrowed_str(v),
+            Value::Styled(v) => visitor.visit_borrowed_str(&v.value),
             other => Err(other.invalid_type(&visitor)),
         }
     }
@@ -851,6 +947,7 @@ impl<'de> Deserializer<'de> for &'de Value {
     {
         match self.untag_ref() {
             Value::String(v) => visitor.visit_borrowed_str(v),
+            Value::Styled(v) => visitor.visit_borrowed_str(&v.value),
             Value::Sequence(v) => visit_sequence_ref(v, visitor),
             other => Err(other.invalid_type(&visitor)),
         }
@@ -937,10 +1034,11 @@ impl<'de> Deserializer<'de> for &'de Value {
         V: Visitor<'de>,
     {
         match self.untag_ref() {
-            Value::Mapping(v) => visit_mapping_ref(v, visitor),
+            Value::Mapping(v) => visit_mapping_ref(v, visitor, true),
             Value::Null => visitor.visit_map(&mut MapRefDeserializer {
                 iter: None,
-                value: None,
+                entry: None,
+                resolve_keys: true,
             }),
             other => Err(other.invalid_type(&visitor)),
         }
@@ -955,7 +1053,15 @@ impl<'de> Deserializer<'de> for &'de Value {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        match self.untag_ref() {
+            Value::Mapping(v) => visit_mapping_ref(v, visitor, false),
+            Value::Null => visitor.visit_map(&mut MapRefDeserializer {
+                iter: None,
+                entry: None,
+                resolve_keys: false,
+            }),
+            other => Err(other.invalid_type(&visitor)),
+        }
     }
 
     fn deserialize_enum<V>(
@@ -976,10 +1082,14 @@ impl<'de> Deserializer<'de> for &'de Value {
                 tag: variant,
                 value: None,
             },
+            Value::Styled(variant) => EnumRefDeserializer {
+                tag: &variant.value,
+                value: None,
+            },
             other => {
                 return Err(Error::invalid_type(
                     other.unexpected(),
-                    &"a Value::Tagged enum",
+                    &"a YAML tag starting with '!'",
                 ));
             }
         })
@@ -989,7 +1099,12 @@ impl<'de> Deserializer<'de> for &'de Value {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_string(visitor)
+        match self.untag_ref() {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(*v),
+            Value::Number(n) => n.deserialize_any(visitor),
+            other => other.deserialize_string(visitor),
+        }
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
@@ -1079,12 +1194,14 @@ impl<'de> VariantAccess<'de> for VariantRefDeserializer<'de> {
 }
 
 pub(crate) struct SeqRefDeserializer<'de> {
-    iter: slice::Iter<'de, Value>,
+    iter: iter::Enumerate<slice::Iter<'de, Value>>,
 }
 
 impl<'de> SeqRefDeserializer<'de> {
     pub(crate) fn new(slice: &'de [Value]) -> Self {
-        SeqRefDeserializer { iter: slice.iter() }
+        SeqRefDeserializer {
+            iter: slice.iter().enumerate(),
+        }
     }
 }
 
@@ -1105,7 +1222,7 @@ impl<'de> Deserializer<'de> for SeqRefDeserializer<'de> {
             if remaining == 0 {
                 Ok(ret)
             } else {
-                Err(Error::invalid_length(len, &"fewer elements in sequence"))
+                Err(Error::invalid_length(len, &ExpectedSeq(len - remaining)))
             }
         }
     }
@@ -1132,7 +1249,10 @@ impl<'de> SeqAccess<'de> for SeqRefDeserializer<'de> {
         T: DeserializeSeed<'de>,
     {
         match self.iter.next() {
-            Some(value) => seed.deserialize(value).map(Some),
+            Some((index, value)) => seed
+                .deserialize(value)
+                .map(Some)
+                .map_err(|error| super::prepend_index(error, index)),
             None => Ok(None),
         }
     }
@@ -1147,14 +1267,16 @@ impl<'de> SeqAccess<'de> for SeqRefDeserializer<'de> {
 
 pub(crate) struct MapRefDeserializer<'de> {
     iter: Option<<&'de Mapping as IntoIterator>::IntoIter>,
-    value: Option<&'de Value>,
+    entry: Option<(&'de Value, &'de Value)>,
+    resolve_keys: bool,
 }
 
 impl<'de> MapRefDeserializer<'de> {
-    pub(crate) fn new(map: &'de Mapping) -> Self {
+    pub(crate) fn new_struct(map: &'de Mapping) -> Self {
         MapRefDeserializer {
             iter: Some(map.iter()),
-            value: None,
+            entry: None,
+            resolve_keys: false,
         }
     }
 }
@@ -1168,8 +1290,12 @@ impl<'de> MapAccess<'de> for MapRefDeserializer<'de> {
     {
         match self.iter.as_mut().and_then(Iterator::next) {
             Some((key, value)) => {
-                self.value = Some(value);
-                seed.deserialize(key).map(Some)
+                self.entry = Some((key, value));
+                if self.resolve_keys {
+                    seed.deserialize(key).map(Some)
+                } else {
+                    seed.deserialize(FieldNameDeserializer(key)).map(Some)
+                }
             }
             None => Ok(None),
         }
@@ -1179,8 +1305,10 @@ impl<'de> MapAccess<'de> for MapRefDeserializer<'de> {
     where
         T: DeserializeSeed<'de>,
     {
-        match self.value.take() {
-            Some(value) => seed.deserialize(value),
+        match self.entry.take() {
+            Some((key, value)) => seed
+                .deserialize(value)
+                .map_err(|error| super::prepend_key(error, key)),
             None => panic!("visit_value called before visit_key"),
         }
     }
@@ -1234,9 +1362,42 @@ impl Value {
             Value::Bool(b) => Unexpected::Bool(*b),
             Value::Number(n) => number::unexpected(n),
             Value::String(s) => Unexpected::Str(s),
+            Value::Styled(s) => Unexpected::Str(&s.value),
             Value::Sequence(_) => Unexpected::Seq,
             Value::Mapping(_) => Unexpected::Map,
             Value::Tagged(_) => Unexpected::Enum,
         }
     }
 }
+
+// Key of a struct mapping. Struct field names are matched as strings only;
+// letting an integer key through to the derived field visitor would have it
+// interpreted as a field index.
+struct FieldNameDeserializer<D>(D);
+
+impl<'de, D> Deserializer<'de> for FieldNameDeserializer<D>
+where
+    D: Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_any(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_string(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum ignored_any
+    }
+}
@@ -16,8 +16,8 @@ macro_rules! from_number {
 }
 
 from_number! {
-    i8 i16 i32 i64 isize
-    u8 u16 u32 u64 usize
+    i8 i16 i32 i64 i128 isize
+    u8 u16 u32 u64 u128 usize
     f32 f64
 }
 
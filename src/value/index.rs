@@ -169,7 +169,7 @@ impl<'a> fmt::Display for Type<'a> {
             Value::Null => formatter.write_str("null"),
             Value::Bool(_) => formatter.write_str("boolean"),
             Value::Number(_) => formatter.write_str("number"),
-            Value::String(_) => formatter.write_str("string"),
+            Value::String(_) | Value::Styled(_) => formatter.write_str("string"),
             Value::Sequence(_) => formatter.write_str("sequence"),
             Value::Mapping(_) => formatter.write_str("mapping"),
             Value::Tagged(_) => unreachable!(),
@@ -4,25 +4,35 @@ mod de;
 mod debug;
 mod from;
 mod index;
+mod normalize;
 mod partial_eq;
+pub(crate) mod roundtrip;
 mod ser;
 pub(crate) mod tagged;
+mod walk;
 
 use crate::error::{self, Error, ErrorImpl};
+use crate::mapping;
+use crate::styled::StyledString;
 use serde::de::{Deserialize, DeserializeOwned, IntoDeserializer};
 use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::mem;
 
 pub use self::index::Index;
+pub use self::normalize::NormalizeOptions;
 pub use self::ser::Serializer;
 pub use self::tagged::{Tag, TaggedValue};
+pub use self::walk::{Path, Segment, Walk};
 #[doc(inline)]
 pub use crate::mapping::Mapping;
-pub use crate::number::Number;
+pub use crate::number::{Number, Radix};
 
 /// Represents any valid YAML value.
-#[derive(Clone, PartialEq, PartialOrd)]
+#[derive(Clone)]
 pub enum Value {
     /// Represents a YAML null value.
     Null,
@@ -32,6 +42,13 @@ pub enum Value {
     Number(Number),
     /// Represents a YAML string.
     String(String),
+    /// Represents a YAML string together with the style of its scalar, as
+    /// deserialized with [`preserve_scalar_style(true)`]. It is equal to the
+    /// `String` with the same content, and the serializer writes it in its
+    /// style again.
+    ///
+    /// [`preserve_scalar_style(true)`]: crate::DeserializerBuilder::preserve_scalar_style
+    Styled(StyledString),
     /// Represents a YAML sequence in which the elements are
     /// `serde_yaml::Value`.
     Sequence(Sequence),
@@ -189,6 +206,445 @@ impl Value {
         index.index_into_mut(self)
     }
 
+    /// Looks up a value by a JSON Pointer, like `/servers/0/port`.
+    ///
+    /// Each `/`-separated token of the pointer selects an element of a
+    /// sequence by its index, or an entry of a map by its key. A token
+    /// matches a string key, or an integer key if the map has no such string
+    /// key. In keys, `~1` stands for `/` and `~0` for `~`. Tags are looked
+    /// through. The empty pointer refers to the whole value.
+    ///
+    /// Returns `None` if the pointer does not start with `/` or a node along
+    /// the way is missing.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let data: Value = serde_yaml::from_str("servers: [{port: 80}, {port: 443}]").unwrap();
+    /// assert_eq!(data.pointer("/servers/1/port").unwrap(), 443);
+    /// assert_eq!(data.pointer("/servers/2"), None);
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value.untag_ref() {
+                Value::Sequence(sequence) => sequence.get(parse_pointer_index(&token)?)?,
+                Value::Mapping(mapping) => match mapping.get(&*token) {
+                    Some(value) => value,
+                    None => mapping.get(pointer_integer_key(&token)?)?,
+                },
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Looks up a value by a JSON Pointer like [`pointer`][Value::pointer],
+    /// returning a mutable reference.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value.untag_mut() {
+                Value::Sequence(sequence) => sequence.get_mut(parse_pointer_index(&token)?)?,
+                Value::Mapping(mapping) => {
+                    if mapping.contains_key(&*token) {
+                        mapping.get_mut(&*token)?
+                    } else {
+                        mapping.get_mut(pointer_integer_key(&token)?)?
+                    }
+                }
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Deserializes the value at a JSON Pointer as a `T`, borrowing from
+    /// `self`.
+    ///
+    /// Returns `Ok(None)` if there is no value at the pointer. Errors from
+    /// deserializing the value have the pointer at the start of their path.
+    /// See [`pointer`][Value::pointer] for the syntax.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let config: Value = serde_yaml::from_str("server: {host: example.com}").unwrap();
+    /// let host: Option<&str> = config.get_as("/server/host").unwrap();
+    /// assert_eq!(host, Some("example.com"));
+    /// ```
+    pub fn get_as<'de, T>(&'de self, pointer: &str) -> Result<Option<T>, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        match self.pointer(pointer) {
+            Some(value) => match T::deserialize(value) {
+                Ok(value) => Ok(Some(value)),
+                Err(error) => Err(prepend_pointer(error, self, pointer)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Takes the value at a JSON Pointer out of `self`, leaving `Value::Null`
+    /// in its place, and deserializes it as a `T` without cloning it.
+    ///
+    /// Returns `Ok(None)` if there is no value at the pointer. Errors from
+    /// deserializing the value have the pointer at the start of their path.
+    /// See [`pointer`][Value::pointer] for the syntax.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// use serde::Deserialize;
+    /// use serde_yaml::Value;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Database {
+    ///     url: String,
+    ///     pool: u32,
+    /// }
+    ///
+    /// let yaml = "
+    /// services:
+    ///   api:
+    ///     database: {url: 'postgres://db', pool: 8}
+    ///     routes: [/health, /v1]
+    /// ";
+    ///
+    /// let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+    /// let database: Database = config.take_as("/services/api/database").unwrap().unwrap();
+    /// assert_eq!(database.url, "postgres://db");
+    /// assert_eq!(database.pool, 8);
+    ///
+    /// assert!(config["services"]["api"]["database"].is_null());
+    /// assert_eq!(config["services"]["api"]["routes"][1], "/v1");
+    ///
+    /// let error = config.take_as::<Vec<u8>>("/services/api/routes").unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "services.api.routes[0]: invalid type: string \"/health\", expected u8",
+    /// );
+    /// ```
+    pub fn take_as<T>(&mut self, pointer: &str) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let value = match self.pointer_mut(pointer) {
+            Some(value) => mem::take(value),
+            None => return Ok(None),
+        };
+        match from_value(value) {
+            Ok(value) => Ok(Some(value)),
+            Err(error) => Err(prepend_pointer(error, self, pointer)),
+        }
+    }
+
+    /// Deserializes every entry of a mapping as a `K` key and `V` value,
+    /// borrowing from `self`, and reports all of the entries that fail
+    /// rather than only the first.
+    ///
+    /// Each failure comes with the key of its entry as it is in the mapping,
+    /// and an error that has the key at the start of its path. A Value does
+    /// not know where in a document it was read from, so the errors have no
+    /// location. If `self` is not a mapping, the error for the whole value is
+    /// the only one, with a null key.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let ports: Value = serde_yaml::from_str("{web: 80, api: eighty, db: 5432, cache: -1}").unwrap();
+    ///
+    /// let errors = ports.to_map_of::<String, u16>().unwrap_err();
+    /// let errors: Vec<String> = errors.iter().map(|(_key, error)| error.to_string()).collect();
+    /// assert_eq!(
+    ///     errors,
+    ///     [
+    ///         "api: invalid type: string \"eighty\", expected u16",
+    ///         "cache: invalid value: integer `-1`, expected u16",
+    ///     ],
+    /// );
+    ///
+    /// let ports: BTreeMap<&str, Value> = ports.to_map_of().unwrap();
+    /// assert_eq!(ports.len(), 4);
+    /// ```
+    pub fn to_map_of<'de, K, V>(&'de self) -> Result<BTreeMap<K, V>, Vec<(Value, Error)>>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        let mapping = match self.untag_ref() {
+            Value::Mapping(mapping) => mapping,
+            other => {
+                return BTreeMap::deserialize(other).map_err(|error| vec![(Value::Null, error)]);
+            }
+        };
+        let mut map = BTreeMap::new();
+        let mut errors = Vec::new();
+        for (key, value) in mapping {
+            match (K::deserialize(key), V::deserialize(value)) {
+                (Ok(k), Ok(v)) => {
+                    map.insert(k, v);
+                }
+                (Err(error), _) | (_, Err(error)) => {
+                    errors.push((key.clone(), prepend_key(error, key)));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(map)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Deserializes every element of a sequence as a `T`, borrowing from
+    /// `self`, and reports all of the elements that fail rather than only
+    /// the first.
+    ///
+    /// Each failure comes with the index of its element, and an error that
+    /// has the index at the start of its path. If `self` is not a sequence,
+    /// the error for the whole value is the only one, at index 0.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let ports: Value = serde_yaml::from_str("[80, eighty, 5432, -1]").unwrap();
+    ///
+    /// let errors = ports.to_vec_of::<u16>().unwrap_err();
+    /// let indices: Vec<usize> = errors.iter().map(|(index, _error)| *index).collect();
+    /// assert_eq!(indices, [1, 3]);
+    /// assert_eq!(errors[0].1.to_string(), ".[1]: invalid type: string \"eighty\", expected u16");
+    /// ```
+    pub fn to_vec_of<'de, T>(&'de self) -> Result<Vec<T>, Vec<(usize, Error)>>
+    where
+        T: Deserialize<'de>,
+    {
+        let sequence = match self.untag_ref() {
+            Value::Sequence(sequence) => sequence,
+            other => return Vec::deserialize(other).map_err(|error| vec![(0, error)]),
+        };
+        let mut vec = Vec::with_capacity(sequence.len());
+        let mut errors = Vec::new();
+        for (index, element) in sequence.iter().enumerate() {
+            match T::deserialize(element) {
+                Ok(element) => vec.push(element),
+                Err(error) => errors.push((index, prepend_index(error, index))),
+            }
+        }
+        if errors.is_empty() {
+            Ok(vec)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Calls a function on every node of the value, parents before their
+    /// children.
+    ///
+    /// The entries of a mapping are visited in order, the key of each entry
+    /// before its value, with the [`Path`] telling the two apart. The value
+    /// inside of a tagged node is visited right after the tagged node, at the
+    /// same path. The function returns whether to visit the children of the
+    /// node, skip them, or stop.
+    ///
+    /// ```
+    /// use serde_yaml::value::{Value, Walk};
+    ///
+    /// let value: Value = serde_yaml::from_str("a: [1, 2]\nb: {c: 3}").unwrap();
+    ///
+    /// let mut count = 0;
+    /// value.walk(|_path, _node| {
+    ///     count += 1;
+    ///     Walk::Continue
+    /// });
+    /// assert_eq!(count, 9);
+    ///
+    /// let mut first = None;
+    /// value.walk(|path, node| {
+    ///     if node.is_number() && !path.is_key() {
+    ///         first = Some(path.to_string());
+    ///         return Walk::Stop;
+    ///     }
+    ///     Walk::Continue
+    /// });
+    /// assert_eq!(first.unwrap(), "/a/0");
+    /// ```
+    pub fn walk<F>(&self, mut f: F)
+    where
+        F: FnMut(&Path, &Value) -> Walk,
+    {
+        walk::walk(self, &mut Vec::new(), &mut f);
+    }
+
+    /// Calls a function on every node of the value like [`walk`][Value::walk],
+    /// with the ability to modify each one.
+    ///
+    /// Mapping keys cannot be changed in place, so they are not visited. The
+    /// function may replace the node entirely, in which case the children of
+    /// the new node are visited afterwards unless it returns
+    /// [`Walk::SkipChildren`].
+    ///
+    /// ```
+    /// use serde_yaml::value::{Value, Walk};
+    ///
+    /// let yaml = "
+    /// Name: Web
+    /// Tags: [Frontend, Public]
+    /// debug: {Verbose: true, Trace: [Http]}
+    /// ";
+    /// let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    ///
+    /// value.walk_mut(|_path, node| {
+    ///     if let Value::String(string) = node {
+    ///         *string = string.to_lowercase();
+    ///     }
+    ///     Walk::Continue
+    /// });
+    /// assert_eq!(value["Tags"][0], "frontend");
+    /// assert_eq!(value["debug"]["Trace"][0], "http");
+    ///
+    /// value.walk_mut(|path, node| {
+    ///     if path.key() == Some("debug") {
+    ///         *node = Value::Null;
+    ///         return Walk::SkipChildren;
+    ///     }
+    ///     Walk::Continue
+    /// });
+    /// let expected: Value = serde_yaml::from_str("{Name: web, Tags: [frontend, public], debug: null}").unwrap();
+    /// assert_eq!(value, expected);
+    /// ```
+    pub fn walk_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Path, &mut Value) -> Walk,
+    {
+        walk::walk_mut(self, &mut Vec::new(), &mut f);
+    }
+
+    /// Sorts the entries of every mapping in the value by key, including the
+    /// mappings in sequences, tagged values and keys.
+    ///
+    /// Keys are ordered by the same total order that `<` uses for values, so
+    /// two values that are equal serialize the same once sorted.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let mut value: Value = serde_yaml::from_str("{b: [{y: 1, x: 2}], a: !t {d: 3, c: 4}}").unwrap();
+    /// value.sort_all_maps();
+    ///
+    /// let yaml = serde_yaml::to_string(&value).unwrap();
+    /// assert_eq!(yaml, "a: !t\n  c: 4\n  d: 3\nb:\n- x: 2\n  y: 1\n");
+    /// ```
+    pub fn sort_all_maps(&mut self) {
+        normalize::sort_all_maps(self);
+    }
+
+    /// Brings the value into a normal form for comparing documents, by
+    /// sorting mappings, resolving quoted scalars, stripping tags and
+    /// unifying nulls as selected by the options.
+    ///
+    /// Returns the value to allow chaining, or an error if it is nested
+    /// deeper than the [depth limit][NormalizeOptions::depth_limit], in which
+    /// case it is left unchanged.
+    ///
+    /// ```
+    /// use serde_yaml::value::{NormalizeOptions, Value};
+    ///
+    /// let mut a: Value = serde_yaml::from_str("{port: '8080', tls: !secret ~}").unwrap();
+    /// let mut b: Value = serde_yaml::from_str("{tls: null, port: 8080}").unwrap();
+    ///
+    /// let options = NormalizeOptions::new();
+    /// assert_eq!(
+    ///     serde_yaml::to_string(a.normalize(options.clone()).unwrap()).unwrap(),
+    ///     serde_yaml::to_string(b.normalize(options).unwrap()).unwrap(),
+    /// );
+    /// ```
+    pub fn normalize(&mut self, options: NormalizeOptions) -> Result<&mut Self, Error> {
+        normalize::normalize(self, &options)?;
+        Ok(self)
+    }
+
+    /// Replaces every tagged value, like `!Variant {...}`, with a mapping of
+    /// one entry from the name of the tag to its contents, like
+    /// `Variant: {...}`. These are the two ways that enums are written, as
+    /// in the [`singleton_map`] module.
+    ///
+    /// Tagged values inside of sequences, mapping values and other tagged
+    /// values are replaced too, but not those used as mapping keys. The tags
+    /// of the core schema, like `!!str`, are left as they are.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let mut value: Value = serde_yaml::from_str("[!Circle {r: 1}, !Point null]").unwrap();
+    /// value.tags_to_singleton_maps();
+    ///
+    /// let expected: Value = serde_yaml::from_str("[Circle: {r: 1}, Point: null]").unwrap();
+    /// assert_eq!(value, expected);
+    /// ```
+    ///
+    /// [`singleton_map`]: crate::with::singleton_map
+    pub fn tags_to_singleton_maps(&mut self) {
+        self.walk_mut(|_path, node| {
+            let core = "tag:yaml.org,2002:";
+            if !matches!(node, Value::Tagged(tagged) if !tagged.tag.string.starts_with(core)) {
+                return Walk::Continue;
+            }
+            if let Value::Tagged(tagged) = mem::take(node) {
+                let key = Value::String(tagged::nobang(&tagged.tag.string).to_owned());
+                let mut mapping = Mapping::new();
+                mapping.insert(key, tagged.value);
+                *node = Value::Mapping(mapping);
+            }
+            Walk::Continue
+        });
+    }
+
+    /// The inverse of [`tags_to_singleton_maps`], which replaces every
+    /// mapping of one entry whose key is a string for which `variant_names`
+    /// returns true with a value tagged by that name.
+    ///
+    /// Only the mappings that the function picks out are changed, so it
+    /// decides which of them are really enums rather than data that happens
+    /// to have one key. Converting a value to singleton maps and back with a
+    /// function that accepts the names of its tags results in the same value.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let yaml = "{shape: {Circle: {r: 1}}, labels: {color: red}}";
+    /// let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    /// value.singleton_maps_to_tags(&|name| name == "Circle");
+    ///
+    /// let expected: Value = serde_yaml::from_str("{shape: !Circle {r: 1}, labels: {color: red}}").unwrap();
+    /// assert_eq!(value, expected);
+    /// ```
+    ///
+    /// [`tags_to_singleton_maps`]: Value::tags_to_singleton_maps
+    pub fn singleton_maps_to_tags(&mut self, variant_names: &dyn Fn(&str) -> bool) {
+        self.walk_mut(|_path, node| {
+            let variant = match node {
+                Value::Mapping(mapping) if mapping.len() == 1 => match mapping.iter().next() {
+                    Some((Value::String(name), _)) if !name.is_empty() && variant_names(name) => {
+                        name.clone()
+                    }
+                    _ => return Walk::Continue,
+                },
+                _ => return Walk::Continue,
+            };
+            let value = match mem::take(node) {
+                Value::Mapping(mapping) => mapping.into_iter().next().unwrap().1,
+                _ => unreachable!(),
+            };
+            *node = Value::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(variant),
+                value,
+            }));
+            Walk::Continue
+        });
+    }
+
     /// Returns true if the `Value` is a Null. Returns false otherwise.
     ///
     /// For any Value on which `is_null` returns true, `as_null` is guaranteed
@@ -378,13 +834,55 @@ impl Value {
         }
     }
 
+    /// If the `Value` is an integer, represent it as i128 if possible.
+    /// Returns None otherwise.
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("-170141183460469231731687303715884105728").unwrap();
+    /// assert_eq!(v.as_i128(), Some(i128::MIN));
+    /// ```
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("false").unwrap();
+    /// assert_eq!(v.as_i128(), None);
+    /// ```
+    pub fn as_i128(&self) -> Option<i128> {
+        match self.untag_ref() {
+            Value::Number(n) => n.as_i128(),
+            _ => None,
+        }
+    }
+
+    /// If the `Value` is an integer, represent it as u128 if possible.
+    /// Returns None otherwise.
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("340282366920938463463374607431768211455").unwrap();
+    /// assert_eq!(v.as_u128(), Some(u128::MAX));
+    /// ```
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("false").unwrap();
+    /// assert_eq!(v.as_u128(), None);
+    /// ```
+    pub fn as_u128(&self) -> Option<u128> {
+        match self.untag_ref() {
+            Value::Number(n) => n.as_u128(),
+            _ => None,
+        }
+    }
+
     /// Returns true if the `Value` is a number that can be represented by f64.
     ///
     /// For any Value on which `is_f64` returns true, `as_f64` is guaranteed to
     /// return the floating point value.
     ///
-    /// Currently this function returns true if and only if both `is_i64` and
-    /// `is_u64` return false but this is not a guarantee in the future.
+    /// Currently this function returns true if and only if the `Value` is a
+    /// number that is not an integer but this is not a guarantee in the future.
     ///
     /// ```
     /// # use serde_yaml::Value;
@@ -462,6 +960,7 @@ impl Value {
     pub fn as_str(&self) -> Option<&str> {
         match self.untag_ref() {
             Value::String(s) => Some(s),
+            Value::Styled(styled) => Some(&styled.value),
             _ => None,
         }
     }
@@ -597,6 +1096,24 @@ impl Value {
         }
     }
 
+    /// Deeply compares two values, treating every mapping as a set of entries
+    /// so that the order of keys does not matter at any level.
+    ///
+    /// This is the same comparison `==` performs and is provided to make the
+    /// intent explicit next to [`Mapping::eq_ordered`]. Sequences are still
+    /// compared element by element in order.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let a: Value = serde_yaml::from_str("{x: {a: 1, b: 2}, y: [1, 2]}").unwrap();
+    /// let b: Value = serde_yaml::from_str("{y: [1, 2], x: {b: 2, a: 1}}").unwrap();
+    /// assert!(a.eq_ignore_order(&b));
+    /// ```
+    pub fn eq_ignore_order(&self, other: &Value) -> bool {
+        self == other
+    }
+
     /// Performs merging of `<<` keys into the surrounding mapping.
     ///
     /// The intended use of this in YAML is described in
@@ -624,45 +1141,92 @@ impl Value {
     /// assert_eq!(value["tasks"]["start"]["args"], "start");
     /// ```
     pub fn apply_merge(&mut self) -> Result<(), Error> {
+        self.merge(false)
+    }
+
+    /// Performs merging of `<<` keys like [`apply_merge`][Value::apply_merge],
+    /// but fails if two of the merged mappings define the same key with
+    /// different values.
+    ///
+    /// Keys written directly in the surrounding mapping still override the
+    /// merged ones without error.
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let config = "\
+    /// base: &base
+    ///   retries: 3
+    /// local: &local
+    ///   retries: 5
+    /// service:
+    ///   <<: [*local, *base]
+    /// ";
+    ///
+    /// let mut value: Value = serde_yaml::from_str(config).unwrap();
+    /// let error = value.apply_merge_strict().unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "service.<<: conflicting values for key \"retries\" in merge",
+    /// );
+    /// ```
+    pub fn apply_merge_strict(&mut self) -> Result<(), Error> {
+        self.merge(true)
+    }
+
+    fn merge(&mut self, strict: bool) -> Result<(), Error> {
+        // Each entry is the depth of its parent, the position of the node
+        // within its parent, and the node. Siblings are pushed in reverse so
+        // that they are visited in order. The path to the node currently
+        // being visited is kept for error messages.
         let mut stack = Vec::new();
-        stack.push(self);
-        while let Some(node) = stack.pop() {
+        let mut path = Vec::new();
+        stack.push((0, None, self));
+        while let Some((depth, segment, node)) = stack.pop() {
+            path.truncate(depth);
+            path.extend(segment);
             match node {
                 Value::Mapping(mapping) => {
-                    match mapping.remove("<<") {
-                        Some(Value::Mapping(merge)) => {
-                            for (k, v) in merge {
-                                mapping.entry(k).or_insert(v);
+                    if let Some(mut merge) = mapping.remove("<<") {
+                        // Merged mappings may contain merge keys of their own,
+                        // which need to be resolved before taking their entries.
+                        let merged = merge
+                            .merge(strict)
+                            .and_then(|()| merge_sources(merge, strict));
+                        match merged {
+                            Ok(merged) => {
+                                for (k, v) in merged {
+                                    mapping.entry(k).or_insert(v);
+                                }
                             }
-                        }
-                        Some(Value::Sequence(sequence)) => {
-                            for value in sequence {
-                                match value {
-                                    Value::Mapping(merge) => {
-                                        for (k, v) in merge {
-                                            mapping.entry(k).or_insert(v);
-                                        }
-                                    }
-                                    Value::Sequence(_) => {
-                                        return Err(error::new(ErrorImpl::SequenceInMergeElement));
-                                    }
-                                    Value::Tagged(_) => {
-                                        return Err(error::new(ErrorImpl::TaggedInMerge));
-                                    }
-                                    _unexpected => {
-                                        return Err(error::new(ErrorImpl::ScalarInMergeElement));
-                                    }
+                            Err(mut error) => {
+                                error = prepend_key(error, &Value::from("<<"));
+                                for segment in path.iter().rev() {
+                                    error = match segment {
+                                        MergeSegment::Key(key) => prepend_key(error, key),
+                                        MergeSegment::Index(index) => prepend_index(error, *index),
+                                    };
                                 }
+                                return Err(error);
                             }
                         }
-                        None => {}
-                        Some(Value::Tagged(_)) => return Err(error::new(ErrorImpl::TaggedInMerge)),
-                        Some(_unexpected) => return Err(error::new(ErrorImpl::ScalarInMerge)),
                     }
-                    stack.extend(mapping.values_mut());
+                    let depth = path.len();
+                    let start = stack.len();
+                    for (key, value) in mapping {
+                        stack.push((depth, Some(MergeSegment::Key(key)), value));
+                    }
+                    stack[start..].reverse();
+                }
+                Value::Sequence(sequence) => {
+                    let depth = path.len();
+                    let start = stack.len();
+                    for (index, value) in sequence.iter_mut().enumerate() {
+                        stack.push((depth, Some(MergeSegment::Index(index)), value));
+                    }
+                    stack[start..].reverse();
                 }
-                Value::Sequence(sequence) => stack.extend(sequence),
-                Value::Tagged(tagged) => stack.push(&mut tagged.value),
+                Value::Tagged(tagged) => stack.push((path.len(), None, &mut tagged.value)),
                 _ => {}
             }
         }
@@ -670,18 +1234,231 @@ impl Value {
     }
 }
 
+enum MergeSegment<'a> {
+    Key(&'a Value),
+    Index(usize),
+}
+
+// Puts the key of a map value in front of the path of an error from that value.
+pub(crate) fn prepend_key(error: Error, key: &Value) -> Error {
+    match key.untag_ref() {
+        Value::String(key) => error::prepend_path(error, key),
+        Value::Bool(boolean) => error::prepend_path(error, &boolean.to_string()),
+        Value::Number(number) => error::prepend_path(error, &number.to_string()),
+        _ => error::prepend_path(error, "?"),
+    }
+}
+
+// Splits a JSON Pointer into its tokens, with `~1` and `~0` unescaped.
+fn pointer_tokens(pointer: &str) -> Option<impl Iterator<Item = Cow<'_, str>>> {
+    let rest = if pointer.is_empty() {
+        None
+    } else {
+        Some(pointer.strip_prefix('/')?)
+    };
+    Some(
+        rest.into_iter()
+            .flat_map(|rest| rest.split('/'))
+            .map(|token| {
+                if token.contains('~') {
+                    Cow::Owned(token.replace("~1", "/").replace("~0", "~"))
+                } else {
+                    Cow::Borrowed(token)
+                }
+            }),
+    )
+}
+
+// An index in a JSON Pointer is written in decimal without leading zeros.
+fn parse_pointer_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || !token.bytes().all(|b| b.is_ascii_digit())
+        || (token.len() > 1 && token.starts_with('0'))
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn pointer_integer_key(token: &str) -> Option<Value> {
+    if let Ok(u) = token.parse::<u64>() {
+        Some(Value::from(u))
+    } else {
+        token.parse::<i64>().ok().map(Value::from)
+    }
+}
+
+// Puts the path of the node that a JSON Pointer refers to in front of the
+// path of an error from that node.
+fn prepend_pointer(mut error: Error, root: &Value, pointer: &str) -> Error {
+    let mut segments = Vec::new();
+    let mut value = root;
+    for token in pointer_tokens(pointer).into_iter().flatten() {
+        let next = match value.untag_ref() {
+            Value::Sequence(sequence) => parse_pointer_index(&token).and_then(|index| {
+                segments.push(format!("[{}]", index));
+                sequence.get(index)
+            }),
+            Value::Mapping(mapping) => {
+                let next = match mapping.get(&*token) {
+                    Some(value) => Some(value),
+                    None => pointer_integer_key(&token).and_then(|key| mapping.get(key)),
+                };
+                segments.push(token.into_owned());
+                next
+            }
+            _ => None,
+        };
+        value = match next {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    for segment in segments.iter().rev() {
+        error = error::prepend_path(error, segment);
+    }
+    error
+}
+
+// Puts the index of a sequence element in front of the path of an error from
+// that element.
+fn prepend_index(error: Error, index: usize) -> Error {
+    error::prepend_path(error, &format!("[{}]", index))
+}
+
+// Combines the mappings named by a `<<` key into one. Earlier mappings in a
+// sequence take precedence over later ones.
+fn merge_sources(merge: Value, strict: bool) -> Result<Mapping, Error> {
+    let sequence = match merge {
+        Value::Mapping(merge) => return Ok(merge),
+        Value::Sequence(sequence) => sequence,
+        Value::Tagged(_) => return Err(error::new(ErrorImpl::TaggedInMerge)),
+        _unexpected => return Err(error::new(ErrorImpl::ScalarInMerge)),
+    };
+    let mut merged = Mapping::new();
+    for (index, value) in sequence.into_iter().enumerate() {
+        let unexpected = match value {
+            Value::Mapping(source) => {
+                merge_source(&mut merged, source, strict)?;
+                continue;
+            }
+            Value::Sequence(_) => ErrorImpl::SequenceInMergeElement,
+            Value::Tagged(_) => ErrorImpl::TaggedInMerge,
+            _unexpected => ErrorImpl::ScalarInMergeElement,
+        };
+        return Err(prepend_index(error::new(unexpected), index));
+    }
+    Ok(merged)
+}
+
+fn merge_source(merged: &mut Mapping, source: Mapping, strict: bool) -> Result<(), Error> {
+    for (k, v) in source {
+        match merged.entry(k) {
+            mapping::Entry::Vacant(entry) => {
+                entry.insert(v);
+            }
+            mapping::Entry::Occupied(entry) => {
+                if strict && *entry.get() != v {
+                    let key = entry.key();
+                    return Err(error::new(ErrorImpl::MergeConflict(key_description(key))));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn key_description(key: &Value) -> String {
+    match key {
+        Value::Null => "null key".to_owned(),
+        Value::Bool(boolean) => format!("key `{}`", boolean),
+        Value::Number(number) => format!("key {}", number),
+        Value::String(string) => format!("key {:?}", string),
+        Value::Styled(styled) => format!("key {:?}", styled.value),
+        Value::Sequence(_) | Value::Mapping(_) | Value::Tagged(_) => {
+            match crate::ser::to_flow_string(key) {
+                Ok(rendered) => format!("key `{}`", rendered),
+                Err(_) => "key".to_owned(),
+            }
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Sequence(a), Value::Sequence(b)) => a == b,
+            (Value::Mapping(a), Value::Mapping(b)) => a == b,
+            (Value::Tagged(a), Value::Tagged(b)) => a == b,
+            _ => match (self.string(), other.string()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Sequence(a), Value::Sequence(b)) => a.partial_cmp(b),
+            (Value::Mapping(a), Value::Mapping(b)) => a.partial_cmp(b),
+            (Value::Tagged(a), Value::Tagged(b)) => a.partial_cmp(b),
+            _ => match (self.string(), other.string()) {
+                (Some(a), Some(b)) => a.partial_cmp(b),
+                _ => self.rank().partial_cmp(&other.rank()),
+            },
+        }
+    }
+}
+
+impl Value {
+    // The content of a String or Styled value, which are equal to each other
+    // if their content is.
+    pub(crate) fn string(&self) -> Option<&String> {
+        match self {
+            Value::String(string) => Some(string),
+            Value::Styled(styled) => Some(&styled.value),
+            _ => None,
+        }
+    }
+
+    // The order of the kinds of values, in which a Styled value is a String.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) | Value::Styled(_) => 3,
+            Value::Sequence(_) => 4,
+            Value::Mapping(_) => 5,
+            Value::Tagged(_) => 6,
+        }
+    }
+}
+
 impl Eq for Value {}
 
 // NOTE: This impl must be kept consistent with HashLikeValue's Hash impl in
 // mapping.rs in order for value[str] indexing to work.
 impl Hash for Value {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        mem::discriminant(self).hash(state);
+        const STRING: Value = Value::String(String::new());
+        match self {
+            Value::Styled(_) => mem::discriminant(&STRING).hash(state),
+            _ => mem::discriminant(self).hash(state),
+        }
         match self {
             Value::Null => {}
             Value::Bool(v) => v.hash(state),
             Value::Number(v) => v.hash(state),
             Value::String(v) => v.hash(state),
+            Value::Styled(v) => v.value.hash(state),
             Value::Sequence(v) => v.hash(state),
             Value::Mapping(v) => v.hash(state),
             Value::Tagged(v) => v.hash(state),
@@ -0,0 +1,201 @@
+use crate::error::{self, Error, ErrorImpl};
+use crate::libyaml::parser::{Scalar, ScalarStyle};
+use crate::mapping::{self, Mapping};
+use crate::schema::CoercionFlags;
+use crate::value::{Tag, TaggedValue, Value};
+use std::mem;
+
+/// What [`Value::normalize`] does to a value.
+///
+/// Every step is enabled by default, which makes two values that differ only
+/// in how their document was written compare and serialize the same.
+#[derive(Clone, Debug)]
+pub struct NormalizeOptions {
+    sort_maps: bool,
+    resolve_scalars: bool,
+    strip_tags: bool,
+    unify_nulls: bool,
+    depth_limit: usize,
+}
+
+impl NormalizeOptions {
+    /// Options with every step enabled and a depth limit of 128, the same as
+    /// the recursion limit of the deserializer.
+    pub fn new() -> Self {
+        NormalizeOptions {
+            sort_maps: true,
+            resolve_scalars: true,
+            strip_tags: true,
+            unify_nulls: true,
+            depth_limit: 128,
+        }
+    }
+
+    /// Sorts the entries of every mapping by key, as
+    /// [`Value::sort_all_maps`] does.
+    pub fn sort_maps(mut self, sort_maps: bool) -> Self {
+        self.sort_maps = sort_maps;
+        self
+    }
+
+    /// Replaces each string that would read back as a null, boolean or
+    /// number if it were written unquoted, like `'8080'`, with that value.
+    pub fn resolve_scalars(mut self, resolve_scalars: bool) -> Self {
+        self.resolve_scalars = resolve_scalars;
+        self
+    }
+
+    /// Replaces each tagged value with its untagged contents.
+    pub fn strip_tags(mut self, strip_tags: bool) -> Self {
+        self.strip_tags = strip_tags;
+        self
+    }
+
+    /// Replaces the strings that spell null, which are `''`, `'~'`,
+    /// `'null'`, `'Null'` and `'NULL'`, with null. Unlike
+    /// [`resolve_scalars`][NormalizeOptions::resolve_scalars], this leaves
+    /// other strings as they are.
+    pub fn unify_nulls(mut self, unify_nulls: bool) -> Self {
+        self.unify_nulls = unify_nulls;
+        self
+    }
+
+    /// The deepest nesting of sequences and mappings that is accepted. A
+    /// value that is nested deeper is left unchanged and results in an error.
+    pub fn depth_limit(mut self, depth_limit: usize) -> Self {
+        self.depth_limit = depth_limit;
+        self
+    }
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        NormalizeOptions::new()
+    }
+}
+
+enum Task {
+    Visit(Value),
+    Sequence(usize),
+    Mapping(usize),
+    Tagged(Tag),
+}
+
+pub(crate) fn normalize(value: &mut Value, options: &NormalizeOptions) -> Result<(), Error> {
+    check_depth(value, options.depth_limit)?;
+    *value = rebuild(mem::take(value), options);
+    Ok(())
+}
+
+pub(crate) fn sort_all_maps(value: &mut Value) {
+    let options = NormalizeOptions::new()
+        .resolve_scalars(false)
+        .strip_tags(false)
+        .unify_nulls(false);
+    *value = rebuild(mem::take(value), &options);
+}
+
+// Rebuilds a value from the bottom up with an explicit stack rather than by
+// recursion, so that the contents of every key are done before the mapping
+// that contains it is sorted.
+fn rebuild(value: Value, options: &NormalizeOptions) -> Value {
+    let mut tasks = vec![Task::Visit(value)];
+    let mut done = Vec::new();
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Visit(Value::Sequence(sequence)) => {
+                tasks.push(Task::Sequence(sequence.len()));
+                tasks.extend(sequence.into_iter().rev().map(Task::Visit));
+            }
+            Task::Visit(Value::Mapping(mapping)) => {
+                tasks.push(Task::Mapping(mapping.len()));
+                let entries: Vec<(Value, Value)> = mapping.into_iter().collect();
+                for (key, value) in entries.into_iter().rev() {
+                    tasks.push(Task::Visit(value));
+                    tasks.push(Task::Visit(key));
+                }
+            }
+            Task::Visit(Value::Tagged(tagged)) => {
+                if !options.strip_tags {
+                    tasks.push(Task::Tagged(tagged.tag));
+                }
+                tasks.push(Task::Visit(tagged.value));
+            }
+            Task::Visit(Value::String(string)) => done.push(resolve(string, options)),
+            Task::Visit(Value::Styled(styled)) => done.push(resolve(styled.value, options)),
+            Task::Visit(scalar) => done.push(scalar),
+            Task::Sequence(len) => {
+                let sequence = done.split_off(done.len() - len);
+                done.push(Value::Sequence(sequence));
+            }
+            Task::Mapping(len) => {
+                let mut entries = Vec::with_capacity(len);
+                let mut contents = done.drain(done.len() - 2 * len..);
+                while let (Some(key), Some(value)) = (contents.next(), contents.next()) {
+                    entries.push((key, value));
+                }
+                drop(contents);
+                if options.sort_maps {
+                    entries.sort_by(|(a, _), (b, _)| mapping::total_cmp(a, b));
+                }
+                // Keys that have become equal keep the last of their values.
+                done.push(Value::Mapping(Mapping::from_iter(entries)));
+            }
+            Task::Tagged(tag) => {
+                let value = done.pop().unwrap();
+                done.push(Value::Tagged(Box::new(TaggedValue { tag, value })));
+            }
+        }
+    }
+    done.pop().unwrap()
+}
+
+fn resolve(string: String, options: &NormalizeOptions) -> Value {
+    if !options.resolve_scalars && !options.unify_nulls {
+        return Value::String(string);
+    }
+    let scalar = Scalar {
+        anchor: None,
+        tag: None,
+        value: Box::from(string.as_bytes()),
+        style: ScalarStyle::Plain,
+        repr: None,
+    };
+    if crate::de::is_string_scalar(&scalar, &CoercionFlags::CORE_12) {
+        return Value::String(string);
+    }
+    match crate::from_str(&string) {
+        Ok(Value::Null) => Value::Null,
+        Ok(resolved) if options.resolve_scalars => resolved,
+        _ => Value::String(string),
+    }
+}
+
+// Checks the depth before anything is changed, so that a value that is too
+// deep is left as it was.
+fn check_depth(value: &Value, depth_limit: usize) -> Result<(), Error> {
+    let mut stack = vec![(value, 0)];
+    while let Some((node, depth)) = stack.pop() {
+        match node {
+            Value::Sequence(_) | Value::Mapping(_) if depth == depth_limit => {
+                return Err(error::new(ErrorImpl::DepthLimitExceeded(depth_limit)));
+            }
+            Value::Sequence(sequence) => {
+                stack.extend(sequence.iter().map(|element| (element, depth + 1)));
+            }
+            Value::Mapping(mapping) => {
+                for (key, value) in mapping {
+                    stack.push((key, depth + 1));
+                    stack.push((value, depth + 1));
+                }
+            }
+            Value::Tagged(tagged) => stack.push((&tagged.value, depth)),
+            Value::Null
+            | Value::Bool(_)
+            | Value::Number(_)
+            | Value::String(_)
+            | Value::Styled(_) => {}
+        }
+    }
+    Ok(())
+}
@@ -83,5 +83,7 @@ macro_rules! partialeq_numeric {
 partialeq_numeric! {
     [i8 i16 i32 i64 isize], as_i64, i64
     [u8 u16 u32 u64 usize], as_u64, u64
+    [i128], as_i128, i128
+    [u128], as_u128, u128
     [f32 f64], as_f64, f64
 }
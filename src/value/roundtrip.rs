@@ -0,0 +1,270 @@
+//! Checking that YAML written by the serializer reads back as the same Value.
+
+use crate::error::{self, Error, ErrorImpl};
+use crate::libyaml::parser::{Scalar, ScalarStyle};
+use crate::libyaml::tag::Tag as CoreTag;
+use crate::schema::CoercionFlags;
+use crate::value::{key_description, prepend_index, prepend_key, Mapping, Tag, TaggedValue};
+use crate::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::rc::Rc;
+
+// State of a serializer created with `verify_roundtrip(true)`. The value of
+// the current document is built up from the same events that are passed to
+// the emitter, and the text written by the emitter is copied aside so that it
+// can be parsed again once the document is complete.
+pub(crate) struct Verify {
+    output: Rc<RefCell<Vec<u8>>>,
+    stack: Vec<Partial>,
+    document: Option<Value>,
+    // The value of each anchor, and the anchors of the collections on the
+    // stack with the depth of each.
+    anchors: HashMap<String, Value>,
+    open_anchors: Vec<(usize, String)>,
+}
+
+enum Partial {
+    Sequence(Option<String>, Vec<Value>),
+    Mapping(Option<String>, Mapping, Option<Value>),
+}
+
+// Writes to the underlying writer and keeps a copy of everything written.
+pub(crate) struct Tee<W> {
+    pub writer: W,
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl<W> io::Write for Tee<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.output.borrow_mut().extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Verify {
+    pub fn new() -> Self {
+        Verify {
+            output: Rc::new(RefCell::new(Vec::new())),
+            stack: Vec::new(),
+            document: None,
+            anchors: HashMap::new(),
+            open_anchors: Vec::new(),
+        }
+    }
+
+    pub fn tee<W>(&self, writer: W) -> Tee<W> {
+        Tee {
+            writer,
+            output: Rc::clone(&self.output),
+        }
+    }
+
+    pub fn scalar(&mut self, tag: Option<&str>, value: &str, plain: bool) {
+        // The serializer writes numbers, booleans and nulls in plain style,
+        // always in a form that resolves to the value. Everything else is a
+        // string, whichever style the emitter ends up choosing for it, also a
+        // StyledString that asks for plain style. The emitter only keeps
+        // plain style for content that reads back the same. A tag of the
+        // core schema, like `!!int`, decides what it reads back as instead.
+        let resolve = match tag {
+            Some(tag) if is_core_tag(tag) => {
+                [CoreTag::INT, CoreTag::FLOAT, CoreTag::BOOL, CoreTag::NULL].contains(&tag)
+            }
+            _ => plain && !is_string(value),
+        };
+        let value = if resolve {
+            crate::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()))
+        } else {
+            Value::String(value.to_owned())
+        };
+        self.push(tagged(tag.map(str::to_owned), value));
+    }
+
+    pub fn start_sequence(&mut self, anchor: Option<&str>, tag: Option<&str>) {
+        let tag = tag.map(str::to_owned);
+        self.stack.push(Partial::Sequence(tag, Vec::new()));
+        self.open_anchor(anchor);
+    }
+
+    pub fn start_mapping(&mut self, anchor: Option<&str>, tag: Option<&str>) {
+        let tag = tag.map(str::to_owned);
+        self.stack.push(Partial::Mapping(tag, Mapping::new(), None));
+        self.open_anchor(anchor);
+    }
+
+    fn open_anchor(&mut self, anchor: Option<&str>) {
+        if let Some(anchor) = anchor {
+            self.open_anchors
+                .push((self.stack.len(), anchor.to_owned()));
+        }
+    }
+
+    // The value of an alias is the value of its anchor, as the deserializer
+    // reads it.
+    pub fn alias(&mut self, anchor: &str) {
+        let value = self.anchors.get(anchor).cloned().unwrap_or(Value::Null);
+        self.push(value);
+    }
+
+    // Leaves out the entry whose key was just passed in, for an entry that
+    // is written as a comment.
+    pub fn skip_value(&mut self) {
+        if let Some(Partial::Mapping(_, _, key)) = self.stack.last_mut() {
+            *key = None;
+        }
+    }
+
+    pub fn end(&mut self) {
+        let depth = self.stack.len();
+        let value = match self.stack.pop() {
+            Some(Partial::Sequence(tag, sequence)) => tagged(tag, Value::Sequence(sequence)),
+            Some(Partial::Mapping(tag, mapping, _)) => tagged(tag, Value::Mapping(mapping)),
+            None => return,
+        };
+        if matches!(self.open_anchors.last(), Some((open, _)) if *open == depth) {
+            let (_depth, anchor) = self.open_anchors.pop().unwrap();
+            self.anchors.insert(anchor, value.clone());
+        }
+        self.push(value);
+    }
+
+    fn push(&mut self, value: Value) {
+        match self.stack.last_mut() {
+            None => self.document = Some(value),
+            Some(Partial::Sequence(_, sequence)) => sequence.push(value),
+            Some(Partial::Mapping(_, mapping, key)) => match key.take() {
+                None => *key = Some(value),
+                Some(key) => {
+                    mapping.insert(key, value);
+                }
+            },
+        }
+    }
+
+    // The value built from the events so far, once a whole node has been
+    // passed in.
+    pub fn into_document(self) -> Option<Value> {
+        self.document
+    }
+
+    // Called after the emitter has been flushed at the end of a document.
+    pub fn check(&mut self) -> Result<(), Error> {
+        let mut output = mem::take(&mut *self.output.borrow_mut());
+        // Before directives, the emitter writes the `...` that ends the
+        // previous document at the start of the next one.
+        for end in [&b"...\n"[..], b"...\r\n"] {
+            if output.starts_with(end) {
+                output.drain(..end.len());
+            }
+        }
+        self.anchors.clear();
+        let expected = match self.document.take() {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        let found = match read_back(&output) {
+            Ok(found) => found,
+            Err(err) => return Err(error::unreadable_output(err)),
+        };
+        match diff(&expected, &found) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+// The deserializer only keeps tags of the core schema with
+// `preserve_core_tags`, which checking the output does not use.
+fn is_core_tag(tag: &str) -> bool {
+    tag.starts_with("tag:yaml.org,2002:")
+}
+
+// Other than those, it only keeps local tags, not global ones like the tags
+// written with the handle of a `%TAG` directive.
+fn tagged(tag: Option<String>, value: Value) -> Value {
+    match tag {
+        Some(tag) if tag.starts_with('!') => Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new(tag),
+            value,
+        })),
+        _ => value,
+    }
+}
+
+// Whether a plain scalar reads back as a string.
+fn is_string(value: &str) -> bool {
+    let scalar = Scalar {
+        anchor: None,
+        tag: None,
+        value: Box::from(value.as_bytes()),
+        style: ScalarStyle::Plain,
+        repr: None,
+    };
+    crate::de::is_string_scalar(&scalar, &CoercionFlags::CORE_12)
+}
+
+fn read_back(output: &[u8]) -> Result<Value, Error> {
+    let output = String::from_utf8_lossy(output);
+    crate::from_str(&output)
+}
+
+// Finds the first node of `found` that is different from the corresponding
+// node of `expected`, and describes both in an error with the path to them.
+fn diff(expected: &Value, found: &Value) -> Option<Error> {
+    match (expected, found) {
+        (Value::Sequence(expected), Value::Sequence(found)) if expected.len() == found.len() => {
+            for (index, (expected, found)) in expected.iter().zip(found).enumerate() {
+                if let Some(err) = diff(expected, found) {
+                    return Some(prepend_index(err, index));
+                }
+            }
+            None
+        }
+        (Value::Mapping(expected), Value::Mapping(found)) if expected.len() == found.len() => {
+            for ((expected_key, expected), (found_key, found)) in expected.iter().zip(found) {
+                if expected_key != found_key {
+                    return Some(error::new(ErrorImpl::NotRoundTrip(
+                        key_description(expected_key),
+                        key_description(found_key),
+                    )));
+                }
+                if let Some(err) = diff(expected, found) {
+                    return Some(prepend_key(err, expected_key));
+                }
+            }
+            None
+        }
+        (Value::Tagged(expected), Value::Tagged(found)) if expected.tag == found.tag => {
+            diff(&expected.value, &found.value)
+        }
+        _ if expected == found => None,
+        _ => Some(error::new(ErrorImpl::NotRoundTrip(
+            describe(expected),
+            describe(found),
+        ))),
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_owned(),
+        Value::Bool(boolean) => format!("boolean `{}`", boolean),
+        Value::Number(number) => format!("number {}", number),
+        Value::String(string) => format!("string {:?}", string),
+        Value::Styled(styled) => format!("string {:?}", styled.value),
+        Value::Sequence(sequence) => format!("sequence of length {}", sequence.len()),
+        Value::Mapping(mapping) => format!("mapping of length {}", mapping.len()),
+        Value::Tagged(tagged) => format!("value tagged {}", tagged.tag),
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error::{self, Error, ErrorImpl};
-use crate::value::tagged::{self, MaybeTag};
+use crate::ser::{check_for_tag, MaybeTag};
 use crate::value::{to_value, Mapping, Number, Sequence, Tag, TaggedValue, Value};
 use serde::ser::{self, Serialize};
 use std::fmt::Display;
@@ -17,6 +17,7 @@ impl Serialize for Value {
             Value::Bool(b) => serializer.serialize_bool(*b),
             Value::Number(n) => n.serialize(serializer),
             Value::String(s) => serializer.serialize_str(s),
+            Value::Styled(s) => s.serialize(serializer),
             Value::Sequence(seq) => seq.serialize(serializer),
             Value::Mapping(mapping) => {
                 use serde::ser::SerializeMap;
@@ -86,13 +87,7 @@ impl ser::Serializer for Serializer {
     }
 
     fn serialize_i128(self, v: i128) -> Result<Value> {
-        if let Ok(v) = u64::try_from(v) {
-            self.serialize_u64(v)
-        } else if let Ok(v) = i64::try_from(v) {
-            self.serialize_i64(v)
-        } else {
-            Ok(Value::String(v.to_string()))
-        }
+        Ok(Value::Number(Number::from(v)))
     }
 
     fn serialize_u8(self, v: u8) -> Result<Value> {
@@ -112,11 +107,7 @@ impl ser::Serializer for Serializer {
     }
 
     fn serialize_u128(self, v: u128) -> Result<Value> {
-        if let Ok(v) = u64::try_from(v) {
-            self.serialize_u64(v)
-        } else {
-            Ok(Value::String(v.to_string()))
-        }
+        Ok(Value::Number(Number::from(v)))
     }
 
     fn serialize_f32(self, v: f32) -> Result<Value> {
@@ -613,7 +604,7 @@ impl ser::SerializeMap for SerializeMap {
             where
                 T: ?Sized + Display,
             {
-                Ok(match tagged::check_for_tag(value) {
+                Ok(match check_for_tag(value) {
                     MaybeTag::Tag(tag) => MaybeTag::Tag(tag),
                     MaybeTag::NotTag(string) => MaybeTag::NotTag(Value::String(string)),
                 })
@@ -7,10 +7,10 @@ use serde::de::{
 };
 use serde::forward_to_deserialize_any;
 use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::cell::Cell;
 use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
 use std::hash::{Hash, Hasher};
-use std::mem;
 
 /// A representation of YAML's `!Tag` syntax, used for enums.
 ///
@@ -117,6 +117,33 @@ impl Value {
     }
 }
 
+thread_local! {
+    // The core schema tag of the scalar that a deserializer with
+    // `preserve_core_tags` is passing to a visitor, for a Value created by
+    // that visitor to keep.
+    static PARSED_TAG: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+// Makes `tag` available to the Value created during `f`.
+pub(crate) fn with_parsed_tag<T>(tag: &'static str, f: impl FnOnce() -> T) -> T {
+    PARSED_TAG.with(|parsed| parsed.set(Some(tag)));
+    let result = f();
+    PARSED_TAG.with(|parsed| parsed.set(None));
+    result
+}
+
+impl Value {
+    pub(crate) fn with_parsed_tag(self) -> Self {
+        match PARSED_TAG.with(Cell::take) {
+            Some(tag) => Value::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(tag),
+                value: self,
+            })),
+            None => self,
+        }
+    }
+}
+
 pub(crate) fn nobang(maybe_banged: &str) -> &str {
     match maybe_banged.strip_prefix('!') {
         Some("") | None => maybe_banged,
@@ -264,6 +291,11 @@ impl<'de> VariantAccess<'de> for Value {
     type Error = Error;
 
     fn unit_variant(self) -> Result<(), Error> {
+        if let Some(string) = self.string() {
+            if string.is_empty() {
+                return Ok(());
+            }
+        }
         Deserialize::deserialize(self)
     }
 
@@ -281,7 +313,7 @@ impl<'de> VariantAccess<'de> for Value {
         if let Value::Sequence(v) = self {
             Deserializer::deserialize_any(SeqDeserializer::new(v), visitor)
         } else {
-            Err(Error::invalid_type(self.unexpected(), &"tuple variant"))
+            Err(Error::invalid_type(self.unexpected(), &visitor))
         }
     }
 
@@ -294,9 +326,9 @@ impl<'de> VariantAccess<'de> for Value {
         V: Visitor<'de>,
     {
         if let Value::Mapping(v) = self {
-            Deserializer::deserialize_any(MapDeserializer::new(v), visitor)
+            Deserializer::deserialize_any(MapDeserializer::new_struct(v), visitor)
         } else {
-            Err(Error::invalid_type(self.unexpected(), &"struct variant"))
+            Err(Error::invalid_type(self.unexpected(), &visitor))
         }
     }
 }
@@ -343,6 +375,11 @@ impl<'de> VariantAccess<'de> for &'de Value {
     type Error = Error;
 
     fn unit_variant(self) -> Result<(), Error> {
+        if let Some(string) = self.string() {
+            if string.is_empty() {
+                return Ok(());
+            }
+        }
         Deserialize::deserialize(self)
     }
 
@@ -360,7 +397,7 @@ impl<'de> VariantAccess<'de> for &'de Value {
         if let Value::Sequence(v) = self {
             Deserializer::deserialize_any(SeqRefDeserializer::new(v), visitor)
         } else {
-            Err(Error::invalid_type(self.unexpected(), &"tuple variant"))
+            Err(Error::invalid_type(self.unexpected(), &visitor))
         }
     }
 
@@ -373,9 +410,9 @@ impl<'de> VariantAccess<'de> for &'de Value {
         V: Visitor<'de>,
     {
         if let Value::Mapping(v) = self {
-            Deserializer::deserialize_any(MapRefDeserializer::new(v), visitor)
+            Deserializer::deserialize_any(MapRefDeserializer::new_struct(v), visitor)
         } else {
-            Err(Error::invalid_type(self.unexpected(), &"struct variant"))
+            Err(Error::invalid_type(self.unexpected(), &visitor))
         }
     }
 }
@@ -417,58 +454,3 @@ impl<'de> DeserializeSeed<'de> for TagStringVisitor {
         deserializer.deserialize_string(self)
     }
 }
-
-pub(crate) enum MaybeTag<T> {
-    Tag(String),
-    NotTag(T),
-}
-
-pub(crate) fn check_for_tag<T>(value: &T) -> MaybeTag<String>
-where
-    T: ?Sized + Display,
-{
-    enum CheckForTag {
-        Empty,
-        Bang,
-        Tag(String),
-        NotTag(String),
-    }
-
-    impl fmt::Write for CheckForTag {
-        fn write_str(&mut self, s: &str) -> fmt::Result {
-            if s.is_empty() {
-                return Ok(());
-            }
-            match self {
-                CheckForTag::Empty => {
-                    if s == "!" {
-                        *self = CheckForTag::Bang;
-                    } else {
-                        *self = CheckForTag::NotTag(s.to_owned());
-                    }
-                }
-                CheckForTag::Bang => {
-                    *self = CheckForTag::Tag(s.to_owned());
-                }
-                CheckForTag::Tag(string) => {
-                    let mut string = mem::take(string);
-                    string.push_str(s);
-                    *self = CheckForTag::NotTag(string);
-                }
-                CheckForTag::NotTag(string) => {
-                    string.push_str(s);
-                }
-            }
-            Ok(())
-        }
-    }
-
-    let mut check_for_tag = CheckForTag::Empty;
-    fmt::write(&mut check_for_tag, format_args!("{}", value)).unwrap();
-    match check_for_tag {
-        CheckForTag::Empty => MaybeTag::NotTag(String::new()),
-        CheckForTag::Bang => MaybeTag::NotTag("!".to_owned()),
-        CheckForTag::Tag(string) => MaybeTag::Tag(string),
-        CheckForTag::NotTag(string) => MaybeTag::NotTag(string),
-    }
-}
@@ -0,0 +1,169 @@
+use crate::value::Value;
+use std::fmt::{self, Display};
+
+/// What [`Value::walk`] and [`Value::walk_mut`] do after visiting a node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Walk {
+    /// Go on to the children of the node, then the rest of the value.
+    Continue,
+    /// Leave out the children of the node but go on with the rest of the
+    /// value.
+    SkipChildren,
+    /// End the walk without visiting any more nodes.
+    Stop,
+}
+
+/// The location of the node being visited by [`Value::walk`] or
+/// [`Value::walk_mut`].
+///
+/// Formatted as a JSON Pointer that [`Value::pointer`] accepts, like
+/// `/servers/0/port`. For a mapping key, this is the pointer of its entry.
+#[derive(Debug)]
+pub struct Path<'a> {
+    segments: &'a [Segment],
+}
+
+/// One step of a [`Path`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    /// An element of a sequence.
+    Index(usize),
+    /// The value of a mapping entry, identified by its key in the same form
+    /// as a token of a JSON Pointer: string keys as they are, numbers and
+    /// booleans as written, and any other key as `?`.
+    Entry(String),
+    /// The key of a mapping entry, identified in the same way as for
+    /// [`Segment::Entry`]. Only the last segment of a path, or one followed
+    /// by the steps into a sequence or mapping used as a key, can be a key.
+    Key(String),
+}
+
+impl<'a> Path<'a> {
+    /// The steps from the root of the value down to the node.
+    pub fn segments(&self) -> &'a [Segment] {
+        self.segments
+    }
+
+    /// Whether the node is a mapping key, or inside of one.
+    pub fn is_key(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Key(_)))
+    }
+
+    /// The key of the mapping entry that the node is the key or value of, or
+    /// `None` for an element of a sequence or the root.
+    pub fn key(&self) -> Option<&'a str> {
+        match self.segments.last() {
+            Some(Segment::Entry(key) | Segment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Display for Path<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for segment in self.segments {
+            match segment {
+                Segment::Index(index) => write!(formatter, "/{}", index)?,
+                Segment::Entry(key) | Segment::Key(key) => {
+                    write!(formatter, "/{}", key.replace('~', "~0").replace('/', "~1"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Each function returns false once the callback has stopped the walk.
+
+pub(crate) fn walk(
+    value: &Value,
+    segments: &mut Vec<Segment>,
+    f: &mut dyn FnMut(&Path, &Value) -> Walk,
+) -> bool {
+    match f(&Path { segments }, value) {
+        Walk::Continue => {}
+        Walk::SkipChildren => return true,
+        Walk::Stop => return false,
+    }
+    match value {
+        Value::Sequence(sequence) => {
+            for (index, element) in sequence.iter().enumerate() {
+                segments.push(Segment::Index(index));
+                let go_on = walk(element, segments, f);
+                segments.pop();
+                if !go_on {
+                    return false;
+                }
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                let token = key_token(key);
+                segments.push(Segment::Key(token.clone()));
+                let go_on = walk(key, segments, f);
+                segments.pop();
+                if !go_on {
+                    return false;
+                }
+                segments.push(Segment::Entry(token));
+                let go_on = walk(value, segments, f);
+                segments.pop();
+                if !go_on {
+                    return false;
+                }
+            }
+        }
+        Value::Tagged(tagged) => return walk(&tagged.value, segments, f),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Styled(_) => {}
+    }
+    true
+}
+
+pub(crate) fn walk_mut(
+    value: &mut Value,
+    segments: &mut Vec<Segment>,
+    f: &mut dyn FnMut(&Path, &mut Value) -> Walk,
+) -> bool {
+    match f(&Path { segments }, value) {
+        Walk::Continue => {}
+        Walk::SkipChildren => return true,
+        Walk::Stop => return false,
+    }
+    match value {
+        Value::Sequence(sequence) => {
+            for (index, element) in sequence.iter_mut().enumerate() {
+                segments.push(Segment::Index(index));
+                let go_on = walk_mut(element, segments, f);
+                segments.pop();
+                if !go_on {
+                    return false;
+                }
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                segments.push(Segment::Entry(key_token(key)));
+                let go_on = walk_mut(value, segments, f);
+                segments.pop();
+                if !go_on {
+                    return false;
+                }
+            }
+        }
+        Value::Tagged(tagged) => return walk_mut(&mut tagged.value, segments, f),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Styled(_) => {}
+    }
+    true
+}
+
+fn key_token(key: &Value) -> String {
+    match key.untag_ref() {
+        Value::String(string) => string.clone(),
+        Value::Styled(styled) => styled.value.clone(),
+        Value::Bool(boolean) => boolean.to_string(),
+        Value::Number(number) => number.to_string(),
+        _ => "?".to_owned(),
+    }
+}
@@ -105,8 +105,8 @@ pub mod singleton_map {
         })
     }
 
-    struct SingletonMap<D> {
-        delegate: D,
+    pub(super) struct SingletonMap<D> {
+        pub(super) delegate: D,
     }
 
     impl<D> Serialize for SingletonMap<D>
@@ -324,7 +324,7 @@ pub mod singleton_map {
         }
     }
 
-    struct SerializeTupleVariantAsSingletonMap<M> {
+    pub(super) struct SerializeTupleVariantAsSingletonMap<M> {
         map: M,
         sequence: Sequence,
     }
@@ -353,7 +353,7 @@ pub mod singleton_map {
         }
     }
 
-    struct SerializeStructVariantAsSingletonMap<M> {
+    pub(super) struct SerializeStructVariantAsSingletonMap<M> {
         map: M,
         mapping: Mapping,
     }
@@ -835,49 +835,43 @@ pub mod singleton_map {
     }
 }
 
-/// Apply [`singleton_map`] to *all* enums contained within the data structure.
+/// Serialize/deserialize a map whose keys are enums, writing each key in the
+/// [`singleton_map`] form.
+///
+/// Keys which are variants with data become mappings, which are emitted as
+/// flow-style complex keys. Variants without data are plain strings. Only the
+/// keys of the map are affected; values keep their usual representation.
 ///
 /// # Example
 ///
 /// ```
 /// # use serde_derive::{Deserialize, Serialize};
 /// use serde::{Deserialize, Serialize};
+/// use std::collections::BTreeMap;
 ///
-/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// enum Enum {
-///     Int(i32),
-/// }
-///
-/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// struct Inner {
-///     a: Enum,
-///     bs: Vec<Enum>,
+/// #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+/// enum Selector {
+///     Any,
+///     Label { name: String },
 /// }
 ///
 /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// struct Outer {
-///     tagged_style: Inner,
-///
-///     #[serde(with = "serde_yaml::with::singleton_map_recursive")]
-///     singleton_map_style: Inner,
+/// struct Policy {
+///     #[serde(with = "serde_yaml::with::singleton_map_keys")]
+///     rules: BTreeMap<Selector, String>,
 /// }
 ///
 /// fn main() {
-///     let object = Outer {
-///         tagged_style: Inner {
-///             a: Enum::Int(0),
-///             bs: vec![Enum::Int(1)],
-///         },
-///         singleton_map_style: Inner {
-///             a: Enum::Int(2),
-///             bs: vec![Enum::Int(3)],
-///         },
-///     };
+///     let mut rules = BTreeMap::new();
+///     rules.insert(Selector::Any, "accept".to_owned());
+///     let name = "env".to_owned();
+///     rules.insert(Selector::Label { name }, "reject".to_owned());
+///     let object = Policy { rules };
 ///
 ///     let yaml = serde_yaml::to_string(&object).unwrap();
 ///     print!("{}", yaml);
 ///
-///     let deserialized: Outer = serde_yaml::from_str(&yaml).unwrap();
+///     let deserialized: Policy = serde_yaml::from_str(&yaml).unwrap();
 ///     assert_eq!(object, deserialized);
 /// }
 /// ```
@@ -885,63 +879,21 @@ pub mod singleton_map {
 /// The serialized output is:
 ///
 /// ```yaml
-/// tagged_style:
-///   a: !Int 0
-///   bs:
-///   - !Int 1
-/// singleton_map_style:
-///   a:
-///     Int: 2
-///   bs:
-///   - Int: 3
-/// ```
-///
-/// This module can also be used for the top-level serializer or deserializer
-/// call, without `serde(with = …)`, as follows.
-///
+/// rules:
+///   Any: accept
+///   ? {Label: {name: env}}
+///   : reject
 /// ```
-/// # use serde_derive::{Deserialize, Serialize};
-/// # use serde::{Deserialize, Serialize};
-/// #
-/// # #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// # enum Enum {
-/// #     Int(i32),
-/// # }
-/// #
-/// # #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// # struct Inner {
-/// #     a: Enum,
-/// #     bs: Vec<Enum>,
-/// # }
-/// #
-/// use std::io::{self, Write};
-///
-/// fn main() {
-///     let object = Inner {
-///         a: Enum::Int(0),
-///         bs: vec![Enum::Int(1)],
-///     };
-///
-///     let mut buf = Vec::new();
-///     let mut serializer = serde_yaml::Serializer::new(&mut buf);
-///     serde_yaml::with::singleton_map_recursive::serialize(&object, &mut serializer).unwrap();
-///     io::stdout().write_all(&buf).unwrap();
 ///
-///     let deserializer = serde_yaml::Deserializer::from_slice(&buf);
-///     let deserialized: Inner = serde_yaml::with::singleton_map_recursive::deserialize(deserializer).unwrap();
-///     assert_eq!(object, deserialized);
-/// }
-/// ```
-pub mod singleton_map_recursive {
-    use crate::value::{Mapping, Sequence, Value};
-    use serde::de::{
-        self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, MapAccess,
-        SeqAccess, Unexpected, VariantAccess, Visitor,
-    };
-    use serde::ser::{
-        self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
-        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer,
-    };
+/// Two keys which are equal once parsed, even if written differently, are
+/// rejected as a duplicate entry.
+pub mod singleton_map_keys {
+    use super::singleton_map::SingletonMap;
+    use crate::ser;
+    use crate::value::Value;
+    use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, Visitor};
+    use serde::ser::{Serialize, SerializeMap, Serializer};
+    use std::collections::HashSet;
     use std::fmt::{self, Display};
 
     #[allow(missing_docs)]
@@ -950,7 +902,7 @@ pub mod singleton_map_recursive {
         T: Serialize,
         S: Serializer,
     {
-        value.serialize(SingletonMapRecursive {
+        value.serialize(SingletonMapKeys {
             delegate: serializer,
         })
     }
@@ -961,16 +913,16 @@ pub mod singleton_map_recursive {
         T: Deserialize<'de>,
         D: Deserializer<'de>,
     {
-        T::deserialize(SingletonMapRecursive {
+        T::deserialize(SingletonMapKeys {
             delegate: deserializer,
         })
     }
 
-    struct SingletonMapRecursive<D> {
+    struct SingletonMapKeys<D> {
         delegate: D,
     }
 
-    impl<D> Serialize for SingletonMapRecursive<D>
+    impl<D> Serialize for SingletonMapKeys<D>
     where
         D: Serialize,
     {
@@ -978,27 +930,26 @@ pub mod singleton_map_recursive {
         where
             S: Serializer,
         {
-            self.delegate.serialize(SingletonMapRecursive {
+            self.delegate.serialize(SingletonMapKeys {
                 delegate: serializer,
             })
         }
     }
 
-    impl<D> Serializer for SingletonMapRecursive<D>
+    impl<D> Serializer for SingletonMapKeys<D>
     where
         D: Serializer,
     {
         type Ok = D::Ok;
         type Error = D::Error;
 
-        type SerializeSeq = SingletonMapRecursive<D::SerializeSeq>;
-        type SerializeTuple = SingletonMapRecursive<D::SerializeTuple>;
-        type SerializeTupleStruct = SingletonMapRecursive<D::SerializeTupleStruct>;
-        type SerializeTupleVariant = SerializeTupleVariantAsSingletonMapRecursive<D::SerializeMap>;
-        type SerializeMap = SingletonMapRecursive<D::SerializeMap>;
-        type SerializeStruct = SingletonMapRecursive<D::SerializeStruct>;
-        type SerializeStructVariant =
-            SerializeStructVariantAsSingletonMapRecursive<D::SerializeMap>;
+        type SerializeSeq = D::SerializeSeq;
+        type SerializeTuple = D::SerializeTuple;
+        type SerializeTupleStruct = D::SerializeTupleStruct;
+        type SerializeTupleVariant = D::SerializeTupleVariant;
+        type SerializeMap = SingletonMapKeys<D::SerializeMap>;
+        type SerializeStruct = D::SerializeStruct;
+        type SerializeStructVariant = D::SerializeStructVariant;
 
         fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
             self.delegate.serialize_bool(v)
@@ -1091,22 +1042,21 @@ pub mod singleton_map_recursive {
             T: ?Sized + Serialize,
         {
             self.delegate
-                .serialize_newtype_struct(name, &SingletonMapRecursive { delegate: value })
+                .serialize_newtype_struct(name, &SingletonMapKeys { delegate: value })
         }
 
         fn serialize_newtype_variant<T>(
             self,
-            _name: &'static str,
-            _variant_index: u32,
+            name: &'static str,
+            variant_index: u32,
             variant: &'static str,
             value: &T,
         ) -> Result<Self::Ok, Self::Error>
         where
             T: ?Sized + Serialize,
         {
-            let mut map = self.delegate.serialize_map(Some(1))?;
-            map.serialize_entry(variant, &SingletonMapRecursive { delegate: value })?;
-            map.end()
+            self.delegate
+                .serialize_newtype_variant(name, variant_index, variant, value)
         }
 
         fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
@@ -1118,19 +1068,15 @@ pub mod singleton_map_recursive {
             V: ?Sized + Serialize,
         {
             self.delegate
-                .serialize_some(&SingletonMapRecursive { delegate: value })
+                .serialize_some(&SingletonMapKeys { delegate: value })
         }
 
         fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-            Ok(SingletonMapRecursive {
-                delegate: self.delegate.serialize_seq(len)?,
-            })
+            self.delegate.serialize_seq(len)
         }
 
         fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-            Ok(SingletonMapRecursive {
-                delegate: self.delegate.serialize_tuple(len)?,
-            })
+            self.delegate.serialize_tuple(len)
         }
 
         fn serialize_tuple_struct(
@@ -1138,26 +1084,22 @@ pub mod singleton_map_recursive {
             name: &'static str,
             len: usize,
         ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-            Ok(SingletonMapRecursive {
-                delegate: self.delegate.serialize_tuple_struct(name, len)?,
-            })
+            self.delegate.serialize_tuple_struct(name, len)
         }
 
         fn serialize_tuple_variant(
             self,
-            _name: &'static str,
-            _variant_index: u32,
+            name: &'static str,
+            variant_index: u32,
             variant: &'static str,
             len: usize,
         ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-            let mut map = self.delegate.serialize_map(Some(1))?;
-            map.serialize_key(variant)?;
-            let sequence = Sequence::with_capacity(len);
-            Ok(SerializeTupleVariantAsSingletonMapRecursive { map, sequence })
+            self.delegate
+                .serialize_tuple_variant(name, variant_index, variant, len)
         }
 
         fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-            Ok(SingletonMapRecursive {
+            Ok(SingletonMapKeys {
                 delegate: self.delegate.serialize_map(len)?,
             })
         }
@@ -1167,22 +1109,18 @@ pub mod singleton_map_recursive {
             name: &'static str,
             len: usize,
         ) -> Result<Self::SerializeStruct, Self::Error> {
-            Ok(SingletonMapRecursive {
-                delegate: self.delegate.serialize_struct(name, len)?,
-            })
+            self.delegate.serialize_struct(name, len)
         }
 
         fn serialize_struct_variant(
             self,
-            _name: &'static str,
-            _variant_index: u32,
+            name: &'static str,
+            variant_index: u32,
             variant: &'static str,
             len: usize,
         ) -> Result<Self::SerializeStructVariant, Self::Error> {
-            let mut map = self.delegate.serialize_map(Some(1))?;
-            map.serialize_key(variant)?;
-            let mapping = Mapping::with_capacity(len);
-            Ok(SerializeStructVariantAsSingletonMapRecursive { map, mapping })
+            self.delegate
+                .serialize_struct_variant(name, variant_index, variant, len)
         }
 
         fn collect_str<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
@@ -1197,59 +1135,34 @@ pub mod singleton_map_recursive {
         }
     }
 
-    impl<D> SerializeSeq for SingletonMapRecursive<D>
+    impl<D> SerializeMap for SingletonMapKeys<D>
     where
-        D: SerializeSeq,
+        D: SerializeMap,
     {
         type Ok = D::Ok;
         type Error = D::Error;
 
-        fn serialize_element<T>(&mut self, elem: &T) -> Result<(), Self::Error>
+        fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
         where
-            T: ?Sized + ser::Serialize,
+            T: ?Sized + Serialize,
         {
-            self.delegate
-                .serialize_element(&SingletonMapRecursive { delegate: elem })
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            self.delegate.end()
+            self.delegate.serialize_key(&FlowKey { delegate: key })
         }
-    }
 
-    impl<D> SerializeTuple for SingletonMapRecursive<D>
-    where
-        D: SerializeTuple,
-    {
-        type Ok = D::Ok;
-        type Error = D::Error;
-
-        fn serialize_element<T>(&mut self, elem: &T) -> Result<(), Self::Error>
+        fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
         where
-            T: ?Sized + ser::Serialize,
+            T: ?Sized + Serialize,
         {
-            self.delegate
-                .serialize_element(&SingletonMapRecursive { delegate: elem })
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            self.delegate.end()
+            self.delegate.serialize_value(value)
         }
-    }
-
-    impl<D> SerializeTupleStruct for SingletonMapRecursive<D>
-    where
-        D: SerializeTupleStruct,
-    {
-        type Ok = D::Ok;
-        type Error = D::Error;
 
-        fn serialize_field<V>(&mut self, value: &V) -> Result<(), Self::Error>
+        fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Self::Error>
         where
-            V: ?Sized + ser::Serialize,
+            K: ?Sized + Serialize,
+            V: ?Sized + Serialize,
         {
             self.delegate
-                .serialize_field(&SingletonMapRecursive { delegate: value })
+                .serialize_entry(&FlowKey { delegate: key }, value)
         }
 
         fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -1257,283 +1170,164 @@ pub mod singleton_map_recursive {
         }
     }
 
-    struct SerializeTupleVariantAsSingletonMapRecursive<M> {
-        map: M,
-        sequence: Sequence,
+    // A key as a singleton map, which serde_yaml writes in flow style, and
+    // which other serializers see as a newtype struct around the map.
+    struct FlowKey<D> {
+        delegate: D,
     }
 
-    impl<M> SerializeTupleVariant for SerializeTupleVariantAsSingletonMapRecursive<M>
+    impl<D> Serialize for FlowKey<D>
     where
-        M: SerializeMap,
+        D: Serialize,
     {
-        type Ok = M::Ok;
-        type Error = M::Error;
-
-        fn serialize_field<T>(&mut self, field: &T) -> Result<(), Self::Error>
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
-            T: ?Sized + Serialize,
+            S: Serializer,
         {
-            let value = field
-                .serialize(SingletonMapRecursive {
-                    delegate: crate::value::Serializer,
-                })
-                .map_err(ser::Error::custom)?;
-            self.sequence.push(value);
-            Ok(())
-        }
-
-        fn end(mut self) -> Result<Self::Ok, Self::Error> {
-            self.map.serialize_value(&self.sequence)?;
-            self.map.end()
+            let key = SingletonMap {
+                delegate: &self.delegate,
+            };
+            serializer.serialize_newtype_struct(ser::FLOW, &key)
         }
     }
 
-    impl<D> SerializeMap for SingletonMapRecursive<D>
+    impl<'de, D> Deserializer<'de> for SingletonMapKeys<D>
     where
-        D: SerializeMap,
+        D: Deserializer<'de>,
     {
-        type Ok = D::Ok;
         type Error = D::Error;
 
-        fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            T: ?Sized + ser::Serialize,
+            V: Visitor<'de>,
         {
-            self.delegate
-                .serialize_key(&SingletonMapRecursive { delegate: key })
+            self.delegate.deserialize_any(visitor)
         }
 
-        fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+        fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            T: ?Sized + ser::Serialize,
+            V: Visitor<'de>,
         {
-            self.delegate
-                .serialize_value(&SingletonMapRecursive { delegate: value })
+            self.delegate.deserialize_bool(visitor)
         }
 
-        fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Self::Error>
+        fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            K: ?Sized + ser::Serialize,
-            V: ?Sized + ser::Serialize,
+            V: Visitor<'de>,
         {
-            self.delegate.serialize_entry(
-                &SingletonMapRecursive { delegate: key },
-                &SingletonMapRecursive { delegate: value },
-            )
+            self.delegate.deserialize_i8(visitor)
         }
 
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            self.delegate.end()
-        }
-    }
-
-    impl<D> SerializeStruct for SingletonMapRecursive<D>
-    where
-        D: SerializeStruct,
-    {
-        type Ok = D::Ok;
-        type Error = D::Error;
-
-        fn serialize_field<V>(&mut self, key: &'static str, value: &V) -> Result<(), Self::Error>
-        where
-            V: ?Sized + ser::Serialize,
-        {
-            self.delegate
-                .serialize_field(key, &SingletonMapRecursive { delegate: value })
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            self.delegate.end()
-        }
-    }
-
-    struct SerializeStructVariantAsSingletonMapRecursive<M> {
-        map: M,
-        mapping: Mapping,
-    }
-
-    impl<M> SerializeStructVariant for SerializeStructVariantAsSingletonMapRecursive<M>
-    where
-        M: SerializeMap,
-    {
-        type Ok = M::Ok;
-        type Error = M::Error;
-
-        fn serialize_field<T>(&mut self, name: &'static str, field: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            let value = field
-                .serialize(SingletonMapRecursive {
-                    delegate: crate::value::Serializer,
-                })
-                .map_err(ser::Error::custom)?;
-            self.mapping.insert(Value::String(name.to_owned()), value);
-            Ok(())
-        }
-
-        fn end(mut self) -> Result<Self::Ok, Self::Error> {
-            self.map.serialize_value(&self.mapping)?;
-            self.map.end()
-        }
-    }
-
-    impl<'de, D> Deserializer<'de> for SingletonMapRecursive<D>
-    where
-        D: Deserializer<'de>,
-    {
-        type Error = D::Error;
-
-        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-        where
-            V: Visitor<'de>,
-        {
-            self.delegate
-                .deserialize_any(SingletonMapRecursive { delegate: visitor })
-        }
-
-        fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-        where
-            V: Visitor<'de>,
-        {
-            self.delegate
-                .deserialize_bool(SingletonMapRecursive { delegate: visitor })
-        }
-
-        fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-        where
-            V: Visitor<'de>,
-        {
-            self.delegate
-                .deserialize_i8(SingletonMapRecursive { delegate: visitor })
-        }
-
-        fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-        where
-            V: Visitor<'de>,
-        {
-            self.delegate
-                .deserialize_i16(SingletonMapRecursive { delegate: visitor })
+        fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_i16(visitor)
         }
 
         fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_i32(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_i32(visitor)
         }
 
         fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_i64(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_i64(visitor)
         }
 
         fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_i128(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_i128(visitor)
         }
 
         fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_u8(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_u8(visitor)
         }
 
         fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_u16(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_u16(visitor)
         }
 
         fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_u32(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_u32(visitor)
         }
 
         fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_u64(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_u64(visitor)
         }
 
         fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_u128(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_u128(visitor)
         }
 
         fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_f32(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_f32(visitor)
         }
 
         fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_f64(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_f64(visitor)
         }
 
         fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_char(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_char(visitor)
         }
 
         fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_str(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_str(visitor)
         }
 
         fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_string(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_string(visitor)
         }
 
         fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_bytes(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_bytes(visitor)
         }
 
         fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_byte_buf(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_byte_buf(visitor)
         }
 
         fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -1541,18 +1335,14 @@ pub mod singleton_map_recursive {
             V: Visitor<'de>,
         {
             self.delegate
-                .deserialize_option(SingletonMapRecursiveAsEnum {
-                    name: "",
-                    delegate: visitor,
-                })
+                .deserialize_option(SingletonMapKeys { delegate: visitor })
         }
 
         fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_unit(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_unit(visitor)
         }
 
         fn deserialize_unit_struct<V>(
@@ -1563,8 +1353,7 @@ pub mod singleton_map_recursive {
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_unit_struct(name, SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_unit_struct(name, visitor)
         }
 
         fn deserialize_newtype_struct<V>(
@@ -1576,23 +1365,21 @@ pub mod singleton_map_recursive {
             V: Visitor<'de>,
         {
             self.delegate
-                .deserialize_newtype_struct(name, SingletonMapRecursive { delegate: visitor })
+                .deserialize_newtype_struct(name, SingletonMapKeys { delegate: visitor })
         }
 
         fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_seq(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_seq(visitor)
         }
 
         fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_tuple(len, SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_tuple(len, visitor)
         }
 
         fn deserialize_tuple_struct<V>(
@@ -1604,11 +1391,7 @@ pub mod singleton_map_recursive {
         where
             V: Visitor<'de>,
         {
-            self.delegate.deserialize_tuple_struct(
-                name,
-                len,
-                SingletonMapRecursive { delegate: visitor },
-            )
+            self.delegate.deserialize_tuple_struct(name, len, visitor)
         }
 
         fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -1616,7 +1399,7 @@ pub mod singleton_map_recursive {
             V: Visitor<'de>,
         {
             self.delegate
-                .deserialize_map(SingletonMapRecursive { delegate: visitor })
+                .deserialize_map(SingletonMapKeys { delegate: visitor })
         }
 
         fn deserialize_struct<V>(
@@ -1628,42 +1411,33 @@ pub mod singleton_map_recursive {
         where
             V: Visitor<'de>,
         {
-            self.delegate.deserialize_struct(
-                name,
-                fields,
-                SingletonMapRecursive { delegate: visitor },
-            )
+            self.delegate.deserialize_struct(name, fields, visitor)
         }
 
         fn deserialize_enum<V>(
             self,
             name: &'static str,
-            _variants: &'static [&'static str],
+            variants: &'static [&'static str],
             visitor: V,
         ) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate.deserialize_any(SingletonMapRecursiveAsEnum {
-                name,
-                delegate: visitor,
-            })
+            self.delegate.deserialize_enum(name, variants, visitor)
         }
 
         fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_identifier(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_identifier(visitor)
         }
 
         fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            self.delegate
-                .deserialize_ignored_any(SingletonMapRecursive { delegate: visitor })
+            self.delegate.deserialize_ignored_any(visitor)
         }
 
         fn is_human_readable(&self) -> bool {
@@ -1671,7 +1445,7 @@ pub mod singleton_map_recursive {
         }
     }
 
-    impl<'de, V> Visitor<'de> for SingletonMapRecursive<V>
+    impl<'de, V> Visitor<'de> for SingletonMapKeys<V>
     where
         V: Visitor<'de>,
     {
@@ -1681,255 +1455,3322 @@ pub mod singleton_map_recursive {
             self.delegate.expecting(formatter)
         }
 
-        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+        fn visit_none<E>(self) -> Result<Self::Value, E>
         where
             E: de::Error,
         {
-            self.delegate.visit_bool(v)
+            self.delegate.visit_none()
         }
 
-        fn visit_i8<E>(self, v: i8) -> Result<Self::Value, E>
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
         where
-            E: de::Error,
+            D: Deserializer<'de>,
         {
-            self.delegate.visit_i8(v)
+            self.delegate.visit_some(SingletonMapKeys {
+                delegate: deserializer,
+            })
         }
 
-        fn visit_i16<E>(self, v: i16) -> Result<Self::Value, E>
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
         where
             E: de::Error,
         {
-            self.delegate.visit_i16(v)
+            self.delegate.visit_unit()
         }
 
-        fn visit_i32<E>(self, v: i32) -> Result<Self::Value, E>
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
         where
-            E: de::Error,
+            D: Deserializer<'de>,
         {
-            self.delegate.visit_i32(v)
+            self.delegate.visit_newtype_struct(SingletonMapKeys {
+                delegate: deserializer,
+            })
         }
 
-        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
         where
-            E: de::Error,
+            A: MapAccess<'de>,
         {
-            self.delegate.visit_i64(v)
+            self.delegate.visit_map(SingletonMapKeysAccess {
+                delegate: map,
+                keys: HashSet::new(),
+            })
         }
+    }
 
-        fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate.visit_i128(v)
-        }
+    struct SingletonMapKeysAccess<M> {
+        delegate: M,
+        keys: HashSet<Value>,
+    }
 
-        fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate.visit_u8(v)
-        }
+    impl<'de, M> MapAccess<'de> for SingletonMapKeysAccess<M>
+    where
+        M: MapAccess<'de>,
+    {
+        type Error = M::Error;
 
-        fn visit_u16<E>(self, v: u16) -> Result<Self::Value, E>
+        fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
         where
-            E: de::Error,
+            K: DeserializeSeed<'de>,
         {
-            self.delegate.visit_u16(v)
+            // Keys are buffered so that ones which only collide after
+            // parsing, such as `{Id: 1}` and `{Id: 0x1}`, can be detected.
+            let key = match self.delegate.next_key::<Value>()? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            if !self.keys.insert(key.clone()) {
+                return Err(de::Error::custom(DuplicateKeyError { key: &key }));
+            }
+            seed.deserialize(SingletonMap { delegate: key })
+                .map(Some)
+                .map_err(de::Error::custom)
         }
 
-        fn visit_u32<E>(self, v: u32) -> Result<Self::Value, E>
+        fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
         where
-            E: de::Error,
+            V: DeserializeSeed<'de>,
         {
-            self.delegate.visit_u32(v)
+            self.delegate.next_value_seed(seed)
         }
 
-        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate.visit_u64(v)
+        fn size_hint(&self) -> Option<usize> {
+            self.delegate.size_hint()
         }
+    }
 
-        fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate.visit_u128(v)
-        }
+    struct DuplicateKeyError<'a> {
+        key: &'a Value,
+    }
 
-        fn visit_f32<E>(self, v: f32) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate.visit_f32(v)
+    impl<'a> Display for DuplicateKeyError<'a> {
+        fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            match crate::ser::to_flow_string(self.key) {
+                Ok(rendered) => write!(formatter, "duplicate entry with key `{}`", rendered),
+                Err(_) => formatter.write_str("duplicate entry in YAML map"),
+            }
         }
+    }
+}
 
-        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate.visit_f64(v)
+/// Apply [`singleton_map`] to *all* enums contained within the data structure.
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// enum Enum {
+///     Int(i32),
+/// }
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Inner {
+///     a: Enum,
+///     bs: Vec<Enum>,
+/// }
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Outer {
+///     tagged_style: Inner,
+///
+///     #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+///     singleton_map_style: Inner,
+/// }
+///
+/// fn main() {
+///     let object = Outer {
+///         tagged_style: Inner {
+///             a: Enum::Int(0),
+///             bs: vec![Enum::Int(1)],
+///         },
+///         singleton_map_style: Inner {
+///             a: Enum::Int(2),
+///             bs: vec![Enum::Int(3)],
+///         },
+///     };
+///
+///     let yaml = serde_yaml::to_string(&object).unwrap();
+///     print!("{}", yaml);
+///
+///     let deserialized: Outer = serde_yaml::from_str(&yaml).unwrap();
+///     assert_eq!(object, deserialized);
+/// }
+/// ```
+///
+/// The serialized output is:
+///
+/// ```yaml
+/// tagged_style:
+///   a: !Int 0
+///   bs:
+///   - !Int 1
+/// singleton_map_style:
+///   a:
+///     Int: 2
+///   bs:
+///   - Int: 3
+/// ```
+///
+/// This module can also be used for the top-level serializer or deserializer
+/// call, without `serde(with = …)`, as follows.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde::{Deserialize, Serialize};
+/// #
+/// # #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// # enum Enum {
+/// #     Int(i32),
+/// # }
+/// #
+/// # #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// # struct Inner {
+/// #     a: Enum,
+/// #     bs: Vec<Enum>,
+/// # }
+/// #
+/// use std::io::{self, Write};
+///
+/// fn main() {
+///     let object = Inner {
+///         a: Enum::Int(0),
+///         bs: vec![Enum::Int(1)],
+///     };
+///
+///     let mut buf = Vec::new();
+///     let mut serializer = serde_yaml::Serializer::new(&mut buf);
+///     serde_yaml::with::singleton_map_recursive::serialize(&object, &mut serializer).unwrap();
+///     io::stdout().write_all(&buf).unwrap();
+///
+///     let deserializer = serde_yaml::Deserializer::from_slice(&buf);
+///     let deserialized: Inner = serde_yaml::with::singleton_map_recursive::deserialize(deserializer).unwrap();
+///     assert_eq!(object, deserialized);
+/// }
+/// ```
+pub mod singleton_map_recursive {
+    use crate::value::{Mapping, Sequence, Value};
+    use serde::de::{
+        self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, MapAccess,
+        SeqAccess, Unexpected, VariantAccess, Visitor,
+    };
+    use serde::ser::{
+        self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer,
+    };
+    use std::fmt::{self, Display};
+
+    #[allow(missing_docs)]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        value.serialize(SingletonMapRecursive {
+            delegate: serializer,
+        })
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(SingletonMapRecursive {
+            delegate: deserializer,
+        })
+    }
+
+    struct SingletonMapRecursive<D> {
+        delegate: D,
+    }
+
+    impl<D> Serialize for SingletonMapRecursive<D>
+    where
+        D: Serialize,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            self.delegate.serialize(SingletonMapRecursive {
+                delegate: serializer,
+            })
+        }
+    }
+
+    impl<D> Serializer for SingletonMapRecursive<D>
+    where
+        D: Serializer,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        type SerializeSeq = SingletonMapRecursive<D::SerializeSeq>;
+        type SerializeTuple = SingletonMapRecursive<D::SerializeTuple>;
+        type SerializeTupleStruct = SingletonMapRecursive<D::SerializeTupleStruct>;
+        type SerializeTupleVariant = SerializeTupleVariantAsSingletonMapRecursive<D::SerializeMap>;
+        type SerializeMap = SingletonMapRecursive<D::SerializeMap>;
+        type SerializeStruct = SingletonMapRecursive<D::SerializeStruct>;
+        type SerializeStructVariant =
+            SerializeStructVariantAsSingletonMapRecursive<D::SerializeMap>;
+
+        fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_bool(v)
+        }
+
+        fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i8(v)
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i16(v)
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i32(v)
+        }
+
+        fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i64(v)
+        }
+
+        fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i128(v)
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u8(v)
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u16(v)
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u32(v)
+        }
+
+        fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u64(v)
+        }
+
+        fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u128(v)
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_f32(v)
+        }
+
+        fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_f64(v)
+        }
+
+        fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_char(v)
+        }
+
+        fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_str(v)
+        }
+
+        fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_bytes(v)
+        }
+
+        fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_unit()
+        }
+
+        fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_unit_struct(name)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+        ) -> Result<Self::Ok, Self::Error> {
+            self.delegate
+                .serialize_unit_variant(name, variant_index, variant)
+        }
+
+        fn serialize_newtype_struct<T>(
+            self,
+            name: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.delegate
+                .serialize_newtype_struct(name, &SingletonMapRecursive { delegate: value })
+        }
+
+        fn serialize_newtype_variant<T>(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            let mut map = self.delegate.serialize_map(Some(1))?;
+            map.serialize_entry(variant, &SingletonMapRecursive { delegate: value })?;
+            map.end()
+        }
+
+        fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_none()
+        }
+
+        fn serialize_some<V>(self, value: &V) -> Result<Self::Ok, Self::Error>
+        where
+            V: ?Sized + Serialize,
+        {
+            self.delegate
+                .serialize_some(&SingletonMapRecursive { delegate: value })
+        }
+
+        fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+            Ok(SingletonMapRecursive {
+                delegate: self.delegate.serialize_seq(len)?,
+            })
+        }
+
+        fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+            Ok(SingletonMapRecursive {
+                delegate: self.delegate.serialize_tuple(len)?,
+            })
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+            Ok(SingletonMapRecursive {
+                delegate: self.delegate.serialize_tuple_struct(name, len)?,
+            })
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+            let mut map = self.delegate.serialize_map(Some(1))?;
+            map.serialize_key(variant)?;
+            let sequence = Sequence::with_capacity(len);
+            Ok(SerializeTupleVariantAsSingletonMapRecursive { map, sequence })
+        }
+
+        fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+            Ok(SingletonMapRecursive {
+                delegate: self.delegate.serialize_map(len)?,
+            })
+        }
+
+        fn serialize_struct(
+            self,
+            name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStruct, Self::Error> {
+            Ok(SingletonMapRecursive {
+                delegate: self.delegate.serialize_struct(name, len)?,
+            })
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStructVariant, Self::Error> {
+            let mut map = self.delegate.serialize_map(Some(1))?;
+            map.serialize_key(variant)?;
+            let mapping = Mapping::with_capacity(len);
+            Ok(SerializeStructVariantAsSingletonMapRecursive { map, mapping })
+        }
+
+        fn collect_str<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Display,
+        {
+            self.delegate.collect_str(value)
+        }
+
+        fn is_human_readable(&self) -> bool {
+            self.delegate.is_human_readable()
+        }
+    }
+
+    impl<D> SerializeSeq for SingletonMapRecursive<D>
+    where
+        D: SerializeSeq,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        fn serialize_element<T>(&mut self, elem: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + ser::Serialize,
+        {
+            self.delegate
+                .serialize_element(&SingletonMapRecursive { delegate: elem })
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.end()
+        }
+    }
+
+    impl<D> SerializeTuple for SingletonMapRecursive<D>
+    where
+        D: SerializeTuple,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        fn serialize_element<T>(&mut self, elem: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + ser::Serialize,
+        {
+            self.delegate
+                .serialize_element(&SingletonMapRecursive { delegate: elem })
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.end()
+        }
+    }
+
+    impl<D> SerializeTupleStruct for SingletonMapRecursive<D>
+    where
+        D: SerializeTupleStruct,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        fn serialize_field<V>(&mut self, value: &V) -> Result<(), Self::Error>
+        where
+            V: ?Sized + ser::Serialize,
+        {
+            self.delegate
+                .serialize_field(&SingletonMapRecursive { delegate: value })
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.end()
+        }
+    }
+
+    struct SerializeTupleVariantAsSingletonMapRecursive<M> {
+        map: M,
+        sequence: Sequence,
+    }
+
+    impl<M> SerializeTupleVariant for SerializeTupleVariantAsSingletonMapRecursive<M>
+    where
+        M: SerializeMap,
+    {
+        type Ok = M::Ok;
+        type Error = M::Error;
+
+        fn serialize_field<T>(&mut self, field: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            let value = field
+                .serialize(SingletonMapRecursive {
+                    delegate: crate::value::Serializer,
+                })
+                .map_err(ser::Error::custom)?;
+            self.sequence.push(value);
+            Ok(())
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            self.map.serialize_value(&self.sequence)?;
+            self.map.end()
+        }
+    }
+
+    impl<D> SerializeMap for SingletonMapRecursive<D>
+    where
+        D: SerializeMap,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + ser::Serialize,
+        {
+            self.delegate
+                .serialize_key(&SingletonMapRecursive { delegate: key })
+        }
+
+        fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + ser::Serialize,
+        {
+            self.delegate
+                .serialize_value(&SingletonMapRecursive { delegate: value })
+        }
+
+        fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Self::Error>
+        where
+            K: ?Sized + ser::Serialize,
+            V: ?Sized + ser::Serialize,
+        {
+            self.delegate.serialize_entry(
+                &SingletonMapRecursive { delegate: key },
+                &SingletonMapRecursive { delegate: value },
+            )
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.end()
+        }
+    }
+
+    impl<D> SerializeStruct for SingletonMapRecursive<D>
+    where
+        D: SerializeStruct,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        fn serialize_field<V>(&mut self, key: &'static str, value: &V) -> Result<(), Self::Error>
+        where
+            V: ?Sized + ser::Serialize,
+        {
+            self.delegate
+                .serialize_field(key, &SingletonMapRecursive { delegate: value })
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.end()
+        }
+    }
+
+    struct SerializeStructVariantAsSingletonMapRecursive<M> {
+        map: M,
+        mapping: Mapping,
+    }
+
+    impl<M> SerializeStructVariant for SerializeStructVariantAsSingletonMapRecursive<M>
+    where
+        M: SerializeMap,
+    {
+        type Ok = M::Ok;
+        type Error = M::Error;
+
+        fn serialize_field<T>(&mut self, name: &'static str, field: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            let value = field
+                .serialize(SingletonMapRecursive {
+                    delegate: crate::value::Serializer,
+                })
+                .map_err(ser::Error::custom)?;
+            self.mapping.insert(Value::String(name.to_owned()), value);
+            Ok(())
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            self.map.serialize_value(&self.mapping)?;
+            self.map.end()
+        }
+    }
+
+    impl<'de, D> Deserializer<'de> for SingletonMapRecursive<D>
+    where
+        D: Deserializer<'de>,
+    {
+        type Error = D::Error;
+
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_any(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_bool(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_i8(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_i16(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_i32(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_i64(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_i128(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_u8(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_u16(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_u32(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_u64(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_u128(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_f32(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_f64(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_char(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_str(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_string(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_bytes(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_byte_buf(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_option(SingletonMapRecursiveAsEnum {
+                    name: "",
+                    delegate: visitor,
+                })
+        }
+
+        fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_unit(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_unit_struct<V>(
+            self,
+            name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_unit_struct(name, SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_newtype_struct<V>(
+            self,
+            name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_newtype_struct(name, SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_seq(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_tuple(len, SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_tuple_struct<V>(
+            self,
+            name: &'static str,
+            len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_tuple_struct(
+                name,
+                len,
+                SingletonMapRecursive { delegate: visitor },
+            )
+        }
+
+        fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_map(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_struct<V>(
+            self,
+            name: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_struct(
+                name,
+                fields,
+                SingletonMapRecursive { delegate: visitor },
+            )
+        }
+
+        fn deserialize_enum<V>(
+            self,
+            name: &'static str,
+            _variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_any(SingletonMapRecursiveAsEnum {
+                name,
+                delegate: visitor,
+            })
+        }
+
+        fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_identifier(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_ignored_any(SingletonMapRecursive { delegate: visitor })
+        }
+
+        fn is_human_readable(&self) -> bool {
+            self.delegate.is_human_readable()
+        }
+    }
+
+    impl<'de, V> Visitor<'de> for SingletonMapRecursive<V>
+    where
+        V: Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            self.delegate.expecting(formatter)
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_bool(v)
+        }
+
+        fn visit_i8<E>(self, v: i8) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_i8(v)
+        }
+
+        fn visit_i16<E>(self, v: i16) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_i16(v)
+        }
+
+        fn visit_i32<E>(self, v: i32) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_i32(v)
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_i64(v)
+        }
+
+        fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_i128(v)
+        }
+
+        fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_u8(v)
+        }
+
+        fn visit_u16<E>(self, v: u16) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_u16(v)
+        }
+
+        fn visit_u32<E>(self, v: u32) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_u32(v)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_u64(v)
+        }
+
+        fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_u128(v)
+        }
+
+        fn visit_f32<E>(self, v: f32) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_f32(v)
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_f64(v)
+        }
+
+        fn visit_char<E>(self, v: char) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_char(v)
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_str(v)
+        }
+
+        fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_borrowed_str(v)
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_string(v)
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_bytes(v)
+        }
+
+        fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_borrowed_bytes(v)
+        }
+
+        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_byte_buf(v)
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_none()
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            self.delegate.visit_some(SingletonMapRecursive {
+                delegate: deserializer,
+            })
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_unit()
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            self.delegate.visit_newtype_struct(SingletonMapRecursive {
+                delegate: deserializer,
+            })
+        }
+
+        fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            self.delegate
+                .visit_seq(SingletonMapRecursive { delegate: seq })
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            self.delegate
+                .visit_map(SingletonMapRecursive { delegate: map })
+        }
+    }
+
+    impl<'de, T> DeserializeSeed<'de> for SingletonMapRecursive<T>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        type Value = T::Value;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            self.delegate.deserialize(SingletonMapRecursive {
+                delegate: deserializer,
+            })
+        }
+    }
+
+    impl<'de, S> SeqAccess<'de> for SingletonMapRecursive<S>
+    where
+        S: SeqAccess<'de>,
+    {
+        type Error = S::Error;
+
+        fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+        where
+            T: DeserializeSeed<'de>,
+        {
+            self.delegate
+                .next_element_seed(SingletonMapRecursive { delegate: seed })
+        }
+    }
+
+    impl<'de, M> MapAccess<'de> for SingletonMapRecursive<M>
+    where
+        M: MapAccess<'de>,
+    {
+        type Error = M::Error;
+
+        fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+        where
+            K: DeserializeSeed<'de>,
+        {
+            self.delegate
+                .next_key_seed(SingletonMapRecursive { delegate: seed })
+        }
+
+        fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+        where
+            V: DeserializeSeed<'de>,
+        {
+            self.delegate
+                .next_value_seed(SingletonMapRecursive { delegate: seed })
+        }
+    }
+
+    struct SingletonMapRecursiveAsEnum<D> {
+        name: &'static str,
+        delegate: D,
+    }
+
+    impl<'de, V> Visitor<'de> for SingletonMapRecursiveAsEnum<V>
+    where
+        V: Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            self.delegate.expecting(formatter)
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_enum(de::value::StrDeserializer::new(v))
+        }
+
+        fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate
+                .visit_enum(de::value::BorrowedStrDeserializer::new(v))
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate
+                .visit_enum(de::value::StringDeserializer::new(v))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_none()
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            self.delegate.visit_some(SingletonMapRecursive {
+                delegate: deserializer,
+            })
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_unit()
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            self.delegate.visit_enum(SingletonMapRecursiveAsEnum {
+                name: self.name,
+                delegate: map,
+            })
+        }
+    }
+
+    impl<'de, D> EnumAccess<'de> for SingletonMapRecursiveAsEnum<D>
+    where
+        D: MapAccess<'de>,
+    {
+        type Error = D::Error;
+        type Variant = Self;
+
+        fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+        where
+            V: DeserializeSeed<'de>,
+        {
+            match self.delegate.next_key_seed(seed)? {
+                Some(value) => Ok((value, self)),
+                None => Err(de::Error::invalid_value(
+                    Unexpected::Map,
+                    &"map with a single key",
+                )),
+            }
+        }
+    }
+
+    impl<'de, D> VariantAccess<'de> for SingletonMapRecursiveAsEnum<D>
+    where
+        D: MapAccess<'de>,
+    {
+        type Error = D::Error;
+
+        fn unit_variant(self) -> Result<(), Self::Error> {
+            Err(de::Error::invalid_type(Unexpected::Map, &"unit variant"))
+        }
+
+        fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Self::Error>
+        where
+            T: DeserializeSeed<'de>,
+        {
+            let value = self
+                .delegate
+                .next_value_seed(SingletonMapRecursive { delegate: seed })?;
+            match self.delegate.next_key()? {
+                None => Ok(value),
+                Some(IgnoredAny) => Err(de::Error::invalid_value(
+                    Unexpected::Map,
+                    &"map with a single key",
+                )),
+            }
+        }
+
+        fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            let value = self.delegate.next_value_seed(TupleVariantSeed {
+                len,
+                visitor: SingletonMapRecursive { delegate: visitor },
+            })?;
+            match self.delegate.next_key()? {
+                None => Ok(value),
+                Some(IgnoredAny) => Err(de::Error::invalid_value(
+                    Unexpected::Map,
+                    &"map with a single key",
+                )),
+            }
+        }
+
+        fn struct_variant<V>(
+            mut self,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            let value = self.delegate.next_value_seed(StructVariantSeed {
+                name: self.name,
+                fields,
+                visitor: SingletonMapRecursive { delegate: visitor },
+            })?;
+            match self.delegate.next_key()? {
+                None => Ok(value),
+                Some(IgnoredAny) => Err(de::Error::invalid_value(
+                    Unexpected::Map,
+                    &"map with a single key",
+                )),
+            }
+        }
+    }
+
+    struct TupleVariantSeed<V> {
+        len: usize,
+        visitor: V,
+    }
+
+    impl<'de, V> DeserializeSeed<'de> for TupleVariantSeed<V>
+    where
+        V: Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_tuple(self.len, self.visitor)
+        }
+    }
+
+    struct StructVariantSeed<V> {
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    }
+
+    impl<'de, V> DeserializeSeed<'de> for StructVariantSeed<V>
+    where
+        V: Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_struct(self.name, self.fields, self.visitor)
+        }
+    }
+}
+
+/// Serialize/deserialize an `Option<Option<T>>` struct field so that `None`
+/// and `Some(None)` stay distinct.
+///
+/// YAML has a single null, so without this both `None` and `Some(None)` are
+/// written as `null` and read back as `None`. With `nested_option`, `None` is
+/// represented by leaving the field out of the mapping, `Some(None)` by
+/// `null`, and `Some(Some(value))` by the value. The field needs
+/// `#[serde(default)]` so that a missing key reads as `None`, and
+/// `skip_serializing_if` so that `None` is left out when serializing.
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Patch {
+///     #[serde(
+///         default,
+///         skip_serializing_if = "Option::is_none",
+///         with = "serde_yaml::with::nested_option"
+///     )]
+///     timeout: Option<Option<u32>>,
+/// }
+///
+/// fn main() {
+///     for (patch, yaml) in [
+///         (Patch { timeout: None }, "{}\n"),
+///         (Patch { timeout: Some(None) }, "timeout: null\n"),
+///         (Patch { timeout: Some(Some(30)) }, "timeout: 30\n"),
+///     ] {
+///         assert_eq!(serde_yaml::to_string(&patch).unwrap(), yaml);
+///         assert_eq!(serde_yaml::from_str::<Patch>(yaml).unwrap(), patch);
+///     }
+/// }
+/// ```
+pub mod nested_option {
+    use serde::de::{Deserialize, Deserializer};
+    use serde::ser::{Serialize, Serializer};
+
+    #[allow(missing_docs)]
+    pub fn serialize<T, S>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        match value {
+            Some(inner) => inner.serialize(serializer),
+            // Only reachable without skip_serializing_if, in which case this
+            // reads back as Some(None).
+            None => serializer.serialize_none(),
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
+
+/// Serialize a set, or any other collection whose order does not matter, as a
+/// sequence sorted by its elements.
+///
+/// Iterating a `HashSet` gives a different order from one run to the next,
+/// and the serializer has no way to tell such a sequence apart from a `Vec`
+/// whose order matters, so even with
+/// [`SerializerBuilder::deterministic`][crate::SerializerBuilder::deterministic]
+/// its elements are written as they come. This sorts them first.
+/// Deserializing is unchanged.
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+/// use std::collections::HashSet;
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Rule {
+///     #[serde(with = "serde_yaml::with::sorted_set")]
+///     verbs: HashSet<String>,
+/// }
+///
+/// fn main() {
+///     let verbs = ["watch", "get", "list"];
+///     let rule = Rule {
+///         verbs: verbs.iter().map(|verb| verb.to_string()).collect(),
+///     };
+///
+///     let yaml = serde_yaml::to_string(&rule).unwrap();
+///     assert_eq!(yaml, "verbs:\n- get\n- list\n- watch\n");
+///     assert_eq!(serde_yaml::from_str::<Rule>(&yaml).unwrap(), rule);
+/// }
+/// ```
+pub mod sorted_set {
+    use serde::de::{Deserialize, Deserializer};
+    use serde::ser::{Serialize, Serializer};
+
+    #[allow(missing_docs)]
+    pub fn serialize<'a, T, E, S>(value: &'a T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a T: IntoIterator<Item = &'a E>,
+        E: Serialize + Ord + 'a,
+        S: Serializer,
+    {
+        let mut elements: Vec<&E> = value.into_iter().collect();
+        elements.sort();
+        serializer.collect_seq(elements)
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
+
+/// Serialize/deserialize a struct wrapped in a YAML map containing one entry
+/// whose key is the name of the struct.
+///
+/// Some files put all of their content under a fixed top-level key, like
+/// `Service:` for a struct named `Service`. With `named_struct` the struct is
+/// read from and written to the value of that one entry. Deserializing fails
+/// if the key is a different name or the map has any other entry. The name is
+/// compared exactly; [`named_struct::ignore_case`] also accepts
+/// it in a different case, like `service:`.
+///
+/// The name is the one Serde knows the struct by, which is the Rust name
+/// unless changed with `#[serde(rename = "…")]`. Types other than structs
+/// are not affected.
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Service {
+///     image: String,
+/// }
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Stack {
+///     #[serde(with = "serde_yaml::with::named_struct")]
+///     web: Service,
+/// }
+///
+/// fn main() {
+///     let yaml = "web:\n  Service:\n    image: nginx\n";
+///     let stack: Stack = serde_yaml::from_str(yaml).unwrap();
+///     assert_eq!(stack.web.image, "nginx");
+///     assert_eq!(serde_yaml::to_string(&stack).unwrap(), yaml);
+/// }
+/// ```
+pub mod named_struct {
+    use crate::value::{Mapping, Value};
+    use serde::de::{
+        self, Deserialize, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Unexpected,
+        Visitor,
+    };
+    use serde::ser::{self, Serialize, SerializeMap, SerializeStruct, Serializer};
+    use std::fmt::{self, Display};
+
+    #[allow(missing_docs)]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        value.serialize(NamedStruct {
+            delegate: serializer,
+            ignore_case: false,
+        })
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(NamedStruct {
+            delegate: deserializer,
+            ignore_case: false,
+        })
+    }
+
+    /// Like [`named_struct`][super::named_struct], but the key may differ
+    /// from the name of the struct in ASCII case. Serializing writes the
+    /// name as it is.
+    pub mod ignore_case {
+        use super::NamedStruct;
+        use serde::de::{Deserialize, Deserializer};
+        use serde::ser::{Serialize, Serializer};
+
+        #[allow(missing_docs)]
+        pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: Serialize,
+            S: Serializer,
+        {
+            value.serialize(NamedStruct {
+                delegate: serializer,
+                ignore_case: true,
+            })
+        }
+
+        #[allow(missing_docs)]
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+        where
+            T: Deserialize<'de>,
+            D: Deserializer<'de>,
+        {
+            T::deserialize(NamedStruct {
+                delegate: deserializer,
+                ignore_case: true,
+            })
+        }
+    }
+
+    struct NamedStruct<D> {
+        delegate: D,
+        ignore_case: bool,
+    }
+
+    impl<D> Serialize for NamedStruct<D>
+    where
+        D: Serialize,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            self.delegate.serialize(NamedStruct {
+                delegate: serializer,
+                ignore_case: self.ignore_case,
+            })
+        }
+    }
+
+    impl<D> Serializer for NamedStruct<D>
+    where
+        D: Serializer,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        type SerializeSeq = D::SerializeSeq;
+        type SerializeTuple = D::SerializeTuple;
+        type SerializeTupleStruct = D::SerializeTupleStruct;
+        type SerializeTupleVariant = D::SerializeTupleVariant;
+        type SerializeMap = D::SerializeMap;
+        type SerializeStruct = SerializeStructAsNamedMap<D::SerializeMap>;
+        type SerializeStructVariant = D::SerializeStructVariant;
+
+        fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_bool(v)
+        }
+
+        fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i8(v)
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i16(v)
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i32(v)
+        }
+
+        fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i64(v)
+        }
+
+        fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i128(v)
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u8(v)
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u16(v)
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u32(v)
+        }
+
+        fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u64(v)
+        }
+
+        fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u128(v)
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_f32(v)
+        }
+
+        fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_f64(v)
+        }
+
+        fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_char(v)
+        }
+
+        fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_str(v)
+        }
+
+        fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_bytes(v)
+        }
+
+        fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_unit()
+        }
+
+        fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_unit_struct(name)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+        ) -> Result<Self::Ok, Self::Error> {
+            self.delegate
+                .serialize_unit_variant(name, variant_index, variant)
+        }
+
+        fn serialize_newtype_struct<T>(
+            self,
+            name: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.delegate.serialize_newtype_struct(name, value)
+        }
+
+        fn serialize_newtype_variant<T>(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.delegate
+                .serialize_newtype_variant(name, variant_index, variant, value)
+        }
+
+        fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_none()
+        }
+
+        fn serialize_some<V>(self, value: &V) -> Result<Self::Ok, Self::Error>
+        where
+            V: ?Sized + Serialize,
+        {
+            self.delegate.serialize_some(&NamedStruct {
+                delegate: value,
+                ignore_case: self.ignore_case,
+            })
+        }
+
+        fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+            self.delegate.serialize_seq(len)
+        }
+
+        fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+            self.delegate.serialize_tuple(len)
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+            self.delegate.serialize_tuple_struct(name, len)
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+            self.delegate
+                .serialize_tuple_variant(name, variant_index, variant, len)
+        }
+
+        fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+            self.delegate.serialize_map(len)
+        }
+
+        fn serialize_struct(
+            self,
+            name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStruct, Self::Error> {
+            let mut map = self.delegate.serialize_map(Some(1))?;
+            map.serialize_key(name)?;
+            let mapping = Mapping::with_capacity(len);
+            Ok(SerializeStructAsNamedMap { map, mapping })
+        }
+
+        fn serialize_struct_variant(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStructVariant, Self::Error> {
+            self.delegate
+                .serialize_struct_variant(name, variant_index, variant, len)
+        }
+
+        fn collect_str<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Display,
+        {
+            self.delegate.collect_str(value)
+        }
+
+        fn is_human_readable(&self) -> bool {
+            self.delegate.is_human_readable()
+        }
+    }
+
+    struct SerializeStructAsNamedMap<M> {
+        map: M,
+        mapping: Mapping,
+    }
+
+    impl<M> SerializeStruct for SerializeStructAsNamedMap<M>
+    where
+        M: SerializeMap,
+    {
+        type Ok = M::Ok;
+        type Error = M::Error;
+
+        fn serialize_field<T>(&mut self, name: &'static str, field: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            let value = field
+                .serialize(crate::value::Serializer)
+                .map_err(ser::Error::custom)?;
+            self.mapping.insert(Value::String(name.to_owned()), value);
+            Ok(())
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            self.map.serialize_value(&self.mapping)?;
+            self.map.end()
+        }
+    }
+
+    impl<'de, D> Deserializer<'de> for NamedStruct<D>
+    where
+        D: Deserializer<'de>,
+    {
+        type Error = D::Error;
+
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_any(visitor)
+        }
+
+        fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_bool(visitor)
+        }
+
+        fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_i8(visitor)
+        }
+
+        fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_i16(visitor)
+        }
+
+        fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_i32(visitor)
+        }
+
+        fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_i64(visitor)
+        }
+
+        fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_i128(visitor)
+        }
+
+        fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u8(visitor)
+        }
+
+        fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u16(visitor)
+        }
+
+        fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u32(visitor)
+        }
+
+        fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u64(visitor)
+        }
+
+        fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u128(visitor)
+        }
+
+        fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_f32(visitor)
+        }
+
+        fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_f64(visitor)
+        }
+
+        fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_char(visitor)
+        }
+
+        fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_str(visitor)
+        }
+
+        fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_string(visitor)
+        }
+
+        fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_bytes(visitor)
+        }
+
+        fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_byte_buf(visitor)
+        }
+
+        fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_option(OptionAsNamedStruct {
+                delegate: visitor,
+                ignore_case: self.ignore_case,
+            })
+        }
+
+        fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_unit(visitor)
+        }
+
+        fn deserialize_unit_struct<V>(
+            self,
+            name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_unit_struct(name, visitor)
+        }
+
+        fn deserialize_newtype_struct<V>(
+            self,
+            name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_newtype_struct(name, visitor)
+        }
+
+        fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_seq(visitor)
+        }
+
+        fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_tuple(len, visitor)
+        }
+
+        fn deserialize_tuple_struct<V>(
+            self,
+            name: &'static str,
+            len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_tuple_struct(name, len, visitor)
+        }
+
+        fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_map(visitor)
+        }
+
+        fn deserialize_struct<V>(
+            self,
+            name: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_map(NamedMapAsStruct {
+                name,
+                fields,
+                ignore_case: self.ignore_case,
+                delegate: visitor,
+            })
+        }
+
+        fn deserialize_enum<V>(
+            self,
+            name: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_enum(name, variants, visitor)
+        }
+
+        fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_identifier(visitor)
+        }
+
+        fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_ignored_any(visitor)
+        }
+
+        fn is_human_readable(&self) -> bool {
+            self.delegate.is_human_readable()
+        }
+    }
+
+    struct OptionAsNamedStruct<V> {
+        delegate: V,
+        ignore_case: bool,
+    }
+
+    impl<'de, V> Visitor<'de> for OptionAsNamedStruct<V>
+    where
+        V: Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            self.delegate.expecting(formatter)
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_none()
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            self.delegate.visit_some(NamedStruct {
+                delegate: deserializer,
+                ignore_case: self.ignore_case,
+            })
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.delegate.visit_unit()
+        }
+    }
+
+    struct NamedMapAsStruct<V> {
+        name: &'static str,
+        fields: &'static [&'static str],
+        ignore_case: bool,
+        delegate: V,
+    }
+
+    impl<'de, V> Visitor<'de> for NamedMapAsStruct<V>
+    where
+        V: Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a map with the single key `{}`", self.name)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let key: String = match map.next_key()? {
+                Some(key) => key,
+                None => return Err(de::Error::invalid_length(0, &self)),
+            };
+            let matches = if self.ignore_case {
+                key.eq_ignore_ascii_case(self.name)
+            } else {
+                key == self.name
+            };
+            if !matches {
+                return Err(de::Error::invalid_value(Unexpected::Str(&key), &self));
+            }
+            let value = map.next_value_seed(StructSeed {
+                name: self.name,
+                fields: self.fields,
+                visitor: self.delegate,
+            })?;
+            match map.next_key()? {
+                None => Ok(value),
+                Some(IgnoredAny) => Err(de::Error::invalid_value(
+                    Unexpected::Map,
+                    &"map with a single key",
+                )),
+            }
+        }
+    }
+
+    struct StructSeed<V> {
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    }
+
+    impl<'de, V> DeserializeSeed<'de> for StructSeed<V>
+    where
+        V: Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_struct(self.name, self.fields, self.visitor)
+        }
+    }
+}
+
+/// Deserialize tagged enum variants that the enum does not know into a
+/// catch-all variant named `Unknown`, and serialize that variant back as it
+/// was read.
+///
+/// `#[serde(other)]` only works for a unit variant, so it cannot keep the data
+/// of a variant like `!NewFeature {size: 3}` from a newer version of a file.
+/// With `enum_fallback`, the enum declares a struct variant `Unknown { tag:
+/// String, value: Value }` instead. A variant whose name is not one of the
+/// enum's own, written either with a tag or in the [`singleton_map`] form
+/// `NewFeature: {size: 3}`, is read into `Unknown` with the name as `tag` and
+/// the content as `value`. Serializing `Unknown` writes the content with the
+/// name as its tag again; other variants are serialized as usual.
+///
+/// The field may also be an `Option` of the enum. An enum without an `Unknown`
+/// variant is deserialized as usual.
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+/// use serde_yaml::Value;
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// enum Step {
+///     Run(String),
+///     Unknown { tag: String, value: Value },
+/// }
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Job {
+///     #[serde(with = "serde_yaml::with::enum_fallback")]
+///     step: Step,
+/// }
+///
+/// fn main() {
+///     let yaml = "step: !Cache\n  key: deps\n";
+///     let job: Job = serde_yaml::from_str(yaml).unwrap();
+///     assert_eq!(
+///         job.step,
+///         Step::Unknown {
+///             tag: "Cache".to_owned(),
+///             value: serde_yaml::from_str("key: deps").unwrap(),
+///         },
+///     );
+///     assert_eq!(serde_yaml::to_string(&job).unwrap(), yaml);
+/// }
+/// ```
+pub mod enum_fallback {
+    use crate::error::Error;
+    use crate::value::{Mapping, Tag, TaggedValue, Value};
+    use serde::de::{self, Deserialize, Deserializer, Visitor};
+    use serde::forward_to_deserialize_any;
+    use serde::ser::{self, Serialize, Serializer};
+
+    const UNKNOWN: &str = "Unknown";
+
+    #[allow(missing_docs)]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        let mut value = crate::value::to_value(value).map_err(ser::Error::custom)?;
+        if let Value::Tagged(tagged) = &mut value {
+            if tagged.tag == UNKNOWN {
+                if let Value::Mapping(fields) = &mut tagged.value {
+                    if let (Some(Value::String(tag)), Some(content)) =
+                        (fields.remove("tag"), fields.remove("value"))
+                    {
+                        value = Value::Tagged(Box::new(TaggedValue {
+                            tag: Tag::new(tag),
+                            value: content,
+                        }));
+                    }
+                }
+            }
+        }
+        value.serialize(serializer)
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let variants = variants::<T>();
+        let value = match variants {
+            Some(variants) if variants.contains(&UNKNOWN) => fallback(value, variants),
+            _ => value,
+        };
+        T::deserialize(value).map_err(de::Error::custom)
+    }
+
+    // Rewrites a variant in either form into a tagged value, which is the form
+    // a Value deserializes as an enum, and an unknown one into `Unknown`.
+    fn fallback(value: Value, variants: &[&str]) -> Value {
+        let (tag, content) = match value {
+            Value::Tagged(tagged) => (tagged.tag.string, tagged.value),
+            Value::Mapping(mapping) if mapping.len() == 1 => {
+                match mapping.into_iter().next().unwrap() {
+                    (Value::String(tag), content) => (tag, content),
+                    (Value::Styled(tag), content) => (tag.value, content),
+                    (key, content) => {
+                        let mut mapping = Mapping::new();
+                        mapping.insert(key, content);
+                        return Value::Mapping(mapping);
+                    }
+                }
+            }
+            value => return value,
+        };
+        let name = crate::value::tagged::nobang(&tag);
+        if variants.contains(&name) {
+            return Value::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(tag),
+                value: content,
+            }));
+        }
+        let mut fields = Mapping::new();
+        fields.insert(Value::from("tag"), Value::from(name));
+        fields.insert(Value::from("value"), content);
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new(UNKNOWN),
+            value: Value::Mapping(fields),
+        }))
+    }
+
+    // The variants of `T` if it is an enum, or an Option of one, found by
+    // having it start to deserialize.
+    fn variants<'de, T>() -> Option<&'static [&'static str]>
+    where
+        T: Deserialize<'de>,
+    {
+        let mut variants = None;
+        let _ = T::deserialize(Variants {
+            variants: &mut variants,
+        });
+        variants
+    }
+
+    struct Variants<'a> {
+        variants: &'a mut Option<&'static [&'static str]>,
+    }
+
+    impl<'de, 'a> Deserializer<'de> for Variants<'a> {
+        type Error = Error;
+
+        fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            Err(de::Error::custom("not an enum"))
+        }
+
+        fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            visitor.visit_some(self)
+        }
+
+        fn deserialize_enum<V>(
+            self,
+            _name: &'static str,
+            variants: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            *self.variants = Some(variants);
+            Err(de::Error::custom("not deserializing"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+            map struct identifier ignored_any
+        }
+    }
+}
+
+/// Serialize a field that has its default value as a comment, so that the
+/// entry is there to be changed but has no effect.
+///
+/// For a template of a configuration file, this shows every setting without
+/// fixing the ones that are left at their default: `# max_connections: 100`
+/// rather than leaving out the field or writing `max_connections: 100`. The
+/// comment is at the position of the entry, with the value written as it
+/// would be otherwise, so taking out the `# ` of its lines sets the field.
+///
+/// The default is `T::default()`. For a field with a different default, use
+/// [`default_as_comment::serialize_with_default`] from a function passed to
+/// `serialize_with`. The field needs `#[serde(default)]` so that reading the
+/// output back, which skips the comment, gives the default again.
+///
+/// Only the serializer of this crate writes comments, and only for an entry
+/// of a block mapping whose key is a scalar. A mapping whose entries are all
+/// comments reads back as null. Anywhere else, and with other serializers
+/// including [`to_value`][crate::to_value], the value is written as usual.
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Config {
+///     name: String,
+///     #[serde(default, with = "serde_yaml::with::default_as_comment")]
+///     verbose: bool,
+///     #[serde(default = "default_port", serialize_with = "port")]
+///     port: u16,
+/// }
+///
+/// fn default_port() -> u16 {
+///     8080
+/// }
+///
+/// fn port<S: serde::Serializer>(port: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+///     serde_yaml::with::default_as_comment::serialize_with_default(port, &default_port(), serializer)
+/// }
+///
+/// fn main() {
+///     let config = Config {
+///         name: "web".to_owned(),
+///         verbose: false,
+///         port: 8080,
+///     };
+///
+///     let yaml = serde_yaml::to_string(&config).unwrap();
+///     assert_eq!(yaml, "name: web\n# verbose: false\n# port: 8080\n");
+///     assert_eq!(serde_yaml::from_str::<Config>(&yaml).unwrap(), config);
+/// }
+/// ```
+pub mod default_as_comment {
+    use crate::comments::COMMENT;
+    use serde::de::{Deserialize, Deserializer};
+    use serde::ser::{Serialize, Serializer};
+
+    #[allow(missing_docs)]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + Default + PartialEq,
+        S: Serializer,
+    {
+        serialize_with_default(value, &T::default(), serializer)
+    }
+
+    /// Serializes `value` as a comment if it is equal to `default`, and as
+    /// usual otherwise.
+    pub fn serialize_with_default<T, S>(
+        value: &T,
+        default: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize + PartialEq,
+        S: Serializer,
+    {
+        if value == default {
+            serializer.serialize_newtype_struct(COMMENT, value)
+        } else {
+            value.serialize(serializer)
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
+
+/// Serialize an `Rc` or `Arc` with an anchor, so that other `Rc`s or `Arc`s
+/// pointing to the same value are written as aliases of it.
+///
+/// Serde writes the value of an `Rc` as if it were not shared, so a value that
+/// many others point to is written out again for each of them. With `shared`,
+/// the serializer of this crate writes the first occurrence of such a value
+/// with an anchor like `&id001` and each later one in the same document as
+/// the alias `*id001`, which the deserializer reads as a copy of it. Only a
+/// value that has more than one owner, and that is a sequence or mapping,
+/// gets an anchor.
+///
+/// The value is written out in full wherever an alias cannot be used: in a
+/// mapping key, in a mapping whose entries are sorted, with a
+/// [`map_scalar`] hook, in [`json`] output, and with other serializers
+/// including [`to_value`][crate::to_value]. Deserializing creates a new `Rc`
+/// or `Arc` for each occurrence.
+///
+/// For `Rc`s inside of a collection, wrap each of them in a newtype struct
+/// whose field has the attribute.
+///
+/// [`map_scalar`]: crate::SerializerBuilder::map_scalar
+/// [`json`]: crate::SerializerBuilder::json
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+/// use std::rc::Rc;
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Mesh {
+///     vertices: Vec<u32>,
+/// }
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Scene {
+///     #[serde(with = "serde_yaml::with::shared")]
+///     left: Rc<Mesh>,
+///     #[serde(with = "serde_yaml::with::shared")]
+///     right: Rc<Mesh>,
+/// }
+///
+/// fn main() {
+///     let mesh = Rc::new(Mesh {
+///         vertices: vec![1, 2],
+///     });
+///     let scene = Scene {
+///         left: Rc::clone(&mesh),
+///         right: mesh,
+///     };
+///
+///     let yaml = serde_yaml::to_string(&scene).unwrap();
+///     assert_eq!(yaml, "left: &id001\n  vertices:\n  - 1\n  - 2\nright: *id001\n");
+///     assert_eq!(serde_yaml::from_str::<Scene>(&yaml).unwrap(), scene);
+/// }
+/// ```
+pub mod shared {
+    use crate::shared::{self, SHARED};
+    use serde::de::{Deserialize, Deserializer};
+    use serde::ser::{Serialize, Serializer};
+    use std::any;
+    use std::ops::Deref;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    /// The pointers that `shared` works with: `Rc` and `Arc`.
+    pub trait SharedPointer: Deref {
+        #[doc(hidden)]
+        fn has_other_owners(&self) -> bool;
+    }
+
+    impl<T: ?Sized> SharedPointer for Rc<T> {
+        fn has_other_owners(&self) -> bool {
+            Rc::strong_count(self) > 1
+        }
+    }
+
+    impl<T: ?Sized> SharedPointer for Arc<T> {
+        fn has_other_owners(&self) -> bool {
+            Arc::strong_count(self) > 1
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn serialize<P, S>(value: &P, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        P: SharedPointer,
+        P::Target: Serialize,
+        S: Serializer,
+    {
+        let target: &P::Target = value;
+        if !value.has_other_owners() {
+            return target.serialize(serializer);
+        }
+        let address = (target as *const P::Target).cast::<()>() as usize;
+        let identity = (address, any::type_name::<P::Target>());
+        shared::with_identity(identity, || {
+            serializer.serialize_newtype_struct(SHARED, target)
+        })
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, P, D>(deserializer: D) -> Result<P, D::Error>
+    where
+        P: SharedPointer + From<P::Target>,
+        P::Target: Deserialize<'de> + Sized,
+        D: Deserializer<'de>,
+    {
+        P::Target::deserialize(deserializer).map(P::from)
+    }
+}
+
+/// Serialize/deserialize a struct with its name as a YAML tag.
+///
+/// Formats like CloudFormation give meaning to application-specific tags, as
+/// in `!Ref Bucket` or `!GetAtt [Bucket, Arn]`. With `tagged`, such a node is
+/// read into a newtype, tuple or regular struct of the same name, and the
+/// struct is written back with the name as its tag. Deserializing fails if
+/// the node has a different tag or none.
+///
+/// The name is the one Serde knows the struct by, which is the Rust name
+/// unless changed with `#[serde(rename = "…")]`. Types other than structs
+/// are not affected. The field may also be an `Option` of the struct.
+///
+/// For a tag that is not the name of a type, [`serialize_with_tag`] and
+/// [`deserialize_with_tag`] write and read any value with the given tag.
+///
+/// [`serialize_with_tag`]: tagged::serialize_with_tag
+/// [`deserialize_with_tag`]: tagged::deserialize_with_tag
+///
+/// # Example
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Ref(String);
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct GetAtt(String, String);
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Output {
+///     #[serde(rename = "Value", with = "serde_yaml::with::tagged")]
+///     value: GetAtt,
+///     #[serde(rename = "Bucket", with = "serde_yaml::with::tagged")]
+///     bucket: Ref,
+///     #[serde(rename = "Name", serialize_with = "sub", deserialize_with = "de_sub")]
+///     name: String,
+/// }
+///
+/// fn sub<S: serde::Serializer>(name: &String, serializer: S) -> Result<S::Ok, S::Error> {
+///     serde_yaml::with::tagged::serialize_with_tag("Sub", name, serializer)
+/// }
+///
+/// fn de_sub<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+///     serde_yaml::with::tagged::deserialize_with_tag("Sub", deserializer)
+/// }
+///
+/// fn main() {
+///     let yaml = "Value: !GetAtt\n- Bucket\n- Arn\nBucket: !Ref Bucket\nName: !Sub ${Stage}-bucket\n";
+///     let output: Output = serde_yaml::from_str(yaml).unwrap();
+///     assert_eq!(output.bucket, Ref("Bucket".to_owned()));
+///     assert_eq!(serde_yaml::to_string(&output).unwrap(), yaml);
+///
+///     let error = serde_yaml::from_str::<Output>("Value: !Ref Bucket\n").unwrap_err();
+///     assert_eq!(
+///         error.to_string(),
+///         "Value: invalid value: tag !Ref, expected a value tagged !GetAtt at line 1 column 8",
+///     );
+/// }
+/// ```
+pub mod tagged {
+    use crate::value::tagged::{nobang, TagStringVisitor};
+    use crate::value::{Mapping, Sequence, Tag, Value};
+    use serde::de::{
+        self, DeserializeSeed, Deserializer, EnumAccess, Unexpected, VariantAccess, Visitor,
+    };
+    use serde::ser::{
+        self, Serialize, SerializeMap, SerializeStruct, SerializeTupleStruct, Serializer,
+    };
+    use std::fmt::{self, Display};
+    use std::marker::PhantomData;
+
+    #[allow(missing_docs)]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        value.serialize(NameAsTag {
+            delegate: serializer,
+        })
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: de::Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(NameAsTag {
+            delegate: deserializer,
+        })
+    }
+
+    /// Serializes `value` with `tag`, which may be written with or without
+    /// its leading `!`.
+    ///
+    /// The value must not be an enum variant with data, which would need a
+    /// tag of its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` is empty.
+    pub fn serialize_with_tag<T, S>(tag: &str, value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&SerializeTag(&Tag::new(tag)), value)?;
+        map.end()
+    }
+
+    /// Deserializes a value that has `tag`, which may be given with or
+    /// without its leading `!`.
+    pub fn deserialize_with_tag<'de, T, D>(tag: &str, deserializer: D) -> Result<T, D::Error>
+    where
+        T: de::Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(TagVisitor {
+            tag,
+            seed: PhantomData::<T>,
+        })
+    }
+
+    struct SerializeTag<'a>(&'a Tag);
+
+    impl<'a> Serialize for SerializeTag<'a> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_str(self.0)
+        }
+    }
+
+    struct NameAsTag<D> {
+        delegate: D,
+    }
+
+    impl<D> Serialize for NameAsTag<D>
+    where
+        D: Serialize,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            self.delegate.serialize(NameAsTag {
+                delegate: serializer,
+            })
+        }
+    }
+
+    impl<D> Serializer for NameAsTag<D>
+    where
+        D: Serializer,
+    {
+        type Ok = D::Ok;
+        type Error = D::Error;
+
+        type SerializeSeq = D::SerializeSeq;
+        type SerializeTuple = D::SerializeTuple;
+        type SerializeTupleStruct = SerializeTupleStructAsTagged<D>;
+        type SerializeTupleVariant = D::SerializeTupleVariant;
+        type SerializeMap = D::SerializeMap;
+        type SerializeStruct = SerializeStructAsTagged<D>;
+        type SerializeStructVariant = D::SerializeStructVariant;
+
+        fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_bool(v)
+        }
+
+        fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i8(v)
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i16(v)
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i32(v)
+        }
+
+        fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i64(v)
+        }
+
+        fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_i128(v)
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u8(v)
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u16(v)
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u32(v)
+        }
+
+        fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u64(v)
+        }
+
+        fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_u128(v)
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_f32(v)
+        }
+
+        fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_f64(v)
+        }
+
+        fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_char(v)
+        }
+
+        fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_str(v)
+        }
+
+        fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_bytes(v)
+        }
+
+        fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_unit()
+        }
+
+        fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_unit_struct(name)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+        ) -> Result<Self::Ok, Self::Error> {
+            self.delegate
+                .serialize_unit_variant(name, variant_index, variant)
+        }
+
+        fn serialize_newtype_struct<T>(
+            self,
+            name: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            serialize_with_tag(name, value, self.delegate)
+        }
+
+        fn serialize_newtype_variant<T>(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.delegate
+                .serialize_newtype_variant(name, variant_index, variant, value)
+        }
+
+        fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+            self.delegate.serialize_none()
+        }
+
+        fn serialize_some<V>(self, value: &V) -> Result<Self::Ok, Self::Error>
+        where
+            V: ?Sized + Serialize,
+        {
+            self.delegate.serialize_some(&NameAsTag { delegate: value })
+        }
+
+        fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+            self.delegate.serialize_seq(len)
+        }
+
+        fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+            self.delegate.serialize_tuple(len)
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+            Ok(SerializeTupleStructAsTagged {
+                delegate: self.delegate,
+                name,
+                sequence: Sequence::with_capacity(len),
+            })
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+            self.delegate
+                .serialize_tuple_variant(name, variant_index, variant, len)
+        }
+
+        fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+            self.delegate.serialize_map(len)
+        }
+
+        fn serialize_struct(
+            self,
+            name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStruct, Self::Error> {
+            Ok(SerializeStructAsTagged {
+                delegate: self.delegate,
+                name,
+                mapping: Mapping::with_capacity(len),
+            })
+        }
+
+        fn serialize_struct_variant(
+            self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStructVariant, Self::Error> {
+            self.delegate
+                .serialize_struct_variant(name, variant_index, variant, len)
         }
 
-        fn visit_char<E>(self, v: char) -> Result<Self::Value, E>
+        fn collect_str<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
         where
-            E: de::Error,
+            T: ?Sized + Display,
         {
-            self.delegate.visit_char(v)
+            self.delegate.collect_str(value)
         }
 
-        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        fn is_human_readable(&self) -> bool {
+            self.delegate.is_human_readable()
+        }
+    }
+
+    // The fields are collected first, because the serializer only takes the
+    // key of a map as a tag if it is serialized together with the value.
+    struct SerializeTupleStructAsTagged<S> {
+        delegate: S,
+        name: &'static str,
+        sequence: Sequence,
+    }
+
+    impl<S> SerializeTupleStruct for SerializeTupleStructAsTagged<S>
+    where
+        S: Serializer,
+    {
+        type Ok = S::Ok;
+        type Error = S::Error;
+
+        fn serialize_field<T>(&mut self, field: &T) -> Result<(), Self::Error>
         where
-            E: de::Error,
+            T: ?Sized + Serialize,
         {
-            self.delegate.visit_str(v)
+            let value = field
+                .serialize(crate::value::Serializer)
+                .map_err(ser::Error::custom)?;
+            self.sequence.push(value);
+            Ok(())
         }
 
-        fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            serialize_with_tag(self.name, &self.sequence, self.delegate)
+        }
+    }
+
+    struct SerializeStructAsTagged<S> {
+        delegate: S,
+        name: &'static str,
+        mapping: Mapping,
+    }
+
+    impl<S> SerializeStruct for SerializeStructAsTagged<S>
+    where
+        S: Serializer,
+    {
+        type Ok = S::Ok;
+        type Error = S::Error;
+
+        fn serialize_field<T>(&mut self, name: &'static str, field: &T) -> Result<(), Self::Error>
         where
-            E: de::Error,
+            T: ?Sized + Serialize,
         {
-            self.delegate.visit_borrowed_str(v)
+            let value = field
+                .serialize(crate::value::Serializer)
+                .map_err(ser::Error::custom)?;
+            self.mapping.insert(Value::String(name.to_owned()), value);
+            Ok(())
         }
 
-        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            serialize_with_tag(self.name, &self.mapping, self.delegate)
+        }
+    }
+
+    impl<'de, D> Deserializer<'de> for NameAsTag<D>
+    where
+        D: Deserializer<'de>,
+    {
+        type Error = D::Error;
+
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            E: de::Error,
+            V: Visitor<'de>,
         {
-            self.delegate.visit_string(v)
+            self.delegate.deserialize_any(visitor)
         }
 
-        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+        fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            E: de::Error,
+            V: Visitor<'de>,
         {
-            self.delegate.visit_bytes(v)
+            self.delegate.deserialize_bool(visitor)
         }
 
-        fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+        fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            E: de::Error,
+            V: Visitor<'de>,
         {
-            self.delegate.visit_borrowed_bytes(v)
+            self.delegate.deserialize_i8(visitor)
         }
 
-        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+        fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            E: de::Error,
+            V: Visitor<'de>,
         {
-            self.delegate.visit_byte_buf(v)
+            self.delegate.deserialize_i16(visitor)
         }
 
-        fn visit_none<E>(self) -> Result<Self::Value, E>
+        fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            E: de::Error,
+            V: Visitor<'de>,
         {
-            self.delegate.visit_none()
+            self.delegate.deserialize_i32(visitor)
         }
 
-        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            D: Deserializer<'de>,
+            V: Visitor<'de>,
         {
-            self.delegate.visit_some(SingletonMapRecursive {
-                delegate: deserializer,
+            self.delegate.deserialize_i64(visitor)
+        }
+
+        fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_i128(visitor)
+        }
+
+        fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u8(visitor)
+        }
+
+        fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u16(visitor)
+        }
+
+        fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u32(visitor)
+        }
+
+        fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u64(visitor)
+        }
+
+        fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_u128(visitor)
+        }
+
+        fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_f32(visitor)
+        }
+
+        fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_f64(visitor)
+        }
+
+        fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_char(visitor)
+        }
+
+        fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_str(visitor)
+        }
+
+        fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_string(visitor)
+        }
+
+        fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_bytes(visitor)
+        }
+
+        fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_byte_buf(visitor)
+        }
+
+        fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate
+                .deserialize_option(OptionAsTagged { delegate: visitor })
+        }
+
+        fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_unit(visitor)
+        }
+
+        fn deserialize_unit_struct<V>(
+            self,
+            name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_unit_struct(name, visitor)
+        }
+
+        fn deserialize_newtype_struct<V>(
+            self,
+            name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_any(TagVisitor {
+                tag: name,
+                seed: StructSeed::Newtype { name, visitor },
             })
         }
 
-        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            E: de::Error,
+            V: Visitor<'de>,
         {
-            self.delegate.visit_unit()
+            self.delegate.deserialize_seq(visitor)
         }
 
-        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
         where
-            D: Deserializer<'de>,
+            V: Visitor<'de>,
+        {
+            self.delegate.deserialize_tuple(len, visitor)
+        }
+
+        fn deserialize_tuple_struct<V>(
+            self,
+            name: &'static str,
+            len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
         {
-            self.delegate.visit_newtype_struct(SingletonMapRecursive {
-                delegate: deserializer,
+            self.delegate.deserialize_any(TagVisitor {
+                tag: name,
+                seed: StructSeed::Tuple { name, len, visitor },
             })
         }
 
-        fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+        fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            A: SeqAccess<'de>,
+            V: Visitor<'de>,
         {
-            self.delegate
-                .visit_seq(SingletonMapRecursive { delegate: seq })
+            self.delegate.deserialize_map(visitor)
         }
 
-        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        fn deserialize_struct<V>(
+            self,
+            name: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
         where
-            A: MapAccess<'de>,
+            V: Visitor<'de>,
         {
-            self.delegate
-                .visit_map(SingletonMapRecursive { delegate: map })
+            self.delegate.deserialize_any(TagVisitor {
+                tag: name,
+                seed: StructSeed::Struct {
+                    name,
+                    fields,
+                    visitor,
+                },
+            })
         }
-    }
-
-    impl<'de, T> DeserializeSeed<'de> for SingletonMapRecursive<T>
-    where
-        T: DeserializeSeed<'de>,
-    {
-        type Value = T::Value;
 
-        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        fn deserialize_enum<V>(
+            self,
+            name: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
         where
-            D: Deserializer<'de>,
+            V: Visitor<'de>,
         {
-            self.delegate.deserialize(SingletonMapRecursive {
-                delegate: deserializer,
-            })
+            self.delegate.deserialize_enum(name, variants, visitor)
         }
-    }
 
-    impl<'de, S> SeqAccess<'de> for SingletonMapRecursive<S>
-    where
-        S: SeqAccess<'de>,
-    {
-        type Error = S::Error;
-
-        fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+        fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            T: DeserializeSeed<'de>,
+            V: Visitor<'de>,
         {
-            self.delegate
-                .next_element_seed(SingletonMapRecursive { delegate: seed })
+            self.delegate.deserialize_identifier(visitor)
         }
-    }
-
-    impl<'de, M> MapAccess<'de> for SingletonMapRecursive<M>
-    where
-        M: MapAccess<'de>,
-    {
-        type Error = M::Error;
 
-        fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+        fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            K: DeserializeSeed<'de>,
+            V: Visitor<'de>,
         {
-            self.delegate
-                .next_key_seed(SingletonMapRecursive { delegate: seed })
+            self.delegate.deserialize_ignored_any(visitor)
         }
 
-        fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
-        where
-            V: DeserializeSeed<'de>,
-        {
-            self.delegate
-                .next_value_seed(SingletonMapRecursive { delegate: seed })
+        fn is_human_readable(&self) -> bool {
+            self.delegate.is_human_readable()
         }
     }
 
-    struct SingletonMapRecursiveAsEnum<D> {
-        name: &'static str,
-        delegate: D,
+    struct OptionAsTagged<V> {
+        delegate: V,
     }
 
-    impl<'de, V> Visitor<'de> for SingletonMapRecursiveAsEnum<V>
+    impl<'de, V> Visitor<'de> for OptionAsTagged<V>
     where
         V: Visitor<'de>,
     {
@@ -1939,29 +4780,6 @@ pub mod singleton_map_recursive {
             self.delegate.expecting(formatter)
         }
 
-        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate.visit_enum(de::value::StrDeserializer::new(v))
-        }
-
-        fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate
-                .visit_enum(de::value::BorrowedStrDeserializer::new(v))
-        }
-
-        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.delegate
-                .visit_enum(de::value::StringDeserializer::new(v))
-        }
-
         fn visit_none<E>(self) -> Result<Self::Value, E>
         where
             E: de::Error,
@@ -1973,7 +4791,7 @@ pub mod singleton_map_recursive {
         where
             D: Deserializer<'de>,
         {
-            self.delegate.visit_some(SingletonMapRecursive {
+            self.delegate.visit_some(NameAsTag {
                 delegate: deserializer,
             })
         }
@@ -1984,111 +4802,59 @@ pub mod singleton_map_recursive {
         {
             self.delegate.visit_unit()
         }
-
-        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
-        where
-            A: MapAccess<'de>,
-        {
-            self.delegate.visit_enum(SingletonMapRecursiveAsEnum {
-                name: self.name,
-                delegate: map,
-            })
-        }
     }
 
-    impl<'de, D> EnumAccess<'de> for SingletonMapRecursiveAsEnum<D>
-    where
-        D: MapAccess<'de>,
-    {
-        type Error = D::Error;
-        type Variant = Self;
-
-        fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
-        where
-            V: DeserializeSeed<'de>,
-        {
-            match self.delegate.next_key_seed(seed)? {
-                Some(value) => Ok((value, self)),
-                None => Err(de::Error::invalid_value(
-                    Unexpected::Map,
-                    &"map with a single key",
-                )),
-            }
-        }
+    // Accepts a node with the given tag, and deserializes its content with
+    // the seed. A tagged node is visited as an enum whose variant is the tag.
+    struct TagVisitor<'a, S> {
+        tag: &'a str,
+        seed: S,
     }
 
-    impl<'de, D> VariantAccess<'de> for SingletonMapRecursiveAsEnum<D>
+    impl<'a, 'de, S> Visitor<'de> for TagVisitor<'a, S>
     where
-        D: MapAccess<'de>,
+        S: DeserializeSeed<'de>,
     {
-        type Error = D::Error;
-
-        fn unit_variant(self) -> Result<(), Self::Error> {
-            Err(de::Error::invalid_type(Unexpected::Map, &"unit variant"))
-        }
+        type Value = S::Value;
 
-        fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Self::Error>
-        where
-            T: DeserializeSeed<'de>,
-        {
-            let value = self
-                .delegate
-                .next_value_seed(SingletonMapRecursive { delegate: seed })?;
-            match self.delegate.next_key()? {
-                None => Ok(value),
-                Some(IgnoredAny) => Err(de::Error::invalid_value(
-                    Unexpected::Map,
-                    &"map with a single key",
-                )),
-            }
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a value tagged !{}", nobang(self.tag))
         }
 
-        fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+        fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
         where
-            V: Visitor<'de>,
+            A: EnumAccess<'de>,
         {
-            let value = self.delegate.next_value_seed(TupleVariantSeed {
-                len,
-                visitor: SingletonMapRecursive { delegate: visitor },
-            })?;
-            match self.delegate.next_key()? {
-                None => Ok(value),
-                Some(IgnoredAny) => Err(de::Error::invalid_value(
-                    Unexpected::Map,
-                    &"map with a single key",
-                )),
+            let (tag, contents) = data.variant_seed(TagStringVisitor)?;
+            if tag != self.tag {
+                let unexpected = format!("tag {}", tag);
+                return Err(de::Error::invalid_value(
+                    Unexpected::Other(&unexpected),
+                    &self,
+                ));
             }
+            contents.newtype_variant_seed(self.seed)
         }
+    }
 
-        fn struct_variant<V>(
-            mut self,
+    enum StructSeed<V> {
+        Newtype {
+            name: &'static str,
+            visitor: V,
+        },
+        Tuple {
+            name: &'static str,
+            len: usize,
+            visitor: V,
+        },
+        Struct {
+            name: &'static str,
             fields: &'static [&'static str],
             visitor: V,
-        ) -> Result<V::Value, Self::Error>
-        where
-            V: Visitor<'de>,
-        {
-            let value = self.delegate.next_value_seed(StructVariantSeed {
-                name: self.name,
-                fields,
-                visitor: SingletonMapRecursive { delegate: visitor },
-            })?;
-            match self.delegate.next_key()? {
-                None => Ok(value),
-                Some(IgnoredAny) => Err(de::Error::invalid_value(
-                    Unexpected::Map,
-                    &"map with a single key",
-                )),
-            }
-        }
+        },
     }
 
-    struct TupleVariantSeed<V> {
-        len: usize,
-        visitor: V,
-    }
-
-    impl<'de, V> DeserializeSeed<'de> for TupleVariantSeed<V>
+    impl<'de, V> DeserializeSeed<'de> for StructSeed<V>
     where
         V: Visitor<'de>,
     {
@@ -2098,27 +4864,213 @@ pub mod singleton_map_recursive {
         where
             D: Deserializer<'de>,
         {
-            deserializer.deserialize_tuple(self.len, self.visitor)
+            match self {
+                StructSeed::Newtype { name, visitor } => {
+                    deserializer.deserialize_newtype_struct(name, visitor)
+                }
+                StructSeed::Tuple { name, len, visitor } => {
+                    deserializer.deserialize_tuple_struct(name, len, visitor)
+                }
+                StructSeed::Struct {
+                    name,
+                    fields,
+                    visitor,
+                } => deserializer.deserialize_struct(name, fields, visitor),
+            }
         }
     }
+}
 
-    struct StructVariantSeed<V> {
-        name: &'static str,
-        fields: &'static [&'static str],
-        visitor: V,
+/// Serialize/deserialize a date and time of the `chrono` or `time` crate as a
+/// YAML timestamp.
+///
+/// The forms of the YAML `!!timestamp` type are accepted, with or without
+/// the tag: a date alone like `2001-12-14`, which is at midnight UTC, and a
+/// date and time in ISO 8601 like `2001-12-14T21:59:43.10-05:00`, with a
+/// space instead of the `T`, a one-digit hour and offset, or no offset for
+/// UTC, as in `2001-12-14 21:59:43.10 -5`. A timestamp is serialized in the
+/// form of RFC 3339, with `Z` for UTC and as many digits of the fraction of
+/// a second as it has.
+///
+/// This works for `chrono::DateTime` with the "chrono" feature, in
+/// `FixedOffset` or any time zone that one converts to such as `Utc`, and
+/// for `time::OffsetDateTime` with the "time" feature.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "chrono")]
+/// # {
+/// # use serde_derive::{Deserialize, Serialize};
+/// use chrono::{DateTime, TimeZone, Utc};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Release {
+///     #[serde(with = "serde_yaml::with::timestamp")]
+///     published: DateTime<Utc>,
+/// }
+///
+/// let release: Release = serde_yaml::from_str("published: 2001-12-14 21:59:43 -5").unwrap();
+/// let published = Utc.with_ymd_and_hms(2001, 12, 15, 2, 59, 43).unwrap();
+/// assert_eq!(release.published, published);
+///
+/// let yaml = serde_yaml::to_string(&release).unwrap();
+/// assert_eq!(yaml, "published: 2001-12-15T02:59:43Z\n");
+/// # }
+/// ```
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub mod timestamp {
+    use crate::timestamp::Fields;
+    use serde::de::{self, Deserializer, Unexpected, Visitor};
+    use serde::ser::{self, Serializer};
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    /// A date and time that [`timestamp`](self) can serialize and
+    /// deserialize.
+    pub trait Timestamp: Sized + private::Sealed {
+        #[doc(hidden)]
+        fn from_fields(fields: Fields) -> Option<Self>;
+        #[doc(hidden)]
+        fn to_fields(&self) -> Option<Fields>;
     }
 
-    impl<'de, V> DeserializeSeed<'de> for StructVariantSeed<V>
+    mod private {
+        pub trait Sealed {}
+    }
+
+    #[cfg(feature = "chrono")]
+    impl<Tz> private::Sealed for chrono::DateTime<Tz> where Tz: chrono::TimeZone {}
+
+    #[cfg(feature = "chrono")]
+    impl<Tz> Timestamp for chrono::DateTime<Tz>
     where
-        V: Visitor<'de>,
+        Tz: chrono::TimeZone,
+        chrono::DateTime<Tz>: From<chrono::DateTime<chrono::FixedOffset>>,
     {
-        type Value = V::Value;
+        fn from_fields(fields: Fields) -> Option<Self> {
+            use chrono::{FixedOffset, NaiveDate, TimeZone};
+            let date = NaiveDate::from_ymd_opt(
+                fields.year,
+                u32::from(fields.month),
+                u32::from(fields.day),
+            )?;
+            // A leap second is the 60th second, which chrono keeps as a
+            // fraction of more than one second after the 59th.
+            let (second, nanosecond) = match fields.second {
+                60 => (59, fields.nanosecond + 1_000_000_000),
+                second => (second, fields.nanosecond),
+            };
+            let time = date.and_hms_nano_opt(
+                u32::from(fields.hour),
+                u32::from(fields.minute),
+                u32::from(second),
+                nanosecond,
+            )?;
+            let offset = FixedOffset::east_opt(fields.offset)?;
+            let datetime = offset.from_local_datetime(&time).single()?;
+            Some(Self::from(datetime))
+        }
+
+        fn to_fields(&self) -> Option<Fields> {
+            use chrono::{Datelike, Offset, Timelike};
+            let local = self.naive_local();
+            let (second, nanosecond) = match local.nanosecond() {
+                nanosecond if nanosecond >= 1_000_000_000 => (60, nanosecond - 1_000_000_000),
+                nanosecond => (local.second() as u8, nanosecond),
+            };
+            Some(Fields {
+                year: local.year(),
+                month: local.month() as u8,
+                day: local.day() as u8,
+                hour: local.hour() as u8,
+                minute: local.minute() as u8,
+                second,
+                nanosecond,
+                offset: self.offset().fix().local_minus_utc(),
+            })
+        }
+    }
 
-        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    #[cfg(feature = "time")]
+    impl private::Sealed for time::OffsetDateTime {}
+
+    #[cfg(feature = "time")]
+    impl Timestamp for time::OffsetDateTime {
+        fn from_fields(fields: Fields) -> Option<Self> {
+            use time::{Date, Month, PrimitiveDateTime, Time, UtcOffset};
+            let month = Month::try_from(fields.month).ok()?;
+            let date = Date::from_calendar_date(fields.year, month, fields.day).ok()?;
+            let time =
+                Time::from_hms_nano(fields.hour, fields.minute, fields.second, fields.nanosecond)
+                    .ok()?;
+            let offset = UtcOffset::from_whole_seconds(fields.offset).ok()?;
+            Some(PrimitiveDateTime::new(date, time).assume_offset(offset))
+        }
+
+        fn to_fields(&self) -> Option<Fields> {
+            let offset = self.offset().whole_seconds();
+            // An offset of a fraction of a minute has no YAML form.
+            if offset % 60 != 0 {
+                return None;
+            }
+            Some(Fields {
+                year: self.year(),
+                month: u8::from(self.month()),
+                day: self.day(),
+                hour: self.hour(),
+                minute: self.minute(),
+                second: self.second(),
+                nanosecond: self.nanosecond(),
+                offset,
+            })
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Timestamp,
+        S: Serializer,
+    {
+        match value.to_fields() {
+            Some(fields) if (0..=9999).contains(&fields.year) => serializer.collect_str(&fields),
+            _ => Err(ser::Error::custom(
+                "date and time cannot be written as a YAML timestamp",
+            )),
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Timestamp,
+        D: Deserializer<'de>,
+    {
+        struct TimestampVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for TimestampVisitor<T>
         where
-            D: Deserializer<'de>,
+            T: Timestamp,
         {
-            deserializer.deserialize_struct(self.name, self.fields, self.visitor)
+            type Value = T;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a YAML timestamp")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<T, E>
+            where
+                E: de::Error,
+            {
+                match Fields::parse(v).and_then(T::from_fields) {
+                    Some(timestamp) => Ok(timestamp),
+                    None => Err(de::Error::invalid_value(Unexpected::Str(v), &self)),
+                }
+            }
         }
+
+        deserializer.deserialize_str(TimestampVisitor(PhantomData))
     }
 }
@@ -8,9 +8,14 @@
 
 use indoc::indoc;
 use serde_derive::Deserialize;
-use serde_yaml::{Deserializer, Number, Value};
-use std::collections::BTreeMap;
+use serde_yaml::{
+    CoercionFlags, Deserializer, DeserializerBuilder, DuplicateKeyPolicy, Location, NodeKind,
+    Number, ScalarStyle, SchemaProfile, Spanned, Value,
+};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
 
 fn test_de<T>(yaml: &str, expected: &T)
 where
@@ -67,6 +72,43 @@ fn test_borrowed() {
     "};
     let expected = vec!["plain nonàscii", "single quoted", "double quoted"];
     test_de_no_value(yaml, &expected);
+
+    let yaml = indoc! {"
+        - |
+          literal
+        - >-
+          folded
+
+        - |+
+          kept
+
+        - &a x
+        - *a
+    "};
+    let borrowed: Vec<&str> = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(borrowed, ["literal\n", "folded", "kept\n\n", "x", "x"]);
+
+    // Content that is not in the input as it is cannot be borrowed.
+    for yaml in ["'it''s'", "\"a\\tb\"", "a\n  b", "|\n  a\n  b\n"] {
+        let err = serde_yaml::from_str::<&str>(yaml).unwrap_err();
+        assert!(
+            err.to_string().contains("expected a borrowed string"),
+            "{:?}: {}",
+            yaml,
+            err,
+        );
+        let cow: Cow<str> = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(cow, Cow::Owned(_)));
+    }
+
+    #[derive(Deserialize)]
+    struct Record<'a> {
+        #[serde(borrow)]
+        name: Cow<'a, str>,
+    }
+
+    let record: Record = serde_yaml::from_str("name: >\n  web\n").unwrap();
+    assert!(matches!(record.name, Cow::Borrowed("web\n")));
 }
 
 #[test]
@@ -356,6 +398,44 @@ fn test_byte_order_mark() {
     test_de(yaml, &expected);
 }
 
+#[test]
+fn test_escaped_control_characters() {
+    // Characters that are not allowed raw in the input can be written as
+    // escapes of a double-quoted scalar.
+    let yaml = r#""\0 \a \x1B \x7F \x9F \uFFFE""#;
+    let expected = "\0 \u{7} \u{1b} \u{7f} \u{9f} \u{fffe}".to_owned();
+    test_de(yaml, &expected);
+}
+
+#[test]
+fn test_line_breaks() {
+    let yaml = indoc! {"
+        plain: a
+          b
+        single: 'c
+
+          d'
+        double: \"e
+          f\"
+        literal: |
+          g
+
+          h
+        folded: >
+          i
+          j
+    "};
+    let mut expected = BTreeMap::new();
+    expected.insert("plain".to_owned(), "a b".to_owned());
+    expected.insert("single".to_owned(), "c\nd".to_owned());
+    expected.insert("double".to_owned(), "e f".to_owned());
+    expected.insert("literal".to_owned(), "g\n\nh\n".to_owned());
+    expected.insert("folded".to_owned(), "i j\n".to_owned());
+    for line_break in ["\n", "\r\n", "\r", "\u{85}"] {
+        test_de(&yaml.replace('\n', line_break), &expected);
+    }
+}
+
 #[test]
 fn test_bomb() {
     #[derive(Debug, Deserialize, PartialEq)]
@@ -567,6 +647,106 @@ fn test_no_required_fields() {
     }
 }
 
+#[test]
+fn test_flatten_catch_all() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Data {
+        name: String,
+        #[serde(flatten)]
+        rest: HashMap<Value, Value>,
+    }
+
+    let yaml = indoc! {"
+        name: data
+        1: one
+        true: yes
+        ~: null key
+        'quoted': 2
+    "};
+
+    let mut rest = HashMap::new();
+    rest.insert(Value::Number(1.into()), Value::from("one"));
+    rest.insert(Value::Bool(true), Value::from("yes"));
+    rest.insert(Value::Null, Value::from("null key"));
+    rest.insert(Value::from("quoted"), Value::Number(2.into()));
+    let expected = Data {
+        name: "data".to_owned(),
+        rest,
+    };
+
+    test_de(yaml, &expected);
+}
+
+#[test]
+fn test_flatten_renamed_non_string_fields() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Data {
+        #[serde(rename = "1")]
+        one: String,
+        #[serde(rename = "true")]
+        yes: String,
+        #[serde(flatten)]
+        rest: HashMap<String, String>,
+    }
+
+    let yaml = indoc! {"
+        1: x
+        2: y
+        true: z
+    "};
+
+    let mut rest = HashMap::new();
+    rest.insert("2".to_owned(), "y".to_owned());
+    let expected = Data {
+        one: "x".to_owned(),
+        yes: "z".to_owned(),
+        rest,
+    };
+
+    let deserialized: Data = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(expected, deserialized);
+    let deserialized: Data = DeserializerBuilder::new().from_str(yaml).unwrap();
+    assert_eq!(expected, deserialized);
+    let deserialized: Data = serde_yaml::from_slice(yaml.as_bytes()).unwrap();
+    assert_eq!(expected, deserialized);
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct One {
+        #[serde(rename = "1")]
+        one: String,
+        #[serde(flatten)]
+        rest: HashMap<Value, Value>,
+    }
+
+    let deserialized: One = serde_yaml::from_str("1: x\n2: y\n").unwrap();
+    let mut rest = HashMap::new();
+    rest.insert(Value::Number(2.into()), Value::from("y"));
+    assert_eq!(
+        One {
+            one: "x".to_owned(),
+            rest,
+        },
+        deserialized,
+    );
+
+    let err = serde_yaml::from_str::<One>("2: y\n").unwrap_err();
+    assert_eq!("missing field `1`", err.to_string());
+}
+
+#[test]
+fn test_integer_key_is_not_field_index() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Data {
+        a: Option<String>,
+    }
+
+    let yaml = indoc! {"
+        0: zero
+    "};
+    let deserialized: Data = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(Data { a: None }, deserialized);
+}
+
 #[test]
 fn test_empty_scalar() {
     #[derive(Deserialize, PartialEq, Debug)]
@@ -586,6 +766,64 @@ fn test_empty_scalar() {
     test_de(yaml, &expected);
 }
 
+#[test]
+fn test_untagged_scalar_resolution() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Null,
+        Bool(bool),
+        Int(i64),
+        Float(f64),
+        Str(String),
+        List(Vec<Untagged>),
+    }
+
+    let yaml = indoc! {"
+        - 01
+        - 1.
+        - .5
+        - ~
+        - null
+        - no
+        - 0x10
+        - 1e2
+        -
+        - '1'
+        - true
+        - -007
+        - .inf
+        - [1]
+    "};
+    let expected = vec![
+        Untagged::Str("01".to_owned()),
+        Untagged::Float(1.0),
+        Untagged::Float(0.5),
+        Untagged::Null,
+        Untagged::Null,
+        Untagged::Str("no".to_owned()),
+        Untagged::Int(16),
+        Untagged::Float(100.0),
+        Untagged::Null,
+        Untagged::Str("1".to_owned()),
+        Untagged::Bool(true),
+        Untagged::Str("-007".to_owned()),
+        Untagged::Float(f64::INFINITY),
+        Untagged::List(vec![Untagged::Int(1)]),
+    ];
+    test_de(yaml, &expected);
+}
+
+#[test]
+fn test_float_resolution_matches_untagged() {
+    assert_eq!(16.0, serde_yaml::from_str::<f64>("0x10").unwrap());
+    assert_eq!(1.0, serde_yaml::from_str::<f64>("1.").unwrap());
+    assert_eq!(1.0, serde_yaml::from_str::<f64>("!!float 01").unwrap());
+    assert!(serde_yaml::from_str::<f64>("01").is_err());
+    assert!(serde_yaml::from_str::<i64>("01").is_err());
+    assert!(serde_yaml::from_str::<bool>("no").is_err());
+}
+
 #[test]
 fn test_python_safe_dump() {
     #[derive(Deserialize, PartialEq, Debug)]
@@ -703,3 +941,1791 @@ fn test_parse_number() {
     let err = " 1 ".parse::<Number>().unwrap_err();
     assert_eq!(err.to_string(), "failed to parse YAML number");
 }
+
+#[test]
+fn test_peek_root() {
+    let yaml = indoc! {"
+        --- !Deployment &root
+        kind: Deployment
+        spec:
+          template: {kind: Pod}
+          replicas: 3
+        ? [complex, key]
+        : 1
+        metadata: ~
+        a: 1
+        b: 2
+        c: 3
+        d: 4
+        e: 5
+        f: 6
+        g: [this is past the last key
+    "};
+    let root = serde_yaml::peek_root(yaml).unwrap();
+    assert_eq!(root.kind(), NodeKind::Mapping);
+    assert_eq!(root.tag(), Some("!Deployment"));
+    assert_eq!(root.anchor(), Some("root"));
+    assert_eq!(
+        root.keys(),
+        ["kind", "spec", "metadata", "a", "b", "c", "d", "e"]
+    );
+
+    let root = serde_yaml::peek_root("- kind: Pod\n").unwrap();
+    assert_eq!(root.kind(), NodeKind::Sequence);
+    assert!(root.keys().is_empty());
+
+    let root = serde_yaml::peek_root("").unwrap();
+    assert_eq!(root.kind(), NodeKind::Scalar);
+    assert_eq!(root.tag(), None);
+
+    let root = serde_yaml::peek_root("{x: 1}").unwrap();
+    assert_eq!(root.keys(), ["x"]);
+
+    let error = serde_yaml::peek_root("*unknown").unwrap_err();
+    assert_eq!(error.to_string(), "unknown anchor");
+}
+
+#[test]
+fn test_validate() {
+    let yaml = indoc! {"
+        ---
+        name: &name web
+        ports: [80, 443]
+        alias: *name
+        ---
+        nested: [[[{deep: \"\\u00e9\"}]]]
+    "};
+    let stats = serde_yaml::validate(yaml).unwrap();
+    assert_eq!(stats.documents(), 2);
+    assert_eq!(stats.max_depth(), 5);
+    assert_eq!(stats.nodes(), 17);
+    assert_eq!(stats.scalar_bytes(), 34);
+
+    let stats = serde_yaml::validate("").unwrap();
+    assert_eq!(stats.documents(), 0);
+    assert_eq!(stats.nodes(), 0);
+
+    let test_invalid = |yaml: &str| {
+        let expected = serde_yaml::from_str::<Value>(yaml).unwrap_err().to_string();
+        assert!(!serde_yaml::is_valid(yaml));
+        assert_eq!(
+            serde_yaml::validate(yaml).unwrap_err().to_string(),
+            expected
+        );
+    };
+    test_invalid("ports: [80, 443");
+    test_invalid("*unknown");
+    test_invalid("key: \"\\x01\"\nnext: \x01");
+    test_invalid(&("[".repeat(129) + &"]".repeat(129)));
+    test_invalid(indoc! {"
+        a: &a ~
+        b: &b [*a,*a,*a,*a,*a,*a,*a,*a,*a]
+        c: &c [*b,*b,*b,*b,*b,*b,*b,*b,*b]
+        d: &d [*c,*c,*c,*c,*c,*c,*c,*c,*c]
+        e: &e [*d,*d,*d,*d,*d,*d,*d,*d,*d]
+        f: &f [*e,*e,*e,*e,*e,*e,*e,*e,*e]
+        g: &g [*f,*f,*f,*f,*f,*f,*f,*f,*f]
+    "});
+
+    let error = serde_yaml::validate("{\"name\": \"web\"} trailing").unwrap_err();
+    let expected = "did not find expected <document start> at line 1 column 17";
+    assert_eq!(error.to_string(), expected);
+    assert!(serde_yaml::validate("&a [*a]").is_err());
+    assert!(serde_yaml::is_valid(&("[".repeat(128) + &"]".repeat(128))));
+}
+
+#[test]
+fn test_conforms() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Shape {
+        kind: String,
+        replicas: u32,
+    }
+
+    let yaml = indoc! {"
+        kind: Deployment
+        replicas: 3
+        labels: {app: web, tier: frontend}
+    "};
+    serde_yaml::conforms::<Shape>(yaml).unwrap();
+
+    let error = serde_yaml::conforms::<Shape>("kind: Deployment\n").unwrap_err();
+    assert_eq!(error.to_string(), "missing field `replicas`");
+}
+
+fn interpolate(raw: &str) -> Option<String> {
+    let vars = [
+        ("NAME", "web: {tier: frontend}"),
+        ("KEY", "region"),
+        ("PORT", "8080"),
+        ("NOTE", "# not a comment\n- not a list"),
+    ];
+    let mut result = raw.to_owned();
+    for (name, value) in vars {
+        result = result.replace(&format!("${{{}}}", name), value);
+    }
+    if result == raw {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+#[test]
+fn test_map_scalar() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        quoted_port: String,
+        literal: String,
+        port: u16,
+        labels: BTreeMap<String, String>,
+        notes: String,
+        folded: String,
+    }
+
+    let yaml = indoc! {r#"
+        name: ${NAME}
+        quoted_port: "${PORT}"
+        literal: '${PORT}'
+        port: ${PORT}
+        labels:
+          ${KEY}: eu-${KEY}
+          "${NAME}": plain
+        notes: |
+          first ${NOTE}
+          last
+        folded: >
+          ${KEY}
+          and ${PORT}
+    "#};
+    // Like a shell, single quotes turn off interpolation.
+    let builder = DeserializerBuilder::new().map_scalar(|raw, style| match style {
+        ScalarStyle::SingleQuoted => Ok(None),
+        _ => Ok(interpolate(raw)),
+    });
+    let expected = Config {
+        name: "web: {tier: frontend}".to_owned(),
+        quoted_port: "8080".to_owned(),
+        literal: "${PORT}".to_owned(),
+        port: 8080,
+        labels: BTreeMap::from([
+            ("region".to_owned(), "eu-region".to_owned()),
+            ("web: {tier: frontend}".to_owned(), "plain".to_owned()),
+        ]),
+        notes: "first # not a comment\n- not a list\nlast\n".to_owned(),
+        folded: "region and 8080\n".to_owned(),
+    };
+    let config: Config = builder.from_str(yaml).unwrap();
+    assert_eq!(config, expected);
+
+    // Plain scalars keep their style, quoted ones stay strings.
+    let value: Value = builder.from_str(yaml).unwrap();
+    assert_eq!(value["port"], 8080);
+    assert_eq!(value["quoted_port"], "8080");
+
+    let recorder = DeserializerBuilder::new()
+        .map_scalar(|raw, style| Ok(Some(format!("{}:{:?}", raw, style))));
+    let value: Value = recorder
+        .from_str(indoc! {"
+            - a
+            - 'b'
+            - \"c\"
+            - |
+              d
+            - 1
+            - null
+            - !!str 2
+            - !!int 3
+        "})
+        .unwrap();
+    let expected: Value = serde_yaml::from_str(indoc! {"
+        - a:Plain
+        - b:SingleQuoted
+        - c:DoubleQuoted
+        - \"d\\n:Literal\"
+        - 1
+        - null
+        - 2:Plain
+        - 3
+    "})
+    .unwrap();
+    assert_eq!(value, expected);
+
+    let mut iter = builder.clone().build_from_str("${KEY}\n---\n- ${KEY}\n");
+    let first: String = serde::Deserialize::deserialize(iter.next().unwrap()).unwrap();
+    let second: Vec<String> = serde::Deserialize::deserialize(iter.next().unwrap()).unwrap();
+    assert_eq!(first, "region");
+    assert_eq!(second, ["region"]);
+}
+
+#[test]
+fn test_schema_profiles() {
+    // Each row is a plain scalar followed by what it resolves to under the
+    // Core12, Json and Legacy11 profiles, written as YAML for the default
+    // profile.
+    let corpus = [
+        ("null", "null", "null", "null"),
+        ("Null", "null", "'Null'", "null"),
+        ("~", "null", "'~'", "null"),
+        ("", "null", "''", "null"),
+        ("true", "true", "true", "true"),
+        ("True", "true", "'True'", "true"),
+        ("yes", "'yes'", "'yes'", "true"),
+        ("NO", "'NO'", "'NO'", "false"),
+        ("on", "'on'", "'on'", "true"),
+        ("0x1F", "31", "'0x1F'", "31"),
+        ("0o17", "15", "'0o17'", "15"),
+        ("0b101", "5", "'0b101'", "5"),
+        ("0755", "'0755'", "'0755'", "493"),
+        ("-0755", "'-0755'", "'-0755'", "-493"),
+        ("01", "'01'", "'01'", "1"),
+        ("0889", "'0889'", "'0889'", "'0889'"),
+        ("1_000", "'1_000'", "'1_000'", "1000"),
+        ("1_000.5", "'1_000.5'", "'1_000.5'", "1000.5"),
+        ("1:30", "'1:30'", "'1:30'", "90"),
+        ("-1:30", "'-1:30'", "'-1:30'", "-90"),
+        ("190:20:30", "'190:20:30'", "'190:20:30'", "685230"),
+        ("1:30.5", "'1:30.5'", "'1:30.5'", "90.5"),
+        ("1:75", "'1:75'", "'1:75'", "'1:75'"),
+        ("+1", "1", "'+1'", "1"),
+        ("-0", "0", "0", "0"),
+        ("1.", "1.0", "'1.'", "1.0"),
+        (".5", "0.5", "'.5'", "0.5"),
+        ("0.1", "0.1", "0.1", "0.1"),
+        ("1e3", "1000.0", "1000.0", "1000.0"),
+        ("1E-3", "0.001", "0.001", "0.001"),
+        (".inf", ".inf", "'.inf'", ".inf"),
+        ("-.inf", "-.inf", "'-.inf'", "-.inf"),
+        (".nan", ".nan", "'.nan'", ".nan"),
+    ];
+
+    let profiles = [
+        SchemaProfile::Core12,
+        SchemaProfile::Json,
+        SchemaProfile::Legacy11,
+    ];
+    for (input, core, json, legacy) in corpus {
+        let yaml = format!("v: {}", input);
+        for (profile, expected) in profiles.iter().zip([core, json, legacy]) {
+            let builder = DeserializerBuilder::new().schema(*profile);
+            let value: Value = builder.from_str(&yaml).unwrap();
+            let expected: Value = serde_yaml::from_str(expected).unwrap();
+            assert_eq!(value["v"], expected, "{:?} under {:?}", input, profile);
+        }
+    }
+
+    // Typed deserialization follows the same resolution.
+    let legacy = DeserializerBuilder::new().schema(SchemaProfile::Legacy11);
+    assert!(legacy.from_str::<bool>("yes").unwrap());
+    assert_eq!(legacy.from_str::<u32>("0755").unwrap(), 0o755);
+    assert_eq!(legacy.from_str::<f64>("0755").unwrap(), 493.0);
+    let json = DeserializerBuilder::new().schema(SchemaProfile::Json);
+    assert!(json.from_str::<bool>("True").is_err());
+    assert!(json.from_str::<i32>("0x1F").is_err());
+    assert!(json.from_str::<f64>(".inf").is_err());
+    assert!(json.from_str::<Option<u8>>("~").is_err());
+    assert_eq!(json.from_str::<Option<u8>>("null").unwrap(), None);
+    assert_eq!(json.from_str::<String>("~").unwrap(), "~");
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server {
+        port: u16,
+        debug: bool,
+    }
+
+    let yaml = "port: '8080'\ndebug: \"true\"\n";
+    assert!(serde_yaml::from_str::<Server>(yaml).is_err());
+    let custom = DeserializerBuilder::new().schema(SchemaProfile::Custom(CoercionFlags {
+        coerce_quoted: true,
+        ..SchemaProfile::Core12.flags()
+    }));
+    let server: Server = custom.from_str(yaml).unwrap();
+    assert_eq!(
+        server,
+        Server {
+            port: 8080,
+            debug: true,
+        },
+    );
+    // Only where a boolean or number is expected.
+    let value: Value = custom.from_str(yaml).unwrap();
+    assert_eq!(value["port"], "8080");
+}
+
+#[test]
+fn test_seed_entry_points() {
+    use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+    use std::marker::PhantomData;
+    use std::ops::Range;
+
+    #[derive(Debug, PartialEq)]
+    struct Record {
+        name: Range<usize>,
+        port: u16,
+    }
+
+    // Reads a sequence of records into a vector and their names into a
+    // string, reusing the allocations of both.
+    struct Reload<'a> {
+        records: &'a mut Vec<Record>,
+        arena: &'a mut String,
+    }
+
+    impl<'de, 'a> DeserializeSeed<'de> for Reload<'a> {
+        type Value = ();
+
+        fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            deserializer.deserialize_seq(self)
+        }
+    }
+
+    impl<'de, 'a> Visitor<'de> for Reload<'a> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a sequence of records")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            self.records.clear();
+            self.arena.clear();
+            while let Some(record) = seq.next_element_seed(RecordSeed(self.arena))? {
+                self.records.push(record);
+            }
+            Ok(())
+        }
+    }
+
+    struct RecordSeed<'a>(&'a mut String);
+
+    impl<'de, 'a> DeserializeSeed<'de> for RecordSeed<'a> {
+        type Value = Record;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Record, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            deserializer.deserialize_map(self)
+        }
+    }
+
+    impl<'de, 'a> Visitor<'de> for RecordSeed<'a> {
+        type Value = Record;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a record")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Record, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut record = Record {
+                name: 0..0,
+                port: 0,
+            };
+            while let Some(field) = map.next_key()? {
+                match field {
+                    Field::Name => {
+                        let start = self.0.len();
+                        map.next_value_seed(PushStr(self.0))?;
+                        record.name = start..self.0.len();
+                    }
+                    Field::Port => record.port = map.next_value()?,
+                }
+            }
+            Ok(record)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "lowercase")]
+    enum Field {
+        Name,
+        Port,
+    }
+
+    // Appends a string to the arena, without borrowing it from the input.
+    struct PushStr<'a>(&'a mut String);
+
+    impl<'de, 'a> DeserializeSeed<'de> for PushStr<'a> {
+        type Value = ();
+
+        fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            deserializer.deserialize_str(self)
+        }
+    }
+
+    impl<'de, 'a> Visitor<'de> for PushStr<'a> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a string")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<(), E> {
+            self.0.push_str(v);
+            Ok(())
+        }
+    }
+
+    let mut records = Vec::with_capacity(4);
+    let mut arena = String::with_capacity(64);
+    let (records_ptr, arena_ptr) = (records.as_ptr(), arena.as_ptr());
+
+    let yaml = "- {name: web, port: 80}\n- {name: api, port: 8080}\n";
+    let seed = Reload {
+        records: &mut records,
+        arena: &mut arena,
+    };
+    serde_yaml::from_str_seed(seed, yaml).unwrap();
+    assert_eq!(arena, "webapi");
+    assert_eq!(
+        records[1],
+        Record {
+            name: 3..6,
+            port: 8080
+        }
+    );
+
+    // Reloading from bytes and from a reader uses the same allocations.
+    let yaml = "- {name: db, port: 5432}\n";
+    let seed = Reload {
+        records: &mut records,
+        arena: &mut arena,
+    };
+    serde_yaml::from_slice_seed(seed, yaml.as_bytes()).unwrap();
+    assert_eq!(
+        records,
+        [Record {
+            name: 0..2,
+            port: 5432
+        }]
+    );
+    assert_eq!((records.as_ptr(), arena.as_ptr()), (records_ptr, arena_ptr));
+
+    let yaml = "- {name: cache, port: 6379}\n- {name: queue, port: 5672}\n";
+    let seed = Reload {
+        records: &mut records,
+        arena: &mut arena,
+    };
+    serde_yaml::from_reader_seed(seed, yaml.as_bytes()).unwrap();
+    assert_eq!(&arena[records[1].name.clone()], "queue");
+    assert_eq!((records.as_ptr(), arena.as_ptr()), (records_ptr, arena_ptr));
+
+    // Each document of a stream.
+    let yaml = "- {name: a, port: 1}\n---\n- {name: b, port: 2}\n- {name: c, port: 3}\n";
+    let mut names = Vec::new();
+    for document in Deserializer::from_str(yaml) {
+        let seed = Reload {
+            records: &mut records,
+            arena: &mut arena,
+        };
+        document.deserialize_seed(seed).unwrap();
+        names.push(arena.clone());
+    }
+    assert_eq!(names, ["a", "bc"]);
+    assert_eq!((records.as_ptr(), arena.as_ptr()), (records_ptr, arena_ptr));
+
+    let seed = Reload {
+        records: &mut records,
+        arena: &mut arena,
+    };
+    let error = serde_yaml::from_str_seed(seed, "- {name: x, port: high}\n").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        ".[0].port: invalid type: string \"high\", expected u16 at line 1 column 19",
+    );
+
+    // Borrowing from the input works from a string but not from a reader.
+    let yaml = "[web, api]";
+    let borrowed = serde_yaml::from_str_seed(PhantomData::<Vec<&str>>, yaml).unwrap();
+    assert_eq!(borrowed, ["web", "api"]);
+    let error = serde_yaml::from_reader_seed(PhantomData::<Vec<&str>>, yaml.as_bytes());
+    assert!(error.is_err());
+}
+
+#[test]
+fn test_core_tags() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Bool(bool),
+        Int(i64),
+        Float(f64),
+        Str(String),
+        Unit(()),
+    }
+
+    fn outcome<T>(yaml: &str) -> String
+    where
+        T: serde::de::DeserializeOwned + Debug,
+    {
+        match serde_yaml::from_str::<T>(yaml) {
+            Ok(value) => format!("{:?}", value),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn via_value<T>(value: &Value) -> String
+    where
+        T: serde::de::DeserializeOwned + Debug,
+    {
+        match serde_yaml::from_value::<T>(value.clone()) {
+            Ok(value) => format!("{:?}", value),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    // An explicit tag decides what a scalar resolves to, whatever its content
+    // and style. Each row is the outcome of deserializing a scalar into a
+    // String, i64, f64, bool, (), an untagged enum and a Value.
+    let matrix = [
+        (
+            "!!str 8080",
+            [
+                "\"8080\"",
+                "invalid type: string \"8080\", expected i64",
+                "invalid type: string \"8080\", expected f64",
+                "invalid type: string \"8080\", expected a boolean",
+                "invalid type: string \"8080\", expected unit",
+                "Str(\"8080\")",
+                "String(\"8080\")",
+            ],
+        ),
+        (
+            "!!str true",
+            [
+                "\"true\"",
+                "invalid type: string \"true\", expected i64",
+                "invalid type: string \"true\", expected f64",
+                "invalid type: string \"true\", expected a boolean",
+                "invalid type: string \"true\", expected unit",
+                "Str(\"true\")",
+                "String(\"true\")",
+            ],
+        ),
+        (
+            "!!str null",
+            [
+                "\"null\"",
+                "invalid type: string \"null\", expected i64",
+                "invalid type: string \"null\", expected f64",
+                "invalid type: string \"null\", expected a boolean",
+                "invalid type: string \"null\", expected unit",
+                "Str(\"null\")",
+                "String(\"null\")",
+            ],
+        ),
+        (
+            "!!str",
+            [
+                "\"\"",
+                "invalid type: string \"\", expected i64",
+                "invalid type: string \"\", expected f64",
+                "invalid type: string \"\", expected a boolean",
+                "invalid type: string \"\", expected unit",
+                "Str(\"\")",
+                "String(\"\")",
+            ],
+        ),
+        (
+            "!!int 8080",
+            [
+                "invalid type: integer `8080`, expected a string",
+                "8080",
+                "8080.0",
+                "invalid type: integer `8080`, expected a boolean",
+                "invalid type: integer `8080`, expected unit",
+                "Int(8080)",
+                "Number(8080)",
+            ],
+        ),
+        (
+            "!!int '0x1F'",
+            [
+                "invalid type: integer `31`, expected a string",
+                "31",
+                "31.0",
+                "invalid type: integer `31`, expected a boolean",
+                "invalid type: integer `31`, expected unit",
+                "Int(31)",
+                "Number(31)",
+            ],
+        ),
+        (
+            "!!int abc",
+            [
+                "invalid value: string \"abc\", expected an integer",
+                "invalid value: string \"abc\", expected an integer",
+                "invalid value: string \"abc\", expected an integer",
+                "invalid value: string \"abc\", expected an integer",
+                "invalid value: string \"abc\", expected an integer",
+                "invalid value: string \"abc\", expected an integer",
+                "invalid value: string \"abc\", expected an integer",
+            ],
+        ),
+        (
+            "!!float 1",
+            [
+                "invalid type: floating point `1.0`, expected a string",
+                "invalid type: floating point `1`, expected i64",
+                "1.0",
+                "invalid type: floating point `1.0`, expected a boolean",
+                "invalid type: floating point `1.0`, expected unit",
+                "Float(1.0)",
+                "Number(1.0)",
+            ],
+        ),
+        (
+            "!!float .inf",
+            [
+                "invalid type: floating point `inf`, expected a string",
+                "invalid type: floating point `.inf`, expected i64",
+                "inf",
+                "invalid type: floating point `inf`, expected a boolean",
+                "invalid type: floating point `inf`, expected unit",
+                "Float(inf)",
+                "Number(.inf)",
+            ],
+        ),
+        (
+            "!!float abc",
+            [
+                "invalid value: string \"abc\", expected a float",
+                "invalid value: string \"abc\", expected a float",
+                "invalid value: string \"abc\", expected a float",
+                "invalid value: string \"abc\", expected a float",
+                "invalid value: string \"abc\", expected a float",
+                "invalid value: string \"abc\", expected a float",
+                "invalid value: string \"abc\", expected a float",
+            ],
+        ),
+        (
+            "!!bool true",
+            [
+                "invalid type: boolean `true`, expected a string",
+                "invalid type: boolean `true`, expected i64",
+                "invalid type: boolean `true`, expected f64",
+                "true",
+                "invalid type: boolean `true`, expected unit",
+                "Bool(true)",
+                "Bool(true)",
+            ],
+        ),
+        (
+            "!!bool \"false\"",
+            [
+                "invalid type: boolean `false`, expected a string",
+                "invalid type: boolean `false`, expected i64",
+                "invalid type: boolean `false`, expected f64",
+                "false",
+                "invalid type: boolean `false`, expected unit",
+                "Bool(false)",
+                "Bool(false)",
+            ],
+        ),
+        (
+            "!!bool yes",
+            [
+                "invalid value: string \"yes\", expected a boolean",
+                "invalid value: string \"yes\", expected a boolean",
+                "invalid value: string \"yes\", expected a boolean",
+                "invalid value: string \"yes\", expected a boolean",
+                "invalid value: string \"yes\", expected a boolean",
+                "invalid value: string \"yes\", expected a boolean",
+                "invalid value: string \"yes\", expected a boolean",
+            ],
+        ),
+        (
+            "!!null null",
+            [
+                "invalid type: unit value, expected a string",
+                "invalid type: unit value, expected i64",
+                "invalid type: unit value, expected f64",
+                "invalid type: unit value, expected a boolean",
+                "()",
+                "Unit(())",
+                "Null",
+            ],
+        ),
+        (
+            "!!null ~",
+            [
+                "invalid type: unit value, expected a string",
+                "invalid type: unit value, expected i64",
+                "invalid type: unit value, expected f64",
+                "invalid type: unit value, expected a boolean",
+                "()",
+                "Unit(())",
+                "Null",
+            ],
+        ),
+        (
+            "!!null ''",
+            [
+                "invalid value: string \"\", expected null",
+                "invalid value: string \"\", expected null",
+                "invalid value: string \"\", expected null",
+                "invalid value: string \"\", expected null",
+                "invalid value: string \"\", expected null",
+                "invalid value: string \"\", expected null",
+                "invalid value: string \"\", expected null",
+            ],
+        ),
+    ];
+    for (yaml, expected) in matrix {
+        let found = [
+            outcome::<String>(yaml),
+            outcome::<i64>(yaml),
+            outcome::<f64>(yaml),
+            outcome::<bool>(yaml),
+            outcome::<()>(yaml),
+            outcome::<Untagged>(yaml),
+            outcome::<Value>(yaml),
+        ];
+        assert_eq!(found, expected, "{}", yaml);
+
+        // Going through a Value resolves the same way.
+        if let Ok(value) = serde_yaml::from_str::<Value>(yaml) {
+            let mut via_value = [
+                via_value::<String>(&value),
+                via_value::<i64>(&value),
+                via_value::<f64>(&value),
+                via_value::<bool>(&value),
+                via_value::<()>(&value),
+                via_value::<Untagged>(&value),
+                via_value::<Value>(&value),
+            ];
+            // Except that a float read as an integer is named as it was
+            // written, which a Value does not keep.
+            if yaml.starts_with("!!float") {
+                via_value[1] = found[1].clone();
+            }
+            assert_eq!(via_value, found, "{}", yaml);
+        }
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server {
+        port: String,
+        debug: Untagged,
+    }
+
+    let yaml = "port: !!str 8080\ndebug: !!str true\n";
+    let server: Server = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(server.port, "8080");
+    assert_eq!(server.debug, Untagged::Str("true".to_owned()));
+
+    // Kept in a Value for writing out again.
+    let yaml = indoc! {"
+        port: !!str 8080
+        debug: !!str true
+        empty: !!str
+        quoted: !!str ' 8080'
+        replicas: !!int 3
+        ratio: !!float 1.5
+        enabled: !!bool true
+        nothing: !!null null
+        list:
+        - !!str null
+        - !!str 1.5
+        - !Local 1
+        !!str 1: one
+    "};
+    let preserving = DeserializerBuilder::new().preserve_core_tags(true);
+    let value: Value = preserving.from_str(yaml).unwrap();
+    assert_eq!(value["port"].as_str(), Some("8080"));
+    assert_eq!(value["replicas"].as_u64(), Some(3));
+    assert_eq!(
+        format!("{:?}", value["port"]),
+        "TaggedValue { tag: !tag:yaml.org,2002:str, value: String(\"8080\") }",
+    );
+    assert_eq!(
+        serde_yaml::from_value::<u64>(value["replicas"].clone()).unwrap(),
+        3
+    );
+    assert!(serde_yaml::from_value::<u64>(value["port"].clone()).is_err());
+    let serialized = serde_yaml::SerializerBuilder::new()
+        .verify_roundtrip(true)
+        .to_string(&value)
+        .unwrap();
+    assert_eq!(serialized, yaml);
+    assert_eq!(preserving.from_str::<Value>(&serialized).unwrap(), value);
+
+    // Without the option, the tags are applied and then dropped.
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(value["port"], "8080");
+    assert!(serde_yaml::to_string(&value)
+        .unwrap()
+        .starts_with("port: '8080'\n"));
+}
+
+#[test]
+fn test_float_int_boundaries() {
+    // Below a key, so that the error has a location.
+    fn error<T>(builder: &DeserializerBuilder, yaml: &str) -> String
+    where
+        T: serde::de::DeserializeOwned + Debug,
+    {
+        let yaml = format!("n: {}", yaml);
+        let err = builder.clone().from_str::<BTreeMap<String, T>>(&yaml);
+        err.unwrap_err().to_string()
+    }
+
+    let default = DeserializerBuilder::new();
+    let strict = DeserializerBuilder::new().strict_numbers(true);
+    let lenient = DeserializerBuilder::new().integral_floats(true);
+
+    // Integers read as floats round to the nearest one, unless strict.
+    let float: f64 = default.clone().from_str("9007199254740992").unwrap();
+    assert_eq!(float, 9007199254740992.0);
+    let float: f64 = default.clone().from_str("9007199254740993").unwrap();
+    assert_eq!(float, 9007199254740992.0);
+    let float: f64 = strict.clone().from_str("9007199254740992").unwrap();
+    assert_eq!(float, 9007199254740992.0);
+    assert_eq!(
+        error::<f64>(&strict, "9007199254740993"),
+        "n: integer `9007199254740993` cannot be represented exactly as f64 at line 1 column 4",
+    );
+    assert_eq!(
+        error::<f64>(&strict, "18446744073709551615"),
+        "n: integer `18446744073709551615` cannot be represented exactly as f64 at line 1 column 4",
+    );
+    assert_eq!(
+        error::<f64>(&strict, "!!int -9007199254740993"),
+        "n: integer `-9007199254740993` cannot be represented exactly as f64 at line 1 column 4",
+    );
+    assert_eq!(
+        error::<f32>(&strict, "16777217"),
+        "n: integer `16777217` cannot be represented exactly as f32 at line 1 column 4",
+    );
+    let float: f32 = strict.clone().from_str("16777216").unwrap();
+    assert_eq!(float, 16777216.0);
+
+    // Floats read as integers only if they are equal to one in range.
+    assert_eq!(
+        error::<u64>(&default, "1e2"),
+        "n: invalid type: floating point `1e2`, expected u64 at line 1 column 4",
+    );
+    assert_eq!(lenient.clone().from_str::<u64>("1e2").unwrap(), 100);
+    assert_eq!(lenient.clone().from_str::<i64>("!!float 7").unwrap(), 7);
+    assert_eq!(
+        error::<u64>(&lenient, "1.5"),
+        "n: invalid type: floating point `1.5`, expected u64 at line 1 column 4",
+    );
+    assert_eq!(
+        error::<i64>(&lenient, ".nan"),
+        "n: invalid type: floating point `.nan`, expected i64 at line 1 column 4",
+    );
+    for builder in [&default, &lenient] {
+        assert_eq!(
+            error::<Vec<u64>>(builder, "[1e20]"),
+            "n[0]: float `1e20` is out of range for u64 at line 1 column 5",
+        );
+        assert_eq!(
+            error::<u64>(builder, "-1.0"),
+            "n: float `-1.0` is out of range for u64 at line 1 column 4",
+        );
+        assert_eq!(
+            error::<i64>(builder, "9223372036854775808.0"),
+            "n: float `9223372036854775808.0` is out of range for i64 at line 1 column 4",
+        );
+    }
+    assert_eq!(
+        lenient
+            .clone()
+            .from_str::<i64>("-9223372036854775808.0")
+            .unwrap(),
+        i64::MIN,
+    );
+
+    // Negative zero keeps its sign only when it is written as a float.
+    let float: f64 = default.clone().from_str("-0.0").unwrap();
+    assert!(float == 0.0 && float.is_sign_negative());
+    let float: f64 = default.clone().from_str("-0").unwrap();
+    assert!(float == 0.0 && float.is_sign_positive());
+    assert_eq!(lenient.clone().from_str::<u64>("-0.0").unwrap(), 0);
+}
+
+#[test]
+fn test_document_source() {
+    let yaml = indoc! {"
+        # leading comment
+        name: first
+        script: |
+          echo start
+          ---
+          echo end
+        ---
+        quoted: '
+          --- not a marker'
+        double: \"a
+          --- b\"
+        ...
+        --- >
+          folded
+          ---
+        --- [a, '---']
+    "};
+    let expected = [
+        "name: first\nscript: |\n  echo start\n  ---\n  echo end\n",
+        "---\nquoted: '\n  --- not a marker'\ndouble: \"a\n  --- b\"\n...",
+        "--- >\n  folded\n  ---\n",
+        "--- [a, '---']\n",
+    ];
+    assert_eq!(Deserializer::from_str(yaml).count(), expected.len());
+    for (document, expected) in Deserializer::from_str(yaml).zip(expected) {
+        let marked = document.source(true).unwrap();
+        let content = document.source(false).unwrap();
+        assert_eq!(marked, expected);
+        let value: Value = serde::Deserialize::deserialize(document).unwrap();
+        assert_eq!(serde_yaml::from_str::<Value>(marked).unwrap(), value);
+        assert_eq!(serde_yaml::from_str::<Value>(content).unwrap(), value);
+    }
+
+    let contents: Vec<&str> = Deserializer::from_slice(b"--- a\n--- b\n")
+        .map(|document| document.source(false).unwrap())
+        .collect();
+    assert_eq!(contents, ["a\n", "b\n"]);
+
+    // Only documents from the iterator of borrowed input have a source.
+    assert_eq!(Deserializer::from_str("a: 1").source(true), None);
+    let mut documents = Deserializer::from_reader(&b"a: 1"[..]);
+    assert_eq!(documents.next().unwrap().source(true), None);
+}
+
+#[test]
+fn test_from_reader_incremental() {
+    // A stream of documents that never ends, which could not be read whole.
+    struct Endless {
+        documents: usize,
+        pending: Vec<u8>,
+    }
+
+    impl std::io::Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.pending.is_empty() {
+                self.documents += 1;
+                self.pending = format!("---\nn: {}\n", self.documents).into_bytes();
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Document {
+        n: usize,
+    }
+
+    let endless = Endless {
+        documents: 0,
+        pending: Vec::new(),
+    };
+    let documents: Vec<Document> = Deserializer::from_reader(endless)
+        .take(3)
+        .map(|document| <Document as serde::Deserialize>::deserialize(document).unwrap())
+        .collect();
+    assert_eq!(
+        documents,
+        [Document { n: 1 }, Document { n: 2 }, Document { n: 3 }],
+    );
+
+    // Errors in later documents are where they are in the whole input.
+    let yaml = "---\na: 1\n---\nb: [\n";
+    let mut documents = Deserializer::from_reader(yaml.as_bytes());
+    <Value as serde::Deserialize>::deserialize(documents.next().unwrap()).unwrap();
+    let error = <Value as serde::Deserialize>::deserialize(documents.next().unwrap()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "did not find expected node content at line 5 column 1, while parsing a flow node",
+    );
+
+    let mut yaml = format!("---\na: {0}\n---\nb: {0}", "x".repeat(20000)).into_bytes();
+    yaml.extend_from_slice(b"\xFF\n");
+    let mut documents = Deserializer::from_reader(&yaml[..]);
+    <Value as serde::Deserialize>::deserialize(documents.next().unwrap()).unwrap();
+    let error = <Value as serde::Deserialize>::deserialize(documents.next().unwrap()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid leading UTF-8 octet 0xFF at line 4 column 20004 (byte 40015)"
+    );
+    assert_eq!(error.context_snippet(10), Some("xxxxxxx\u{fffd}"));
+}
+
+#[test]
+fn test_utf16_utf32() {
+    fn encode(yaml: &str, bom: bool, unit: impl Fn(u32) -> Vec<u8>, utf16: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        if bom {
+            bytes.extend(unit(0xFEFF));
+        }
+        for ch in yaml.chars() {
+            if utf16 {
+                for u in ch.encode_utf16(&mut [0; 2]) {
+                    bytes.extend(unit(u32::from(*u)));
+                }
+            } else {
+                bytes.extend(unit(u32::from(ch)));
+            }
+        }
+        bytes
+    }
+
+    let yaml = "k: v\nemoji: \u{1F600}\n";
+    let mut expected = BTreeMap::new();
+    expected.insert("k".to_owned(), "v".to_owned());
+    expected.insert("emoji".to_owned(), "\u{1F600}".to_owned());
+
+    let le16 = |u: u32| (u as u16).to_le_bytes().to_vec();
+    let be16 = |u: u32| (u as u16).to_be_bytes().to_vec();
+    let le32 = |u: u32| u.to_le_bytes().to_vec();
+    let be32 = |u: u32| u.to_be_bytes().to_vec();
+    for bom in [true, false] {
+        for bytes in [
+            encode(yaml, bom, le16, true),
+            encode(yaml, bom, be16, true),
+            encode(yaml, bom, le32, false),
+            encode(yaml, bom, be32, false),
+        ] {
+            let map: BTreeMap<String, String> = serde_yaml::from_slice(&bytes).unwrap();
+            assert_eq!(map, expected);
+            let map: BTreeMap<String, String> = serde_yaml::from_reader(&bytes[..]).unwrap();
+            assert_eq!(map, expected);
+        }
+    }
+
+    // A low surrogate without the high one before it.
+    let mut bytes = encode("k: v", true, le16, true);
+    bytes.extend_from_slice(&[0x00, 0xDC]);
+    let error = serde_yaml::from_slice::<Value>(&bytes).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid UTF-16LE at byte 10 of the input"
+    );
+    let error = serde_yaml::from_reader::<_, Value>(&bytes[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid UTF-16LE at byte 10 of the input"
+    );
+
+    // Half of a character at the end.
+    let mut bytes = encode("k: v", false, be32, false);
+    bytes.pop();
+    let error = serde_yaml::from_slice::<Value>(&bytes).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid UTF-32BE at byte 12 of the input"
+    );
+}
+
+#[test]
+fn test_from_str_multi() {
+    let yaml = "---\na: 1\n---\nb: 2\n...\n---\n";
+    let documents: Vec<Option<BTreeMap<String, u8>>> = serde_yaml::from_str_multi(yaml).unwrap();
+    let mut a = BTreeMap::new();
+    a.insert("a".to_owned(), 1);
+    let mut b = BTreeMap::new();
+    b.insert("b".to_owned(), 2);
+    assert_eq!(documents, [Some(a), Some(b), None]);
+
+    let documents: Vec<i32> = serde_yaml::from_str_multi("1").unwrap();
+    assert_eq!(documents, [1]);
+    for yaml in ["", "# only a comment\n"] {
+        let documents: Vec<i32> = serde_yaml::from_str_multi(yaml).unwrap();
+        assert!(documents.is_empty());
+    }
+
+    // The first error, which can be one of the parser in a later document.
+    let yaml = "--- 1\n--- x\n--- [\n";
+    let error = serde_yaml::from_str_multi::<i32>(yaml).unwrap_err();
+    assert_eq!(error.location().unwrap().line(), 2);
+    let error = serde_yaml::from_str_multi::<Value>(yaml).unwrap_err();
+    assert_eq!(error.location().unwrap().line(), 4);
+}
+
+#[test]
+fn test_builder_limits_and_duplicate_keys() {
+    let builder = Deserializer::builder().duplicate_keys(DuplicateKeyPolicy::Error);
+
+    // The same keys in different mappings, values and tags are not duplicates.
+    let yaml = indoc! {"
+        - {a: 1, b: a}
+        - {a: 2, !x a: 3, 'x': [a, a]}
+        - base: &base {a: 1}
+          <<: *base
+    "};
+    builder.from_str::<Value>(yaml).unwrap();
+
+    let yaml = indoc! {"
+        outer:
+          inner: {a: 1, b: 2}
+          inner: {}
+    "};
+    let err = builder.from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(err.to_string(), "duplicate key `inner` at line 3 column 3");
+
+    // Quoting a key does not make it another one.
+    let err = builder.from_str::<Value>("[{a: 1, 'a': 2}]").unwrap_err();
+    assert_eq!(err.to_string(), "duplicate key `a` at line 1 column 9");
+
+    let nested = format!("{}{}", "[".repeat(200), "]".repeat(200));
+    serde_yaml::from_str::<Value>(&nested).unwrap_err();
+    let builder = Deserializer::builder().recursion_limit(300);
+    builder.from_str::<Value>(&nested).unwrap();
+    let builder = Deserializer::builder().recursion_limit(0);
+    assert_eq!(builder.from_str::<u8>("1").unwrap(), 1);
+    builder.from_str::<Vec<u8>>("[]").unwrap_err();
+}
+
+#[test]
+fn test_duplicate_key_policy() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Service {
+        name: String,
+        port: u16,
+    }
+
+    let yaml = indoc! {"
+        name: web
+        port: 80
+        name: &name db
+        port: 5432
+    "};
+    let err = serde_yaml::from_str::<Service>(yaml).unwrap_err();
+    assert_eq!(err.to_string(), "duplicate field `name`");
+
+    let builder = Deserializer::builder().duplicate_keys(DuplicateKeyPolicy::FirstWins);
+    let first: Service = builder.from_str(yaml).unwrap();
+    let expected = Service {
+        name: "web".to_owned(),
+        port: 80,
+    };
+    assert_eq!(first, expected);
+
+    let builder = Deserializer::builder().duplicate_keys(DuplicateKeyPolicy::LastWins);
+    let last: Service = builder.from_str(yaml).unwrap();
+    let expected = Service {
+        name: "db".to_owned(),
+        port: 5432,
+    };
+    assert_eq!(last, expected);
+
+    // The kept entry is where the last one was, and an anchor in a skipped
+    // entry can still be referred to.
+    let yaml = indoc! {"
+        a: &x 1
+        b: 2
+        a: 3
+        c: {d: *x, d: 4}
+    "};
+    let value: Value = builder.from_str(yaml).unwrap();
+    let expected: Value = serde_yaml::from_str("{b: 2, a: 3, c: {d: 4}}").unwrap();
+    assert_eq!(value, expected);
+    let serialized = serde_yaml::to_string(&value).unwrap();
+    assert_eq!(serialized, "b: 2\na: 3\nc:\n  d: 4\n");
+
+    let builder = Deserializer::builder().duplicate_keys(DuplicateKeyPolicy::FirstWins);
+    let value: Value = builder.from_str(yaml).unwrap();
+    let expected: Value = serde_yaml::from_str("{a: 1, b: 2, c: {d: 1}}").unwrap();
+    assert_eq!(value, expected);
+
+    // Keys are compared by what they resolve to, so the string '1' is not the
+    // integer 1, but 0x1 is.
+    let distinct = "'1': a\n1: b\n";
+    let expected: Value = serde_yaml::from_str(distinct).unwrap();
+    assert_eq!(expected.as_mapping().unwrap().len(), 2);
+    let same = "1: a\n0x1: b\n";
+    for policy in [
+        DuplicateKeyPolicy::Error,
+        DuplicateKeyPolicy::FirstWins,
+        DuplicateKeyPolicy::LastWins,
+    ] {
+        let builder = Deserializer::builder().duplicate_keys(policy);
+        let value: Value = builder.from_str(distinct).unwrap();
+        assert_eq!(value, expected);
+        let result = builder.from_str::<Value>(same);
+        let kept = match policy {
+            DuplicateKeyPolicy::Error => {
+                let err = result.unwrap_err();
+                assert_eq!(err.to_string(), "duplicate key `0x1` at line 2 column 1");
+                continue;
+            }
+            DuplicateKeyPolicy::FirstWins => "a",
+            _ => "b",
+        };
+        let mut expected = serde_yaml::Mapping::new();
+        expected.insert(Value::from(1), Value::from(kept));
+        assert_eq!(result.unwrap(), Value::Mapping(expected));
+    }
+}
+
+#[test]
+fn test_merge_keys() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Point {
+        x: i32,
+        y: i32,
+        r: i32,
+    }
+
+    let yaml = indoc! {"
+        - &center {x: 1, y: 2}
+        - &left {x: 0}
+        - &big {r: 10}
+        - &small {r: 1}
+        - # Explicit keys
+          x: 1
+          y: 2
+          r: 10
+        - # Merge one map
+          <<: *center
+          r: 10
+        - # Merge multiple maps
+          <<: [*center, *big]
+        - # Override
+          <<: [*big, *left, *small]
+          x: 1
+          y: 2
+    "};
+    let builder = Deserializer::builder().merge_keys(true);
+    let points: Vec<Value> = builder.clone().from_str(yaml).unwrap();
+    let expected = Point { x: 1, y: 2, r: 10 };
+    for point in &points[4..] {
+        let point = <Point as serde::Deserialize>::deserialize(point).unwrap();
+        assert_eq!(point, expected);
+    }
+
+    // The same as merging into a Value afterwards.
+    let mut merged: Value = serde_yaml::from_str(yaml).unwrap();
+    merged.apply_merge().unwrap();
+    assert_eq!(Value::Sequence(points), merged);
+
+    // Directly into a struct, with a mapping of its own, and merges of
+    // mappings that have merged others.
+    let yaml = indoc! {"
+        base: &base {x: 1, y: 1}
+        more: &more
+          <<: *base
+          y: 2
+        point:
+          <<: [*more, {r: 10}]
+    "};
+    #[derive(Deserialize, Debug)]
+    struct Doc {
+        point: Point,
+    }
+    let doc: Doc = builder.clone().from_str(yaml).unwrap();
+    assert_eq!(doc.point, expected);
+
+    // Merged keys are not duplicates.
+    let builder = builder.duplicate_keys(DuplicateKeyPolicy::Error);
+    let doc: Doc = builder.clone().from_str(yaml).unwrap();
+    assert_eq!(doc.point, expected);
+
+    // Without the option, `<<` is a key like any other, as is a quoted one
+    // with it.
+    let err = serde_yaml::from_str::<Doc>(yaml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "point: missing field `x` at line 6 column 3"
+    );
+    let value: Value = builder.from_str("a: 1\n'<<': {b: 2}\n").unwrap();
+    assert_eq!(value["<<"]["b"], 2);
+}
+
+#[test]
+fn test_merge_keys_errors() {
+    let builder = Deserializer::builder().merge_keys(true);
+    let cases = [
+        ("a: 1\n<<: 2\n", "expected a mapping or list of mappings for merging, but found scalar at line 2 column 5"),
+        ("a: 1\n<<: [{b: 2}, 3]\n", "expected a mapping for merging, but found scalar at line 2 column 5"),
+        ("a: 1\n<<: [[b]]\n", "expected a mapping for merging, but found sequence at line 2 column 5"),
+        ("a: 1\n<<: !tag {b: 2}\n", "unexpected tagged value in merge at line 2 column 5"),
+        ("&a {<<: *a}\n", "recursion limit exceeded at line 1 column 9"),
+    ];
+    for (yaml, expected) in cases {
+        let err = builder.clone().from_str::<Value>(yaml).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{}", yaml);
+    }
+}
+
+#[test]
+fn test_binary_into_bytes() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Blob {
+        vec: Vec<u8>,
+        array: [u8; 3],
+        alias: Vec<u8>,
+        seq: Vec<u8>,
+    }
+
+    let yaml = indoc! {"
+        vec: &vec !!binary |
+          aGVsbG8g
+          d29ybGQ=
+        array: !!binary ////
+        alias: *vec
+        seq: [1, 2]
+    "};
+    let expected = Blob {
+        vec: b"hello world".to_vec(),
+        array: [0xFF; 3],
+        alias: b"hello world".to_vec(),
+        seq: vec![1, 2],
+    };
+    assert_eq!(expected, serde_yaml::from_str::<Blob>(yaml).unwrap());
+
+    let err = serde_yaml::from_str::<Vec<u8>>("!!binary aGk").unwrap_err();
+    let expected = "invalid value: string \"aGk\", expected base64-encoded binary data";
+    assert_eq!(err.to_string(), expected);
+    let err = serde_yaml::from_str::<[u8; 2]>("!!binary YWJj").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid length 3, expected 2 elements in sequence"
+    );
+
+    // The decoded bytes cannot be borrowed from the input.
+    let err = serde_yaml::from_str::<&[u8]>("!!binary YWJj").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: byte array, expected a borrowed byte array"
+    );
+
+    // Any other scalar is not a sequence of bytes.
+    let err = serde_yaml::from_str::<Vec<u8>>("YWJj").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"YWJj\", expected a sequence"
+    );
+}
+
+#[test]
+fn test_spanned() {
+    #[derive(Deserialize, Debug)]
+    struct Config {
+        name: Spanned<String>,
+        ports: Spanned<Vec<Spanned<u16>>>,
+        limits: Spanned<BTreeMap<String, u32>>,
+        backup: Option<Spanned<String>>,
+        alias: Spanned<String>,
+    }
+
+    let yaml = indoc! {"
+        name: &name web
+        ports:
+          - 80
+          - !!int 443
+        limits: {cpu: 2, memory: 512}
+        backup: null
+        alias: *name
+    "};
+    let config: Config = serde_yaml::from_str(yaml).unwrap();
+    let text = |spanned_start: &Location, spanned_end: &Location| {
+        &yaml[spanned_start.index()..spanned_end.index()]
+    };
+
+    assert_eq!(config.name.value, "web");
+    assert_eq!(
+        (config.name.start.line(), config.name.start.column()),
+        (1, 7)
+    );
+    assert_eq!(text(&config.name.start, &config.name.end), "&name web");
+
+    let ports = &config.ports;
+    assert_eq!((ports.start.line(), ports.start.column()), (3, 3));
+    assert_eq!((ports.end.line(), ports.end.column()), (4, 14));
+    assert_eq!(text(&ports.start, &ports.end), "- 80\n  - !!int 443");
+    assert_eq!(ports.value[0].value, 80);
+    assert_eq!(text(&ports.value[0].start, &ports.value[0].end), "80");
+    assert_eq!(
+        text(&ports.value[1].start, &ports.value[1].end),
+        "!!int 443"
+    );
+
+    let limits = &config.limits;
+    assert_eq!(text(&limits.start, &limits.end), "{cpu: 2, memory: 512}");
+    assert_eq!(limits.value["memory"], 512);
+
+    assert!(config.backup.is_none());
+
+    // An alias is where it is written.
+    assert_eq!(config.alias.value, "web");
+    assert_eq!(config.alias.start.line(), 7);
+    assert_eq!(text(&config.alias.start, &config.alias.end), "*name");
+
+    // An error in the value is at its own location.
+    let err = serde_yaml::from_str::<Config>("name: [x]\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "name: invalid type: sequence, expected a string at line 1 column 7",
+    );
+
+    // Serialized as the value alone.
+    let serialized = serde_yaml::to_string(&config.limits).unwrap();
+    assert_eq!(serialized, "cpu: 2\nmemory: 512\n");
+
+    // Other deserializers do not know where the value is.
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    let err = serde_yaml::from_value::<Spanned<Value>>(value).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Spanned can only be deserialized by serde_yaml::Deserializer",
+    );
+}
+
+#[test]
+fn test_on_ignored_key() {
+    #[derive(Deserialize, Debug)]
+    struct Dependency {
+        version: String,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Manifest {
+        name: String,
+        dependencies: BTreeMap<String, Dependency>,
+    }
+
+    fn ignored<T>(
+        builder: DeserializerBuilder,
+        yaml: &str,
+    ) -> (serde_yaml::Result<T>, Vec<(String, usize, usize)>)
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let builder = builder.on_ignored_key({
+            let reports = Arc::clone(&reports);
+            move |path, location| {
+                let report = (path.to_owned(), location.line(), location.column());
+                reports.lock().unwrap().push(report);
+            }
+        });
+        let result = builder.from_str(yaml);
+        let reports = reports.lock().unwrap().clone();
+        (result, reports)
+    }
+
+    let yaml = indoc! {"
+        name: app
+        edition: 2021
+        dependencies:
+          serde:
+            version: '1'
+            featrues: [derive]
+          log: {version: '0.4', optional: true}
+    "};
+    let (manifest, reports) = ignored::<Manifest>(Deserializer::builder(), yaml);
+    let manifest = manifest.unwrap();
+    assert_eq!(manifest.name, "app");
+    assert_eq!(manifest.dependencies["log"].version, "0.4");
+    assert_eq!(
+        reports,
+        [
+            ("edition".to_owned(), 2, 1),
+            ("dependencies.serde.featrues".to_owned(), 6, 5),
+            ("dependencies.log.optional".to_owned(), 7, 25),
+        ],
+    );
+
+    // Entries that are deserialized into a map are not ignored.
+    let (value, reports) = ignored::<Value>(Deserializer::builder(), yaml);
+    value.unwrap();
+    assert!(reports.is_empty());
+
+    // With max_errors, each entry is reported once.
+    let yaml = indoc! {"
+        - extra: 1
+          version: [1]
+        - version: '2'
+          extra: 2
+        - version: {}
+    "};
+    let builder = Deserializer::builder().max_errors(10);
+    let (result, reports) = ignored::<Vec<Dependency>>(builder, yaml);
+    assert_eq!(result.unwrap_err().errors().len(), 2);
+    assert_eq!(
+        reports,
+        [
+            (".[0].extra".to_owned(), 1, 3),
+            (".[1].extra".to_owned(), 4, 3),
+        ],
+    );
+}
+
+#[test]
+fn test_ignore_unknown_tags() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Action {
+        Stop,
+        Retry(u8),
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Task {
+        name: String,
+        timeout: Option<u32>,
+        hosts: Vec<String>,
+        vars: BTreeMap<String, i32>,
+        on_error: Action,
+        on_timeout: Action,
+    }
+
+    let yaml = indoc! {"
+        name: !vault deploy
+        timeout: !vendor ~
+        hosts: !inventory [a, b]
+        vars: !!vendor/vars {x: !vendor 1}
+        on_error: !Retry 3
+        on_timeout: !vendor Stop
+    "};
+    let builder = Deserializer::builder().ignore_unknown_tags(true);
+    let task: Task = builder.from_str(yaml).unwrap();
+    let expected = Task {
+        name: "deploy".to_owned(),
+        timeout: None,
+        hosts: vec!["a".to_owned(), "b".to_owned()],
+        vars: BTreeMap::from([("x".to_owned(), 1)]),
+        on_error: Action::Retry(3),
+        on_timeout: Action::Stop,
+    };
+    assert_eq!(task, expected);
+
+    // By default the tag is the variant.
+    let err = serde_yaml::from_str::<Task>(yaml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "timeout: invalid type: unit value, expected u32 at line 2 column 10",
+    );
+
+    // A Value has the nodes without their tag, but keeps the core schema.
+    let builder = Deserializer::builder().ignore_unknown_tags(true);
+    let value: Value = builder.from_str("[!vault ~, !!str 1, !Retry 3]").unwrap();
+    assert_eq!(value, serde_yaml::from_str::<Value>("[~, '1', 3]").unwrap());
+
+    // A collection with a tag that is not a variant is no enum.
+    let builder = Deserializer::builder().ignore_unknown_tags(true);
+    let err = builder.from_str::<Action>("!vendor [3]").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: sequence, expected a YAML tag starting with '!'",
+    );
+}
+
+#[test]
+fn test_construct_tag() {
+    use serde::de::Error as _;
+    use serde_yaml::value::{Tag, TaggedValue};
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Port {
+        Fixed(u16),
+        Any,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Service {
+        name: String,
+        hosts: Vec<String>,
+        port: Port,
+        replicas: Vec<u32>,
+    }
+
+    let builder = Deserializer::builder()
+        .construct_tag("!upper", |tagged| match tagged.value {
+            Value::String(s) => Ok(Value::String(s.to_uppercase())),
+            _ => Err(serde_yaml::Error::custom("expected a string")),
+        })
+        .construct_tag("join", |tagged| match tagged.value {
+            Value::Sequence(parts) => {
+                let parts = parts.iter().map(|part| part.as_str().unwrap_or_default());
+                Ok(Value::String(parts.collect::<Vec<_>>().join(".")))
+            }
+            _ => Err(serde_yaml::Error::custom("expected a sequence")),
+        })
+        .construct_tag("!port", |tagged| {
+            let tag = if tagged.value.is_null() {
+                "Any"
+            } else {
+                "Fixed"
+            };
+            let value = Value::Number(tagged.value.as_u64().unwrap_or(0).into());
+            Ok(Value::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(tag),
+                value,
+            })))
+        })
+        .construct_tag("!count", |tagged| {
+            let n = tagged.value.as_u64().unwrap_or(0);
+            Ok(Value::Sequence((0..n).map(Value::from).collect()))
+        });
+
+    // The nodes inside of a node are constructed first, and an alias to a
+    // constructed node is its value.
+    let yaml = indoc! {"
+        name: &name !join [!upper web, example]
+        hosts: [*name, !upper db]
+        port: !port 8080
+        replicas: !count 3
+    "};
+    let service: Service = builder.from_str(yaml).unwrap();
+    let expected = Service {
+        name: "WEB.example".to_owned(),
+        hosts: vec!["WEB.example".to_owned(), "DB".to_owned()],
+        port: Port::Fixed(8080),
+        replicas: vec![0, 1, 2],
+    };
+    assert_eq!(service, expected);
+
+    // A string that a constructor returns stays a string.
+    let value: Value = builder.from_str("!upper 'true'").unwrap();
+    assert_eq!(value, Value::from("TRUE"));
+
+    // An error is at the node.
+    let yaml = "name: x\nhosts: [!upper [a]]\n";
+    let err = builder.from_str::<Service>(yaml).unwrap_err();
+    assert_eq!(err.to_string(), "expected a string at line 2 column 9");
+
+    // Anchors inside of a constructed node are gone.
+    let yaml = "[!join [&a x, y], *a]";
+    let err = builder.from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(err.to_string(), "unknown anchor at line 1 column 19",);
+
+    // Tags without a constructor are as before.
+    let value: Value = builder.from_str("!other x").unwrap();
+    assert_eq!(value, serde_yaml::from_str::<Value>("!other x").unwrap());
+}
+
+#[test]
+fn test_from_str_at() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Container {
+        image: String,
+        ports: Vec<u16>,
+    }
+
+    let yaml = indoc! {"
+        defaults: &defaults
+          image: base
+          ports: [80]
+        spec:
+          containers:
+            - image: web
+              ports: [80]
+            - *defaults
+          matrix: [[1, 2], [3, 4]]
+          name: first
+          name: second
+        ignored: [oops: not a container]
+    "};
+    let container: Container = serde_yaml::from_str_at(yaml, "spec.containers[0]").unwrap();
+    let expected = Container {
+        image: "web".to_owned(),
+        ports: vec![80],
+    };
+    assert_eq!(container, expected);
+
+    // Through an alias.
+    let image: String = serde_yaml::from_str_at(yaml, "spec.containers[1].image").unwrap();
+    assert_eq!(image, "base");
+    let port: u16 = serde_yaml::from_str_at(yaml, "spec.containers[1].ports[0]").unwrap();
+    assert_eq!(port, 80);
+
+    let n: u8 = serde_yaml::from_str_at(yaml, "spec.matrix[1][0]").unwrap();
+    assert_eq!(n, 3);
+
+    // The key that is kept of duplicate ones.
+    let name: String = serde_yaml::from_str_at(yaml, "spec.name").unwrap();
+    assert_eq!(name, "second");
+
+    let value: Value = serde_yaml::from_str_at("a: [1]", ".").unwrap();
+    assert_eq!(value, serde_yaml::from_str::<Value>("a: [1]").unwrap());
+
+    // The rest of the document still has to be valid YAML.
+    let err = serde_yaml::from_str_at::<u8>("a: 1\nb: [\n", "a").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "did not find expected node content at line 3 column 1, while parsing a flow node",
+    );
+
+    // Errors have the whole path.
+    let err = serde_yaml::from_str_at::<u16>(yaml, "spec.containers[0].image").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "spec.containers[0].image: invalid type: string \"web\", expected u16 at line 6 column 14",
+    );
+
+    let err = serde_yaml::from_str_at::<Value>(yaml, "spec.containers[2]").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no node at path `spec.containers[2]` at line 6 column 5",
+    );
+    let err = serde_yaml::from_str_at::<Value>(yaml, "spec.matrix.name").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no node at path `spec.matrix.name` at line 9 column 11",
+    );
+}
+
+#[test]
+fn test_ignore_unknown_anchors() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Service {
+        name: String,
+        port: u16,
+    }
+
+    let yaml = indoc! {"
+        name: web
+        labels: {app: *app, tier: [*tier]}
+        port: 80
+    "};
+    let builder = Deserializer::builder().ignore_unknown_anchors(true);
+    let service: Service = builder.from_str(yaml).unwrap();
+    let expected = Service {
+        name: "web".to_owned(),
+        port: 80,
+    };
+    assert_eq!(service, expected);
+
+    // Other aliases still refer to their anchor.
+    let value: (String, serde::de::IgnoredAny, String) =
+        builder.from_str("[&a x, *b, *a]").unwrap();
+    assert_eq!(value.2, "x");
+
+    // The alias is an error where it is deserialized.
+    let err = builder.from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(err.to_string(), "unknown anchor at line 2 column 15",);
+    let builder = builder.max_errors(5);
+    let err = builder
+        .from_str::<BTreeMap<String, Value>>(yaml)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "unknown anchor at line 2 column 15",);
+
+    let builder = Deserializer::builder()
+        .ignore_unknown_anchors(true)
+        .merge_keys(true);
+    let err = builder.from_str::<Value>("a: {<<: *base}").unwrap_err();
+    assert_eq!(err.to_string(), "unknown anchor at line 1 column 9");
+    let err = builder.from_str::<Value>("a: {<<: [*base]}").unwrap_err();
+    assert_eq!(err.to_string(), "unknown anchor at line 1 column 10");
+
+    // By default the alias is an error in any case.
+    let err = serde_yaml::from_str::<Service>(yaml).unwrap_err();
+    assert_eq!(err.to_string(), "unknown anchor at line 2 column 15");
+}
@@ -6,12 +6,13 @@ use serde::de::Deserialize;
 use serde::de::{SeqAccess, Visitor};
 use serde_derive::{Deserialize, Serialize};
 use serde_yaml::value::{Tag, TaggedValue};
-use serde_yaml::{Deserializer, Value};
+use serde_yaml::{Deserializer, DeserializerBuilder, Value};
 #[cfg(not(miri))]
 use std::collections::BTreeMap;
 #[cfg(not(miri))]
 use std::fmt;
 use std::fmt::Debug;
+use std::marker::PhantomData;
 
 fn test_error<'de, T>(yaml: &'de str, expected: &str)
 where
@@ -36,6 +37,55 @@ fn test_scan_error() {
     test_error::<Value>(yaml, expected);
 }
 
+#[test]
+fn test_control_characters() {
+    // Every C0 and C1 control character other than tab and the line breaks,
+    // and DEL, written raw in each kind of scalar and in a comment.
+    let control = (0x00..0x20)
+        .chain(0x7F..0xA0)
+        .filter_map(char::from_u32)
+        .filter(|ch| !matches!(ch, '\t' | '\n' | '\r' | '\u{85}'));
+    for ch in control {
+        let cases = [
+            (format!("a: x{}y\n", ch), 1, 5),
+            (format!("a: 'x{}y'\n", ch), 1, 6),
+            (format!("a: \"x{}y\"\n", ch), 1, 6),
+            (format!("a: |\n  x{}y\n", ch), 2, 4),
+            (format!("a: >\n  x\n  x{}y\n", ch), 3, 4),
+            (format!("a: x # {}\n", ch), 1, 8),
+            (format!("a: x\n{}", ch), 2, 1),
+        ];
+        for (yaml, line, column) in cases {
+            let expected = format!(
+                "control character U+{:04X} not allowed in YAML at line {} column {}",
+                ch as u32, line, column,
+            );
+            test_error::<Value>(&yaml, &expected);
+            let location = serde_yaml::from_str::<Value>(&yaml)
+                .unwrap_err()
+                .location()
+                .unwrap();
+            assert_eq!((line, column), (location.line(), location.column()));
+            assert_eq!(yaml.find(ch), Some(location.index()));
+        }
+    }
+
+    let yaml = "a: \u{FFFE}\n";
+    let expected = "character U+FFFE not allowed in YAML at line 1 column 4";
+    test_error::<Value>(yaml, expected);
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let err = serde_yaml::from_slice::<Value>(b"\xFF").unwrap_err();
+    let expected = "invalid leading UTF-8 octet 0xFF at line 1 column 1";
+    assert_eq!(expected, err.to_string());
+
+    let err = serde_yaml::from_slice::<Value>(b"a: 1\nb: x\xC3(\n").unwrap_err();
+    let expected = "invalid trailing UTF-8 octet 0x28 at line 2 column 6";
+    assert_eq!(expected, err.to_string());
+}
+
 #[test]
 fn test_incorrect_type() {
     let yaml = indoc! {"
@@ -71,6 +121,139 @@ fn test_incorrect_nested_type() {
     test_error::<A>(yaml, expected);
 }
 
+#[test]
+fn test_internally_tagged_enum() {
+    #[derive(Deserialize, Debug)]
+    struct A {
+        #[allow(dead_code)]
+        items: Vec<B>,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(tag = "type")]
+    enum B {
+        C {
+            #[allow(dead_code)]
+            x: u8,
+        },
+    }
+    let yaml = indoc! {"
+        items:
+          - type: C
+            x: 1
+          - type: D
+            x: 2
+    "};
+    let expected = "items[1].type: unknown variant `D`, expected `C` at line 4 column 11";
+    test_error::<A>(yaml, expected);
+
+    let yaml = indoc! {"
+        items:
+          - type: C
+            x: 1
+          - type: C
+            x: 300
+    "};
+    let expected = "items[1].x: invalid value: integer `300`, expected u8 at line 5 column 8";
+    test_error::<A>(yaml, expected);
+
+    // The same value more than once, so which one is not known.
+    let yaml = indoc! {"
+        items:
+          - type: C
+            x: 300
+            y: 300
+    "};
+    let expected = "items[0]: invalid value: integer `300`, expected u8 at line 2 column 5";
+    test_error::<A>(yaml, expected);
+
+    // An equal scalar under a different key, which is the one `x` is read
+    // from through an alias.
+    let yaml = indoc! {"
+        items:
+          - type: C
+            y: &n 300
+            x: *n
+    "};
+    let expected = "items[0]: invalid value: integer `300`, expected u8 at line 2 column 5";
+    test_error::<A>(yaml, expected);
+    let yaml = indoc! {"
+        n: &n 300
+        items:
+          - type: C
+            x: *n
+    "};
+    let expected = "items[0].x: invalid value: integer `300`, expected u8 at line 4 column 8";
+    test_error::<A>(yaml, expected);
+    // At the top level, where the document is gone once `T::deserialize` of
+    // a Deserializer returns, but not for `from_str` or `deserialize_seed`.
+    let yaml = indoc! {"
+        type: C
+        x: true
+    "};
+    let expected = "x: invalid type: boolean `true`, expected u8 at line 2 column 4";
+    let result = serde_yaml::from_str::<B>(yaml);
+    assert_eq!(expected, result.unwrap_err().to_string());
+    let document = Deserializer::from_str(yaml).next().unwrap();
+    let result = document.deserialize_seed(PhantomData::<B>);
+    assert_eq!(expected, result.unwrap_err().to_string());
+
+    #[derive(Deserialize, Debug)]
+    #[serde(tag = "type", deny_unknown_fields)]
+    enum E {
+        F {
+            #[allow(dead_code)]
+            x: u8,
+        },
+    }
+    let yaml = indoc! {"
+        - type: F
+          x: 1
+          typo: 2
+    "};
+    let expected = ".[0]: unknown field `typo`, expected `x` at line 3 column 3";
+    test_error::<Vec<E>>(yaml, expected);
+}
+
+#[test]
+fn test_adjacently_tagged_enum() {
+    #[derive(Deserialize, Debug)]
+    struct A {
+        #[allow(dead_code)]
+        items: Vec<B>,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(tag = "type", content = "spec")]
+    enum B {
+        C(#[allow(dead_code)] Vec<u8>),
+    }
+    let yaml = indoc! {"
+        items:
+          - type: D
+            spec: [1]
+    "};
+    let expected = "items[0].type: unknown variant `D`, expected `C` at line 2 column 11";
+    test_error::<A>(yaml, expected);
+
+    let yaml = indoc! {"
+        items:
+          - type: C
+            spec: [1, 999]
+    "};
+    let expected =
+        "items[0].spec[1]: invalid value: integer `999`, expected u8 at line 3 column 15";
+    test_error::<A>(yaml, expected);
+
+    let yaml = indoc! {"
+        items:
+          - spec: [1, 999]
+            type: C
+    "};
+    // The content is buffered inside the mapping, where errors from it are
+    // located at the whole mapping.
+    let expected = "items[0]: invalid value: integer `999`, expected u8 at line 2 column 5";
+    test_error::<A>(yaml, expected);
+}
+
 #[test]
 fn test_empty() {
     let expected = "EOF while parsing a value";
@@ -121,8 +304,8 @@ fn test_ignored_unknown_anchor() {
 
 #[test]
 fn test_bytes() {
-    let expected = "serialization and deserialization of bytes in YAML is not implemented";
-    test_error::<&[u8]>("...", expected);
+    let expected = "invalid value: string \"aGk\", expected base64-encoded binary data";
+    test_error::<&[u8]>("!!binary aGk", expected);
 }
 
 #[test]
@@ -210,6 +393,40 @@ fn test_serialize_nested_enum() {
     assert_eq!(error.to_string(), expected);
 }
 
+#[test]
+fn test_serialize_duplicate_key() {
+    #[derive(Serialize, Debug)]
+    #[serde(rename_all = "kebab-case")]
+    struct Config {
+        max_retries: u32,
+        #[serde(rename = "max-retries")]
+        retry_limit: u32,
+    }
+
+    let config = Config {
+        max_retries: 1,
+        retry_limit: 2,
+    };
+    let error = serde_yaml::to_string(&config).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "duplicate entry with key \"max-retries\"",
+    );
+
+    #[derive(Serialize, Debug)]
+    enum Enum {
+        Variant {
+            #[serde(rename = "1")]
+            one: u32,
+            #[serde(rename = "1")]
+            uno: u32,
+        },
+    }
+
+    let error = serde_yaml::to_string(&vec![Enum::Variant { one: 1, uno: 1 }]).unwrap_err();
+    assert_eq!(error.to_string(), "duplicate entry with key \"1\"");
+}
+
 #[test]
 fn test_deserialize_nested_enum() {
     #[derive(Deserialize, Debug)]
@@ -407,6 +624,28 @@ fn test_finite_recursion_arrays() {
     test_error::<S>(&yaml, expected);
 }
 
+#[cfg(not(miri))]
+#[test]
+fn test_configured_recursion_limit() {
+    #[derive(Deserialize, Debug)]
+    struct Device {
+        #[allow(dead_code)]
+        children: Vec<Device>,
+    }
+
+    // Each level is a mapping and a sequence.
+    let yaml = "{children: [".repeat(100) + &"]}".repeat(100);
+    let expected = "recursion limit exceeded at line 1 column 769";
+    test_error::<Device>(&yaml, expected);
+    let builder = Deserializer::builder().recursion_limit(200);
+    builder.from_str::<Device>(&yaml).unwrap();
+
+    let builder = Deserializer::builder().recursion_limit(8);
+    let error = builder.from_str::<Device>(&yaml).unwrap_err();
+    let expected = "recursion limit exceeded at line 1 column 49";
+    assert_eq!(error.to_string(), expected);
+}
+
 #[cfg(not(miri))]
 #[test]
 fn test_billion_laughs() {
@@ -457,6 +696,28 @@ fn test_billion_laughs() {
     test_error::<BTreeMap<String, X>>(yaml, expected);
 }
 
+#[test]
+fn test_configured_repetition_limit() {
+    // Shared defaults that each of many entries refers to.
+    let mut yaml = String::from("defaults: &defaults {retries: 3, timeout: 30, region: eu}\n");
+    yaml += "services:\n";
+    for i in 0..1000 {
+        yaml += &format!("  - {{name: s{}, settings: *defaults}}\n", i);
+    }
+    let value: Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(value["services"][999]["settings"]["region"], "eu");
+
+    let builder = Deserializer::builder().repetition_limit(Some(0));
+    let error = builder.from_str::<Value>(&yaml).unwrap_err();
+    assert_eq!(error.to_string(), "repetition limit exceeded");
+
+    // Without a limit, only the recursion limit applies.
+    let builder = Deserializer::builder().repetition_limit(None);
+    builder.from_str::<Value>(&yaml).unwrap();
+    let error = builder.from_str::<Value>("&a [*a]").unwrap_err();
+    assert_eq!(error.to_string(), "recursion limit exceeded");
+}
+
 #[test]
 fn test_duplicate_keys() {
     let yaml = indoc! {"
@@ -491,3 +752,368 @@ fn test_duplicate_keys() {
     let expected = "duplicate entry in YAML map at line 2 column 1";
     test_error::<Value>(yaml, expected);
 }
+
+#[test]
+fn test_singleton_map_keys_duplicate() {
+    #[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+    enum Selector {
+        Id(u32),
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Policy {
+        #[serde(with = "serde_yaml::with::singleton_map_keys")]
+        #[allow(dead_code)]
+        rules: BTreeMap<Selector, String>,
+    }
+
+    let yaml = indoc! {"
+        rules:
+          ? {Id: 1}
+          : accept
+          ? {Id: 0x1}
+          : reject
+    "};
+    let expected = "rules: duplicate entry with key `{Id: 1}` at line 2 column 3";
+    test_error::<Policy>(yaml, expected);
+}
+
+#[test]
+fn test_map_scalar_error() {
+    let builder = DeserializerBuilder::new().map_scalar(|raw, _style| {
+        match raw
+            .trim_end()
+            .strip_prefix("${")
+            .and_then(|rest| rest.strip_suffix('}'))
+        {
+            Some(name) => Err(serde::de::Error::custom(format!(
+                "environment variable {} is not set",
+                name,
+            ))),
+            None => Ok(None),
+        }
+    });
+
+    let yaml = indoc! {"
+        database:
+          url: postgres://db
+          password: ${DB_PASSWORD}
+    "};
+    let expected = "environment variable DB_PASSWORD is not set at line 3 column 13";
+    let error = builder.from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+    let location = error.location().unwrap();
+    assert_eq!((location.line(), location.column()), (3, 13));
+
+    let yaml = "labels:\n  ? |\n    ${KEY}\n  : value\n";
+    let expected = "environment variable KEY is not set at line 2 column 5";
+    let error = builder.from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+
+    let mut deserializer = builder.build_from_str("ok\n---\n- ${SECOND}\n");
+    let first = deserializer.next().unwrap();
+    assert_eq!(String::deserialize(first).unwrap(), "ok");
+    let second = deserializer.next().unwrap();
+    let expected = "environment variable SECOND is not set at line 3 column 3";
+    assert_eq!(
+        Value::deserialize(second).unwrap_err().to_string(),
+        expected
+    );
+}
+
+#[test]
+fn test_long_line() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        name: String,
+        port: u16,
+    }
+
+    let padding = "x".repeat(200_000);
+    let yaml = format!("{{name: {}, port: eighty}}", padding);
+    let expected =
+        "port: invalid type: string \"eighty\", expected u16 at line 1 column 200016 (byte 200015)";
+    test_error::<Config>(&yaml, expected);
+
+    let error = serde_yaml::from_str::<Config>(&yaml).unwrap_err();
+    assert_eq!(error.context_snippet(21), Some("xxxxxx, port: eighty}"));
+    assert_eq!(error.context_snippet(8), Some("rt: eigh"));
+    assert_eq!(error.context_snippet(1000).unwrap().len(), 256);
+
+    let yaml = format!("a: [{}}}\nb: c\n", "1, ".repeat(1000));
+    let error = serde_yaml::from_str::<Value>(&yaml).unwrap_err();
+    let snippet = error.context_snippet(1000).unwrap();
+    assert_eq!(snippet.len(), 256);
+    assert!(snippet.ends_with("1, }"));
+
+    let mut deserializer = Deserializer::from_reader(yaml.as_bytes());
+    let error = Value::deserialize(deserializer.next().unwrap()).unwrap_err();
+    assert_eq!(error.context_snippet(4), Some("1, }"));
+
+    let error = serde_yaml::from_value::<u16>(Value::from("eighty")).unwrap_err();
+    assert_eq!(error.context_snippet(10), None);
+}
+
+#[test]
+fn test_same_message_from_value() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum Mode {
+        Fixed(u8),
+        Range(u8, u8),
+        Custom { step: u8 },
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        port: Option<u16>,
+        pair: Option<(u8, u8)>,
+        mode: Option<Mode>,
+        unit: Option<()>,
+        nested: Option<Vec<Nested>>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Nested {
+        values: Vec<i8>,
+    }
+
+    fn assert_same(yaml: &str) {
+        let from_str = serde_yaml::from_str::<Config>(yaml)
+            .unwrap_err()
+            .to_string();
+        // Without the location, which a Value does not have.
+        let expected = match from_str.find(" at line ") {
+            Some(at) => &from_str[..at],
+            None => &from_str,
+        };
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let from_ref = Config::deserialize(&value).unwrap_err().to_string();
+        assert_eq!(from_ref, expected);
+        let from_value = serde_yaml::from_value::<Config>(value).unwrap_err();
+        assert_eq!(from_value.to_string(), expected);
+    }
+
+    assert_same("port: -1");
+    assert_same("port: 70000");
+    assert_same("port: [80]");
+    assert_same("pair: [1, 2, 3]");
+    assert_same("pair: [1]");
+    assert_same("mode: [1]");
+    assert_same("mode: !Fixed x");
+    assert_same("mode: !Range [1, 2, 3]");
+    assert_same("mode: !Custom 1");
+    assert_same("mode: !Custom {step: x}");
+    assert_same("mode: Other");
+    assert_same("unit: 1");
+    assert_same("nested: [{values: [1, 2, -129]}]");
+    assert_same("nested: [{values: [9223372036854775808]}]");
+}
+
+#[test]
+fn test_wrapped_custom_error() {
+    use serde::de::Error as _;
+    use std::error::Error as _;
+
+    #[derive(Debug)]
+    struct Port(#[allow(dead_code)] u16);
+
+    impl<'de> Deserialize<'de> for Port {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            u16::deserialize(deserializer)
+                .map(Port)
+                .map_err(|err| D::Error::custom(format_args!("bad port: {}", err)))
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Server {
+        name: String,
+        port: Port,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        servers: Vec<Server>,
+    }
+
+    let yaml = indoc! {"
+        servers:
+          - name: a
+            port: x
+    "};
+    let err = serde_yaml::from_str::<Config>(yaml).unwrap_err();
+    let expected =
+        "bad port: servers[0].port: invalid type: string \"x\", expected u16 at line 3 column 11";
+    assert_eq!(expected, err.to_string());
+
+    let source = err.source().unwrap();
+    let expected = "servers[0].port: invalid type: string \"x\", expected u16 at line 3 column 11";
+    assert_eq!(expected, source.to_string());
+    let source = source.downcast_ref::<serde_yaml::Error>().unwrap();
+    let location = source.location().unwrap();
+    assert_eq!((3, 11), (location.line(), location.column()));
+    assert!(source.source().is_none());
+
+    // A message that does not display another error has no source.
+    let err = serde_yaml::Error::custom("plain message");
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn test_crlf_location() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        name: String,
+        port: u16,
+    }
+
+    let yaml = indoc! {"
+        name: |
+          first
+
+          second
+        port: x
+    "};
+    let expected = "port: invalid type: string \"x\", expected u16 at line 5 column 7";
+    for line_break in ["\n", "\r\n", "\r"] {
+        let yaml = yaml.replace('\n', line_break);
+        test_error::<Config>(&yaml, expected);
+        let err = serde_yaml::from_str::<Config>(&yaml).unwrap_err();
+        assert_eq!(err.context_snippet(80), Some("port: x"));
+    }
+}
+
+#[test]
+fn test_error_span() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Server {
+        host: String,
+        port: u16,
+        tags: Vec<String>,
+    }
+
+    // The part of the input from the location of the error to its end.
+    fn spanned<'a>(yaml: &'a str, err: &serde_yaml::Error) -> &'a str {
+        let start = err.location().unwrap();
+        let end = err.end_location().unwrap();
+        &yaml[start.index()..end.index()]
+    }
+
+    let cases = [
+        ("host: a\nport: \"80\"\ntags: []\n", "\"80\""),
+        ("host: a\nport: 80\ntags: [x, [y, z]]\n", "[y, z]"),
+        ("host: a\nport: 80\ntags: x\n", "x"),
+        (
+            "host:\n  name: a\n  alias: b\nport: 80\ntags: []\n",
+            "name: a\n  alias: b",
+        ),
+        ("host: a\nport: 80\n", "host: a\nport: 80"),
+        ("- host: a\n", "- host: a"),
+    ];
+    for (yaml, expected) in cases {
+        let err = serde_yaml::from_str::<Server>(yaml).unwrap_err();
+        assert_eq!(spanned(yaml, &err), expected, "{}", err);
+    }
+
+    let yaml = "a: 1\na: [1, 2]\n";
+    let builder = DeserializerBuilder::new().duplicate_keys(serde_yaml::DuplicateKeyPolicy::Error);
+    let err = builder.from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(spanned(yaml, &err), "a");
+
+    // An error at a single point ends where it starts.
+    let yaml = "a: [1, 2\n";
+    let err = serde_yaml::from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(err.location().unwrap(), err.end_location().unwrap());
+
+    let err = <serde_yaml::Error as serde::de::Error>::custom("plain message");
+    assert!(err.location().is_none());
+    assert!(err.end_location().is_none());
+}
+
+#[test]
+fn test_max_errors() {
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Config {
+        name: String,
+        #[serde(default)]
+        debug: bool,
+        servers: Vec<Server>,
+        limits: BTreeMap<String, u32>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    let yaml = indoc! {"
+        name: app
+        debug: maybe
+        servers:
+          - host: a
+            port: 80
+          - host: [b]
+            port: -1
+          - host: c
+        limits:
+          cpu: 2
+          memory: lots
+        colour: blue
+    "};
+    let builder = DeserializerBuilder::new().max_errors(100);
+    let err = builder.from_str::<Config>(yaml).unwrap_err();
+    let expected = [
+        "debug: invalid type: string \"maybe\", expected a boolean at line 2 column 8",
+        "servers[1].host: invalid type: sequence, expected a string at line 6 column 11",
+        "servers[1].port: invalid value: integer `-1`, expected u16 at line 7 column 11",
+        "servers[2]: missing field `port` at line 8 column 5",
+        "limits.memory: invalid type: string \"lots\", expected u32 at line 11 column 11",
+        "unknown field `colour`, expected one of `name`, `debug`, `servers`, `limits` at line 12 column 1",
+    ];
+    let messages: Vec<String> = err.errors().iter().map(ToString::to_string).collect();
+    assert_eq!(messages, expected);
+    assert_eq!(err.to_string(), expected.join("\n"));
+    let location = err.location().unwrap();
+    assert_eq!((location.line(), location.column()), (2, 8));
+
+    // Up to the limit.
+    let builder = DeserializerBuilder::new().max_errors(2);
+    let err = builder.from_str::<Config>(yaml).unwrap_err();
+    assert_eq!(err.errors().len(), 2);
+    let err = DeserializerBuilder::new()
+        .from_str::<Config>(yaml)
+        .unwrap_err();
+    assert_eq!(err.errors().len(), 1);
+    assert_eq!(err.to_string(), expected[0]);
+
+    // Valid input, and invalid syntax.
+    let builder = DeserializerBuilder::new().max_errors(100);
+    let config = builder.from_slice::<Config>(b"{name: a, servers: [], limits: {}}");
+    assert_eq!(config.unwrap().name, "a");
+    let err = builder
+        .from_reader::<_, Config>("name: [a\nservers: x".as_bytes())
+        .unwrap_err();
+    assert_eq!(err.errors().len(), 1);
+
+    // An error in a node that an alias refers to is found once, where the
+    // node is.
+    let yaml = "a: &x true\nb: *x\n";
+    let err = builder.from_str::<BTreeMap<String, u8>>(yaml).unwrap_err();
+    let expected = "a: invalid type: boolean `true`, expected u8 at line 1 column 4";
+    assert_eq!(err.to_string(), expected);
+}
@@ -0,0 +1,190 @@
+use indoc::indoc;
+use serde_yaml::event::{Emitter, Event, Events, MappingStart, Scalar, SequenceStart};
+use serde_yaml::ScalarStyle;
+
+fn scalar(value: &str, style: ScalarStyle) -> Event {
+    Event::Scalar(Scalar {
+        anchor: None,
+        tag: None,
+        value: value.to_owned(),
+        style,
+    })
+}
+
+fn emit(events: impl IntoIterator<Item = Event>) -> serde_yaml::Result<String> {
+    let mut output = Vec::new();
+    let mut emitter = Emitter::new(&mut output);
+    for event in events {
+        emitter.emit(event)?;
+    }
+    drop(emitter);
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn test_events() {
+    let yaml = indoc! {"
+        base: &base !!map {retries: 3}
+        hosts:
+          - !host 'web'
+          - *base
+    "};
+    let events: Vec<Event> = Events::from_str(yaml)
+        .map(|event| event.unwrap().value)
+        .collect();
+    let expected = vec![
+        Event::StreamStart,
+        Event::DocumentStart,
+        Event::MappingStart(MappingStart {
+            anchor: None,
+            tag: None,
+            flow: false,
+        }),
+        scalar("base", ScalarStyle::Plain),
+        Event::MappingStart(MappingStart {
+            anchor: Some("base".to_owned()),
+            tag: Some("tag:yaml.org,2002:map".to_owned()),
+            flow: true,
+        }),
+        scalar("retries", ScalarStyle::Plain),
+        scalar("3", ScalarStyle::Plain),
+        Event::MappingEnd,
+        scalar("hosts", ScalarStyle::Plain),
+        Event::SequenceStart(SequenceStart {
+            anchor: None,
+            tag: None,
+            flow: false,
+        }),
+        Event::Scalar(Scalar {
+            anchor: None,
+            tag: Some("!host".to_owned()),
+            value: "web".to_owned(),
+            style: ScalarStyle::SingleQuoted,
+        }),
+        Event::Alias("base".to_owned()),
+        Event::SequenceEnd,
+        Event::MappingEnd,
+        Event::DocumentEnd,
+        Event::StreamEnd,
+    ];
+    assert_eq!(events, expected);
+}
+
+#[test]
+fn test_locations() {
+    let yaml = "key: [a, 'b']\n";
+    let spans: Vec<(usize, usize)> = Events::from_str(yaml)
+        .map(|event| {
+            let event = event.unwrap();
+            (event.start.index(), event.end.index())
+        })
+        .collect();
+    let expected = [
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 3),
+        (5, 6),
+        (6, 7),
+        (9, 12),
+        (12, 13),
+        (14, 14),
+        (14, 14),
+        (14, 14),
+    ];
+    assert_eq!(spans, expected);
+}
+
+#[test]
+fn test_round_trip() {
+    let yaml = indoc! {"
+        base: &base !!map {retries: 3}
+        hosts:
+        - !host 'web'
+        - *base
+        - &text |
+          two
+          lines
+        --- [1, \"two\"]
+    "};
+    let events = Events::from_str(yaml).map(|event| event.unwrap().value);
+    assert_eq!(emit(events).unwrap(), yaml);
+
+    let events = Events::from_reader(yaml.as_bytes()).map(|event| event.unwrap().value);
+    assert_eq!(emit(events).unwrap(), yaml);
+
+    let utf16: Vec<u8> = yaml.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let events = Events::from_slice(&utf16).map(|event| event.unwrap().value);
+    assert_eq!(emit(events).unwrap(), yaml);
+}
+
+#[test]
+fn test_emit_style() {
+    // A scalar that cannot be plain is quoted, and one that can stays too.
+    let events = [
+        Event::StreamStart,
+        Event::DocumentStart,
+        Event::SequenceStart(SequenceStart {
+            anchor: Some("list".to_owned()),
+            tag: None,
+            flow: false,
+        }),
+        scalar(": x", ScalarStyle::Plain),
+        scalar("yes", ScalarStyle::Plain),
+        Event::Scalar(Scalar {
+            anchor: Some("id".to_owned()),
+            tag: Some("!id".to_owned()),
+            value: "7".to_owned(),
+            style: ScalarStyle::DoubleQuoted,
+        }),
+        Event::Alias("id".to_owned()),
+        Event::SequenceEnd,
+        Event::DocumentEnd,
+        Event::StreamEnd,
+    ];
+    let expected = indoc! {r#"
+        &list
+        - ': x'
+        - yes
+        - &id !id "7"
+        - *id
+    "#};
+    assert_eq!(emit(events).unwrap(), expected);
+}
+
+#[test]
+fn test_errors() {
+    let mut events = Events::from_str("a: [b\n");
+    let errors: Vec<String> = events
+        .by_ref()
+        .filter_map(|event| event.err())
+        .map(|err| err.to_string())
+        .collect();
+    assert_eq!(
+        errors,
+        ["did not find expected ',' or ']' at line 2 column 1, while parsing a flow sequence at line 1 column 4"],
+    );
+    assert!(events.next().is_none());
+
+    let err = Events::from_slice(b"\xFF\xFEa")
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "invalid UTF-16LE at byte 2 of the input");
+
+    // Events out of place.
+    let err = emit([Event::StreamStart, Event::MappingEnd]).unwrap_err();
+    assert_eq!(err.to_string(), "expected DOCUMENT-START or STREAM-END");
+
+    let tagged = Event::Scalar(Scalar {
+        anchor: None,
+        tag: Some("!a\0b".to_owned()),
+        value: String::new(),
+        style: ScalarStyle::Plain,
+    });
+    let err = emit([Event::StreamStart, Event::DocumentStart, tagged]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "tag `!a\\0b` contains character U+0000, which is not allowed in YAML",
+    );
+}
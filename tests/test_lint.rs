@@ -0,0 +1,206 @@
+use indoc::indoc;
+use serde_yaml::lint::{LintConfig, Rule, Severity};
+
+fn test_lint(yaml: &str, rule: Rule, expected: &[&str]) {
+    let config = LintConfig::new();
+    let diagnostics: Vec<String> = serde_yaml::lint(yaml, &config)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.rule() == rule)
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(expected, diagnostics);
+}
+
+#[test]
+fn test_clean() {
+    let yaml = indoc! {"
+        ---
+        name: serde
+        versions:
+          - 1
+          - 2
+        features: [std, 'no']
+        base: &base
+          retries: 3
+        local:
+          <<: *base
+    "};
+    let diagnostics = serde_yaml::lint(yaml, &LintConfig::new());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
+
+#[test]
+fn test_syntax() {
+    let yaml = "---\nkey: [1, 2\n";
+    let expected = &[
+        "3:1: error: did not find expected ',' or ']' at line 3 column 1, while parsing a flow sequence at line 2 column 6 (syntax)",
+    ];
+    test_lint(yaml, Rule::Syntax, expected);
+}
+
+#[test]
+fn test_duplicate_keys() {
+    let yaml = indoc! {"
+        ---
+        a: 1
+        b:
+          a: 2
+          c: 3
+          c: 4
+        a: 5
+        'a': 6
+        '1': 7
+        1: 8
+        0x1: 9
+    "};
+    let expected = &[
+        "6:3: error: duplicate key `c` (duplicate-keys)",
+        "7:1: error: duplicate key `a` (duplicate-keys)",
+        "8:1: error: duplicate key `a` (duplicate-keys)",
+        "11:1: error: duplicate key `0x1` (duplicate-keys)",
+    ];
+    test_lint(yaml, Rule::DuplicateKeys, expected);
+}
+
+#[test]
+fn test_tabs() {
+    let yaml = "---\na:\n\tb: 1\nc: \"\td\"\n";
+    let expected = &["3:1: warning: indentation contains a tab (tabs)"];
+    test_lint(yaml, Rule::Tabs, expected);
+}
+
+#[test]
+fn test_trailing_whitespace() {
+    let yaml = "---\na: 1  \nb: 2\t\r\nc: 3\n";
+    let expected = &[
+        "2:5: warning: trailing whitespace (trailing-whitespace)",
+        "3:5: warning: trailing whitespace (trailing-whitespace)",
+    ];
+    test_lint(yaml, Rule::TrailingWhitespace, expected);
+
+    // Lines are counted the same way as in the locations of syntax errors.
+    for line_break in ["\r", "\u{85}", "\u{2028}"] {
+        let yaml = "---\na: 1 \nb: [\n".replace('\n', line_break);
+        let expected = &["2:5: warning: trailing whitespace (trailing-whitespace)"];
+        test_lint(&yaml, Rule::TrailingWhitespace, expected);
+        let expected = &["4:1: error: did not find expected node content at line 4 column 1, while parsing a flow node (syntax)"];
+        test_lint(&yaml, Rule::Syntax, expected);
+    }
+}
+
+#[test]
+fn test_indentation() {
+    let yaml = indoc! {"
+        ---
+        a:
+          b:
+              c: 1
+          d:
+          - 1
+          e: {f: 1}
+        g:
+           h: 1
+    "};
+    let expected = &[
+        "4:7: warning: wrong indentation: expected 2 spaces but found 4 (indentation)",
+        "9:4: warning: wrong indentation: expected 2 spaces but found 3 (indentation)",
+    ];
+    test_lint(yaml, Rule::Indentation, expected);
+
+    let config = LintConfig::new().indent_width(4);
+    let diagnostics = serde_yaml::lint(yaml, &config);
+    let lines: Vec<usize> = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.rule() == Rule::Indentation)
+        .map(|diagnostic| diagnostic.location().line())
+        .collect();
+    assert_eq!(lines, [3, 9]);
+}
+
+#[test]
+fn test_ambiguous_scalars() {
+    let yaml = indoc! {"
+        ---
+        country: NO
+        enabled: yes
+        quoted: 'no'
+        tagged: !!str off
+        time: 08:30
+        angle: -190:20:30.15
+        port: 8080
+        ratio: 1:2:3:60
+    "};
+    let expected = &[
+        "2:10: warning: plain scalar `NO` is a boolean in YAML 1.1; quote it to keep it a string (ambiguous-scalars)",
+        "3:10: warning: plain scalar `yes` is a boolean in YAML 1.1; quote it to keep it a string (ambiguous-scalars)",
+        "6:7: warning: plain scalar `08:30` is a base 60 number in YAML 1.1; quote it to keep it a string (ambiguous-scalars)",
+        "7:8: warning: plain scalar `-190:20:30.15` is a base 60 number in YAML 1.1; quote it to keep it a string (ambiguous-scalars)",
+    ];
+    test_lint(yaml, Rule::AmbiguousScalars, expected);
+}
+
+#[test]
+fn test_line_length() {
+    let yaml = format!("---\nshort: line\nlong: {}\n", "x".repeat(80));
+    let expected =
+        &["3:81: warning: line is 86 characters long, more than the limit of 80 (line-length)"];
+    test_lint(&yaml, Rule::LineLength, expected);
+
+    let config = LintConfig::new().max_line_length(100);
+    assert!(serde_yaml::lint(&yaml, &config).is_empty());
+}
+
+#[test]
+fn test_document_start() {
+    let yaml = "# comment\n\na: 1\n";
+    let expected = &["3:1: warning: missing document start marker `---` (document-start)"];
+    test_lint(yaml, Rule::DocumentStart, expected);
+
+    test_lint("%YAML 1.2\n---\na: 1\n", Rule::DocumentStart, &[]);
+    test_lint("", Rule::DocumentStart, &[]);
+}
+
+#[test]
+fn test_unused_anchors() {
+    let yaml = indoc! {"
+        ---
+        a: &used 1
+        b: &unused [1]
+        c: *used
+        ---
+        d: *unused
+    "};
+    let expected = &["3:4: warning: anchor \"unused\" is never used (unused-anchors)"];
+    test_lint(yaml, Rule::UnusedAnchors, expected);
+}
+
+#[test]
+fn test_config() {
+    let yaml = "a: yes \n";
+
+    let diagnostics = serde_yaml::lint(yaml, &LintConfig::new());
+    let rules: Vec<Rule> = diagnostics.iter().map(|d| d.rule()).collect();
+    assert_eq!(
+        rules,
+        [
+            Rule::DocumentStart,
+            Rule::AmbiguousScalars,
+            Rule::TrailingWhitespace
+        ],
+    );
+
+    let config = LintConfig::new()
+        .disable(Rule::DocumentStart)
+        .disable(Rule::TrailingWhitespace)
+        .severity(Rule::AmbiguousScalars, Severity::Error);
+    let diagnostics = serde_yaml::lint(yaml, &config);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule(), Rule::AmbiguousScalars);
+    assert_eq!(diagnostics[0].severity(), Severity::Error);
+    assert_eq!(diagnostics[0].location().column(), 4);
+
+    let config = LintConfig::new().disable(Rule::Syntax);
+    let diagnostics = serde_yaml::lint("---\n[", &config);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule(), Rule::Syntax);
+}
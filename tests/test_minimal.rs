@@ -0,0 +1,86 @@
+// Deserialization into fixed types, which must keep working in a build with
+// only the "de" feature:
+//
+//     cargo test --no-default-features --features de --test test_minimal
+
+use indoc::indoc;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct Config {
+    name: String,
+    retries: u8,
+    ratio: f64,
+    enabled: bool,
+    mode: Mode,
+    limits: BTreeMap<String, u32>,
+    tags: Vec<String>,
+    parent: Option<Box<Config>>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+enum Mode {
+    Fast,
+    Slow { delay: u32 },
+}
+
+#[test]
+fn test_from_str() {
+    let yaml = indoc! {"
+        name: sensor
+        retries: 3
+        ratio: 0.5
+        enabled: true
+        mode: !Slow
+          delay: 10
+        limits:
+          cpu: 2
+        tags: [a, b]
+        parent:
+          name: hub
+          retries: 0x10
+          ratio: 1
+          enabled: false
+          mode: Fast
+          limits: {}
+          tags: []
+          parent: ~
+    "};
+    let expected = Config {
+        name: "sensor".to_owned(),
+        retries: 3,
+        ratio: 0.5,
+        enabled: true,
+        mode: Mode::Slow { delay: 10 },
+        limits: BTreeMap::from([("cpu".to_owned(), 2)]),
+        tags: vec!["a".to_owned(), "b".to_owned()],
+        parent: Some(Box::new(Config {
+            name: "hub".to_owned(),
+            retries: 16,
+            ratio: 1.0,
+            enabled: false,
+            mode: Mode::Fast,
+            limits: BTreeMap::new(),
+            tags: Vec::new(),
+            parent: None,
+        })),
+    };
+    assert_eq!(expected, serde_yaml::from_str::<Config>(yaml).unwrap());
+    assert_eq!(
+        expected,
+        serde_yaml::from_slice::<Config>(yaml.as_bytes()).unwrap(),
+    );
+}
+
+#[test]
+fn test_error() {
+    let yaml = "name: sensor\nretries: many\n";
+    let error = serde_yaml::from_str::<Config>(yaml).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "retries: invalid type: string \"many\", expected u8 at line 2 column 10",
+    );
+    let location = error.location().unwrap();
+    assert_eq!((location.line(), location.column()), (2, 10));
+}
@@ -8,9 +8,14 @@
 use indoc::indoc;
 use serde::ser::SerializeMap;
 use serde_derive::{Deserialize, Serialize};
-use serde_yaml::{Mapping, Number, Value};
-use std::collections::BTreeMap;
-use std::fmt::Debug;
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{
+    Commented, DeserializerBuilder, EmitterCompat, FloatFormat, Mapping, NodeAction, NodeRef,
+    NullStyle, Number, QuoteStyle, ScalarAction, ScalarRef, SerializerBuilder, SerializerProfile,
+    UnitVariants, Value,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Debug};
 use std::iter;
 
 fn test_serde<T>(thing: &T, yaml: &str)
@@ -96,6 +101,37 @@ fn test_u128_small() {
     test_serde(&thing, yaml);
 }
 
+#[test]
+fn test_i128_big() {
+    let thing = i128::MIN;
+    let yaml = indoc! {"
+        -170141183460469231731687303715884105728
+    "};
+    test_serde(&thing, yaml);
+    let value = serde_yaml::to_value(thing).unwrap();
+    assert_eq!(value, Value::Number(thing.into()));
+    assert_eq!(value.as_i128(), Some(thing));
+}
+
+#[test]
+fn test_u128_big() {
+    let thing = u128::MAX;
+    let yaml = indoc! {"
+        340282366920938463463374607431768211455
+    "};
+    test_serde(&thing, yaml);
+    let value = serde_yaml::to_value(thing).unwrap();
+    assert_eq!(value, Value::Number(thing.into()));
+    assert_eq!(value, thing);
+    assert_eq!(value.as_u64(), None);
+    assert_eq!(
+        serde_yaml::from_value::<u64>(value)
+            .unwrap_err()
+            .to_string(),
+        "invalid type: integer `340282366920938463463374607431768211455` as u128, expected u64"
+    );
+}
+
 #[test]
 fn test_float() {
     let thing = 25.6;
@@ -410,6 +446,71 @@ fn test_unit_variant() {
     test_serde(&thing, yaml);
 }
 
+#[test]
+fn test_unit_variant_styles() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+    enum State {
+        Active,
+        Retired,
+        Pending(u8),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Data {
+        state: State,
+        history: Vec<State>,
+        by_name: BTreeMap<String, State>,
+        counts: BTreeMap<State, usize>,
+    }
+
+    let thing = Data {
+        state: State::Active,
+        history: vec![State::Pending(1), State::Retired, State::Active],
+        by_name: BTreeMap::from([("a".to_owned(), State::Retired)]),
+        counts: BTreeMap::from([(State::Active, 2)]),
+    };
+
+    let plain = indoc! {"
+        state: Active
+        history:
+        - !Pending 1
+        - Retired
+        - Active
+        by_name:
+          a: Retired
+        counts:
+          Active: 2
+    "};
+    let tagged = indoc! {"
+        state: !Active
+        history:
+        - !Pending 1
+        - !Retired
+        - !Active
+        by_name:
+          a: !Retired
+        counts:
+          !Active '': 2
+    "};
+
+    let options = SerializerBuilder::new().unit_variants(UnitVariants::PlainString);
+    assert_eq!(plain, options.to_string(&thing).unwrap());
+    let options = SerializerBuilder::new().unit_variants(UnitVariants::Tag);
+    assert_eq!(tagged, options.to_string(&thing).unwrap());
+
+    for yaml in [plain, tagged] {
+        let deserialized: Data = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(thing, deserialized);
+
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let deserialized: Data = serde::Deserialize::deserialize(&value).unwrap();
+        assert_eq!(thing, deserialized);
+
+        let deserialized: Data = serde_yaml::from_value(value).unwrap();
+        assert_eq!(thing, deserialized);
+    }
+}
+
 #[test]
 fn test_newtype_struct() {
     #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -556,6 +657,131 @@ fn test_mapping() {
     test_serde(&thing, yaml);
 }
 
+#[test]
+fn test_flatten_non_string_keys() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Data {
+        name: String,
+        #[serde(flatten)]
+        rest: Mapping,
+    }
+
+    let mut thing = Data {
+        name: "data".to_owned(),
+        rest: Mapping::new(),
+    };
+    thing
+        .rest
+        .insert(Value::Number(1.into()), Value::from("one"));
+    thing.rest.insert(Value::Bool(true), Value::from("yes"));
+    thing
+        .rest
+        .insert(Value::from("key"), Value::Number(2.into()));
+
+    let yaml = indoc! {"
+        name: data
+        1: one
+        true: yes
+        key: 2
+    "};
+
+    test_serde(&thing, yaml);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Typed {
+        name: String,
+        #[serde(flatten)]
+        rest: BTreeMap<u32, String>,
+    }
+
+    let mut thing = Typed {
+        name: "typed".to_owned(),
+        rest: BTreeMap::new(),
+    };
+    thing.rest.insert(1, "one".to_owned());
+    thing.rest.insert(20, "twenty".to_owned());
+
+    let yaml = indoc! {"
+        name: typed
+        1: one
+        20: twenty
+    "};
+
+    test_serde(&thing, yaml);
+}
+
+#[test]
+fn test_flatten_order() {
+    #[derive(Serialize)]
+    struct Service {
+        name: String,
+        #[serde(flatten)]
+        labels: Mapping,
+        replicas: u32,
+        #[serde(flatten)]
+        annotations: BTreeMap<String, String>,
+        image: String,
+    }
+
+    let mut labels = Mapping::new();
+    labels.insert("tier".into(), "web".into());
+    labels.insert("app".into(), "shop".into());
+    let mut thing = Service {
+        name: "frontend".to_owned(),
+        labels,
+        replicas: 3,
+        annotations: BTreeMap::from([
+            ("owner".to_owned(), "team-a".to_owned()),
+            ("commit".to_owned(), "abc123".to_owned()),
+        ]),
+        image: "nginx".to_owned(),
+    };
+
+    let yaml = indoc! {"
+        name: frontend
+        tier: web
+        app: shop
+        replicas: 3
+        commit: abc123
+        owner: team-a
+        image: nginx
+    "};
+    assert_eq!(serde_yaml::to_string(&thing).unwrap(), yaml);
+    let value = serde_yaml::to_value(&thing).unwrap();
+    assert_eq!(serde_yaml::to_string(&value).unwrap(), yaml);
+
+    #[derive(Serialize)]
+    struct NamedFirst {
+        name: String,
+        replicas: u32,
+        #[serde(flatten)]
+        labels: Mapping,
+        #[serde(flatten)]
+        annotations: BTreeMap<String, String>,
+    }
+
+    thing.labels.insert("name".into(), "duplicate".into());
+    let mut reordered = NamedFirst {
+        name: thing.name,
+        replicas: thing.replicas,
+        labels: thing.labels,
+        annotations: thing.annotations,
+    };
+    let error = serde_yaml::to_string(&reordered).unwrap_err();
+    assert_eq!(error.to_string(), "duplicate entry with key \"name\"");
+    reordered.labels.remove("name");
+
+    let yaml = indoc! {"
+        name: frontend
+        replicas: 3
+        tier: web
+        app: shop
+        commit: abc123
+        owner: team-a
+    "};
+    assert_eq!(serde_yaml::to_string(&reordered).unwrap(), yaml);
+}
+
 #[test]
 fn test_long_string() {
     #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -573,3 +799,3619 @@ fn test_long_string() {
 
     test_serde(&thing, yaml);
 }
+
+#[test]
+fn test_singleton_map_keys() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+    enum Selector {
+        Any,
+        Id(u32),
+        Label { name: String },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Policy {
+        #[serde(with = "serde_yaml::with::singleton_map_keys")]
+        rules: BTreeMap<Selector, String>,
+        #[serde(with = "serde_yaml::with::singleton_map_keys")]
+        limits: Option<BTreeMap<Selector, Vec<u32>>>,
+    }
+
+    let thing = Policy {
+        rules: BTreeMap::from([
+            (Selector::Any, "accept".to_owned()),
+            (Selector::Id(7), "log".to_owned()),
+            (
+                Selector::Label {
+                    name: "env".to_owned(),
+                },
+                "reject".to_owned(),
+            ),
+        ]),
+        limits: Some(BTreeMap::from([(Selector::Id(1), vec![1, 2])])),
+    };
+    let yaml = indoc! {"
+        rules:
+          Any: accept
+          ? {Id: 7}
+          : log
+          ? {Label: {name: env}}
+          : reject
+        limits:
+          ? {Id: 1}
+          : - 1
+            - 2
+    "};
+    assert_eq!(serde_yaml::to_string(&thing).unwrap(), yaml);
+    let deserialized: Policy = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(deserialized, thing);
+    // Only the serializer sees that a key is from singleton_map_keys, so as
+    // a Value the keys are written like any other mapping key.
+    let value = serde_yaml::to_value(&thing).unwrap();
+    let yaml = indoc! {"
+        rules:
+          Any: accept
+          ? Id: 7
+          : log
+          ? Label:
+              name: env
+          : reject
+        limits:
+          ? Id: 1
+          : - 1
+            - 2
+    "};
+    assert_eq!(serde_yaml::to_string(&value).unwrap(), yaml);
+    let deserialized: Policy = serde_yaml::from_value(value).unwrap();
+    assert_eq!(deserialized, thing);
+
+    let thing = Policy {
+        rules: BTreeMap::new(),
+        limits: None,
+    };
+    let yaml = indoc! {"
+        rules: {}
+        limits: null
+    "};
+    test_serde(&thing, yaml);
+}
+
+#[test]
+fn test_distinct_keys() {
+    // Keys that look alike in YAML text but resolve to different values are
+    // not duplicates.
+    let mut map = HashMap::new();
+    map.insert(Value::from(1), Value::from("1"));
+    map.insert(Value::from("1"), Value::from(1));
+    map.insert(Value::Null, Value::from("null"));
+    map.insert(Value::from("null"), Value::Null);
+    map.insert(Value::from(true), Value::from("true"));
+    map.insert(Value::from("true"), Value::from(true));
+    let nested = BTreeMap::from([("1", BTreeMap::from([("1", 1)]))]);
+    map.insert(Value::from("nested"), serde_yaml::to_value(nested).unwrap());
+
+    let yaml = serde_yaml::to_string(&map).unwrap();
+    let deserialized: HashMap<Value, Value> = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(map, deserialized);
+}
+
+#[test]
+fn test_option_root() {
+    fn test_none<T>()
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
+    {
+        assert_eq!(serde_yaml::to_string(&None::<T>).unwrap(), "null\n");
+        for yaml in ["", "---\n", "# empty\n", "null\n", "~\n", "--- null\n"] {
+            assert_eq!(serde_yaml::from_str::<Option<T>>(yaml).unwrap(), None);
+        }
+    }
+
+    test_none::<()>();
+    test_none::<Option<u32>>();
+    test_none::<Option<Option<u32>>>();
+    test_none::<Value>();
+
+    // Some(T) is written the same as T, which for a null-like T reads back
+    // as None.
+    assert_eq!(serde_yaml::to_string(&Some(())).unwrap(), "null\n");
+    assert_eq!(serde_yaml::to_string(&Some(None::<u32>)).unwrap(), "null\n");
+    assert_eq!(serde_yaml::to_string(&Some(Value::Null)).unwrap(), "null\n");
+
+    test_serde(&Some(Some(1u32)), "1\n");
+    let value = Value::Sequence(vec![Value::Null]);
+    test_serde(&Some(value.clone()), "- null\n");
+    assert_eq!(
+        serde_yaml::from_str::<Option<Value>>("- ~\n").unwrap(),
+        Some(value)
+    );
+}
+
+#[test]
+fn test_nested_option() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Patch {
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "serde_yaml::with::nested_option"
+        )]
+        timeout: Option<Option<u32>>,
+        retries: Option<u32>,
+    }
+
+    let thing = Patch {
+        timeout: None,
+        retries: Some(3),
+    };
+    test_serde(&thing, "retries: 3\n");
+
+    let thing = Patch {
+        timeout: Some(None),
+        retries: None,
+    };
+    test_serde(&thing, "timeout: null\nretries: null\n");
+
+    let thing = Patch {
+        timeout: Some(Some(30)),
+        retries: None,
+    };
+    test_serde(&thing, "timeout: 30\nretries: null\n");
+
+    // Without nested_option, Some(None) reads back as None.
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Plain {
+        timeout: Option<Option<u32>>,
+    }
+
+    let thing = Plain {
+        timeout: Some(None),
+    };
+    let yaml = serde_yaml::to_string(&thing).unwrap();
+    assert_eq!(yaml, "timeout: null\n");
+    let deserialized: Plain = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(deserialized.timeout, None);
+}
+
+// Generates pseudorandom Values, with strings built from characters that are
+// significant somewhere in YAML syntax or scalar resolution.
+struct ValueGenerator {
+    state: u64,
+}
+
+impl ValueGenerator {
+    fn next(&mut self) -> u64 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn string(&mut self) -> String {
+        const WORDS: &[&str] = &[
+            "", "~", "null", "NULL", "true", "False", "yes", "no", "1", "-1", "0x1F", "0o17", "+1",
+            "1e3", "1_000", ".inf", "-.Inf", ".nan", "0.5", ".5", "012", "---", "...", "<<", "=",
+        ];
+        const CHARS: &[char] = &[
+            ' ',
+            '\t',
+            '\n',
+            '\r',
+            ':',
+            '#',
+            '-',
+            '?',
+            '!',
+            '&',
+            '*',
+            '\'',
+            '"',
+            '.',
+            ',',
+            '[',
+            ']',
+            '{',
+            '}',
+            '|',
+            '>',
+            '%',
+            '@',
+            '`',
+            '~',
+            '0',
+            '1',
+            'e',
+            'x',
+            '+',
+            '_',
+            'a',
+            'n',
+            'é',
+            '\u{85}',
+            '\u{a0}',
+            '\u{feff}',
+            '\u{2028}',
+            '\u{1f600}',
+        ];
+        let mut string = String::new();
+        for _ in 0..self.below(4) {
+            if self.below(3) == 0 {
+                string.push_str(WORDS[self.below(WORDS.len() as u64) as usize]);
+            } else {
+                string.push(CHARS[self.below(CHARS.len() as u64) as usize]);
+            }
+        }
+        string
+    }
+
+    fn value(&mut self, depth: usize) -> Value {
+        let choices = if depth == 0 { 5 } else { 8 };
+        match self.below(choices) {
+            0 => Value::Null,
+            1 => Value::Bool(self.below(2) == 0),
+            2 => match self.below(4) {
+                0 => Value::Number(self.next().into()),
+                1 => Value::Number((self.next() as i64).into()),
+                2 => Value::Number(f64::from_bits(self.next()).into()),
+                _ => Value::Number(Number::from(self.below(2000) as f64 / 8.0 - 100.0)),
+            },
+            3 | 4 => Value::String(self.string()),
+            5 => Value::Sequence((0..self.below(4)).map(|_| self.value(depth - 1)).collect()),
+            6 => {
+                let mut mapping = Mapping::new();
+                for _ in 0..self.below(4) {
+                    let key = self.value(depth - 1);
+                    let value = self.value(depth - 1);
+                    mapping.insert(key, value);
+                }
+                Value::Mapping(mapping)
+            }
+            _ => {
+                let tag = format!("!{}", ["A", "Tag", "e1"][self.below(3) as usize]);
+                let value = match self.value(depth - 1) {
+                    Value::Tagged(tagged) => tagged.value,
+                    value => value,
+                };
+                Value::Tagged(Box::new(TaggedValue {
+                    tag: Tag::new(tag),
+                    value,
+                }))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_verify_roundtrip_fuzz() {
+    let builder = SerializerBuilder::new().verify_roundtrip(true);
+    let mut generator = ValueGenerator {
+        state: 0x853c_49e6_748f_ea9b,
+    };
+    for _ in 0..2000 {
+        let value = generator.value(3);
+        if let Err(err) = builder.to_string(&value) {
+            panic!("{:?}: {}", value, err);
+        }
+    }
+}
+
+#[test]
+fn test_verify_roundtrip() {
+    struct Entries(Vec<(Value, Value)>);
+
+    impl serde::Serialize for Entries {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            let mut map = serializer.serialize_map(Some(self.0.len()))?;
+            for (k, v) in &self.0 {
+                map.serialize_entry(k, v)?;
+            }
+            map.end()
+        }
+    }
+
+    let builder = SerializerBuilder::new().verify_roundtrip(true);
+    let entries = Entries(vec![
+        (Value::from(0.0), Value::from(1)),
+        (Value::from(-0.0), Value::from(2)),
+    ]);
+    let yaml = serde_yaml::to_string(&entries).unwrap();
+    assert_eq!(yaml, "0.0: 1\n-0.0: 2\n");
+    let error = builder.to_string(&entries).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "serialized YAML cannot be read back: duplicate entry with key 0.0",
+    );
+
+    let thing = BTreeMap::from([(u128::MAX, i128::MIN)]);
+    let yaml = builder.to_string(&thing).unwrap();
+    let value: Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(value, serde_yaml::to_value(&thing).unwrap());
+
+    let mut buffer = Vec::new();
+    let mut serializer = builder.build(&mut buffer);
+    serde::Serialize::serialize(&Value::from("1"), &mut serializer).unwrap();
+    serde::Serialize::serialize(&Value::from(1), &mut serializer).unwrap();
+    serializer.into_inner().unwrap();
+    assert_eq!(buffer, b"'1'\n--- 1\n");
+}
+
+#[test]
+fn test_map_scalar() {
+    #[derive(Serialize)]
+    struct Config {
+        name: String,
+        password: String,
+        database: Database,
+        services: Vec<Service>,
+        extra: BTreeMap<String, Value>,
+    }
+
+    #[derive(Serialize)]
+    struct Database {
+        url: String,
+        password: String,
+        timeout: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Service {
+        name: String,
+        token: Option<String>,
+    }
+
+    let config = Config {
+        name: "  prod  ".to_owned(),
+        password: "secret-root".to_owned(),
+        database: Database {
+            url: "postgres://db".to_owned(),
+            password: "secret-db".to_owned(),
+            timeout: 2.500001,
+        },
+        services: vec![
+            Service {
+                name: "api".to_owned(),
+                token: Some("secret-api".to_owned()),
+            },
+            Service {
+                name: "worker".to_owned(),
+                token: None,
+            },
+        ],
+        extra: BTreeMap::from([(
+            "cache".to_owned(),
+            serde_yaml::from_str("{token: secret-cache, hosts: [a, b]}").unwrap(),
+        )]),
+    };
+
+    let redacted = SerializerBuilder::new().map_scalar(|path, _scalar| match path.key() {
+        Some("password" | "token") => ScalarAction::Replace(ScalarRef::String("<redacted>".into())),
+        _ => ScalarAction::Keep,
+    });
+    let yaml = redacted.to_string(&config).unwrap();
+    let expected = indoc! {"
+        name: '  prod  '
+        password: <redacted>
+        database:
+          url: postgres://db
+          password: <redacted>
+          timeout: 2.500001
+        services:
+        - name: api
+          token: <redacted>
+        - name: worker
+          token: <redacted>
+        extra:
+          cache:
+            token: <redacted>
+            hosts:
+            - a
+            - b
+    "};
+    assert_eq!(yaml, expected);
+    assert!(!yaml.contains("secret"));
+
+    let normalized = SerializerBuilder::new().map_scalar(|path, scalar| match scalar {
+        _ if path.key() == Some("token") => ScalarAction::Skip,
+        ScalarRef::String(string) if string.trim() != string => {
+            ScalarAction::Replace(ScalarRef::String(string.trim().to_owned().into()))
+        }
+        ScalarRef::Number(number) => match number.parse::<f64>() {
+            Ok(float) => {
+                let rounded = (float * 100.0).round() / 100.0;
+                ScalarAction::Replace(ScalarRef::Number(rounded.to_string().into()))
+            }
+            Err(_) => ScalarAction::Keep,
+        },
+        _ if path.to_string() == "extra.cache.hosts[1]" => ScalarAction::Skip,
+        _ => ScalarAction::Keep,
+    });
+    let yaml = normalized.to_string(&config).unwrap();
+    let expected = indoc! {"
+        name: prod
+        password: secret-root
+        database:
+          url: postgres://db
+          password: secret-db
+          timeout: 2.5
+        services:
+        - name: api
+        - name: worker
+        extra:
+          cache:
+            hosts:
+            - a
+    "};
+    assert_eq!(yaml, expected);
+
+    let yaml = normalized.to_string("token").unwrap();
+    assert_eq!(yaml, "token\n");
+    let skip_all = SerializerBuilder::new().map_scalar(|_path, _scalar| ScalarAction::Skip);
+    assert_eq!(skip_all.to_string("secret").unwrap(), "null\n");
+    assert_eq!(skip_all.to_string(&config.database).unwrap(), "{}\n");
+}
+
+#[test]
+fn test_map_node() {
+    #[derive(Serialize)]
+    struct Deployment {
+        name: String,
+        internal: Internal,
+        replicas: Vec<Replica>,
+        env: BTreeMap<String, String>,
+    }
+
+    #[derive(Serialize)]
+    struct Internal {
+        build: u32,
+        hosts: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Replica {
+        zone: String,
+        debug: Vec<String>,
+    }
+
+    let deployment = Deployment {
+        name: "web".to_owned(),
+        internal: Internal {
+            build: 7,
+            hosts: vec!["build-1".to_owned()],
+        },
+        replicas: vec![
+            Replica {
+                zone: "a".to_owned(),
+                debug: vec!["trace".to_owned()],
+            },
+            Replica {
+                zone: "b".to_owned(),
+                debug: Vec::new(),
+            },
+        ],
+        env: BTreeMap::from([("TOKEN".to_owned(), "abc".to_owned())]),
+    };
+
+    let visited = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let audited = SerializerBuilder::new().map_node({
+        let visited = visited.clone();
+        move |path, node| {
+            visited.lock().unwrap().push(path.to_string());
+            match (path.to_string().as_str(), node) {
+                ("internal" | "replicas[1]", _) => NodeAction::Skip,
+                (_, NodeRef::Sequence) if path.key() == Some("debug") => {
+                    NodeAction::Replace(ScalarRef::Null)
+                }
+                ("env", _) => NodeAction::Replace(ScalarRef::String("<redacted>".into())),
+                (".", NodeRef::Mapping) => NodeAction::Insert(vec![
+                    ("audited".to_owned(), ScalarRef::Bool(true)),
+                    ("by".to_owned(), ScalarRef::String("null".into())),
+                ]),
+                _ => NodeAction::Keep,
+            }
+        }
+    });
+    let yaml = audited.to_string(&deployment).unwrap();
+    let expected = indoc! {"
+        name: web
+        replicas:
+        - zone: a
+          debug: null
+        env: <redacted>
+        audited: true
+        by: 'null'
+    "};
+    assert_eq!(yaml, expected);
+    // Nothing inside of a node that is left out or replaced is visited.
+    let expected = [
+        ".",
+        "name",
+        "internal",
+        "replicas",
+        "replicas[0]",
+        "replicas[0].zone",
+        "replicas[0].debug",
+        "replicas[1]",
+        "env",
+    ];
+    assert_eq!(*visited.lock().unwrap(), expected);
+
+    let value = serde_yaml::to_value(&deployment).unwrap();
+    let sorted = audited.clone().sort_keys(true);
+    let expected = indoc! {"
+        audited: true
+        by: 'null'
+        env: <redacted>
+        name: web
+        replicas:
+        - debug: null
+          zone: a
+    "};
+    assert_eq!(sorted.to_string(&value).unwrap(), expected);
+
+    let duplicate = SerializerBuilder::new().map_node(|_path, node| match node {
+        NodeRef::Mapping => NodeAction::Insert(vec![("name".to_owned(), ScalarRef::Null)]),
+        _ => NodeAction::Keep,
+    });
+    let error = duplicate.to_string(&deployment).unwrap_err();
+    assert_eq!(error.to_string(), "duplicate entry with key \"name\"");
+
+    let skip_all = SerializerBuilder::new().map_node(|_path, _node| NodeAction::Skip);
+    assert_eq!(skip_all.to_string(&deployment).unwrap(), "null\n");
+}
+
+#[test]
+fn test_serializer_profiles() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Deployment {
+        kind: String,
+        metadata: Metadata,
+        replicas: u32,
+        ports: Vec<u16>,
+        schedule: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Metadata {
+        name: String,
+        labels: BTreeMap<String, String>,
+    }
+
+    let thing = Deployment {
+        kind: "Deployment".to_owned(),
+        metadata: Metadata {
+            name: "web".to_owned(),
+            labels: BTreeMap::from([
+                ("country".to_owned(), "NO".to_owned()),
+                ("window".to_owned(), "12:30".to_owned()),
+            ]),
+        },
+        replicas: 3,
+        ports: vec![80, 443],
+        schedule: None,
+    };
+
+    let kubernetes = indoc! {"
+        kind: Deployment
+        metadata:
+          name: web
+          labels:
+            country: 'NO'
+            window: '12:30'
+        replicas: 3
+        ports:
+          - 80
+          - 443
+        schedule: null
+    "};
+    let github_actions = indoc! {"
+        kind: Deployment
+        metadata:
+          name: web
+          labels:
+            country: NO
+            window: 12:30
+        replicas: 3
+        ports:
+          - 80
+          - 443
+        schedule: null
+    "};
+    let canonical = indoc! {r#"
+        ---
+        !!map {
+          ? !!str "kind"
+          : !!str "Deployment",
+          ? !!str "metadata"
+          : !!map {
+            ? !!str "name"
+            : !!str "web",
+            ? !!str "labels"
+            : !!map {
+              ? !!str "country"
+              : !!str "NO",
+              ? !!str "window"
+              : !!str "12:30",
+            },
+          },
+          ? !!str "replicas"
+          : !!int "3",
+          ? !!str "ports"
+          : !!seq [
+            !!int "80",
+            !!int "443",
+          ],
+          ? !!str "schedule"
+          : !!null "null",
+        }
+    "#};
+    let compat11 = indoc! {"
+        kind: Deployment
+        metadata:
+          name: web
+          labels:
+            country: 'NO'
+            window: '12:30'
+        replicas: 3
+        ports:
+        - 80
+        - 443
+        schedule: null
+    "};
+
+    for (profile, expected) in [
+        (SerializerProfile::KubernetesStyle, kubernetes),
+        (SerializerProfile::GitHubActionsStyle, github_actions),
+        (SerializerProfile::Canonical, canonical),
+        (SerializerProfile::Compat11, compat11),
+    ] {
+        let builder = SerializerBuilder::with_profile(profile);
+        let yaml = builder.to_string(&thing).unwrap();
+        assert_eq!(yaml, expected, "{:?}", profile);
+        let deserialized: Deployment = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(deserialized, thing, "{:?}", profile);
+    }
+
+    #[derive(Serialize)]
+    enum Probe {
+        Http(u16),
+    }
+
+    for profile in [
+        SerializerProfile::KubernetesStyle,
+        SerializerProfile::GitHubActionsStyle,
+    ] {
+        let error = SerializerBuilder::with_profile(profile)
+            .to_string(&[Probe::Http(8080)])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "tag `!Http` is not allowed when tags are disabled",
+        );
+    }
+    let yaml = SerializerBuilder::with_profile(SerializerProfile::Compat11)
+        .to_string(&[Probe::Http(8080)])
+        .unwrap();
+    assert_eq!(yaml, "- !Http 8080\n");
+
+    // Options of a profile can be overridden individually.
+    let yaml = SerializerBuilder::with_profile(SerializerProfile::KubernetesStyle)
+        .indent(4)
+        .document_start(true)
+        .to_string(&thing.metadata)
+        .unwrap();
+    let expected = indoc! {"
+        ---
+        name: web
+        labels:
+            country: 'NO'
+            window: '12:30'
+    "};
+    assert_eq!(yaml, expected);
+
+    // The same builders from the Serializer, like Deserializer::builder.
+    let yaml = serde_yaml::Serializer::with_profile(SerializerProfile::KubernetesStyle)
+        .indent(4)
+        .document_start(true)
+        .to_string(&thing.metadata)
+        .unwrap();
+    assert_eq!(yaml, expected);
+    let yaml = serde_yaml::Serializer::builder()
+        .indent_sequences(true)
+        .to_string(&thing.metadata)
+        .unwrap();
+    let indented = SerializerBuilder::new()
+        .indent_sequences(true)
+        .to_string(&thing.metadata)
+        .unwrap();
+    assert_eq!(yaml, indented);
+}
+
+#[test]
+fn test_deterministic() {
+    #[derive(Serialize)]
+    struct Service {
+        name: String,
+        weight: f64,
+        #[serde(with = "serde_yaml::with::sorted_set")]
+        hosts: HashSet<String>,
+        ports: HashMap<u16, String>,
+    }
+
+    fn build() -> HashMap<String, Service> {
+        let mut services = HashMap::new();
+        for name in ["web", "api", "db", "cache", "queue"] {
+            let service = Service {
+                name: name.to_owned(),
+                weight: -0.0,
+                hosts: ["c", "a", "b", "d"]
+                    .iter()
+                    .map(|host| format!("{}.{}", host, name))
+                    .collect(),
+                ports: HashMap::from([
+                    (10, "ten".to_owned()),
+                    (9, "nine".to_owned()),
+                    (443, "https".to_owned()),
+                    (80, "http".to_owned()),
+                ]),
+            };
+            services.insert(name.to_owned(), service);
+        }
+        services
+    }
+
+    let builder = SerializerBuilder::new()
+        .deterministic(true)
+        .verify_roundtrip(true);
+    let outputs: HashSet<String> = (0..100)
+        .map(|_| builder.to_string(&build()).unwrap())
+        .collect();
+    assert_eq!(outputs.len(), 1);
+
+    let yaml = outputs.into_iter().next().unwrap();
+    let expected = indoc! {"
+        name: api
+        weight: 0.0
+        hosts:
+        - a.api
+        - b.api
+        - c.api
+        - d.api
+        ports:
+          9: nine
+          10: ten
+          80: http
+          443: https
+    "};
+    let value: Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(serde_yaml::to_string(&value["api"]).unwrap(), expected);
+    let names: Vec<&str> = value
+        .as_mapping()
+        .unwrap()
+        .keys()
+        .map(|key| key.as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["api", "cache", "db", "queue", "web"]);
+
+    // Keys of different types are ordered like Value.
+    let mut mapping = Mapping::new();
+    mapping.insert(Value::from("b"), Value::from(1));
+    mapping.insert(Value::from(2), Value::from(2));
+    mapping.insert(Value::from(true), Value::from(3));
+    mapping.insert(Value::Null, Value::from(4));
+    mapping.insert(Value::from("a"), Value::from(5));
+    let yaml = builder.to_string(&mapping).unwrap();
+    let expected = indoc! {"
+        null: 4
+        true: 3
+        2: 2
+        a: 5
+        b: 1
+    "};
+    assert_eq!(yaml, expected);
+}
+
+#[test]
+fn test_named_struct() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Service {
+        image: String,
+        replicas: u8,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Stack {
+        #[serde(with = "serde_yaml::with::named_struct")]
+        web: Service,
+        #[serde(with = "serde_yaml::with::named_struct::ignore_case")]
+        worker: Option<Service>,
+    }
+
+    let service = |image: &str| Service {
+        image: image.to_owned(),
+        replicas: 2,
+    };
+
+    let thing = Stack {
+        web: service("nginx"),
+        worker: Some(service("celery")),
+    };
+    let yaml = indoc! {"
+        web:
+          Service:
+            image: nginx
+            replicas: 2
+        worker:
+          Service:
+            image: celery
+            replicas: 2
+    "};
+    test_serde(&thing, yaml);
+
+    let thing = Stack {
+        web: service("nginx"),
+        worker: None,
+    };
+    test_serde(
+        &thing,
+        "web:\n  Service:\n    image: nginx\n    replicas: 2\nworker: null\n",
+    );
+
+    let yaml =
+        "web: {Service: {image: a, replicas: 2}}\nworker: {service: {image: b, replicas: 2}}\n";
+    let stack: Stack = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(stack.worker, Some(service("b")));
+
+    let yaml = "web: {service: {image: a, replicas: 2}}\nworker: null\n";
+    let error = serde_yaml::from_str::<Stack>(yaml).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "web: invalid value: string \"service\", expected a map with the single key `Service` at line 1 column 6",
+    );
+
+    let yaml = "web: {Service: {image: a, replicas: 2}, extra: 1}\nworker: null\n";
+    let error = serde_yaml::from_str::<Stack>(yaml).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "web: invalid value: map, expected map with a single key at line 1 column 6",
+    );
+
+    let yaml = "Service:\n  image: redis\n  replicas: 2\n";
+    let deserializer = serde_yaml::Deserializer::from_str(yaml);
+    let root: Service = serde_yaml::with::named_struct::deserialize(deserializer).unwrap();
+    assert_eq!(root, service("redis"));
+    let mut buffer = Vec::new();
+    let mut serializer = serde_yaml::Serializer::new(&mut buffer);
+    serde_yaml::with::named_struct::serialize(&root, &mut serializer).unwrap();
+    assert_eq!(String::from_utf8(buffer).unwrap(), yaml);
+}
+
+#[test]
+fn test_enum_fallback() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Step {
+        Run(String),
+        Checkout { depth: u32 },
+        Unknown { tag: String, value: Value },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Job {
+        #[serde(with = "serde_yaml::with::enum_fallback")]
+        first: Step,
+        #[serde(with = "serde_yaml::with::enum_fallback")]
+        second: Option<Step>,
+    }
+
+    let yaml = indoc! {"
+        first: !Run make
+        second: !Checkout
+          depth: 1
+    "};
+    let thing = Job {
+        first: Step::Run("make".to_owned()),
+        second: Some(Step::Checkout { depth: 1 }),
+    };
+    test_serde(&thing, yaml);
+
+    let yaml = indoc! {"
+        first: !Cache
+          key: deps
+          paths:
+          - target
+        second: !Upload artifact
+    "};
+    let cache: Value = serde_yaml::from_str("{key: deps, paths: [target]}").unwrap();
+    let thing = Job {
+        first: Step::Unknown {
+            tag: "Cache".to_owned(),
+            value: cache.clone(),
+        },
+        second: Some(Step::Unknown {
+            tag: "Upload".to_owned(),
+            value: Value::from("artifact"),
+        }),
+    };
+    test_serde(&thing, yaml);
+
+    let yaml = indoc! {"
+        first:
+          Cache:
+            key: deps
+            paths: [target]
+        second:
+          Checkout:
+            depth: 1
+    "};
+    let job: Job = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(
+        job.first,
+        Step::Unknown {
+            tag: "Cache".to_owned(),
+            value: cache,
+        },
+    );
+    assert_eq!(job.second, Some(Step::Checkout { depth: 1 }));
+
+    let job: Job = serde_yaml::from_str("first: !Run make\nsecond: null\n").unwrap();
+    assert_eq!(job.second, None);
+
+    let error = serde_yaml::from_str::<Job>("first: !Checkout {depth: x}\nsecond: null\n");
+    assert_eq!(
+        error.unwrap_err().to_string(),
+        "first: depth: invalid type: string \"x\", expected u32 at line 1 column 8",
+    );
+}
+
+#[test]
+fn test_styled_string() {
+    use serde_yaml::de::StyledString;
+    use serde_yaml::ScalarStyle;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Rule {
+        name: StyledString,
+        when: Vec<StyledString>,
+        args: BTreeMap<String, StyledString>,
+    }
+
+    let yaml = indoc! {r#"
+        name: plain
+        when:
+        - 'single'
+        - "double"
+        args:
+          folded: >
+            one line
+          literal: |
+            two
+            lines
+    "#};
+    let rule: Rule = serde_yaml::from_str(yaml).unwrap();
+    let expected = Rule {
+        name: StyledString::new("plain", ScalarStyle::Plain),
+        when: vec![
+            StyledString::new("single", ScalarStyle::SingleQuoted),
+            StyledString::new("double", ScalarStyle::DoubleQuoted),
+        ],
+        args: BTreeMap::from([
+            (
+                "folded".to_owned(),
+                StyledString::new("one line\n", ScalarStyle::Folded),
+            ),
+            (
+                "literal".to_owned(),
+                StyledString::new("two\nlines\n", ScalarStyle::Literal),
+            ),
+        ]),
+    };
+    assert_eq!(expected, rule);
+    assert_eq!(yaml, serde_yaml::to_string(&rule).unwrap());
+
+    // Deserializers of other formats have no style to give.
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    let rule: Rule = serde_yaml::from_value(value).unwrap();
+    assert_eq!(ScalarStyle::Plain, rule.when[0].style);
+    assert_eq!(ScalarStyle::Plain, rule.args["literal"].style);
+}
+
+#[test]
+fn test_preserve_scalar_style() {
+    use serde_yaml::ScalarStyle;
+
+    let yaml = indoc! {r#"
+        'name': plain
+        when:
+        - 'single'
+        - "double"
+        - "8080"
+        args:
+          folded: >
+            one line
+          literal: |
+            two
+            lines
+        'on': "yes"
+    "#};
+    let builder = DeserializerBuilder::new().preserve_scalar_style(true);
+    let value: Value = builder.from_str(yaml).unwrap();
+    assert_eq!(value["name"], "plain");
+    assert_eq!(value["when"][0], Value::String("single".to_owned()));
+    assert_eq!(value["when"][2].as_str(), Some("8080"));
+    match &value["args"]["literal"] {
+        Value::Styled(styled) => assert_eq!(ScalarStyle::Literal, styled.style),
+        other => panic!("expected a styled string, got {:?}", other),
+    }
+    assert_eq!(yaml, serde_yaml::to_string(&value).unwrap());
+
+    // Styles do not take part in comparisons.
+    let unstyled: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(unstyled, value);
+
+    let when: Vec<String> = serde_yaml::from_value(value["when"].clone()).unwrap();
+    assert_eq!(when, ["single", "double", "8080"]);
+}
+
+#[test]
+fn test_default_as_comment() {
+    #[derive(Serialize, Deserialize, PartialEq, Default, Debug)]
+    struct Pool {
+        size: u32,
+        idle_timeout: String,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Template {
+        #[serde(
+            default = "max_connections",
+            serialize_with = "comment_max_connections"
+        )]
+        max_connections: u32,
+        name: String,
+        #[serde(default, with = "serde_yaml::with::default_as_comment")]
+        hosts: Vec<String>,
+        #[serde(default, with = "serde_yaml::with::default_as_comment")]
+        pool: Pool,
+        replicas: u32,
+    }
+
+    fn max_connections() -> u32 {
+        100
+    }
+
+    fn comment_max_connections<S>(value: &u32, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde_yaml::with::default_as_comment::serialize_with_default(
+            value,
+            &max_connections(),
+            serializer,
+        )
+    }
+
+    let template = Template {
+        max_connections: 100,
+        name: "web".to_owned(),
+        hosts: Vec::new(),
+        pool: Pool::default(),
+        replicas: 3,
+    };
+    let expected = indoc! {"
+        # max_connections: 100
+        name: web
+        # hosts: []
+        # pool:
+        #   size: 0
+        #   idle_timeout: ''
+        replicas: 3
+    "};
+    assert_eq!(serde_yaml::to_string(&template).unwrap(), expected);
+    assert_eq!(
+        serde_yaml::from_str::<Template>(expected).unwrap(),
+        template
+    );
+
+    let expected = indoc! {"
+        - # max_connections: 100
+          name: web
+          # hosts: []
+          # pool:
+          #   size: 0
+          #   idle_timeout: ''
+          replicas: 3
+    "};
+    let templates = vec![template];
+    let yaml = SerializerBuilder::new()
+        .verify_roundtrip(true)
+        .to_string(&templates)
+        .unwrap();
+    assert_eq!(yaml, expected);
+    assert_eq!(
+        serde_yaml::from_str::<Vec<Template>>(&yaml).unwrap(),
+        templates
+    );
+
+    // Values that differ from the default are written as usual.
+    let template = Template {
+        max_connections: 10,
+        name: "db".to_owned(),
+        hosts: vec!["a".to_owned()],
+        pool: Pool {
+            size: 4,
+            idle_timeout: "5m".to_owned(),
+        },
+        replicas: 1,
+    };
+    let expected = indoc! {"
+        max_connections: 10
+        name: db
+        hosts:
+        - a
+        pool:
+          size: 4
+          idle_timeout: 5m
+        replicas: 1
+    "};
+    assert_eq!(serde_yaml::to_string(&template).unwrap(), expected);
+}
+
+#[test]
+fn test_crlf() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Document {
+        plain: String,
+        quoted: String,
+        literal: String,
+        list: Vec<u8>,
+        #[serde(with = "serde_yaml::with::default_as_comment", default)]
+        nested: BTreeMap<String, String>,
+    }
+
+    let thing = Document {
+        plain: "a b".to_owned(),
+        quoted: "yes\t".to_owned(),
+        literal: "first\n\nsecond\n".to_owned(),
+        list: vec![1, 2],
+        nested: BTreeMap::new(),
+    };
+    let yaml = indoc! {"
+        plain: a b
+        quoted: \"yes\\t\"
+        literal: |
+          first
+
+          second
+        list:
+        - 1
+        - 2
+        # nested: {}
+    "}
+    .replace('\n', "\r\n");
+    let serialized = SerializerBuilder::new()
+        .crlf(true)
+        .verify_roundtrip(true)
+        .to_string(&thing)
+        .unwrap();
+    assert_eq!(yaml, serialized);
+    assert_eq!(
+        thing,
+        serde_yaml::from_str::<Document>(&serialized).unwrap()
+    );
+}
+
+#[test]
+fn test_key_order() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Config {
+        version: u32,
+        labels: HashMap<String, String>,
+        services: Vec<Service>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Service {
+        name: String,
+        env: HashMap<String, String>,
+        port: u16,
+    }
+
+    let yaml = indoc! {"
+        services:
+        - port: 80
+          name: web
+          env:
+            ZONE: b
+            MODE: prod
+            DEBUG: 'false'
+        - env:
+            B: '2'
+            A: '1'
+          name: db
+          port: 5432
+        labels:
+          tier: front
+          app: shop
+          zone: b
+        version: 3
+    "};
+    let (mut config, order): (Config, _) = serde_yaml::from_str_with_order(yaml).unwrap();
+    config.services[1].port = 5433;
+
+    let serialized = SerializerBuilder::new()
+        .key_order(&order)
+        .to_string(&config)
+        .unwrap();
+    let changed: Vec<(&str, &str)> = yaml
+        .lines()
+        .zip(serialized.lines())
+        .filter(|(before, after)| before != after)
+        .collect();
+    assert_eq!(changed, [("  port: 5432", "  port: 5433")]);
+    assert_eq!(yaml.lines().count(), serialized.lines().count());
+
+    // Keys that were not read are written after the others.
+    config.labels.insert("owner".to_owned(), "ops".to_owned());
+    config.labels.insert("env".to_owned(), "dev".to_owned());
+    let serialized = SerializerBuilder::new()
+        .key_order(&order)
+        .deterministic(true)
+        .to_string(&config)
+        .unwrap();
+    let labels = "labels:\n  tier: front\n  app: shop\n  zone: b\n  env: dev\n  owner: ops\n";
+    assert!(serialized.contains(labels), "{}", serialized);
+}
+
+#[test]
+fn test_nonprintable_characters() {
+    use serde_yaml::de::StyledString;
+    use serde_yaml::ScalarStyle;
+
+    // Every class of character that YAML does not allow unescaped: the C0
+    // control characters other than tab and line breaks, DEL, the C1 control
+    // characters, and the noncharacters U+FFFE and U+FFFF. Also NEL, which is
+    // a line break, and the byte order mark and line and paragraph separators,
+    // which are allowed but are not written raw in every position.
+    let mut chars: Vec<char> = (0x00..0x20)
+        .chain(0x7F..0xA0)
+        .filter_map(char::from_u32)
+        .collect();
+    chars.extend(['\u{FEFF}', '\u{2028}', '\u{2029}', '\u{FFFE}', '\u{FFFF}']);
+
+    let styles = [
+        ScalarStyle::Plain,
+        ScalarStyle::SingleQuoted,
+        ScalarStyle::DoubleQuoted,
+        ScalarStyle::Literal,
+        ScalarStyle::Folded,
+    ];
+    let builder = SerializerBuilder::new().verify_roundtrip(true);
+    for ch in chars {
+        let strings = [
+            ch.to_string(),
+            format!("a{}", ch),
+            format!("{}b", ch),
+            format!(" {} ", ch),
+            format!("a{}b\n{}{}c\n", ch, ch, ch),
+        ];
+        for string in strings {
+            for style in styles {
+                let yaml = builder
+                    .to_string(&StyledString::new(string.clone(), style))
+                    .unwrap();
+                let deserialized: String = serde_yaml::from_str(&yaml).unwrap();
+                assert_eq!(deserialized, string, "{:?}", yaml);
+            }
+
+            let mut map = BTreeMap::new();
+            map.insert(string.clone(), vec![string.clone()]);
+            let yaml = builder.to_string(&map).unwrap();
+            let deserialized: BTreeMap<String, Vec<String>> = serde_yaml::from_str(&yaml).unwrap();
+            assert_eq!(deserialized, map, "{:?}", yaml);
+        }
+    }
+
+    // Tags cannot have escapes.
+    let value = Value::Tagged(Box::new(TaggedValue {
+        tag: Tag::new("bad\u{FFFE}"),
+        value: Value::Null,
+    }));
+    let error = serde_yaml::to_string(&value).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "tag `!bad\\u{fffe}` contains character U+FFFE, which is not allowed in YAML",
+    );
+}
+
+#[test]
+fn test_quote_reason() {
+    use serde_yaml::scalar::{quote_reason, QuoteContext, QuoteReason};
+    use serde_yaml::SchemaProfile;
+
+    let corpus = [
+        "",
+        "text",
+        "true",
+        "True",
+        "yes",
+        "~",
+        "null",
+        "8080",
+        "0x1F",
+        ".inf",
+        "1e3",
+        "12:30",
+        "-",
+        "- a",
+        "-a",
+        "?",
+        "? a",
+        "?a",
+        ":",
+        ": a",
+        ":a",
+        "a:",
+        "a: b",
+        "a:b",
+        "a #b",
+        "a#b",
+        "#a",
+        "*a",
+        "&a",
+        "!a",
+        "|",
+        ">",
+        "'a",
+        "\"a",
+        "%a",
+        "@a",
+        "`a",
+        "[a",
+        "]",
+        "{a",
+        "}",
+        ",a",
+        "a,b",
+        "a[b]",
+        "a{b}",
+        "a?b",
+        "---",
+        "--- a",
+        "...",
+        "..a",
+        " a",
+        "a ",
+        "a\nb",
+        "a\n",
+        "\n",
+        "a\rb",
+        "a\u{85}b",
+        "a\u{2028}b",
+        "\ta",
+        "a\t",
+        "a\tb",
+        "a\t#b",
+        "\u{7}",
+        "\u{FEFF}a",
+        "\u{FFFE}",
+        "é",
+        "日本",
+        "😀",
+        "a - b",
+        "a -b",
+    ];
+    let is_plain = |yaml: &str| !yaml.starts_with(['\'', '"', '|', '>']);
+    for string in corpus {
+        let reason = quote_reason(string, QuoteContext::new());
+        let yaml = serde_yaml::to_string(&string).unwrap();
+        assert_eq!(is_plain(&yaml), reason.is_none(), "{:?} {:?}", yaml, reason);
+        if reason == Some(QuoteReason::LineBreak) && string.contains('\n') {
+            assert!(yaml.starts_with(['|', '"']), "{:?}", yaml);
+        }
+        let deserialized: String = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(deserialized, string);
+
+        let reason = quote_reason(string, QuoteContext::new().flow(true));
+        let flow = SerializerBuilder::new().flow_style(true);
+        let yaml = flow.to_string(&[string]).unwrap();
+        let written = yaml
+            .strip_prefix('[')
+            .and_then(|yaml| yaml.strip_suffix("]\n"))
+            .unwrap();
+        assert_eq!(
+            is_plain(written),
+            reason.is_none(),
+            "{:?} {:?}",
+            yaml,
+            reason
+        );
+        let deserialized: Vec<String> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(deserialized, [string]);
+    }
+
+    let context = QuoteContext::new().quote_for(SchemaProfile::Legacy11);
+    assert_eq!(quote_reason("yes", context), Some(QuoteReason::Bool));
+    assert_eq!(quote_reason("0755", context), Some(QuoteReason::Number));
+    assert_eq!(quote_reason("yes", QuoteContext::new()), None);
+
+    assert_eq!(quote_reason("a,b", QuoteContext::new()), None);
+    assert_eq!(
+        quote_reason("a,b", QuoteContext::new().flow(true)),
+        Some(QuoteReason::FlowIndicator(',')),
+    );
+    assert_eq!(
+        quote_reason("- a", QuoteContext::new()),
+        Some(QuoteReason::Indicator('-')),
+    );
+    assert_eq!(
+        quote_reason("a #b", QuoteContext::new()),
+        Some(QuoteReason::SpaceHash)
+    );
+    assert_eq!(
+        quote_reason("a\tb", QuoteContext::new()),
+        Some(QuoteReason::SpecialCharacter('\t')),
+    );
+    assert_eq!(
+        quote_reason("", QuoteContext::new()),
+        Some(QuoteReason::Null)
+    );
+    assert_eq!(
+        QuoteReason::Indicator('*').to_string(),
+        "starts with the indicator `*`",
+    );
+}
+
+#[test]
+fn test_initial_indent() {
+    #[derive(Serialize)]
+    struct Fragment {
+        name: &'static str,
+        script: &'static str,
+        indented: &'static str,
+        args: Vec<&'static str>,
+        env: BTreeMap<&'static str, &'static str>,
+        #[serde(with = "serde_yaml::with::default_as_comment")]
+        extra: Vec<u8>,
+    }
+
+    let fragment = Fragment {
+        name: "build",
+        script: "make\n\nmake install\n",
+        indented: "  starts with spaces\nend",
+        args: vec!["-j", "- not a list"],
+        env: BTreeMap::new(),
+        extra: Vec::new(),
+    };
+    let expected = indoc! {"
+        outer:
+          key:
+            name: build
+            script: |
+              make
+
+              make install
+            indented: |2-
+                starts with spaces
+              end
+            args:
+            - -j
+            - '- not a list'
+            env: {}
+            # extra: []
+          after: 1
+    "};
+    let serialized = SerializerBuilder::new()
+        .initial_indent(4)
+        .verify_roundtrip(true)
+        .to_string(&fragment)
+        .unwrap();
+    let document = format!("outer:\n  key:\n{}  after: 1\n", serialized);
+    assert_eq!(expected, document);
+
+    // Spliced into a wrapper document, the output reads back as the value
+    // below the key, also after the key on its line and with CRLF.
+    let value = serde_yaml::to_value(&fragment).unwrap();
+    let splice = |builder: SerializerBuilder, value: &Value, bare: bool| {
+        let builder = builder.initial_indent(4).bare_first_line(bare);
+        let serialized = builder.to_string(value).unwrap();
+        let document = if bare {
+            format!("outer:\n  key: {}  after: 1\n", serialized)
+        } else {
+            format!("outer:\n  key:\n{}  after: 1\n", serialized)
+        };
+        let deserialized: Value = serde_yaml::from_str(&document).unwrap();
+        assert_eq!(deserialized["outer"]["key"], *value, "{}", document);
+        assert_eq!(deserialized["outer"]["after"], 1, "{}", document);
+    };
+    let roots = [
+        value,
+        Value::from("text"),
+        Value::from("first\nsecond\n"),
+        Value::from("'quoted'"),
+        Value::from(8080),
+        Value::Null,
+        Value::Sequence(Vec::new()),
+        Value::Mapping(Mapping::new()),
+        Value::Sequence(vec![Value::from("a"), Value::from("b: c")]),
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("point"),
+            value: Value::Sequence(vec![Value::from(1), Value::from(2)]),
+        })),
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("empty"),
+            value: Value::Mapping(Mapping::new()),
+        })),
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("text"),
+            value: Value::from("multiple\nlines"),
+        })),
+    ];
+    for root in &roots {
+        for bare in [false, true] {
+            splice(SerializerBuilder::new(), root, bare);
+            splice(SerializerBuilder::new().crlf(true), root, bare);
+            splice(SerializerBuilder::new().indent(4), root, bare);
+        }
+    }
+
+    let serialized = SerializerBuilder::new()
+        .initial_indent(4)
+        .bare_first_line(true)
+        .to_string(&BTreeMap::from([("a", 1)]))
+        .unwrap();
+    assert_eq!(serialized, "\n    a: 1\n");
+
+    // At the root, the indentation indicator of `|2` would count from the
+    // indentation of the key that the output is pasted below.
+    for bare in [false, true] {
+        let err = SerializerBuilder::new()
+            .initial_indent(4)
+            .bare_first_line(bare)
+            .to_string("  starts with spaces\nend")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "block scalar that starts with a space or line break cannot be written with an initial indent",
+        );
+    }
+    let serialized = SerializerBuilder::new()
+        .initial_indent(4)
+        .to_string("ends with a space \nend")
+        .unwrap();
+    assert_eq!(serialized, "    \"ends with a space \\nend\"\n");
+}
+
+#[test]
+fn test_emitter_compat() {
+    #[derive(Serialize)]
+    struct Corpus {
+        strings: Vec<String>,
+        floats: Vec<f64>,
+        small: Vec<f32>,
+        keys: BTreeMap<&'static str, u8>,
+    }
+
+    let strings = [
+        "",
+        "~",
+        "null",
+        "yes",
+        "No",
+        "true",
+        "0755",
+        "0x1F",
+        "1e3",
+        "1_000",
+        ".inf",
+        "12:30",
+        "- item",
+        "-item",
+        "? key",
+        ":colon",
+        "a: b",
+        "a:b",
+        "# comment",
+        "a #b",
+        "a#b",
+        "*ref",
+        "!tag",
+        "%dir",
+        "'single'",
+        "\"double\"",
+        " leading",
+        "trailing ",
+        "--- marker",
+        "...",
+        "tab\there",
+        "bom\u{feff}",
+        "nel\u{85}",
+        "two\nlines",
+        "trailing newline\n",
+        "\n leading break",
+        "crlf\r\nbreak",
+    ];
+    let corpus = Corpus {
+        strings: strings
+            .iter()
+            .map(|string| string.to_string())
+            .chain(iter::once("long ".repeat(20)))
+            .collect(),
+        floats: vec![
+            0.1,
+            1.0,
+            -0.0,
+            1e15,
+            1e16,
+            1e21,
+            1e-7,
+            123456.789,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ],
+        small: vec![0.1, 16777216.0, f32::MAX],
+        keys: BTreeMap::from([("[flow]", 1), ("a, b", 2), ("{x}", 3)]),
+    };
+
+    // The output of each version is frozen. A change to the rules of Latest
+    // gets a new version and a snapshot of its own here.
+    let v0_9 = indoc! {r#"
+        strings:
+        - ''
+        - '~'
+        - 'null'
+        - yes
+        - No
+        - 'true'
+        - 0755
+        - '0x1F'
+        - '1e3'
+        - 1_000
+        - '.inf'
+        - 12:30
+        - '- item'
+        - -item
+        - '? key'
+        - :colon
+        - 'a: b'
+        - a:b
+        - '# comment'
+        - 'a #b'
+        - a#b
+        - '*ref'
+        - '!tag'
+        - '%dir'
+        - '''single'''
+        - '"double"'
+        - ' leading'
+        - 'trailing '
+        - '--- marker'
+        - '...'
+        - "tab\there"
+        - "bom\uFEFF"
+        - "nel\N"
+        - |-
+          two
+          lines
+        - |
+          trailing newline
+        - |2-
+
+           leading break
+        - "crlf\r\nbreak"
+        - 'long long long long long long long long long long long long long long long long long long long long '
+        floats:
+        - 0.1
+        - 1.0
+        - -0.0
+        - 1000000000000000.0
+        - 1e16
+        - 1e21
+        - 1e-7
+        - 123456.789
+        - 1.7976931348623157e308
+        - 2.2250738585072014e-308
+        - 5e-324
+        - .inf
+        - -.inf
+        - .nan
+        small:
+        - 0.1
+        - 16777216.0
+        - 3.4028235e38
+        keys:
+          '[flow]': 1
+          a, b: 2
+          '{x}': 3
+    "#};
+    for (compat, expected) in [(EmitterCompat::V0_9, v0_9), (EmitterCompat::Latest, v0_9)] {
+        let builder = SerializerBuilder::new().emitter_compat(compat);
+        assert_eq!(
+            builder.to_string(&corpus).unwrap(),
+            expected,
+            "{:?}",
+            compat
+        );
+    }
+    assert_eq!(serde_yaml::to_string(&corpus).unwrap(), v0_9);
+}
+
+#[test]
+fn test_indent_width() {
+    #[derive(Serialize)]
+    struct Config {
+        server: Server,
+    }
+
+    #[derive(Serialize)]
+    struct Server {
+        port: u16,
+        hosts: Vec<&'static str>,
+        tls: BTreeMap<&'static str, bool>,
+    }
+
+    let config = Config {
+        server: Server {
+            port: 80,
+            hosts: vec!["a", "b"],
+            tls: BTreeMap::from([("enabled", true)]),
+        },
+    };
+    for (indent, expected) in [
+        (
+            2,
+            "server:\n  port: 80\n  hosts:\n  - a\n  - b\n  tls:\n    enabled: true\n",
+        ),
+        (
+            4,
+            "server:\n    port: 80\n    hosts:\n    - a\n    - b\n    tls:\n        enabled: true\n",
+        ),
+        (
+            8,
+            "server:\n        port: 80\n        hosts:\n        - a\n        - b\n        tls:\n                enabled: true\n",
+        ),
+    ] {
+        let builder = SerializerBuilder::new().indent(indent);
+        assert_eq!(builder.to_string(&config).unwrap(), expected);
+
+        let mut buffer = Vec::new();
+        builder.to_writer(&mut buffer, &config).unwrap();
+        assert_eq!(buffer, expected.as_bytes());
+
+        let mut buffer = Vec::new();
+        let mut serializer = builder.clone().build(&mut buffer);
+        serde::Serialize::serialize(&config, &mut serializer).unwrap();
+        drop(serializer);
+        assert_eq!(buffer, expected.as_bytes());
+    }
+}
+
+#[test]
+fn test_flow_style() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Snippet {
+        name: String,
+        ports: Vec<u16>,
+        labels: BTreeMap<String, String>,
+        probe: Option<Probe>,
+        empty: Vec<u8>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Probe {
+        Http { path: String, port: u16 },
+        Exec(Vec<String>),
+    }
+
+    let snippet = Snippet {
+        name: "web".to_owned(),
+        ports: vec![80, 443],
+        labels: BTreeMap::from([
+            ("tier".to_owned(), "a: b, [c]".to_owned()),
+            ("note".to_owned(), "first\nsecond".to_owned()),
+        ]),
+        probe: Some(Probe::Http {
+            path: "/healthz".to_owned(),
+            port: 8080,
+        }),
+        empty: Vec::new(),
+    };
+    let builder = SerializerBuilder::new().flow_style(true);
+    let yaml = builder.to_string(&snippet).unwrap();
+    let expected = "{name: web, ports: [80, 443], labels: {note: \"first\\nsecond\", tier: 'a: b, [c]'}, probe: !Http {path: /healthz, port: 8080}, empty: []}\n";
+    assert_eq!(yaml, expected);
+    assert_eq!(serde_yaml::from_str::<Snippet>(&yaml).unwrap(), snippet);
+
+    let probe = Probe::Exec(vec!["cat".to_owned(), "{}".to_owned()]);
+    let yaml = builder.to_string(&probe).unwrap();
+    assert_eq!(yaml, "!Exec [cat, '{}']\n");
+    assert_eq!(serde_yaml::from_str::<Probe>(&yaml).unwrap(), probe);
+
+    // A scalar at the root is written as it would be otherwise.
+    assert_eq!(builder.to_string("a, b").unwrap(), "a, b\n");
+    assert_eq!(
+        builder.to_string("two\nlines").unwrap(),
+        "|-\n  two\n  lines\n"
+    );
+}
+
+#[test]
+fn test_quote_style() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Settings {
+        answer: String,
+        version: String,
+        ratio: f64,
+        note: String,
+        mode: Mode,
+        initial: char,
+        missing: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Mode {
+        Fast,
+    }
+
+    let settings = Settings {
+        answer: "no".to_owned(),
+        version: "1.0".to_owned(),
+        ratio: 1.0,
+        note: "it's\nlong".to_owned(),
+        mode: Mode::Fast,
+        initial: 'x',
+        missing: None,
+    };
+    let cases = [
+        (
+            QuoteStyle::WhenNeeded,
+            indoc! {"
+                answer: no
+                version: '1.0'
+                ratio: 1.0
+                note: |-
+                  it's
+                  long
+                mode: Fast
+                initial: 'x'
+                missing: null
+            "},
+        ),
+        (
+            QuoteStyle::Single,
+            indoc! {"
+                'answer': 'no'
+                'version': '1.0'
+                'ratio': 1.0
+                'note': 'it''s
+
+                  long'
+                'mode': 'Fast'
+                'initial': 'x'
+                'missing': null
+            "},
+        ),
+        (
+            QuoteStyle::Double,
+            indoc! {r#"
+                "answer": "no"
+                "version": "1.0"
+                "ratio": 1.0
+                "note": "it's\nlong"
+                "mode": "Fast"
+                "initial": "x"
+                "missing": null
+            "#},
+        ),
+    ];
+    for (quote_style, expected) in cases {
+        let yaml = SerializerBuilder::new()
+            .quote_style(quote_style)
+            .to_string(&settings)
+            .unwrap();
+        assert_eq!(yaml, expected, "{:?}", quote_style);
+        assert_eq!(serde_yaml::from_str::<Settings>(&yaml).unwrap(), settings);
+    }
+
+    // Characters that need an escape are double-quoted either way.
+    let yaml = SerializerBuilder::new()
+        .quote_style(QuoteStyle::Single)
+        .to_string("bell\u{7}")
+        .unwrap();
+    assert_eq!(yaml, "\"bell\\a\"\n");
+}
+
+#[test]
+fn test_width() {
+    let words = ["lorem", "ipsum", "dolor", "sit", "amet"]
+        .repeat(6)
+        .join(" ");
+    let mut map = BTreeMap::new();
+    map.insert("plain", words.clone());
+    map.insert("quoted", format!("{}: yes", words));
+    map.insert("literal", format!("{}\n{}", words, words));
+
+    let expected = format!(
+        "literal: |-\n  {0}\n  {0}\nplain: {0}\nquoted: '{0}: yes'\n",
+        words,
+    );
+    assert_eq!(serde_yaml::to_string(&map).unwrap(), expected);
+    let yaml = SerializerBuilder::new()
+        .width(None)
+        .to_string(&map)
+        .unwrap();
+    assert_eq!(yaml, expected);
+
+    for width in [40, 120] {
+        let yaml = SerializerBuilder::new()
+            .width(Some(width))
+            .to_string(&map)
+            .unwrap();
+        let mut lines = yaml.lines();
+        assert_eq!(lines.next(), Some("literal: |-"));
+        assert_eq!(lines.next().unwrap().len(), words.len() + 2);
+        assert_eq!(lines.next().unwrap().len(), words.len() + 2);
+        for line in lines {
+            // Broken at the first space past the width.
+            let last_space = line.rfind(' ').unwrap();
+            assert!(last_space <= width, "{:?}", line);
+        }
+        let back: BTreeMap<&str, String> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(back, map.iter().map(|(k, v)| (*k, v.clone())).collect());
+    }
+
+    // A width that leaves no room after the indentation.
+    let yaml = SerializerBuilder::new()
+        .width(Some(0))
+        .to_string(&["alpha beta gamma"])
+        .unwrap();
+    assert_eq!(yaml, "- alpha\n  beta\n  gamma\n");
+}
+
+#[test]
+fn test_null_style() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Deployment {
+        name: String,
+        replicas: Option<u32>,
+        args: Vec<Option<String>>,
+        labels: BTreeMap<String, Option<String>>,
+    }
+
+    let deployment = Deployment {
+        name: "web".to_owned(),
+        replicas: None,
+        args: vec![None, Some("--verbose".to_owned())],
+        labels: BTreeMap::from([("tier".to_owned(), None)]),
+    };
+    for (null_style, expected) in [
+        (
+            NullStyle::Null,
+            indoc! {"
+                name: web
+                replicas: null
+                args:
+                - null
+                - --verbose
+                labels:
+                  tier: null
+            "},
+        ),
+        (
+            NullStyle::Tilde,
+            indoc! {"
+                name: web
+                replicas: ~
+                args:
+                - ~
+                - --verbose
+                labels:
+                  tier: ~
+            "},
+        ),
+        (
+            NullStyle::Empty,
+            indoc! {"
+                name: web
+                replicas:
+                args:
+                -
+                - --verbose
+                labels:
+                  tier:
+            "},
+        ),
+    ] {
+        let builder = SerializerBuilder::new()
+            .null_style(null_style)
+            .verify_roundtrip(true);
+        assert_eq!(builder.to_string(&deployment).unwrap(), expected);
+        let back: Deployment = serde_yaml::from_str(expected).unwrap();
+        assert_eq!(back, deployment);
+    }
+
+    // An empty key or flow item would read back as a string.
+    let mut map = BTreeMap::new();
+    map.insert(None::<u8>, vec![None, Some(1)]);
+    let builder = SerializerBuilder::new().null_style(NullStyle::Empty);
+    assert_eq!(builder.to_string(&map).unwrap(), "null:\n-\n- 1\n");
+    let builder = builder.flow_style(true);
+    assert_eq!(builder.to_string(&map).unwrap(), "{null: [null, 1]}\n");
+}
+
+#[test]
+fn test_sort_keys() {
+    #[derive(Serialize)]
+    struct Config {
+        version: u8,
+        env: HashMap<&'static str, &'static str>,
+        extra: Value,
+        check: Check,
+    }
+
+    #[derive(Serialize)]
+    enum Check {
+        Http { timeout: u32, path: &'static str },
+    }
+
+    let config = Config {
+        version: 1,
+        env: HashMap::from([("TZ", "UTC"), ("LANG", "C"), ("HOME", "/root")]),
+        extra: serde_yaml::from_str("zone: b\n2: two\nname: x\n").unwrap(),
+        check: Check::Http {
+            timeout: 5,
+            path: "/health",
+        },
+    };
+    let yaml = SerializerBuilder::new()
+        .sort_keys(true)
+        .to_string(&config)
+        .unwrap();
+    let expected = indoc! {"
+        check: !Http
+          path: /health
+          timeout: 5
+        env:
+          HOME: /root
+          LANG: C
+          TZ: UTC
+        extra:
+          2: two
+          name: x
+          zone: b
+        version: 1
+    "};
+    assert_eq!(yaml, expected);
+
+    let yaml = SerializerBuilder::new()
+        .deterministic(true)
+        .to_string(&config)
+        .unwrap();
+    assert!(
+        yaml.starts_with("version: 1\nenv:\n  HOME: /root\n"),
+        "{}",
+        yaml
+    );
+}
+
+#[test]
+fn test_document_start() {
+    let documents = [
+        Value::String("text".to_owned()),
+        Value::String("two\nlines".to_owned()),
+        Value::Null,
+        serde_yaml::from_str("[1, 2]").unwrap(),
+        serde_yaml::from_str("{a: 1}").unwrap(),
+        serde_yaml::from_str("!Tag 1").unwrap(),
+    ];
+    let expected = [
+        ("text\n", "--- text\n"),
+        ("|-\n  two\n  lines\n", "--- |-\n  two\n  lines\n"),
+        ("null\n", "--- null\n"),
+        ("- 1\n- 2\n", "---\n- 1\n- 2\n"),
+        ("a: 1\n", "---\na: 1\n"),
+        ("!Tag 1\n", "--- !Tag 1\n"),
+    ];
+    for (document, (implicit, explicit)) in documents.iter().zip(expected) {
+        let builder = SerializerBuilder::new();
+        assert_eq!(builder.to_string(document).unwrap(), implicit);
+        let builder = SerializerBuilder::new().document_start(true);
+        assert_eq!(builder.to_string(document).unwrap(), explicit);
+    }
+
+    for (document_start, expected) in [
+        (false, "text\n---\n- 1\n- 2\n"),
+        (true, "--- text\n---\n- 1\n- 2\n"),
+    ] {
+        let mut buffer = Vec::new();
+        let mut serializer = SerializerBuilder::new()
+            .document_start(document_start)
+            .build(&mut buffer);
+        serde::Serialize::serialize(&documents[0], &mut serializer).unwrap();
+        serde::Serialize::serialize(&documents[3], &mut serializer).unwrap();
+        drop(serializer);
+        assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_literal_strings() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Job {
+        name: String,
+        script: String,
+        notes: Vec<String>,
+    }
+
+    let job = Job {
+        name: "test".to_owned(),
+        script: "cargo build\ncargo test --workspace\n".to_owned(),
+        notes: vec!["keep\n\n".to_owned(), "\tindented\nby a tab".to_owned()],
+    };
+    let literal = indoc! {"
+        script: |
+          cargo build
+          cargo test --workspace
+        notes:
+        - |+
+          keep
+
+        - \"\\tindented\\nby a tab\"
+    "};
+    for (quote_style, name) in [
+        (QuoteStyle::WhenNeeded, "name: test\n"),
+        (QuoteStyle::Single, "'name': 'test'\n"),
+        (QuoteStyle::Double, "\"name\": \"test\"\n"),
+    ] {
+        let builder = SerializerBuilder::new()
+            .quote_style(quote_style)
+            .literal_strings(true);
+        let yaml = builder.to_string(&job).unwrap();
+        let mut expected = name.to_owned();
+        expected += literal;
+        if quote_style != QuoteStyle::WhenNeeded {
+            let quote = if quote_style == QuoteStyle::Single {
+                "'"
+            } else {
+                "\""
+            };
+            expected = expected
+                .replace("script:", &format!("{0}script{0}:", quote))
+                .replace("notes:", &format!("{0}notes{0}:", quote));
+        }
+        assert_eq!(yaml, expected);
+        let back: Job = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(back, job);
+    }
+
+    let builder = SerializerBuilder::new()
+        .quote_style(QuoteStyle::Single)
+        .literal_strings(true)
+        .flow_style(true);
+    let yaml = builder.to_string(&job.notes).unwrap();
+    assert_eq!(yaml, "[\"keep\\n\\n\", \"\\tindented\\nby a tab\"]\n");
+}
+
+#[test]
+fn test_fold_long_strings() {
+    use serde_yaml::de::StyledString;
+    use serde_yaml::ScalarStyle;
+
+    let prose = "YAML is a human-friendly data serialization language for all \
+                 programming languages, commonly used for configuration files and \
+                 in applications where data is being stored or transmitted.";
+    let mut doc = BTreeMap::new();
+    doc.insert("title".to_owned(), vec!["YAML".to_owned()]);
+    doc.insert(
+        "text".to_owned(),
+        vec![prose.to_owned(), format!(" {}", prose)],
+    );
+    doc.insert(prose.to_owned(), vec!["as a key".to_owned()]);
+
+    let builder = SerializerBuilder::new()
+        .fold_long_strings(true)
+        .verify_roundtrip(true);
+    let yaml = builder.to_string(&doc).unwrap();
+    let expected = indoc! {"
+        ? YAML is a human-friendly data serialization language for all programming languages,
+          commonly used for configuration files and in applications where data is being stored
+          or transmitted.
+        : - as a key
+        text:
+        - >-
+          YAML is a human-friendly data serialization language for all programming languages,
+          commonly used for configuration files and in applications where data is being stored
+          or transmitted.
+        - ' YAML is a human-friendly data serialization language for all programming languages,
+          commonly used for configuration files and in applications where data is being stored
+          or transmitted.'
+        title:
+        - YAML
+    "};
+    assert_eq!(yaml, expected);
+    let back: BTreeMap<String, Vec<String>> = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(back, doc);
+
+    let yaml = builder
+        .clone()
+        .flow_style(true)
+        .to_string(&doc["text"][..1])
+        .unwrap();
+    assert!(yaml.starts_with("['YAML is a human-friendly"), "{}", yaml);
+
+    // Not folded with lines that are never broken.
+    let yaml = builder.width(None).to_string(&[prose]).unwrap();
+    assert_eq!(yaml, format!("- {}\n", prose));
+
+    let styled = StyledString::new("short", ScalarStyle::Folded);
+    assert_eq!(serde_yaml::to_string(&styled).unwrap(), ">-\n  short\n");
+}
+
+#[test]
+fn test_canonical_normalizes_scalars() {
+    let yaml = "{b: 0x10, a: ~, c: -0.0, d: 1e3}";
+    let builder = SerializerBuilder::new()
+        .canonical(true)
+        .sort_keys(true)
+        .null_style(NullStyle::Tilde);
+    let expected = indoc! {r#"
+        ---
+        !!map {
+          ? !!str "a"
+          : !!null "null",
+          ? !!str "b"
+          : !!int "16",
+          ? !!str "c"
+          : !!float "0.0",
+          ? !!str "d"
+          : !!float "1000.0",
+        }
+    "#};
+
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(builder.to_string(&value).unwrap(), expected);
+
+    let value: Value = DeserializerBuilder::new()
+        .preserve_number_repr(true)
+        .from_str(yaml)
+        .unwrap();
+    assert_eq!(builder.to_string(&value).unwrap(), expected);
+    let yaml = SerializerBuilder::new().to_string(&value).unwrap();
+    assert_eq!(yaml, "b: 0x10\na: null\nc: -0.0\nd: 1e3\n");
+}
+
+#[test]
+fn test_explicit_tags() {
+    #[derive(Serialize)]
+    enum Step {
+        Run(String),
+        Stop,
+    }
+
+    #[derive(Serialize)]
+    struct Job {
+        #[serde(with = "serde_yaml::with::default_as_comment")]
+        needs: Vec<String>,
+        steps: Vec<Step>,
+        retries: u8,
+        timeout: Option<f64>,
+        script: String,
+    }
+
+    let job = Job {
+        needs: Vec::new(),
+        steps: vec![Step::Run("make".to_owned()), Step::Stop],
+        retries: 3,
+        timeout: None,
+        script: "make\nmake test\n".to_owned(),
+    };
+    let builder = SerializerBuilder::new()
+        .explicit_tags(true)
+        .align_values(true)
+        .verify_roundtrip(true);
+    let expected = indoc! {"
+        !!map
+        !!str needs:   !!seq []
+        !!str steps: !!seq
+        - !Run make
+        - !!str Stop
+        !!str retries: !!int 3
+        !!str timeout: !!null null
+        !!str script:  !!str |
+          make
+          make test
+    "};
+    assert_eq!(builder.to_string(&job).unwrap(), expected);
+}
+
+#[test]
+fn test_json() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Service {
+        name: String,
+        replicas: u32,
+        ratio: f64,
+        enabled: bool,
+        owner: Option<String>,
+        labels: BTreeMap<String, String>,
+        ports: Vec<u16>,
+    }
+
+    let service = Service {
+        name: "no".to_owned(),
+        replicas: 3,
+        ratio: 0.5,
+        enabled: true,
+        owner: None,
+        labels: BTreeMap::from([
+            (
+                "description".to_owned(),
+                "line one\nline \"two\"\ttabbed".to_owned(),
+            ),
+            ("empty".to_owned(), String::new()),
+        ]),
+        ports: vec![],
+    };
+    let builder = SerializerBuilder::new()
+        .json(true)
+        .flow_style(false)
+        .document_start(true)
+        .width(Some(20));
+    let json = builder.to_string(&service).unwrap();
+    let expected = concat!(
+        r#"{"name": "no", "replicas": 3, "ratio": 0.5, "enabled": true, "owner": null, "#,
+        r#""labels": {"description": "line one\nline \"two\"\ttabbed", "empty": ""}, "#,
+        r#""ports": []}"#,
+        "\n",
+    );
+    assert_eq!(json, expected);
+    let back: Service = serde_yaml::from_str(&json).unwrap();
+    assert_eq!(back, service);
+
+    let json = builder.to_string("text\u{8}\u{C}\u{FEFF}\u{A0}").unwrap();
+    assert_eq!(json, "\"text\\b\\f\\uFEFF\u{A0}\"\n");
+
+    // The escapes that JSON does not have are written as `\u` escapes.
+    let text = "\0\u{7}\u{1B}[0m\u{85}\u{2028}\u{2029}";
+    let json = builder.to_string(text).unwrap();
+    assert_eq!(json, "\"\\u0000\\u0007\\u001B[0m\\u0085\\u2028\\u2029\"\n");
+    assert_eq!(serde_yaml::from_str::<String>(&json).unwrap(), text);
+
+    // So are keys that YAML writes as explicit keys, after a `?`.
+    let long = "k".repeat(200);
+    let map = BTreeMap::from([(long.clone(), 1), ("two\nlines".to_owned(), 2)]);
+    let json = builder.to_string(&map).unwrap();
+    assert_eq!(json, format!("{{\"{}\": 1, \"two\\nlines\": 2}}\n", long));
+    let back: BTreeMap<String, i32> = serde_yaml::from_str(&json).unwrap();
+    assert_eq!(back, map);
+
+    // As with serde_json, keys that are not strings are written as strings
+    // and enums as mappings from the variant to its data.
+    let map = BTreeMap::from([(1u32, "one"), (20, "twenty")]);
+    let json = builder.to_string(&map).unwrap();
+    assert_eq!(json, "{\"1\": \"one\", \"20\": \"twenty\"}\n");
+    let back: BTreeMap<String, String> = serde_yaml::from_str(&json).unwrap();
+    assert_eq!(back["20"], "twenty");
+    let json = builder
+        .to_string(&BTreeMap::from([(true, 1.5), (false, 0.0)]))
+        .unwrap();
+    assert_eq!(json, "{\"false\": 0.0, \"true\": 1.5}\n");
+    let mut float_key = Mapping::new();
+    float_key.insert(Value::from(-1.5), Value::from(1));
+    let json = builder.to_string(&float_key).unwrap();
+    assert_eq!(json, "{\"-1.5\": 1}\n");
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Check {
+        Http(u16),
+        Tcp(String, u16),
+        Exec { command: Vec<String> },
+        None,
+    }
+
+    let checks = vec![
+        Check::Http(80),
+        Check::Tcp("db".to_owned(), 5432),
+        Check::Exec {
+            command: vec!["true".to_owned()],
+        },
+        Check::None,
+    ];
+    let json = builder.to_string(&checks).unwrap();
+    let expected = concat!(
+        r#"[{"Http": 80}, {"Tcp": ["db", 5432]}, {"Exec": {"command": ["true"]}}, "#,
+        r#""None"]"#,
+        "\n",
+    );
+    assert_eq!(json, expected);
+    let deserializer = serde_yaml::Deserializer::from_str(&json);
+    let back: Vec<Check> =
+        serde_yaml::with::singleton_map_recursive::deserialize(deserializer).unwrap();
+    assert_eq!(back, checks);
+
+    let mut tagged = Mapping::new();
+    tagged.insert(
+        Value::from("check"),
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("Http"),
+            value: Value::from(80),
+        })),
+    );
+    let mut null_key = Mapping::new();
+    null_key.insert(Value::Null, Value::from(1));
+    let long = "k".repeat(1023);
+    for (error, value) in [
+        (
+            "tag `!Http` cannot be written as JSON",
+            builder.to_string(&tagged),
+        ),
+        (
+            "`.inf` cannot be written as JSON",
+            builder.to_string(&[f64::INFINITY]),
+        ),
+        (
+            "key `null` cannot be written as JSON",
+            builder.to_string(&null_key),
+        ),
+        (
+            "sequence as a key cannot be written as JSON",
+            builder.to_string(&BTreeMap::from([(vec![1], 2)])),
+        ),
+        (
+            "key longer than 1024 bytes cannot be written as JSON",
+            builder.to_string(&BTreeMap::from([(long, 2)])),
+        ),
+    ] {
+        assert_eq!(value.unwrap_err().to_string(), error);
+    }
+
+    let mut buffer = Vec::new();
+    let mut serializer = builder.build(&mut buffer);
+    serde::Serialize::serialize(&1, &mut serializer).unwrap();
+    let error = serde::Serialize::serialize(&2, &mut serializer).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "more than one document cannot be written as JSON",
+    );
+}
+
+#[test]
+fn test_indent_sequences() {
+    let value: Value = serde_yaml::from_str(indoc! {"
+        jobs:
+          test:
+            steps:
+            - run: |
+                cargo build
+                - not a sequence
+              with: [a, b]
+            - tags: !Tagged
+              - x
+        ? [key]
+        : - value
+    "})
+    .unwrap();
+    let builder = SerializerBuilder::new()
+        .indent_sequences(true)
+        .verify_roundtrip(true);
+    let expected = indoc! {"
+        jobs:
+          test:
+            steps:
+              - run: |
+                  cargo build
+                  - not a sequence
+                with:
+                  - a
+                  - b
+              - tags: !Tagged
+                  - x
+        ? - key
+        : - value
+    "};
+    assert_eq!(builder.to_string(&value).unwrap(), expected);
+
+    let yaml = builder.clone().indent(4).to_string(&value).unwrap();
+    assert!(
+        yaml.starts_with("jobs:\n    test:\n        steps:\n            -   run: |\n"),
+        "{}",
+        yaml
+    );
+    assert_eq!(serde_yaml::from_str::<Value>(&yaml).unwrap(), value);
+
+    let mut serializer = builder.build(Vec::new());
+    serde::Serialize::serialize(&BTreeMap::from([("a", [1])]), &mut serializer).unwrap();
+    serde::Serialize::serialize(&[BTreeMap::from([("b", [2])])], &mut serializer).unwrap();
+    let yaml = String::from_utf8(serializer.into_inner().unwrap()).unwrap();
+    assert_eq!(yaml, "a:\n  - 1\n---\n- b:\n    - 2\n");
+}
+
+#[test]
+fn test_float_format() {
+    let floats = [0.1 + 0.2, 2.5, 123.456, -0.004, 1e16, f64::INFINITY];
+
+    let to_string = |format| {
+        SerializerBuilder::new()
+            .float_format(format)
+            .flow_style(true)
+            .to_string(&floats)
+            .unwrap()
+    };
+    assert_eq!(
+        to_string(FloatFormat::Shortest),
+        "[0.30000000000000004, 2.5, 123.456, -0.004, 1e16, .inf]\n",
+    );
+    assert_eq!(
+        to_string(FloatFormat::Fixed(2)),
+        "[0.30, 2.50, 123.46, -0.00, 10000000000000000.00, .inf]\n",
+    );
+    assert_eq!(
+        to_string(FloatFormat::Fixed(0)),
+        "[0.0, 2.0, 123.0, -0.0, 10000000000000000.0, .inf]\n",
+    );
+    assert_eq!(
+        to_string(FloatFormat::Significant(2)),
+        "[0.3, 2.5, 120.0, -0.004, 1e16, .inf]\n",
+    );
+    assert_eq!(
+        to_string(FloatFormat::Significant(0)),
+        "[0.3, 2.0, 100.0, -0.004, 1e16, .inf]\n",
+    );
+    assert_eq!(
+        to_string(FloatFormat::Significant(17)),
+        to_string(FloatFormat::Shortest),
+    );
+
+    // Every float reads back as a float.
+    for format in [FloatFormat::Fixed(0), FloatFormat::Significant(1)] {
+        let value: Value = serde_yaml::from_str(&to_string(format)).unwrap();
+        for float in value.as_sequence().unwrap() {
+            assert!(float.is_f64(), "{:?}", float);
+        }
+    }
+
+    let yaml = SerializerBuilder::new()
+        .float_format(FloatFormat::Significant(3))
+        .to_string(&(1.0f32 / 3.0, 0.1f32 + 0.2f32))
+        .unwrap();
+    assert_eq!(yaml, "- 0.333\n- 0.3\n");
+
+    // Equal floats are written the same with deterministic output.
+    let yaml = SerializerBuilder::new()
+        .float_format(FloatFormat::Fixed(2))
+        .deterministic(true)
+        .to_string(&[0.0, -0.0])
+        .unwrap();
+    assert_eq!(yaml, "- 0.00\n- 0.00\n");
+
+    // A number that was read with its text keeps it.
+    let mut value: Value = DeserializerBuilder::new()
+        .preserve_number_repr(true)
+        .from_str("[0.500, 0.25]")
+        .unwrap();
+    value.as_sequence_mut().unwrap().push(Value::from(0.75));
+    let yaml = SerializerBuilder::new()
+        .float_format(FloatFormat::Fixed(1))
+        .to_string(&value)
+        .unwrap();
+    assert_eq!(yaml, "- 0.500\n- 0.25\n- 0.8\n");
+}
+
+#[test]
+fn test_scientific_threshold() {
+    let floats = [1500000.0, 250000.0, 0.5, 0.000012, -1e-6, 0.0, 1e20];
+
+    let to_string =
+        |builder: SerializerBuilder| builder.flow_style(true).to_string(&floats).unwrap();
+    assert_eq!(
+        to_string(SerializerBuilder::new()),
+        "[1500000.0, 250000.0, 0.5, 0.000012, -1e-6, 0.0, 1e20]\n",
+    );
+    assert_eq!(
+        to_string(SerializerBuilder::new().scientific_threshold(Some(6))),
+        "[1.5e6, 250000.0, 0.5, 0.000012, -1e-6, 0.0, 1e20]\n",
+    );
+    assert_eq!(
+        to_string(SerializerBuilder::new().scientific_threshold(Some(0))),
+        "[1.5e6, 2.5e5, 5e-1, 1.2e-5, -1e-6, 0.0, 1e20]\n",
+    );
+    assert_eq!(
+        to_string(SerializerBuilder::new().scientific_threshold(Some(25))),
+        "[1500000.0, 250000.0, 0.5, 0.000012, -0.000001, 0.0, 100000000000000000000.0]\n",
+    );
+    assert_eq!(
+        to_string(
+            SerializerBuilder::new()
+                .float_format(FloatFormat::Significant(1))
+                .scientific_threshold(Some(5)),
+        ),
+        "[2e6, 2e5, 0.5, 1e-5, -1e-6, 0.0, 1e20]\n",
+    );
+    assert_eq!(
+        to_string(
+            SerializerBuilder::new()
+                .float_format(FloatFormat::Fixed(1))
+                .scientific_threshold(Some(0)),
+        ),
+        "[1500000.0, 250000.0, 0.5, 0.0, -0.0, 0.0, 100000000000000000000.0]\n",
+    );
+
+    let value: Value = serde_yaml::from_str(&to_string(
+        SerializerBuilder::new().scientific_threshold(Some(0)),
+    ))
+    .unwrap();
+    let expected: Vec<Value> = floats.iter().map(|&float| Value::from(float)).collect();
+    assert_eq!(value, Value::Sequence(expected));
+}
+
+#[test]
+fn test_commented() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Mode {
+        Fast { level: u8 },
+        Slow(u8),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Server {
+        host: Commented<String>,
+        port: u16,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Config {
+        server: Commented<Server>,
+        replicas: Vec<Commented<Server>>,
+        matrix: Vec<Vec<Commented<u8>>>,
+        mode: Commented<Mode>,
+        fallback: Commented<Mode>,
+        banner: Commented<String>,
+    }
+
+    let server = |comment: &str, host: &str| Server {
+        host: Commented::new(comment, host.to_owned()),
+        port: 80,
+    };
+    let config = Config {
+        server: Commented::new("The main server", server("Where it is", "a")),
+        replicas: vec![
+            Commented::new("Standby", server("", "b")),
+            Commented::new("", server("Backup", "c")),
+        ],
+        matrix: vec![vec![Commented::new("first", 1), Commented::new("", 2)]],
+        mode: Commented::new("Fast or slow", Mode::Fast { level: 3 }),
+        fallback: Commented::new("Used on errors", Mode::Slow(1)),
+        banner: Commented::new("Shown at\n\nlogin\r\n  indented", "hello\nworld".to_owned()),
+    };
+    let expected = indoc! {"
+        # The main server
+        server:
+          # Where it is
+          host: a
+          port: 80
+        replicas:
+        # Standby
+        - host: b
+          port: 80
+        - # Backup
+          host: c
+          port: 80
+        matrix:
+        - # first
+          - 1
+          - 2
+        # Fast or slow
+        mode: !Fast
+          level: 3
+        # Used on errors
+        fallback: !Slow 1
+        # Shown at
+        #
+        # login
+        #   indented
+        banner: |-
+          hello
+          world
+    "};
+    assert_eq!(serde_yaml::to_string(&config).unwrap(), expected);
+
+    // The comments are not read back.
+    let deserialized: Config = serde_yaml::from_str(expected).unwrap();
+    assert_eq!(deserialized.server.comment, "");
+    assert_eq!(deserialized.server.value.host.value, "a");
+    assert_eq!(deserialized.fallback.value, Mode::Slow(1));
+
+    // Each comment stays above its entry when the entries are sorted.
+    let mut map = BTreeMap::new();
+    map.insert("b", Commented::new("second", 2));
+    map.insert("a", Commented::new("first", 1));
+    map.insert("c", Commented::new("", 3));
+    let yaml = SerializerBuilder::new()
+        .sort_keys(true)
+        .verify_roundtrip(true)
+        .to_string(&config)
+        .unwrap();
+    assert!(yaml.starts_with("# Shown at\n#\n# login\n#   indented\nbanner: |-\n"));
+    let yaml = SerializerBuilder::new()
+        .deterministic(true)
+        .to_string(&map)
+        .unwrap();
+    assert_eq!(yaml, "# first\na: 1\n# second\nb: 2\nc: 3\n");
+
+    // The lines of a comment are indented like the others.
+    let yaml = SerializerBuilder::new()
+        .initial_indent(2)
+        .indent_sequences(true)
+        .crlf(true)
+        .to_string(&config.replicas)
+        .unwrap();
+    let expected = "  # Standby\r\n  - host: b\r\n    port: 80\r\n  - # Backup\r\n    host: c\r\n    port: 80\r\n";
+    assert_eq!(yaml, expected);
+    let mut wrapped = BTreeMap::new();
+    wrapped.insert("replicas", &config.replicas);
+    let yaml = SerializerBuilder::new()
+        .indent_sequences(true)
+        .to_string(&wrapped)
+        .unwrap();
+    let expected = indoc! {"
+        replicas:
+          # Standby
+          - host: b
+            port: 80
+          - # Backup
+            host: c
+            port: 80
+    "};
+    assert_eq!(yaml, expected);
+
+    // Where a comment cannot be written, the value is written without it.
+    let flow = SerializerBuilder::new()
+        .flow_style(true)
+        .to_string(&map)
+        .unwrap();
+    assert_eq!(flow, "{a: 1, b: 2, c: 3}\n");
+    let root = serde_yaml::to_string(&Commented::new("root", 1)).unwrap();
+    assert_eq!(root, "1\n");
+    let mut complex = BTreeMap::new();
+    complex.insert(vec![1], Commented::new("complex", 1));
+    assert_eq!(serde_yaml::to_string(&complex).unwrap(), "? - 1\n: 1\n");
+    let value = serde_yaml::to_value(&map).unwrap();
+    assert_eq!(
+        value,
+        serde_yaml::from_str::<Value>("{a: 1, b: 2, c: 3}").unwrap()
+    );
+
+    // A skipped value takes its comment with it.
+    let yaml = SerializerBuilder::new()
+        .map_scalar(|path, _scalar| match path.to_string().as_str() {
+            "a" => ScalarAction::Skip,
+            _ => ScalarAction::Keep,
+        })
+        .to_string(&map)
+        .unwrap();
+    assert_eq!(yaml, "# second\nb: 2\nc: 3\n");
+
+    let error = serde_yaml::to_string(&[Commented::new("bell\x07", 1)]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "comment contains character U+0007, which is not allowed in YAML",
+    );
+
+    // Strings that look like the placeholders of comments are data.
+    let items = vec![
+        Commented::new("", "__serde_yaml_comment_above_0"),
+        Commented::new("note", "x"),
+    ];
+    let yaml = serde_yaml::to_string(&items).unwrap();
+    assert_eq!(yaml, "- __serde_yaml_comment_above_0\n# note\n- x\n");
+    let mut map = BTreeMap::new();
+    map.insert("a", Commented::new("first", "__serde_yaml_comment_0"));
+    map.insert("b", Commented::new("", "__serde_yaml_comment_above_1"));
+    let yaml = serde_yaml::to_string(&map).unwrap();
+    let expected = indoc! {"
+        # first
+        a: __serde_yaml_comment_0
+        b: __serde_yaml_comment_above_1
+    "};
+    assert_eq!(yaml, expected);
+}
+
+#[test]
+fn test_shared() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Circle { radius: u8 },
+        Polygon(Vec<u8>),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+    struct Node(#[serde(with = "serde_yaml::with::shared")] Rc<Vec<u8>>);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Scene {
+        nodes: Vec<Node>,
+        #[serde(with = "serde_yaml::with::shared")]
+        shape: Arc<Shape>,
+        #[serde(with = "serde_yaml::with::shared")]
+        outline: Arc<Shape>,
+        #[serde(with = "serde_yaml::with::shared")]
+        name: Rc<String>,
+        #[serde(with = "serde_yaml::with::shared")]
+        title: Rc<String>,
+        #[serde(with = "serde_yaml::with::shared")]
+        single: Rc<Vec<u8>>,
+        nested: Commented<Vec<Node>>,
+    }
+
+    let points = Rc::new(vec![1, 2]);
+    let shape = Arc::new(Shape::Circle { radius: 3 });
+    let name = Rc::new("scene".to_owned());
+    let scene = Scene {
+        nodes: vec![Node(Rc::clone(&points)), Node(Rc::clone(&points))],
+        shape: Arc::clone(&shape),
+        outline: shape,
+        name: Rc::clone(&name),
+        title: name,
+        single: Rc::new(vec![4]),
+        nested: Commented::new("Again", vec![Node(Rc::clone(&points))]),
+    };
+    let expected = indoc! {"
+        nodes:
+        - &id001
+          - 1
+          - 2
+        - *id001
+        shape: &id002 !Circle
+          radius: 3
+        outline: *id002
+        name: scene
+        title: scene
+        single:
+        - 4
+        # Again
+        nested:
+        - *id001
+    "};
+    assert_eq!(serde_yaml::to_string(&scene).unwrap(), expected);
+    let mut deserialized: Scene = serde_yaml::from_str(expected).unwrap();
+    assert_eq!(deserialized.nested.comment, "");
+    deserialized.nested.comment = scene.nested.comment.clone();
+    assert_eq!(deserialized, scene);
+
+    let yaml = SerializerBuilder::new()
+        .verify_roundtrip(true)
+        .indent_sequences(true)
+        .to_string(&scene.nodes)
+        .unwrap();
+    assert_eq!(yaml, "- &id001\n  - 1\n  - 2\n- *id001\n");
+    let yaml = SerializerBuilder::new()
+        .flow_style(true)
+        .to_string(&scene.nodes)
+        .unwrap();
+    assert_eq!(yaml, "[&id001 [1, 2], *id001]\n");
+
+    // Anchors are not shared between documents.
+    let mut buffer = Vec::new();
+    let mut ser = serde_yaml::Serializer::new(&mut buffer);
+    serde::Serialize::serialize(&scene.nodes, &mut ser).unwrap();
+    serde::Serialize::serialize(&scene.nodes, &mut ser).unwrap();
+    drop(ser);
+    let expected = "- &id001\n  - 1\n  - 2\n- *id001\n---\n- &id001\n  - 1\n  - 2\n- *id001\n";
+    assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+
+    // Where an alias cannot be used, the value is written out every time.
+    let unshared = "- - 1\n  - 2\n- - 1\n  - 2\n";
+    let mut map = BTreeMap::new();
+    map.insert("b", Node(Rc::clone(&points)));
+    map.insert("a", Node(Rc::clone(&points)));
+    let yaml = SerializerBuilder::new()
+        .deterministic(true)
+        .to_string(&map)
+        .unwrap();
+    assert_eq!(yaml, "a:\n- 1\n- 2\nb:\n- 1\n- 2\n");
+    let yaml = SerializerBuilder::new()
+        .map_scalar(|_path, _scalar| ScalarAction::Keep)
+        .to_string(&scene.nodes)
+        .unwrap();
+    assert_eq!(yaml, unshared);
+    let mut keys = BTreeMap::new();
+    keys.insert(vec![Node(Rc::clone(&points)), Node(Rc::clone(&points))], 1);
+    assert_eq!(
+        serde_yaml::to_string(&keys).unwrap(),
+        "? - - 1\n    - 2\n  - - 1\n    - 2\n: 1\n"
+    );
+    let value = serde_yaml::to_value(&scene.nodes).unwrap();
+    assert_eq!(value, serde_yaml::from_str::<Value>(unshared).unwrap());
+}
+
+#[test]
+fn test_ascii() {
+    let builder = SerializerBuilder::new().ascii(true).verify_roundtrip(true);
+    let mut map = BTreeMap::new();
+    map.insert("clé", vec!["ü\u{a0}", "😀\nsmile", "plain"]);
+    let expected = indoc! {r#"
+        "cl\xE9":
+        - "\xFC\_"
+        - "\U0001F600\nsmile"
+        - plain
+    "#};
+    let yaml = builder.to_string(&map).unwrap();
+    assert_eq!(yaml, expected);
+    assert_eq!(
+        serde_yaml::from_str::<BTreeMap<String, Vec<String>>>(&yaml).unwrap()["clé"][1],
+        "😀\nsmile"
+    );
+    let yaml = builder
+        .clone()
+        .literal_strings(true)
+        .to_string(&map)
+        .unwrap();
+    assert_eq!(yaml, expected);
+
+    let value = Commented::new("Café", "x");
+    let error = builder.to_string(&[value]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "comment contains character U+00E9, which cannot be written as ASCII",
+    );
+
+    // JSON only has `\u` escapes, and YAML does not accept a surrogate pair
+    // of them for a character above U+FFFF.
+    let json = builder.clone().json(true);
+    let expected = "[\"\\u4E2D\\u00E9\"]\n";
+    assert_eq!(json.to_string(&["中é"]).unwrap(), expected);
+    let error = json.to_string(&["😀"]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "string with character U+1F600 cannot be written as JSON",
+    );
+}
+
+#[test]
+fn test_tagged() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Ref(String);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(rename = "GetAtt")]
+    struct Attribute(String, String);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Template {
+        #[serde(with = "serde_yaml::with::tagged")]
+        bucket: Ref,
+        #[serde(with = "serde_yaml::with::tagged")]
+        origin: Point,
+        #[serde(with = "serde_yaml::with::tagged")]
+        arn: Attribute,
+        #[serde(default, with = "serde_yaml::with::tagged")]
+        role: Option<Ref>,
+        #[serde(serialize_with = "import", deserialize_with = "de_import")]
+        import: Vec<String>,
+    }
+
+    fn import<S: serde::Serializer>(value: &Vec<String>, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_yaml::with::tagged::serialize_with_tag("!ImportValue", value, serializer)
+    }
+
+    fn de_import<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        serde_yaml::with::tagged::deserialize_with_tag("ImportValue", deserializer)
+    }
+
+    let template = Template {
+        bucket: Ref("Bucket".to_owned()),
+        origin: Point { x: 1, y: 2 },
+        arn: Attribute("Bucket".to_owned(), "Arn".to_owned()),
+        role: Some(Ref("Role".to_owned())),
+        import: vec!["Shared".to_owned()],
+    };
+    let yaml = indoc! {"
+        bucket: !Ref Bucket
+        origin: !Point
+          x: 1
+          y: 2
+        arn: !GetAtt
+        - Bucket
+        - Arn
+        role: !Ref Role
+        import: !ImportValue
+        - Shared
+    "};
+    test_serde(&template, yaml);
+    let value = serde_yaml::to_value(&template).unwrap();
+    assert_eq!(serde_yaml::from_value::<Template>(value).unwrap(), template);
+
+    let yaml = "bucket: !Ref Bucket\norigin: !Point {x: 1, y: 2}\narn: !GetAtt [Bucket, Arn]\nimport: !ImportValue []\n";
+    let template: Template = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(template.role, None);
+
+    let yaml = "bucket: Bucket\n";
+    let error = serde_yaml::from_str::<Template>(yaml).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "bucket: invalid type: string \"Bucket\", expected a value tagged !Ref at line 1 column 9",
+    );
+    let yaml = "bucket: !Ref Bucket\norigin: !Point {x: 1, y: 2}\narn: !GetAtt [Bucket, Arn]\nimport: !Sub x\n";
+    let error = serde_yaml::from_str::<Template>(yaml).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "import: invalid value: tag !Sub, expected a value tagged !ImportValue at line 4 column 9",
+    );
+}
+
+#[test]
+fn test_directives() {
+    #[derive(Serialize, PartialEq, Debug)]
+    enum Shape {
+        #[serde(rename = "tag:example.com,2000:circle")]
+        Circle {
+            radius: u8,
+        },
+        Square {
+            side: u8,
+        },
+    }
+
+    let builder = SerializerBuilder::new()
+        .yaml_directive(true)
+        .tag_directive("!e!", "tag:example.com,2000:")
+        .verify_roundtrip(true);
+    let mut buffer = Vec::new();
+    let mut ser = builder.clone().build(&mut buffer);
+    serde::Serialize::serialize(&Shape::Circle { radius: 3 }, &mut ser).unwrap();
+    serde::Serialize::serialize(&Shape::Square { side: 2 }, &mut ser).unwrap();
+    drop(ser);
+    let expected = indoc! {"
+        %YAML 1.2
+        %TAG !e! tag:example.com,2000:
+        --- !e!circle
+        radius: 3
+        ...
+        %YAML 1.2
+        %TAG !e! tag:example.com,2000:
+        --- !Square
+        side: 2
+    "};
+    assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+    let documents: Vec<Value> = serde_yaml::Deserializer::from_str(expected)
+        .map(|document| serde::Deserialize::deserialize(document).unwrap())
+        .collect();
+    assert_eq!(
+        documents[1],
+        serde_yaml::from_str::<Value>("!Square {side: 2}").unwrap()
+    );
+
+    let json = builder.clone().json(true).to_string(&[1]).unwrap();
+    assert_eq!(json, "[1]\n");
+
+    let error = SerializerBuilder::new()
+        .tag_directive("e", "tag:example.com,2000:")
+        .to_string(&1)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "tag handle must start with '!'");
+}
+
+#[test]
+fn test_compact() {
+    #[derive(Serialize)]
+    enum Shape {
+        Polygon(Vec<u8>),
+    }
+
+    #[derive(Serialize)]
+    struct Unit {
+        name: &'static str,
+        shape: Shape,
+        waypoints: Vec<(i32, i32)>,
+        notes: Vec<&'static str>,
+        path: Commented<Vec<u32>>,
+        items: Vec<Commented<u8>>,
+        tags: BTreeMap<&'static str, &'static str>,
+        empty: Vec<u8>,
+    }
+
+    let mut tags = BTreeMap::new();
+    tags.insert("faction", "red");
+    tags.insert("role", "scout");
+    let unit = Unit {
+        name: "scout",
+        shape: Shape::Polygon(vec![1, 2, 3]),
+        waypoints: vec![(0, 0), (10, -4)],
+        notes: vec!["two\nlines", "x"],
+        path: Commented::new("Route", vec![100000; 8]),
+        items: vec![Commented::new("Sword", 1)],
+        tags,
+        empty: Vec::new(),
+    };
+    let expected = indoc! {"
+        name: scout
+        shape: !Polygon [1, 2, 3]
+        waypoints:
+        - [0, 0]
+        - [10, -4]
+        notes:
+        - |-
+          two
+          lines
+        - x
+        # Route
+        path:
+        - 100000
+        - 100000
+        - 100000
+        - 100000
+        - 100000
+        - 100000
+        - 100000
+        - 100000
+        items:
+        # Sword
+        - 1
+        tags: {faction: red, role: scout}
+        empty: []
+    "};
+    let builder = SerializerBuilder::new()
+        .compact(true)
+        .verify_roundtrip(true);
+    assert_eq!(builder.to_string(&unit).unwrap(), expected);
+
+    // The root is not written in flow style, and neither are collections
+    // with collections in them.
+    assert_eq!(builder.to_string(&[1, 2]).unwrap(), "- 1\n- 2\n");
+    let yaml = builder
+        .clone()
+        .deterministic(true)
+        .indent_sequences(true)
+        .to_string(&unit.tags)
+        .unwrap();
+    assert_eq!(yaml, "faction: red\nrole: scout\n");
+    let mut nested = BTreeMap::new();
+    nested.insert("b", vec![vec!["x"]]);
+    nested.insert("a", vec![vec!["y", "z"]]);
+    let yaml = builder
+        .clone()
+        .deterministic(true)
+        .to_string(&nested)
+        .unwrap();
+    assert_eq!(yaml, "a:\n- [y, z]\nb:\n- [x]\n");
+}
+
+#[test]
+fn test_document_terminators() {
+    fn documents(builder: SerializerBuilder) -> String {
+        let mut buffer = Vec::new();
+        let mut ser = builder.build(&mut buffer);
+        serde::Serialize::serialize("first", &mut ser).unwrap();
+        serde::Serialize::serialize(&["a", "b"], &mut ser).unwrap();
+        drop(ser);
+        String::from_utf8(buffer).unwrap()
+    }
+
+    let builder = SerializerBuilder::new().verify_roundtrip(true);
+    let yaml = documents(builder.clone().trailing_newline(false));
+    assert_eq!(yaml, "first\n---\n- a\n- b");
+    let yaml = documents(builder.clone().trailing_newline(false).crlf(true));
+    assert_eq!(yaml, "first\r\n---\r\n- a\r\n- b");
+    let yaml = documents(builder.clone().trailing_newline(false).initial_indent(2));
+    assert_eq!(yaml, "  first\n  ---\n  - a\n  - b");
+    let yaml = documents(builder.clone().document_end(true));
+    assert_eq!(yaml, "first\n...\n---\n- a\n- b\n...\n");
+    let yaml = documents(builder.clone().document_end(true).trailing_newline(false));
+    assert_eq!(yaml, "first\n...\n---\n- a\n- b\n...");
+    let yaml = documents(builder.clone().document_end(true).yaml_directive(true));
+    assert_eq!(
+        yaml,
+        "%YAML 1.2\n--- first\n...\n%YAML 1.2\n---\n- a\n- b\n...\n"
+    );
+
+    let mut ser = serde_yaml::SerializerBuilder::new()
+        .trailing_newline(false)
+        .build(Vec::new());
+    serde::Serialize::serialize("text", &mut ser).unwrap();
+    assert_eq!(ser.into_inner().unwrap(), b"text");
+
+    #[derive(Serialize)]
+    struct Config {
+        name: &'static str,
+        #[serde(with = "serde_yaml::with::default_as_comment")]
+        verbose: bool,
+    }
+    let yaml = builder
+        .clone()
+        .document_end(true)
+        .trailing_newline(false)
+        .to_string(&Config {
+            name: "web",
+            verbose: false,
+        })
+        .unwrap();
+    assert_eq!(yaml, "name: web\n# verbose: false\n...");
+
+    let json = builder
+        .json(true)
+        .document_end(true)
+        .to_string(&[1])
+        .unwrap();
+    assert_eq!(json, "[1]\n");
+}
+
+#[test]
+fn test_quote_keys() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Workflow {
+        on: BTreeMap<String, u32>,
+        #[serde(rename = "runs-on")]
+        runs_on: String,
+        retries: BTreeMap<u32, bool>,
+    }
+
+    let mut on = BTreeMap::new();
+    on.insert("push".to_owned(), 1);
+    on.insert("tab\there".to_owned(), 2);
+    let mut retries = BTreeMap::new();
+    retries.insert(3, true);
+    let workflow = Workflow {
+        on,
+        runs_on: "ubuntu".to_owned(),
+        retries,
+    };
+    let yaml = SerializerBuilder::new()
+        .quote_keys(true)
+        .to_string(&workflow)
+        .unwrap();
+    let expected = indoc! {r#"
+        'on':
+          'push': 1
+          "tab\there": 2
+        'runs-on': ubuntu
+        'retries':
+          3: true
+    "#};
+    assert_eq!(yaml, expected);
+    assert_eq!(workflow, serde_yaml::from_str::<Workflow>(&yaml).unwrap());
+
+    let yaml = SerializerBuilder::new()
+        .quote_keys(true)
+        .quote_style(QuoteStyle::Double)
+        .to_string(&workflow)
+        .unwrap();
+    assert!(yaml.starts_with("\"on\":\n  \"push\": 1\n"), "{}", yaml);
+}
+
+#[test]
+fn test_bytes() {
+    #[derive(PartialEq, Debug)]
+    struct Bytes(Vec<u8>);
+
+    impl serde::Serialize for Bytes {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for Bytes {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct BytesVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                type Value = Bytes;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("bytes")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                    Ok(Bytes(v))
+                }
+
+                fn visit_seq<A>(self, mut seq: A) -> Result<Bytes, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                {
+                    let mut bytes = Vec::new();
+                    while let Some(byte) = seq.next_element()? {
+                        bytes.push(byte);
+                    }
+                    Ok(Bytes(bytes))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Blob {
+        key: Bytes,
+        empty: Bytes,
+        chunks: Vec<Bytes>,
+    }
+
+    let blob = Blob {
+        key: Bytes(b"hello world".to_vec()),
+        empty: Bytes(Vec::new()),
+        chunks: vec![
+            Bytes(b"a".to_vec()),
+            Bytes(b"ab".to_vec()),
+            Bytes(vec![0xFF; 3]),
+        ],
+    };
+    let yaml = serde_yaml::to_string(&blob).unwrap();
+    let expected = indoc! {"
+        key: !!binary aGVsbG8gd29ybGQ=
+        empty: !!binary ''
+        chunks:
+        - !!binary YQ==
+        - !!binary YWI=
+        - !!binary ////
+    "};
+    assert_eq!(yaml, expected);
+    assert_eq!(blob, serde_yaml::from_str::<Blob>(&yaml).unwrap());
+
+    // Line breaks are skipped, and a sequence of bytes is accepted too.
+    let yaml = indoc! {"
+        key: !!binary |
+          aGVsbG8g
+          d29ybGQ=
+        empty: []
+        chunks: [[97], !!binary YWI=, [255, 255, 255]]
+    "};
+    assert_eq!(blob, serde_yaml::from_str::<Blob>(yaml).unwrap());
+
+    let error = SerializerBuilder::new()
+        .json(true)
+        .to_string(&blob)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "bytes cannot be written as JSON");
+    let error = SerializerBuilder::new()
+        .tags(false)
+        .to_string(&blob)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "tag `!!binary` is not allowed when tags are disabled",
+    );
+}
+
+#[test]
+fn test_multi_document() {
+    #[derive(Serialize)]
+    struct Manifest {
+        kind: &'static str,
+        replicas: Option<u32>,
+    }
+
+    let manifests = vec![
+        Manifest {
+            kind: "Service",
+            replicas: None,
+        },
+        Manifest {
+            kind: "Deployment",
+            replicas: Some(3),
+        },
+    ];
+    let yaml = serde_yaml::to_string_multi(&manifests).unwrap();
+    let expected = indoc! {"
+        kind: Service
+        replicas: null
+        ---
+        kind: Deployment
+        replicas: 3
+    "};
+    assert_eq!(yaml, expected);
+
+    let values: Vec<Value> = serde_yaml::Deserializer::from_str(&yaml)
+        .map(|document| serde::Deserialize::deserialize(document).unwrap())
+        .collect();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[1]["replicas"], 3);
+
+    let yaml = SerializerBuilder::new()
+        .document_start(true)
+        .document_end(true)
+        .to_string_multi([1, 2])
+        .unwrap();
+    assert_eq!(yaml, "--- 1\n...\n--- 2\n...\n");
+
+    assert_eq!(
+        serde_yaml::to_string_multi(Vec::<Value>::new()).unwrap(),
+        ""
+    );
+
+    let mut buffer = Vec::new();
+    serde_yaml::to_writer_multi(&mut buffer, ["a", "b"]).unwrap();
+    assert_eq!(buffer, b"a\n--- b\n");
+
+    let error = SerializerBuilder::new()
+        .json(true)
+        .to_string_multi([1, 2])
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "more than one document cannot be written as JSON"
+    );
+}
+
+#[test]
+fn test_fmt_writer() {
+    struct Config(BTreeMap<String, Vec<String>>);
+
+    impl fmt::Display for Config {
+        fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            serde_yaml::to_fmt_writer(formatter, &self.0).map_err(|_| fmt::Error)
+        }
+    }
+
+    // Long enough for the emitter to write out its buffer in the middle of
+    // a character.
+    let words: Vec<String> = (0..5000).map(|i| format!("é{}😀", i)).collect();
+    let config = Config(BTreeMap::from([("words".to_owned(), words)]));
+    let yaml = serde_yaml::to_string(&config.0).unwrap();
+    assert_eq!(config.to_string(), yaml);
+
+    let mut string = String::new();
+    SerializerBuilder::new()
+        .json(true)
+        .to_fmt_writer(&mut string, &config.0)
+        .unwrap();
+    assert_eq!(
+        string,
+        SerializerBuilder::new()
+            .json(true)
+            .to_string(&config.0)
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_streaming_writer() {
+    use serde::ser::{SerializeSeq, Serializer};
+    use std::cell::Cell;
+    use std::io;
+    use std::rc::Rc;
+
+    struct CountingWriter(Rc<Cell<usize>>);
+
+    impl io::Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.set(self.0.get() + buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Records how much was written by the time the last item is serialized.
+    struct Items(Rc<Cell<usize>>, Rc<Cell<usize>>);
+
+    impl serde::Serialize for Items {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut seq = serializer.serialize_seq(None)?;
+            for i in 0..10000 {
+                seq.serialize_element(&format!("item {}", i))?;
+            }
+            self.1.set(self.0.get());
+            seq.end()
+        }
+    }
+
+    for builder in [
+        SerializerBuilder::new(),
+        SerializerBuilder::new().json(true),
+    ] {
+        let written = Rc::new(Cell::new(0));
+        let before_end = Rc::new(Cell::new(0));
+        let items = Items(Rc::clone(&written), Rc::clone(&before_end));
+        builder
+            .to_writer(CountingWriter(Rc::clone(&written)), &items)
+            .unwrap();
+        assert!(before_end.get() > written.get() / 2);
+    }
+}
+
+#[test]
+fn test_align_values() {
+    let value: Value = serde_yaml::from_str(indoc! {r#"
+        name: web
+        "long key": 1
+        ünï: x
+        nested:
+          a: 1
+          list: [1, 2]
+          tagged: !t 5
+          text: "line1\nline2\n"
+        items:
+        - id: 1
+          description: first
+        - x
+    "#})
+    .unwrap();
+    let builder = SerializerBuilder::new().align_values(true);
+    let yaml = builder.to_string(&value).unwrap();
+    let expected = indoc! {"
+        name:     web
+        long key: 1
+        ünï:      x
+        nested:
+          a:      1
+          list:
+          - 1
+          - 2
+          tagged: !t 5
+          text:   |
+            line1
+            line2
+        items:
+        - id:          1
+          description: first
+        - x
+    "};
+    assert_eq!(yaml, expected);
+    assert_eq!(value, serde_yaml::from_str::<Value>(&yaml).unwrap());
+
+    let yaml = builder
+        .clone()
+        .indent_sequences(true)
+        .verify_roundtrip(true)
+        .to_string(&value["items"])
+        .unwrap();
+    assert_eq!(yaml, "- id:          1\n  description: first\n- x\n");
+
+    #[derive(Serialize)]
+    struct Config {
+        name: &'static str,
+        #[serde(with = "serde_yaml::with::default_as_comment")]
+        verbose: bool,
+        port: Commented<u16>,
+        replicas: u8,
+    }
+
+    let config = Config {
+        name: "web",
+        verbose: false,
+        port: Commented::new("The port to listen on", 80),
+        replicas: 3,
+    };
+    let yaml = builder.to_string(&config).unwrap();
+    let expected = indoc! {"
+        name:     web
+        # verbose: false
+        # The port to listen on
+        port:     80
+        replicas: 3
+    "};
+    assert_eq!(yaml, expected);
+}
@@ -0,0 +1,124 @@
+#![allow(clippy::uninlined_format_args)]
+
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::Debug;
+use time::{Date, Month, OffsetDateTime, Time as TimeOfDay, UtcOffset};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Chrono<Tz>
+where
+    Tz: chrono::TimeZone,
+    DateTime<Tz>: From<DateTime<FixedOffset>>,
+{
+    #[serde(with = "serde_yaml::with::timestamp")]
+    at: DateTime<Tz>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Time {
+    #[serde(with = "serde_yaml::with::timestamp")]
+    at: OffsetDateTime,
+}
+
+#[test]
+fn test_chrono() {
+    let offset = FixedOffset::west_opt(5 * 3600).unwrap();
+    let at = offset
+        .with_ymd_and_hms(2001, 12, 14, 21, 59, 43)
+        .unwrap()
+        .checked_add_signed(chrono::Duration::milliseconds(100))
+        .unwrap();
+    for yaml in [
+        "at: 2001-12-14t21:59:43.10-05:00",
+        "at: 2001-12-14 21:59:43.10 -5",
+        "at: !!timestamp 2001-12-14T21:59:43.1-05:00",
+        "at: '2001-12-14T21:59:43.100000000-05:00'",
+        "at: 2001-12-15 2:59:43.10",
+        "at: 2001-12-15T02:59:43.1Z",
+    ] {
+        let deserialized: Chrono<FixedOffset> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(deserialized.at, at, "{}", yaml);
+    }
+
+    let deserialized: Chrono<Utc> = serde_yaml::from_str("at: 2002-12-14").unwrap();
+    assert_eq!(
+        deserialized.at,
+        Utc.with_ymd_and_hms(2002, 12, 14, 0, 0, 0).unwrap(),
+    );
+
+    let yaml = serde_yaml::to_string(&Chrono { at }).unwrap();
+    assert_eq!(yaml, "at: 2001-12-14T21:59:43.1-05:00\n");
+    let yaml = serde_yaml::to_string(&Chrono {
+        at: at.with_timezone(&Utc),
+    })
+    .unwrap();
+    assert_eq!(yaml, "at: 2001-12-15T02:59:43.1Z\n");
+}
+
+fn datetime(date: (i32, u8, u8), time: (u8, u8, u8, u32), offset: i32) -> OffsetDateTime {
+    let month = Month::try_from(date.1).unwrap();
+    let date = Date::from_calendar_date(date.0, month, date.2).unwrap();
+    let time = TimeOfDay::from_hms_nano(time.0, time.1, time.2, time.3).unwrap();
+    let offset = UtcOffset::from_whole_seconds(offset).unwrap();
+    date.with_time(time).assume_offset(offset)
+}
+
+#[test]
+fn test_time() {
+    let at = datetime((2001, 12, 14), (21, 59, 43, 100_000_000), -5 * 3600);
+    let deserialized: Time = serde_yaml::from_str("at: 2001-12-14 21:59:43.10 -5").unwrap();
+    assert_eq!(deserialized.at, at);
+    let yaml = serde_yaml::to_string(&Time { at }).unwrap();
+    assert_eq!(yaml, "at: 2001-12-14T21:59:43.1-05:00\n");
+    assert_eq!(serde_yaml::from_str::<Time>(&yaml).unwrap().at, at);
+
+    // Through a Value too.
+    let value: serde_yaml::Value = serde_yaml::from_str("at: !!timestamp 2002-12-14").unwrap();
+    let deserialized: Time = serde_yaml::from_value(value).unwrap();
+    assert_eq!(deserialized.at, datetime((2002, 12, 14), (0, 0, 0, 0), 0));
+
+    let at = datetime((2001, 12, 14), (21, 59, 43, 0), 30);
+    let err = serde_yaml::to_string(&Time { at }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "date and time cannot be written as a YAML timestamp",
+    );
+}
+
+#[test]
+fn test_invalid() {
+    for (yaml, expected) in [
+        ("at: 2001-12-14x", "invalid value: string \"2001-12-14x\""),
+        ("at: 2001-13-14", "invalid value: string \"2001-13-14\""),
+        ("at: 2001-1-14", "invalid value: string \"2001-1-14\""),
+        (
+            "at: 2001-12-14 21:59",
+            "invalid value: string \"2001-12-14 21:59\"",
+        ),
+        (
+            "at: 2001-12-14 25:00:00",
+            "invalid value: string \"2001-12-14 25:00:00\"",
+        ),
+        (
+            "at: 2001-12-14T21:59:43+5:3",
+            "invalid value: string \"2001-12-14T21:59:43+5:3\"",
+        ),
+        (
+            "at: 2001-12-14 21:59:43 PST",
+            "invalid value: string \"2001-12-14 21:59:43 PST\"",
+        ),
+        ("at: 1", "invalid value: string \"1\""),
+    ] {
+        let err = serde_yaml::from_str::<Time>(yaml).unwrap_err().to_string();
+        let expected = format!(
+            "at: {}, expected a YAML timestamp at line 1 column 5",
+            expected
+        );
+        assert_eq!(err, expected, "{}", yaml);
+        let err = serde_yaml::from_str::<Chrono<Utc>>(yaml)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, expected, "{}", yaml);
+    }
+}
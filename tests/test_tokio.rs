@@ -0,0 +1,233 @@
+use serde_derive::Deserialize;
+use serde_yaml::{DeserializerBuilder, Value};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use tokio::io::{AsyncRead, ReadBuf};
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Polls the future on this thread until it is done, without a runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+// Gives a few bytes of the input at a time, and is pending before each of
+// them, like a connection that the input comes in slowly over.
+struct Trickle {
+    input: &'static [u8],
+    ready: bool,
+    reads: usize,
+}
+
+impl Trickle {
+    fn new(input: &'static [u8]) -> Self {
+        Trickle {
+            input,
+            ready: false,
+            reads: 0,
+        }
+    }
+}
+
+impl AsyncRead for Trickle {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        if !self.ready {
+            self.ready = true;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        self.ready = false;
+        self.reads += 1;
+        let n = self.input.len().min(3).min(buf.remaining());
+        buf.put_slice(&self.input[..n]);
+        self.input = &self.input[n..];
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct Manifest {
+    name: String,
+    hosts: Vec<String>,
+}
+
+#[test]
+fn test_from_async_reader() {
+    let yaml = "name: web\nhosts: [a, b]\n";
+    let manifest: Manifest = block_on(serde_yaml::from_async_reader(yaml.as_bytes())).unwrap();
+    let expected = Manifest {
+        name: "web".to_owned(),
+        hosts: vec!["a".to_owned(), "b".to_owned()],
+    };
+    assert_eq!(manifest, expected);
+}
+
+#[test]
+fn test_chunked() {
+    let yaml = b"name: web\nhosts: [a, b]\n";
+    let value: Value = block_on(serde_yaml::from_async_reader(Trickle::new(yaml))).unwrap();
+    assert_eq!(value["hosts"][1], "b");
+}
+
+#[test]
+fn test_documents() {
+    let yaml = b"name: web\nhosts: [a]\n---\nname: db\nhosts: []\n...\n# end\n";
+    let mut read = Trickle::new(yaml);
+    let mut documents = DeserializerBuilder::new().async_documents(&mut read);
+    let mut names = Vec::new();
+    // The first document is given before the rest of the input is read.
+    let first: Manifest = block_on(documents.next_document()).unwrap().unwrap();
+    names.push(first.name);
+    while let Some(manifest) = block_on(documents.next_document::<Manifest>()) {
+        names.push(manifest.unwrap().name);
+    }
+    assert_eq!(names, ["web", "db"]);
+    drop(documents);
+    assert_eq!(read.input, b"");
+
+    let err = block_on(serde_yaml::from_async_reader::<_, Value>(&yaml[..])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "deserializing from YAML containing more than one document is not supported",
+    );
+}
+
+#[test]
+fn test_documents_read_lazily() {
+    let yaml = b"a: 1\n---\nb: 2\n";
+    let mut read = Trickle::new(yaml);
+    let mut documents = DeserializerBuilder::new().async_documents(&mut read);
+    let first: Value = block_on(documents.next_document()).unwrap().unwrap();
+    assert_eq!(first["a"], 1);
+    drop(documents);
+    // Reading stopped after the `---` line of the second document.
+    assert_eq!(read.input, b"b: 2\n");
+}
+
+#[test]
+fn test_documents_empty() {
+    for yaml in ["", "# nothing\n", "...\n"] {
+        let mut documents = DeserializerBuilder::new().async_documents(yaml.as_bytes());
+        let value: Value = block_on(documents.next_document()).unwrap().unwrap();
+        assert_eq!(value, Value::Null);
+        assert!(block_on(documents.next_document::<Value>()).is_none());
+    }
+
+    let yaml = "%YAML 1.2\n---\na\n...\n%YAML 1.2\n---\nb\n";
+    let mut documents = DeserializerBuilder::new().async_documents(yaml.as_bytes());
+    let mut values = Vec::new();
+    while let Some(value) = block_on(documents.next_document::<String>()) {
+        values.push(value.unwrap());
+    }
+    assert_eq!(values, ["a", "b"]);
+}
+
+#[test]
+fn test_documents_errors() {
+    // The location is in all of the input, not in the second document.
+    let yaml = b"name: web\nhosts: [a]\n---\nname: db\r\nhosts: a\n";
+    let mut documents = DeserializerBuilder::new().async_documents(Trickle::new(yaml));
+    block_on(documents.next_document::<Manifest>())
+        .unwrap()
+        .unwrap();
+    let err = block_on(documents.next_document::<Manifest>())
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "hosts: invalid type: string \"a\", expected a sequence at line 5 column 8",
+    );
+    assert_eq!(err.location().unwrap().index(), 42);
+
+    let yaml = b"a: 1\n---\nb: [\n";
+    let mut documents = DeserializerBuilder::new().async_documents(&yaml[..]);
+    block_on(documents.next_document::<Value>())
+        .unwrap()
+        .unwrap();
+    let err = block_on(documents.next_document::<Value>())
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.location().unwrap().line(), 4);
+}
+
+#[test]
+fn test_utf16() {
+    let yaml: Vec<u8> = "\u{feff}a: 1\n---\nb: 2\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let mut documents = DeserializerBuilder::new().async_documents(&yaml[..]);
+    let first: Value = block_on(documents.next_document()).unwrap().unwrap();
+    let second: Value = block_on(documents.next_document()).unwrap().unwrap();
+    assert_eq!(first["a"], 1);
+    assert_eq!(second["b"], 2);
+    assert!(block_on(documents.next_document::<Value>()).is_none());
+}
+
+#[test]
+fn test_send() {
+    fn assert_send<T: Send>(_: T) {}
+    assert_send(serde_yaml::from_async_reader::<_, Value>(&b""[..]));
+    let mut documents = DeserializerBuilder::new().async_documents(&b""[..]);
+    assert_send(documents.next_document::<Value>());
+}
+
+#[test]
+fn test_builder() {
+    let builder = DeserializerBuilder::new().recursion_limit(2);
+    let err = block_on(builder.from_async_reader::<_, Value>(&b"[[[1]]]"[..])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "recursion limit exceeded at line 1 column 3"
+    );
+}
+
+#[test]
+fn test_errors() {
+    struct Failing;
+
+    impl AsyncRead for Failing {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            _buf: &mut ReadBuf,
+        ) -> Poll<io::Result<()>> {
+            Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::Other,
+                "connection reset",
+            )))
+        }
+    }
+
+    let err = block_on(serde_yaml::from_async_reader::<_, Value>(Failing)).unwrap_err();
+    assert_eq!(err.to_string(), "connection reset");
+
+    let yaml: &[u8] = b"name: web\nhosts: a\n";
+    let err = block_on(serde_yaml::from_async_reader::<_, Manifest>(yaml)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "hosts: invalid type: string \"a\", expected a sequence at line 2 column 8",
+    );
+}
@@ -8,7 +8,10 @@ use indoc::indoc;
 use serde::de::IntoDeserializer;
 use serde::Deserialize;
 use serde_derive::{Deserialize, Serialize};
-use serde_yaml::{Number, Value};
+use serde_yaml::value::{Segment, Walk};
+use serde_yaml::{DeserializerBuilder, Mapping, Number, SchemaProfile, SerializerBuilder, Value};
+use std::collections::HashMap;
+use std::mem;
 
 #[test]
 fn test_nan() {
@@ -98,6 +101,235 @@ fn test_merge() {
     }
 }
 
+#[test]
+fn test_merge_precedence() {
+    // Merged mappings which contain merge keys of their own.
+    let yaml = indoc! {"
+        - &BASE
+          x: 0
+          y: 0
+          label: base
+        - &LEFT
+          <<: *BASE
+          x: -1
+        - &RIGHT
+          <<: *BASE
+          x: 1
+          label: right
+        - <<: [*LEFT, *RIGHT]
+        - <<: [*RIGHT, *LEFT]
+          y: 2
+    "};
+
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    value.apply_merge().unwrap();
+
+    let expected: Value = serde_yaml::from_str(indoc! {"
+        x: -1
+        y: 0
+        label: base
+    "})
+    .unwrap();
+    assert_eq!(value[3], expected);
+
+    let expected: Value = serde_yaml::from_str(indoc! {"
+        y: 2
+        x: 1
+        label: right
+    "})
+    .unwrap();
+    assert_eq!(value[4], expected);
+    assert_eq!(value[4]["<<"], Value::Null);
+}
+
+#[test]
+fn test_merge_strict() {
+    let yaml = indoc! {"
+        - &A
+          retries: 3
+          timeout: 10
+        - &B
+          retries: 3
+          verbose: true
+        - <<: [*A, *B]
+    "};
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    value.apply_merge_strict().unwrap();
+    assert_eq!(value[2]["verbose"], true);
+
+    let yaml = indoc! {"
+        - &A
+          retries: 3
+        - &B
+          retries: 5
+        - <<: [*A, *B]
+          name: overrides are fine
+        - <<: [*A, *B]
+          retries: 1
+    "};
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    let error = value.apply_merge_strict().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        ".[2].<<: conflicting values for key \"retries\" in merge",
+    );
+
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    value.apply_merge().unwrap();
+    assert_eq!(value[2]["retries"], 3);
+    assert_eq!(value[3]["retries"], 1);
+}
+
+#[test]
+fn test_merge_error_path() {
+    let yaml = indoc! {"
+        defaults:
+          retry:
+            policy:
+              <<: [{attempts: 3}, 5]
+    "};
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    let error = value.apply_merge().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "defaults.retry.policy.<<[1]: expected a mapping for merging, but found scalar",
+    );
+    assert!(error.location().is_none());
+
+    let yaml = indoc! {"
+        - name: a
+          <<: &base
+            <<: !Tagged {}
+    "};
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    let error = value.apply_merge().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        ".[0].<<.<<: unexpected tagged value in merge",
+    );
+}
+
+#[test]
+fn test_from_value_error_path() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        defaults: Defaults,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Defaults {
+        retry: Vec<Retry>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Retry {
+        attempts: u8,
+    }
+
+    let yaml = indoc! {"
+        defaults:
+          retry:
+            - attempts: 1
+            - attempts: many
+    "};
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    let expected = "defaults.retry[1].attempts: invalid type: string \"many\", expected u8";
+
+    let error = Config::deserialize(&value).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+
+    let error = serde_yaml::from_value::<Config>(value).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+
+    // The keys of a map, where one that is a sequence is shown as `?`.
+    let value: Value = serde_yaml::from_str("{7: 1, cpu: many}").unwrap();
+    let error = serde_yaml::from_value::<HashMap<Value, u8>>(value).unwrap_err();
+    let expected = "cpu: invalid type: string \"many\", expected u8";
+    assert_eq!(error.to_string(), expected);
+    let value: Value = serde_yaml::from_str("{[a, b]: many}").unwrap();
+    let error = serde_yaml::from_value::<HashMap<Value, u8>>(value).unwrap_err();
+    let expected = "?: invalid type: string \"many\", expected u8";
+    assert_eq!(error.to_string(), expected);
+    let value: Value = serde_yaml::from_str("{7: many}").unwrap();
+    let error = serde_yaml::from_value::<HashMap<u32, u8>>(value).unwrap_err();
+    let expected = "7: invalid type: string \"many\", expected u8";
+    assert_eq!(error.to_string(), expected);
+}
+
+#[test]
+fn test_mapping_capacity() {
+    let mut mapping = Mapping::with_capacity(100);
+    assert!(mapping.capacity() >= 100);
+    assert!(mapping.is_empty());
+
+    for i in 0..10 {
+        mapping.insert(Value::from(i), Value::Null);
+    }
+    mapping.reserve(1000);
+    assert!(mapping.capacity() >= 1010);
+
+    mapping.retain(|key, _value| key.as_u64() == Some(0));
+    mapping.shrink_to_fit();
+    assert!(mapping.capacity() >= 1);
+    assert!(mapping.capacity() < 1010);
+    assert_eq!(mapping.len(), 1);
+
+    let value = Value::Mapping(mapping);
+    assert_eq!(value[0], Value::Null);
+}
+
+#[test]
+fn test_mapping_equality() {
+    let golden: Value = serde_yaml::from_str(indoc! {"
+        name: app
+        ports: [80, 443]
+        limits:
+          cpu: 2
+          .nan: 0.0
+    "})
+    .unwrap();
+    let reordered: Value = serde_yaml::from_str(indoc! {"
+        name: app
+        limits:
+          .NaN: -0.0
+          cpu: 2
+        ports: [80, 443]
+    "})
+    .unwrap();
+    let swapped_ports: Value = serde_yaml::from_str(indoc! {"
+        name: app
+        ports: [443, 80]
+        limits:
+          cpu: 2
+          .nan: 0.0
+    "})
+    .unwrap();
+
+    assert_eq!(golden, reordered);
+    assert!(golden.eq_ignore_order(&reordered));
+    let (golden_mapping, reordered_mapping) = (
+        golden.as_mapping().unwrap(),
+        reordered.as_mapping().unwrap(),
+    );
+    assert!(!golden_mapping.eq_ordered(reordered_mapping));
+
+    let mut rebuilt = Mapping::new();
+    for (k, v) in golden_mapping {
+        rebuilt.insert(k.clone(), v.clone());
+    }
+    rebuilt.insert("limits".into(), reordered["limits"].clone());
+    assert!(!golden_mapping.eq_ordered(&rebuilt));
+    rebuilt.insert("limits".into(), golden["limits"].clone());
+    assert!(golden_mapping.eq_ordered(&rebuilt));
+
+    assert_ne!(golden, swapped_ports);
+    assert!(!golden.eq_ignore_order(&swapped_ports));
+    assert!(!golden_mapping.eq_ordered(swapped_ports.as_mapping().unwrap()));
+}
+
 #[test]
 fn test_debug() {
     let yaml = indoc! {"
@@ -151,3 +383,425 @@ fn test_tagged() {
     let serialized = serde_yaml::to_value(&value).unwrap();
     assert_eq!(value, serialized);
 }
+
+#[test]
+fn test_pointer() {
+    let yaml = indoc! {"
+        a/b: 1
+        m~n: 2
+        list: [x, {y: z}]
+        42: answer
+        '42': string
+        -1: negative
+        tagged: !T {inner: 3}
+    "};
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(value.pointer(""), Some(&value));
+    assert_eq!(*value.pointer("/a~1b").unwrap(), 1);
+    assert_eq!(*value.pointer("/m~0n").unwrap(), 2);
+    assert_eq!(*value.pointer("/list/1/y").unwrap(), "z");
+    assert_eq!(*value.pointer("/42").unwrap(), "string");
+    assert_eq!(*value.pointer("/-1").unwrap(), "negative");
+    assert_eq!(*value.pointer("/tagged/inner").unwrap(), 3);
+    assert_eq!(value.pointer("/list/01"), None);
+    assert_eq!(value.pointer("/list/2"), None);
+    assert_eq!(value.pointer("list"), None);
+    assert_eq!(value.pointer("/list/0/x"), None);
+
+    let mut value = value;
+    *value.pointer_mut("/list/0").unwrap() = Value::from("w");
+    assert_eq!(value["list"][0], "w");
+}
+
+#[test]
+fn test_take_as() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Retry {
+        attempts: u8,
+    }
+
+    let yaml = indoc! {"
+        retry:
+          attempts: 3
+        hooks:
+          - attempts: 1
+          - attempts: lots
+    "};
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+
+    let borrowed: Option<Retry> = value.get_as("/retry").unwrap();
+    assert_eq!(borrowed, Some(Retry { attempts: 3 }));
+    let taken: Option<Retry> = value.take_as("/retry").unwrap();
+    assert_eq!(taken, Some(Retry { attempts: 3 }));
+    assert_eq!(value["retry"], Value::Null);
+    assert_eq!(value.take_as::<Retry>("/missing").unwrap(), None);
+
+    let error = value.get_as::<Retry>("/hooks/1").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "hooks[1].attempts: invalid type: string \"lots\", expected u8",
+    );
+    let error = value.take_as::<Vec<Retry>>("/hooks").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "hooks[1].attempts: invalid type: string \"lots\", expected u8",
+    );
+
+    let mut mapping: Mapping = serde_yaml::from_str(yaml).unwrap();
+    let retry: Option<Retry> = mapping.get_as("retry").unwrap();
+    assert_eq!(retry, Some(Retry { attempts: 3 }));
+    let retry: Option<Retry> = mapping.remove_as("retry").unwrap();
+    assert_eq!(retry, Some(Retry { attempts: 3 }));
+    assert!(!mapping.contains_key("retry"));
+    assert_eq!(mapping.remove_as::<Retry, _>("retry").unwrap(), None);
+    let error = mapping.remove_as::<Vec<Retry>, _>("hooks").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "hooks[1].attempts: invalid type: string \"lots\", expected u8",
+    );
+    assert!(mapping.is_empty());
+}
+
+#[test]
+fn test_walk() {
+    let yaml = indoc! {"
+        a/b: !Tagged [x]
+        1: {? [k]: v}
+    "};
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+
+    let mut visits = Vec::new();
+    value.walk(|path, node| {
+        let kind = match path.segments().last() {
+            Some(Segment::Key(_)) => "key",
+            Some(Segment::Entry(_)) => "entry",
+            Some(Segment::Index(_)) => "index",
+            None => "root",
+        };
+        let node = match node {
+            Value::Sequence(_) => "sequence".to_owned(),
+            Value::Mapping(_) => "mapping".to_owned(),
+            Value::Tagged(tagged) => tagged.tag.to_string(),
+            scalar => serde_yaml::to_string(scalar).unwrap().trim_end().to_owned(),
+        };
+        visits.push(format!("{} {} {}", path, kind, node));
+        Walk::Continue
+    });
+    let expected = [
+        " root mapping",
+        "/a~1b key a/b",
+        "/a~1b entry !Tagged",
+        "/a~1b entry sequence",
+        "/a~1b/0 index x",
+        "/1 key 1",
+        "/1 entry mapping",
+        "/1/? key sequence",
+        "/1/?/0 index k",
+        "/1/? entry v",
+    ];
+    assert_eq!(visits, expected);
+    assert_eq!(value.pointer("/a~1b/0").unwrap(), "x");
+
+    let mut visits = 0;
+    value.walk(|path, _node| {
+        visits += 1;
+        if path.key() == Some("a/b") && !path.is_key() {
+            Walk::SkipChildren
+        } else if path.to_string() == "/1" && !path.is_key() {
+            Walk::Stop
+        } else {
+            Walk::Continue
+        }
+    });
+    assert_eq!(visits, 5);
+
+    let mut value = value;
+    value.walk_mut(|path, node| {
+        assert!(!path.is_key());
+        if node.is_mapping() && path.key() == Some("1") {
+            *node = Value::Sequence(vec![Value::from("replaced")]);
+        } else if let Value::String(string) = node {
+            string.push('!');
+        }
+        Walk::Continue
+    });
+    assert_eq!(value.pointer("/1/0").unwrap(), "replaced!");
+    assert_eq!(value.pointer("/a~1b/0").unwrap(), "x!");
+}
+
+#[test]
+fn test_number_repr() {
+    let yaml = indoc! {"
+        - 0x1F
+        - 0o17
+        - -0x10
+        - 1e3
+        - -1.5E-3
+        - -0
+        - -0.0
+        - .inf
+        - 12
+    "};
+    let builder = DeserializerBuilder::new().preserve_number_repr(true);
+    let value: Value = builder.from_str(yaml).unwrap();
+    let numbers: Vec<Number> = builder.clone().from_str(yaml).unwrap();
+    assert_eq!(numbers[0].original_repr(), Some("0x1F"));
+    assert_eq!(numbers[6].original_repr(), Some("-0.0"));
+    assert_eq!(value, serde_yaml::from_str::<Value>(yaml).unwrap());
+    assert_eq!(serde_yaml::to_string(&value).unwrap(), yaml);
+
+    let plain: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(
+        serde_yaml::to_string(&plain).unwrap(),
+        "- 31\n- 15\n- -16\n- 1000.0\n- -0.0015\n- 0\n- -0.0\n- .inf\n- 12\n",
+    );
+
+    let builder = DeserializerBuilder::new()
+        .schema(SchemaProfile::Legacy11)
+        .preserve_number_repr(true);
+    let value: Value = builder.from_str("[1_000, 0755]").unwrap();
+    assert_eq!(serde_yaml::to_string(&value).unwrap(), "- 1000\n- 493\n");
+
+    let value: Value = DeserializerBuilder::new()
+        .preserve_number_repr(true)
+        .from_str("0x1F")
+        .unwrap();
+    let mut out = Vec::new();
+    let mut serializer = SerializerBuilder::new().deterministic(true).build(&mut out);
+    serde::Serialize::serialize(&value, &mut serializer).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "31\n");
+}
+
+// Numbers wider than 64 bits, and ones with their original_repr, are kept
+// in a box, so that a Value is no bigger for them.
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn test_value_size() {
+    assert_eq!(mem::size_of::<Number>(), 16);
+    assert_eq!(mem::size_of::<Value>(), 72);
+
+    let numbers: Vec<Number> = DeserializerBuilder::new()
+        .preserve_number_repr(true)
+        .from_str("[0x1F, 340282366920938463463374607431768211455, -1]")
+        .unwrap();
+    assert_eq!(numbers[0].original_repr(), Some("0x1F"));
+    assert_eq!(numbers[1].as_u128(), Some(u128::MAX));
+    assert_eq!(numbers[1], Number::from(u128::MAX));
+    assert_eq!(numbers[2], Number::from(-1));
+    assert_eq!(Number::from(i128::MIN).as_i128(), Some(i128::MIN));
+    assert_eq!(Number::from(i128::MIN).original_repr(), None);
+}
+
+#[test]
+fn test_aggregate_errors() {
+    let yaml = indoc! {"
+        web: 80
+        api: 8080
+        db: five
+        cache: 6379
+        queue: 5672
+        search: -9200
+        metrics: 9090
+        mail: 25
+        ? [admin]
+        : 8443
+        dns: 53
+    "};
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    let errors = value.to_map_of::<String, u16>().unwrap_err();
+    let errors: Vec<(Value, String)> = errors
+        .into_iter()
+        .map(|(key, error)| (key, error.to_string()))
+        .collect();
+    let expected = [
+        (
+            Value::from("db"),
+            "db: invalid type: string \"five\", expected u16".to_owned(),
+        ),
+        (
+            Value::from("search"),
+            "search: invalid value: integer `-9200`, expected u16".to_owned(),
+        ),
+        (
+            Value::Sequence(vec![Value::from("admin")]),
+            "?: invalid type: sequence, expected a string".to_owned(),
+        ),
+    ];
+    assert_eq!(errors, expected);
+
+    let value: Value = serde_yaml::from_str("[1, 2, x, 4, 5, 6.5, 7, 8, [9], 10]").unwrap();
+    let errors = value.to_vec_of::<u8>().unwrap_err();
+    let errors: Vec<(usize, String)> = errors
+        .into_iter()
+        .map(|(index, error)| (index, error.to_string()))
+        .collect();
+    let expected = [
+        (
+            2,
+            ".[2]: invalid type: string \"x\", expected u8".to_owned(),
+        ),
+        (
+            5,
+            ".[5]: invalid type: floating point `6.5`, expected u8".to_owned(),
+        ),
+        (8, ".[8]: invalid type: sequence, expected u8".to_owned()),
+    ];
+    assert_eq!(errors, expected);
+
+    let value: Value = serde_yaml::from_str("!ports [1, 2]").unwrap();
+    assert_eq!(value.to_vec_of::<u8>().unwrap(), [1, 2]);
+
+    let errors = value.to_map_of::<String, u8>().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, Value::Null);
+    assert_eq!(
+        errors[0].1.to_string(),
+        "invalid type: sequence, expected a map",
+    );
+}
+
+#[test]
+fn test_normalize() {
+    use serde_yaml::value::NormalizeOptions;
+
+    let yaml = indoc! {"
+        zone: b
+        ? {y: 1, x: [{q: 1, p: 2}]}
+        : !tag {d: 4, c: 3}
+        list:
+          - {b: 2, a: 1}
+          - !tag [{z: 0, m: 1}]
+        10: ten
+        2: two
+        null: ~
+        true: t
+    "};
+    let mut sorted: Value = serde_yaml::from_str(yaml).unwrap();
+    sorted.sort_all_maps();
+    let expected = indoc! {"
+        null: null
+        true: t
+        2: two
+        10: ten
+        list:
+        - a: 1
+          b: 2
+        - !tag
+          - m: 1
+            z: 0
+        zone: b
+        ? x:
+          - p: 2
+            q: 1
+          y: 1
+        : !tag
+          c: 3
+          d: 4
+    "};
+    let serialized = serde_yaml::to_string(&sorted).unwrap();
+    assert_eq!(serialized, expected);
+
+    // Sorting again, or sorting the same value written in another order,
+    // gives the same result.
+    let mut again = sorted.clone();
+    again.sort_all_maps();
+    assert_eq!(serde_yaml::to_string(&again).unwrap(), serialized);
+    let mut reordered: Value = serde_yaml::from_str(&serialized).unwrap();
+    reordered.sort_all_maps();
+    assert_eq!(serde_yaml::to_string(&reordered).unwrap(), serialized);
+
+    let yaml = "{port: '8080', on: 'true', name: web, off: '', tls: !secret ~, hex: '0x1F'}";
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    let options = NormalizeOptions::new();
+    let normalized = serde_yaml::to_string(value.normalize(options.clone()).unwrap()).unwrap();
+    assert_eq!(
+        normalized,
+        "hex: 31\nname: web\noff: null\non: true\nport: 8080\ntls: null\n",
+    );
+    let twice = serde_yaml::to_string(value.normalize(options).unwrap()).unwrap();
+    assert_eq!(twice, normalized);
+
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    let options = NormalizeOptions::new()
+        .sort_maps(false)
+        .resolve_scalars(false)
+        .strip_tags(false);
+    value.normalize(options).unwrap();
+    assert_eq!(
+        serde_yaml::to_string(&value).unwrap(),
+        "port: '8080'\non: 'true'\nname: web\noff: null\ntls: !secret null\nhex: '0x1F'\n",
+    );
+
+    let mut deep = Value::Null;
+    for _ in 0..200 {
+        deep = Value::Sequence(vec![deep]);
+    }
+    let before = deep.clone();
+    let error = deep.normalize(NormalizeOptions::new()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "value is nested more than 128 levels deep"
+    );
+    assert_eq!(deep, before);
+    let options = NormalizeOptions::new().depth_limit(200);
+    assert_eq!(*deep.normalize(options).unwrap(), before);
+    deep.sort_all_maps();
+    assert_eq!(deep, before);
+
+    let mut shallow: Value = serde_yaml::from_str("[[1]]").unwrap();
+    let options = NormalizeOptions::new().depth_limit(1);
+    assert!(shallow.normalize(options).is_err());
+    let options = NormalizeOptions::new().depth_limit(2);
+    assert!(shallow.normalize(options).is_ok());
+}
+
+#[test]
+fn test_tags_and_singleton_maps() {
+    let tagged = indoc! {"
+        shapes:
+        - !Circle
+          radius: 1
+        - !Group
+          - !Point
+          - !Square {side: !Length 2}
+        default: !Point
+        labels:
+          color: red
+        !Key key: kept
+        port: !!str 8080
+    "};
+    let singleton = indoc! {"
+        shapes:
+        - Circle:
+            radius: 1
+        - Group:
+          - Point: null
+          - Square: {side: {Length: 2}}
+        default:
+          Point: null
+        labels:
+          color: red
+        !Key key: kept
+        port: !!str 8080
+    "};
+    let preserving = DeserializerBuilder::new().preserve_core_tags(true);
+    let original: Value = preserving.from_str(tagged).unwrap();
+    let expected: Value = preserving.from_str(singleton).unwrap();
+
+    let mut value = original.clone();
+    value.tags_to_singleton_maps();
+    assert_eq!(value, expected);
+
+    let variants = ["Circle", "Group", "Point", "Square", "Length"];
+    value.singleton_maps_to_tags(&|name| variants.contains(&name));
+    assert_eq!(value, original);
+
+    // Mappings that the predicate does not pick out are left alone.
+    let mut value = expected.clone();
+    value.singleton_maps_to_tags(&|name| name == "Point");
+    assert_eq!(value["default"], original["default"]);
+    assert_eq!(value["shapes"][0], expected["shapes"][0]);
+    assert_eq!(value["labels"], expected["labels"]);
+    value.singleton_maps_to_tags(&|_name| true);
+    assert!(matches!(value["labels"], Value::Tagged(_)));
+}